@@ -0,0 +1,113 @@
+use std::path::Path;
+use std::process::Command;
+
+// Expose the target triple to the binary so `self-update` can pick the
+// matching release asset, and the git commit for `version --json`.
+fn main() {
+    println!(
+        "cargo:rustc-env=TASK_WATCHDOG_TARGET={}",
+        std::env::var("TARGET").unwrap()
+    );
+
+    let git = |args: &[&str]| {
+        Command::new("git")
+            .args(args)
+            .output()
+            .ok()
+            .filter(|out| out.status.success())
+            .and_then(|out| String::from_utf8(out.stdout).ok())
+            .map(|s| s.trim().to_string())
+    };
+    let hash = git(&["rev-parse", "--short=12", "HEAD"]).unwrap_or_else(|| "unknown".into());
+    println!("cargo:rustc-env=TASK_WATCHDOG_GIT_HASH={hash}");
+
+    // Rebuild when HEAD moves (new commit or branch switch)
+    if let Some(git_dir) = git(&["rev-parse", "--git-dir"]) {
+        let git_dir = Path::new(&git_dir);
+        println!("cargo:rerun-if-changed={}", git_dir.join("HEAD").display());
+        if let Some(head_ref) = git(&["symbolic-ref", "-q", "HEAD"]) {
+            println!(
+                "cargo:rerun-if-changed={}",
+                git_dir.join(head_ref).display()
+            );
+        }
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+
+    #[cfg(feature = "grpc")]
+    grpc_service();
+}
+
+/// Server and client stubs for `task_watchdog.v1.Watchdog`, as defined in
+/// proto/task_watchdog/v1/watchdog.proto. The messages are written out in
+/// src/grpc.rs, so no protoc is needed; keep all three in step.
+#[cfg(feature = "grpc")]
+fn grpc_service() {
+    use tonic_build::manual::{Builder, Method, Service};
+
+    let method = |name: &str, route: &str, input: &str, output: &str, comment: &str| {
+        Method::builder()
+            .name(name)
+            .route_name(route)
+            .input_type(format!("super::{input}"))
+            .output_type(format!("super::{output}"))
+            .codec_path("tonic::codec::ProstCodec")
+            .comment(comment)
+    };
+    let service = Service::builder()
+        .name("Watchdog")
+        .package("task_watchdog.v1")
+        .method(
+            method(
+                "register_task",
+                "RegisterTask",
+                "RegisterTaskRequest",
+                "Task",
+                "Register a task, or queue it (`queue`) when the machine is full",
+            )
+            .build(),
+        )
+        .method(
+            method(
+                "kill",
+                "Kill",
+                "KillRequest",
+                "Task",
+                "Stop a task's processes or container and mark it killed",
+            )
+            .build(),
+        )
+        .method(
+            method(
+                "check",
+                "Check",
+                "CheckRequest",
+                "CheckResponse",
+                "A task, and whether its process or container is running",
+            )
+            .build(),
+        )
+        .method(
+            method(
+                "stream_events",
+                "StreamEvents",
+                "StreamEventsRequest",
+                "Event",
+                "The daemon's events, from now on",
+            )
+            .server_streaming()
+            .build(),
+        )
+        .method(
+            method(
+                "stats",
+                "Stats",
+                "StatsRequest",
+                "Stats",
+                "Task counts by status",
+            )
+            .build(),
+        )
+        .build();
+    Builder::new().build_transport(false).compile(&[service]);
+}
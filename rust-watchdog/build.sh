@@ -44,11 +44,15 @@ else
     BINARY="./target/release/task-watchdog"
     SIZE=$(du -h "$BINARY" | cut -f1)
 
+    # TypeScript definitions ship with the release for the extension/dashboard
+    "$BINARY" typescript > ./target/release/task-watchdog.d.ts
+
     echo ""
     echo "✅ Release build complete!"
     echo "   Binary: $BINARY"
     echo "   Size: $SIZE"
     echo "   Optimizations: LTO, size optimization, stripped"
+    echo "   TypeScript: ./target/release/task-watchdog.d.ts"
     echo ""
     echo "📊 Performance specs:"
     echo "   Startup time: <5ms"
@@ -0,0 +1,386 @@
+// TypeScript definitions for the JSON task-watchdog reads and writes.
+//
+// Kept in step with the serde types in task-watchdog-core: the tests in
+// core/src/typescript.rs check the JSON they emit against these types.
+// Print with `task-watchdog typescript`; release builds ship it next to the
+// binary as task-watchdog.d.ts.
+
+/** RFC 3339 timestamp, always UTC */
+export type Timestamp = string;
+
+export type ExecutionMode = "native" | "docker";
+
+export type TaskStatus =
+  | "queued"
+  | "starting"
+  | "running"
+  | "completed"
+  | "failed"
+  | "cancelled"
+  | "killed"
+  | "timed_out"
+  | "unknown";
+
+/** Why a task failed without the watchdog seeing it exit */
+export type FailureReason = "system_rebooted" | "dependency_failed";
+
+/** How dependent tasks tell a task is ready (`--ready`) */
+export type ReadinessProbe =
+  | { port: number }
+  | { http: string }
+  | { command: TaskCommand };
+
+/** Core dump a native task's process left when it crashed */
+export interface Coredump {
+  pid: number;
+  signal?: number;
+  path?: string;
+  /** Kept by systemd-coredump (`coredumpctl debug <pid>`) */
+  coredumpctl?: boolean;
+  /** Top frames of its backtrace, innermost first */
+  backtrace?: string[];
+}
+
+/**
+ * Kernel resource limits a native task is started under (`--rlimit`),
+ * sizes in bytes and `cpu` in seconds
+ */
+export interface Rlimits {
+  nofile?: number;
+  nproc?: number;
+  fsize?: number;
+  as?: number;
+  core?: number;
+  cpu?: number;
+  stack?: number;
+}
+
+/** When the daemon starts a finished task again */
+export type RestartPolicy = "no" | "on-failure" | "always";
+
+/**
+ * Command a task runs: an argv array executed directly, or (with `shell`)
+ * a single command line handed to `sh -c`. Registries written before argv
+ * support hold a plain string, which means a shell command.
+ */
+export type TaskCommand = TaskArgv | string;
+
+export interface TaskArgv {
+  argv: string[];
+  shell?: boolean;
+}
+
+export interface NativeTask {
+  pid: number;
+  pgid: number;
+  /** Process start time from /proc, used to detect PID reuse */
+  start_time: string;
+  env_tag?: string;
+  /** Windows job object holding the task's process tree */
+  job?: string;
+}
+
+export interface ResourceLimits {
+  /** Docker-style size, e.g. `512m` */
+  memory: string;
+  /** Number of CPUs, e.g. `1.0` */
+  cpu: string;
+}
+
+export interface DockerTask {
+  container_id: string;
+  container_name: string;
+  resource_limits: ResourceLimits;
+  /** Seconds to wait after SIGTERM before SIGKILL */
+  stop_timeout_secs?: number;
+  /** Image to recreate the container from if it was removed */
+  image?: string;
+  /** Remove the container once it has stopped */
+  auto_remove?: boolean;
+  /** With `auto_remove`, keep the container of a failed run */
+  keep_on_failure?: boolean;
+  /** Exit code of the container's last run, once it has stopped */
+  exit_code?: number;
+}
+
+/** Component that registered or killed a task, and where it ran */
+export interface TaskOrigin {
+  /** `cli`, `daemon`, `api`, `python-sdk`, `mcp`, ... */
+  tool: string;
+  user?: string;
+  host?: string;
+  /** tmux pane it was run from, for `goto` */
+  tmux?: TmuxPane;
+}
+
+/** A tmux pane, by its server-unique ID and its position when recorded */
+export interface TmuxPane {
+  /** `%12` */
+  pane_id: string;
+  session: string;
+  window: number;
+  pane: number;
+  /** Server socket, from `$TMUX` */
+  socket?: string;
+}
+
+/** Outcome a container task reports itself in `.watchdog/result.json` */
+export interface TaskResult {
+  exit_code?: number;
+  summary?: string;
+  /** Files the task produced, relative to `/workspace` */
+  artifacts?: string[];
+}
+
+/** AI API usage reported by a task (cumulative) */
+export interface TaskCost {
+  input_tokens: number;
+  output_tokens: number;
+  /** Spend in US dollars */
+  usd: number;
+}
+
+export interface TaskInfo {
+  mode: ExecutionMode;
+  command: TaskCommand;
+  description?: string;
+  expected_outcome?: string;
+  status: TaskStatus;
+  started_at: Timestamp;
+  completed_at?: Timestamp;
+  kill_reason?: string;
+  failure_reason?: FailureReason;
+  /** Likely cause of a failure, e.g. "command not found: ..." */
+  triage_hint?: string;
+  coredump?: Coredump;
+  /** Traceback, panic or compiler errors at the end of a failed task's output */
+  failure_excerpt?: string;
+  /** What the task reported about its own run (`.watchdog/result.json`) */
+  result?: TaskResult;
+  cwd?: string;
+  repo_root?: string;
+  owner?: string;
+  origin?: TaskOrigin;
+  labels?: Record<string, string>;
+  /** Related tasks it is managed with, e.g. one agent swarm (`--group`) */
+  group?: string;
+  /** Most tasks of its group running at once (`swarm --jobs`) */
+  jobs?: number;
+  cost?: TaskCost;
+  cpu_seconds?: number;
+  reservation?: ResourceLimits;
+  native?: NativeTask;
+  docker?: DockerTask;
+  /** TCP port the task serves, set by `adopt --port` */
+  port?: number;
+  /** File whose appearance marks the task finished (see `--done-file`) */
+  done_file?: string;
+  /** Where the output of a spawned task goes (see `logs`) */
+  log_file?: string;
+  /** Longest the task may run before it is terminated (`--timeout`) */
+  timeout_secs?: number;
+  /** Resident memory (MB) above which it is flagged (`--max-memory`) */
+  max_memory_mb?: number;
+  /** CPU, in percent of one core, above which it is flagged (`--max-cpu`) */
+  max_cpu_percent?: number;
+  /** Processes its group may hold before it is flagged (`--max-processes`) */
+  max_processes?: number;
+  /** Open files it may hold before it is flagged (`--max-open-files`) */
+  max_open_files?: number;
+  /** Threads it may run before it is flagged (`--max-threads`) */
+  max_threads?: number;
+  rlimits?: Rlimits;
+  /** Tasks that must be ready before this one starts (`--after`) */
+  depends_on?: string[];
+  readiness?: ReadinessProbe;
+  /** When the readiness probe first passed */
+  ready_at?: Timestamp;
+  /** Absent means `no` */
+  restart?: RestartPolicy;
+  /** How many times it has been started again */
+  restarts?: number;
+  constitution_rules: string[];
+  /** Opaque JSON set by whoever registered the task */
+  metadata?: unknown;
+}
+
+/** `.claude/process_registry.json` */
+export interface ProcessRegistry {
+  tasks: Record<string, TaskInfo>;
+  /** Kernel boot ID when the registry was last loaded */
+  boot_id?: string;
+  /** Processes watched with `watch-pid`, by name */
+  watched?: Record<string, WatchedProcess>;
+}
+
+/** A process watched for liveness and resources, not run as a task */
+export interface WatchedProcess {
+  pid: number;
+  start_time: string;
+  /** When watching started */
+  since: Timestamp;
+  command?: string;
+}
+
+/** Task counts by status */
+export interface RegistryStats {
+  total: number;
+  running: number;
+  completed: number;
+  failed: number;
+  killed: number;
+  queued: number;
+  starting: number;
+  unknown: number;
+  timed_out: number;
+}
+
+/** A task with its ID (`check --output json`, the API's `/tasks/{id}`) */
+export interface TaskEntry extends TaskInfo {
+  task_id: string;
+  /** Whether its process or container is running, where that was checked */
+  alive?: boolean;
+}
+
+/** `report --output json` and the API's `/report` */
+export interface Report {
+  stats: RegistryStats;
+  cost: TaskCost;
+  /** Unfinished tasks, or those matching `--where` */
+  tasks: Record<string, TaskInfo>;
+  watched?: Record<string, WatchedProcess>;
+}
+
+/** `rehydrate --output json` */
+export interface Rehydration {
+  active: TaskEntry[];
+  stats: RegistryStats;
+}
+
+/** One group in `stats --group-by ... --output json`, keyed by group */
+export interface GroupStats {
+  tasks: number;
+  running: number;
+  failed: number;
+  cost: TaskCost;
+  cpu_seconds: number;
+}
+
+/** What the daemon detected during a check, tagged by `event` */
+export type WatchdogEvent =
+  | { event: "task_registered"; task_id: string }
+  | { event: "task_started"; task_id: string; from_queue: boolean }
+  | { event: "task_completed"; task_id: string }
+  | {
+      event: "task_died";
+      task_id: string;
+      exit_code?: number;
+      triage_hint?: string;
+      failure_excerpt?: string;
+    }
+  | { event: "timed_out"; task_id: string; timeout_secs: number }
+  | { event: "task_ready"; task_id: string }
+  | { event: "task_restarted"; task_id: string; restarts: number }
+  | { event: "dependency_failed"; task_id: string; dependency: string }
+  | { event: "zombie_detected"; task_id: string }
+  | { event: "task_unknown"; task_id: string; reason: string }
+  | {
+      event: "threshold_exceeded";
+      task_id: string;
+      /** `memory`, `cpu`, `processes`, `process_rate`, `open_files` or `threads` */
+      resource: string;
+      usage: string;
+      limit: string;
+    }
+  | {
+      event: "limit_exceeded";
+      task_id: string;
+      /** `memory`, `cpu`, ... */
+      resource: string;
+      limit: string;
+    }
+  | {
+      event: "duration_anomaly";
+      task_id: string;
+      running_secs: number;
+      p95_secs: number;
+    }
+  | { event: "port_closed"; task_id: string; port: number }
+  | { event: "watched_process_exited"; name: string; pid: number }
+  | { event: "check_completed"; stats: RegistryStats };
+
+/** What a transition did to a task */
+export type Change =
+  | "registered"
+  | "started"
+  | "restarted"
+  | "completed"
+  | "failed"
+  | "cancelled"
+  | "killed"
+  | "timed_out"
+  | "lost"
+  | "cleaned_up";
+
+/** A status change in the lifecycle journal (`process_events.jsonl`) */
+export interface Transition {
+  at: Timestamp;
+  task_id: string;
+  change: Change;
+  /** Missing when the task was registered */
+  from?: TaskStatus;
+  /** Missing when the task was cleaned up */
+  to?: TaskStatus;
+  actor: "watchdog" | "cli" | "api";
+  user?: string;
+  /** Kill reason, failure reason or exit code */
+  reason?: string;
+}
+
+/** An event the daemon published that changes no status, in the journal */
+export type EventRecord = WatchdogEvent & { at: Timestamp };
+
+/** One line of the lifecycle journal */
+export type JournalLine = Transition | EventRecord;
+
+/** `replay --output json`: a window of the lifecycle journal */
+export interface Timeline {
+  start?: Timestamp;
+  end?: Timestamp;
+  /** Checks only appear when the counts changed */
+  entries: TimelineEntry[];
+  /** Each task as of the end of the window, by task ID */
+  tasks: Record<string, TimelineTask>;
+  /** Counts of the last check in the window */
+  stats?: RegistryStats;
+}
+
+export type TimelineEntry = JournalLine & {
+  /** Seconds since the window opened */
+  offset_secs: number;
+  description: string;
+  /** The task's status from here on, when the line changed it */
+  status?: TaskStatus;
+};
+
+export interface TimelineTask {
+  /** Missing if the task was only registered */
+  status?: TaskStatus;
+  last_event?: Timestamp;
+  /** Lines about it during the window */
+  events: number;
+}
+
+/** `task-watchdog version --json` */
+export interface BuildInfo {
+  name: string;
+  version: string;
+  /** Short commit hash, or `unknown` outside a git checkout */
+  git_hash: string;
+  /** Target triple the binary was built for */
+  target: string;
+  /** Optional subsystems compiled in */
+  features: string[];
+  /** Registry file format this binary reads and writes */
+  registry_schema_version: number;
+}
@@ -0,0 +1,291 @@
+//! Alerts: the events worth telling someone about, once per condition.
+//!
+//! Some conditions produce the same event on every check for as long as
+//! they last (a task running unusually long, a container Docker can't
+//! report on, a task that crashes every time it is restarted).
+//! [`AlertTracker`] turns the event stream into alerts: one when a
+//! condition first appears, nothing while it persists, and one more for
+//! each escalation threshold it outlasts (still there after 1h, then after
+//! 4h). A condition missing from a whole check has cleared, and alerts
+//! afresh if it comes back.
+
+use crate::events::WatchdogEvent;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// An event to notify about
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Alert {
+    pub event: WatchdogEvent,
+    /// 0 when the condition is new, then the number of escalation
+    /// thresholds it has outlasted
+    pub tier: usize,
+    /// When the condition was first seen
+    pub first_seen: DateTime<Utc>,
+    /// Checks it has been seen in so far
+    pub occurrences: u32,
+}
+
+impl Alert {
+    pub fn is_escalation(&self) -> bool {
+        self.tier > 0
+    }
+}
+
+/// The condition `event` reports, if it is worth an alert: events about
+/// the same condition share a key
+pub fn condition_key(event: &WatchdogEvent) -> Option<String> {
+    use WatchdogEvent::*;
+    let key = match event {
+        TaskDied { task_id, .. } => format!("task_died:{task_id}"),
+        TimedOut { task_id, .. } => format!("timed_out:{task_id}"),
+        DependencyFailed { task_id, .. } => format!("dependency_failed:{task_id}"),
+        ZombieDetected { task_id } => format!("zombie_detected:{task_id}"),
+        TaskUnknown { task_id, .. } => format!("task_unknown:{task_id}"),
+        ThresholdExceeded {
+            task_id, resource, ..
+        } => format!("threshold_exceeded:{task_id}:{resource}"),
+        LimitExceeded {
+            task_id, resource, ..
+        } => format!("limit_exceeded:{task_id}:{resource}"),
+        DurationAnomaly { task_id, .. } => format!("duration_anomaly:{task_id}"),
+        PortClosed { task_id, .. } => format!("port_closed:{task_id}"),
+        WatchedProcessExited { name, .. } => format!("watched_process_exited:{name}"),
+        TaskRegistered { .. }
+        | TaskStarted { .. }
+        | TaskCompleted { .. }
+        | TaskReady { .. }
+        | TaskRestarted { .. }
+        | CheckCompleted { .. } => return None,
+    };
+    Some(key)
+}
+
+/// One line describing what `event` reports, for a notification
+pub fn describe(event: &WatchdogEvent) -> String {
+    use WatchdogEvent::*;
+    match event {
+        TaskRegistered { task_id } => format!("Task {task_id} registered"),
+        TaskStarted { task_id, .. } => format!("Task {task_id} started"),
+        TaskCompleted { task_id } => format!("Task {task_id} completed"),
+        TaskDied {
+            task_id,
+            exit_code,
+            triage_hint,
+            ..
+        } => {
+            let mut line = match exit_code {
+                Some(code) => format!("Task {task_id} failed with exit code {code}"),
+                None => format!("Task {task_id} died"),
+            };
+            if let Some(hint) = triage_hint {
+                line.push_str(&format!(" ({hint})"));
+            }
+            line
+        }
+        TimedOut {
+            task_id,
+            timeout_secs,
+        } => format!("Task {task_id} timed out after {timeout_secs}s"),
+        TaskReady { task_id } => format!("Task {task_id} is ready"),
+        TaskRestarted { task_id, restarts } => {
+            format!("Task {task_id} restarted (restart #{restarts})")
+        }
+        DependencyFailed {
+            task_id,
+            dependency,
+        } => format!("Task {task_id} failed: {dependency} finished without becoming ready"),
+        ZombieDetected { task_id } => {
+            format!("Task {task_id} was still running after it finished and was killed")
+        }
+        TaskUnknown { task_id, reason } => format!("Task {task_id} is unknown: {reason}"),
+        ThresholdExceeded {
+            task_id,
+            resource,
+            usage,
+            limit,
+        } => format!("Task {task_id} is over its {resource} limit: {usage} (limit {limit})"),
+        LimitExceeded {
+            task_id,
+            resource,
+            limit,
+        } => format!("Task {task_id} was killed for exceeding its {resource} limit ({limit})"),
+        DurationAnomaly {
+            task_id,
+            running_secs,
+            p95_secs,
+        } => format!(
+            "Task {task_id} has run {:.0}s, {:.1}x its usual p95",
+            running_secs,
+            running_secs / p95_secs
+        ),
+        PortClosed { task_id, port } => format!("Task {task_id} no longer serves port {port}"),
+        WatchedProcessExited { name, pid } => {
+            format!("Watched process {name} (PID {pid}) exited")
+        }
+        CheckCompleted { stats } => format!(
+            "Check completed: {} running, {} failed",
+            stats.running, stats.failed
+        ),
+    }
+}
+
+#[derive(Debug)]
+struct Condition {
+    first_seen: DateTime<Utc>,
+    tier: usize,
+    occurrences: u32,
+}
+
+/// Deduplicates events into alerts across checks
+#[derive(Debug, Default)]
+pub struct AlertTracker {
+    /// How long a condition must last for each escalation, shortest first
+    escalate_after: Vec<Duration>,
+    open: HashMap<String, Condition>,
+    /// Conditions seen since the last [`AlertTracker::end_check`]
+    seen: HashSet<String>,
+}
+
+impl AlertTracker {
+    pub fn new(mut escalate_after: Vec<Duration>) -> Self {
+        escalate_after.sort();
+        escalate_after.dedup();
+        Self {
+            escalate_after,
+            ..Default::default()
+        }
+    }
+
+    /// The alert `event` calls for at `now`, if any: for a new condition,
+    /// or one that has just outlasted another escalation threshold
+    pub fn observe(&mut self, event: &WatchdogEvent, now: DateTime<Utc>) -> Option<Alert> {
+        let key = condition_key(event)?;
+        let first_check = self.seen.insert(key.clone());
+        let condition = match self.open.get_mut(&key) {
+            Some(condition) => {
+                // Several events in one check count once
+                if first_check {
+                    condition.occurrences += 1;
+                }
+                let tier = self
+                    .escalate_after
+                    .iter()
+                    .filter(|after| now - condition.first_seen >= **after)
+                    .count();
+                if tier <= condition.tier {
+                    return None;
+                }
+                condition.tier = tier;
+                condition
+            }
+            None => self.open.entry(key).or_insert(Condition {
+                first_seen: now,
+                tier: 0,
+                occurrences: 1,
+            }),
+        };
+        Some(Alert {
+            event: event.clone(),
+            tier: condition.tier,
+            first_seen: condition.first_seen,
+            occurrences: condition.occurrences,
+        })
+    }
+
+    /// Close a check: conditions it didn't see have cleared. Returns their
+    /// keys.
+    pub fn end_check(&mut self) -> Vec<String> {
+        let seen = std::mem::take(&mut self.seen);
+        let mut cleared: Vec<String> = self
+            .open
+            .keys()
+            .filter(|key| !seen.contains(*key))
+            .cloned()
+            .collect();
+        cleared.sort();
+        for key in &cleared {
+            self.open.remove(key);
+        }
+        cleared
+    }
+
+    /// Conditions still ongoing
+    pub fn open_conditions(&self) -> usize {
+        self.open.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn anomaly(task_id: &str) -> WatchdogEvent {
+        WatchdogEvent::DurationAnomaly {
+            task_id: task_id.into(),
+            running_secs: 600.0,
+            p95_secs: 60.0,
+        }
+    }
+
+    #[test]
+    fn test_ongoing_condition_alerts_once_then_escalates() {
+        let start = Utc::now();
+        let mut tracker = AlertTracker::new(vec![Duration::hours(4), Duration::hours(1)]);
+
+        let first = tracker.observe(&anomaly("T001"), start).unwrap();
+        assert_eq!((first.tier, first.occurrences), (0, 1));
+        tracker.end_check();
+
+        for minutes in [5, 30, 55] {
+            let now = start + Duration::minutes(minutes);
+            assert_eq!(tracker.observe(&anomaly("T001"), now), None);
+            tracker.end_check();
+        }
+
+        let escalated = tracker
+            .observe(&anomaly("T001"), start + Duration::minutes(65))
+            .unwrap();
+        assert!(escalated.is_escalation());
+        assert_eq!((escalated.tier, escalated.occurrences), (1, 5));
+        assert_eq!(escalated.first_seen, start);
+        tracker.end_check();
+
+        // A long gap crosses both thresholds at once: one alert
+        let late = tracker
+            .observe(&anomaly("T001"), start + Duration::hours(5))
+            .unwrap();
+        assert_eq!(late.tier, 2);
+        assert_eq!(
+            tracker.observe(&anomaly("T001"), start + Duration::hours(9)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_cleared_condition_alerts_again() {
+        let now = Utc::now();
+        let mut tracker = AlertTracker::new(Vec::new());
+        assert!(tracker.observe(&anomaly("T001"), now).is_some());
+        assert!(tracker.observe(&anomaly("T002"), now).is_some());
+        assert_eq!(tracker.end_check(), Vec::<String>::new());
+
+        // T001 recovered for a check; T002 persists
+        assert!(tracker.observe(&anomaly("T002"), now).is_none());
+        assert_eq!(tracker.end_check(), vec!["duration_anomaly:T001"]);
+        assert_eq!(tracker.open_conditions(), 1);
+
+        assert!(tracker.observe(&anomaly("T001"), now).is_some());
+    }
+
+    #[test]
+    fn test_routine_events_never_alert() {
+        let mut tracker = AlertTracker::new(Vec::new());
+        let event = WatchdogEvent::TaskReady {
+            task_id: "T001".into(),
+        };
+        assert_eq!(tracker.observe(&event, Utc::now()), None);
+        assert_eq!(tracker.open_conditions(), 0);
+    }
+}
@@ -0,0 +1,135 @@
+//! Duration anomalies: running tasks that take far longer than usual.
+//!
+//! Completed tasks form the history. For every command that has completed
+//! at least [`MIN_SAMPLES`] times, a running task of the same command is
+//! anomalous once it has run for more than [`FACTOR`] times the p95 of
+//! those durations. No timeout needs to be configured for this to fire.
+
+use crate::clock;
+use crate::types::{ProcessRegistry, TaskInfo, TaskStatus};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+
+/// Completed runs needed before a command's durations are trusted
+pub const MIN_SAMPLES: usize = 5;
+
+/// Multiple of the historical p95 that counts as anomalous
+pub const FACTOR: f64 = 3.0;
+
+/// A running task that has outlived its command's usual duration
+#[derive(Debug, Clone, PartialEq)]
+pub struct DurationAnomaly {
+    pub task_id: String,
+    pub running_secs: f64,
+    pub p95_secs: f64,
+}
+
+impl DurationAnomaly {
+    /// How many times the p95 the task has been running
+    pub fn ratio(&self) -> f64 {
+        self.running_secs / self.p95_secs
+    }
+}
+
+/// Tasks are compared against history of the same command line
+fn history_key(task: &TaskInfo) -> String {
+    task.command.to_string()
+}
+
+/// p95 duration (seconds) per command, for commands with enough history
+pub fn p95_by_command(registry: &ProcessRegistry) -> HashMap<String, f64> {
+    let mut durations: HashMap<String, Vec<f64>> = HashMap::new();
+    for task in registry.tasks.values() {
+        if task.status != TaskStatus::Completed {
+            continue;
+        }
+        if let Some(done) = task.completed_at {
+            let secs = (done - task.started_at).num_milliseconds() as f64 / 1000.0;
+            if secs >= 0.0 {
+                durations.entry(history_key(task)).or_default().push(secs);
+            }
+        }
+    }
+
+    durations
+        .into_iter()
+        .filter(|(_, d)| d.len() >= MIN_SAMPLES)
+        .map(|(key, mut d)| {
+            d.sort_by(f64::total_cmp);
+            // Nearest-rank percentile
+            let rank = ((d.len() as f64 * 0.95).ceil() as usize).clamp(1, d.len());
+            (key, d[rank - 1])
+        })
+        .collect()
+}
+
+/// Running tasks that have exceeded [`FACTOR`] × their command's p95
+pub fn find_anomalies(registry: &ProcessRegistry, now: DateTime<Utc>) -> Vec<DurationAnomaly> {
+    let p95 = p95_by_command(registry);
+    let mut anomalies: Vec<DurationAnomaly> = registry
+        .tasks
+        .iter()
+        .filter(|(_, task)| task.status == TaskStatus::Running)
+        .filter_map(|(task_id, task)| {
+            let p95_secs = *p95.get(&history_key(task))?;
+            let running_secs = clock::secs_between(task.started_at, now);
+            (p95_secs > 0.0 && running_secs > p95_secs * FACTOR).then(|| DurationAnomaly {
+                task_id: task_id.clone(),
+                running_secs,
+                p95_secs,
+            })
+        })
+        .collect();
+    anomalies.sort_by(|a, b| b.ratio().total_cmp(&a.ratio()));
+    anomalies
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::TaskCommand;
+    use chrono::Duration;
+
+    fn task(command: &str, status: TaskStatus, started: DateTime<Utc>, secs: i64) -> TaskInfo {
+        TaskInfo {
+            started_at: started,
+            completed_at: (status == TaskStatus::Completed)
+                .then(|| started + Duration::seconds(secs)),
+            status,
+            ..TaskInfo::new(TaskCommand::shell(command))
+        }
+    }
+
+    #[test]
+    fn test_flags_tasks_far_beyond_their_p95() {
+        let now = Utc::now();
+        let mut registry = ProcessRegistry::new();
+        for (i, secs) in [50, 55, 60, 58, 62].into_iter().enumerate() {
+            let started = now - Duration::hours(1 + i as i64);
+            registry.add_task(
+                format!("H{i}"),
+                task("pytest", TaskStatus::Completed, started, secs),
+            );
+        }
+        assert_eq!(p95_by_command(&registry)["pytest"], 62.0);
+
+        let stuck = now - Duration::seconds(200);
+        registry.add_task(
+            "STUCK".into(),
+            task("pytest", TaskStatus::Running, stuck, 0),
+        );
+        let fine = now - Duration::seconds(120);
+        registry.add_task("FINE".into(), task("pytest", TaskStatus::Running, fine, 0));
+        // No history for this command, so never flagged
+        let new = now - Duration::days(1);
+        registry.add_task(
+            "NEW".into(),
+            task("cargo build", TaskStatus::Running, new, 0),
+        );
+
+        let anomalies = find_anomalies(&registry, now);
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].task_id, "STUCK");
+        assert!(anomalies[0].ratio() > 3.0);
+    }
+}
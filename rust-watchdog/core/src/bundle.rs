@@ -0,0 +1,326 @@
+//! Context bundle: everything known about one task in a single document,
+//! sized to fit an LLM's context window (`context-bundle`).
+//!
+//! The bundle holds the task itself, how long it ran compared with earlier
+//! runs of the same command, its resource use, its rules and the other
+//! tasks it was launched with. When the rendered bundle is over the token
+//! budget the oldest related tasks are dropped first, then the oldest
+//! earlier runs.
+
+use crate::anomaly;
+use crate::clock::secs_between;
+use crate::types::{ProcessRegistry, TaskInfo, TaskStatus};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// Rough size of a token for budgeting; no tokenizer is shipped
+pub const CHARS_PER_TOKEN: usize = 4;
+
+/// Budget used when none is given
+pub const DEFAULT_MAX_TOKENS: usize = 4000;
+
+/// Most earlier runs and related tasks included before trimming
+const MAX_LISTED: usize = 20;
+
+/// The task a bundle is about, plus everything gathered around it
+#[derive(Debug, Clone, Serialize)]
+pub struct ContextBundle {
+    pub task_id: String,
+    pub task: TaskInfo,
+    /// Seconds from start to finish, or to now while unfinished
+    pub duration_secs: f64,
+    /// p95 duration of earlier completed runs of the same command
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usual_p95_secs: Option<f64>,
+    pub resources: Resources,
+    /// Why the command policy would refuse this command, if it would
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub policy_violation: Option<String>,
+    /// Other runs of the same command, newest first
+    pub previous_runs: Vec<RelatedTask>,
+    /// Tasks sharing a label with this one, newest first
+    pub related: Vec<RelatedTask>,
+    /// Entries dropped to stay within the token budget
+    pub omitted: usize,
+}
+
+/// What is known about the task's resource use
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Resources {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_seconds: Option<f64>,
+    /// Current usage, while the process is alive
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_percent: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_kb: Option<u64>,
+}
+
+/// One line of summary about another task
+#[derive(Debug, Clone, Serialize)]
+pub struct RelatedTask {
+    pub task_id: String,
+    pub status: TaskStatus,
+    pub command: String,
+    pub started_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_secs: Option<f64>,
+    /// Kill or failure reason
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl RelatedTask {
+    fn new(task_id: &str, task: &TaskInfo) -> Self {
+        Self {
+            task_id: task_id.to_string(),
+            status: task.status.clone(),
+            command: task.command.to_string(),
+            started_at: task.started_at,
+            duration_secs: task
+                .completed_at
+                .map(|done| secs_between(task.started_at, done)),
+            reason: task
+                .kill_reason
+                .clone()
+                .or_else(|| task.failure_reason.as_ref().map(|r| r.to_string())),
+        }
+    }
+}
+
+impl ContextBundle {
+    /// Gather the bundle for `task_id`, or `None` if it isn't registered
+    pub fn build(registry: &ProcessRegistry, task_id: &str, now: DateTime<Utc>) -> Option<Self> {
+        let task = registry.get_task(task_id)?;
+        let command = task.command.to_string();
+
+        let mut previous_runs = Vec::new();
+        let mut related = Vec::new();
+        for (id, other) in &registry.tasks {
+            if id == task_id {
+                continue;
+            }
+            if other.command.to_string() == command {
+                previous_runs.push(RelatedTask::new(id, other));
+            } else if other
+                .labels
+                .iter()
+                .any(|(k, v)| task.labels.get(k) == Some(v))
+            {
+                related.push(RelatedTask::new(id, other));
+            }
+        }
+        for list in [&mut previous_runs, &mut related] {
+            list.sort_by(|a, b| {
+                b.started_at
+                    .cmp(&a.started_at)
+                    .then(a.task_id.cmp(&b.task_id))
+            });
+        }
+        let omitted = previous_runs.len().saturating_sub(MAX_LISTED)
+            + related.len().saturating_sub(MAX_LISTED);
+        previous_runs.truncate(MAX_LISTED);
+        related.truncate(MAX_LISTED);
+
+        Some(Self {
+            task_id: task_id.to_string(),
+            task: task.clone(),
+            duration_secs: secs_between(task.started_at, task.completed_at.unwrap_or(now)),
+            usual_p95_secs: anomaly::p95_by_command(registry).get(&command).copied(),
+            resources: Resources {
+                cpu_seconds: task.cpu_seconds,
+                ..Default::default()
+            },
+            policy_violation: None,
+            previous_runs,
+            related,
+            omitted,
+        })
+    }
+
+    /// Estimated tokens of `text`
+    pub fn estimate_tokens(text: &str) -> usize {
+        text.len().div_ceil(CHARS_PER_TOKEN)
+    }
+
+    /// Drop related tasks, then earlier runs, oldest first, until
+    /// `render(self)` fits in `max_tokens`. The task itself is always kept.
+    pub fn fit<F>(&mut self, max_tokens: usize, render: F) -> String
+    where
+        F: Fn(&Self) -> String,
+    {
+        loop {
+            let text = render(self);
+            if Self::estimate_tokens(&text) <= max_tokens
+                || (self.related.pop().is_none() && self.previous_runs.pop().is_none())
+            {
+                return text;
+            }
+            self.omitted += 1;
+        }
+    }
+
+    /// Render as Markdown
+    pub fn to_markdown(&self) -> String {
+        let task = &self.task;
+        let mut out = format!("# Task {}\n\n", self.task_id);
+        let mut field = |name: &str, value: &dyn std::fmt::Display| {
+            out += &format!("- **{name}:** {value}\n");
+        };
+        field("Status", &task.status);
+        field("Command", &format_args!("`{}`", task.command));
+        if let Some(description) = &task.description {
+            field("Goal", description);
+        }
+        if let Some(expected) = &task.expected_outcome {
+            field("Expected outcome", expected);
+        }
+        field("Mode", &format_args!("{:?}", task.mode));
+        field("Started", &task.started_at.to_rfc3339());
+        if let Some(done) = task.completed_at {
+            field("Finished", &done.to_rfc3339());
+        }
+        match self.usual_p95_secs {
+            Some(p95) => field(
+                "Duration",
+                &format_args!("{:.0}s (usual p95 {:.0}s)", self.duration_secs, p95),
+            ),
+            None => field("Duration", &format_args!("{:.0}s", self.duration_secs)),
+        }
+        if let Some(reason) = &task.kill_reason {
+            field("Kill reason", reason);
+        }
+        if let Some(reason) = &task.failure_reason {
+            field("Failure reason", reason);
+        }
+        if let Some(hint) = &task.triage_hint {
+            field("Likely cause", hint);
+        }
+        if let Some(result) = &task.result {
+            if let Some(summary) = &result.summary {
+                field("Result", summary);
+            }
+            if !result.artifacts.is_empty() {
+                field("Artifacts", &result.artifacts.join(", "));
+            }
+        }
+        if let Some(cwd) = &task.cwd {
+            field("Directory", &cwd.display());
+        }
+        if let Some(origin) = &task.origin {
+            field("Origin", origin);
+        }
+        if !task.labels.is_empty() {
+            let labels: Vec<String> = task
+                .labels
+                .iter()
+                .map(|(k, v)| format!("{k}={v}"))
+                .collect();
+            field("Labels", &labels.join(", "));
+        }
+        if let Some(seconds) = self.resources.cpu_seconds {
+            field("CPU time", &format_args!("{seconds:.1}s"));
+        }
+        if let (Some(cpu), Some(mem)) = (self.resources.cpu_percent, self.resources.memory_kb) {
+            field("Now using", &format_args!("{cpu:.1}% CPU, {mem}KB"));
+        }
+        if let Some(cost) = &task.cost {
+            field("Spend", &format_args!("${:.2}", cost.usd));
+        }
+        if !task.constitution_rules.is_empty() {
+            field("Rules", &task.constitution_rules.join(", "));
+        }
+        if let Some(violation) = &self.policy_violation {
+            field("Policy violation", violation);
+        }
+        if !task.metadata.is_null() {
+            field("Metadata", &format_args!("`{}`", task.metadata));
+        }
+
+        for (title, list) in [
+            ("Earlier runs of this command", &self.previous_runs),
+            ("Related tasks", &self.related),
+        ] {
+            if list.is_empty() {
+                continue;
+            }
+            out += &format!("\n## {title}\n\n");
+            for other in list {
+                out += &format!(
+                    "- {} [{}] {}",
+                    other.task_id,
+                    other.status,
+                    other.started_at.format("%Y-%m-%d %H:%M")
+                );
+                if let Some(secs) = other.duration_secs {
+                    out += &format!(", {secs:.0}s");
+                }
+                if title == "Related tasks" {
+                    out += &format!(", `{}`", other.command);
+                }
+                if let Some(reason) = &other.reason {
+                    out += &format!(" ({reason})");
+                }
+                out.push('\n');
+            }
+        }
+        if self.omitted > 0 {
+            out += &format!("\n_{} more tasks omitted._\n", self.omitted);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::TaskCommand;
+    use chrono::Duration;
+
+    fn task(command: &str, label: &str, hours_ago: i64, status: TaskStatus) -> TaskInfo {
+        let started = Utc::now() - Duration::hours(hours_ago);
+        TaskInfo {
+            completed_at: status.is_terminal().then(|| started + Duration::minutes(5)),
+            status,
+            started_at: started,
+            labels: [("swarm".to_string(), label.to_string())].into(),
+            ..TaskInfo::new(TaskCommand::shell(command))
+        }
+    }
+
+    #[test]
+    fn test_bundle_gathers_and_fits_budget() {
+        let mut registry = ProcessRegistry::new();
+        registry.add_task("T1".into(), task("pytest", "a", 0, TaskStatus::Failed));
+        for i in 1..=8 {
+            registry.add_task(
+                format!("RUN{i}"),
+                task("pytest", "z", i, TaskStatus::Completed),
+            );
+            registry.add_task(
+                format!("SIB{i}"),
+                task("ruff", "a", i, TaskStatus::Completed),
+            );
+        }
+        registry.add_task("OTHER".into(), task("ruff", "b", 1, TaskStatus::Completed));
+
+        let mut bundle = ContextBundle::build(&registry, "T1", Utc::now()).unwrap();
+        assert_eq!(bundle.previous_runs.len(), 8);
+        assert_eq!(bundle.previous_runs[0].task_id, "RUN1");
+        assert_eq!(bundle.related.len(), 8);
+        assert!(bundle.related.iter().all(|r| r.task_id.starts_with("SIB")));
+        assert_eq!(bundle.usual_p95_secs, Some(300.0));
+
+        let full = ContextBundle::estimate_tokens(&bundle.to_markdown());
+        let text = bundle.fit(full - 20, ContextBundle::to_markdown);
+        assert!(ContextBundle::estimate_tokens(&text) <= full - 20);
+        assert!(bundle.omitted > 0 && bundle.related.len() < 8);
+        assert_eq!(bundle.previous_runs.len(), 8);
+        assert!(text.contains("more tasks omitted"));
+
+        // The task itself survives any budget
+        let text = bundle.fit(1, ContextBundle::to_markdown);
+        assert!(text.starts_with("# Task T1"));
+        assert!(bundle.previous_runs.is_empty() && bundle.related.is_empty());
+    }
+}
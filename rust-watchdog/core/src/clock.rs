@@ -0,0 +1,94 @@
+//! Clock skew: registry timestamps this machine's clock can't have written.
+//!
+//! A VM restored from a snapshot, or a container with the wrong clock or
+//! time zone, reads a registry whose tasks started in the future, or
+//! decades ago. [`find_skew`] spots them so the CLI can say why its numbers
+//! look off, and [`secs_between`] keeps durations computed against such a
+//! clock from going negative.
+
+use crate::types::ProcessRegistry;
+use chrono::{DateTime, Duration, Utc};
+
+/// How far ahead of the clock a timestamp may be before it counts as skew
+/// (small clock adjustments, other hosts sharing the registry)
+pub const FUTURE_TOLERANCE_SECS: i64 = 300;
+
+/// Age past which a timestamp can't have come from a working clock
+pub const MAX_AGE_DAYS: i64 = 3650;
+
+/// Seconds from `from` to `to`, or 0 if `to` comes first
+pub fn secs_between(from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+    ((to - from).num_milliseconds() as f64 / 1000.0).max(0.0)
+}
+
+/// Tasks with timestamps out of line with the clock
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClockSkew {
+    /// Tasks with a timestamp in the future
+    pub ahead: Vec<String>,
+    /// How far ahead the furthest of them is
+    pub max_ahead_secs: i64,
+    /// Tasks with a timestamp older than [`MAX_AGE_DAYS`]
+    pub ancient: Vec<String>,
+}
+
+/// Timestamps in `registry` (when tasks started, finished and became
+/// ready) that are in the future or absurdly old as of `now`
+pub fn find_skew(registry: &ProcessRegistry, now: DateTime<Utc>) -> Option<ClockSkew> {
+    let latest = now + Duration::seconds(FUTURE_TOLERANCE_SECS);
+    let earliest = now - Duration::days(MAX_AGE_DAYS);
+    let mut skew = ClockSkew::default();
+    for (task_id, task) in &registry.tasks {
+        let times = [Some(task.started_at), task.completed_at, task.ready_at];
+        let times = times.into_iter().flatten();
+        if let Some(max) = times.clone().filter(|at| *at > latest).max() {
+            skew.ahead.push(task_id.clone());
+            skew.max_ahead_secs = skew.max_ahead_secs.max((max - now).num_seconds());
+        }
+        if times.into_iter().any(|at| at < earliest) {
+            skew.ancient.push(task_id.clone());
+        }
+    }
+    skew.ahead.sort();
+    skew.ancient.sort();
+    (!skew.ahead.is_empty() || !skew.ancient.is_empty()).then_some(skew)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{TaskCommand, TaskInfo};
+
+    fn task(started_at: DateTime<Utc>) -> TaskInfo {
+        TaskInfo {
+            started_at,
+            ..TaskInfo::new(TaskCommand::shell("true"))
+        }
+    }
+
+    #[test]
+    fn test_find_skew() {
+        let now = Utc::now();
+        let mut registry = ProcessRegistry::new();
+        registry.add_task("OK".into(), task(now - Duration::minutes(5)));
+        registry.add_task("NEAR".into(), task(now + Duration::seconds(30)));
+        assert_eq!(find_skew(&registry, now), None);
+
+        registry.add_task("AHEAD".into(), task(now + Duration::hours(2)));
+        let mut done = task(now - Duration::hours(1));
+        done.completed_at = Some(now + Duration::hours(3));
+        registry.add_task("DONE".into(), done);
+        registry.add_task("EPOCH".into(), task(DateTime::UNIX_EPOCH));
+        let skew = find_skew(&registry, now).unwrap();
+        assert_eq!(skew.ahead, ["AHEAD", "DONE"]);
+        assert_eq!(skew.max_ahead_secs, 3 * 3600);
+        assert_eq!(skew.ancient, ["EPOCH"]);
+    }
+
+    #[test]
+    fn test_secs_between_never_negative() {
+        let now = Utc::now();
+        assert_eq!(secs_between(now, now + Duration::milliseconds(1500)), 1.5);
+        assert_eq!(secs_between(now + Duration::hours(1), now), 0.0);
+    }
+}
@@ -0,0 +1,169 @@
+//! What the daemon detected during a check, as data.
+//!
+//! The daemon prints these for humans and publishes them to subscribers
+//! (`task_watchdog::daemon::Watchdog::subscribe`). Serialized with an
+//! `event` tag, e.g. `{"event":"task_died","task_id":"T001","exit_code":1}`.
+
+use crate::report::RegistryStats;
+use serde::{Deserialize, Serialize};
+
+/// Something the watchdog noticed about a task or finished doing
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum WatchdogEvent {
+    /// A task appeared in the registry since the previous check
+    TaskRegistered { task_id: String },
+    /// A task is confirmed running: its container started, or it was
+    /// promoted from the queue
+    TaskStarted { task_id: String, from_queue: bool },
+    /// The task reported success: its done file or container exit code
+    TaskCompleted { task_id: String },
+    /// The task's process or container went away before it completed
+    TaskDied {
+        task_id: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        exit_code: Option<i64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        triage_hint: Option<String>,
+        /// The end of its output that shows what went wrong
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        failure_excerpt: Option<String>,
+    },
+    /// The task ran past its `--timeout` and was terminated
+    TimedOut { task_id: String, timeout_secs: u64 },
+    /// The task's readiness probe passed; tasks that depend on it may start
+    TaskReady { task_id: String },
+    /// A finished task was started again under its restart policy
+    TaskRestarted { task_id: String, restarts: u32 },
+    /// A queued task was failed because a task it depends on finished
+    /// without becoming ready
+    DependencyFailed { task_id: String, dependency: String },
+    /// The task finished but its process was still running, and was killed
+    ZombieDetected { task_id: String },
+    /// The task's liveness can't be determined
+    TaskUnknown { task_id: String, reason: String },
+    /// The task is using more than its `--max-memory`, `--max-cpu` or
+    /// process limits allow (reported on every check it does, until it is
+    /// back under)
+    ThresholdExceeded {
+        task_id: String,
+        /// `memory`, `cpu`, `processes`, `process_rate`, `open_files` or
+        /// `threads`
+        resource: String,
+        /// What it uses, e.g. `812MB`
+        usage: String,
+        limit: String,
+    },
+    /// The task was killed for exceeding a resource limit
+    LimitExceeded {
+        task_id: String,
+        /// `memory`, `cpu`, ...
+        resource: String,
+        limit: String,
+    },
+    /// The task has been running far longer than its command usually takes
+    DurationAnomaly {
+        task_id: String,
+        running_secs: f64,
+        p95_secs: f64,
+    },
+    /// Nothing listens on the port an adopted task serves anymore
+    PortClosed { task_id: String, port: u16 },
+    /// A process watched with `watch-pid` exited (it is no longer watched)
+    WatchedProcessExited { name: String, pid: i32 },
+    /// A check finished; counts are after its changes
+    CheckCompleted { stats: RegistryStats },
+}
+
+impl WatchdogEvent {
+    /// The task this event is about (`None` for registry-wide events)
+    pub fn task_id(&self) -> Option<&str> {
+        match self {
+            WatchdogEvent::TaskRegistered { task_id }
+            | WatchdogEvent::TaskStarted { task_id, .. }
+            | WatchdogEvent::TaskCompleted { task_id }
+            | WatchdogEvent::TaskDied { task_id, .. }
+            | WatchdogEvent::TimedOut { task_id, .. }
+            | WatchdogEvent::TaskReady { task_id }
+            | WatchdogEvent::TaskRestarted { task_id, .. }
+            | WatchdogEvent::DependencyFailed { task_id, .. }
+            | WatchdogEvent::ZombieDetected { task_id }
+            | WatchdogEvent::TaskUnknown { task_id, .. }
+            | WatchdogEvent::ThresholdExceeded { task_id, .. }
+            | WatchdogEvent::LimitExceeded { task_id, .. }
+            | WatchdogEvent::DurationAnomaly { task_id, .. }
+            | WatchdogEvent::PortClosed { task_id, .. } => Some(task_id),
+            WatchdogEvent::WatchedProcessExited { .. } | WatchdogEvent::CheckCompleted { .. } => {
+                None
+            }
+        }
+    }
+
+    /// The `event` tag it is serialized with
+    pub fn name(&self) -> &'static str {
+        match self {
+            WatchdogEvent::TaskRegistered { .. } => "task_registered",
+            WatchdogEvent::TaskStarted { .. } => "task_started",
+            WatchdogEvent::TaskCompleted { .. } => "task_completed",
+            WatchdogEvent::TaskDied { .. } => "task_died",
+            WatchdogEvent::TimedOut { .. } => "timed_out",
+            WatchdogEvent::TaskReady { .. } => "task_ready",
+            WatchdogEvent::TaskRestarted { .. } => "task_restarted",
+            WatchdogEvent::DependencyFailed { .. } => "dependency_failed",
+            WatchdogEvent::ZombieDetected { .. } => "zombie_detected",
+            WatchdogEvent::TaskUnknown { .. } => "task_unknown",
+            WatchdogEvent::ThresholdExceeded { .. } => "threshold_exceeded",
+            WatchdogEvent::LimitExceeded { .. } => "limit_exceeded",
+            WatchdogEvent::DurationAnomaly { .. } => "duration_anomaly",
+            WatchdogEvent::PortClosed { .. } => "port_closed",
+            WatchdogEvent::WatchedProcessExited { .. } => "watched_process_exited",
+            WatchdogEvent::CheckCompleted { .. } => "check_completed",
+        }
+    }
+}
+
+/// Every event's `event` tag
+pub const EVENT_NAMES: &[&str] = &[
+    "task_registered",
+    "task_started",
+    "task_completed",
+    "task_died",
+    "timed_out",
+    "task_ready",
+    "task_restarted",
+    "dependency_failed",
+    "zombie_detected",
+    "task_unknown",
+    "threshold_exceeded",
+    "limit_exceeded",
+    "duration_anomaly",
+    "port_closed",
+    "watched_process_exited",
+    "check_completed",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name_is_the_serialized_tag() {
+        let events = [
+            WatchdogEvent::ZombieDetected {
+                task_id: "T001".into(),
+            },
+            WatchdogEvent::WatchedProcessExited {
+                name: "server".into(),
+                pid: 42,
+            },
+            WatchdogEvent::CheckCompleted {
+                stats: RegistryStats::default(),
+            },
+        ];
+        for event in events {
+            let json = serde_json::to_value(&event).unwrap();
+            assert_eq!(json["event"], event.name());
+            assert!(EVENT_NAMES.contains(&event.name()));
+        }
+    }
+}
@@ -0,0 +1,289 @@
+//! Failure excerpts: the block of a failed task's output that shows what
+//! went wrong, attached as `failure_excerpt`.
+//!
+//! A log can run to megabytes, of which a notification or `check` wants a
+//! dozen lines: the last Python traceback, Rust panic, pytest summary, or
+//! rustc or tsc errors in the output. The block is cut down to
+//! [`MAX_LINES`] lines of at most [`MAX_LINE_CHARS`] characters each.
+
+/// Longest excerpt, in lines
+pub const MAX_LINES: usize = 40;
+
+/// Longer lines are cut short
+pub const MAX_LINE_CHARS: usize = 240;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Block {
+    PythonTraceback,
+    RustPanic,
+    PytestSummary,
+    RustErrors,
+    TypeScriptErrors,
+}
+
+impl Block {
+    /// Whether the end of a block too long to keep whole matters most: the
+    /// exception ends a traceback, while the first compiler error is the
+    /// one to fix
+    fn keeps_end(self) -> bool {
+        matches!(self, Block::PythonTraceback | Block::RustPanic)
+    }
+}
+
+/// The last block of `output` that explains a failure, if there is one
+pub fn extract(output: &str) -> Option<String> {
+    let lines: Vec<String> = output.lines().map(strip_ansi).collect();
+    let mut last = None;
+    let mut i = 0;
+    while i < lines.len() {
+        match block_at(&lines, i) {
+            Some((block, end)) => {
+                last = Some((block, i, end));
+                i = end + 1;
+            }
+            None => i += 1,
+        }
+    }
+    let (block, start, end) = last?;
+    Some(shorten(block, &lines[start..=end]))
+}
+
+/// The block starting at line `i`, and the line it ends on
+fn block_at(lines: &[String], i: usize) -> Option<(Block, usize)> {
+    let line = lines[i].trim_end();
+    let rest = || lines.iter().enumerate().skip(i + 1);
+    if line.trim_start() == "Traceback (most recent call last):" {
+        // Indented frames, then the exception
+        let end = rest()
+            .find(|(_, l)| !l.starts_with(char::is_whitespace))
+            .map_or(lines.len() - 1, |(j, _)| j);
+        return Some((Block::PythonTraceback, end));
+    }
+    if line.starts_with("thread '") && line.contains("' panicked at") {
+        // The message (and backtrace, if enabled), up to the RUST_BACKTRACE note
+        let end = rest()
+            .find(|(_, l)| l.trim().is_empty() || l.starts_with("note: "))
+            .map_or(lines.len() - 1, |(j, _)| j - 1);
+        return Some((Block::RustPanic, end));
+    }
+    if line.starts_with('=') && line.contains(" short test summary info ") {
+        let end = rest()
+            .find(|(_, l)| is_pytest_result(l))
+            .map_or(lines.len() - 1, |(j, _)| j);
+        return Some((Block::PytestSummary, end));
+    }
+    if is_pytest_result(line) && (line.contains(" failed") || line.contains(" error")) {
+        // Run with the summary turned off
+        return Some((Block::PytestSummary, i));
+    }
+    if line.starts_with("error[E") {
+        // Every error up to cargo giving up, or just this one
+        let end = rest()
+            .find(|(_, l)| l.starts_with("error: could not compile"))
+            .or_else(|| {
+                rest()
+                    .find(|(_, l)| l.trim().is_empty())
+                    .map(|(j, l)| (j - 1, l))
+            })
+            .map_or(lines.len() - 1, |(j, _)| j);
+        return Some((Block::RustErrors, end));
+    }
+    if is_tsc_error(line) {
+        // Up to "Found 3 errors", or the errors and their indented details
+        let end = rest()
+            .find(|(_, l)| l.starts_with("Found ") && l.contains(" error"))
+            .map(|(j, _)| j)
+            .or_else(|| {
+                rest()
+                    .take_while(|(_, l)| is_tsc_error(l) || l.starts_with(char::is_whitespace))
+                    .last()
+                    .map(|(j, _)| j)
+            })
+            .unwrap_or(i);
+        return Some((Block::TypeScriptErrors, end));
+    }
+    None
+}
+
+/// pytest's last line: `=== 2 failed, 10 passed in 1.23s ===`
+fn is_pytest_result(line: &str) -> bool {
+    let line = line.trim();
+    line.len() > 2 && line.starts_with('=') && line.ends_with('=') && line.contains(" in ")
+}
+
+/// `src/a.ts(3,7): error TS2322: ...`, or `src/a.ts:3:7 - error TS2322: ...`
+/// with `--pretty`
+fn is_tsc_error(line: &str) -> bool {
+    line.contains(": error TS") || line.contains(" - error TS")
+}
+
+/// `block` (its lines) cut down to size
+fn shorten(block: Block, lines: &[String]) -> String {
+    let mut lines: Vec<String> = lines
+        .iter()
+        .map(|line| match line.char_indices().nth(MAX_LINE_CHARS) {
+            Some((at, _)) => format!("{}…", &line[..at]),
+            None => line.clone(),
+        })
+        .collect();
+    while lines.last().is_some_and(|line| line.trim().is_empty()) {
+        lines.pop();
+    }
+    if lines.len() > MAX_LINES {
+        let dropped = lines.len() - (MAX_LINES - 1);
+        if block.keeps_end() {
+            // Keep the first line, which says what the block is
+            lines.splice(1..=dropped, [format!("… ({dropped} lines)")]);
+        } else {
+            lines.truncate(MAX_LINES - 1);
+            lines.push(format!("… ({dropped} more lines)"));
+        }
+    }
+    lines.join("\n")
+}
+
+/// `line` without terminal color codes
+fn strip_ansi(line: &str) -> String {
+    let mut stripped = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c != '\u{1b}' {
+            stripped.push(c);
+            continue;
+        }
+        // CSI sequences end with a byte in @..~
+        if chars.next() == Some('[') {
+            for c in chars.by_ref() {
+                if ('@'..='~').contains(&c) {
+                    break;
+                }
+            }
+        }
+    }
+    stripped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_python_traceback() {
+        let output = "\
+Loading data
+Traceback (most recent call last):
+  File \"ingest.py\", line 3, in <module>
+    main()
+  File \"ingest.py\", line 2, in main
+    int('x')
+ValueError: invalid literal for int() with base 10: 'x'
+cleanup done
+";
+        assert_eq!(
+            extract(output).unwrap(),
+            output
+                .lines()
+                .skip(1)
+                .take(6)
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_last_block_wins() {
+        let output = "\
+thread 'main' panicked at src/main.rs:2:5:
+first
+note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace
+thread 'worker' panicked at src/lib.rs:9:13:
+\u{1b}[31mcalled `Option::unwrap()` on a `None` value\u{1b}[0m
+note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace
+";
+        assert_eq!(
+            extract(output).unwrap(),
+            "thread 'worker' panicked at src/lib.rs:9:13:\n\
+             called `Option::unwrap()` on a `None` value"
+        );
+        assert_eq!(extract("all good\n"), None);
+    }
+
+    #[test]
+    fn test_pytest_summary() {
+        let output = "\
+tests/test_api.py .F.                                                  [100%]
+=================================== FAILURES ===================================
+____________________________________ test_x ____________________________________
+    def test_x():
+>       assert 1 == 2
+E       AssertionError
+=========================== short test summary info ============================
+FAILED tests/test_api.py::test_x - AssertionError
+========================= 1 failed, 2 passed in 0.12s ==========================
+";
+        let excerpt = extract(output).unwrap();
+        assert!(excerpt.starts_with("=========================== short test summary"));
+        assert!(excerpt.ends_with("1 failed, 2 passed in 0.12s =========================="));
+        assert_eq!(excerpt.lines().count(), 3);
+
+        let bare = "collected 3 items\n===== 1 failed, 2 passed in 0.12s =====\n";
+        assert_eq!(
+            extract(bare).unwrap(),
+            "===== 1 failed, 2 passed in 0.12s ====="
+        );
+        assert_eq!(extract("===== 3 passed in 0.12s =====\n"), None);
+    }
+
+    #[test]
+    fn test_compiler_errors() {
+        let rustc = "\
+   Compiling app v0.1.0
+error[E0308]: mismatched types
+ --> src/main.rs:2:18
+  |
+2 |     let x: i32 = \"a\";
+  |            ---   ^^^ expected `i32`, found `&str`
+
+For more information about this error, try `rustc --explain E0308`.
+error: could not compile `app` (bin \"app\") due to 1 previous error
+";
+        let excerpt = extract(rustc).unwrap();
+        assert!(excerpt.starts_with("error[E0308]: mismatched types"));
+        assert!(excerpt.ends_with("due to 1 previous error"));
+
+        let tsc = "\
+> tsc --noEmit
+src/a.ts(3,7): error TS2322: Type 'string' is not assignable to type 'number'.
+src/b.ts(9,1): error TS2304: Cannot find name 'foo'.
+npm ERR! code 2
+";
+        assert_eq!(
+            extract(tsc).unwrap(),
+            tsc.lines().skip(1).take(2).collect::<Vec<_>>().join("\n")
+        );
+    }
+
+    #[test]
+    fn test_long_blocks_are_shortened() {
+        let frames: String = (0..100)
+            .map(|i| format!("  File \"f.py\", line {i}, in f\n"))
+            .collect();
+        let output = format!(
+            "Traceback (most recent call last):\n{frames}RecursionError: {}\n",
+            "x".repeat(500)
+        );
+        let excerpt = extract(&output).unwrap();
+        let lines: Vec<&str> = excerpt.lines().collect();
+        assert_eq!(lines.len(), MAX_LINES);
+        assert_eq!(lines[1], "… (63 lines)");
+        assert!(lines[MAX_LINES - 1].starts_with("RecursionError"));
+        assert_eq!(lines[MAX_LINES - 1].chars().count(), MAX_LINE_CHARS + 1);
+
+        let errors: String = (0..60)
+            .map(|i| format!("src/a.ts({i},1): error TS2304: Cannot find name 'x'.\n"))
+            .collect();
+        let excerpt = extract(&errors).unwrap();
+        assert!(excerpt.starts_with("src/a.ts(0,1)"));
+        assert!(excerpt.ends_with("… (21 more lines)"));
+    }
+}
@@ -0,0 +1,551 @@
+//! Filter expressions for selecting tasks (`--where`).
+//!
+//! ```text
+//! status=running and label.team=infra and started<2h ago
+//! (status=failed or status=killed) and not command~pytest
+//! cost>=1.5 or duration>30m
+//! ```
+//!
+//! A comparison is `field op value`. Operators are `=`, `!=`, `~`
+//! (contains), `<`, `<=`, `>` and `>=`; `and` binds tighter than `or`,
+//! `not` negates, parentheses group. Values with spaces or operator
+//! characters are quoted (`description~"flaky test"`).
+//!
+//! Text fields: `id`, `status`, `mode`, `command`, `owner`, `description`,
+//! `origin` (the registering tool), `group` and `label.<key>`. Times:
+//! `started` and `completed`, compared against `<duration> ago` or a
+//! date/RFC 3339 timestamp, so `started<2h ago` means started more than two
+//! hours ago.
+//! Numbers: `cost` (USD), `cpu` (CPU seconds) and `duration` (seconds, or
+//! `30m`-style; running tasks count up to now). A comparison on a field the
+//! task doesn't have is false, except `!=`.
+
+use crate::clock;
+use crate::types::TaskInfo;
+use crate::units::parse_duration;
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, Duration, Local, NaiveDate, TimeZone, Utc};
+use std::fmt;
+use std::str::FromStr;
+
+/// A parsed `--where` expression
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter {
+    expr: Expr,
+    source: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare(Field, Op, Value),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Field {
+    Id,
+    Status,
+    Mode,
+    Command,
+    Owner,
+    Description,
+    Origin,
+    Group,
+    Label(String),
+    Started,
+    Completed,
+    Duration,
+    Cost,
+    Cpu,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Contains,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Text(String),
+    Number(f64),
+    /// Seconds before the time of matching
+    Ago(f64),
+    At(DateTime<Utc>),
+}
+
+impl Filter {
+    /// Whether the task matches. `now` anchors `ago` and running durations.
+    pub fn matches(&self, task_id: &str, task: &TaskInfo, now: DateTime<Utc>) -> bool {
+        self.expr.matches(task_id, task, now)
+    }
+
+    /// Tasks in `group` (`--group`)
+    pub fn group(group: &str) -> Self {
+        Filter {
+            expr: Expr::Compare(Field::Group, Op::Eq, Value::Text(group.to_string())),
+            source: format!("group={group:?}"),
+        }
+    }
+
+    /// `filter` narrowed to tasks in `group`, when either is given
+    pub fn in_group(filter: Option<Filter>, group: Option<&str>) -> Option<Filter> {
+        match (filter, group.map(Filter::group)) {
+            (Some(filter), Some(group)) => Some(Filter {
+                source: format!("({}) and {}", filter.source, group.source),
+                expr: Expr::And(Box::new(filter.expr), Box::new(group.expr)),
+            }),
+            (filter, group) => filter.or(group),
+        }
+    }
+}
+
+impl FromStr for Filter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let tokens = tokenize(s)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.or_expr()?;
+        if let Some(token) = parser.peek() {
+            bail!("Unexpected '{}' in filter '{}'", token, s);
+        }
+        Ok(Filter {
+            expr,
+            source: s.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl Expr {
+    fn matches(&self, task_id: &str, task: &TaskInfo, now: DateTime<Utc>) -> bool {
+        match self {
+            Expr::And(a, b) => a.matches(task_id, task, now) && b.matches(task_id, task, now),
+            Expr::Or(a, b) => a.matches(task_id, task, now) || b.matches(task_id, task, now),
+            Expr::Not(e) => !e.matches(task_id, task, now),
+            Expr::Compare(field, op, value) => compare(field, *op, value, task_id, task, now),
+        }
+    }
+}
+
+fn compare(
+    field: &Field,
+    op: Op,
+    value: &Value,
+    task_id: &str,
+    task: &TaskInfo,
+    now: DateTime<Utc>,
+) -> bool {
+    let text = |actual: Option<String>| {
+        let Value::Text(expected) = value else {
+            return false;
+        };
+        match (actual, op) {
+            (Some(actual), Op::Eq) => actual.eq_ignore_ascii_case(expected),
+            (Some(actual), Op::Ne) => !actual.eq_ignore_ascii_case(expected),
+            (Some(actual), Op::Contains) => {
+                actual.to_lowercase().contains(&expected.to_lowercase())
+            }
+            (None, Op::Ne) => true,
+            _ => false,
+        }
+    };
+    let number = |actual: Option<f64>| {
+        let Value::Number(expected) = value else {
+            return false;
+        };
+        match actual {
+            Some(actual) => ordered(op, actual.total_cmp(expected)),
+            None => op == Op::Ne,
+        }
+    };
+    let time = |actual: Option<DateTime<Utc>>| {
+        let expected = match value {
+            Value::Ago(secs) => now - Duration::milliseconds((secs * 1000.0) as i64),
+            Value::At(at) => *at,
+            _ => return false,
+        };
+        match actual {
+            Some(actual) => ordered(op, actual.cmp(&expected)),
+            None => op == Op::Ne,
+        }
+    };
+
+    match field {
+        Field::Id => text(Some(task_id.to_string())),
+        Field::Status => text(Some(task.status.to_string())),
+        Field::Mode => text(Some(format!("{:?}", task.mode))),
+        Field::Command => text(Some(task.command.to_string())),
+        Field::Owner => text(task.owner.clone()),
+        Field::Description => text(task.description.clone()),
+        Field::Origin => text(task.origin.as_ref().map(|o| o.tool.clone())),
+        Field::Group => text(task.group.clone()),
+        Field::Label(key) => text(task.labels.get(key).cloned()),
+        Field::Started => time(Some(task.started_at)),
+        Field::Completed => time(task.completed_at),
+        Field::Duration => {
+            let end = task.completed_at.unwrap_or(now);
+            number(Some(clock::secs_between(task.started_at, end)))
+        }
+        Field::Cost => number(task.cost.map(|c| c.usd)),
+        Field::Cpu => number(task.cpu_seconds),
+    }
+}
+
+/// Apply an ordering operator to a comparison result
+fn ordered(op: Op, ordering: std::cmp::Ordering) -> bool {
+    use std::cmp::Ordering::*;
+    match op {
+        Op::Eq => ordering == Equal,
+        Op::Ne => ordering != Equal,
+        Op::Lt => ordering == Less,
+        Op::Le => ordering != Greater,
+        Op::Gt => ordering == Greater,
+        Op::Ge => ordering != Less,
+        Op::Contains => false,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Op(Op),
+    Word(String),
+    Quoted(String),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Open => write!(f, "("),
+            Token::Close => write!(f, ")"),
+            Token::Op(op) => write!(f, "{}", op.symbol()),
+            Token::Word(w) => write!(f, "{w}"),
+            Token::Quoted(q) => write!(f, "\"{q}\""),
+        }
+    }
+}
+
+impl Op {
+    fn symbol(self) -> &'static str {
+        match self {
+            Op::Eq => "=",
+            Op::Ne => "!=",
+            Op::Contains => "~",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+        }
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' => {
+                chars.next();
+                tokens.push(if c == '(' { Token::Open } else { Token::Close });
+            }
+            '=' | '~' => {
+                chars.next();
+                tokens.push(Token::Op(if c == '=' { Op::Eq } else { Op::Contains }));
+            }
+            '!' | '<' | '>' => {
+                chars.next();
+                let eq = chars.next_if_eq(&'=').is_some();
+                tokens.push(Token::Op(match (c, eq) {
+                    ('!', true) => Op::Ne,
+                    ('<', false) => Op::Lt,
+                    ('<', true) => Op::Le,
+                    ('>', false) => Op::Gt,
+                    ('>', true) => Op::Ge,
+                    _ => bail!("Expected '!=' in filter '{}'", s),
+                }));
+            }
+            '"' | '\'' => {
+                chars.next();
+                let mut quoted = String::new();
+                loop {
+                    match chars.next() {
+                        Some(q) if q == c => break,
+                        Some(ch) => quoted.push(ch),
+                        None => bail!("Unterminated quote in filter '{}'", s),
+                    }
+                }
+                tokens.push(Token::Quoted(quoted));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(ch) =
+                    chars.next_if(|ch| !ch.is_whitespace() && !"()=~!<>\"'".contains(*ch))
+                {
+                    word.push(ch);
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    if tokens.is_empty() {
+        bail!("Empty filter");
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Word(w)) if w.eq_ignore_ascii_case(keyword));
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn or_expr(&mut self) -> Result<Expr> {
+        let mut expr = self.and_expr()?;
+        while self.keyword("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and_expr()?));
+        }
+        Ok(expr)
+    }
+
+    fn and_expr(&mut self) -> Result<Expr> {
+        let mut expr = self.unary()?;
+        while self.keyword("and") {
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        if self.keyword("not") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.peek() == Some(&Token::Open) {
+            self.pos += 1;
+            let expr = self.or_expr()?;
+            if self.next() != Some(Token::Close) {
+                bail!("Missing ')' in filter");
+            }
+            return Ok(expr);
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr> {
+        let name = match self.next() {
+            Some(Token::Word(name)) => name,
+            Some(token) => bail!("Expected a field name, found '{}'", token),
+            None => bail!("Filter ends where a field name was expected"),
+        };
+        let field = parse_field(&name)?;
+        let op = match self.next() {
+            Some(Token::Op(op)) => op,
+            _ => bail!("Expected an operator after '{}'", name),
+        };
+        let raw = match self.next() {
+            Some(Token::Word(w) | Token::Quoted(w)) => w,
+            _ => bail!("Expected a value after '{}{}'", name, op.symbol()),
+        };
+
+        let value = match field {
+            Field::Started | Field::Completed => {
+                if op == Op::Contains {
+                    bail!("'~' only applies to text fields, not '{}'", name);
+                }
+                if self.keyword("ago") {
+                    Value::Ago(parse_duration(&raw).ok_or_else(|| {
+                        anyhow!("Invalid duration '{}' (use e.g. 90s, 30m, 2h, 7d)", raw)
+                    })?)
+                } else {
+                    Value::At(parse_time(&raw)?)
+                }
+            }
+            Field::Duration | Field::Cost | Field::Cpu => {
+                if op == Op::Contains {
+                    bail!("'~' only applies to text fields, not '{}'", name);
+                }
+                let number = if field == Field::Duration {
+                    parse_duration(&raw)
+                } else {
+                    raw.parse().ok()
+                };
+                Value::Number(
+                    number.ok_or_else(|| anyhow!("Invalid number '{}' for '{}'", raw, name))?,
+                )
+            }
+            _ => {
+                if !matches!(op, Op::Eq | Op::Ne | Op::Contains) {
+                    bail!("'{}' is text: use =, != or ~", name);
+                }
+                Value::Text(raw)
+            }
+        };
+        Ok(Expr::Compare(field, op, value))
+    }
+}
+
+fn parse_field(name: &str) -> Result<Field> {
+    if let Some(key) = name.strip_prefix("label.") {
+        return Ok(Field::Label(key.to_string()));
+    }
+    Ok(match name {
+        "id" => Field::Id,
+        "status" => Field::Status,
+        "mode" => Field::Mode,
+        "command" => Field::Command,
+        "owner" => Field::Owner,
+        "description" => Field::Description,
+        "origin" => Field::Origin,
+        "group" => Field::Group,
+        "started" => Field::Started,
+        "completed" => Field::Completed,
+        "duration" => Field::Duration,
+        "cost" => Field::Cost,
+        "cpu" => Field::Cpu,
+        other => bail!(
+            "Unknown filter field '{}' (id, status, mode, command, owner, description, \
+             origin, group, label.<key>, started, completed, duration, cost, cpu)",
+            other
+        ),
+    })
+}
+
+/// RFC 3339 timestamp, or a date meaning local midnight
+fn parse_time(s: &str) -> Result<DateTime<Utc>> {
+    if let Ok(at) = DateTime::parse_from_rfc3339(s) {
+        return Ok(at.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .and_then(|midnight| Local.from_local_datetime(&midnight).earliest())
+        .map(|at| at.with_timezone(&Utc))
+        .ok_or_else(|| anyhow!("Invalid time '{}' (use e.g. '2h ago' or 2026-01-31)", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{TaskCommand, TaskCost, TaskStatus};
+    use std::collections::BTreeMap;
+
+    fn task(status: TaskStatus, team: &str, hours_ago: i64) -> TaskInfo {
+        TaskInfo {
+            status,
+            started_at: Utc::now() - Duration::hours(hours_ago),
+            owner: Some("alice".to_string()),
+            labels: BTreeMap::from([("team".to_string(), team.to_string())]),
+            group: Some(format!("{team}-swarm")),
+            cost: Some(TaskCost {
+                usd: 2.0,
+                ..Default::default()
+            }),
+            ..TaskInfo::new(TaskCommand::shell("pytest -k 'not slow'"))
+        }
+    }
+
+    fn matches(filter: &str, task: &TaskInfo) -> bool {
+        let filter: Filter = filter.parse().unwrap();
+        filter.matches("T001", task, Utc::now())
+    }
+
+    #[test]
+    fn test_filter_expressions() {
+        let old_infra = task(TaskStatus::Running, "infra", 3);
+        let new_web = task(TaskStatus::Failed, "web", 1);
+
+        let f = "status=running and label.team=infra and started<2h ago";
+        assert!(matches(f, &old_infra));
+        assert!(!matches(f, &new_web));
+
+        // `and` binds tighter than `or`
+        let f = "status=failed or status=running and label.team=web";
+        assert!(!matches(f, &old_infra));
+        assert!(matches(f, &new_web));
+        assert!(matches(
+            "(status=failed or status=running) and not label.team=web",
+            &old_infra
+        ));
+
+        assert!(matches("command~'not slow' and owner=ALICE", &old_infra));
+        assert!(matches("cost>=2 and duration>90m", &old_infra));
+        assert!(!matches("cpu>0", &old_infra));
+        assert!(matches(
+            "label.missing!=x and completed!=2026-01-01",
+            &old_infra
+        ));
+    }
+
+    #[test]
+    fn test_filter_in_group() {
+        let infra = task(TaskStatus::Running, "infra", 3);
+        let web = task(TaskStatus::Failed, "web", 1);
+        let now = Utc::now();
+
+        assert!(matches("group=INFRA-swarm", &infra));
+        assert!(Filter::in_group(None, None).is_none());
+        let filter = Filter::in_group(None, Some("web-swarm")).unwrap();
+        assert!(filter.matches("T001", &web, now));
+        assert!(!filter.matches("T001", &infra, now));
+
+        let where_: Filter = "status=running or status=failed".parse().unwrap();
+        let filter = Filter::in_group(Some(where_), Some("infra-swarm")).unwrap();
+        assert_eq!(
+            filter.to_string(),
+            "(status=running or status=failed) and group=\"infra-swarm\""
+        );
+        assert!(filter.matches("T001", &infra, now));
+        assert!(!filter.matches("T001", &web, now));
+    }
+
+    #[test]
+    fn test_filter_errors_are_specific() {
+        for (filter, error) in [
+            ("", "Empty filter"),
+            ("colour=red", "Unknown filter field 'colour'"),
+            ("status>running", "'status' is text"),
+            ("started<2 fortnights ago", "Invalid time"),
+            ("started<2y ago", "Invalid duration '2y'"),
+            ("(status=running", "Missing ')'"),
+            ("status=running label.team=x", "Unexpected 'label.team'"),
+        ] {
+            let err = filter.parse::<Filter>().unwrap_err().to_string();
+            assert!(err.contains(error), "{filter}: {err}");
+        }
+    }
+}
@@ -0,0 +1,205 @@
+//! Project health: one score for orchestrators deciding whether to slow down.
+//!
+//! The score starts at 100 and loses points for the recent failure rate,
+//! crash loops (daemon crashes, or one command failing over and over) and
+//! open resource alerts. It is deliberately coarse: a single number an
+//! agent can threshold on, with the reasons alongside for humans.
+
+use crate::anomaly;
+use crate::types::{ProcessRegistry, TaskStatus};
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashMap;
+
+/// Window for the failure rate
+const FAILURE_WINDOW_HOURS: i64 = 24;
+
+/// Window for crash loops
+const CRASH_LOOP_WINDOW_HOURS: i64 = 1;
+
+/// Failures of one command within the crash-loop window that make a loop
+const CRASH_LOOP_FAILURES: usize = 3;
+
+/// Overall state derived from the score
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthStatus {
+    Healthy,
+    Degraded,
+    Unhealthy,
+}
+
+impl std::fmt::Display for HealthStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HealthStatus::Healthy => write!(f, "healthy"),
+            HealthStatus::Degraded => write!(f, "degraded"),
+            HealthStatus::Unhealthy => write!(f, "unhealthy"),
+        }
+    }
+}
+
+/// Conditions outside the registry that count against health
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Signals {
+    /// Daemon crash reports within the crash-loop window
+    pub recent_daemon_crashes: usize,
+    /// CPU is thermally throttling
+    pub throttling: bool,
+}
+
+/// Health score (0-100) with the reasons it isn't 100
+#[derive(Debug, Clone, PartialEq)]
+pub struct Health {
+    pub score: u8,
+    pub reasons: Vec<String>,
+}
+
+impl Health {
+    /// Score the registry at `now`
+    pub fn assess(registry: &ProcessRegistry, signals: Signals, now: DateTime<Utc>) -> Self {
+        let mut score = 100.0;
+        let mut reasons = Vec::new();
+
+        // Recent failure rate: up to -50
+        let since = now - Duration::hours(FAILURE_WINDOW_HOURS);
+        let finished: Vec<_> = registry
+            .tasks
+            .values()
+            .filter(|t| t.status.is_terminal() && t.completed_at.is_some_and(|at| at >= since))
+            .collect();
+        let failed = finished
+            .iter()
+            .filter(|t| {
+                matches!(
+                    t.status,
+                    TaskStatus::Failed | TaskStatus::Killed | TaskStatus::TimedOut
+                )
+            })
+            .count();
+        if failed > 0 {
+            let rate = failed as f64 / finished.len() as f64;
+            score -= 50.0 * rate;
+            reasons.push(format!(
+                "{failed}/{} tasks failed or were killed in the last {FAILURE_WINDOW_HOURS}h",
+                finished.len()
+            ));
+        }
+
+        // Crash loops: up to -30
+        let mut crash_penalty = 0.0;
+        if signals.recent_daemon_crashes > 0 {
+            crash_penalty += 15.0 * signals.recent_daemon_crashes as f64;
+            reasons.push(format!(
+                "daemon crashed {} time(s) in the last {CRASH_LOOP_WINDOW_HOURS}h",
+                signals.recent_daemon_crashes
+            ));
+        }
+        let loop_since = now - Duration::hours(CRASH_LOOP_WINDOW_HOURS);
+        let mut failures_by_command: HashMap<String, usize> = HashMap::new();
+        for task in registry.tasks.values() {
+            if task.status == TaskStatus::Failed
+                && task.completed_at.is_some_and(|at| at >= loop_since)
+            {
+                *failures_by_command
+                    .entry(task.command.to_string())
+                    .or_default() += 1;
+            }
+        }
+        let mut looping: Vec<_> = failures_by_command
+            .into_iter()
+            .filter(|(_, n)| *n >= CRASH_LOOP_FAILURES)
+            .collect();
+        looping.sort();
+        for (command, n) in looping {
+            crash_penalty += 15.0;
+            reasons.push(format!(
+                "`{command}` failed {n} times in the last {CRASH_LOOP_WINDOW_HOURS}h"
+            ));
+        }
+        score -= f64::min(crash_penalty, 30.0);
+
+        // Open resource alerts: up to -20
+        let mut alert_penalty = 0.0;
+        let anomalies = anomaly::find_anomalies(registry, now).len();
+        if anomalies > 0 {
+            alert_penalty += 5.0 * anomalies as f64;
+            reasons.push(format!("{anomalies} task(s) running unusually long"));
+        }
+        let unknown = registry
+            .tasks
+            .values()
+            .filter(|t| t.status == TaskStatus::Unknown)
+            .count();
+        if unknown > 0 {
+            alert_penalty += 5.0 * unknown as f64;
+            reasons.push(format!("{unknown} task(s) in unknown state"));
+        }
+        if signals.throttling {
+            alert_penalty += 10.0;
+            reasons.push("CPU is thermally throttling".to_string());
+        }
+        score -= f64::min(alert_penalty, 20.0);
+
+        Self {
+            score: score.clamp(0.0, 100.0).round() as u8,
+            reasons,
+        }
+    }
+
+    pub fn status(&self) -> HealthStatus {
+        match self.score {
+            80.. => HealthStatus::Healthy,
+            50..=79 => HealthStatus::Degraded,
+            _ => HealthStatus::Unhealthy,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{TaskCommand, TaskInfo};
+
+    fn finished(command: &str, status: TaskStatus, minutes_ago: i64) -> TaskInfo {
+        let done = Utc::now() - Duration::minutes(minutes_ago);
+        TaskInfo {
+            status,
+            started_at: done - Duration::minutes(1),
+            completed_at: Some(done),
+            ..TaskInfo::new(TaskCommand::shell(command))
+        }
+    }
+
+    #[test]
+    fn test_empty_registry_is_healthy() {
+        let health = Health::assess(&ProcessRegistry::new(), Signals::default(), Utc::now());
+        assert_eq!(health.score, 100);
+        assert_eq!(health.status(), HealthStatus::Healthy);
+        assert!(health.reasons.is_empty());
+    }
+
+    #[test]
+    fn test_failures_and_crash_loops_lower_the_score() {
+        let mut registry = ProcessRegistry::new();
+        registry.add_task("A".into(), finished("make", TaskStatus::Completed, 90));
+        for i in 0..3 {
+            registry.add_task(
+                format!("F{i}"),
+                finished("pytest", TaskStatus::Failed, 10 + i),
+            );
+        }
+
+        // 3/4 failed: -37.5; pytest crash loop: -15
+        let health = Health::assess(&registry, Signals::default(), Utc::now());
+        assert_eq!(health.score, 48);
+        assert_eq!(health.status(), HealthStatus::Unhealthy);
+        assert_eq!(health.reasons.len(), 2);
+
+        let signals = Signals {
+            recent_daemon_crashes: 2,
+            throttling: true,
+        };
+        // Crash penalty capped at 30, throttling -10
+        let health = Health::assess(&registry, signals, Utc::now());
+        assert_eq!(health.score, 23);
+    }
+}
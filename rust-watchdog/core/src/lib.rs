@@ -0,0 +1,22 @@
+//! Pure data model and report logic shared by the `task-watchdog` CLI and
+//! anything else that reads its registry (dashboards, other tools).
+//!
+//! Nothing here touches processes, containers or the filesystem, so the
+//! crate compiles to `wasm32-unknown-unknown`: parse `process_registry.json`
+//! with serde and call the same functions the CLI uses.
+
+pub mod alerts;
+pub mod anomaly;
+pub mod bundle;
+pub mod clock;
+pub mod events;
+pub mod excerpt;
+pub mod filter;
+pub mod health;
+pub mod report;
+pub mod state;
+pub mod timeline;
+pub mod triage;
+pub mod types;
+pub mod typescript;
+pub mod units;
@@ -0,0 +1,286 @@
+//! Registry-wide counts, spend and CPU time (`stats`, `stats --group-by`),
+//! and the selection `list` prints.
+
+use crate::filter::Filter;
+use crate::types::{ProcessRegistry, TaskCost, TaskEntry, TaskInfo, TaskStatus, WatchedProcess};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// How `list` orders tasks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TaskOrder {
+    /// Most recently started first
+    #[default]
+    Started,
+    /// Most CPU time first
+    Cpu,
+}
+
+impl std::str::FromStr for TaskOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "started" => Ok(TaskOrder::Started),
+            "cpu" => Ok(TaskOrder::Cpu),
+            other => Err(format!("Unknown sort order: {other} (started or cpu)")),
+        }
+    }
+}
+
+/// Which tasks `list` shows
+#[derive(Debug, Clone, Default)]
+pub struct TaskQuery {
+    /// Any of these statuses (any status when empty)
+    pub statuses: Vec<TaskStatus>,
+    /// Started at most this long ago
+    pub since: Option<Duration>,
+    /// A `--where` expression they also match
+    pub filter: Option<Filter>,
+    pub order: TaskOrder,
+    pub limit: Option<usize>,
+}
+
+/// Registry statistics
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RegistryStats {
+    pub total: usize,
+    pub running: usize,
+    pub completed: usize,
+    pub failed: usize,
+    #[serde(default)]
+    pub killed: usize,
+    #[serde(default)]
+    pub queued: usize,
+    #[serde(default)]
+    pub starting: usize,
+    #[serde(default)]
+    pub unknown: usize,
+    #[serde(default)]
+    pub timed_out: usize,
+}
+
+impl RegistryStats {
+    /// Count one more task with `status`
+    pub fn add(&mut self, status: &TaskStatus) {
+        self.total += 1;
+        if let Some(counter) = self.counter(status) {
+            *counter += 1;
+        }
+    }
+
+    /// Count one task with `status` less
+    pub fn remove(&mut self, status: &TaskStatus) {
+        self.total = self.total.saturating_sub(1);
+        if let Some(counter) = self.counter(status) {
+            *counter = counter.saturating_sub(1);
+        }
+    }
+
+    /// The count tasks with `status` go to (cancelled ones only count
+    /// towards the total)
+    fn counter(&mut self, status: &TaskStatus) -> Option<&mut usize> {
+        match status {
+            TaskStatus::Running => Some(&mut self.running),
+            TaskStatus::Completed => Some(&mut self.completed),
+            TaskStatus::Failed => Some(&mut self.failed),
+            TaskStatus::Killed => Some(&mut self.killed),
+            TaskStatus::TimedOut => Some(&mut self.timed_out),
+            TaskStatus::Queued => Some(&mut self.queued),
+            TaskStatus::Starting => Some(&mut self.starting),
+            TaskStatus::Unknown => Some(&mut self.unknown),
+            TaskStatus::Cancelled => None,
+        }
+    }
+}
+
+/// Counts and spend for one group of tasks in `stats --group-by`
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct GroupStats {
+    pub tasks: usize,
+    pub running: usize,
+    pub failed: usize,
+    pub cost: TaskCost,
+    pub cpu_seconds: f64,
+}
+
+/// `report --output json` and the API's `/report`
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub stats: RegistryStats,
+    pub cost: TaskCost,
+    /// The tasks reported on: unfinished ones, or those matching `--where`
+    pub tasks: BTreeMap<String, TaskInfo>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub watched: BTreeMap<String, WatchedProcess>,
+}
+
+/// `rehydrate --output json`
+#[derive(Debug, Clone, Serialize)]
+pub struct Rehydration {
+    pub active: Vec<TaskEntry>,
+    pub stats: RegistryStats,
+}
+
+impl ProcessRegistry {
+    /// Count tasks by status, in one pass over the registry
+    pub fn stats(&self) -> RegistryStats {
+        let mut stats = RegistryStats::default();
+        for task in self.tasks.values() {
+            stats.add(&task.status);
+        }
+        stats
+    }
+
+    /// Per-group counts, spend and CPU time. Tasks are grouped by the value
+    /// of `label` (tasks without it fall into `"-"`) and/or by the local
+    /// date they finished (or started, while still running).
+    pub fn stats_grouped(
+        &self,
+        label: Option<&str>,
+        per_day: bool,
+    ) -> BTreeMap<String, GroupStats> {
+        let mut groups: BTreeMap<String, GroupStats> = BTreeMap::new();
+        for task in self.tasks.values() {
+            let mut key = Vec::new();
+            if let Some(label) = label {
+                key.push(
+                    task.labels
+                        .get(label)
+                        .cloned()
+                        .unwrap_or_else(|| "-".to_string()),
+                );
+            }
+            if per_day {
+                let at = task.completed_at.unwrap_or(task.started_at);
+                key.push(
+                    at.with_timezone(&chrono::Local)
+                        .format("%Y-%m-%d")
+                        .to_string(),
+                );
+            }
+            let stats = groups.entry(key.join(" ")).or_default();
+            stats.tasks += 1;
+            match task.status {
+                TaskStatus::Running => stats.running += 1,
+                TaskStatus::Failed => stats.failed += 1,
+                _ => {}
+            }
+            if let Some(cost) = &task.cost {
+                stats.cost.add(cost);
+            }
+            stats.cpu_seconds += task.cpu_seconds.unwrap_or(0.0);
+        }
+        groups
+    }
+
+    /// Tasks matching `query` at `now`, in its order and up to its limit.
+    /// Ties keep task ID order.
+    pub fn select(&self, query: &TaskQuery, now: DateTime<Utc>) -> Vec<(&String, &TaskInfo)> {
+        let mut tasks: Vec<_> = self
+            .tasks
+            .iter()
+            .filter(|(_, task)| query.statuses.is_empty() || query.statuses.contains(&task.status))
+            .filter(|(_, task)| {
+                query
+                    .since
+                    .is_none_or(|since| task.started_at >= now - since)
+            })
+            .filter(|(id, task)| {
+                query
+                    .filter
+                    .as_ref()
+                    .is_none_or(|f| f.matches(id, task, now))
+            })
+            .collect();
+        match query.order {
+            TaskOrder::Started => tasks.sort_by_key(|(_, task)| std::cmp::Reverse(task.started_at)),
+            TaskOrder::Cpu => tasks.sort_by(|a, b| {
+                let cpu = |task: &TaskInfo| task.cpu_seconds.unwrap_or(0.0);
+                cpu(b.1).total_cmp(&cpu(a.1))
+            }),
+        }
+        if let Some(limit) = query.limit {
+            tasks.truncate(limit);
+        }
+        tasks
+    }
+
+    /// Total reported spend across all tasks
+    pub fn total_cost(&self) -> TaskCost {
+        let mut total = TaskCost::default();
+        for cost in self.tasks.values().filter_map(|t| t.cost.as_ref()) {
+            total.add(cost);
+        }
+        total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> ProcessRegistry {
+        let now = Utc::now();
+        let mut registry = ProcessRegistry::new();
+        for (id, status, minutes_ago, cpu) in [
+            ("A", "completed", 300, Some(40.0)),
+            ("B", "failed", 90, Some(5.0)),
+            ("C", "running", 10, None),
+            ("D", "failed", 30, Some(120.0)),
+        ] {
+            let task: TaskInfo = serde_json::from_value(serde_json::json!({
+                "mode": "native",
+                "command": "true",
+                "status": status,
+                "started_at": now - Duration::minutes(minutes_ago),
+                "cpu_seconds": cpu,
+            }))
+            .unwrap();
+            registry.tasks.insert(id.to_string(), task);
+        }
+        registry
+    }
+
+    fn ids(query: &TaskQuery) -> Vec<String> {
+        let registry = registry();
+        let tasks = registry.select(query, Utc::now());
+        tasks.into_iter().map(|(id, _)| id.clone()).collect()
+    }
+
+    #[test]
+    fn test_select_orders_newest_first_by_default() {
+        assert_eq!(ids(&TaskQuery::default()), ["C", "D", "B", "A"]);
+        let cpu = TaskQuery {
+            order: TaskOrder::Cpu,
+            limit: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(ids(&cpu), ["D", "A"]);
+    }
+
+    #[test]
+    fn test_select_filters_status_and_age() {
+        let failed = TaskQuery {
+            statuses: vec![TaskStatus::Failed],
+            ..Default::default()
+        };
+        assert_eq!(ids(&failed), ["D", "B"]);
+        let recent = TaskQuery {
+            since: Some(Duration::hours(2)),
+            ..failed
+        };
+        assert_eq!(ids(&recent), ["D", "B"]);
+        let recent = TaskQuery {
+            since: Some(Duration::hours(1)),
+            ..recent
+        };
+        assert_eq!(ids(&recent), ["D"]);
+        let cpu_heavy = TaskQuery {
+            filter: Some("cpu>=10".parse().unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(ids(&cpu_heavy), ["D", "A"]);
+    }
+}
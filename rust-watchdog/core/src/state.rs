@@ -0,0 +1,206 @@
+//! Task lifecycle state machine.
+//!
+//! ```text
+//! Queued ──► Starting ──► Running ──► Completed / Failed / Cancelled / Killed / TimedOut
+//!    │           │          ▲  │
+//!    │           └─────► Unknown ◄┘
+//!    └──► (any terminal state)
+//! ```
+//!
+//! `Starting` is a container `up` has created that the daemon hasn't seen
+//! running yet.
+//!
+//! `Unknown` is entered when liveness cannot be checked (Docker unreachable,
+//! metadata missing) and left as soon as it can.
+//!
+//! Terminal states are final: a finished task is never silently revived by
+//! a re-registration, and one terminal outcome never overwrites another.
+//! The one way back is [`TaskInfo::restart`], which the daemon takes for
+//! tasks whose restart policy asks for it.
+
+use crate::types::{TaskInfo, TaskStatus};
+use chrono::Utc;
+
+/// A status change the state machine does not allow
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidTransition {
+    pub from: TaskStatus,
+    pub to: TaskStatus,
+}
+
+impl std::fmt::Display for InvalidTransition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid task transition {} → {}", self.from, self.to)
+    }
+}
+
+impl std::error::Error for InvalidTransition {}
+
+impl TaskStatus {
+    /// Whether the task has finished (no further transitions allowed)
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            TaskStatus::Completed
+                | TaskStatus::Failed
+                | TaskStatus::Cancelled
+                | TaskStatus::Killed
+                | TaskStatus::TimedOut
+        )
+    }
+
+    /// Whether `self → next` is an allowed transition
+    pub fn can_transition_to(&self, next: &TaskStatus) -> bool {
+        use TaskStatus::*;
+        match self {
+            Queued => next.is_terminal() || matches!(next, Starting | Running),
+            Starting | Running | Unknown => next.is_terminal() || matches!(next, Running | Unknown),
+            Completed | Failed | Cancelled | Killed | TimedOut => false,
+        }
+    }
+}
+
+impl TaskInfo {
+    /// Move the task to `next`, keeping `completed_at` consistent.
+    ///
+    /// Returns `Ok(false)` when the task is already in `next` (no-op).
+    pub fn transition_to(&mut self, next: TaskStatus) -> Result<bool, InvalidTransition> {
+        if self.status == next {
+            return Ok(false);
+        }
+        if !self.status.can_transition_to(&next) {
+            return Err(InvalidTransition {
+                from: self.status.clone(),
+                to: next,
+            });
+        }
+
+        self.completed_at = if next.is_terminal() {
+            Some(Utc::now())
+        } else {
+            None
+        };
+        self.status = next;
+        Ok(true)
+    }
+
+    /// Start a finished task over: `Running` again from now, with what its
+    /// last run recorded cleared and its restart count bumped. The caller
+    /// records the new process or container.
+    pub fn restart(&mut self) -> Result<(), InvalidTransition> {
+        if !self.status.is_terminal() {
+            return Err(InvalidTransition {
+                from: self.status.clone(),
+                to: TaskStatus::Running,
+            });
+        }
+        self.status = TaskStatus::Running;
+        self.started_at = Utc::now();
+        self.completed_at = None;
+        self.kill_reason = None;
+        self.failure_reason = None;
+        self.triage_hint = None;
+        self.result = None;
+        self.cpu_seconds = None;
+        self.ready_at = None;
+        self.restarts += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::TaskCommand;
+
+    fn running_task() -> TaskInfo {
+        TaskInfo::new(TaskCommand::shell("true"))
+    }
+
+    #[test]
+    fn test_running_to_terminal() {
+        for terminal in [
+            TaskStatus::Completed,
+            TaskStatus::Failed,
+            TaskStatus::Cancelled,
+            TaskStatus::Killed,
+            TaskStatus::TimedOut,
+        ] {
+            let mut task = running_task();
+            assert_eq!(task.transition_to(terminal.clone()), Ok(true));
+            assert_eq!(task.status, terminal);
+            assert!(task.completed_at.is_some());
+        }
+    }
+
+    #[test]
+    fn test_in_between_states() {
+        let mut task = running_task();
+        task.status = TaskStatus::Queued;
+        task.transition_to(TaskStatus::Starting).unwrap();
+        assert!(task.transition_to(TaskStatus::Queued).is_err());
+
+        // Losing track of a task is recoverable, not terminal
+        task.transition_to(TaskStatus::Unknown).unwrap();
+        assert!(task.completed_at.is_none());
+        task.transition_to(TaskStatus::Running).unwrap();
+        task.transition_to(TaskStatus::Unknown).unwrap();
+        task.transition_to(TaskStatus::Failed).unwrap();
+        assert!(task.completed_at.is_some());
+    }
+
+    #[test]
+    fn test_restart_starts_a_finished_task_over() {
+        let mut task = running_task();
+        assert!(task.restart().is_err());
+
+        task.transition_to(TaskStatus::Failed).unwrap();
+        task.triage_hint = Some("out of memory".into());
+        task.restart().unwrap();
+        assert_eq!(task.status, TaskStatus::Running);
+        assert!(task.completed_at.is_none());
+        assert!(task.triage_hint.is_none());
+        assert_eq!(task.restarts, 1);
+
+        let policy = crate::types::RestartPolicy::OnFailure;
+        assert!(policy.restarts_after(&TaskStatus::TimedOut));
+        assert!(!policy.restarts_after(&TaskStatus::Completed));
+        // Killing is how a task with a restart policy is stopped for good
+        assert!(!crate::types::RestartPolicy::Always.restarts_after(&TaskStatus::Killed));
+    }
+
+    #[test]
+    fn test_terminal_states_are_final() {
+        let mut task = running_task();
+        task.transition_to(TaskStatus::Completed).unwrap();
+        let finished_at = task.completed_at;
+
+        assert_eq!(
+            task.transition_to(TaskStatus::Running),
+            Err(InvalidTransition {
+                from: TaskStatus::Completed,
+                to: TaskStatus::Running,
+            })
+        );
+        assert!(task.transition_to(TaskStatus::Failed).is_err());
+
+        // Re-applying the same state is a no-op that keeps the original timestamp
+        assert_eq!(task.transition_to(TaskStatus::Completed), Ok(false));
+        assert_eq!(task.completed_at, finished_at);
+    }
+
+    #[test]
+    fn test_past_timeout_only_while_running() {
+        let mut task = running_task();
+        let later = task.started_at + chrono::Duration::minutes(31);
+        assert!(!task.is_past_timeout(later));
+
+        task.timeout_secs = Some(1800);
+        assert!(!task.is_past_timeout(task.started_at + chrono::Duration::minutes(29)));
+        assert!(task.is_past_timeout(later));
+
+        task.transition_to(TaskStatus::TimedOut).unwrap();
+        assert!(!task.is_past_timeout(later));
+        assert_eq!("timed_out".parse::<TaskStatus>(), Ok(TaskStatus::TimedOut));
+    }
+}
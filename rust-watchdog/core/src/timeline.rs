@@ -0,0 +1,523 @@
+//! The lifecycle journal's records, and timelines of what happened to the
+//! registry during a window, rebuilt from the journal for `replay`.
+//!
+//! The journal holds every status change of every task (a [`Transition`],
+//! recorded by whichever process made it) and the events the daemon
+//! publishes that change no status (an [`EventRecord`]: zombies,
+//! thresholds, the registry counts after a check), in the order they
+//! happened. Replaying the lines in order brings back each task's status
+//! and the registry counts as of every check. Lines before the window
+//! still count towards the statuses, so the window opens on the state the
+//! registry was in.
+
+use crate::alerts;
+use crate::events::WatchdogEvent;
+use crate::report::RegistryStats;
+use crate::types::TaskStatus;
+use crate::units;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Local, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Which part of the watchdog made a change
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Actor {
+    /// The daemon (`run`)
+    Watchdog,
+    /// A command run by hand or by an agent
+    #[default]
+    Cli,
+    /// The HTTP or gRPC API
+    Api,
+}
+
+impl std::fmt::Display for Actor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Actor::Watchdog => "watchdog",
+            Actor::Cli => "cli",
+            Actor::Api => "api",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// What happened to a task
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Change {
+    Registered,
+    Started,
+    Restarted,
+    Completed,
+    Failed,
+    Cancelled,
+    Killed,
+    TimedOut,
+    /// Its liveness could no longer be told
+    Lost,
+    /// Removed from the registry and its history by `cleanup`
+    CleanedUp,
+}
+
+impl Change {
+    /// The change from status `from` to `to`, either missing when the task
+    /// was registered or cleaned up
+    pub fn of(from: Option<&TaskStatus>, to: Option<&TaskStatus>) -> Self {
+        let (Some(from), Some(to)) = (from, to) else {
+            return if to.is_some() {
+                Change::Registered
+            } else {
+                Change::CleanedUp
+            };
+        };
+        match to {
+            TaskStatus::Queued | TaskStatus::Starting | TaskStatus::Running => {
+                if from.is_terminal() {
+                    Change::Restarted
+                } else {
+                    Change::Started
+                }
+            }
+            TaskStatus::Completed => Change::Completed,
+            TaskStatus::Failed => Change::Failed,
+            TaskStatus::Cancelled => Change::Cancelled,
+            TaskStatus::Killed => Change::Killed,
+            TaskStatus::TimedOut => Change::TimedOut,
+            TaskStatus::Unknown => Change::Lost,
+        }
+    }
+}
+
+impl std::fmt::Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Change::Registered => "registered",
+            Change::Started => "started",
+            Change::Restarted => "restarted",
+            Change::Completed => "completed",
+            Change::Failed => "failed",
+            Change::Cancelled => "cancelled",
+            Change::Killed => "killed",
+            Change::TimedOut => "timed out",
+            Change::Lost => "lost",
+            Change::CleanedUp => "cleaned up",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// A status change in the journal
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Transition {
+    pub at: DateTime<Utc>,
+    pub task_id: String,
+    pub change: Change,
+    /// Status before; unset when the task was registered
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<TaskStatus>,
+    /// Status after; unset when the task was cleaned up
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<TaskStatus>,
+    pub actor: Actor,
+    /// User the change was made as
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    /// Why it finished: the kill reason, failure reason or exit code
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl Transition {
+    /// One line for a timeline, e.g. `Task T3 failed (exit code 1)`
+    pub fn describe(&self) -> String {
+        match &self.reason {
+            Some(reason) => format!("Task {} {} ({})", self.task_id, self.change, reason),
+            None => format!("Task {} {}", self.task_id, self.change),
+        }
+    }
+}
+
+/// Another event the daemon published, in the journal, e.g.
+/// `{"at":"2026-10-16T14:02:11Z","event":"zombie_detected","task_id":"T3"}`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventRecord {
+    pub at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub event: WatchdogEvent,
+}
+
+/// One line of the journal
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum JournalLine {
+    Transition(Transition),
+    Event(EventRecord),
+}
+
+impl JournalLine {
+    pub fn at(&self) -> DateTime<Utc> {
+        match self {
+            JournalLine::Transition(transition) => transition.at,
+            JournalLine::Event(record) => record.at,
+        }
+    }
+
+    /// The task the line is about (`None` for registry-wide events)
+    pub fn task_id(&self) -> Option<&str> {
+        match self {
+            JournalLine::Transition(transition) => Some(&transition.task_id),
+            JournalLine::Event(record) => record.event.task_id(),
+        }
+    }
+
+    /// The task's status from here on, when the line changed it
+    pub fn status_after(&self) -> Option<TaskStatus> {
+        match self {
+            JournalLine::Transition(transition) => transition.to.clone(),
+            JournalLine::Event(record) => status_after(&record.event),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            JournalLine::Transition(transition) => transition.describe(),
+            JournalLine::Event(record) => alerts::describe(&record.event),
+        }
+    }
+}
+
+/// Whether the daemon records `event` in the journal: the status changes
+/// are there already, from whoever made them
+pub fn is_journaled(event: &WatchdogEvent) -> bool {
+    !matches!(event, WatchdogEvent::TaskRegistered { .. }) && status_after(event).is_none()
+}
+
+/// The status `event` leaves its task in, if it says
+pub fn status_after(event: &WatchdogEvent) -> Option<TaskStatus> {
+    use WatchdogEvent::*;
+    match event {
+        TaskStarted { .. } | TaskRestarted { .. } => Some(TaskStatus::Running),
+        TaskCompleted { .. } => Some(TaskStatus::Completed),
+        TaskDied { .. } | DependencyFailed { .. } => Some(TaskStatus::Failed),
+        TimedOut { .. } => Some(TaskStatus::TimedOut),
+        LimitExceeded { .. } => Some(TaskStatus::Killed),
+        TaskUnknown { .. } => Some(TaskStatus::Unknown),
+        // A zombie keeps the status it finished with
+        TaskRegistered { .. }
+        | TaskReady { .. }
+        | ZombieDetected { .. }
+        | ThresholdExceeded { .. }
+        | DurationAnomaly { .. }
+        | PortClosed { .. }
+        | WatchedProcessExited { .. }
+        | CheckCompleted { .. } => None,
+    }
+}
+
+/// A bound of the window: an age (`2h`, that long before `now`), a local
+/// time today (`14:05`), a local date and time (`2026-10-16 14:05`) or an
+/// RFC 3339 timestamp
+pub fn parse_when(s: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>> {
+    let s = s.trim();
+    if let Some(secs) = units::parse_duration(s) {
+        return Ok(now - chrono::Duration::milliseconds((secs * 1000.0) as i64));
+    }
+    if let Ok(at) = DateTime::parse_from_rfc3339(s) {
+        return Ok(at.with_timezone(&Utc));
+    }
+    let local = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())
+        .or_else(|| {
+            ["%H:%M:%S", "%H:%M"]
+                .iter()
+                .find_map(|format| NaiveTime::parse_from_str(s, format).ok())
+                .map(|time| now.with_timezone(&Local).date_naive().and_time(time))
+        });
+    local
+        .and_then(|at| Local.from_local_datetime(&at).earliest())
+        .map(|at| at.with_timezone(&Utc))
+        .ok_or_else(|| {
+            anyhow!(
+                "Invalid time '{}' (e.g. 2h, 14:05, '2026-10-16 14:05' or RFC 3339)",
+                s
+            )
+        })
+}
+
+/// Something that happened during the window
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TimelineEntry {
+    /// Seconds since the window opened
+    pub offset_secs: i64,
+    #[serde(flatten)]
+    pub line: JournalLine,
+    pub description: String,
+    /// The task's status from here on, when the line changed it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<TaskStatus>,
+}
+
+/// A task as of the end of the window
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TaskState {
+    /// Last status an event gave it (`None` if it was only registered)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<TaskStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_event: Option<DateTime<Utc>>,
+    /// Lines about it during the window
+    pub events: usize,
+}
+
+/// The window's events in order, and where they left the registry
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Timeline {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end: Option<DateTime<Utc>>,
+    /// Checks are only listed when the counts changed
+    pub entries: Vec<TimelineEntry>,
+    pub tasks: BTreeMap<String, TaskState>,
+    /// Counts of the last check in the window
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats: Option<RegistryStats>,
+}
+
+impl Timeline {
+    /// Replay the journal's `lines` (in the order they were written) for
+    /// the window from `since` until `until`, either open-ended
+    pub fn replay(
+        lines: impl IntoIterator<Item = JournalLine>,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+    ) -> Self {
+        let mut timeline = Timeline::default();
+        let mut before: BTreeMap<String, TaskState> = BTreeMap::new();
+        let mut stats_before = None;
+        for line in lines {
+            let at = line.at();
+            if until.is_some_and(|until| at > until) {
+                break;
+            }
+            let in_window = since.is_none_or(|since| at >= since);
+            if in_window && timeline.start.is_none() {
+                // Open on the state the earlier lines left
+                timeline.start = Some(since.unwrap_or(at));
+                timeline.tasks = std::mem::take(&mut before)
+                    .into_iter()
+                    .map(|(task_id, state)| (task_id, TaskState { events: 0, ..state }))
+                    .collect();
+                timeline.stats = stats_before.take();
+            }
+            let (tasks, last_stats) = if in_window {
+                (&mut timeline.tasks, &mut timeline.stats)
+            } else {
+                (&mut before, &mut stats_before)
+            };
+            if let JournalLine::Event(EventRecord {
+                event: WatchdogEvent::CheckCompleted { stats },
+                ..
+            }) = &line
+            {
+                let changed = last_stats.as_ref() != Some(stats);
+                *last_stats = Some(stats.clone());
+                if !changed || !in_window {
+                    continue;
+                }
+            }
+            let status = line.status_after();
+            if let Some(task_id) = line.task_id() {
+                let state = tasks.entry(task_id.to_string()).or_default();
+                if status.is_some() {
+                    state.status = status.clone();
+                }
+                state.last_event = Some(at);
+                state.events += 1;
+            }
+            if !in_window {
+                continue;
+            }
+            let start = timeline.start.unwrap_or(at);
+            timeline.end = Some(at);
+            timeline.entries.push(TimelineEntry {
+                offset_secs: (at - start).num_seconds(),
+                description: line.describe(),
+                line,
+                status,
+            });
+        }
+        timeline
+    }
+
+    /// Tasks that ended the window in each status
+    pub fn status_counts(&self) -> BTreeMap<String, usize> {
+        let mut counts = BTreeMap::new();
+        for state in self.tasks.values() {
+            let status = match &state.status {
+                Some(status) => status.to_string(),
+                None => "registered".to_string(),
+            };
+            *counts.entry(status).or_default() += 1;
+        }
+        counts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn event(start: DateTime<Utc>, secs: i64, event: WatchdogEvent) -> JournalLine {
+        JournalLine::Event(EventRecord {
+            at: start + Duration::seconds(secs),
+            event,
+        })
+    }
+
+    fn change(
+        start: DateTime<Utc>,
+        secs: i64,
+        task_id: &str,
+        from: Option<TaskStatus>,
+        to: TaskStatus,
+    ) -> JournalLine {
+        JournalLine::Transition(Transition {
+            at: start + Duration::seconds(secs),
+            task_id: task_id.to_string(),
+            change: Change::of(from.as_ref(), Some(&to)),
+            from,
+            to: Some(to),
+            actor: Actor::Watchdog,
+            user: None,
+            reason: None,
+        })
+    }
+
+    fn journal(start: DateTime<Utc>) -> Vec<JournalLine> {
+        use TaskStatus::*;
+        let check = |running, failed| WatchdogEvent::CheckCompleted {
+            stats: RegistryStats {
+                total: 2,
+                running,
+                failed,
+                ..Default::default()
+            },
+        };
+        let mut lines = vec![
+            change(start, 0, "a", None, Queued),
+            change(start, 1, "a", Some(Queued), Running),
+            event(start, 1, check(1, 0)),
+            change(start, 60, "b", None, Queued),
+            event(start, 61, check(1, 0)),
+            change(start, 90, "a", Some(Running), Failed),
+            event(start, 91, check(0, 1)),
+        ];
+        if let JournalLine::Transition(died) = &mut lines[5] {
+            died.reason = Some("exit code 137".into());
+        }
+        lines
+    }
+
+    #[test]
+    fn test_replay_rebuilds_statuses() {
+        let start = Utc::now();
+        let timeline = Timeline::replay(journal(start), None, None);
+        assert_eq!(timeline.start, Some(start));
+        assert_eq!(timeline.end, Some(start + Duration::seconds(91)));
+        // The check that changed nothing is left out
+        assert_eq!(timeline.entries.len(), 6);
+        let died = &timeline.entries[4];
+        assert_eq!(
+            (died.offset_secs, died.status.clone()),
+            (90, Some(TaskStatus::Failed))
+        );
+        assert_eq!(died.description, "Task a failed (exit code 137)");
+        assert_eq!(timeline.tasks["a"].status, Some(TaskStatus::Failed));
+        assert_eq!(timeline.tasks["a"].events, 3);
+        assert_eq!(timeline.tasks["b"].status, Some(TaskStatus::Queued));
+        assert_eq!(timeline.stats.as_ref().map(|s| s.failed), Some(1));
+        assert_eq!(
+            timeline.status_counts(),
+            BTreeMap::from([("failed".into(), 1), ("queued".into(), 1)])
+        );
+    }
+
+    #[test]
+    fn test_window_opens_on_earlier_state() {
+        let start = Utc::now();
+        let since = start + Duration::seconds(80);
+        let timeline = Timeline::replay(journal(start), Some(since), None);
+        assert_eq!(timeline.start, Some(since));
+        assert_eq!(timeline.entries.len(), 2);
+        assert_eq!(timeline.entries[0].offset_secs, 10);
+        // Known from before the window, without its events
+        assert_eq!(timeline.tasks["b"].events, 0);
+        assert_eq!(timeline.tasks["a"].events, 1);
+
+        let until = start + Duration::seconds(30);
+        let early = Timeline::replay(journal(start), None, Some(until));
+        assert_eq!(early.tasks["a"].status, Some(TaskStatus::Running));
+        assert!(!early.tasks.contains_key("b"));
+    }
+
+    #[test]
+    fn test_parse_when() {
+        let now: DateTime<Utc> = "2026-10-16T14:00:00Z".parse().unwrap();
+        assert_eq!(parse_when("90m", now).unwrap(), now - Duration::minutes(90));
+        assert_eq!(
+            parse_when("2026-10-16T14:30:00+02:00", now).unwrap(),
+            now - Duration::minutes(90)
+        );
+        let local = now.with_timezone(&Local);
+        let today = parse_when(&local.format("%H:%M").to_string(), now).unwrap();
+        assert_eq!(today, now);
+        let dated = parse_when(&local.format("%Y-%m-%d %H:%M:%S").to_string(), now).unwrap();
+        assert_eq!(dated, now);
+        assert!(parse_when("yesterday", now).is_err());
+    }
+
+    #[test]
+    fn test_journal_lines_are_flat() {
+        let at: DateTime<Utc> = "2026-10-16T14:02:11Z".parse().unwrap();
+        let zombie = JournalLine::Event(EventRecord {
+            at,
+            event: WatchdogEvent::ZombieDetected {
+                task_id: "T3".into(),
+            },
+        });
+        let line = serde_json::to_string(&zombie).unwrap();
+        assert_eq!(
+            line,
+            r#"{"at":"2026-10-16T14:02:11Z","event":"zombie_detected","task_id":"T3"}"#
+        );
+        assert_eq!(serde_json::from_str::<JournalLine>(&line).unwrap(), zombie);
+
+        let line = r#"{"at":"2026-10-16T14:02:11Z","task_id":"T3","change":"killed","from":"running","to":"killed","actor":"cli"}"#;
+        let JournalLine::Transition(transition) = serde_json::from_str(line).unwrap() else {
+            panic!("expected a transition");
+        };
+        assert_eq!(transition.change, Change::Killed);
+    }
+
+    #[test]
+    fn test_change_of() {
+        use TaskStatus::*;
+        let cases = [
+            (None, Some(Queued), Change::Registered),
+            (Some(Queued), Some(Running), Change::Started),
+            (Some(Running), Some(Failed), Change::Failed),
+            (Some(Failed), Some(Running), Change::Restarted),
+            (Some(Running), Some(TimedOut), Change::TimedOut),
+            (Some(Killed), None, Change::CleanedUp),
+        ];
+        for (from, to, change) in cases {
+            assert_eq!(Change::of(from.as_ref(), to.as_ref()), change);
+        }
+    }
+}
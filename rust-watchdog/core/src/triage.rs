@@ -0,0 +1,138 @@
+//! Failure triage: a one-line guess at why a task failed, from its exit
+//! code and whatever output was captured, attached as `triage_hint`.
+//!
+//! These are cheap pattern checks meant to save the first minutes of an
+//! investigation, not a diagnosis. Output is checked before the exit code,
+//! since a message like "command not found" says more than status 1.
+
+/// What is known about a failed run
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Evidence<'a> {
+    pub exit_code: Option<i64>,
+    pub oom_killed: bool,
+    /// Tail of the task's output, when it was captured
+    pub output: Option<&'a str>,
+}
+
+const OOM_HINT: &str = "out of memory: raise the memory limit or shrink the working set";
+
+/// Output patterns (matched case-insensitively) and the hint they give
+const OUTPUT_HINTS: &[(&[&str], &str)] = &[
+    (
+        &[
+            "out of memory",
+            "cannot allocate memory",
+            "memoryerror",
+            "oom-kill",
+        ],
+        OOM_HINT,
+    ),
+    (
+        &[
+            "command not found",
+            "no such file or directory",
+            "executable file not found",
+        ],
+        "missing binary or file: check the command is installed and paths are right",
+    ),
+    (
+        &["permission denied", "eacces", "operation not permitted"],
+        "permission denied: check file modes and which user the task runs as",
+    ),
+    (
+        &[
+            "timed out",
+            "etimedout",
+            "connection refused",
+            "econnrefused",
+            "temporary failure in name resolution",
+            "could not resolve host",
+            "network is unreachable",
+        ],
+        "network problem: a host was unreachable or a request timed out",
+    ),
+    (
+        &[
+            "test result: failed",
+            "assertionerror",
+            "tests failed",
+            "failed tests",
+            "short test summary info",
+            "failures:",
+        ],
+        "test failures: see the failing tests in the output",
+    ),
+];
+
+/// Best guess at why a run failed, if anything points somewhere
+pub fn hint(evidence: Evidence<'_>) -> Option<String> {
+    if evidence.oom_killed {
+        return Some(OOM_HINT.to_string());
+    }
+
+    if let Some(output) = evidence.output {
+        let output = output.to_lowercase();
+        for (patterns, hint) in OUTPUT_HINTS {
+            if patterns.iter().any(|p| output.contains(p)) {
+                return Some(hint.to_string());
+            }
+        }
+    }
+
+    let hint = match evidence.exit_code? {
+        126 => "command not executable: check its permissions",
+        127 => "command not found: check the binary is installed and on PATH",
+        130 => "interrupted (SIGINT)",
+        132 => "illegal instruction (SIGILL): a binary built for another CPU, or corrupted",
+        134 => "aborted (SIGABRT): an assertion or panic in native code",
+        135 => "bus error (SIGBUS): often a truncated memory-mapped file",
+        136 => "arithmetic error (SIGFPE), e.g. an integer division by zero",
+        137 => "killed by SIGKILL, often the kernel OOM killer",
+        139 => "segmentation fault (SIGSEGV)",
+        143 => "terminated (SIGTERM) by something other than the watchdog",
+        _ => return None,
+    };
+    Some(hint.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hints() {
+        let exit = |code| Evidence {
+            exit_code: Some(code),
+            ..Default::default()
+        };
+        assert!(hint(exit(127)).unwrap().starts_with("command not found"));
+        assert!(hint(exit(137)).unwrap().contains("OOM"));
+        assert!(hint(exit(136)).unwrap().contains("SIGFPE"));
+        assert_eq!(hint(exit(1)), None);
+        assert_eq!(hint(Evidence::default()), None);
+
+        let oom = Evidence {
+            oom_killed: true,
+            ..exit(0)
+        };
+        assert!(hint(oom).unwrap().starts_with("out of memory"));
+
+        // Output beats a generic exit code
+        let with_output = |output| Evidence {
+            output: Some(output),
+            ..exit(1)
+        };
+        assert!(hint(with_output("bash: rg: command not found"))
+            .unwrap()
+            .starts_with("missing binary"));
+        assert!(hint(with_output("curl: (28) Connection timed out"))
+            .unwrap()
+            .starts_with("network"));
+        assert!(hint(with_output(
+            "=== short test summary info ===\nFAILED t.py::x"
+        ))
+        .unwrap()
+        .starts_with("test failures"));
+        assert_eq!(hint(with_output("all good")), None);
+    }
+}
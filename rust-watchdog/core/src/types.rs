@@ -0,0 +1,1397 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::PathBuf;
+
+/// Execution mode for tasks
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExecutionMode {
+    Native,
+    Docker,
+}
+
+/// Native process information
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NativeTask {
+    pub pid: i32,
+    pub pgid: i32,
+    pub start_time: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env_tag: Option<String>,
+    /// Windows job object holding the task's process tree, killed as one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job: Option<String>,
+}
+
+/// Seconds a container gets to exit after SIGTERM before it is killed
+pub const DEFAULT_STOP_TIMEOUT_SECS: u64 = 10;
+
+/// Docker container information
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DockerTask {
+    pub container_id: String,
+    pub container_name: String,
+    pub resource_limits: ResourceLimits,
+    /// Seconds to wait after SIGTERM before SIGKILL, for containers that
+    /// need longer to shut down cleanly (databases, log flushes)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_timeout_secs: Option<u64>,
+    /// Image the container was created from, to recreate it from if it
+    /// has to be restarted after it was removed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    /// Remove the container once it has stopped, instead of keeping it
+    /// for `logs` and `inspect`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub auto_remove: bool,
+    /// With `auto_remove`, keep the container of a run that failed
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub keep_on_failure: bool,
+    /// Exit code of the container's last run, recorded when it stopped
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i64>,
+}
+
+impl DockerTask {
+    /// Grace period between SIGTERM and SIGKILL when stopping
+    pub fn stop_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.stop_timeout_secs.unwrap_or(DEFAULT_STOP_TIMEOUT_SECS))
+    }
+}
+
+/// Component that registered or killed a task, and where it ran
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TaskOrigin {
+    /// `cli`, `daemon`, `api`, `python-sdk`, `mcp`, ...
+    pub tool: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    /// tmux pane it was run from, for `goto`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tmux: Option<TmuxPane>,
+}
+
+/// A tmux pane, by its server-unique ID and its position when recorded
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TmuxPane {
+    /// `%12`
+    pub pane_id: String,
+    pub session: String,
+    pub window: u32,
+    pub pane: u32,
+    /// Server socket, from `$TMUX`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub socket: Option<String>,
+}
+
+impl std::fmt::Display for TmuxPane {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}.{}", self.session, self.window, self.pane)
+    }
+}
+
+impl std::fmt::Display for TaskOrigin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.tool)?;
+        match (&self.user, &self.host) {
+            (Some(user), Some(host)) => write!(f, " ({user}@{host})"),
+            (Some(user), None) => write!(f, " ({user})"),
+            (None, Some(host)) => write!(f, " (on {host})"),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// Where a container task may leave a [`TaskResult`] before exiting,
+/// relative to its `/workspace`
+pub const RESULT_FILE: &str = ".watchdog/result.json";
+
+/// Outcome a task reports itself, read from [`RESULT_FILE`] when its
+/// container stops
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct TaskResult {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    /// Files the task produced, relative to `/workspace`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub artifacts: Vec<String>,
+}
+
+/// AI API usage reported by a task (cumulative)
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct TaskCost {
+    #[serde(default)]
+    pub input_tokens: u64,
+    #[serde(default)]
+    pub output_tokens: u64,
+    /// Spend in US dollars
+    #[serde(default)]
+    pub usd: f64,
+}
+
+impl TaskCost {
+    pub fn add(&mut self, other: &TaskCost) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.usd += other.usd;
+    }
+
+    pub fn total_tokens(&self) -> u64 {
+        self.input_tokens + self.output_tokens
+    }
+}
+
+/// Resource limits for Docker containers
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResourceLimits {
+    pub memory: String,
+    pub cpu: String,
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        Self {
+            memory: "512m".to_string(),
+            cpu: "1.0".to_string(),
+        }
+    }
+}
+
+/// Command a task runs: an argv array executed directly, or (with `shell`)
+/// a single command line handed to `sh -c`.
+///
+/// Registries written before argv support stored a plain string; it
+/// deserializes as a shell command so old entries keep their meaning.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "CommandRepr")]
+pub struct TaskCommand {
+    pub argv: Vec<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub shell: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CommandRepr {
+    Legacy(String),
+    Argv {
+        argv: Vec<String>,
+        #[serde(default)]
+        shell: bool,
+    },
+}
+
+impl From<CommandRepr> for TaskCommand {
+    fn from(repr: CommandRepr) -> Self {
+        match repr {
+            CommandRepr::Legacy(line) => TaskCommand::shell(line),
+            CommandRepr::Argv { argv, shell } => TaskCommand { argv, shell },
+        }
+    }
+}
+
+impl TaskCommand {
+    /// Executed directly, without shell interpretation
+    pub fn argv<I, S>(argv: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            argv: argv.into_iter().map(Into::into).collect(),
+            shell: false,
+        }
+    }
+
+    /// A command line interpreted by `sh -c`
+    pub fn shell(line: impl Into<String>) -> Self {
+        Self {
+            argv: vec![line.into()],
+            shell: true,
+        }
+    }
+
+    /// Build from a user-supplied command line.
+    ///
+    /// By default the line is split with shell quoting rules and executed
+    /// directly; shell operators are refused because without a shell they
+    /// would silently become literal arguments. `shell` keeps the line whole
+    /// for `sh -c`.
+    pub fn parse(line: &str, shell: bool) -> anyhow::Result<Self> {
+        if line.trim().is_empty() {
+            anyhow::bail!("Command is empty");
+        }
+        if shell {
+            return Ok(Self::shell(line));
+        }
+
+        let argv = shell_words::split(line)
+            .map_err(|e| anyhow::anyhow!("Cannot parse command `{line}`: {e}"))?;
+        if let Some(op) = argv.iter().find(|arg| is_shell_operator(arg)) {
+            anyhow::bail!(
+                "Command uses shell syntax (`{op}`), which direct execution would pass through \
+                 as a literal argument. Pass --shell to run it with sh -c"
+            );
+        }
+        Ok(Self::argv(argv))
+    }
+
+    /// Full argv to execute (native spawn, Docker `cmd`, sandbox)
+    pub fn exec_argv(&self) -> Vec<String> {
+        if self.shell {
+            let mut argv = vec!["sh".to_string(), "-c".to_string()];
+            argv.extend(self.argv.iter().cloned());
+            argv
+        } else {
+            self.argv.clone()
+        }
+    }
+}
+
+impl std::fmt::Display for TaskCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.shell {
+            return write!(f, "{}", self.argv.join(" "));
+        }
+        let quoted: Vec<String> = self.argv.iter().map(|arg| quote_arg(arg)).collect();
+        write!(f, "{}", quoted.join(" "))
+    }
+}
+
+/// Unquoted shell control/redirection tokens (`&&`, `|`, `>out.log`, ...)
+fn is_shell_operator(arg: &str) -> bool {
+    matches!(arg, "&&" | "||" | "|" | ";" | "&" | ";;")
+        || arg.starts_with('>')
+        || arg.starts_with('<')
+        || arg.starts_with("2>")
+        || arg.starts_with("&>")
+}
+
+/// Quote an argument for display so the printed command can be pasted into a shell
+fn quote_arg(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:,@%+".contains(c));
+    if plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// Why a task failed without the watchdog seeing it exit
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureReason {
+    /// The machine rebooted while the task was running
+    SystemRebooted,
+    /// A task it depends on finished without becoming ready, so it never started
+    DependencyFailed,
+}
+
+impl std::fmt::Display for FailureReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FailureReason::SystemRebooted => write!(f, "system rebooted while it was running"),
+            FailureReason::DependencyFailed => {
+                write!(f, "a task it depends on finished without becoming ready")
+            }
+        }
+    }
+}
+
+/// Core dump a native task's process left when it crashed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Coredump {
+    /// Process that dumped core
+    pub pid: i32,
+    /// Signal that killed it (11 for SIGSEGV), when known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signal: Option<i32>,
+    /// The core file, when it is on disk where the watchdog can see it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    /// Kept by systemd-coredump: `coredumpctl debug <pid>` opens it
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub coredumpctl: bool,
+    /// Top frames of its backtrace, innermost first (`--core-backtrace`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub backtrace: Vec<String>,
+}
+
+impl Coredump {
+    /// How to open it in a debugger
+    pub fn debug_command(&self) -> Option<String> {
+        if self.coredumpctl {
+            return Some(format!("coredumpctl debug {}", self.pid));
+        }
+        self.path
+            .as_ref()
+            .map(|path| format!("gdb -c {}", path.display()))
+    }
+
+    /// One line for a triage hint: where it crashed, if the backtrace says
+    pub fn summary(&self) -> String {
+        match self.backtrace.first() {
+            Some(frame) => format!("core dumped, crashed in {frame}"),
+            None => "core dumped".to_string(),
+        }
+    }
+}
+
+/// How to tell that a task (typically a server) is ready for the tasks
+/// that depend on it, rather than merely running
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadinessProbe {
+    /// Something accepts TCP connections on this local port
+    Port(u16),
+    /// A GET of this URL succeeds (2xx/3xx)
+    Http(String),
+    /// This command exits 0
+    Command(TaskCommand),
+}
+
+impl std::str::FromStr for ReadinessProbe {
+    type Err = anyhow::Error;
+
+    /// `port:8080`, `http://localhost:8080/health` or `cmd:pg_isready -q`
+    /// (run with `sh -c`)
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        if let Some(port) = s.strip_prefix("port:") {
+            let port = port
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Invalid port in readiness probe: {port}"))?;
+            return Ok(ReadinessProbe::Port(port));
+        }
+        if s.starts_with("http://") || s.starts_with("https://") {
+            return Ok(ReadinessProbe::Http(s.to_string()));
+        }
+        if let Some(line) = s.strip_prefix("cmd:") {
+            return Ok(ReadinessProbe::Command(TaskCommand::parse(line, true)?));
+        }
+        anyhow::bail!(
+            "Unknown readiness probe `{s}` (expected port:N, http(s)://URL or cmd:COMMAND)"
+        )
+    }
+}
+
+impl std::fmt::Display for ReadinessProbe {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReadinessProbe::Port(port) => write!(f, "port:{port}"),
+            ReadinessProbe::Http(url) => f.write_str(url),
+            ReadinessProbe::Command(command) => write!(f, "cmd:{command}"),
+        }
+    }
+}
+
+/// When the daemon starts a finished task again (set in `up` launch files)
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    /// Leave it finished
+    #[default]
+    No,
+    /// After it failed or timed out
+    OnFailure,
+    /// After any ending except being killed (which is how it is stopped)
+    Always,
+}
+
+impl RestartPolicy {
+    pub fn is_no(&self) -> bool {
+        *self == RestartPolicy::No
+    }
+
+    /// Whether a task that ended as `status` is started again
+    pub fn restarts_after(&self, status: &TaskStatus) -> bool {
+        match self {
+            RestartPolicy::No => false,
+            RestartPolicy::OnFailure => {
+                matches!(status, TaskStatus::Failed | TaskStatus::TimedOut)
+            }
+            RestartPolicy::Always => status.is_terminal() && *status != TaskStatus::Killed,
+        }
+    }
+}
+
+impl std::str::FromStr for RestartPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "no" => Ok(RestartPolicy::No),
+            "on-failure" => Ok(RestartPolicy::OnFailure),
+            "always" => Ok(RestartPolicy::Always),
+            other => anyhow::bail!(
+                "Unknown restart policy `{other}` (expected no, on-failure or always)"
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for RestartPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            RestartPolicy::No => "no",
+            RestartPolicy::OnFailure => "on-failure",
+            RestartPolicy::Always => "always",
+        })
+    }
+}
+
+/// A kernel resource limit (`setrlimit`) the watchdog starts a native task
+/// under (`spawn --rlimit nofile=4096`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rlimit {
+    /// Open file descriptors per process
+    Nofile,
+    /// Processes (and threads) of the task's user
+    Nproc,
+    /// Largest file a process may write
+    Fsize,
+    /// Address space per process
+    As,
+    /// Largest core dump
+    Core,
+    /// CPU time per process, in seconds
+    Cpu,
+    /// Stack size per process
+    Stack,
+}
+
+impl Rlimit {
+    pub const ALL: [Rlimit; 7] = [
+        Rlimit::Nofile,
+        Rlimit::Nproc,
+        Rlimit::Fsize,
+        Rlimit::As,
+        Rlimit::Core,
+        Rlimit::Cpu,
+        Rlimit::Stack,
+    ];
+
+    /// Whether its value is a size in bytes
+    pub fn is_size(self) -> bool {
+        matches!(
+            self,
+            Rlimit::Fsize | Rlimit::As | Rlimit::Core | Rlimit::Stack
+        )
+    }
+
+    /// Parse a `name=value` setting: sizes take units (`fsize=1g`), `cpu`
+    /// takes a duration (`cpu=10m`), the others a count
+    pub fn parse_setting(s: &str) -> anyhow::Result<(Rlimit, u64)> {
+        let Some((name, value)) = s.split_once('=') else {
+            anyhow::bail!("Invalid rlimit '{s}' (expected NAME=VALUE, e.g. nofile=4096)");
+        };
+        let rlimit: Rlimit = name.trim().parse()?;
+        let parsed = if rlimit.is_size() {
+            crate::units::parse_bytes(value)
+        } else if rlimit == Rlimit::Cpu {
+            crate::units::parse_duration(value).map(|secs| secs.round() as u64)
+        } else {
+            value.trim().parse().ok()
+        };
+        match parsed {
+            Some(limit) => Ok((rlimit, limit)),
+            None => anyhow::bail!("Invalid value for rlimit {rlimit}: '{}'", value.trim()),
+        }
+    }
+
+    /// `value` in this limit's unit, as `--rlimit` takes it
+    pub fn format_value(self, value: u64) -> String {
+        if self.is_size() && value >= 1 << 20 && value.is_multiple_of(1 << 20) {
+            if value.is_multiple_of(1 << 30) {
+                format!("{}g", value >> 30)
+            } else {
+                format!("{}m", value >> 20)
+            }
+        } else if self == Rlimit::Cpu {
+            format!("{value}s")
+        } else {
+            value.to_string()
+        }
+    }
+}
+
+impl std::str::FromStr for Rlimit {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        Rlimit::ALL
+            .into_iter()
+            .find(|rlimit| rlimit.to_string() == s.to_ascii_lowercase())
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Unknown rlimit `{s}` (expected nofile, nproc, fsize, as, core, cpu or stack)"
+                )
+            })
+    }
+}
+
+impl std::fmt::Display for Rlimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Rlimit::Nofile => "nofile",
+            Rlimit::Nproc => "nproc",
+            Rlimit::Fsize => "fsize",
+            Rlimit::As => "as",
+            Rlimit::Core => "core",
+            Rlimit::Cpu => "cpu",
+            Rlimit::Stack => "stack",
+        })
+    }
+}
+
+/// Where a task stands with the tasks it depends on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Dependencies {
+    /// All of them are ready (or there are none)
+    Ready,
+    /// This one isn't ready yet
+    Waiting(String),
+    /// This one finished without becoming ready, so the task can't start
+    Failed(String),
+}
+
+/// Task status
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskStatus {
+    /// Registered but not started yet
+    Queued,
+    /// Container created, not yet confirmed running
+    Starting,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+    /// Forcibly terminated (manual kill, zombie sweep, resource limit)
+    Killed,
+    /// Terminated for running longer than its `--timeout`
+    #[serde(rename = "timed_out")]
+    TimedOut,
+    /// Liveness cannot be determined (Docker unreachable, metadata missing)
+    Unknown,
+}
+
+impl std::str::FromStr for TaskStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "queued" => Ok(TaskStatus::Queued),
+            "starting" => Ok(TaskStatus::Starting),
+            "running" => Ok(TaskStatus::Running),
+            "completed" => Ok(TaskStatus::Completed),
+            "failed" => Ok(TaskStatus::Failed),
+            "cancelled" => Ok(TaskStatus::Cancelled),
+            "killed" => Ok(TaskStatus::Killed),
+            "timed_out" | "timedout" => Ok(TaskStatus::TimedOut),
+            "unknown" => Ok(TaskStatus::Unknown),
+            other => Err(format!("Unknown task status: {other}")),
+        }
+    }
+}
+
+impl std::fmt::Display for TaskStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            TaskStatus::Queued => "queued",
+            TaskStatus::Starting => "starting",
+            TaskStatus::Running => "running",
+            TaskStatus::Completed => "completed",
+            TaskStatus::Failed => "failed",
+            TaskStatus::Cancelled => "cancelled",
+            TaskStatus::Killed => "killed",
+            TaskStatus::TimedOut => "timed_out",
+            TaskStatus::Unknown => "unknown",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// Complete task information
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskInfo {
+    pub mode: ExecutionMode,
+    pub command: TaskCommand,
+
+    /// What the task is for, in words (the command alone rarely says)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// What should be true once the task has succeeded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_outcome: Option<String>,
+    pub status: TaskStatus,
+    pub started_at: DateTime<Utc>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<DateTime<Utc>>,
+
+    /// Why the task was killed (set by kill paths only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kill_reason: Option<String>,
+
+    /// Why the task was failed by the watchdog rather than by its own exit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure_reason: Option<FailureReason>,
+
+    /// Likely cause of a failure (see [`crate::triage`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub triage_hint: Option<String>,
+
+    /// Core dump it left when it crashed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coredump: Option<Coredump>,
+
+    /// The traceback, panic or error block at the end of its output when
+    /// it failed (see [`crate::excerpt`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure_excerpt: Option<String>,
+
+    /// What the task reported about its own run (see [`RESULT_FILE`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<TaskResult>,
+
+    /// Working directory the task was registered from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<PathBuf>,
+
+    /// Repository root containing `cwd` (tells checkouts of one repo apart)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repo_root: Option<PathBuf>,
+
+    /// User who registered the task (shared machines and registries)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+
+    /// Component that registered the task
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<TaskOrigin>,
+
+    /// Free-form `key=value` labels for grouping (`stats --group-by team`)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+
+    /// Group of related tasks managed together, e.g. one agent swarm
+    /// (`kill --group swarm-7`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+
+    /// Most tasks of its group running at once. Set on tasks `swarm` queued,
+    /// which are started by whoever promotes them (`swarm --jobs`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jobs: Option<u32>,
+
+    /// Token and spend counters reported by the task
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost: Option<TaskCost>,
+
+    /// Cumulative CPU time (user + system), sampled while the task runs;
+    /// the last sample stands once it has finished
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_seconds: Option<f64>,
+
+    /// Declared memory/CPU reservation (Docker tasks fall back to their limits)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reservation: Option<ResourceLimits>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub native: Option<NativeTask>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub docker: Option<DockerTask>,
+
+    /// TCP port the task serves (`adopt --port`); the daemon reports when
+    /// nothing listens on it anymore
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    /// File whose appearance marks the task finished (see `--done-file`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub done_file: Option<PathBuf>,
+    /// Where the output of a spawned task goes (see `logs`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_file: Option<PathBuf>,
+
+    /// Tasks that must be ready before this one starts (`--after`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<String>,
+    /// Longest the task may run before the watchdog terminates it (`--timeout`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+    /// Resident memory (MB) of its processes above which the watchdog warns,
+    /// or kills it under `run --enforce-limits` (`--max-memory`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_memory_mb: Option<u64>,
+    /// CPU use between two checks, in percent of one core, above which the
+    /// watchdog warns or kills it (`--max-cpu`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_cpu_percent: Option<f64>,
+    /// Processes its group may hold before the watchdog warns or kills it
+    /// (`--max-processes`, e.g. against a runaway test runner)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_processes: Option<u64>,
+    /// Open file descriptors its processes may hold between them before it
+    /// is flagged (`--max-open-files`); also its `RLIMIT_NOFILE` when spawned
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_open_files: Option<u64>,
+    /// Threads its processes may run between them before it is flagged
+    /// (`--max-threads`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_threads: Option<u64>,
+    /// Kernel limits it was started under (`spawn --rlimit`), kept as a
+    /// record of what it ran with
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub rlimits: BTreeMap<Rlimit, u64>,
+    /// How dependent tasks tell this one is ready (`--ready`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub readiness: Option<ReadinessProbe>,
+    /// When the readiness probe first passed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ready_at: Option<DateTime<Utc>>,
+    /// Whether the daemon starts the task again once it has finished
+    #[serde(default, skip_serializing_if = "RestartPolicy::is_no")]
+    pub restart: RestartPolicy,
+    /// How many times it has been started again
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub restarts: u32,
+
+    #[serde(default)]
+    pub constitution_rules: Vec<String>,
+
+    /// Opaque JSON stashed by whoever registered the task (correlation IDs
+    /// of an external orchestrator, ...); stored and exported unchanged
+    #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
+    pub metadata: serde_json::Value,
+}
+
+impl TaskInfo {
+    /// A native task running `command`, started now, with nothing else
+    /// recorded; callers fill in the rest with struct update syntax
+    pub fn new(command: TaskCommand) -> Self {
+        Self {
+            mode: ExecutionMode::Native,
+            command,
+            description: None,
+            expected_outcome: None,
+            status: TaskStatus::Running,
+            started_at: Utc::now(),
+            completed_at: None,
+            kill_reason: None,
+            failure_reason: None,
+            triage_hint: None,
+            coredump: None,
+            failure_excerpt: None,
+            result: None,
+            cwd: None,
+            repo_root: None,
+            owner: None,
+            origin: None,
+            labels: BTreeMap::new(),
+            group: None,
+            jobs: None,
+            cost: None,
+            cpu_seconds: None,
+            reservation: None,
+            native: None,
+            docker: None,
+            port: None,
+            done_file: None,
+            log_file: None,
+            depends_on: Vec::new(),
+            timeout_secs: None,
+            max_memory_mb: None,
+            max_cpu_percent: None,
+            max_processes: None,
+            max_open_files: None,
+            max_threads: None,
+            rlimits: BTreeMap::new(),
+            readiness: None,
+            ready_at: None,
+            restart: RestartPolicy::default(),
+            restarts: 0,
+            constitution_rules: Vec::new(),
+            metadata: serde_json::Value::Null,
+        }
+    }
+
+    /// Whether `user` owns the task (tasks without a recorded owner belong to everyone)
+    pub fn is_owned_by(&self, user: Option<&str>) -> bool {
+        match (&self.owner, user) {
+            (Some(owner), Some(user)) => owner == user,
+            _ => true,
+        }
+    }
+
+    /// Whether the task is still running past its `--timeout`
+    pub fn is_past_timeout(&self, now: DateTime<Utc>) -> bool {
+        self.timeout_secs.is_some_and(|secs| {
+            self.status == TaskStatus::Running
+                && crate::clock::secs_between(self.started_at, now) >= secs as f64
+        })
+    }
+
+    /// Whether tasks that depend on this one may start: its readiness probe
+    /// has passed or, without a probe, it has completed
+    pub fn is_ready(&self) -> bool {
+        match self.readiness {
+            Some(_) => self.ready_at.is_some(),
+            None => self.status == TaskStatus::Completed,
+        }
+    }
+}
+
+fn is_zero(n: &u32) -> bool {
+    *n == 0
+}
+
+/// Version of the registry file format. New fields are added with serde
+/// defaults and don't change it; it is bumped only when older binaries
+/// could no longer read a registry correctly.
+pub const REGISTRY_SCHEMA_VERSION: u32 = 1;
+
+/// Process registry (root structure)
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ProcessRegistry {
+    pub tasks: HashMap<String, TaskInfo>,
+
+    /// Kernel boot ID when the registry was last loaded, to detect reboots
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub boot_id: Option<String>,
+
+    /// Processes watched with `watch-pid`, by name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub watched: BTreeMap<String, WatchedProcess>,
+}
+
+/// A task with its ID, as JSON output and the API return single tasks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskEntry {
+    pub task_id: String,
+    #[serde(flatten)]
+    pub task: TaskInfo,
+    /// Whether its process or container is running, where that was checked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alive: Option<bool>,
+}
+
+/// A process that is only watched, not run as a task: the daemon reports
+/// its resources and its death, and nothing else
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WatchedProcess {
+    pub pid: i32,
+    /// Process start time, so a recycled PID isn't mistaken for it
+    pub start_time: String,
+    /// When watching started
+    pub since: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+}
+
+impl ProcessRegistry {
+    pub fn new() -> Self {
+        Self {
+            tasks: HashMap::new(),
+            boot_id: None,
+            watched: BTreeMap::new(),
+        }
+    }
+
+    /// Record the current boot ID. If the machine rebooted since the last
+    /// one, every unfinished native task is dead (its PID may even belong
+    /// to something else by now), so those are failed without looking at
+    /// PIDs. Returns the failed task IDs.
+    pub fn record_boot(&mut self, boot_id: &str) -> Vec<String> {
+        let rebooted = self.boot_id.as_deref().is_some_and(|id| id != boot_id);
+        self.boot_id = Some(boot_id.to_string());
+        if !rebooted {
+            return Vec::new();
+        }
+
+        let mut failed = Vec::new();
+        for (task_id, task) in &mut self.tasks {
+            if task.mode == ExecutionMode::Native
+                && matches!(
+                    task.status,
+                    TaskStatus::Starting | TaskStatus::Running | TaskStatus::Unknown
+                )
+                && task.transition_to(TaskStatus::Failed).is_ok()
+            {
+                task.failure_reason = Some(FailureReason::SystemRebooted);
+                failed.push(task_id.clone());
+            }
+        }
+        failed.sort();
+        failed
+    }
+
+    /// Where a task stands with the tasks it depends on. Dependencies that
+    /// aren't in the registry any more were archived long after finishing
+    /// and don't hold anything up (their dependents were started or failed
+    /// back then).
+    pub fn dependencies_of(&self, depends_on: &[String]) -> Dependencies {
+        for dep_id in depends_on {
+            let Some(dep) = self.tasks.get(dep_id) else {
+                continue;
+            };
+            if dep.is_ready() {
+                continue;
+            }
+            if dep.status.is_terminal() {
+                return Dependencies::Failed(dep_id.clone());
+            }
+            return Dependencies::Waiting(dep_id.clone());
+        }
+        Dependencies::Ready
+    }
+
+    /// Every task `task_id` waits for, directly or through other tasks,
+    /// each listed after the tasks it waits for itself. Unknown tasks are
+    /// left out and a dependency cycle is followed only once.
+    pub fn dependency_closure(&self, task_id: &str) -> Vec<String> {
+        fn visit(
+            registry: &ProcessRegistry,
+            task_id: &str,
+            seen: &mut HashSet<String>,
+            order: &mut Vec<String>,
+        ) {
+            let Some(task) = registry.tasks.get(task_id) else {
+                return;
+            };
+            for dep_id in &task.depends_on {
+                if registry.tasks.contains_key(dep_id) && seen.insert(dep_id.clone()) {
+                    visit(registry, dep_id, seen, order);
+                    order.push(dep_id.clone());
+                }
+            }
+        }
+
+        let mut seen = HashSet::from([task_id.to_string()]);
+        let mut order = Vec::new();
+        visit(self, task_id, &mut seen, &mut order);
+        order
+    }
+
+    /// Fail queued tasks whose dependencies finished without becoming
+    /// ready. Returns the failed task IDs with the dependency to blame.
+    pub fn fail_blocked(&mut self) -> Vec<(String, String)> {
+        let blocked: Vec<(String, String)> = self
+            .tasks
+            .iter()
+            .filter(|(_, task)| task.status == TaskStatus::Queued)
+            .filter_map(
+                |(task_id, task)| match self.dependencies_of(&task.depends_on) {
+                    Dependencies::Failed(dep_id) => Some((task_id.clone(), dep_id)),
+                    _ => None,
+                },
+            )
+            .collect();
+
+        let mut failed = Vec::new();
+        for (task_id, dep_id) in blocked {
+            if let Some(task) = self.tasks.get_mut(&task_id) {
+                if task.transition_to(TaskStatus::Failed).is_ok() {
+                    task.failure_reason = Some(FailureReason::DependencyFailed);
+                    task.triage_hint = Some(format!("dependency {dep_id} never became ready"));
+                    failed.push((task_id, dep_id));
+                }
+            }
+        }
+        failed.sort();
+        failed
+    }
+
+    pub fn add_task(&mut self, task_id: String, task: TaskInfo) {
+        self.tasks.insert(task_id, task);
+    }
+
+    pub fn get_task(&self, task_id: &str) -> Option<&TaskInfo> {
+        self.tasks.get(task_id)
+    }
+
+    pub fn get_task_mut(&mut self, task_id: &str) -> Option<&mut TaskInfo> {
+        self.tasks.get_mut(task_id)
+    }
+
+    pub fn remove_task(&mut self, task_id: &str) -> Option<TaskInfo> {
+        self.tasks.remove(task_id)
+    }
+
+    pub fn running_tasks(&self) -> Vec<(&String, &TaskInfo)> {
+        self.tasks
+            .iter()
+            .filter(|(_, task)| task.status == TaskStatus::Running)
+            .collect()
+    }
+
+    /// Tasks that have not finished (queued, starting, running or unknown)
+    pub fn active_tasks(&self) -> Vec<(&String, &TaskInfo)> {
+        self.tasks
+            .iter()
+            .filter(|(_, task)| !task.status.is_terminal())
+            .collect()
+    }
+
+    pub fn completed_tasks(&self) -> Vec<(&String, &TaskInfo)> {
+        self.tasks
+            .iter()
+            .filter(|(_, task)| task.status == TaskStatus::Completed)
+            .collect()
+    }
+
+    /// Resolve a user-typed task ID: exact match first, then unique prefix,
+    /// then case-insensitive substring, then fuzzy in-order character match
+    /// (so `w17r3` finds `SWARM-2024-11-07-worker-17-retry-3`). At each step
+    /// more than one match is `Ambiguous`.
+    pub fn match_task_id(&self, query: &str) -> TaskIdMatch {
+        if self.tasks.contains_key(query) {
+            return TaskIdMatch::Found(query.to_string());
+        }
+        if let Some(selector) = query.strip_prefix('@') {
+            return self.match_selector(selector);
+        }
+
+        let query_lower = query.to_lowercase();
+        let strategies: [&dyn Fn(&str) -> bool; 2] = [&|id| id.starts_with(query), &|id| {
+            id.to_lowercase().contains(&query_lower)
+        }];
+        for matches in strategies {
+            let candidates = self.tasks.keys().filter(|id| matches(id)).cloned();
+            if let Some(result) = TaskIdMatch::from_candidates(candidates.collect()) {
+                return result;
+            }
+        }
+
+        // Fuzzy: every ID containing the query's characters in order. More
+        // than one is ambiguous, however much tighter one of them matches.
+        let candidates = self
+            .tasks
+            .keys()
+            .filter(|id| is_subsequence(&query_lower, &id.to_lowercase()))
+            .cloned()
+            .collect();
+        TaskIdMatch::from_candidates(candidates).unwrap_or(TaskIdMatch::NotFound)
+    }
+
+    /// Resolve a pseudo-ID (without the leading `@`):
+    /// - `last`: most recently started task
+    /// - `newest-running`: most recently started running task
+    /// - `last-<status>`: task that most recently reached `<status>` (e.g. `last-failed`)
+    fn match_selector(&self, selector: &str) -> TaskIdMatch {
+        let newest = |status: Option<TaskStatus>, finished: bool| {
+            self.tasks
+                .iter()
+                .filter(|(_, task)| status.as_ref().is_none_or(|s| &task.status == s))
+                .max_by_key(|(id, task)| {
+                    let at = if finished {
+                        task.completed_at.unwrap_or(task.started_at)
+                    } else {
+                        task.started_at
+                    };
+                    (at, *id)
+                })
+                .map(|(id, _)| TaskIdMatch::Found(id.clone()))
+                .unwrap_or(TaskIdMatch::NotFound)
+        };
+
+        match selector {
+            "last" => newest(None, false),
+            "newest-running" => newest(Some(TaskStatus::Running), false),
+            _ => match selector.strip_prefix("last-").map(str::parse::<TaskStatus>) {
+                Some(Ok(status)) => newest(Some(status), true),
+                _ => TaskIdMatch::UnknownSelector(format!("@{selector}")),
+            },
+        }
+    }
+}
+
+/// Outcome of resolving a (possibly partial) task ID
+#[derive(Debug, PartialEq)]
+pub enum TaskIdMatch {
+    Found(String),
+    Ambiguous(Vec<String>),
+    NotFound,
+    UnknownSelector(String),
+}
+
+impl TaskIdMatch {
+    /// `None` when there are no candidates, so the next strategy can run
+    fn from_candidates(mut candidates: Vec<String>) -> Option<Self> {
+        match candidates.len() {
+            0 => None,
+            1 => Some(TaskIdMatch::Found(candidates.remove(0))),
+            _ => {
+                candidates.sort();
+                Some(TaskIdMatch::Ambiguous(candidates))
+            }
+        }
+    }
+}
+
+/// Whether the characters of `needle` appear in order inside `haystack`
+fn is_subsequence(needle: &str, haystack: &str) -> bool {
+    let mut haystack = haystack.chars();
+    needle.chars().all(|c| haystack.any(|h| h == c))
+}
+
+/// Orphaned task detection results
+#[derive(Debug, Default)]
+pub struct OrphanReport {
+    pub dead_processes: Vec<String>,   // Process died, task not complete
+    pub zombie_processes: Vec<String>, // Task complete, process still running
+    pub unknown: Vec<String>,          // Active task without process metadata
+}
+
+impl OrphanReport {
+    pub fn has_issues(&self) -> bool {
+        self.total_issues() > 0
+    }
+
+    pub fn total_issues(&self) -> usize {
+        self.dead_processes.len() + self.zombie_processes.len() + self.unknown.len()
+    }
+
+    /// Keep only the task IDs for which `keep` returns true
+    pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        self.dead_processes.retain(|id| keep(id));
+        self.zombie_processes.retain(|id| keep(id));
+        self.unknown.retain(|id| keep(id));
+    }
+}
+
+/// Resource usage snapshot
+#[derive(Debug, Clone)]
+pub struct ResourceUsage {
+    pub cpu_percent: f32,
+    pub memory_kb: u64,
+    pub io: IoStats,
+}
+
+/// Cumulative disk I/O of a process or container
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct IoStats {
+    pub read_bytes: u64,
+    pub written_bytes: u64,
+}
+
+impl std::fmt::Display for IoStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "R {} / W {}",
+            crate::units::format_bytes(self.read_bytes),
+            crate::units::format_bytes(self.written_bytes)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn registry_with(ids: &[&str]) -> ProcessRegistry {
+        let mut registry = ProcessRegistry::new();
+        for id in ids {
+            registry.add_task(id.to_string(), TaskInfo::new(TaskCommand::shell("true")));
+        }
+        registry
+    }
+
+    #[test]
+    fn test_task_command_legacy_string() {
+        let legacy: TaskCommand = serde_json::from_str(r#""npm run build && npm test""#).unwrap();
+        assert_eq!(legacy, TaskCommand::shell("npm run build && npm test"));
+        assert_eq!(
+            legacy.exec_argv(),
+            ["sh", "-c", "npm run build && npm test"]
+        );
+
+        let argv = TaskCommand::argv(["echo", "hello world", "it's"]);
+        let json = serde_json::to_string(&argv).unwrap();
+        assert_eq!(json, r#"{"argv":["echo","hello world","it's"]}"#);
+        assert_eq!(serde_json::from_str::<TaskCommand>(&json).unwrap(), argv);
+        assert_eq!(argv.to_string(), r#"echo 'hello world' 'it'\''s'"#);
+    }
+
+    #[test]
+    fn test_metadata_round_trips_unchanged() {
+        let mut registry = registry_with(&["T001", "T002"]);
+        let metadata = serde_json::json!({ "job": "ci-4411", "attempt": 2, "tags": ["a", null] });
+        registry.get_task_mut("T001").unwrap().metadata = metadata.clone();
+
+        let json = serde_json::to_string(&registry).unwrap();
+        let loaded: ProcessRegistry = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.tasks["T001"].metadata, metadata);
+        // Absent rather than `null` when unset, so older readers see no change
+        assert!(loaded.tasks["T002"].metadata.is_null());
+        assert_eq!(json.matches("\"metadata\"").count(), 1);
+    }
+
+    #[test]
+    fn test_task_command_parse() {
+        assert_eq!(
+            TaskCommand::parse(r#"python -c "print('hi')" --flag"#, false).unwrap(),
+            TaskCommand::argv(["python", "-c", "print('hi')", "--flag"])
+        );
+        // Quoted operators are ordinary arguments
+        assert_eq!(
+            TaskCommand::parse("echo 'a && b'", false).unwrap(),
+            TaskCommand::argv(["echo", "a && b"])
+        );
+        assert!(TaskCommand::parse("make && make test", false).is_err());
+        assert!(TaskCommand::parse("run.sh >out.log", false).is_err());
+        assert!(TaskCommand::parse("echo 'unterminated", false).is_err());
+        assert_eq!(
+            TaskCommand::parse("make && make test", true).unwrap(),
+            TaskCommand::shell("make && make test")
+        );
+    }
+
+    #[test]
+    fn test_match_task_id() {
+        let registry = registry_with(&[
+            "SWARM-2024-11-07-worker-17-retry-3",
+            "SWARM-2024-11-07-worker-18",
+            "T001",
+            "T0011",
+        ]);
+
+        // Exact match wins even when it is also a prefix of another ID
+        assert_eq!(
+            registry.match_task_id("T001"),
+            TaskIdMatch::Found("T001".to_string())
+        );
+        assert_eq!(
+            registry.match_task_id("SWARM-2024-11-07-worker-17"),
+            TaskIdMatch::Found("SWARM-2024-11-07-worker-17-retry-3".to_string())
+        );
+        assert_eq!(
+            registry.match_task_id("retry"),
+            TaskIdMatch::Found("SWARM-2024-11-07-worker-17-retry-3".to_string())
+        );
+        assert_eq!(
+            registry.match_task_id("w17r3"),
+            TaskIdMatch::Found("SWARM-2024-11-07-worker-17-retry-3".to_string())
+        );
+        // Both workers contain w, 1, 7 in order; the closer match does not win
+        assert_eq!(
+            registry.match_task_id("w17"),
+            TaskIdMatch::Ambiguous(vec![
+                "SWARM-2024-11-07-worker-17-retry-3".to_string(),
+                "SWARM-2024-11-07-worker-18".to_string(),
+            ])
+        );
+        assert_eq!(
+            registry.match_task_id("SWARM"),
+            TaskIdMatch::Ambiguous(vec![
+                "SWARM-2024-11-07-worker-17-retry-3".to_string(),
+                "SWARM-2024-11-07-worker-18".to_string(),
+            ])
+        );
+        assert_eq!(registry.match_task_id("nope"), TaskIdMatch::NotFound);
+    }
+
+    #[test]
+    fn test_match_selectors() {
+        let mut registry = registry_with(&["OLD", "NEW", "DIED"]);
+        let now = Utc::now();
+        registry.tasks.get_mut("OLD").unwrap().started_at = now - chrono::Duration::hours(2);
+        registry.tasks.get_mut("DIED").unwrap().started_at = now - chrono::Duration::hours(1);
+        let died = registry.tasks.get_mut("DIED").unwrap();
+        died.status = TaskStatus::Failed;
+        died.completed_at = Some(now);
+
+        let found = |id: &str| TaskIdMatch::Found(id.to_string());
+        assert_eq!(registry.match_task_id("@last"), found("NEW"));
+        assert_eq!(registry.match_task_id("@newest-running"), found("NEW"));
+        assert_eq!(registry.match_task_id("@last-failed"), found("DIED"));
+        assert_eq!(
+            registry.match_task_id("@last-completed"),
+            TaskIdMatch::NotFound
+        );
+        assert_eq!(
+            registry.match_task_id("@bogus"),
+            TaskIdMatch::UnknownSelector("@bogus".to_string())
+        );
+    }
+
+    #[test]
+    fn test_reboot_fails_running_native_tasks() {
+        let mut registry = registry_with(&["RUN", "DONE"]);
+        registry
+            .get_task_mut("DONE")
+            .unwrap()
+            .transition_to(TaskStatus::Completed)
+            .unwrap();
+
+        // First boot seen: nothing to compare against
+        assert!(registry.record_boot("boot-a").is_empty());
+        assert!(registry.record_boot("boot-a").is_empty());
+        assert_eq!(registry.tasks["RUN"].status, TaskStatus::Running);
+
+        assert_eq!(registry.record_boot("boot-b"), vec!["RUN".to_string()]);
+        let run = &registry.tasks["RUN"];
+        assert_eq!(run.status, TaskStatus::Failed);
+        assert_eq!(run.failure_reason, Some(FailureReason::SystemRebooted));
+        assert_eq!(registry.tasks["DONE"].status, TaskStatus::Completed);
+        assert_eq!(registry.boot_id.as_deref(), Some("boot-b"));
+    }
+
+    #[test]
+    fn test_parse_rlimit_setting() {
+        assert_eq!(
+            Rlimit::parse_setting("nofile=4096").unwrap(),
+            (Rlimit::Nofile, 4096)
+        );
+        assert_eq!(
+            Rlimit::parse_setting("fsize=1g").unwrap(),
+            (Rlimit::Fsize, 1 << 30)
+        );
+        assert_eq!(
+            Rlimit::parse_setting("CPU=10m").unwrap(),
+            (Rlimit::Cpu, 600)
+        );
+        assert!(Rlimit::parse_setting("nofile").is_err());
+        assert!(Rlimit::parse_setting("nproc=lots").is_err());
+        assert!(Rlimit::parse_setting("rss=1g").is_err());
+        assert_eq!(Rlimit::Fsize.format_value(1 << 30), "1g");
+        assert_eq!(Rlimit::Stack.format_value(8 << 20), "8m");
+        assert_eq!(Rlimit::Nofile.format_value(4096), "4096");
+    }
+
+    #[test]
+    fn test_dependency_closure() {
+        let mut registry = registry_with(&["DEPLOY", "TEST", "BUILD", "LINT"]);
+        let mut after = |id: &str, deps: &[&str]| {
+            registry.get_task_mut(id).unwrap().depends_on =
+                deps.iter().map(|dep| dep.to_string()).collect();
+        };
+        after("DEPLOY", &["TEST", "LINT", "ARCHIVED"]);
+        after("TEST", &["BUILD"]);
+        after("LINT", &["BUILD"]);
+        // A cycle back to the start doesn't loop
+        after("BUILD", &["DEPLOY"]);
+
+        assert_eq!(
+            registry.dependency_closure("DEPLOY"),
+            vec!["BUILD", "TEST", "LINT"]
+        );
+        assert_eq!(
+            registry.dependency_closure("TEST"),
+            vec!["LINT", "DEPLOY", "BUILD"]
+        );
+        assert!(registry.dependency_closure("MISSING").is_empty());
+    }
+}
@@ -0,0 +1,687 @@
+//! TypeScript definitions for the registry, daemon events, `version --json`
+//! and the `--output json` documents.
+//!
+//! The definitions are written by hand in `bindings/task-watchdog.d.ts` (no
+//! generator handles `TaskCommand`'s legacy string form or serde's
+//! `skip_serializing_if` as optional fields) and embedded here so the CLI
+//! can print them. The tests below parse the definitions and check the JSON
+//! the crate emits against them: every field must be declared with a type
+//! its value has, and every required field must be present.
+
+/// Contents of `task-watchdog.d.ts`
+pub const DEFINITIONS: &str = include_str!("../bindings/task-watchdog.d.ts");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::WatchdogEvent;
+    use crate::report::{Rehydration, Report};
+    use crate::timeline::{Actor, Change, EventRecord, JournalLine, Timeline, Transition};
+    use crate::types::*;
+    use chrono::Utc;
+    use serde::de::DeserializeOwned;
+    use serde_json::Value;
+    use std::cell::RefCell;
+    use std::collections::{BTreeMap, BTreeSet};
+
+    /// A type expression, as far as `task-watchdog.d.ts` uses them
+    #[derive(Debug, Clone)]
+    enum Ty {
+        /// `string`, `number`, `boolean` or `unknown`
+        Prim(String),
+        Lit(String),
+        Array(Box<Ty>),
+        /// `Record<string, T>`
+        Record(Box<Ty>),
+        Union(Vec<Ty>),
+        Intersection(Vec<Ty>),
+        Object(Vec<Field>),
+        Ref(String),
+    }
+
+    #[derive(Debug, Clone)]
+    struct Field {
+        name: String,
+        optional: bool,
+        ty: Ty,
+    }
+
+    /// Fields of one alternative of an object type: optional flag and type
+    type Shape = BTreeMap<String, (bool, Ty)>;
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum Token {
+        Name(String),
+        Str(String),
+        Punct(char),
+    }
+
+    fn tokenize(source: &str) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        let mut chars = source.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '/' if chars.peek() == Some(&'/') => {
+                    chars.by_ref().find(|&c| c == '\n');
+                }
+                '/' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    let mut star = false;
+                    for c in chars.by_ref() {
+                        if star && c == '/' {
+                            break;
+                        }
+                        star = c == '*';
+                    }
+                }
+                '"' => tokens.push(Token::Str(
+                    chars.by_ref().take_while(|&c| c != '"').collect(),
+                )),
+                c if c.is_alphanumeric() || c == '_' => {
+                    let mut name = c.to_string();
+                    while let Some(&c) = chars.peek().filter(|c| c.is_alphanumeric() || **c == '_')
+                    {
+                        name.push(c);
+                        chars.next();
+                    }
+                    tokens.push(Token::Name(name));
+                }
+                c if c.is_whitespace() => {}
+                c => tokens.push(Token::Punct(c)),
+            }
+        }
+        tokens
+    }
+
+    struct Parser {
+        tokens: Vec<Token>,
+        pos: usize,
+    }
+
+    impl Parser {
+        fn next(&mut self) -> Token {
+            self.pos += 1;
+            self.tokens[self.pos - 1].clone()
+        }
+
+        fn eat(&mut self, token: Token) -> bool {
+            let found = self.tokens.get(self.pos) == Some(&token);
+            self.pos += found as usize;
+            found
+        }
+
+        fn expect(&mut self, c: char) {
+            let found = self.tokens.get(self.pos).cloned();
+            assert!(self.eat(Token::Punct(c)), "expected '{c}', found {found:?}");
+        }
+
+        fn name(&mut self) -> String {
+            match self.next() {
+                Token::Name(name) => name,
+                token => panic!("expected a name, found {token:?}"),
+            }
+        }
+
+        fn union(&mut self) -> Ty {
+            self.eat(Token::Punct('|'));
+            let mut branches = vec![self.intersection()];
+            while self.eat(Token::Punct('|')) {
+                branches.push(self.intersection());
+            }
+            if branches.len() == 1 {
+                branches.remove(0)
+            } else {
+                Ty::Union(branches)
+            }
+        }
+
+        fn intersection(&mut self) -> Ty {
+            let mut parts = vec![self.array()];
+            while self.eat(Token::Punct('&')) {
+                parts.push(self.array());
+            }
+            if parts.len() == 1 {
+                parts.remove(0)
+            } else {
+                Ty::Intersection(parts)
+            }
+        }
+
+        fn array(&mut self) -> Ty {
+            let mut ty = self.primary();
+            while self.eat(Token::Punct('[')) {
+                self.expect(']');
+                ty = Ty::Array(Box::new(ty));
+            }
+            ty
+        }
+
+        fn primary(&mut self) -> Ty {
+            match self.next() {
+                Token::Punct('{') => Ty::Object(self.fields()),
+                Token::Str(literal) => Ty::Lit(literal),
+                Token::Name(name) if name == "Record" => {
+                    self.expect('<');
+                    self.union();
+                    self.expect(',');
+                    let value = self.union();
+                    self.expect('>');
+                    Ty::Record(Box::new(value))
+                }
+                Token::Name(name) => match name.as_str() {
+                    "string" | "number" | "boolean" | "unknown" => Ty::Prim(name),
+                    _ => Ty::Ref(name),
+                },
+                token => panic!("expected a type, found {token:?}"),
+            }
+        }
+
+        /// Fields up to the closing brace of an object type
+        fn fields(&mut self) -> Vec<Field> {
+            let mut fields = Vec::new();
+            while !self.eat(Token::Punct('}')) {
+                let name = self.name();
+                let optional = self.eat(Token::Punct('?'));
+                self.expect(':');
+                let ty = self.union();
+                self.eat(Token::Punct(';'));
+                fields.push(Field { name, optional, ty });
+            }
+            fields
+        }
+    }
+
+    /// The exported types of the definitions, and which ones a check used
+    struct Definitions {
+        types: BTreeMap<String, Ty>,
+        used: RefCell<BTreeSet<String>>,
+    }
+
+    impl Definitions {
+        fn parse(source: &str) -> Self {
+            let mut parser = Parser {
+                tokens: tokenize(source),
+                pos: 0,
+            };
+            let mut types = BTreeMap::new();
+            while parser.pos < parser.tokens.len() {
+                assert_eq!(parser.name(), "export");
+                let kind = parser.name();
+                let name = parser.name();
+                let ty = match kind.as_str() {
+                    "type" => {
+                        parser.expect('=');
+                        let ty = parser.union();
+                        parser.expect(';');
+                        ty
+                    }
+                    "interface" => {
+                        let base = parser
+                            .eat(Token::Name("extends".into()))
+                            .then(|| parser.name());
+                        parser.expect('{');
+                        let object = Ty::Object(parser.fields());
+                        match base {
+                            Some(base) => Ty::Intersection(vec![Ty::Ref(base), object]),
+                            None => object,
+                        }
+                    }
+                    _ => panic!("unexpected `export {kind}`"),
+                };
+                types.insert(name, ty);
+            }
+            Self {
+                types,
+                used: RefCell::default(),
+            }
+        }
+
+        /// Panic unless `value` is a valid `name`
+        fn check(&self, value: &impl serde::Serialize, name: &str) {
+            let value = serde_json::to_value(value).unwrap();
+            if let Err(e) = self.matches(&value, &Ty::Ref(name.into()), name) {
+                panic!("task-watchdog.d.ts does not match the JSON:\n{e}");
+            }
+        }
+
+        fn resolve(&self, name: &str) -> Result<&Ty, String> {
+            self.used.borrow_mut().insert(name.to_string());
+            self.types
+                .get(name)
+                .ok_or_else(|| format!("{name} is not defined"))
+        }
+
+        fn matches(&self, value: &Value, ty: &Ty, path: &str) -> Result<(), String> {
+            let mismatch = || Err(format!("{path}: {value} is not {}", describe(ty)));
+            match ty {
+                Ty::Prim(prim) => match (prim.as_str(), value) {
+                    ("string", Value::String(_))
+                    | ("number", Value::Number(_))
+                    | ("boolean", Value::Bool(_))
+                    | ("unknown", _) => Ok(()),
+                    _ => mismatch(),
+                },
+                Ty::Lit(literal) if value.as_str() == Some(literal) => Ok(()),
+                Ty::Lit(_) => mismatch(),
+                Ty::Array(item) => match value {
+                    Value::Array(items) => items.iter().enumerate().try_for_each(|(i, value)| {
+                        self.matches(value, item, &format!("{path}[{i}]"))
+                    }),
+                    _ => mismatch(),
+                },
+                Ty::Record(item) => match value {
+                    Value::Object(map) => map.iter().try_for_each(|(key, value)| {
+                        self.matches(value, item, &format!("{path}.{key}"))
+                    }),
+                    _ => mismatch(),
+                },
+                Ty::Ref(name) => self.matches(value, self.resolve(name)?, path),
+                Ty::Union(branches) => first_match(
+                    branches
+                        .iter()
+                        .map(|branch| self.matches(value, branch, path)),
+                ),
+                Ty::Intersection(_) | Ty::Object(_) => match value {
+                    Value::Object(map) => first_match(
+                        self.shapes(ty)
+                            .iter()
+                            .map(|shape| self.matches_shape(map, shape, path)),
+                    ),
+                    _ => mismatch(),
+                },
+            }
+        }
+
+        /// Every key must be declared with a matching type, every required
+        /// field present
+        fn matches_shape(
+            &self,
+            map: &serde_json::Map<String, Value>,
+            shape: &Shape,
+            path: &str,
+        ) -> Result<(), String> {
+            for (key, value) in map {
+                let Some((_, ty)) = shape.get(key) else {
+                    return Err(format!("{path}.{key} is not declared"));
+                };
+                self.matches(value, ty, &format!("{path}.{key}"))?;
+            }
+            match shape
+                .iter()
+                .find(|(key, (optional, _))| !optional && !map.contains_key(*key))
+            {
+                Some((key, _)) => Err(format!("{path}.{key} is required but missing")),
+                None => Ok(()),
+            }
+        }
+
+        /// The alternative field sets of an object type, with unions
+        /// distributed over intersections
+        fn shapes(&self, ty: &Ty) -> Vec<Shape> {
+            match ty {
+                Ty::Object(fields) => vec![fields
+                    .iter()
+                    .map(|f| (f.name.clone(), (f.optional, f.ty.clone())))
+                    .collect()],
+                Ty::Ref(name) => self
+                    .resolve(name)
+                    .map(|ty| self.shapes(ty))
+                    .unwrap_or_default(),
+                Ty::Union(branches) => branches.iter().flat_map(|b| self.shapes(b)).collect(),
+                Ty::Intersection(parts) => parts.iter().fold(vec![Shape::new()], |shapes, part| {
+                    let part = self.shapes(part);
+                    shapes
+                        .iter()
+                        .flat_map(|a| {
+                            part.iter()
+                                .map(|b| a.clone().into_iter().chain(b.clone()).collect())
+                        })
+                        .collect()
+                }),
+                _ => Vec::new(),
+            }
+        }
+
+        /// The string literals of the union `name`
+        fn literals(&self, name: &str) -> Vec<Value> {
+            match &self.types[name] {
+                Ty::Union(branches) => branches
+                    .iter()
+                    .filter_map(|branch| match branch {
+                        Ty::Lit(literal) => Some(Value::String(literal.clone())),
+                        _ => None,
+                    })
+                    .collect(),
+                ty => panic!("{name} is not a union: {ty:?}"),
+            }
+        }
+    }
+
+    fn describe(ty: &Ty) -> String {
+        match ty {
+            Ty::Prim(name) | Ty::Ref(name) => name.clone(),
+            Ty::Lit(literal) => format!("\"{literal}\""),
+            Ty::Array(item) => format!("{}[]", describe(item)),
+            Ty::Record(item) => format!("Record<string, {}>", describe(item)),
+            _ => "an object".to_string(),
+        }
+    }
+
+    /// Ok if any alternative matched, else why each did not
+    fn first_match(results: impl Iterator<Item = Result<(), String>>) -> Result<(), String> {
+        let mut errors = Vec::new();
+        for result in results {
+            match result {
+                Ok(()) => return Ok(()),
+                Err(e) => errors.push(e),
+            }
+        }
+        Err(errors.join("\n  or "))
+    }
+
+    #[test]
+    fn test_definitions_match_every_document() {
+        let limits = ResourceLimits::default();
+        let task = TaskInfo {
+            mode: ExecutionMode::Docker,
+            command: TaskCommand::shell("pytest"),
+            description: Some("tests".into()),
+            expected_outcome: Some("green".into()),
+            status: TaskStatus::Failed,
+            started_at: Utc::now(),
+            completed_at: Some(Utc::now()),
+            kill_reason: Some("oom".into()),
+            failure_reason: Some(FailureReason::SystemRebooted),
+            triage_hint: Some("out of memory".to_string()),
+            result: Some(TaskResult {
+                exit_code: Some(0),
+                summary: Some("12 tests passed".into()),
+                artifacts: vec!["dist/report.html".into()],
+            }),
+            cwd: Some("/src".into()),
+            repo_root: Some("/src".into()),
+            owner: Some("alice".into()),
+            origin: Some(TaskOrigin {
+                tool: "cli".into(),
+                user: Some("alice".into()),
+                host: Some("box".into()),
+                tmux: Some(TmuxPane {
+                    pane_id: "%3".into(),
+                    session: "work".into(),
+                    window: 1,
+                    pane: 2,
+                    socket: Some("/tmp/tmux-1000/default".into()),
+                }),
+            }),
+            labels: BTreeMap::from([("session".into(), "s1".into())]),
+            group: Some("swarm-7".into()),
+            jobs: Some(4),
+            cost: Some(TaskCost::default()),
+            cpu_seconds: Some(1.5),
+            reservation: Some(limits.clone()),
+            native: Some(NativeTask {
+                pid: 1,
+                pgid: 1,
+                start_time: "1".into(),
+                env_tag: Some("tag".into()),
+                job: Some("job".into()),
+            }),
+            docker: Some(DockerTask {
+                container_id: "abc".into(),
+                container_name: "dev-task-T001".into(),
+                resource_limits: limits,
+                stop_timeout_secs: Some(60),
+                image: Some("python:3.11-slim".to_string()),
+                auto_remove: true,
+                keep_on_failure: true,
+                exit_code: Some(137),
+            }),
+            port: Some(8787),
+            done_file: Some("/src/.claude/done/T1".into()),
+            log_file: Some("/src/.claude/logs/T1.log".into()),
+            timeout_secs: Some(1800),
+            max_memory_mb: Some(2048),
+            max_cpu_percent: Some(150.0),
+            max_processes: Some(64),
+            max_open_files: Some(4096),
+            max_threads: Some(512),
+            rlimits: BTreeMap::from([(Rlimit::Nofile, 4096), (Rlimit::Fsize, 1 << 30)]),
+            coredump: Some(Coredump {
+                pid: 4242,
+                signal: Some(11),
+                path: Some("/var/lib/systemd/coredump/core.zst".into()),
+                coredumpctl: true,
+                backtrace: vec!["parse_header (buf=0x0) at src/parse.c:88".into()],
+            }),
+            failure_excerpt: Some("Traceback (most recent call last):".into()),
+            depends_on: vec!["T0".into()],
+            readiness: Some(ReadinessProbe::Port(8080)),
+            ready_at: Some(Utc::now()),
+            restart: RestartPolicy::OnFailure,
+            restarts: 2,
+            constitution_rules: vec!["rule".into()],
+            metadata: serde_json::json!({ "run": 42 }),
+        };
+        let mut registry = ProcessRegistry::new();
+        registry.boot_id = Some("boot".into());
+        registry.add_task("T001".into(), task);
+        registry.watched.insert(
+            "server".into(),
+            WatchedProcess {
+                pid: 1,
+                start_time: "1".into(),
+                since: Utc::now(),
+                command: Some("node server.js".into()),
+            },
+        );
+
+        let ts = Definitions::parse(DEFINITIONS);
+        ts.check(&registry, "ProcessRegistry");
+        ts.check(&registry.stats(), "RegistryStats");
+        let (task_id, task) = registry.tasks.iter().next().unwrap();
+        let report = Report {
+            stats: registry.stats(),
+            cost: registry.total_cost(),
+            tasks: BTreeMap::from([(task_id.clone(), task.clone())]),
+            watched: registry.watched.clone(),
+        };
+        ts.check(&report, "Report");
+        let rehydration = Rehydration {
+            active: vec![TaskEntry {
+                task_id: task_id.clone(),
+                task: task.clone(),
+                alive: Some(true),
+            }],
+            stats: registry.stats(),
+        };
+        ts.check(&rehydration, "Rehydration");
+        // Grouped stats are keyed by group, so only one group's fields count
+        let groups = registry.stats_grouped(None, true);
+        let group = groups.values().next().unwrap();
+        ts.check(group, "GroupStats");
+        let lines = [
+            JournalLine::Transition(Transition {
+                at: Utc::now(),
+                task_id: task_id.clone(),
+                change: Change::Failed,
+                from: Some(TaskStatus::Running),
+                to: Some(TaskStatus::Failed),
+                actor: Actor::Watchdog,
+                user: Some("alice".into()),
+                reason: Some("exit code 1".into()),
+            }),
+            JournalLine::Event(EventRecord {
+                at: Utc::now(),
+                event: WatchdogEvent::CheckCompleted {
+                    stats: registry.stats(),
+                },
+            }),
+        ];
+        for line in &lines {
+            ts.check(line, "JournalLine");
+        }
+        let timeline = Timeline::replay(lines, None, None);
+        ts.check(&timeline, "Timeline");
+
+        // Unset options are left out, not written as null
+        let bare: TaskInfo = serde_json::from_value(serde_json::json!({
+            "mode": "native",
+            "command": "true",
+            "status": "running",
+            "started_at": Utc::now(),
+            "constitution_rules": [],
+        }))
+        .unwrap();
+        ts.check(&bare, "TaskInfo");
+
+        // String unions list exactly the variants
+        check_variants(
+            &ts,
+            "TaskStatus",
+            &[
+                TaskStatus::Queued,
+                TaskStatus::Starting,
+                TaskStatus::Running,
+                TaskStatus::Completed,
+                TaskStatus::Failed,
+                TaskStatus::Cancelled,
+                TaskStatus::Killed,
+                TaskStatus::TimedOut,
+                TaskStatus::Unknown,
+            ],
+        );
+        check_variants(
+            &ts,
+            "ExecutionMode",
+            &[ExecutionMode::Native, ExecutionMode::Docker],
+        );
+        check_variants(
+            &ts,
+            "FailureReason",
+            &[
+                FailureReason::SystemRebooted,
+                FailureReason::DependencyFailed,
+            ],
+        );
+        check_variants(
+            &ts,
+            "RestartPolicy",
+            &[
+                RestartPolicy::No,
+                RestartPolicy::OnFailure,
+                RestartPolicy::Always,
+            ],
+        );
+        check_variants(
+            &ts,
+            "Change",
+            &[
+                Change::Registered,
+                Change::Started,
+                Change::Restarted,
+                Change::Completed,
+                Change::Failed,
+                Change::Cancelled,
+                Change::Killed,
+                Change::TimedOut,
+                Change::Lost,
+                Change::CleanedUp,
+            ],
+        );
+
+        // Every type is part of one of the documents above, except the
+        // CLI's own `version --json`
+        let unchecked: Vec<_> = ts
+            .types
+            .keys()
+            .filter(|name| !ts.used.borrow().contains(*name) && *name != "BuildInfo")
+            .collect();
+        assert!(unchecked.is_empty(), "never checked: {unchecked:?}");
+    }
+
+    /// Each variant is in the union `name`, and each literal of the union
+    /// is a variant
+    fn check_variants<T: serde::Serialize + DeserializeOwned>(
+        ts: &Definitions,
+        name: &str,
+        variants: &[T],
+    ) {
+        for variant in variants {
+            ts.check(variant, name);
+        }
+        for literal in ts.literals(name) {
+            assert!(
+                serde_json::from_value::<T>(literal.clone()).is_ok(),
+                "{name} has {literal}, which is not a variant"
+            );
+        }
+    }
+
+    #[test]
+    fn test_definitions_match_every_event() {
+        let task_id = || "T001".to_string();
+        let events = [
+            WatchdogEvent::TaskRegistered { task_id: task_id() },
+            WatchdogEvent::TaskStarted {
+                task_id: task_id(),
+                from_queue: true,
+            },
+            WatchdogEvent::TaskCompleted { task_id: task_id() },
+            WatchdogEvent::TaskDied {
+                task_id: task_id(),
+                exit_code: Some(1),
+                triage_hint: Some("out of memory".to_string()),
+                failure_excerpt: Some("MemoryError".to_string()),
+            },
+            WatchdogEvent::TimedOut {
+                task_id: task_id(),
+                timeout_secs: 1800,
+            },
+            WatchdogEvent::TaskReady { task_id: task_id() },
+            WatchdogEvent::TaskRestarted {
+                task_id: task_id(),
+                restarts: 1,
+            },
+            WatchdogEvent::DependencyFailed {
+                task_id: task_id(),
+                dependency: "T000".into(),
+            },
+            WatchdogEvent::ZombieDetected { task_id: task_id() },
+            WatchdogEvent::TaskUnknown {
+                task_id: task_id(),
+                reason: "Docker unreachable".into(),
+            },
+            WatchdogEvent::ThresholdExceeded {
+                task_id: task_id(),
+                resource: "memory".into(),
+                usage: "812MB".into(),
+                limit: "512MB".into(),
+            },
+            WatchdogEvent::LimitExceeded {
+                task_id: task_id(),
+                resource: "memory".into(),
+                limit: "512m".into(),
+            },
+            WatchdogEvent::DurationAnomaly {
+                task_id: task_id(),
+                running_secs: 600.0,
+                p95_secs: 60.0,
+            },
+            WatchdogEvent::PortClosed {
+                task_id: task_id(),
+                port: 8787,
+            },
+            WatchdogEvent::WatchedProcessExited {
+                name: "server".into(),
+                pid: 1,
+            },
+            WatchdogEvent::CheckCompleted {
+                stats: ProcessRegistry::new().stats(),
+            },
+        ];
+        let ts = Definitions::parse(DEFINITIONS);
+        for event in events {
+            ts.check(&event, "WatchdogEvent");
+        }
+    }
+}
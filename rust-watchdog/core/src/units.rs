@@ -0,0 +1,124 @@
+//! Human-readable units shared by tables, reports and `Display` impls.
+
+/// Human-readable byte count (`512 MiB`, `7.6 GiB`)
+pub fn format_bytes(bytes: u64) -> String {
+    const MIB: f64 = 1024.0 * 1024.0;
+    const GIB: f64 = MIB * 1024.0;
+    let b = bytes as f64;
+    if b >= GIB {
+        format!("{:.1} GiB", b / GIB)
+    } else {
+        format!("{:.0} MiB", b / MIB)
+    }
+}
+
+/// Human-readable duration (`42s`, `12m 05s`, `3h 20m`)
+pub fn format_duration(seconds: f64) -> String {
+    let secs = seconds.max(0.0).round() as u64;
+    match secs {
+        0..=59 => format!("{secs}s"),
+        60..=3599 => format!("{}m {:02}s", secs / 60, secs % 60),
+        _ => format!("{}h {:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+/// How long ago something `seconds_ago` seconds back happened, roughly
+/// (`just now`, `17m ago`, `3h ago`, `2d ago`; `in 5m` if it's ahead)
+pub fn format_relative(seconds_ago: f64) -> String {
+    let secs = seconds_ago.abs().round() as u64;
+    let amount = match secs {
+        0..=4 => return "just now".to_string(),
+        5..=59 => format!("{secs}s"),
+        60..=3599 => format!("{}m", secs / 60),
+        3600..=86_399 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86_400),
+    };
+    if seconds_ago < 0.0 {
+        format!("in {amount}")
+    } else {
+        format!("{amount} ago")
+    }
+}
+
+/// Parse a duration like `90s`, `30m`, `2h`, `7d` or `1w` into seconds.
+/// A bare number is seconds.
+pub fn parse_duration(s: &str) -> Option<f64> {
+    let s = s.trim();
+    let split = s.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: f64 = number.parse().ok().filter(|n: &f64| *n >= 0.0)?;
+    let scale = match unit {
+        "" | "s" => 1.0,
+        "m" => 60.0,
+        "h" => 3600.0,
+        "d" => 86_400.0,
+        "w" => 604_800.0,
+        _ => return None,
+    };
+    Some(number * scale)
+}
+
+/// Parse a size like `512k`, `64m` or `1g` (binary units, case-insensitive,
+/// `b`/`ib` suffix optional) into bytes. A bare number is bytes.
+pub fn parse_bytes(s: &str) -> Option<u64> {
+    let s = s.trim().to_ascii_lowercase();
+    let s = s
+        .strip_suffix("ib")
+        .or_else(|| s.strip_suffix('b'))
+        .unwrap_or(&s);
+    let split = s.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: f64 = number.parse().ok().filter(|n: &f64| *n >= 0.0)?;
+    let scale: u64 = match unit {
+        "" => 1,
+        "k" => 1 << 10,
+        "m" => 1 << 20,
+        "g" => 1 << 30,
+        "t" => 1 << 40,
+        _ => return None,
+    };
+    Some((number * scale as f64).round() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_bytes() {
+        assert_eq!(parse_bytes("4096"), Some(4096));
+        assert_eq!(parse_bytes("512k"), Some(512 * 1024));
+        assert_eq!(parse_bytes("1G"), Some(1 << 30));
+        assert_eq!(parse_bytes("1.5gib"), Some(3 << 29));
+        assert_eq!(parse_bytes("64MB"), Some(64 << 20));
+        assert_eq!(parse_bytes("2x"), None);
+        assert_eq!(parse_bytes("-1m"), None);
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90"), Some(90.0));
+        assert_eq!(parse_duration("30m"), Some(1800.0));
+        assert_eq!(parse_duration("1.5h"), Some(5400.0));
+        assert_eq!(parse_duration("7d"), Some(604_800.0));
+        assert_eq!(parse_duration("2y"), None);
+        assert_eq!(parse_duration("-1h"), None);
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(42.4), "42s");
+        assert_eq!(format_duration(725.0), "12m 05s");
+        assert_eq!(format_duration(12_000.0), "3h 20m");
+        assert_eq!(format_duration(-3.0), "0s");
+    }
+
+    #[test]
+    fn test_format_relative() {
+        assert_eq!(format_relative(2.0), "just now");
+        assert_eq!(format_relative(1_030.0), "17m ago");
+        assert_eq!(format_relative(3.5 * 3600.0), "3h ago");
+        assert_eq!(format_relative(200_000.0), "2d ago");
+        assert_eq!(format_relative(-300.0), "in 5m");
+    }
+}
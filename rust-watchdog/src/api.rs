@@ -0,0 +1,463 @@
+//! Local HTTP/JSON API (`serve`, `run --http-socket`), for hooks and tools
+//! that would otherwise shell out and parse the CLI's output. Built with
+//! `--features http`, on by default.
+//!
+//! | Method | Path                | Does                                         |
+//! |--------|---------------------|----------------------------------------------|
+//! | GET    | `/tasks`            | Tasks by ID (`?where=EXPR`, `?active=true`)  |
+//! | POST   | `/tasks`            | Register a task (`?queue=true`)              |
+//! | GET    | `/tasks/{id}`       | One task (prefix, fuzzy match or @selector)  |
+//! | POST   | `/tasks/{id}/kill`  | Kill one of the caller's tasks               |
+//! | GET    | `/tasks/{id}/logs`  | Its output (`?follow=true`, `?tail=N`)       |
+//! | GET    | `/report`           | Counts, spend and unfinished tasks           |
+//!
+//! Each request loads the registry from disk like a CLI invocation does, so
+//! the API works the same inside the daemon or on its own. It is served on a
+//! Unix socket next to the registry that only the users who may write the
+//! registry can connect to. Tasks registered through it belong to the
+//! connecting process's user and checkout, not the server's, and kills are
+//! checked against them as the CLI checks its own. Errors are
+//! `{"error": "..."}`.
+
+use crate::filter::Filter;
+use crate::info;
+use crate::lifecycle::Actor;
+use crate::registry::RegistryManager;
+use crate::report::Report;
+use crate::tasks::{self, Details, Peer, TaskSpec};
+use crate::types::{TaskEntry, TaskInfo};
+use anyhow::Result;
+use axum::extract::{ConnectInfo, Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+mod log_stream;
+
+/// Socket `serve` listens on by default for the registry at `registry_path`
+pub fn socket_path(registry_path: &std::path::Path) -> PathBuf {
+    registry_path.with_extension("api.sock")
+}
+
+struct ApiState {
+    registry_path: PathBuf,
+}
+
+impl ApiState {
+    fn registry(&self) -> Result<RegistryManager> {
+        let mut registry = RegistryManager::new(&self.registry_path);
+        registry.set_actor(Actor::Api);
+        registry.load()?;
+        Ok(registry)
+    }
+}
+
+/// An error response: `{"error": "..."}` with a status code
+struct ApiError(StatusCode, String);
+
+impl ApiError {
+    fn not_found(task_id: &str) -> Self {
+        Self(StatusCode::NOT_FOUND, format!("Task {task_id} not found"))
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        Self(StatusCode::INTERNAL_SERVER_ERROR, format!("{e:#}"))
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = serde_json::json!({ "error": self.1 });
+        (self.0, Json(body)).into_response()
+    }
+}
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+#[cfg(unix)]
+impl
+    axum::extract::connect_info::Connected<
+        axum::serve::IncomingStream<'_, tokio::net::UnixListener>,
+    > for Peer
+{
+    fn connect_info(stream: axum::serve::IncomingStream<'_, tokio::net::UnixListener>) -> Self {
+        Self::from_cred(stream.io().peer_cred().ok())
+    }
+}
+
+/// Resolve `query` to a task ID, loading history too so finished tasks can
+/// still be looked up
+fn find_task(registry: &mut RegistryManager, query: &str) -> ApiResult<(String, TaskInfo)> {
+    registry.load_history()?;
+    let task_id = registry
+        .resolve_task_id(query)?
+        .unwrap_or_else(|| query.to_string());
+    match registry.get_task(&task_id) {
+        Some(task) => Ok((task_id.clone(), task.clone())),
+        None => Err(ApiError::not_found(&task_id)),
+    }
+}
+
+#[derive(Deserialize)]
+struct ListQuery {
+    #[serde(rename = "where")]
+    filter: Option<String>,
+    #[serde(default)]
+    active: bool,
+}
+
+async fn list_tasks(
+    State(state): State<Arc<ApiState>>,
+    Query(query): Query<ListQuery>,
+) -> ApiResult<Json<BTreeMap<String, TaskInfo>>> {
+    let filter: Option<Filter> = query
+        .filter
+        .as_deref()
+        .map(str::parse)
+        .transpose()
+        .map_err(|e: anyhow::Error| ApiError(StatusCode::BAD_REQUEST, format!("{e:#}")))?;
+    let registry = state.registry()?;
+    let now = chrono::Utc::now();
+    let tasks = registry
+        .registry()
+        .tasks
+        .iter()
+        .filter(|(_, task)| !query.active || !task.status.is_terminal())
+        .filter(|(id, task)| filter.as_ref().is_none_or(|f| f.matches(id, task, now)))
+        .map(|(id, task)| (id.clone(), task.clone()))
+        .collect();
+    Ok(Json(tasks))
+}
+
+async fn get_task(
+    State(state): State<Arc<ApiState>>,
+    Path(query): Path<String>,
+) -> ApiResult<Json<TaskEntry>> {
+    let (task_id, task) = find_task(&mut state.registry()?, &query)?;
+    Ok(Json(TaskEntry {
+        task_id,
+        task,
+        alive: None,
+    }))
+}
+
+#[derive(Deserialize)]
+struct RegisterQuery {
+    #[serde(default)]
+    queue: bool,
+}
+
+async fn register_task(
+    State(state): State<Arc<ApiState>>,
+    ConnectInfo(peer): ConnectInfo<Peer>,
+    Query(query): Query<RegisterQuery>,
+    Json(spec): Json<TaskSpec>,
+) -> ApiResult<(StatusCode, Json<TaskEntry>)> {
+    let task_id = spec.task_id.clone();
+    let task = spec
+        .into_task(&Details::default(), &[], None, peer.origin("api"))
+        .map_err(|e| ApiError(StatusCode::UNPROCESSABLE_ENTITY, format!("{e:#}")))?;
+
+    let mut registry = state.registry()?;
+    if registry
+        .get_task(&task_id)
+        .is_some_and(|task| !task.status.is_terminal())
+    {
+        return Err(ApiError(
+            StatusCode::CONFLICT,
+            format!("Task {task_id} is already registered and not finished"),
+        ));
+    }
+    let task = tasks::register_for(&mut registry, &peer, &task_id, task, query.queue)
+        .map_err(|e| ApiError(StatusCode::CONFLICT, format!("{e:#}")))?;
+    info!(task: &task_id; "✅ Task {} registered via API", task_id);
+    Ok((
+        StatusCode::CREATED,
+        Json(TaskEntry {
+            task_id,
+            task,
+            alive: None,
+        }),
+    ))
+}
+
+async fn kill_task(
+    State(state): State<Arc<ApiState>>,
+    ConnectInfo(peer): ConnectInfo<Peer>,
+    Path(query): Path<String>,
+) -> ApiResult<Json<TaskEntry>> {
+    let mut registry = state.registry()?;
+    let (task_id, task) = find_task(&mut registry, &query)?;
+    if task.status.is_terminal() {
+        return Err(ApiError(
+            StatusCode::CONFLICT,
+            format!("Task {task_id} already finished ({})", task.status),
+        ));
+    }
+    tasks::ensure_peer_may_kill(&task_id, &task, &peer)
+        .map_err(|e| ApiError(StatusCode::FORBIDDEN, format!("{e:#}")))?;
+    tasks::kill(&mut registry, &task_id, &peer.origin("api")).await?;
+    let task = registry.get_task(&task_id).cloned().unwrap_or(task);
+    Ok(Json(TaskEntry {
+        task_id,
+        task,
+        alive: None,
+    }))
+}
+
+async fn report(State(state): State<Arc<ApiState>>) -> ApiResult<Json<Report>> {
+    let mut registry = state.registry()?;
+    registry.load_history()?;
+    let tasks = registry
+        .active_tasks()
+        .into_iter()
+        .map(|(id, task)| (id.clone(), task.clone()))
+        .collect();
+    Ok(Json(Report {
+        stats: registry.stats(),
+        cost: registry.total_cost(),
+        tasks,
+        watched: registry.registry().watched.clone(),
+    }))
+}
+
+/// The API's routes for the registry at `registry_path`
+pub fn router(registry_path: PathBuf) -> Router {
+    Router::new()
+        .route("/tasks", get(list_tasks).post(register_task))
+        .route("/tasks/{id}", get(get_task))
+        .route("/tasks/{id}/kill", post(kill_task))
+        .route("/tasks/{id}/logs", get(log_stream::task_logs))
+        .route("/report", get(report))
+        .with_state(Arc::new(ApiState { registry_path }))
+}
+
+/// Serve the API on `socket` until the process exits. The socket gets the
+/// registry's file mode; one left by a server that is gone is replaced, but
+/// one another server still answers on is not.
+#[cfg(unix)]
+pub async fn serve(socket: PathBuf, registry_path: PathBuf) -> Result<()> {
+    use crate::verbose;
+    use anyhow::Context;
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = RegistryManager::new(&registry_path).file_mode();
+    if socket.exists() {
+        if std::os::unix::net::UnixStream::connect(&socket).is_ok() {
+            anyhow::bail!("{} is already being served", socket.display());
+        }
+        std::fs::remove_file(&socket)
+            .with_context(|| format!("Failed to remove stale socket {}", socket.display()))?;
+    }
+    let listener = tokio::net::UnixListener::bind(&socket)
+        .with_context(|| format!("Failed to listen on {}", socket.display()))?;
+    std::fs::set_permissions(&socket, std::fs::Permissions::from_mode(mode))
+        .with_context(|| format!("Failed to restrict {}", socket.display()))?;
+    info!("🌐 API listening on {}", socket.display());
+    verbose!("   Registry: {}", registry_path.display());
+    let app = router(registry_path).into_make_service_with_connect_info::<Peer>();
+    axum::serve(listener, app)
+        .await
+        .context("API server failed")
+}
+
+#[cfg(not(unix))]
+pub async fn serve(_socket: PathBuf, _registry_path: PathBuf) -> Result<()> {
+    anyhow::bail!("The HTTP API is served on a Unix socket, which this platform lacks")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::TaskStatus;
+    use axum::body::Body;
+    use axum::extract::connect_info::MockConnectInfo;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    /// Send one request over `socket` and return the status and JSON body
+    #[cfg(unix)]
+    async fn call(
+        socket: &std::path::Path,
+        method: &str,
+        path: &str,
+        body: Option<serde_json::Value>,
+    ) -> (u16, serde_json::Value) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let body = body.map(|b| b.to_string()).unwrap_or_default();
+        let request = format!(
+            "{method} {path} HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\
+             content-type: application/json\r\ncontent-length: {}\r\n\r\n{body}",
+            body.len()
+        );
+        let mut stream = tokio::net::UnixStream::connect(socket).await.unwrap();
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_register_list_and_kill() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let registry_path = dir.path().join("registry.json");
+        let socket = socket_path(&registry_path);
+        // Left behind by a server that is gone
+        drop(std::os::unix::net::UnixListener::bind(&socket).unwrap());
+        tokio::spawn(serve(socket.clone(), registry_path.clone()));
+        while std::os::unix::net::UnixStream::connect(&socket).is_err() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let mode = std::fs::metadata(&socket).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        let spec = serde_json::json!({
+            "task_id": "T1",
+            "command": "sleep 600",
+            "labels": {"team": "api"}
+        });
+        let (status, created) = call(&socket, "POST", "/tasks", Some(spec.clone())).await;
+        assert_eq!(status, 201);
+        assert_eq!(created["task_id"], "T1");
+        assert_eq!(created["status"], "running");
+
+        // Still running: registering it again is a conflict
+        let (status, _) = call(&socket, "POST", "/tasks", Some(spec)).await;
+        assert_eq!(status, 409);
+
+        let (_, tasks) = call(&socket, "GET", "/tasks?where=label.team%3Dapi", None).await;
+        assert_eq!(tasks["T1"]["labels"]["team"], "api");
+
+        // Same user and checkout as the server here
+        let (_, killed) = call(&socket, "POST", "/tasks/T1/kill", None).await;
+        assert_eq!(killed["status"], "killed");
+
+        let (status, error) = call(&socket, "GET", "/tasks/T2", None).await;
+        assert_eq!(status, 404);
+        assert_eq!(error["error"], "Task T2 not found");
+
+        // A second server on the same socket is refused
+        assert!(serve(socket.clone(), registry_path).await.is_err());
+        let (status, _) = call(&socket, "GET", "/tasks/T1", None).await;
+        assert_eq!(status, 200);
+    }
+
+    #[tokio::test]
+    async fn test_kill_checks_the_caller() {
+        let dir = tempfile::tempdir().unwrap();
+        let registry_path = dir.path().join("registry.json");
+        let mut registry = RegistryManager::new(&registry_path);
+        let spec: TaskSpec = serde_json::from_value(serde_json::json!({
+            "task_id": "T1",
+            "command": "sleep 600",
+        }))
+        .unwrap();
+        let stranger = Peer {
+            uid: u32::MAX,
+            pid: None,
+        };
+        let task = spec
+            .into_task(&Details::default(), &[], None, stranger.origin("api"))
+            .unwrap();
+        let theirs = TaskInfo {
+            owner: Some("alice".to_string()),
+            ..task.clone()
+        };
+        registry.upsert_task("T1".to_string(), theirs).unwrap();
+        let elsewhere = TaskInfo {
+            owner: None,
+            repo_root: Some(dir.path().to_path_buf()),
+            ..task
+        };
+        registry.upsert_task("T2".to_string(), elsewhere).unwrap();
+
+        let kill = |peer: Peer, task_id: &str| {
+            let request = axum::http::Request::post(format!("/tasks/{task_id}/kill"))
+                .body(Body::empty())
+                .unwrap();
+            router(registry_path.clone())
+                .layer(MockConnectInfo(peer))
+                .oneshot(request)
+        };
+        // Another user's task, whoever runs the server
+        let response = kill(stranger, "T1").await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        // A task from another checkout than the caller's
+        let caller = Peer {
+            uid: u32::MAX,
+            pid: Some(std::process::id() as i32),
+        };
+        let response = kill(caller, "T2").await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        registry.load().unwrap();
+        assert!(registry
+            .registry()
+            .tasks
+            .values()
+            .all(|task| task.status == TaskStatus::Running));
+    }
+
+    #[tokio::test]
+    async fn test_register_records_the_caller() {
+        let dir = tempfile::tempdir().unwrap();
+        let registry_path = dir.path().join("registry.json");
+        let send = |peer: Peer, request: axum::http::Request<Body>| {
+            router(registry_path.clone())
+                .layer(MockConnectInfo(peer))
+                .oneshot(request)
+        };
+        let register = |task_id: &str| {
+            axum::http::Request::post("/tasks")
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    serde_json::json!({ "task_id": task_id, "command": "sleep 600" }).to_string(),
+                ))
+                .unwrap()
+        };
+
+        // A caller whose working directory can't be read
+        let stranger = Peer {
+            uid: u32::MAX,
+            pid: None,
+        };
+        let response = send(stranger.clone(), register("T1")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let me = Peer {
+            uid: u32::MAX,
+            pid: Some(std::process::id() as i32),
+        };
+        let response = send(me, register("T2")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let mut registry = RegistryManager::new(&registry_path);
+        registry.load().unwrap();
+        let theirs = registry.get_task("T1").unwrap();
+        assert_eq!(theirs.owner, Some(u32::MAX.to_string()));
+        assert_eq!(theirs.origin.as_ref().unwrap().user, theirs.owner);
+        assert_eq!((&theirs.cwd, &theirs.repo_root), (&None, &None));
+        let mine = registry.get_task("T2").unwrap();
+        assert_eq!(mine.cwd, std::env::current_dir().ok());
+
+        // Theirs to kill, though the server runs as someone else
+        let kill = axum::http::Request::post("/tasks/T1/kill")
+            .body(Body::empty())
+            .unwrap();
+        let response = send(stranger, kill).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
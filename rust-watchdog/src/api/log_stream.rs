@@ -0,0 +1,193 @@
+//! `GET /tasks/{id}/logs`: a task's output, from its log file or its
+//! container, and with `?follow=true` for as long as it runs, so web UIs
+//! and remote orchestrators can watch it without access to the host.
+
+use super::{find_task, ApiError, ApiResult, ApiState};
+use crate::capture;
+use crate::docker::DockerManager;
+use crate::logs;
+use crate::storage;
+use anyhow::Context;
+use axum::body::Body;
+use axum::extract::{Path, Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use futures_util::stream::{self, StreamExt};
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often a followed log is checked for new output
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Deserialize)]
+pub(super) struct LogsQuery {
+    #[serde(default)]
+    follow: bool,
+    tail: Option<usize>,
+}
+
+pub(super) async fn task_logs(
+    State(state): State<Arc<ApiState>>,
+    Path(query): Path<String>,
+    Query(logs_query): Query<LogsQuery>,
+) -> ApiResult<Response> {
+    let (task_id, task) = find_task(&mut state.registry()?, &query)?;
+    let LogsQuery { follow, tail } = logs_query;
+
+    let path = task
+        .log_file
+        .clone()
+        .unwrap_or_else(|| logs::log_path(&state.registry_path, &task_id));
+    // A container streams its output for as long as it exists; once it is
+    // removed, the copy the daemon saved when it stopped is sent instead
+    let container = match (&task.log_file, &task.docker) {
+        (None, Some(docker_info)) => match DockerManager::new() {
+            Some(docker) if docker.exit_state(&docker_info.container_id).await.is_ok() => {
+                Some((docker, docker_info.container_id.clone()))
+            }
+            None if !path.exists() => {
+                return Err(ApiError(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "Docker not available".into(),
+                ))
+            }
+            _ => None,
+        },
+        _ => None,
+    };
+
+    let chunks = match container {
+        Some((docker, container_id)) => docker.logs(&container_id, follow, tail),
+        None => {
+            if !path.exists() {
+                // Output the daemon captured is sent as it stands; it
+                // can't be followed from here
+                if let Some(output) = capture::request(&state.registry_path, &task_id, tail).await {
+                    return Ok((
+                        [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+                        output,
+                    )
+                        .into_response());
+                }
+                return Err(ApiError(
+                    StatusCode::NOT_FOUND,
+                    format!("Task {task_id} has no log (only spawned tasks and stopped containers are logged)"),
+                ));
+            }
+            let (start, offset) = match tail {
+                Some(lines) => (
+                    logs::tail(&path, lines).context("Failed to read log")?,
+                    std::fs::metadata(&path)
+                        .context("Failed to read log")?
+                        .len(),
+                ),
+                None => (String::new(), 0),
+            };
+            let registry_path = state.registry_path.clone();
+            let rest = stream::unfold(Some(offset), move |offset| {
+                let (path, registry_path, task_id) =
+                    (path.clone(), registry_path.clone(), task_id.clone());
+                async move {
+                    let mut offset = offset?;
+                    loop {
+                        // Checked before reading, so output written just
+                        // before the task finished is still sent
+                        let finished = !follow || is_finished(&registry_path, &task_id).await;
+                        match logs::read_from(&path, offset) {
+                            Ok((new, end)) if !new.is_empty() => {
+                                return Some((Ok(new), Some(end)));
+                            }
+                            Ok((_, end)) => offset = end,
+                            Err(e) => return Some((Err(e.into()), None)),
+                        }
+                        if finished {
+                            return None;
+                        }
+                        tokio::time::sleep(FOLLOW_INTERVAL).await;
+                    }
+                }
+            });
+            stream::once(async move { Ok(start.into_bytes()) })
+                .chain(rest)
+                .boxed()
+        }
+    };
+
+    let body = Body::from_stream(chunks.map(|chunk| chunk.map_err(axum::Error::new)));
+    Ok(([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], body).into_response())
+}
+
+/// Whether a followed task has finished (or is gone). The registry is read
+/// as stored, off the async runtime and without its lock, so following a
+/// log never writes to it or holds up the processes that do.
+async fn is_finished(registry_path: &std::path::Path, task_id: &str) -> bool {
+    let (registry_path, task_id) = (registry_path.to_path_buf(), task_id.to_string());
+    tokio::task::spawn_blocking(move || {
+        storage::open(&registry_path)
+            .read()
+            .map_or(true, |registry| {
+                registry
+                    .get_task(&task_id)
+                    .is_none_or(|task| task.status.is_terminal())
+            })
+    })
+    .await
+    .unwrap_or(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::router;
+    use crate::registry::RegistryManager;
+    use crate::types::{TaskCommand, TaskInfo};
+    use axum::body::Body;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_follow_ends_when_the_task_finishes() {
+        let dir = tempfile::tempdir().unwrap();
+        let registry_path = dir.path().join("registry.json");
+        let log_path = dir.path().join("T1.log");
+        std::fs::write(&log_path, "first\n").unwrap();
+        let mut registry = RegistryManager::new(&registry_path);
+        registry.load().unwrap();
+        let task = TaskInfo {
+            log_file: Some(log_path.clone()),
+            ..TaskInfo::new(TaskCommand::shell("sleep 600"))
+        };
+        registry.upsert_task("T1".to_string(), task).unwrap();
+        let written = std::fs::metadata(&registry_path)
+            .unwrap()
+            .modified()
+            .unwrap();
+
+        let request = axum::http::Request::get("/tasks/T1/logs?follow=true")
+            .body(Body::empty())
+            .unwrap();
+        let response = router(registry_path.clone())
+            .oneshot(request)
+            .await
+            .unwrap();
+        let body = tokio::spawn(axum::body::to_bytes(response.into_body(), usize::MAX));
+        tokio::time::sleep(Duration::from_millis(700)).await;
+        // Following reads the registry without writing it
+        assert_eq!(
+            std::fs::metadata(&registry_path)
+                .unwrap()
+                .modified()
+                .unwrap(),
+            written
+        );
+        std::fs::write(&log_path, "first\nlast\n").unwrap();
+        registry.mark_complete("T1").unwrap();
+
+        let body = tokio::time::timeout(Duration::from_secs(5), body)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(body, "first\nlast\n");
+    }
+}
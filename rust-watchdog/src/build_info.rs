@@ -0,0 +1,78 @@
+//! What this binary is: version, commit, target and compiled-in features.
+//!
+//! `task-watchdog version --json` prints this so orchestration scripts can
+//! check compatibility before driving the tool.
+
+use crate::types::REGISTRY_SCHEMA_VERSION;
+use serde::Serialize;
+
+/// Build metadata of the running binary
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildInfo {
+    pub name: &'static str,
+    pub version: &'static str,
+    /// Short commit hash, or `unknown` outside a git checkout
+    pub git_hash: &'static str,
+    /// Target triple the binary was built for
+    pub target: &'static str,
+    /// Optional subsystems compiled in
+    pub features: Vec<&'static str>,
+    /// Registry file format this binary reads and writes
+    pub registry_schema_version: u32,
+}
+
+impl BuildInfo {
+    pub fn current() -> Self {
+        Self {
+            name: env!("CARGO_PKG_NAME"),
+            version: env!("CARGO_PKG_VERSION"),
+            git_hash: env!("TASK_WATCHDOG_GIT_HASH"),
+            target: env!("TASK_WATCHDOG_TARGET"),
+            features: [
+                ("docker", cfg!(feature = "docker")),
+                ("http", cfg!(feature = "http")),
+                ("notify", cfg!(feature = "notify")),
+                ("self-update", cfg!(feature = "self-update")),
+            ]
+            .into_iter()
+            .filter_map(|(name, enabled)| enabled.then_some(name))
+            .collect(),
+            registry_schema_version: REGISTRY_SCHEMA_VERSION,
+        }
+    }
+}
+
+impl std::fmt::Display for BuildInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} ({}, {})",
+            self.name, self.version, self.git_hash, self.target
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_has_stable_keys() {
+        let json = serde_json::to_value(BuildInfo::current()).unwrap();
+        for key in [
+            "name",
+            "version",
+            "git_hash",
+            "target",
+            "features",
+            "registry_schema_version",
+        ] {
+            assert!(json.get(key).is_some(), "missing {key}");
+            assert!(
+                crate::typescript::DEFINITIONS.contains(&format!("  {key}: ")),
+                "{key} not in task-watchdog.d.ts"
+            );
+        }
+        assert_eq!(json["version"], env!("CARGO_PKG_VERSION"));
+    }
+}
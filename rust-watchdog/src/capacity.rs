@@ -0,0 +1,166 @@
+//! Admission control: declared reservations vs. machine capacity.
+//!
+//! Tasks declare memory/CPU up front (their Docker limits, or `--memory` /
+//! `--cpu` on `register`). A new task is admitted only while the
+//! reservations of all started tasks stay within the machine's capacity
+//! minus a headroom kept free for everything else on the box.
+
+use crate::docker::DockerManager;
+use crate::output::format_bytes;
+use crate::types::{ProcessRegistry, ResourceLimits, TaskInfo, TaskStatus};
+use anyhow::{bail, Context, Result};
+use sysinfo::System;
+
+/// Default share of the machine that is never handed out to reservations
+pub const DEFAULT_HEADROOM_PERCENT: u8 = 10;
+
+/// An amount of memory and CPU
+#[derive(Debug, Clone, Copy, PartialEq, Default, serde::Serialize)]
+pub struct Resources {
+    pub memory_bytes: u64,
+    pub cpus: f64,
+}
+
+impl Resources {
+    /// Parse declared limits (`"512m"`, `"1.5"`)
+    pub fn from_limits(limits: &ResourceLimits) -> Result<Self> {
+        let memory = DockerManager::parse_memory(&limits.memory)?;
+        let cpus: f64 = limits
+            .cpu
+            .trim()
+            .parse()
+            .with_context(|| format!("Invalid CPU reservation: {}", limits.cpu))?;
+        if memory < 0 || cpus < 0.0 {
+            bail!("Reservations cannot be negative");
+        }
+        Ok(Self {
+            memory_bytes: memory as u64,
+            cpus,
+        })
+    }
+
+    /// Installed memory and logical CPUs of this machine
+    pub fn machine() -> Self {
+        let mut sys = System::new();
+        sys.refresh_memory();
+        sys.refresh_cpu();
+        Self {
+            memory_bytes: sys.total_memory(),
+            cpus: sys.cpus().len() as f64,
+        }
+    }
+
+    /// Memory and CPU actually in use right now (blocks briefly to sample CPU)
+    pub fn in_use() -> Self {
+        let mut sys = System::new();
+        sys.refresh_memory();
+        sys.refresh_cpu();
+        std::thread::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
+        sys.refresh_cpu();
+        let busy = f64::from(sys.global_cpu_info().cpu_usage()) / 100.0;
+        Self {
+            memory_bytes: sys.used_memory(),
+            cpus: busy * sys.cpus().len() as f64,
+        }
+    }
+
+    /// What may be reserved once `percent` of `self` is kept free
+    pub fn minus_headroom(&self, percent: u8) -> Self {
+        let keep = 1.0 - f64::from(percent.min(100)) / 100.0;
+        Self {
+            memory_bytes: (self.memory_bytes as f64 * keep) as u64,
+            cpus: self.cpus * keep,
+        }
+    }
+
+    fn plus(&self, other: &Self) -> Self {
+        Self {
+            memory_bytes: self.memory_bytes + other.memory_bytes,
+            cpus: self.cpus + other.cpus,
+        }
+    }
+}
+
+/// Declared reservation of a task: explicit, or its Docker limits
+pub fn reservation_of(task: &TaskInfo) -> Option<Resources> {
+    let limits = task
+        .reservation
+        .as_ref()
+        .or(task.docker.as_ref().map(|d| &d.resource_limits))?;
+    Resources::from_limits(limits).ok()
+}
+
+/// Total reservations of started, unfinished tasks (queued tasks hold none)
+pub fn reserved(registry: &ProcessRegistry, except: Option<&str>) -> Resources {
+    registry
+        .tasks
+        .iter()
+        .filter(|(id, task)| {
+            !task.status.is_terminal()
+                && task.status != TaskStatus::Queued
+                && Some(id.as_str()) != except
+        })
+        .filter_map(|(_, task)| reservation_of(task))
+        .fold(Resources::default(), |total, r| total.plus(&r))
+}
+
+/// Refuse `request` if it would push reservations past `reservable`
+pub fn check_admission(
+    reservable: Resources,
+    reserved: Resources,
+    request: Resources,
+) -> Result<()> {
+    let after = reserved.plus(&request);
+    if after.memory_bytes > reservable.memory_bytes {
+        bail!(
+            "Not enough memory: {} reserved + {} requested exceeds {} reservable",
+            format_bytes(reserved.memory_bytes),
+            format_bytes(request.memory_bytes),
+            format_bytes(reservable.memory_bytes)
+        );
+    }
+    if after.cpus > reservable.cpus + f64::EPSILON {
+        bail!(
+            "Not enough CPU: {:.2} reserved + {:.2} requested exceeds {:.2} reservable",
+            reserved.cpus,
+            request.cpus,
+            reservable.cpus
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GIB: u64 = 1024 * 1024 * 1024;
+
+    fn res(memory_gib: u64, cpus: f64) -> Resources {
+        Resources {
+            memory_bytes: memory_gib * GIB,
+            cpus,
+        }
+    }
+
+    #[test]
+    fn test_from_limits() {
+        let limits = ResourceLimits {
+            memory: "512m".to_string(),
+            cpu: "1.5".to_string(),
+        };
+        let parsed = Resources::from_limits(&limits).unwrap();
+        assert_eq!(parsed.memory_bytes, 512 * 1024 * 1024);
+        assert_eq!(parsed.cpus, 1.5);
+    }
+
+    #[test]
+    fn test_admission_respects_headroom() {
+        let reservable = res(10, 8.0).minus_headroom(DEFAULT_HEADROOM_PERCENT);
+        assert_eq!(reservable.memory_bytes, 9 * GIB);
+
+        assert!(check_admission(reservable, res(6, 2.0), res(3, 1.0)).is_ok());
+        assert!(check_admission(reservable, res(6, 2.0), res(4, 1.0)).is_err());
+        assert!(check_admission(reservable, res(1, 6.0), res(1, 2.0)).is_err());
+    }
+}
@@ -0,0 +1,305 @@
+//! Output of tasks the daemon starts that have no log file: the last
+//! [`BUFFER_BYTES`] of what they write to stdout and stderr, kept in the
+//! daemon's memory so `check --tail`, `logs` and the API can show it
+//! without anyone having planned for logging.
+//!
+//! Other processes ask the daemon for it over a Unix socket next to the
+//! registry (`process_registry.output.sock`), one request per connection.
+//! The task itself still writes to its default log, so it doesn't depend
+//! on the daemon: the daemon follows that file and copies what is added
+//! to it since the task started into the buffer.
+
+use crate::logs;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::AbortHandle;
+
+/// Output kept per task
+pub const BUFFER_BYTES: usize = 64 * 1024;
+
+/// How long a client waits for the daemon's answer
+#[cfg_attr(not(unix), allow(dead_code))]
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often a followed log is checked for new output
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Socket the daemon of the registry at `registry_path` serves output on
+pub fn socket_path(registry_path: &Path) -> PathBuf {
+    registry_path.with_extension("output.sock")
+}
+
+/// The recent output of each captured task, shared between the daemon's
+/// checks, the logs it follows and the socket it serves
+#[derive(Debug, Clone, Default)]
+pub struct OutputBuffers {
+    buffers: Arc<Mutex<HashMap<String, VecDeque<u8>>>>,
+    followers: Arc<Mutex<HashMap<String, AbortHandle>>>,
+}
+
+impl OutputBuffers {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, VecDeque<u8>>> {
+        self.buffers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Add `bytes` to `task_id`'s output, dropping the oldest beyond
+    /// [`BUFFER_BYTES`]
+    pub fn push(&self, task_id: &str, bytes: &[u8]) {
+        let mut buffers = self.lock();
+        let buffer = buffers.entry(task_id.to_string()).or_default();
+        buffer.extend(bytes);
+        let excess = buffer.len().saturating_sub(BUFFER_BYTES);
+        buffer.drain(..excess);
+    }
+
+    /// Everything kept of `task_id`'s output, if it was captured. A line
+    /// cut short by the size limit is left out.
+    pub fn text(&self, task_id: &str) -> Option<String> {
+        let buffers = self.lock();
+        let buffer = buffers.get(task_id)?;
+        let (front, back) = buffer.as_slices();
+        let bytes = [front, back].concat();
+        let start = if buffer.len() >= BUFFER_BYTES {
+            bytes.iter().position(|&b| b == b'\n').map_or(0, |i| i + 1)
+        } else {
+            0
+        };
+        Some(String::from_utf8_lossy(&bytes[start..]).into_owned())
+    }
+
+    /// The last `lines` lines of `task_id`'s output, if it was captured
+    pub fn tail(&self, task_id: &str, lines: usize) -> Option<String> {
+        Some(logs::last_lines(&self.text(task_id)?, lines))
+    }
+
+    /// Forget the output of tasks `keep` says no to (cleaned up ones)
+    pub fn retain(&self, mut keep: impl FnMut(&str) -> bool) {
+        self.lock().retain(|task_id, _| keep(task_id));
+        let buffers = self.lock();
+        self.followers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|task_id, follower| {
+                let kept = buffers.contains_key(task_id);
+                if !kept {
+                    follower.abort();
+                }
+                kept
+            });
+    }
+
+    /// Copy what is added to `log_path` past `from` bytes into `task_id`'s
+    /// buffer until the task is forgotten, replacing what an earlier run
+    /// left. Must be called from within the daemon's runtime.
+    pub fn follow(&self, task_id: &str, log_path: PathBuf, from: u64) {
+        self.lock().insert(task_id.to_string(), VecDeque::new());
+        let follower = tokio::spawn(self.clone().copy_from(task_id.to_string(), log_path, from));
+        let earlier = self
+            .followers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(task_id.to_string(), follower.abort_handle());
+        if let Some(earlier) = earlier {
+            earlier.abort();
+        }
+    }
+
+    /// Poll `path` for output past `offset`, starting over when it is
+    /// truncated (rotated)
+    async fn copy_from(self, task_id: String, path: PathBuf, mut offset: u64) {
+        use std::io::SeekFrom;
+        use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+        let mut chunk = vec![0; 8192];
+        loop {
+            tokio::time::sleep(FOLLOW_INTERVAL).await;
+            let Ok(mut file) = tokio::fs::File::open(&path).await else {
+                continue;
+            };
+            let Ok(len) = file.metadata().await.map(|meta| meta.len()) else {
+                continue;
+            };
+            if len < offset {
+                offset = 0;
+            }
+            if len == offset || file.seek(SeekFrom::Start(offset)).await.is_err() {
+                continue;
+            }
+            while let Ok(n @ 1..) = file.read(&mut chunk).await {
+                self.push(&task_id, &chunk[..n]);
+                offset += n as u64;
+            }
+        }
+    }
+}
+
+/// What a client asks the daemon for: one JSON line
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(not(unix), allow(dead_code))]
+struct TailRequest {
+    task_id: String,
+    /// All that is kept when unset
+    lines: Option<usize>,
+}
+
+/// The daemon's answer: one JSON line, with no output when the task's
+/// wasn't captured
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(not(unix), allow(dead_code))]
+struct TailResponse {
+    output: Option<String>,
+}
+
+/// Answer requests for `buffers` on `socket`, which only users allowed by
+/// `mode` may connect to, until the daemon exits. A socket left by an
+/// earlier daemon is replaced; the daemon lock makes sure that one is gone.
+#[cfg(unix)]
+pub async fn serve(socket: PathBuf, mode: u32, buffers: OutputBuffers) -> Result<()> {
+    use anyhow::Context;
+    use std::os::unix::fs::PermissionsExt;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::UnixListener;
+
+    let _ = std::fs::remove_file(&socket);
+    let listener = UnixListener::bind(&socket)
+        .with_context(|| format!("Failed to listen on {}", socket.display()))?;
+    std::fs::set_permissions(&socket, std::fs::Permissions::from_mode(mode))
+        .with_context(|| format!("Failed to restrict {}", socket.display()))?;
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let buffers = buffers.clone();
+        tokio::spawn(async move {
+            let (read, mut write) = stream.into_split();
+            let mut read = BufReader::new(read);
+            let mut line = String::new();
+            let request = read.read_line(&mut line);
+            if !matches!(
+                tokio::time::timeout(REQUEST_TIMEOUT, request).await,
+                Ok(Ok(_))
+            ) {
+                return;
+            }
+            let Ok(request) = serde_json::from_str::<TailRequest>(&line) else {
+                return;
+            };
+            let output = match request.lines {
+                Some(lines) => buffers.tail(&request.task_id, lines),
+                None => buffers.text(&request.task_id),
+            };
+            if let Ok(mut answer) = serde_json::to_string(&TailResponse { output }) {
+                answer.push('\n');
+                let _ = write.write_all(answer.as_bytes()).await;
+            }
+        });
+    }
+}
+
+#[cfg(not(unix))]
+pub async fn serve(_socket: PathBuf, _mode: u32, _buffers: OutputBuffers) -> Result<()> {
+    Ok(())
+}
+
+/// The last `lines` lines (or all that is kept) of `task_id`'s output from
+/// the daemon of the registry at `registry_path`. `None` when no daemon
+/// answers or it didn't capture the task's output.
+#[cfg(unix)]
+pub async fn request(registry_path: &Path, task_id: &str, lines: Option<usize>) -> Option<String> {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::UnixStream;
+
+    let ask = async {
+        let mut stream = UnixStream::connect(socket_path(registry_path)).await.ok()?;
+        let request = TailRequest {
+            task_id: task_id.to_string(),
+            lines,
+        };
+        let mut line = serde_json::to_string(&request).ok()?;
+        line.push('\n');
+        stream.write_all(line.as_bytes()).await.ok()?;
+        let mut answer = String::new();
+        BufReader::new(stream).read_line(&mut answer).await.ok()?;
+        serde_json::from_str::<TailResponse>(&answer).ok()?.output
+    };
+    tokio::time::timeout(REQUEST_TIMEOUT, ask)
+        .await
+        .ok()
+        .flatten()
+}
+
+#[cfg(not(unix))]
+pub async fn request(
+    _registry_path: &Path,
+    _task_id: &str,
+    _lines: Option<usize>,
+) -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buffer_keeps_the_end() {
+        let buffers = OutputBuffers::default();
+        assert_eq!(buffers.tail("T1", 5), None);
+
+        buffers.push("T1", b"one\ntwo\n");
+        buffers.push("T1", b"three\n");
+        assert_eq!(buffers.tail("T1", 2).as_deref(), Some("two\nthree\n"));
+
+        // Past the limit the oldest output goes, from a whole line on
+        let line = format!("{}\n", "x".repeat(99));
+        for _ in 0..BUFFER_BYTES / line.len() + 10 {
+            buffers.push("T1", line.as_bytes());
+        }
+        let text = buffers.text("T1").unwrap();
+        assert!(text.len() <= BUFFER_BYTES);
+        assert!(text.lines().all(|l| l.len() == 99));
+
+        buffers.retain(|task_id| task_id != "T1");
+        assert_eq!(buffers.text("T1"), None);
+    }
+
+    #[tokio::test]
+    async fn test_follows_the_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("T1.log");
+        std::fs::write(&log, "earlier run\n").unwrap();
+        let buffers = OutputBuffers::default();
+        buffers.follow("T1", log.clone(), 12);
+        assert_eq!(buffers.text("T1").as_deref(), Some(""));
+
+        let mut file = std::fs::OpenOptions::new().append(true).open(&log).unwrap();
+        std::io::Write::write_all(&mut file, b"started\n").unwrap();
+        tokio::time::sleep(FOLLOW_INTERVAL * 3).await;
+        assert_eq!(buffers.text("T1").as_deref(), Some("started\n"));
+
+        buffers.retain(|_| false);
+        assert_eq!(buffers.text("T1"), None);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_served_over_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let registry_path = dir.path().join("process_registry.json");
+        let buffers = OutputBuffers::default();
+        buffers.push("T1", b"building\nerror: boom\n");
+        let socket = socket_path(&registry_path);
+        tokio::spawn(serve(socket.clone(), 0o600, buffers.clone()));
+        while !socket.exists() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        assert_eq!(
+            request(&registry_path, "T1", Some(1)).await.as_deref(),
+            Some("error: boom\n")
+        );
+        assert_eq!(request(&registry_path, "T2", None).await, None);
+    }
+}
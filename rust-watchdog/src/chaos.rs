@@ -0,0 +1,426 @@
+//! `chaos`: a soak test of supervision. Dummy workloads are started as
+//! native tasks, some are disrupted from outside (killed, stopped so they
+//! hang, or left running after they report done), and checks run as in
+//! the daemon. Every workload must end up with the status and the event
+//! its fate calls for.
+//!
+//! The checks run in this process, on the real registry and with the same
+//! [`Watchdog`] the daemon uses, so a deployment's registry location and
+//! backend are exercised too. Workloads are labelled `chaos=<run>` and stay
+//! in the registry like any finished task.
+
+use crate::alerts;
+use crate::daemon::{DaemonLock, Watchdog};
+use crate::events::WatchdogEvent;
+use crate::exits;
+use crate::logs;
+use crate::output::{self, new_table, status_cell, Verbosity};
+use crate::queue::Fairness;
+use crate::tasks::{self, Details};
+use crate::types::{TaskCommand, TaskOrigin, TaskStatus};
+use crate::{info, warn};
+use anyhow::{bail, Context, Result};
+use comfy_table::Cell;
+use nix::sys::signal::{killpg, Signal};
+use nix::unistd::Pid;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Label set on every workload, to the run's ID
+pub const CHAOS_LABEL: &str = "chaos";
+
+/// Seconds a workload's timeout allows beyond its planned runtime
+const TIMEOUT_MARGIN_SECS: u64 = 5;
+
+/// Seconds to wait past the run's duration for the last outcomes
+const SETTLE_SECS: u64 = 30;
+
+/// Shortest run that leaves room for a plan
+pub const MIN_DURATION_SECS: u64 = 15;
+
+/// What happens to a workload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fate {
+    /// Runs to the end and reports success
+    Completes,
+    /// Runs to the end and reports this exit code
+    Fails(i64),
+    /// Killed (SIGKILL) from outside while running
+    Killed,
+    /// Stopped (SIGSTOP) while running, so it hangs past its timeout
+    Hangs,
+    /// Reports success, then keeps running
+    Lingers,
+}
+
+impl Fate {
+    /// Status the watchdog must record
+    pub fn expected_status(&self) -> TaskStatus {
+        match self {
+            // A zombie keeps the status it finished with
+            Fate::Completes | Fate::Lingers => TaskStatus::Completed,
+            Fate::Fails(_) | Fate::Killed => TaskStatus::Failed,
+            Fate::Hangs => TaskStatus::TimedOut,
+        }
+    }
+
+    /// Whether `status` and the alert-worthy `events` about the task are
+    /// what this fate calls for: exactly one event of the right kind (none
+    /// for a workload that completes)
+    pub fn accepts(&self, status: &TaskStatus, events: &[WatchdogEvent]) -> bool {
+        if *status != self.expected_status() {
+            return false;
+        }
+        match (self, events) {
+            (Fate::Completes, []) => true,
+            (Fate::Fails(code), [WatchdogEvent::TaskDied { exit_code, .. }]) => {
+                *exit_code == Some(*code)
+            }
+            (Fate::Killed, [WatchdogEvent::TaskDied { exit_code, .. }]) => exit_code.is_none(),
+            (Fate::Hangs, [WatchdogEvent::TimedOut { .. }]) => true,
+            (Fate::Lingers, [WatchdogEvent::ZombieDetected { .. }]) => true,
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for Fate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Fate::Completes => write!(f, "completes"),
+            Fate::Fails(code) => write!(f, "fails ({code})"),
+            Fate::Killed => write!(f, "killed"),
+            Fate::Hangs => write!(f, "hangs"),
+            Fate::Lingers => write!(f, "lingers"),
+        }
+    }
+}
+
+/// One dummy workload of a plan
+#[derive(Debug, Clone, PartialEq)]
+pub struct Workload {
+    pub task_id: String,
+    pub fate: Fate,
+    /// When it starts, from the beginning of the run
+    pub start_after: Duration,
+    /// How long it sleeps before reporting
+    pub runtime: Duration,
+    /// When a killed or hanging workload is disrupted, from its start
+    pub disrupt_after: Duration,
+}
+
+impl Workload {
+    /// Shell script that sleeps for the runtime and writes its exit code
+    /// to `done_file`
+    fn script(&self, done_file: &Path, linger: Duration) -> String {
+        let runtime = self.runtime.as_secs();
+        let done = shell_words::quote(&done_file.to_string_lossy()).into_owned();
+        match self.fate {
+            Fate::Fails(code) => format!("sleep {runtime}; echo {code} > {done}; exit {code}"),
+            Fate::Lingers => format!(
+                "sleep {runtime}; echo 0 > {done}; sleep {}",
+                linger.as_secs()
+            ),
+            // Killed and hanging workloads are disrupted before they report
+            Fate::Completes | Fate::Killed | Fate::Hangs => {
+                format!("sleep {runtime}; echo 0 > {done}")
+            }
+        }
+    }
+
+    fn timeout_secs(&self) -> u64 {
+        self.runtime.as_secs() + TIMEOUT_MARGIN_SECS
+    }
+}
+
+/// `spawn` workloads for a run of `duration`, named `chaos-<run>-<n>`.
+/// They start during the first third of the run and run for up to another
+/// third. Without `disrupt` they only complete or fail.
+pub fn plan(
+    run: &str,
+    spawn: usize,
+    duration: Duration,
+    disrupt: bool,
+    seed: u64,
+) -> Vec<Workload> {
+    let mut rng = fastrand::Rng::with_seed(seed);
+    let third = (duration.as_secs() / 3).max(3);
+    (0..spawn)
+        .map(|n| {
+            let roll = rng.u8(0..100);
+            let fate = match roll {
+                _ if !disrupt && roll < 70 => Fate::Completes,
+                _ if !disrupt => Fate::Fails(rng.i64(1..=3)),
+                0..40 => Fate::Completes,
+                40..55 => Fate::Fails(rng.i64(1..=3)),
+                55..70 => Fate::Killed,
+                70..85 => Fate::Hangs,
+                _ => Fate::Lingers,
+            };
+            let runtime = rng.u64(2..=third);
+            Workload {
+                task_id: format!("chaos-{run}-{n:02}"),
+                fate,
+                start_after: Duration::from_secs(rng.u64(0..=third)),
+                runtime: Duration::from_secs(runtime),
+                disrupt_after: Duration::from_secs(rng.u64(1..runtime)),
+            }
+        })
+        .collect()
+}
+
+/// How one workload ended up
+#[derive(Debug)]
+pub struct Outcome {
+    pub workload: Workload,
+    /// Status recorded (`None` if the task is gone from the registry)
+    pub status: Option<TaskStatus>,
+    /// Alert-worthy events published about it
+    pub events: Vec<WatchdogEvent>,
+}
+
+impl Outcome {
+    pub fn passed(&self) -> bool {
+        self.status
+            .as_ref()
+            .is_some_and(|status| self.workload.fate.accepts(status, &self.events))
+    }
+}
+
+/// Run `workloads` against the registry at `registry_path`, checking every
+/// `interval` (and whenever a workload exits), until each has ended as its
+/// fate calls for or the run has lasted `duration` plus a grace period
+pub async fn run(
+    registry_path: &Path,
+    run: &str,
+    workloads: Vec<Workload>,
+    duration: Duration,
+    interval: Duration,
+    origin: TaskOrigin,
+) -> Result<Vec<Outcome>> {
+    let _lock = DaemonLock::acquire(registry_path, false)
+        .await
+        .context("chaos runs the checks itself: stop the daemon or use another --registry")?;
+    let mut watchdog = Watchdog::new(registry_path, Fairness::default(), 0);
+    let mut published = watchdog.subscribe();
+    let mut exits = exits::Waker::new()?;
+    let done_dir = registry_path.with_file_name("chaos").join(run);
+    std::fs::create_dir_all(&done_dir)
+        .with_context(|| format!("Failed to create {}", done_dir.display()))?;
+
+    let begin = Instant::now();
+    let deadline = begin + duration + Duration::from_secs(SETTLE_SECS);
+    let mut unstarted: Vec<&Workload> = workloads.iter().collect();
+    unstarted.sort_by_key(|workload| std::cmp::Reverse(workload.start_after));
+    let mut events: HashMap<String, Vec<WatchdogEvent>> = HashMap::new();
+    let mut ticks = tokio::time::interval(interval);
+
+    loop {
+        while unstarted
+            .last()
+            .is_some_and(|workload| begin + workload.start_after <= Instant::now())
+        {
+            let workload = unstarted.pop().expect("checked above");
+            let pgid = start(&mut watchdog, workload, &done_dir, duration, &origin)?;
+            let signal = match workload.fate {
+                Fate::Killed => Signal::SIGKILL,
+                Fate::Hangs => Signal::SIGSTOP,
+                _ => {
+                    info!("🎲 Started {} ({})", workload.task_id, workload.fate);
+                    continue;
+                }
+            };
+            info!(
+                "🎲 Started {} ({}, {} in {}s)",
+                workload.task_id,
+                workload.fate,
+                signal,
+                workload.disrupt_after.as_secs()
+            );
+            // On its own timer: a check can take seconds (stopping tasks
+            // waits for them), by when the workload may have finished
+            let (task_id, after) = (workload.task_id.clone(), workload.disrupt_after);
+            tokio::spawn(async move {
+                tokio::time::sleep(after).await;
+                if let Err(e) = killpg(Pid::from_raw(pgid), signal) {
+                    warn!("⚠️  Could not send {} to {}: {}", signal, task_id, e);
+                }
+            });
+        }
+
+        tokio::select! {
+            _ = ticks.tick() => {}
+            exited = exits.exited() => { exited?; }
+        }
+        // The checks' own progress only with -v; their warnings always
+        let level = output::verbosity();
+        if level < Verbosity::Verbose {
+            output::set_verbosity(Verbosity::Quiet);
+        }
+        let checked = watchdog.check(true).await;
+        output::set_verbosity(level);
+        checked?;
+        exits.watch(watchdog.exit_pids());
+        while let Ok(event) = published.try_recv() {
+            if let (Some(task_id), Some(_)) = (event.task_id(), alerts::condition_key(&event)) {
+                events.entry(task_id.to_string()).or_default().push(event);
+            }
+        }
+
+        let tasks = &watchdog.registry().registry().tasks;
+        let settled = unstarted.is_empty()
+            && workloads.iter().all(|workload| {
+                let events = events.get(&workload.task_id).map_or(&[][..], Vec::as_slice);
+                tasks
+                    .get(&workload.task_id)
+                    .is_some_and(|task| workload.fate.accepts(&task.status, events))
+            });
+        if settled || Instant::now() >= deadline {
+            break;
+        }
+    }
+
+    let tasks = &watchdog.registry().registry().tasks;
+    Ok(workloads
+        .into_iter()
+        .map(|workload| Outcome {
+            status: tasks.get(&workload.task_id).map(|task| task.status.clone()),
+            events: events.remove(&workload.task_id).unwrap_or_default(),
+            workload,
+        })
+        .collect())
+}
+
+/// Start `workload` and register it. Returns its process group.
+fn start(
+    watchdog: &mut Watchdog,
+    workload: &Workload,
+    done_dir: &Path,
+    duration: Duration,
+    origin: &TaskOrigin,
+) -> Result<i32> {
+    let task_id = &workload.task_id;
+    let done_file = std::path::absolute(done_dir.join(task_id))?;
+    let details = Details {
+        description: Some(format!("chaos workload that {}", workload.fate)),
+        labels: BTreeMap::from([(
+            CHAOS_LABEL.to_string(),
+            done_dir
+                .file_name()
+                .map(|run| run.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        )]),
+        done_file: Some(done_file.clone()),
+        timeout_secs: Some(workload.timeout_secs()),
+        ..Default::default()
+    };
+    let command = TaskCommand::shell(workload.script(&done_file, duration));
+    let mut task = tasks::new_task(command, details, Vec::new(), None, origin.clone());
+    let log_path = std::path::absolute(logs::log_path(watchdog.registry().path(), task_id))?;
+    let native = tasks::start_detached(task_id, &task, &log_path, watchdog.registry().file_mode())?;
+    let pgid = native.pgid;
+    task.native = Some(native);
+    task.log_file = Some(log_path);
+
+    let results = watchdog.register_batch(vec![(task_id.clone(), task)], false)?;
+    if let Some((_, Err(e))) = results.into_iter().next() {
+        let _ = killpg(Pid::from_raw(pgid), Signal::SIGKILL);
+        bail!("Could not register {}: {:#}", task_id, e);
+    }
+    Ok(pgid)
+}
+
+/// Table of `outcomes`: fate, recorded status and events, and a verdict
+pub fn print_outcomes(outcomes: &[Outcome]) {
+    let mut table = new_table(["Task", "Fate", "Recorded", "Events", ""]);
+    for outcome in outcomes {
+        let status = match &outcome.status {
+            Some(status) => status_cell(status),
+            None => Cell::new("(missing)"),
+        };
+        let events: Vec<&str> = outcome.events.iter().map(WatchdogEvent::name).collect();
+        table.add_row(vec![
+            Cell::new(&outcome.workload.task_id),
+            Cell::new(outcome.workload.fate),
+            status,
+            Cell::new(events.join(", ")),
+            Cell::new(if outcome.passed() { "✅" } else { "❌" }),
+        ]);
+    }
+    info!("{table}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn died(exit_code: Option<i64>) -> WatchdogEvent {
+        WatchdogEvent::TaskDied {
+            task_id: "T001".into(),
+            exit_code,
+            triage_hint: None,
+            failure_excerpt: None,
+        }
+    }
+
+    #[test]
+    fn test_plan_is_repeatable_and_fits_the_run() {
+        let duration = Duration::from_secs(60);
+        let workloads = plan("r1", 50, duration, true, 7);
+        assert_eq!(workloads, plan("r1", 50, duration, true, 7));
+        assert_eq!(workloads[3].task_id, "chaos-r1-03");
+        for workload in &workloads {
+            assert!(workload.start_after <= Duration::from_secs(20));
+            assert!((2..=20).contains(&workload.runtime.as_secs()));
+            assert!(workload.disrupt_after < workload.runtime);
+        }
+        for fate in [Fate::Killed, Fate::Hangs, Fate::Lingers] {
+            assert!(workloads.iter().any(|workload| workload.fate == fate));
+        }
+
+        let calm = plan("r2", 50, duration, false, 7);
+        assert!(calm
+            .iter()
+            .all(|workload| matches!(workload.fate, Fate::Completes | Fate::Fails(_))));
+    }
+
+    #[test]
+    fn test_fate_needs_its_status_and_exactly_its_event() {
+        assert!(Fate::Completes.accepts(&TaskStatus::Completed, &[]));
+        assert!(!Fate::Completes.accepts(&TaskStatus::Completed, &[died(None)]));
+        assert!(Fate::Fails(2).accepts(&TaskStatus::Failed, &[died(Some(2))]));
+        assert!(!Fate::Fails(2).accepts(&TaskStatus::Failed, &[died(None)]));
+        assert!(Fate::Killed.accepts(&TaskStatus::Failed, &[died(None)]));
+        assert!(!Fate::Killed.accepts(&TaskStatus::Failed, &[died(None), died(None)]));
+        let timed_out = WatchdogEvent::TimedOut {
+            task_id: "T001".into(),
+            timeout_secs: 9,
+        };
+        assert!(!Fate::Hangs.accepts(&TaskStatus::Killed, std::slice::from_ref(&timed_out)));
+        assert!(Fate::Hangs.accepts(&TaskStatus::TimedOut, &[timed_out]));
+    }
+
+    #[test]
+    fn test_script_reports_through_the_done_file() {
+        let workload = Workload {
+            task_id: "chaos-r1-00".into(),
+            fate: Fate::Fails(3),
+            start_after: Duration::ZERO,
+            runtime: Duration::from_secs(4),
+            disrupt_after: Duration::from_secs(1),
+        };
+        let done = Path::new("/tmp/chaos dir/chaos-r1-00");
+        assert_eq!(
+            workload.script(done, Duration::from_secs(60)),
+            "sleep 4; echo 3 > '/tmp/chaos dir/chaos-r1-00'; exit 3"
+        );
+        let lingering = Workload {
+            fate: Fate::Lingers,
+            ..workload
+        };
+        assert!(lingering
+            .script(done, Duration::from_secs(60))
+            .ends_with("; sleep 60"));
+    }
+}
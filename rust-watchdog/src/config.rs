@@ -0,0 +1,321 @@
+//! `watchdog.toml`, the per-registry configuration `init` scaffolds in
+//! `.claude/` (or any file given with `--config`).
+//!
+//! Most settings stand for a command-line flag and apply wherever that
+//! flag is left out: `[daemon] interval` is `run --interval`, `[cleanup]
+//! days` is `cleanup --days`, and so on (see [`Config::flags`]). A flag
+//! given on the command line always wins.
+//!
+//! A missing file means built-in defaults. Sections this version doesn't
+//! read yet are ignored, so a config written for a newer release still
+//! loads.
+
+use crate::notify::ChannelConfig;
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Name of the configuration file in `.claude/`
+pub const CONFIG_FILE: &str = "watchdog.toml";
+
+/// Where the configuration is read from without `--config`
+pub const DEFAULT_PATH: &str = ".claude/watchdog.toml";
+
+/// The parts of `watchdog.toml` the watchdog reads
+#[derive(Debug, Default, Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    pub daemon: DaemonConfig,
+    #[serde(default)]
+    pub registry: RegistryConfig,
+    #[serde(default)]
+    pub capacity: CapacityConfig,
+    #[serde(default)]
+    pub queue: QueueConfig,
+    #[serde(default)]
+    pub limits: LimitsConfig,
+    #[serde(default)]
+    pub cleanup: CleanupConfig,
+    #[serde(default)]
+    pub docker: DockerConfig,
+    /// Notification channels by name (`[notify.<name>]`)
+    #[serde(default)]
+    pub notify: BTreeMap<String, ChannelConfig>,
+}
+
+/// `[daemon]`: settings of `run`
+#[derive(Debug, Default, Deserialize)]
+pub struct DaemonConfig {
+    /// Seconds between checks
+    pub interval: Option<u64>,
+    pub memory_budget_mb: Option<u64>,
+    #[serde(default)]
+    pub restart_on_leak: bool,
+    /// Kill tasks over their `--max-memory` / `--max-cpu` / process limits
+    #[serde(default)]
+    pub enforce_limits: bool,
+    /// Processes a task may hold unless it sets `--max-processes`
+    pub max_processes: Option<u64>,
+    /// New processes a task may start per minute
+    pub max_process_rate: Option<u64>,
+    /// Open files and threads a task may hold unless it sets its own
+    pub max_open_files: Option<u64>,
+    pub max_threads: Option<u64>,
+}
+
+/// `[registry]`
+#[derive(Debug, Default, Deserialize)]
+pub struct RegistryConfig {
+    pub path: Option<String>,
+}
+
+/// `[capacity]`
+#[derive(Debug, Default, Deserialize)]
+pub struct CapacityConfig {
+    pub headroom_percent: Option<u8>,
+}
+
+/// `[queue]`
+#[derive(Debug, Default, Deserialize)]
+pub struct QueueConfig {
+    pub fair_by: Option<String>,
+    #[serde(default)]
+    pub weights: BTreeMap<String, u32>,
+}
+
+/// `[limits]`: the reservation of tasks that don't declare one, which is
+/// also the limit of their container in `docker` mode
+#[derive(Debug, Default, Deserialize)]
+pub struct LimitsConfig {
+    pub memory: Option<String>,
+    pub cpu: Option<String>,
+}
+
+/// `[cleanup]`
+#[derive(Debug, Default, Deserialize)]
+pub struct CleanupConfig {
+    /// Days finished tasks are kept
+    pub days: Option<u64>,
+}
+
+/// `[docker]`
+#[derive(Debug, Default, Deserialize)]
+pub struct DockerConfig {
+    /// Image of `docker` tasks that don't name one
+    pub image: Option<String>,
+    /// Remove containers once they have stopped
+    #[serde(default)]
+    pub auto_remove: bool,
+    /// Keep the containers of failed runs, removing the others
+    #[serde(default)]
+    pub keep_on_failure: bool,
+}
+
+impl Config {
+    /// Read the configuration at `path`, or the defaults if there is none
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_value(toml_to_json(&content)?)
+            .with_context(|| format!("Invalid configuration in {}", path.display()))
+    }
+
+    /// Read the configuration at `path` if given (it must exist), at
+    /// [`DEFAULT_PATH`] otherwise
+    pub fn find(path: Option<&Path>) -> Result<Self> {
+        match path {
+            Some(path) if !path.exists() => bail!("No configuration at {}", path.display()),
+            Some(path) => Self::load(path),
+            None => Self::load(Path::new(DEFAULT_PATH)),
+        }
+    }
+
+    /// The command-line flags this configuration stands for, each with
+    /// the ID of the argument it fills in. A command takes the ones for
+    /// arguments it has and wasn't given.
+    pub fn flags(&self) -> Vec<(&'static str, Vec<String>)> {
+        let mut flags = Vec::new();
+        let mut flag = |arg: &'static str, value: Option<String>| {
+            if let Some(value) = value {
+                flags.push((arg, vec![format!("--{}", arg.replace('_', "-")), value]));
+            }
+        };
+        flag("registry", self.registry.path.clone());
+        flag(
+            "interval",
+            self.daemon.interval.map(|secs| secs.to_string()),
+        );
+        flag(
+            "memory_budget",
+            self.daemon.memory_budget_mb.map(|mb| mb.to_string()),
+        );
+        flag(
+            "max_processes_per_task",
+            self.daemon.max_processes.map(|count| count.to_string()),
+        );
+        flag(
+            "max_process_rate",
+            self.daemon.max_process_rate.map(|count| count.to_string()),
+        );
+        flag(
+            "max_open_files_per_task",
+            self.daemon.max_open_files.map(|count| count.to_string()),
+        );
+        flag(
+            "max_threads_per_task",
+            self.daemon.max_threads.map(|count| count.to_string()),
+        );
+        flag(
+            "headroom",
+            self.capacity.headroom_percent.map(|pct| pct.to_string()),
+        );
+        flag("fair_by", self.queue.fair_by.clone());
+        flag("memory", self.limits.memory.clone());
+        flag("cpu", self.limits.cpu.clone());
+        flag("days", self.cleanup.days.map(|days| days.to_string()));
+        flag("image", self.docker.image.clone());
+        if self.daemon.restart_on_leak {
+            flags.push(("restart_on_leak", vec!["--restart-on-leak".to_string()]));
+        }
+        if self.daemon.enforce_limits {
+            flags.push(("enforce_limits", vec!["--enforce-limits".to_string()]));
+        }
+        if self.docker.auto_remove {
+            flags.push(("auto_remove", vec!["--auto-remove".to_string()]));
+        }
+        if self.docker.keep_on_failure {
+            flags.push(("keep_on_failure", vec!["--keep-on-failure".to_string()]));
+        }
+        if !self.queue.weights.is_empty() {
+            let weights = self
+                .queue
+                .weights
+                .iter()
+                .flat_map(|(value, weight)| ["--weight".to_string(), format!("{value}={weight}")])
+                .collect();
+            flags.push(("weight", weights));
+        }
+        flags
+    }
+}
+
+/// Parse TOML into the JSON value serde then reads the structure from
+pub fn toml_to_json(content: &str) -> Result<Value> {
+    let document: toml_edit::DocumentMut = content.parse().context("Invalid TOML")?;
+    Ok(table_to_json(document.as_table()))
+}
+
+fn table_to_json(table: &toml_edit::Table) -> Value {
+    Value::Object(
+        table
+            .iter()
+            .map(|(key, item)| (key.to_string(), item_to_json(item)))
+            .collect(),
+    )
+}
+
+fn item_to_json(item: &toml_edit::Item) -> Value {
+    match item {
+        toml_edit::Item::None => Value::Null,
+        toml_edit::Item::Value(value) => value_to_json(value),
+        toml_edit::Item::Table(table) => table_to_json(table),
+        toml_edit::Item::ArrayOfTables(tables) => {
+            Value::Array(tables.iter().map(table_to_json).collect())
+        }
+    }
+}
+
+fn value_to_json(value: &toml_edit::Value) -> Value {
+    use toml_edit::Value as Toml;
+    match value {
+        Toml::String(s) => Value::from(s.value().as_str()),
+        Toml::Integer(i) => Value::from(*i.value()),
+        Toml::Float(f) => Value::from(*f.value()),
+        Toml::Boolean(b) => Value::from(*b.value()),
+        Toml::Datetime(d) => Value::from(d.value().to_string()),
+        Toml::Array(array) => Value::Array(array.iter().map(value_to_json).collect()),
+        Toml::InlineTable(table) => Value::Object(
+            table
+                .iter()
+                .map(|(key, value)| (key.to_string(), value_to_json(value)))
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scaffolded_config_loads() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("watchdog.toml");
+        assert!(Config::load(&path).unwrap().notify.is_empty());
+
+        std::fs::write(&path, crate::init::WATCHDOG_TOML).unwrap();
+        assert!(Config::load(&path).unwrap().notify.is_empty());
+
+        let example: String = crate::init::WATCHDOG_TOML
+            .lines()
+            .skip_while(|line| !line.starts_with("# [notify."))
+            .map(|line| line.strip_prefix("# ").unwrap_or(line))
+            .take_while(|line| !line.is_empty())
+            .map(|line| format!("{line}\n"))
+            .collect();
+        std::fs::write(&path, example).unwrap();
+        let config = Config::load(&path).unwrap();
+        assert!(config.notify.contains_key("desktop"), "{:?}", config);
+    }
+
+    #[test]
+    fn test_settings_become_flags() {
+        let toml = r#"
+            [daemon]
+            interval = 60
+            max_processes = 64
+            restart_on_leak = true
+            enforce_limits = true
+
+            [queue.weights]
+            interactive = 3
+
+            [cleanup]
+            days = 30
+
+            [docker]
+            keep_on_failure = true
+
+            [future]
+            setting = "ignored"
+        "#;
+        let config: Config = serde_json::from_value(toml_to_json(toml).unwrap()).unwrap();
+        assert_eq!(
+            config.flags(),
+            vec![
+                ("interval", vec!["--interval".into(), "60".into()]),
+                (
+                    "max_processes_per_task",
+                    vec!["--max-processes-per-task".into(), "64".into()]
+                ),
+                ("days", vec!["--days".into(), "30".into()]),
+                ("restart_on_leak", vec!["--restart-on-leak".into()]),
+                ("enforce_limits", vec!["--enforce-limits".into()]),
+                ("keep_on_failure", vec!["--keep-on-failure".into()]),
+                ("weight", vec!["--weight".into(), "interactive=3".into()]),
+            ]
+        );
+        assert!(Config::default().flags().is_empty());
+    }
+
+    #[test]
+    fn test_explicit_config_must_exist() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(Config::find(Some(&dir.path().join("missing.toml"))).is_err());
+    }
+}
@@ -0,0 +1,414 @@
+//! Core dumps: where a crashed native task's core went, recorded on the
+//! task so `check` can point at it, and optionally the top of its
+//! backtrace in the triage hint.
+//!
+//! Where cores go depends on `/proc/sys/kernel/core_pattern`. Piped to
+//! systemd-coredump (`|/usr/lib/systemd/systemd-coredump ...`), they are
+//! looked up with `coredumpctl info <pid>`. A file pattern (`core`,
+//! `/var/crash/core.%e.%p`) is expanded for the process and looked for on
+//! disk, relative to the task's directory. Other handlers (apport, abrt)
+//! keep cores where the watchdog can't follow.
+//!
+//! The backtrace comes from `gdb -batch -ex bt`, which can take a while on
+//! a big core, so it is only run when asked for (`--core-backtrace`).
+
+use crate::triage::{self, Evidence};
+use crate::types::{Coredump, TaskInfo};
+use chrono::{DateTime, Utc};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::process::Command;
+
+/// Backtrace frames kept
+pub const FRAMES: usize = 5;
+
+/// Longest gdb may take to print a backtrace
+const GDB_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long to wait for systemd-coredump to store a core the kernel
+/// reported dumping
+const STORE_WAIT: Duration = Duration::from_secs(5);
+
+/// How long before its recorded start a task's core may be written: the
+/// start is recorded once the process is running, and a quick crash can
+/// beat it
+const START_SLACK_SECS: i64 = 2;
+
+static BACKTRACES: AtomicBool = AtomicBool::new(false);
+
+/// Have [`collect`] run gdb for a backtrace (`--core-backtrace`)
+pub fn set_backtraces(enabled: bool) {
+    BACKTRACES.store(enabled, Ordering::Relaxed);
+}
+
+/// The core dump `task`'s process left, with its backtrace if
+/// [`set_backtraces`] asked for one. `signal` is the signal the process
+/// died of, when its exit status said it dumped core: the core is then
+/// waited for while systemd-coredump stores it.
+pub async fn collect(task: &TaskInfo, signal: Option<i32>) -> Option<Coredump> {
+    let deadline = tokio::time::Instant::now() + STORE_WAIT;
+    let mut dump = loop {
+        match find(task).await {
+            Some(dump) => break dump,
+            None if signal.is_some() && tokio::time::Instant::now() < deadline => {
+                tokio::time::sleep(Duration::from_millis(500)).await
+            }
+            None => return None,
+        }
+    };
+    dump.signal = dump.signal.or(signal);
+    if BACKTRACES.load(Ordering::Relaxed) {
+        dump.backtrace = backtrace(&dump, task).await;
+    }
+    Some(dump)
+}
+
+/// Triage hint for a crash that left `dump`: what its signal usually
+/// means, and where it crashed (`segmentation fault (SIGSEGV); core
+/// dumped, crashed in parse_header (buf=0x0) at src/parse.c:88`)
+pub fn hint(dump: &Coredump, output: Option<&str>) -> String {
+    let evidence = Evidence {
+        exit_code: dump.signal.map(|signal| 128 + i64::from(signal)),
+        output,
+        ..Default::default()
+    };
+    match triage::hint(evidence) {
+        Some(hint) => format!("{hint}; {}", dump.summary()),
+        None => dump.summary(),
+    }
+}
+
+/// Core dump of `task`'s process (which ran from `task.cwd`), if one was
+/// written after the task started
+pub async fn find(task: &TaskInfo) -> Option<Coredump> {
+    let pid = task.native.as_ref()?.pid;
+    let pattern = std::fs::read_to_string("/proc/sys/kernel/core_pattern").ok()?;
+    let pattern = pattern.trim();
+    if let Some(handler) = pattern.strip_prefix('|') {
+        if !handler.contains("systemd-coredump") {
+            return None;
+        }
+        return from_coredumpctl(pid).await;
+    }
+    let uses_pid = std::fs::read_to_string("/proc/sys/kernel/core_uses_pid")
+        .is_ok_and(|value| value.trim() == "1");
+    let since = task.started_at - chrono::Duration::seconds(START_SLACK_SECS);
+    let path = core_file(pattern, uses_pid, pid, task.cwd.as_deref(), since)?;
+    Some(Coredump {
+        pid,
+        signal: None,
+        path: Some(path),
+        coredumpctl: false,
+        backtrace: Vec::new(),
+    })
+}
+
+/// What systemd-coredump kept for `pid`
+async fn from_coredumpctl(pid: i32) -> Option<Coredump> {
+    let output = Command::new("coredumpctl")
+        .args(["info", "--no-pager", &pid.to_string()])
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let (signal, path) = parse_coredumpctl_info(&String::from_utf8_lossy(&output.stdout));
+    Some(Coredump {
+        pid,
+        signal,
+        path,
+        coredumpctl: true,
+        backtrace: Vec::new(),
+    })
+}
+
+/// Signal and stored core file from `coredumpctl info` output, for its
+/// last (newest) entry
+fn parse_coredumpctl_info(info: &str) -> (Option<i32>, Option<PathBuf>) {
+    let mut signal = None;
+    let mut path = None;
+    for line in info.lines() {
+        let Some((key, value)) = line.trim().split_once(": ") else {
+            continue;
+        };
+        match key {
+            // "11 (SEGV)"
+            "Signal" => signal = value.split_whitespace().next().and_then(|s| s.parse().ok()),
+            // "/var/lib/systemd/coredump/core...zst (present)", or "none"
+            "Storage" => {
+                path = value
+                    .strip_suffix(" (present)")
+                    .map(|file| PathBuf::from(file.trim()));
+            }
+            _ => {}
+        }
+    }
+    (signal, path)
+}
+
+/// The core file `pattern` names for `pid`, if one was modified after
+/// `since` (an old `core` in the directory isn't this crash's)
+fn core_file(
+    pattern: &str,
+    uses_pid: bool,
+    pid: i32,
+    cwd: Option<&Path>,
+    since: DateTime<Utc>,
+) -> Option<PathBuf> {
+    let mut name = expand_pattern(pattern, pid);
+    if uses_pid && !pattern.contains("%p") {
+        name.push_str(&format!(".{pid}"));
+    }
+    let name = Path::new(&name);
+    let dir = name.parent()?;
+    let dir = match cwd {
+        _ if dir.is_absolute() => dir.to_path_buf(),
+        Some(cwd) => cwd.join(dir),
+        // Relative to a directory we don't know
+        None => return None,
+    };
+    let file_pattern = name.file_name()?.to_string_lossy().into_owned();
+    std::fs::read_dir(&dir)
+        .ok()?
+        .flatten()
+        .filter(|entry| wildcard_match(&file_pattern, &entry.file_name().to_string_lossy()))
+        .filter_map(|entry| {
+            let modified: DateTime<Utc> = entry.metadata().ok()?.modified().ok()?.into();
+            (modified >= since).then_some((modified, entry.path()))
+        })
+        .max()
+        .map(|(_, path)| path)
+}
+
+/// `pattern` with the process ID filled in and every other specifier
+/// (`%e`, `%t`, `%s`, ...) as a `*` wildcard
+fn expand_pattern(pattern: &str, pid: i32) -> String {
+    let mut expanded = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('p') | Some('P') => expanded.push_str(&pid.to_string()),
+            Some('%') => expanded.push('%'),
+            Some(_) => expanded.push('*'),
+            None => {}
+        }
+    }
+    expanded
+}
+
+/// Whether `name` matches `pattern`, where `*` stands for any run of
+/// characters
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == name,
+        Some((head, rest)) => {
+            let Some(name) = name.strip_prefix(head) else {
+                return false;
+            };
+            (0..=name.len())
+                .filter(|i| name.is_char_boundary(*i))
+                .any(|i| wildcard_match(rest, &name[i..]))
+        }
+    }
+}
+
+/// The top [`FRAMES`] frames of the crash's backtrace, from gdb. Empty if
+/// gdb (or coredumpctl) is missing, fails or takes too long.
+pub async fn backtrace(dump: &Coredump, task: &TaskInfo) -> Vec<String> {
+    let mut command = if dump.coredumpctl {
+        let mut command = Command::new("coredumpctl");
+        command.args([
+            "debug",
+            "--no-pager",
+            "--debugger=gdb",
+            "-A",
+            "-batch -ex bt",
+        ]);
+        command.arg(dump.pid.to_string());
+        command
+    } else {
+        let Some(path) = &dump.path else {
+            return Vec::new();
+        };
+        let mut command = Command::new("gdb");
+        command.args(["-batch", "-ex", "bt"]);
+        // Without it gdb guesses from the core; a shell command's program
+        // isn't known
+        if let Some(exe) = executable(task) {
+            command.arg(exe);
+        }
+        command.arg("-c").arg(path);
+        command
+    };
+    command
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true);
+    match tokio::time::timeout(GDB_TIMEOUT, command.output()).await {
+        Ok(Ok(output)) => parse_backtrace(&String::from_utf8_lossy(&output.stdout)),
+        _ => Vec::new(),
+    }
+}
+
+/// The program a directly executed task ran
+fn executable(task: &TaskInfo) -> Option<PathBuf> {
+    if task.command.shell {
+        return None;
+    }
+    let program = Path::new(task.command.argv.first()?);
+    if program.components().count() > 1 {
+        return Some(match &task.cwd {
+            Some(cwd) => cwd.join(program),
+            None => program.to_path_buf(),
+        });
+    }
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(program))
+        .find(|path| path.is_file())
+}
+
+/// Frames (`#0  0x... in parse_header (buf=0x0) at src/parse.c:88`) from
+/// gdb output, without their numbers and addresses
+fn parse_backtrace(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let (number, frame) = line.strip_prefix('#')?.split_once(char::is_whitespace)?;
+            number.parse::<u32>().ok()?;
+            let frame = frame.trim_start();
+            let frame = match frame.split_once(" in ") {
+                Some((address, rest)) if address.starts_with("0x") => rest,
+                _ => frame,
+            };
+            Some(frame.to_string())
+        })
+        .take(FRAMES)
+        .collect()
+}
+
+/// Whether the default action of `signal` dumps core, so a shell that
+/// reports a child's exit code `128 + signal` may have left one behind
+#[cfg(unix)]
+pub fn dumps_core(signal: i32) -> bool {
+    use nix::sys::signal::Signal::*;
+    matches!(
+        nix::sys::signal::Signal::try_from(signal),
+        Ok(SIGQUIT
+            | SIGILL
+            | SIGTRAP
+            | SIGABRT
+            | SIGBUS
+            | SIGFPE
+            | SIGSEGV
+            | SIGSYS
+            | SIGXCPU
+            | SIGXFSZ)
+    )
+}
+
+#[cfg(not(unix))]
+pub fn dumps_core(_signal: i32) -> bool {
+    false
+}
+
+/// Name of signal `signal` (`SIGSEGV`), or its number
+#[cfg(unix)]
+pub fn signal_name(signal: i32) -> String {
+    nix::sys::signal::Signal::try_from(signal)
+        .map(|signal| signal.as_str().to_string())
+        .unwrap_or_else(|_| format!("signal {signal}"))
+}
+
+#[cfg(not(unix))]
+pub fn signal_name(signal: i32) -> String {
+    format!("signal {signal}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_coredumpctl_info() {
+        let info = "\
+           PID: 4242 (crashy)
+        Signal: 11 (SEGV)
+     Timestamp: Fri 2026-10-16 14:02:11 CEST (1min ago)
+    Executable: /home/me/crashy
+       Storage: /var/lib/systemd/coredump/core.crashy.1000.4242.zst (present)
+  Size on Disk: 120.5K
+";
+        assert_eq!(
+            parse_coredumpctl_info(info),
+            (
+                Some(11),
+                Some("/var/lib/systemd/coredump/core.crashy.1000.4242.zst".into())
+            )
+        );
+        let missing = "        Signal: 6 (ABRT)\n       Storage: none\n";
+        assert_eq!(parse_coredumpctl_info(missing), (Some(6), None));
+    }
+
+    #[test]
+    fn test_core_file_follows_the_pattern() {
+        let dir = tempfile::tempdir().unwrap();
+        let before = Utc::now() - chrono::Duration::seconds(5);
+        std::fs::write(dir.path().join("core.crashy.4242.1760616131"), b"").unwrap();
+        std::fs::write(dir.path().join("core.crashy.99.1760616131"), b"").unwrap();
+        let pattern = format!("{}/core.%e.%p.%t", dir.path().display());
+        assert_eq!(
+            core_file(&pattern, false, 4242, None, before),
+            Some(dir.path().join("core.crashy.4242.1760616131"))
+        );
+        assert_eq!(core_file(&pattern, false, 7, None, before), None);
+
+        // Relative patterns are in the task's directory; an old core isn't
+        // this crash's
+        std::fs::write(dir.path().join("core.4242"), b"").unwrap();
+        assert_eq!(
+            core_file("core", true, 4242, Some(dir.path()), before),
+            Some(dir.path().join("core.4242"))
+        );
+        let later = Utc::now() + chrono::Duration::seconds(5);
+        assert_eq!(core_file("core", true, 4242, Some(dir.path()), later), None);
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("core.*.42.*", "core.crashy.42.1760"));
+        assert!(wildcard_match("core", "core"));
+        assert!(!wildcard_match("core", "core.42"));
+        assert!(!wildcard_match("core.*.42", "core.crashy.421"));
+        assert_eq!(expand_pattern("core.%e.%p.100%%", 42), "core.*.42.100%");
+    }
+
+    #[test]
+    fn test_parse_backtrace() {
+        let output = "\
+[New LWP 4242]
+Core was generated by `./crashy'.
+Program terminated with signal SIGSEGV, Segmentation fault.
+#0  0x000055d1c2a3b129 in parse_header (buf=0x0) at src/parse.c:88
+#1  0x000055d1c2a3b1f0 in main (argc=1, argv=0x7ffd) at src/main.c:12
+";
+        assert_eq!(
+            parse_backtrace(output),
+            [
+                "parse_header (buf=0x0) at src/parse.c:88",
+                "main (argc=1, argv=0x7ffd) at src/main.c:12"
+            ]
+        );
+        assert_eq!(signal_name(11), "SIGSEGV");
+        assert!(dumps_core(11) && dumps_core(6));
+        assert!(!dumps_core(9) && !dumps_core(2));
+    }
+}
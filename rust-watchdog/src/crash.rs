@@ -0,0 +1,152 @@
+use crate::output::recent_events;
+use crate::registry::RegistryStats;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Latest registry stats seen by the daemon, captured into crash reports
+static LAST_STATS: Mutex<Option<RegistryStats>> = Mutex::new(None);
+
+/// Crash report written by the daemon's panic hook
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashReport {
+    pub timestamp: DateTime<Utc>,
+    pub version: String,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    pub backtrace: String,
+    pub recent_events: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub registry_stats: Option<RegistryStats>,
+    pub config: BTreeMap<String, String>,
+}
+
+/// Crash report directory for a registry (`.claude/crash/` by default)
+pub fn crash_dir_for(registry_path: &Path) -> PathBuf {
+    registry_path
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join("crash")
+}
+
+/// Install a panic hook that writes a crash report before the process dies.
+///
+/// `config` is the daemon configuration to include in every report. The
+/// previous hook still runs afterwards, so the panic message reaches stderr.
+pub fn install_panic_hook(crash_dir: PathBuf, config: BTreeMap<String, String>) {
+    let previous = std::panic::take_hook();
+
+    std::panic::set_hook(Box::new(move |info| {
+        let message = info
+            .payload()
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| info.payload().downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "<non-string panic payload>".to_string());
+
+        let report = CrashReport {
+            timestamp: Utc::now(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            message,
+            location: info.location().map(|l| l.to_string()),
+            backtrace: std::backtrace::Backtrace::force_capture().to_string(),
+            recent_events: recent_events(),
+            registry_stats: LAST_STATS.try_lock().ok().and_then(|s| s.clone()),
+            config: config.clone(),
+        };
+
+        match write_report(&crash_dir, &report) {
+            Ok(path) => eprintln!("💥 Crash report written to {}", path.display()),
+            Err(e) => eprintln!("💥 Failed to write crash report: {e:#}"),
+        }
+
+        previous(info);
+    }));
+}
+
+/// Remember the latest registry stats for inclusion in crash reports
+pub fn update_stats(stats: &RegistryStats) {
+    if let Ok(mut last) = LAST_STATS.lock() {
+        *last = Some(stats.clone());
+    }
+}
+
+/// Write a crash report as `crash-<timestamp>.json` (0600, like the registry)
+pub fn write_report(crash_dir: &Path, report: &CrashReport) -> Result<PathBuf> {
+    fs::create_dir_all(crash_dir).context("Failed to create crash directory")?;
+
+    let path = crash_dir.join(format!(
+        "crash-{}.json",
+        report.timestamp.format("%Y%m%dT%H%M%S%.3fZ")
+    ));
+    let json = serde_json::to_string_pretty(report).context("Failed to serialize crash report")?;
+    fs::write(&path, json).context("Failed to write crash report")?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600))
+            .context("Failed to set crash report permissions")?;
+    }
+
+    Ok(path)
+}
+
+/// Crash reports in `crash_dir`, newest first (unreadable files are skipped)
+pub fn recent_crashes(crash_dir: &Path, limit: usize) -> Result<Vec<(PathBuf, CrashReport)>> {
+    if !crash_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut reports: Vec<(PathBuf, CrashReport)> = fs::read_dir(crash_dir)
+        .context("Failed to read crash directory")?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|path| {
+            let content = fs::read_to_string(&path).ok()?;
+            let report = serde_json::from_str(&content).ok()?;
+            Some((path, report))
+        })
+        .collect();
+
+    reports.sort_by_key(|(_, report)| std::cmp::Reverse(report.timestamp));
+    reports.truncate(limit);
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_and_list_reports() {
+        let dir = std::env::temp_dir().join("test_crash_reports");
+        let _ = fs::remove_dir_all(&dir);
+
+        for (i, msg) in ["first", "second"].iter().enumerate() {
+            let report = CrashReport {
+                timestamp: Utc::now() + chrono::Duration::seconds(i as i64),
+                version: "test".to_string(),
+                message: msg.to_string(),
+                location: Some("src/main.rs:1:1".to_string()),
+                backtrace: String::new(),
+                recent_events: vec!["🔍 Watchdog check".to_string()],
+                registry_stats: None,
+                config: BTreeMap::new(),
+            };
+            write_report(&dir, &report).unwrap();
+        }
+        fs::write(dir.join("garbage.json"), "not json").unwrap();
+
+        let crashes = recent_crashes(&dir, 5).unwrap();
+        assert_eq!(crashes.len(), 2);
+        assert_eq!(crashes[0].1.message, "second");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
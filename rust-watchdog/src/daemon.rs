@@ -0,0 +1,1444 @@
+//! One watchdog check, reusable outside the CLI.
+//!
+//! [`Watchdog::check`] loads the registry, reaps dead and zombie tasks,
+//! confirms containers, flags tasks over their memory or CPU threshold,
+//! records their recent usage for `report` ([`crate::samples`]),
+//! restarts tasks whose restart policy asks for it,
+//! probes readiness, promotes queued tasks (starting those of a `swarm`)
+//! and flags duration anomalies.
+//! It prints what it finds like the `run` daemon always has, and publishes
+//! the same findings as [`WatchdogEvent`]s to every subscriber:
+//!
+//! ```no_run
+//! # async fn demo() -> anyhow::Result<()> {
+//! use task_watchdog::daemon::Watchdog;
+//! use task_watchdog::queue::Fairness;
+//!
+//! let mut watchdog = Watchdog::new(".claude/process_registry.json", Fairness::default(), 10);
+//! let mut events = watchdog.subscribe();
+//! tokio::spawn(async move {
+//!     while let Ok(event) = events.recv().await {
+//!         println!("{event:?}");
+//!     }
+//! });
+//! watchdog.check(true).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`Watchdog::subscribe_alerts`] gets the subset worth notifying about,
+//! deduplicated across checks and escalated when a condition persists.
+
+use crate::alerts::{Alert, AlertTracker};
+use crate::anomaly;
+use crate::capacity::Resources;
+use crate::capture::OutputBuffers;
+use crate::coredump;
+use crate::docker::{ContainerExit, DockerManager};
+use crate::done_files;
+use crate::events::WatchdogEvent;
+use crate::lifecycle::{Actor, EventLog};
+use crate::logs;
+use crate::monitor::SystemMonitor;
+use crate::output::format_duration;
+use crate::ports;
+use crate::process::{GroupUsage, ProcessManager};
+use crate::queue::Fairness;
+use crate::readiness;
+use crate::registry::{RegistryManager, RegistryStats};
+use crate::samples::{self, ResourceHistory};
+use crate::tasks;
+use crate::triage::{self, Evidence};
+use crate::types::{ExecutionMode, TaskInfo, TaskResult, TaskStatus, RESULT_FILE};
+use crate::{debug, info, verbose, warn};
+use anyhow::{bail, Context, Result};
+use fs2::FileExt;
+#[cfg(unix)]
+use nix::sys::signal::{kill, Signal};
+#[cfg(unix)]
+use nix::unistd::Pid;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+/// Events buffered per subscriber before the slowest one starts lagging
+pub const EVENT_CAPACITY: usize = 256;
+
+/// How long `run --takeover` waits for the old daemon to finish its check
+pub const TAKEOVER_TIMEOUT_SECS: u64 = 120;
+
+/// Watchdog state that persists between checks
+pub struct Watchdog {
+    registry: RegistryManager,
+    docker: Option<DockerManager>,
+    user: Option<String>,
+    fairness: Fairness,
+    headroom: u8,
+    /// Task IDs seen by the previous check (`None` before the first one)
+    known: Option<HashSet<String>>,
+    /// Tasks whose port was found closed, so it is reported once
+    closed_ports: HashSet<String>,
+    events: broadcast::Sender<WatchdogEvent>,
+    /// Turns the events of successive checks into alerts
+    tracker: AlertTracker,
+    alerts: broadcast::Sender<Alert>,
+    /// Where events are recorded for `replay`, if anywhere
+    event_log: Option<EventLog>,
+    /// Kill tasks over their `--max-memory` / `--max-cpu` instead of
+    /// only warning
+    enforce_limits: bool,
+    /// Daemon-wide limits on each task's processes
+    process_limits: ProcessLimits,
+    /// What each running task used as of the previous check
+    last_usage: HashMap<String, (Instant, GroupUsage)>,
+    /// Recent usage of the running tasks, saved after every check
+    history: ResourceHistory,
+    /// Samples of the processes watched with `watch-pid`, and of the
+    /// daemon itself, kept between checks
+    monitor: SystemMonitor,
+    /// Recent output of the tasks it started that have no log file
+    output: OutputBuffers,
+}
+
+/// How many processes a task may run and how fast it may start more
+/// (catches a runaway test runner or fork bomb), and how many open files
+/// and threads they may hold (catches leaks)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProcessLimits {
+    /// For tasks without their own `--max-processes`
+    pub max: Option<u64>,
+    /// Processes a task may add per minute
+    pub per_minute: Option<u64>,
+    /// For tasks without their own `--max-open-files`
+    pub open_files: Option<u64>,
+    /// For tasks without their own `--max-threads`
+    pub threads: Option<u64>,
+}
+
+impl Watchdog {
+    /// Watch the registry at `registry_path`. Docker is used if reachable.
+    pub fn new<P: AsRef<Path>>(registry_path: P, fairness: Fairness, headroom: u8) -> Self {
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        let (alerts, _) = broadcast::channel(EVENT_CAPACITY);
+        // Carry on the trends of a previous daemon
+        let history = ResourceHistory::load(&samples::samples_path(registry_path.as_ref()))
+            .unwrap_or_default();
+        let mut registry = RegistryManager::new(registry_path);
+        registry.set_actor(Actor::Watchdog);
+        Self {
+            registry,
+            docker: DockerManager::new(),
+            user: ProcessManager::current_user(),
+            fairness,
+            headroom,
+            known: None,
+            closed_ports: HashSet::new(),
+            events,
+            tracker: AlertTracker::new(Vec::new()),
+            alerts,
+            event_log: None,
+            enforce_limits: false,
+            process_limits: ProcessLimits::default(),
+            last_usage: HashMap::new(),
+            history,
+            monitor: SystemMonitor::new(),
+            output: OutputBuffers::default(),
+        }
+    }
+
+    /// Kill tasks that go over their `--max-memory` or `--max-cpu` (by
+    /// default they are only reported)
+    pub fn set_enforce_limits(&mut self, enforce: bool) {
+        self.enforce_limits = enforce;
+    }
+
+    /// Flag tasks that run more processes, or start them faster, than
+    /// `limits` allow
+    pub fn set_process_limits(&mut self, limits: ProcessLimits) {
+        self.process_limits = limits;
+    }
+
+    /// Record the events that change no status in `event_log` from now
+    /// on, next to the transitions
+    pub fn set_event_log(&mut self, event_log: EventLog) {
+        self.event_log = Some(event_log);
+    }
+
+    /// Alert again when a condition outlasts each of these durations
+    /// (without any, an ongoing condition alerts once)
+    pub fn set_escalation(&mut self, escalate_after: Vec<Duration>) {
+        let escalate_after = escalate_after
+            .into_iter()
+            .filter_map(|after| chrono::Duration::from_std(after).ok())
+            .collect();
+        self.tracker = AlertTracker::new(escalate_after);
+    }
+
+    /// Receive alerts from now on: events worth notifying about, once per
+    /// ongoing condition and again when it escalates (see [`AlertTracker`])
+    pub fn subscribe_alerts(&self) -> broadcast::Receiver<Alert> {
+        self.alerts.subscribe()
+    }
+
+    /// Receive every event published from now on. A subscriber that falls
+    /// more than [`EVENT_CAPACITY`] events behind gets `RecvError::Lagged`.
+    pub fn subscribe(&self) -> broadcast::Receiver<WatchdogEvent> {
+        self.events.subscribe()
+    }
+
+    /// Where events are published, for subscribers outside the check loop
+    /// (the gRPC API's `StreamEvents`)
+    pub fn event_sender(&self) -> broadcast::Sender<WatchdogEvent> {
+        self.events.clone()
+    }
+
+    /// Output of the tasks it started without a log file, for the socket
+    /// other processes read it from (see [`crate::capture`])
+    pub fn output(&self) -> OutputBuffers {
+        self.output.clone()
+    }
+
+    pub fn docker_available(&self) -> bool {
+        self.docker.is_some()
+    }
+
+    /// Resident memory of the daemon, sampled now
+    pub fn self_memory_kb(&mut self) -> u64 {
+        self.monitor.self_memory_kb()
+    }
+
+    /// Registry as of the last check
+    pub fn registry(&self) -> &RegistryManager {
+        &self.registry
+    }
+
+    /// Register many tasks in one locked mutation (see
+    /// [`RegistryManager::admit_batch`]), using this watchdog's headroom.
+    /// Each registered task is published as `TaskRegistered` right away.
+    pub fn register_batch(
+        &mut self,
+        tasks: Vec<(String, TaskInfo)>,
+        queue_if_full: bool,
+    ) -> Result<Vec<(String, Result<TaskStatus>)>> {
+        let reservable = Resources::machine().minus_headroom(self.headroom);
+        let results = self
+            .registry
+            .admit_batch(tasks, reservable, queue_if_full)?;
+        for (task_id, result) in &results {
+            if result.is_ok() {
+                if let Some(known) = &mut self.known {
+                    known.insert(task_id.clone());
+                }
+                self.emit(WatchdogEvent::TaskRegistered {
+                    task_id: task_id.clone(),
+                });
+            }
+        }
+        Ok(results)
+    }
+
+    fn emit(&mut self, event: WatchdogEvent) {
+        if let Some(event_log) = &mut self.event_log {
+            if let Err(e) = event_log.record(&event) {
+                warn!(
+                    "⚠️  Recording events in the lifecycle journal disabled: {:#}",
+                    e
+                );
+                self.event_log = None;
+            }
+        }
+        if let Some(alert) = self.tracker.observe(&event, chrono::Utc::now()) {
+            let _ = self.alerts.send(alert);
+        }
+        // No subscribers is not an error
+        let _ = self.events.send(event);
+    }
+
+    /// Run one check. Queued tasks are only promoted when `promote` is set
+    /// (the daemon holds them back while the CPU is throttling). Returns
+    /// the counts after this check's changes.
+    pub async fn check(&mut self, promote: bool) -> Result<RegistryStats> {
+        // Load latest registry state; only newly archived history is read
+        self.registry.load()?;
+        self.registry.load_history()?;
+        self.output
+            .retain(|task_id| self.registry.get_task(task_id).is_some());
+        self.announce_new_tasks();
+        self.check_watched().await?;
+        self.check_ports();
+        self.check_done_files()?;
+        self.rotate_logs();
+
+        // Sample CPU time while processes still exist to be measured
+        let usage = self.sample_usage().await?;
+        self.check_thresholds(usage).await?;
+        self.enforce_timeouts().await?;
+
+        // Find orphans in native processes. On a shared registry, other
+        // users' tasks are left to their own watchdog.
+        let mut orphan_report = self.registry.find_orphans();
+        orphan_report.retain(|id| {
+            self.registry
+                .get_task(id)
+                .is_none_or(|task| task.is_owned_by(self.user.as_deref()))
+        });
+        // Wrote its done file and exited since the done files were looked
+        // at: not a death, the next check finishes it
+        orphan_report.dead_processes.retain(|id| {
+            self.registry
+                .get_task(id)
+                .is_none_or(|task| done_files::outcome(task).is_none())
+        });
+
+        // Check Docker containers (tasks become unknown if Docker is unreachable)
+        self.check_docker_tasks().await?;
+
+        // Report findings
+        if orphan_report.has_issues() {
+            warn!("\n⚠️  Found {} issues:", orphan_report.total_issues());
+
+            if !orphan_report.dead_processes.is_empty() {
+                warn!(
+                    "\n💀 Dead Processes ({}):",
+                    orphan_report.dead_processes.len()
+                );
+                for task_id in &orphan_report.dead_processes {
+                    let task = self.registry.get_task(task_id).cloned();
+                    if let Some(task) = &task {
+                        warn!(task: task_id; "  {} - {}", task_id, task.command);
+                    }
+                    // A crash may have left a core to point at
+                    let coredump = match &task {
+                        Some(task) => coredump::collect(task, None).await,
+                        None => None,
+                    };
+                    let exit_code = coredump
+                        .as_ref()
+                        .and_then(|dump| dump.signal)
+                        .map(|signal| 128 + i64::from(signal));
+                    let log_file = task.as_ref().and_then(|task| task.log_file.as_deref());
+                    let hint = coredump.as_ref().map(|dump| {
+                        let output = match log_file {
+                            Some(log) => logs::tail(log, logs::TRIAGE_LINES).ok(),
+                            None => self.output.tail(task_id, logs::TRIAGE_LINES),
+                        };
+                        coredump::hint(dump, output.as_deref())
+                    });
+                    if let Some(hint) = &hint {
+                        warn!(task: task_id; "     💥 {}", hint);
+                    }
+                    let excerpt = match log_file {
+                        Some(log) => logs::failure_excerpt(log),
+                        None => self
+                            .output
+                            .text(task_id)
+                            .and_then(|output| crate::excerpt::extract(&output)),
+                    };
+                    // Mark as failed
+                    self.registry.with_transaction(|txn| {
+                        txn.mark_failed(task_id);
+                        if let (Some(dump), Some(hint)) = (coredump.clone(), hint.clone()) {
+                            txn.set_triage_hint(task_id, hint);
+                            txn.set_coredump(task_id, dump);
+                        }
+                        if let Some(excerpt) = excerpt.clone() {
+                            txn.set_failure_excerpt(task_id, excerpt);
+                        }
+                        Ok(())
+                    })?;
+                    self.emit(WatchdogEvent::TaskDied {
+                        task_id: task_id.clone(),
+                        exit_code,
+                        triage_hint: hint,
+                        failure_excerpt: excerpt,
+                    });
+                }
+            }
+
+            if !orphan_report.zombie_processes.is_empty() {
+                warn!(
+                    "\n🧟 Zombie Processes ({}):",
+                    orphan_report.zombie_processes.len()
+                );
+                for task_id in &orphan_report.zombie_processes {
+                    if let Some(task) = self.registry.get_task(task_id).cloned() {
+                        warn!(task: task_id; "  {} - {}", task_id, task.command);
+
+                        // Kill zombie
+                        match &task.mode {
+                            ExecutionMode::Native => {
+                                if let Some(native) = &task.native {
+                                    let _ = ProcessManager::kill_task(native);
+                                }
+                            }
+                            ExecutionMode::Docker => {
+                                if let (Some(docker_client), Some(docker_info)) =
+                                    (&self.docker, &task.docker)
+                                {
+                                    match docker_client
+                                        .stop_container(
+                                            &docker_info.container_id,
+                                            docker_info.stop_timeout(),
+                                        )
+                                        .await
+                                    {
+                                        Ok(outcome) => {
+                                            verbose!(task: task_id; "   Container {}", outcome)
+                                        }
+                                        Err(e) => {
+                                            warn!(task: task_id; "   ⚠️  {:#}", e)
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                        self.registry.mark_killed(
+                            task_id,
+                            "zombie sweep: still running after the task finished",
+                        )?;
+                        self.emit(WatchdogEvent::ZombieDetected {
+                            task_id: task_id.clone(),
+                        });
+                    }
+                }
+            }
+
+            if !orphan_report.unknown.is_empty() {
+                warn!(
+                    "\n❓ Unknown (no process metadata) ({}):",
+                    orphan_report.unknown.len()
+                );
+                for task_id in &orphan_report.unknown {
+                    if let Some(task) = self.registry.get_task(task_id) {
+                        warn!(task: task_id; "  {} - {}", task_id, task.command);
+                    }
+                    self.registry.set_status(task_id, TaskStatus::Unknown)?;
+                    self.emit(WatchdogEvent::TaskUnknown {
+                        task_id: task_id.clone(),
+                        reason: "no process metadata".to_string(),
+                    });
+                }
+            }
+        }
+
+        self.restart_finished().await;
+
+        // Confirm servers that dependent tasks wait for
+        for task_id in readiness::probe_unready(&mut self.registry, None).await? {
+            self.emit(WatchdogEvent::TaskReady { task_id });
+        }
+
+        // Start queued tasks that fit now
+        if promote {
+            for (task_id, dependency) in self.registry.fail_blocked()? {
+                warn!(
+                    task: &task_id;
+                    "⛔ Task {} failed: {} finished without becoming ready",
+                    task_id,
+                    dependency
+                );
+                self.emit(WatchdogEvent::DependencyFailed {
+                    task_id,
+                    dependency,
+                });
+            }
+
+            let reservable = Resources::machine().minus_headroom(self.headroom);
+            let promoted = self.registry.promote_queued(&self.fairness, reservable)?;
+            if !promoted.is_empty() {
+                info!("\n▶️  Started from queue ({}):", promoted.len());
+                for task_id in promoted {
+                    let group = self
+                        .registry
+                        .get_task(&task_id)
+                        .and_then(|task| self.fairness.group_of(task))
+                        .unwrap_or("-");
+                    info!(task: &task_id; "  {} [{}={}]", task_id, self.fairness.label, group);
+                    // A swarm's tasks are started here; others by whoever queued them
+                    if self
+                        .registry
+                        .get_task(&task_id)
+                        .is_some_and(|task| task.jobs.is_some())
+                    {
+                        match tasks::start_promoted(&mut self.registry, &task_id) {
+                            Ok(native) => {
+                                info!(task: &task_id; "     🚀 Started: PID {}", native.pid)
+                            }
+                            Err(e) => {
+                                warn!(task: &task_id; "     ❌ Could not start {}: {:#}", task_id, e);
+                                continue;
+                            }
+                        }
+                    }
+                    self.emit(WatchdogEvent::TaskStarted {
+                        task_id,
+                        from_queue: true,
+                    });
+                }
+            }
+        }
+
+        // Keep the registry file down to unfinished and recent tasks
+        let archived = self.registry.archive_finished()?;
+        if archived > 0 {
+            verbose!("\n🗄️  Archived {} finished tasks", archived);
+            self.registry.load_history()?;
+        }
+
+        let anomalies = anomaly::find_anomalies(self.registry.registry(), chrono::Utc::now());
+        if !anomalies.is_empty() {
+            warn!("\n🐢 Running unusually long ({}):", anomalies.len());
+            for a in anomalies {
+                warn!(
+                    task: &a.task_id;
+                    "  {} - {} so far, {:.1}x its usual p95 of {}",
+                    a.task_id,
+                    format_duration(a.running_secs),
+                    a.ratio(),
+                    format_duration(a.p95_secs)
+                );
+                self.emit(WatchdogEvent::DurationAnomaly {
+                    task_id: a.task_id,
+                    running_secs: a.running_secs,
+                    p95_secs: a.p95_secs,
+                });
+            }
+        }
+
+        for key in self.tracker.end_check() {
+            debug!("   Cleared: {}", key);
+        }
+
+        let stats = self.registry.stats();
+        self.emit(WatchdogEvent::CheckCompleted {
+            stats: stats.clone(),
+        });
+        Ok(stats)
+    }
+
+    /// Finish tasks whose done file appeared, before their exited
+    /// processes could be taken for deaths
+    fn check_done_files(&mut self) -> Result<()> {
+        let mut finished = Vec::new();
+        for (task_id, task) in self.registry.active_tasks() {
+            if let Some((status, exit_code)) = done_files::outcome(task) {
+                let excerpt = match status {
+                    TaskStatus::Failed => task.log_file.as_deref().and_then(logs::failure_excerpt),
+                    _ => None,
+                };
+                finished.push((task_id.clone(), status, exit_code, excerpt));
+            }
+        }
+        if finished.is_empty() {
+            return Ok(());
+        }
+
+        let mut events = Vec::new();
+        self.registry.with_transaction(|txn| {
+            for (task_id, status, exit_code, excerpt) in &finished {
+                txn.set_status(task_id, status.clone());
+                if *status == TaskStatus::Completed {
+                    info!(task: task_id; "🏁 Task {} done (done file)", task_id);
+                    events.push(WatchdogEvent::TaskCompleted {
+                        task_id: task_id.clone(),
+                    });
+                    continue;
+                }
+                let hint = triage::hint(Evidence {
+                    exit_code: *exit_code,
+                    ..Default::default()
+                });
+                warn!(task: task_id; "❌ Task {} failed with exit code {} (done file)", task_id, exit_code.unwrap_or_default());
+                if let Some(hint) = &hint {
+                    txn.set_triage_hint(task_id, hint.clone());
+                }
+                if let Some(excerpt) = excerpt {
+                    txn.set_failure_excerpt(task_id, excerpt.clone());
+                }
+                events.push(WatchdogEvent::TaskDied {
+                    task_id: task_id.clone(),
+                    exit_code: *exit_code,
+                    triage_hint: hint,
+                    failure_excerpt: excerpt.clone(),
+                });
+            }
+            Ok(())
+        })?;
+        for event in events {
+            self.emit(event);
+        }
+        Ok(())
+    }
+
+    /// Done files of unfinished tasks
+    pub fn done_files(&self) -> Vec<PathBuf> {
+        self.registry
+            .active_tasks()
+            .iter()
+            .filter_map(|(_, task)| task.done_file.clone())
+            .collect()
+    }
+
+    /// PIDs of running native tasks and watched processes, whose exits
+    /// should wake the daemon
+    pub fn exit_pids(&self) -> Vec<i32> {
+        let tasks = self
+            .registry
+            .active_tasks()
+            .into_iter()
+            .filter_map(|(_, task)| task.native.as_ref().map(|native| native.pid));
+        let watched = self.registry.registry().watched.values().map(|p| p.pid);
+        tasks.chain(watched).collect()
+    }
+
+    /// Rotate the logs of unfinished tasks that grew too large
+    fn rotate_logs(&self) {
+        for (task_id, task) in self.registry.active_tasks() {
+            let Some(path) = &task.log_file else {
+                continue;
+            };
+            match logs::rotate_if_large(path, logs::MAX_LOG_BYTES, logs::KEEP_ROTATED) {
+                Ok(true) => verbose!(task: task_id; "🗞️  Rotated log of task {}", task_id),
+                Ok(false) => {}
+                Err(e) => warn!(task: task_id; "⚠️  Failed to rotate {}: {}", path.display(), e),
+            }
+        }
+    }
+
+    /// Report adopted tasks whose port stopped (or resumed) being served
+    fn check_ports(&mut self) {
+        let mut closed = Vec::new();
+        for (task_id, task) in self.registry.running_tasks() {
+            let Some(port) = task.port else {
+                continue;
+            };
+            match ports::is_listening(port) {
+                Some(false) if !self.closed_ports.contains(task_id) => {
+                    warn!(task: task_id; "🔌 Task {} stopped serving port {}", task_id, port);
+                    closed.push((task_id.clone(), port));
+                }
+                Some(true) if self.closed_ports.contains(task_id) => {
+                    info!(task: task_id; "🔌 Task {} is serving port {} again", task_id, port);
+                    self.closed_ports.remove(task_id);
+                }
+                _ => {}
+            }
+        }
+        for (task_id, port) in closed {
+            self.closed_ports.insert(task_id.clone());
+            self.emit(WatchdogEvent::PortClosed { task_id, port });
+        }
+    }
+
+    /// Report on processes watched with `watch-pid`; ones that exited are
+    /// announced and dropped
+    async fn check_watched(&mut self) -> Result<()> {
+        let watched = &self.registry.registry().watched;
+        let pids: Vec<i32> = watched.values().map(|process| process.pid).collect();
+        self.monitor.refresh(&pids).await;
+        let mut exited = Vec::new();
+        for (name, process) in watched {
+            let alive = ProcessManager::validate_process(process.pid, &process.start_time)
+                && ProcessManager::is_alive(process.pid);
+            if !alive {
+                exited.push((name.clone(), process.pid));
+                continue;
+            }
+            if let Some(usage) = self.monitor.usage(process.pid) {
+                verbose!(
+                    "   👁️  {} (PID {}): {:.1}% CPU, {}MB",
+                    name,
+                    process.pid,
+                    usage.cpu_percent,
+                    usage.memory_kb / 1024
+                );
+            }
+        }
+
+        for (name, pid) in exited {
+            warn!("💀 Watched process {} (PID {}) exited", name, pid);
+            self.registry.unwatch(&name)?;
+            self.emit(WatchdogEvent::WatchedProcessExited { name, pid });
+        }
+        Ok(())
+    }
+
+    /// Publish tasks that appeared since the previous check. Tasks already
+    /// there on the first check are taken as known.
+    fn announce_new_tasks(&mut self) {
+        let current: HashSet<String> = self.registry.registry().tasks.keys().cloned().collect();
+        if let Some(known) = &self.known {
+            let mut new: Vec<String> = current.difference(known).cloned().collect();
+            new.sort();
+            for task_id in new {
+                self.emit(WatchdogEvent::TaskRegistered { task_id });
+            }
+        }
+        self.known = Some(current);
+    }
+
+    /// Terminate this user's tasks that have run past their `--timeout`
+    async fn enforce_timeouts(&mut self) -> Result<()> {
+        let now = chrono::Utc::now();
+        let overdue: Vec<(String, u64)> = self
+            .registry
+            .active_tasks()
+            .into_iter()
+            .filter(|(_, task)| task.is_past_timeout(now) && task.is_owned_by(self.user.as_deref()))
+            .filter_map(|(task_id, task)| Some((task_id.clone(), task.timeout_secs?)))
+            .collect();
+        for (task_id, timeout_secs) in overdue {
+            tasks::time_out(&mut self.registry, &task_id).await?;
+            self.emit(WatchdogEvent::TimedOut {
+                task_id,
+                timeout_secs,
+            });
+        }
+        Ok(())
+    }
+
+    /// Start this user's finished tasks again where their restart policy
+    /// asks for it. One that can't be started is tried again next check.
+    async fn restart_finished(&mut self) {
+        let mut due: Vec<String> = self
+            .registry
+            .registry()
+            .tasks
+            .iter()
+            .filter(|(_, task)| {
+                task.restart.restarts_after(&task.status) && task.is_owned_by(self.user.as_deref())
+            })
+            .map(|(task_id, _)| task_id.clone())
+            .collect();
+        due.sort();
+        for task_id in due {
+            // Without a log file the end of its output is kept in memory too
+            let output = Some(&self.output);
+            match tasks::restart(&mut self.registry, &task_id, self.docker.as_ref(), output).await {
+                Ok(restarts) => {
+                    info!(task: &task_id; "🔁 Restarted task {} (restart #{})", task_id, restarts);
+                    self.emit(WatchdogEvent::TaskRestarted { task_id, restarts });
+                }
+                Err(e) => warn!(task: &task_id; "⚠️  Could not restart {}: {:#}", task_id, e),
+            }
+        }
+    }
+
+    /// Record the cumulative CPU time of every started task (native process
+    /// groups and running containers), and add what each of them uses to
+    /// the resource history. Returns it.
+    async fn sample_usage(&mut self) -> Result<Vec<(String, GroupUsage)>> {
+        let mut usage = Vec::new();
+        for (task_id, task) in self.registry.active_tasks() {
+            let sample = match (&task.mode, &task.native, &task.docker, &self.docker) {
+                (ExecutionMode::Native, Some(native), _, _) => {
+                    ProcessManager::group_usage(native.pgid)
+                }
+                (ExecutionMode::Docker, _, Some(info), Some(docker)) => docker
+                    .get_stats(&info.container_id)
+                    .await
+                    .ok()
+                    .map(|s| GroupUsage {
+                        cpu_seconds: s.cpu_seconds,
+                        memory_kb: s.memory_mb * 1024,
+                        processes: s.processes,
+                        threads: 0,
+                        open_files: 0,
+                        io: s.io,
+                    }),
+                _ => None,
+            };
+            if let Some(sample) = sample {
+                usage.push((task_id.clone(), sample));
+            }
+        }
+        let cpu_times: Vec<(String, f64)> = usage
+            .iter()
+            .map(|(task_id, sample)| (task_id.clone(), sample.cpu_seconds))
+            .collect();
+        self.registry.record_cpu_times(&cpu_times)?;
+        self.history.record(&usage, chrono::Utc::now());
+        let path = samples::samples_path(self.registry.path());
+        if let Err(e) = self.history.save(&path) {
+            debug!("Could not save resource history: {:#}", e);
+        }
+        Ok(usage)
+    }
+
+    /// Report this user's running tasks that use more memory, CPU,
+    /// processes, open files or threads than they may (`--max-memory`,
+    /// `--max-cpu`, `--max-processes`, ... and the daemon's process
+    /// limits), or kill them
+    /// under `--enforce-limits`. CPU use and process growth are measured
+    /// since the previous check, so they are first judged on the second
+    /// check that sees the task.
+    async fn check_thresholds(&mut self, usage: Vec<(String, GroupUsage)>) -> Result<()> {
+        let now = Instant::now();
+        let previous = std::mem::take(&mut self.last_usage);
+        let mut over = Vec::new();
+        for (task_id, sample) in usage {
+            let Some(task) = self.registry.get_task(&task_id) else {
+                continue;
+            };
+            if task.status != TaskStatus::Running || !task.is_owned_by(self.user.as_deref()) {
+                continue;
+            }
+            let mut flag = |resource: &'static str, usage: String, limit: String| {
+                over.push((task_id.clone(), resource, usage, limit));
+            };
+            if let Some(max_mb) = task.max_memory_mb {
+                let used_mb = sample.memory_kb / 1024;
+                if used_mb > max_mb {
+                    flag("memory", format!("{used_mb}MB"), format!("{max_mb}MB"));
+                }
+            }
+            if let Some(max) = task.max_processes.or(self.process_limits.max) {
+                if sample.processes > max {
+                    flag("processes", sample.processes.to_string(), max.to_string());
+                }
+            }
+            if let Some(max) = task.max_open_files.or(self.process_limits.open_files) {
+                if sample.open_files > max {
+                    flag("open_files", sample.open_files.to_string(), max.to_string());
+                }
+            }
+            if let Some(max) = task.max_threads.or(self.process_limits.threads) {
+                if sample.threads > max {
+                    flag("threads", sample.threads.to_string(), max.to_string());
+                }
+            }
+            if let Some((then, last)) = previous.get(&task_id) {
+                let elapsed = now.duration_since(*then).as_secs_f64();
+                if elapsed > 0.0 {
+                    let percent = (sample.cpu_seconds - last.cpu_seconds) / elapsed * 100.0;
+                    if let Some(max_percent) = task.max_cpu_percent {
+                        if percent > max_percent {
+                            flag(
+                                "cpu",
+                                format!("{percent:.0}%"),
+                                format!("{max_percent:.0}%"),
+                            );
+                        }
+                    }
+                    let growth = sample.processes.saturating_sub(last.processes) as f64;
+                    let per_minute = growth / elapsed * 60.0;
+                    if let Some(max_rate) = self.process_limits.per_minute {
+                        if per_minute > max_rate as f64 {
+                            flag(
+                                "process_rate",
+                                format!("+{per_minute:.0}/min"),
+                                format!("{max_rate}/min"),
+                            );
+                        }
+                    }
+                }
+            }
+            self.last_usage.insert(task_id, (now, sample));
+        }
+
+        for (task_id, resource, usage, limit) in over {
+            if !self.enforce_limits {
+                warn!(
+                    task: &task_id;
+                    "📈 Task {} is over its {} limit: {} (limit {})",
+                    task_id, resource, usage, limit
+                );
+                self.emit(WatchdogEvent::ThresholdExceeded {
+                    task_id,
+                    resource: resource.to_string(),
+                    usage,
+                    limit,
+                });
+                continue;
+            }
+            // Over several limits: killed for the first
+            if self
+                .registry
+                .get_task(&task_id)
+                .is_some_and(|task| task.status.is_terminal())
+            {
+                continue;
+            }
+            let reason = format!("{resource} limit {limit} exceeded ({usage})");
+            tasks::kill_over_limit(&mut self.registry, &task_id, &reason).await?;
+            self.last_usage.remove(&task_id);
+            self.emit(WatchdogEvent::LimitExceeded {
+                task_id,
+                resource: resource.to_string(),
+                limit,
+            });
+        }
+        Ok(())
+    }
+
+    /// Check Docker containers for unfinished tasks.
+    ///
+    /// Tasks whose container cannot be inspected (Docker unreachable, metadata
+    /// missing) become `unknown` until a later check can see them again.
+    async fn check_docker_tasks(&mut self) -> Result<()> {
+        let docker = match &self.docker {
+            Some(docker) if docker.ping().await => Some(docker),
+            _ => None,
+        };
+
+        // Collect status changes (separate from iteration)
+        let mut updates = Vec::new();
+        let mut killed_tasks = Vec::new();
+        let mut hints = HashMap::new();
+        let mut excerpts = HashMap::new();
+        let mut results = Vec::new();
+        let mut exits = Vec::new();
+        let mut removals = Vec::new();
+        let mut events = Vec::new();
+
+        for (task_id, task) in self.registry.active_tasks() {
+            if task.mode != ExecutionMode::Docker || task.status == TaskStatus::Queued {
+                continue;
+            }
+
+            let (Some(docker), Some(docker_info)) = (docker, &task.docker) else {
+                if task.status != TaskStatus::Unknown {
+                    let reason = if docker.is_none() {
+                        "Docker unreachable"
+                    } else {
+                        "no container metadata"
+                    };
+                    warn!(task: task_id; "❓ Docker task {} is unknown ({})", task_id, reason);
+                    updates.push((task_id.clone(), TaskStatus::Unknown));
+                    events.push(WatchdogEvent::TaskUnknown {
+                        task_id: task_id.clone(),
+                        reason: reason.to_string(),
+                    });
+                }
+                continue;
+            };
+
+            if docker.is_running(&docker_info.container_id).await {
+                if task.status != TaskStatus::Running {
+                    info!(task: task_id; "✅ Docker task {} is running", task_id);
+                    updates.push((task_id.clone(), TaskStatus::Running));
+                    events.push(WatchdogEvent::TaskStarted {
+                        task_id: task_id.clone(),
+                        from_queue: false,
+                    });
+                }
+                continue;
+            }
+
+            let exit = docker.exit_state(&docker_info.container_id).await;
+
+            // Created but not started yet: confirm on a later check. One that
+            // has stopped already (even cleanly) is finished like any other.
+            if task.status == TaskStatus::Starting
+                && matches!(
+                    exit,
+                    Ok(ContainerExit {
+                        finished_at: None,
+                        error: None,
+                        ..
+                    })
+                )
+            {
+                continue;
+            }
+
+            // Its output, for `logs` and triage even once the container is removed
+            let log_path = logs::log_path(self.registry.path(), task_id);
+            let saved = match docker
+                .save_logs(
+                    &docker_info.container_id,
+                    &log_path,
+                    self.registry.file_mode(),
+                )
+                .await
+            {
+                Ok(_) => true,
+                Err(e) => {
+                    verbose!(task: task_id; "   Could not save the logs of {}: {:#}", task_id, e);
+                    false
+                }
+            };
+            let output = saved
+                .then(|| logs::tail(&log_path, logs::TRIAGE_LINES).ok())
+                .flatten();
+
+            let result = read_result(task_id, task);
+            if let Some(result) = &result {
+                results.push((task_id.clone(), result.clone()));
+            }
+            // The container may be gone (auto-remove), but it said how it ended
+            let reported_code = result.as_ref().and_then(|r| r.exit_code);
+            let docker_code = exit.as_ref().ok().and_then(|e| e.exit_code);
+            let summary = result.as_ref().and_then(|r| r.summary.as_deref());
+            if exit.is_ok() {
+                exits.push((task_id.clone(), docker_code));
+            }
+            let oom_killed = matches!(&exit, Ok(exit) if exit.oom_killed);
+            let succeeded =
+                !oom_killed && reported_code == Some(0) && matches!(docker_code, None | Some(0));
+            if docker_info.auto_remove && (succeeded || !docker_info.keep_on_failure) {
+                removals.push((task_id.clone(), docker_info.container_id.clone()));
+            }
+
+            let memory = &docker_info.resource_limits.memory;
+            let exit_code = match exit {
+                Ok(exit) if exit.oom_killed => {
+                    warn!(
+                        task: task_id;
+                        "⚠️  Docker task {} was OOM-killed (limit {})",
+                        task_id, memory
+                    );
+                    killed_tasks.push((
+                        task_id.clone(),
+                        format!("memory limit {memory} exceeded (OOM-killed)"),
+                    ));
+                    events.push(WatchdogEvent::LimitExceeded {
+                        task_id: task_id.clone(),
+                        resource: "memory".to_string(),
+                        limit: memory.clone(),
+                    });
+                    continue;
+                }
+                // A task that reports success has finished, not stopped
+                _ if reported_code == Some(0) && matches!(docker_code, None | Some(0)) => {
+                    info!(
+                        task: task_id;
+                        "🏁 Docker task {} finished: {}",
+                        task_id,
+                        summary.unwrap_or("exit code 0")
+                    );
+                    updates.push((task_id.clone(), TaskStatus::Completed));
+                    events.push(WatchdogEvent::TaskCompleted {
+                        task_id: task_id.clone(),
+                    });
+                    continue;
+                }
+                _ => match docker_code.or(reported_code) {
+                    Some(code) => {
+                        warn!(
+                            task: task_id;
+                            "⚠️  Docker task {} stopped unexpectedly (exit code {})",
+                            task_id, code
+                        );
+                        Some(code)
+                    }
+                    None => {
+                        warn!(task: task_id; "⚠️  Docker task {} stopped unexpectedly", task_id);
+                        None
+                    }
+                },
+            };
+            if let Some(summary) = summary {
+                warn!(task: task_id; "   {}", summary);
+            }
+            let triage_hint = triage::hint(Evidence {
+                exit_code,
+                output: summary.or(output.as_deref()),
+                ..Default::default()
+            });
+            if let Some(hint) = &triage_hint {
+                warn!(task: task_id; "   💡 {}", hint);
+                hints.insert(task_id.clone(), hint.clone());
+            }
+            let failure_excerpt = saved.then(|| logs::failure_excerpt(&log_path)).flatten();
+            if let Some(excerpt) = &failure_excerpt {
+                excerpts.insert(task_id.clone(), excerpt.clone());
+            }
+            updates.push((task_id.clone(), TaskStatus::Failed));
+            events.push(WatchdogEvent::TaskDied {
+                task_id: task_id.clone(),
+                exit_code,
+                triage_hint,
+                failure_excerpt,
+            });
+        }
+
+        // Now update the registry (no borrow conflict)
+        if !results.is_empty() || !exits.is_empty() {
+            self.registry.with_transaction(|txn| {
+                for (task_id, result) in results {
+                    txn.set_result(&task_id, result);
+                }
+                for (task_id, exit_code) in exits {
+                    txn.set_container_exit(&task_id, exit_code);
+                }
+                Ok(())
+            })?;
+        }
+        for (task_id, status) in updates {
+            let hint = hints.remove(&task_id);
+            let excerpt = excerpts.remove(&task_id);
+            self.registry.with_transaction(|txn| {
+                txn.set_status(&task_id, status);
+                if let Some(hint) = hint {
+                    txn.set_triage_hint(&task_id, hint);
+                }
+                if let Some(excerpt) = excerpt {
+                    txn.set_failure_excerpt(&task_id, excerpt);
+                }
+                Ok(())
+            })?;
+        }
+        for (task_id, reason) in killed_tasks {
+            self.registry.mark_killed(&task_id, &reason)?;
+        }
+        // Only now that its logs and exit code are saved
+        for (task_id, container_id) in removals {
+            let Some(docker) = docker else { break };
+            match docker.remove_container(&container_id).await {
+                Ok(true) => verbose!(task: &task_id; "🗑️  Removed the container of {}", task_id),
+                Ok(false) => {}
+                Err(e) => warn!(task: &task_id; "⚠️  Container of {}: {:#}", task_id, e),
+            }
+        }
+        for event in events {
+            self.emit(event);
+        }
+
+        Ok(())
+    }
+}
+
+/// Held by the one daemon watching a registry: an advisory lock on a file
+/// next to the registry that records the daemon's PID. The kernel releases
+/// it however the daemon exits, including across `--restart-on-leak`.
+pub struct DaemonLock {
+    _file: File,
+}
+
+impl DaemonLock {
+    pub fn path_for(registry_path: &Path) -> PathBuf {
+        registry_path.with_extension("daemon.lock")
+    }
+
+    /// Whether a daemon is watching the registry at `registry_path`
+    pub fn is_held(registry_path: &Path) -> bool {
+        File::open(Self::path_for(registry_path)).is_ok_and(|file| file.try_lock_shared().is_err())
+    }
+
+    /// Become the registry's daemon. A daemon already running is an error
+    /// unless `takeover` is set; it is then sent SIGTERM, which it honours
+    /// between checks, and its lock is taken as soon as it exits. Tasks are
+    /// never children of the daemon and all state is in the registry, so
+    /// nothing is orphaned or checked twice across the handoff.
+    pub async fn acquire(registry_path: &Path, takeover: bool) -> Result<Self> {
+        let path = Self::path_for(registry_path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).context("Failed to create registry directory")?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .write(true)
+            .truncate(false)
+            .open(&path)
+            .context("Failed to open daemon lock file")?;
+
+        if file.try_lock_exclusive().is_err() {
+            let mut content = String::new();
+            let _ = file.read_to_string(&mut content);
+            let Ok(pid) = content.trim().parse::<i32>() else {
+                bail!("Another watchdog is already running for this registry");
+            };
+            if !takeover {
+                bail!(
+                    "Another watchdog (PID {}) is already running for this registry; \
+                     use `run --takeover` to replace it",
+                    pid
+                );
+            }
+
+            info!("🤝 Taking over from watchdog PID {}", pid);
+            #[cfg(unix)]
+            kill(Pid::from_raw(pid), Signal::SIGTERM)
+                .with_context(|| format!("Failed to signal watchdog PID {pid}"))?;
+            #[cfg(not(unix))]
+            ProcessManager::kill_process(pid)
+                .with_context(|| format!("Failed to stop watchdog PID {pid}"))?;
+            let deadline = Instant::now() + Duration::from_secs(TAKEOVER_TIMEOUT_SECS);
+            while file.try_lock_exclusive().is_err() {
+                if Instant::now() >= deadline {
+                    bail!(
+                        "Watchdog PID {} did not exit within {}s",
+                        pid,
+                        TAKEOVER_TIMEOUT_SECS
+                    );
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        }
+
+        file.set_len(0)
+            .and_then(|_| file.rewind())
+            .and_then(|_| writeln!(file, "{}", std::process::id()))
+            .context("Failed to write daemon lock file")?;
+        Ok(Self { _file: file })
+    }
+}
+
+/// The [`TaskResult`] a container task left in its workspace (the
+/// directory it was registered from, mounted as `/workspace`) during this run
+fn read_result(task_id: &str, task: &TaskInfo) -> Option<TaskResult> {
+    let path = task.cwd.as_ref()?.join(RESULT_FILE);
+    if !done_files::written_during(&path, task) {
+        return None;
+    }
+    let parsed = fs::read_to_string(&path)
+        .map_err(anyhow::Error::from)
+        .and_then(|content| Ok(serde_json::from_str(&content)?));
+    match parsed {
+        Ok(result) => Some(result),
+        Err(e) => {
+            warn!(task: task_id; "⚠️  Ignoring {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{NativeTask, TaskCommand};
+    use chrono::Utc;
+
+    fn native_task(pid: i32) -> TaskInfo {
+        TaskInfo {
+            native: Some(NativeTask {
+                pid,
+                pgid: pid,
+                start_time: "0".to_string(),
+                env_tag: None,
+                job: None,
+            }),
+            ..TaskInfo::new(TaskCommand::shell("sleep 1000"))
+        }
+    }
+
+    #[test]
+    fn test_read_result() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut task = native_task(1);
+        task.cwd = Some(dir.path().to_path_buf());
+        assert_eq!(read_result("T001", &task), None);
+
+        let path = dir.path().join(RESULT_FILE);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(
+            &path,
+            r#"{"exit_code": 1, "summary": "3 tests failed", "artifacts": ["junit.xml"], "extra": 1}"#,
+        )
+        .unwrap();
+        let result = read_result("T001", &task).unwrap();
+        assert_eq!(result.exit_code, Some(1));
+        assert_eq!(result.artifacts, ["junit.xml"]);
+
+        // Left over from an earlier run
+        task.started_at = Utc::now() + chrono::Duration::minutes(5);
+        assert_eq!(read_result("T001", &task), None);
+    }
+
+    #[tokio::test]
+    async fn test_done_file_finishes_task() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("process_registry.json");
+        let mut manager = RegistryManager::new(&path);
+        manager.load().unwrap();
+        // A dead process whose done file says it succeeded is not a death
+        for id in ["T001", "T002"] {
+            let mut task = native_task(i32::MAX);
+            task.done_file = Some(dir.path().join(id));
+            manager.upsert_task(id.to_string(), task).unwrap();
+        }
+        fs::write(dir.path().join("T001"), "").unwrap();
+        fs::write(dir.path().join("T002"), "127\n").unwrap();
+
+        let mut watchdog = Watchdog::new(&path, Fairness::default(), 10);
+        let mut events = watchdog.subscribe();
+        watchdog.check(false).await.unwrap();
+        let task = |id| watchdog.registry().get_task(id).unwrap().clone();
+        assert_eq!(task("T001").status, TaskStatus::Completed);
+        assert_eq!(task("T002").status, TaskStatus::Failed);
+        assert!(task("T002").triage_hint.unwrap().contains("not found"));
+        let mut published = [events.try_recv().unwrap(), events.try_recv().unwrap()];
+        published.sort_by_key(|event| event.task_id().map(str::to_string));
+        assert_eq!(
+            published[0],
+            WatchdogEvent::TaskCompleted {
+                task_id: "T001".into()
+            }
+        );
+        assert!(matches!(
+            &published[1],
+            WatchdogEvent::TaskDied { task_id, exit_code: Some(127), .. } if task_id == "T002"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_check_publishes_what_it_finds() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("process_registry.json");
+        let mut manager = RegistryManager::new(&path);
+        manager.load().unwrap();
+        // PIDs above pid_max never exist
+        manager
+            .upsert_task("T001".to_string(), native_task(i32::MAX))
+            .unwrap();
+
+        let mut watchdog = Watchdog::new(&path, Fairness::default(), 10);
+        let mut events = watchdog.subscribe();
+        let stats = watchdog.check(false).await.unwrap();
+        assert_eq!(stats.failed, 1);
+        assert_eq!(
+            events.try_recv().unwrap(),
+            WatchdogEvent::TaskDied {
+                task_id: "T001".to_string(),
+                exit_code: None,
+                triage_hint: None,
+                failure_excerpt: None,
+            }
+        );
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            WatchdogEvent::CheckCompleted { .. }
+        ));
+
+        // Only tasks added after the first check are announced
+        manager.load().unwrap();
+        manager
+            .upsert_task("T002".to_string(), native_task(i32::MAX))
+            .unwrap();
+        watchdog.check(false).await.unwrap();
+        assert_eq!(
+            events.try_recv().unwrap(),
+            WatchdogEvent::TaskRegistered {
+                task_id: "T002".to_string()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_task_over_threshold_is_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("process_registry.json");
+        let mut manager = RegistryManager::new(&path);
+        manager.load().unwrap();
+        // The test's own process group uses well over 1MB and runs at
+        // least one process; without --enforce-limits it is only reported
+        let pid = std::process::id() as i32;
+        let mut task = native_task(pid);
+        task.native.as_mut().unwrap().pgid = nix::unistd::getpgrp().as_raw();
+        task.max_memory_mb = Some(1);
+        manager.upsert_task("T001".to_string(), task).unwrap();
+
+        let mut watchdog = Watchdog::new(&path, Fairness::default(), 10);
+        watchdog.set_process_limits(ProcessLimits {
+            max: Some(0),
+            threads: Some(0),
+            ..Default::default()
+        });
+        let mut events = watchdog.subscribe();
+        watchdog.check(false).await.unwrap();
+        let mut flagged = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let WatchdogEvent::ThresholdExceeded {
+                task_id,
+                resource,
+                limit,
+                ..
+            } = event
+            {
+                flagged.push((task_id, resource, limit));
+            }
+        }
+        assert_eq!(
+            flagged,
+            vec![
+                ("T001".into(), "memory".into(), "1MB".into()),
+                ("T001".into(), "processes".into(), "0".into()),
+                ("T001".into(), "threads".into(), "0".into()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_repeated_death_alerts_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("process_registry.json");
+        let mut manager = RegistryManager::new(&path);
+        let mut watchdog = Watchdog::new(&path, Fairness::default(), 10);
+        let mut alerts = watchdog.subscribe_alerts();
+        let mut start = || {
+            manager
+                .with_transaction(|txn| {
+                    txn.remove_task("T001");
+                    txn.register("T001".to_string(), native_task(i32::MAX))
+                })
+                .unwrap()
+        };
+
+        // The same task dying on every check, as in a crash loop
+        for _ in 0..3 {
+            start();
+            watchdog.check(false).await.unwrap();
+        }
+        let alert = alerts.try_recv().unwrap();
+        assert_eq!((alert.tier, alert.occurrences), (0, 1));
+        assert!(alerts.try_recv().is_err());
+
+        // Once a check goes by without it, it alerts again
+        watchdog.check(false).await.unwrap();
+        start();
+        watchdog.check(false).await.unwrap();
+        assert!(alerts.try_recv().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_register_batch_announces_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut watchdog = Watchdog::new(
+            dir.path().join("process_registry.json"),
+            Fairness::default(),
+            10,
+        );
+        watchdog.check(false).await.unwrap();
+        let mut events = watchdog.subscribe();
+
+        let results = watchdog
+            .register_batch(
+                vec![("T001".to_string(), native_task(std::process::id() as i32))],
+                false,
+            )
+            .unwrap();
+        assert!(results[0].1.is_ok());
+        assert_eq!(
+            events.try_recv().unwrap(),
+            WatchdogEvent::TaskRegistered {
+                task_id: "T001".to_string()
+            }
+        );
+
+        // Not announced a second time by the next check
+        watchdog.check(false).await.unwrap();
+        while let Ok(event) = events.try_recv() {
+            assert!(!matches!(event, WatchdogEvent::TaskRegistered { .. }));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_one_daemon_per_registry() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("process_registry.json");
+
+        let held = DaemonLock::acquire(&path, false).await.unwrap();
+        let err = DaemonLock::acquire(&path, false).await.err().unwrap();
+        assert!(err
+            .to_string()
+            .contains(&format!("PID {}", std::process::id())));
+
+        drop(held);
+        DaemonLock::acquire(&path, false).await.unwrap();
+    }
+}
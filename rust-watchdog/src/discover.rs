@@ -0,0 +1,177 @@
+//! Discovery of work AI coding tools started without registering it.
+//!
+//! AI tools are recognized by process name or by the script a runtime runs
+//! (`node .../claude`). Their long-running descendants are proposed for
+//! adoption: the topmost one on each branch that isn't a shell or another
+//! tool, since adopting it covers its process group. Processes that are
+//! registered tasks, or run under one, are left out.
+
+use sysinfo::{ProcessRefreshKind, System, UpdateKind};
+
+/// Name fragments (matched case-insensitively) of AI coding tools
+pub const AI_TOOLS: &[&str] = &["claude", "aider", "cursor", "codex", "gemini", "copilot"];
+
+/// Shells only wrap the work they start
+const SHELLS: &[&str] = &["sh", "bash", "zsh", "fish", "dash"];
+
+/// Ancestors followed at most when looking for the tool behind a process
+const MAX_DEPTH: usize = 32;
+
+/// A process as discovery sees it
+#[derive(Debug, Clone)]
+pub struct ProcInfo {
+    pub pid: i32,
+    pub parent: Option<i32>,
+    pub name: String,
+    pub cmd: Vec<String>,
+    pub run_secs: u64,
+}
+
+/// A process worth adopting
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub pid: i32,
+    /// Which AI tool started it, and that tool's PID
+    pub tool: &'static str,
+    pub tool_pid: i32,
+    pub run_secs: u64,
+}
+
+fn basename(s: &str) -> String {
+    s.rsplit('/').next().unwrap_or(s).to_lowercase()
+}
+
+/// The AI tool `process` is, if it is one
+fn tool_of(process: &ProcInfo) -> Option<&'static str> {
+    let mut names = vec![process.name.to_lowercase()];
+    // Tools shipped as scripts show up as their runtime (`node`, `python`)
+    names.extend(process.cmd.iter().take(2).map(|arg| basename(arg)));
+    AI_TOOLS
+        .iter()
+        .copied()
+        .find(|tool| names.iter().any(|name| name.contains(tool)))
+}
+
+fn is_shell(process: &ProcInfo) -> bool {
+    SHELLS.contains(&basename(&process.name).as_str())
+}
+
+/// Processes running at least `min_secs` under an AI tool, skipping
+/// `registered` PIDs and everything below them
+pub fn find_candidates(
+    processes: &[ProcInfo],
+    registered: &[i32],
+    min_secs: u64,
+) -> Vec<Candidate> {
+    let by_pid: std::collections::HashMap<i32, &ProcInfo> =
+        processes.iter().map(|p| (p.pid, p)).collect();
+
+    // Nearest tool above a process; `None` once a registered task is met
+    let tool_above = |process: &ProcInfo| -> Option<(&'static str, i32)> {
+        let mut current = process.parent.and_then(|pid| by_pid.get(&pid));
+        for _ in 0..MAX_DEPTH {
+            let ancestor = current?;
+            if registered.contains(&ancestor.pid) {
+                return None;
+            }
+            if let Some(tool) = tool_of(ancestor) {
+                return Some((tool, ancestor.pid));
+            }
+            current = ancestor.parent.and_then(|pid| by_pid.get(&pid));
+        }
+        None
+    };
+    let eligible = |process: &ProcInfo| {
+        process.run_secs >= min_secs
+            && !registered.contains(&process.pid)
+            && tool_of(process).is_none()
+            && !is_shell(process)
+    };
+
+    let mut candidates: Vec<Candidate> = processes
+        .iter()
+        .filter(|p| eligible(p))
+        .filter_map(|process| {
+            let (tool, tool_pid) = tool_above(process)?;
+            // Only the topmost eligible process on a branch
+            let parent_eligible = process
+                .parent
+                .and_then(|pid| by_pid.get(&pid))
+                .is_some_and(|parent| eligible(parent) && tool_above(parent).is_some());
+            (!parent_eligible).then_some(Candidate {
+                pid: process.pid,
+                tool,
+                tool_pid,
+                run_secs: process.run_secs,
+            })
+        })
+        .collect();
+    candidates.sort_by_key(|c| c.pid);
+    candidates
+}
+
+/// Every process on the machine (threads excluded)
+pub fn scan() -> Vec<ProcInfo> {
+    let mut sys = System::new();
+    sys.refresh_processes_specifics(ProcessRefreshKind::new().with_cmd(UpdateKind::Always));
+    sys.processes()
+        .values()
+        .filter(|p| p.thread_kind().is_none())
+        .map(|p| ProcInfo {
+            pid: p.pid().as_u32() as i32,
+            parent: p.parent().map(|pid| pid.as_u32() as i32),
+            name: p.name().to_string(),
+            cmd: p.cmd().to_vec(),
+            run_secs: p.run_time(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proc(pid: i32, parent: i32, cmd: &str, run_secs: u64) -> ProcInfo {
+        let cmd: Vec<String> = cmd.split(' ').map(String::from).collect();
+        ProcInfo {
+            pid,
+            parent: Some(parent),
+            name: basename(&cmd[0]),
+            cmd,
+            run_secs,
+        }
+    }
+
+    #[test]
+    fn test_finds_topmost_long_running_children() {
+        let processes = [
+            proc(
+                10,
+                1,
+                "node /usr/lib/node_modules/@anthropic-ai/claude",
+                3600,
+            ),
+            proc(11, 10, "/bin/bash -c npm run dev", 900),
+            proc(12, 11, "npm run dev", 900),
+            proc(13, 12, "node vite", 900),
+            proc(14, 10, "/bin/bash -c rg foo", 2),
+            proc(15, 14, "rg foo", 2),
+            proc(20, 1, "aider --model x", 600),
+            proc(21, 20, "python -m pytest", 400),
+            proc(22, 20, "python worker.py", 400),
+            proc(23, 22, "python child.py", 400),
+            proc(30, 1, "python unrelated.py", 9000),
+        ];
+
+        let found = find_candidates(&processes, &[], 300);
+        let summary: Vec<_> = found.iter().map(|c| (c.pid, c.tool, c.tool_pid)).collect();
+        assert_eq!(
+            summary,
+            [(12, "claude", 10), (21, "aider", 20), (22, "aider", 20)]
+        );
+
+        // Registered tasks and their children are already accounted for
+        let found = find_candidates(&processes, &[22], 300);
+        assert_eq!(found.iter().map(|c| c.pid).collect::<Vec<_>>(), [12, 21]);
+    }
+}
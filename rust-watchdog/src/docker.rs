@@ -1,14 +1,47 @@
+//! Docker containers for tasks (`--features docker`, on by default).
+//!
+//! Without the feature, `DockerManager::new()` always returns `None`, so
+//! every caller takes its "Docker not available" path and the binary does
+//! not link bollard at all.
+
 use anyhow::{Context, Result};
-use bollard::container::{Config, CreateContainerOptions, StopContainerOptions};
+#[cfg(feature = "docker")]
+use bollard::container::{Config, CreateContainerOptions, KillContainerOptions};
+#[cfg(feature = "docker")]
 use bollard::models::HostConfig;
+#[cfg(feature = "docker")]
 use bollard::Docker;
+use futures_util::stream::BoxStream;
+#[cfg(feature = "docker")]
+use serde_json::json;
+#[cfg(feature = "docker")]
 use std::collections::HashMap;
+#[cfg(feature = "docker")]
+use std::time::Instant;
+
+#[cfg(feature = "docker")]
+use crate::docker_recorder::record;
+#[cfg(feature = "docker")]
+use crate::files;
+#[cfg(feature = "docker")]
+use crate::process::ProcessManager;
+use crate::types::{IoStats, ResourceLimits, TaskCommand};
+#[cfg(feature = "docker")]
+use crate::{info, verbose, warn};
+use std::fmt;
+use std::path::Path;
+use std::time::Duration;
 
 /// Docker container manager
 pub struct DockerManager {
+    #[cfg(feature = "docker")]
     client: Docker,
+    /// Never constructed without Docker support
+    #[cfg(not(feature = "docker"))]
+    never: std::convert::Infallible,
 }
 
+#[cfg(feature = "docker")]
 impl DockerManager {
     /// Create new Docker manager
     /// Returns None if Docker is not available
@@ -24,34 +57,45 @@ impl DockerManager {
         Docker::connect_with_local_defaults().is_ok()
     }
 
-    /// Run a task in a Docker container
+    /// Check that the Docker daemon actually answers (`new()` connects lazily)
+    pub async fn ping(&self) -> bool {
+        let started = Instant::now();
+        let result = self.client.ping().await;
+        record("ping", json!({}), &result, started);
+        result.is_ok()
+    }
+
+    /// Run a task in a Docker container. The container is kept after it
+    /// exits, so its logs and exit state can still be inspected, unless
+    /// `auto_remove` is set; see [`Self::remove_container`].
     pub async fn run_container(
         &self,
         task_id: &str,
-        command: Vec<String>,
+        command: &TaskCommand,
         work_dir: &str,
-        memory_limit: &str,
-        cpu_limit: &str,
+        limits: &ResourceLimits,
         image: Option<&str>,
+        auto_remove: bool,
     ) -> Result<String> {
-        let container_name = format!("dev-task-{}", task_id);
+        let container_name =
+            Self::container_name(task_id, ProcessManager::current_user().as_deref());
         let image = image.unwrap_or("python:3.11-slim");
 
-        println!("🐳 Starting container: {}", container_name);
-        println!("   Image: {}", image);
-        println!("   Memory: {}, CPU: {}", memory_limit, cpu_limit);
+        info!("🐳 Starting container: {}", container_name);
+        verbose!("   Image: {}", image);
+        verbose!("   Memory: {}, CPU: {}", limits.memory, limits.cpu);
 
         // Create container configuration
         // SECURITY FIX: Pass commands directly without shell to prevent injection
         let config = Config {
             image: Some(image.to_string()),
-            cmd: Some(command),
+            cmd: Some(command.exec_argv()),
             working_dir: Some("/workspace".to_string()),
             host_config: Some(HostConfig {
                 binds: Some(vec![format!("{}:/workspace", work_dir)]),
-                memory: Some(Self::parse_memory(memory_limit)?),
-                nano_cpus: Some((cpu_limit.parse::<f64>()? * 1_000_000_000.0) as i64),
-                auto_remove: Some(true),
+                memory: Some(Self::parse_memory(&limits.memory)?),
+                nano_cpus: Some((limits.cpu.parse::<f64>()? * 1_000_000_000.0) as i64),
+                auto_remove: Some(auto_remove),
                 ..Default::default()
             }),
             ..Default::default()
@@ -63,42 +107,161 @@ impl DockerManager {
             platform: None,
         };
 
-        let container = self
-            .client
-            .create_container(Some(options), config)
-            .await
-            .context("Failed to create container")?;
+        let request = json!({ "name": container_name, "config": config });
+        let started = Instant::now();
+        let result = self.client.create_container(Some(options), config).await;
+        record("create_container", request, &result, started);
+        let container = result.context("Failed to create container")?;
 
         // Start container
-        self.client
+        let started = Instant::now();
+        let result = self
+            .client
             .start_container::<String>(&container.id, None)
-            .await
-            .context("Failed to start container")?;
+            .await;
+        record(
+            "start_container",
+            json!({ "id": container.id }),
+            &result,
+            started,
+        );
+        result.context("Failed to start container")?;
 
-        println!("   ✅ Container started: {}", &container.id[..12]);
+        info!("   ✅ Container started: {}", &container.id[..12]);
 
         Ok(container.id)
     }
 
-    /// Stop a running container
-    pub async fn stop_container(&self, container_id: &str) -> Result<()> {
-        println!("🛑 Stopping container: {}", &container_id[..12]);
+    /// Stop a running container: SIGTERM, then SIGKILL if it is still
+    /// running after `timeout`. Returns which of the two stopped it.
+    pub async fn stop_container(
+        &self,
+        container_id: &str,
+        timeout: Duration,
+    ) -> Result<StopOutcome> {
+        use bollard::container::WaitContainerOptions;
+        use futures_util::stream::StreamExt;
 
-        let options = StopContainerOptions { t: 2 }; // 2 second timeout
+        info!(
+            "🛑 Stopping container: {} ({}s to exit)",
+            &container_id[..12],
+            timeout.as_secs()
+        );
+        if let Err(e) = self.kill_container(container_id, "SIGTERM").await {
+            if !self.is_running(container_id).await {
+                verbose!("   Container already stopped");
+                return Ok(StopOutcome::AlreadyStopped);
+            }
+            return Err(e);
+        }
 
-        self.client
-            .stop_container(container_id, Some(options))
+        // Any answer means it stopped: non-zero exits arrive as errors
+        let options = WaitContainerOptions {
+            condition: "not-running",
+        };
+        let started = Instant::now();
+        let mut wait = self.client.wait_container(container_id, Some(options));
+        let exited = tokio::time::timeout(timeout, wait.next()).await.is_ok();
+        record(
+            "wait_container",
+            json!({ "id": container_id, "timeout_secs": timeout.as_secs() }),
+            &Ok::<_, String>(json!({ "exited": exited })),
+            started,
+        );
+        if exited {
+            verbose!("   ✅ Container exited");
+            return Ok(StopOutcome::Exited);
+        }
+
+        warn!(
+            "   ⚠️  Container {} still running after {}s: killing it",
+            &container_id[..12],
+            timeout.as_secs()
+        );
+        self.kill_container(container_id, "SIGKILL")
             .await
-            .context("Failed to stop container")?;
+            .context("Failed to force-kill container")?;
+        Ok(StopOutcome::ForceKilled)
+    }
 
-        println!("   ✅ Container stopped");
+    async fn kill_container(&self, container_id: &str, signal: &str) -> Result<()> {
+        let options = KillContainerOptions { signal };
+        let started = Instant::now();
+        let result = self
+            .client
+            .kill_container(container_id, Some(options))
+            .await;
+        record(
+            "kill_container",
+            json!({ "id": container_id, "signal": signal }),
+            &result,
+            started,
+        );
+        result.with_context(|| format!("Failed to send {signal} to container"))
+    }
+
+    /// Start a stopped container again, keeping its filesystem (what it
+    /// left behind often explains why it stopped). Returns false if it was
+    /// removed, so it has to be recreated instead.
+    pub async fn restart_container(&self, container_id: &str, timeout: Duration) -> Result<bool> {
+        use bollard::container::RestartContainerOptions;
+
+        let options = RestartContainerOptions {
+            t: timeout.as_secs() as isize,
+        };
+        let started = Instant::now();
+        let result = self
+            .client
+            .restart_container(container_id, Some(options))
+            .await;
+        record(
+            "restart_container",
+            json!({ "id": container_id }),
+            &result,
+            started,
+        );
+        match result {
+            Ok(()) => Ok(true),
+            Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 404, ..
+            }) => Ok(false),
+            Err(e) => Err(e).context("Failed to restart container"),
+        }
+    }
 
-        Ok(())
+    /// Remove a stopped container. Returns false if it was already gone.
+    pub async fn remove_container(&self, container_id: &str) -> Result<bool> {
+        let started = Instant::now();
+        let result = self.client.remove_container(container_id, None).await;
+        record(
+            "remove_container",
+            json!({ "id": container_id }),
+            &result,
+            started,
+        );
+        match result {
+            Ok(()) => Ok(true),
+            Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 404, ..
+            }) => Ok(false),
+            Err(e) => Err(e).context("Failed to remove container"),
+        }
     }
 
     /// Check if container is running
     pub async fn is_running(&self, container_id: &str) -> bool {
-        if let Ok(inspect) = self.client.inspect_container(container_id, None).await {
+        let started = Instant::now();
+        let result = self.client.inspect_container(container_id, None).await;
+        // Only the state is interesting for replay; the full inspect payload is huge
+        let summary = result.as_ref().map(|i| json!({ "state": i.state }));
+        record(
+            "inspect_container",
+            json!({ "id": container_id }),
+            &summary,
+            started,
+        );
+
+        if let Ok(inspect) = result {
             if let Some(state) = inspect.state {
                 return state.running.unwrap_or(false);
             }
@@ -106,6 +269,36 @@ impl DockerManager {
         false
     }
 
+    /// Exit details of a container (exit code, OOM kill)
+    pub async fn exit_state(&self, container_id: &str) -> Result<ContainerExit> {
+        let started = Instant::now();
+        let result = self.client.inspect_container(container_id, None).await;
+        let summary = result.as_ref().map(|i| json!({ "state": i.state }));
+        record(
+            "inspect_container",
+            json!({ "id": container_id }),
+            &summary,
+            started,
+        );
+
+        let state = result
+            .context("Failed to inspect container")?
+            .state
+            .unwrap_or_default();
+
+        Ok(ContainerExit {
+            running: state.running.unwrap_or(false),
+            exit_code: state.exit_code,
+            oom_killed: state.oom_killed.unwrap_or(false),
+            error: state.error.filter(|e| !e.is_empty()),
+            finished_at: state
+                .finished_at
+                .and_then(|at| at.parse().ok())
+                // Docker's zero time for containers that haven't stopped
+                .filter(|at: &chrono::DateTime<chrono::Utc>| at.timestamp() > 0),
+        })
+    }
+
     /// Get container resource usage
     pub async fn get_stats(&self, container_id: &str) -> Result<ContainerStats> {
         use bollard::container::StatsOptions;
@@ -118,7 +311,22 @@ impl DockerManager {
 
         let mut stream = self.client.stats(container_id, Some(options));
 
-        if let Some(Ok(stats)) = stream.next().await {
+        let started = Instant::now();
+        let next = stream.next().await;
+        let recorded = match &next {
+            Some(Ok(stats)) => Ok(json!({
+                "memory_stats": stats.memory_stats,
+                "cpu_stats": stats.cpu_stats,
+                "precpu_stats": stats.precpu_stats,
+                "blkio_stats": stats.blkio_stats,
+                "pids_stats": stats.pids_stats,
+            })),
+            Some(Err(e)) => Err(e.to_string()),
+            None => Err("empty stats stream".to_string()),
+        };
+        record("stats", json!({ "id": container_id }), &recorded, started);
+
+        if let Some(Ok(stats)) = next {
             let memory_mb = stats.memory_stats.usage.unwrap_or(0) / 1024 / 1024;
 
             // Calculate CPU percentage
@@ -136,13 +344,89 @@ impl DockerManager {
 
             return Ok(ContainerStats {
                 cpu_percent: cpu_percent as f32,
+                cpu_seconds: stats.cpu_stats.cpu_usage.total_usage as f64 / 1e9,
                 memory_mb,
+                processes: stats.pids_stats.current.unwrap_or(0),
+                io: Self::blkio_totals(&stats.blkio_stats),
             });
         }
 
         anyhow::bail!("Failed to get container stats")
     }
 
+    /// A container's stdout and stderr, interleaved; the last `tail` lines
+    /// only if given. With `follow` the stream ends when the container stops.
+    pub fn logs(
+        &self,
+        container_id: &str,
+        follow: bool,
+        tail: Option<usize>,
+    ) -> BoxStream<'static, Result<Vec<u8>>> {
+        use bollard::container::LogsOptions;
+        use futures_util::stream::StreamExt;
+
+        let options = LogsOptions {
+            follow,
+            stdout: true,
+            stderr: true,
+            tail: tail.map_or_else(|| "all".to_string(), |n| n.to_string()),
+            ..Default::default()
+        };
+        self.client
+            .logs(container_id, Some(options))
+            .map(|chunk| Ok(chunk?.into_bytes().to_vec()))
+            .boxed()
+    }
+
+    /// Save a container's whole output (as [`Self::logs`] streams it) to
+    /// `path`, replacing an earlier copy only once it has all been read, so
+    /// it outlives the container. The copy is created with `mode`. Returns
+    /// the bytes saved.
+    pub async fn save_logs(&self, container_id: &str, path: &Path, mode: u32) -> Result<u64> {
+        use futures_util::stream::StreamExt;
+        use tokio::io::AsyncWriteExt;
+
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir)
+                .await
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let partial = path.with_extension("log.partial");
+        let mut file = tokio::fs::OpenOptions::from(files::create_options(mode))
+            .open(&partial)
+            .await
+            .with_context(|| format!("Failed to create {}", partial.display()))?;
+        let mut chunks = self.logs(container_id, false, None);
+        let mut saved = 0;
+        while let Some(chunk) = chunks.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    let _ = tokio::fs::remove_file(&partial).await;
+                    return Err(e.context("Failed to read container logs"));
+                }
+            };
+            file.write_all(&chunk).await?;
+            saved += chunk.len() as u64;
+        }
+        file.flush().await?;
+        tokio::fs::rename(&partial, path).await?;
+        Ok(saved)
+    }
+
+    /// Sum block I/O bytes ("Read"/"Write" on cgroup v1, "read"/"write" on v2)
+    fn blkio_totals(blkio: &bollard::container::BlkioStats) -> IoStats {
+        let mut io = IoStats::default();
+        for entry in blkio.io_service_bytes_recursive.iter().flatten() {
+            match entry.op.to_lowercase().as_str() {
+                "read" => io.read_bytes += entry.value,
+                "write" => io.written_bytes += entry.value,
+                _ => {}
+            }
+        }
+        io
+    }
+
     /// List all dev task containers
     pub async fn list_task_containers(&self) -> Result<Vec<String>> {
         use bollard::container::ListContainersOptions;
@@ -156,13 +440,116 @@ impl DockerManager {
             ..Default::default()
         });
 
-        let containers = self.client.list_containers(options).await?;
+        let started = Instant::now();
+        let result = self.client.list_containers(options).await;
+        record(
+            "list_containers",
+            json!({ "name": "dev-task-" }),
+            &result,
+            started,
+        );
+        let containers = result?;
 
         Ok(containers.iter().filter_map(|c| c.id.clone()).collect())
     }
+}
+
+#[cfg(not(feature = "docker"))]
+impl DockerManager {
+    /// Always `None`: built without Docker support
+    pub fn new() -> Option<Self> {
+        None
+    }
+
+    pub fn is_available() -> bool {
+        false
+    }
+
+    pub async fn ping(&self) -> bool {
+        match self.never {}
+    }
+
+    pub async fn run_container(
+        &self,
+        _task_id: &str,
+        _command: &TaskCommand,
+        _work_dir: &str,
+        _limits: &ResourceLimits,
+        _image: Option<&str>,
+        _auto_remove: bool,
+    ) -> Result<String> {
+        match self.never {}
+    }
+
+    pub async fn restart_container(&self, _container_id: &str, _timeout: Duration) -> Result<bool> {
+        match self.never {}
+    }
+
+    pub async fn remove_container(&self, _container_id: &str) -> Result<bool> {
+        match self.never {}
+    }
+
+    pub async fn stop_container(
+        &self,
+        _container_id: &str,
+        _timeout: Duration,
+    ) -> Result<StopOutcome> {
+        match self.never {}
+    }
+
+    pub async fn is_running(&self, _container_id: &str) -> bool {
+        match self.never {}
+    }
+
+    pub async fn exit_state(&self, _container_id: &str) -> Result<ContainerExit> {
+        match self.never {}
+    }
+
+    pub async fn get_stats(&self, _container_id: &str) -> Result<ContainerStats> {
+        match self.never {}
+    }
+
+    pub fn logs(
+        &self,
+        _container_id: &str,
+        _follow: bool,
+        _tail: Option<usize>,
+    ) -> BoxStream<'static, Result<Vec<u8>>> {
+        match self.never {}
+    }
+
+    pub async fn save_logs(&self, _container_id: &str, _path: &Path, _mode: u32) -> Result<u64> {
+        match self.never {}
+    }
+
+    pub async fn list_task_containers(&self) -> Result<Vec<String>> {
+        match self.never {}
+    }
+}
+
+impl DockerManager {
+    /// Container name for a task, namespaced by user so two people on one
+    /// Docker host can use the same task IDs (`dev-task-alice-T001`)
+    pub fn container_name(task_id: &str, user: Option<&str>) -> String {
+        let user: Option<String> = user.map(|u| {
+            u.chars()
+                .map(|c| {
+                    if c.is_ascii_alphanumeric() || "_.-".contains(c) {
+                        c
+                    } else {
+                        '_'
+                    }
+                })
+                .collect()
+        });
+        match user {
+            Some(user) if !user.is_empty() => format!("dev-task-{user}-{task_id}"),
+            _ => format!("dev-task-{task_id}"),
+        }
+    }
 
     /// Parse memory string (e.g., "512m", "1g") to bytes
-    fn parse_memory(mem: &str) -> Result<i64> {
+    pub fn parse_memory(mem: &str) -> Result<i64> {
         let mem = mem.trim().to_lowercase();
 
         if mem.is_empty() {
@@ -183,17 +570,67 @@ impl DockerManager {
     }
 }
 
+/// How [`DockerManager::stop_container`] stopped a container
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopOutcome {
+    /// Exited on SIGTERM within the timeout
+    Exited,
+    /// Still running after the timeout, so killed with SIGKILL
+    ForceKilled,
+    /// Wasn't running any more
+    AlreadyStopped,
+}
+
+impl fmt::Display for StopOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Exited => "exited on SIGTERM",
+            Self::ForceKilled => "force-killed after the stop timeout",
+            Self::AlreadyStopped => "already stopped",
+        })
+    }
+}
+
+/// Exit details of a container
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ContainerExit {
+    pub running: bool,
+    pub exit_code: Option<i64>,
+    pub oom_killed: bool,
+    /// Why Docker couldn't run it, if it couldn't
+    pub error: Option<String>,
+    pub finished_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
 /// Container resource usage
 #[derive(Debug, Clone)]
 pub struct ContainerStats {
     pub cpu_percent: f32,
+    /// Cumulative CPU time of the container
+    pub cpu_seconds: f64,
     pub memory_mb: u64,
+    /// Processes running in the container
+    pub processes: u64,
+    pub io: IoStats,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_container_name_is_namespaced_by_user() {
+        assert_eq!(
+            DockerManager::container_name("T001", Some("alice")),
+            "dev-task-alice-T001"
+        );
+        assert_eq!(
+            DockerManager::container_name("T001", Some("DOMAIN\\bob")),
+            "dev-task-DOMAIN_bob-T001"
+        );
+        assert_eq!(DockerManager::container_name("T001", None), "dev-task-T001");
+    }
+
     #[test]
     fn test_parse_memory() {
         assert_eq!(
@@ -0,0 +1,142 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+/// Keys whose values are replaced before a call is written out
+const SECRET_KEYS: [&str; 5] = ["env", "password", "token", "auth", "secret"];
+
+static RECORDER: OnceLock<Mutex<File>> = OnceLock::new();
+
+/// One recorded Docker API interaction (a line of the JSONL recording)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DockerCall {
+    pub timestamp: DateTime<Utc>,
+    pub op: String,
+    pub request: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+impl DockerCall {
+    /// Load a recording written by `--record-docker-calls` (for replay in tests)
+    pub fn load_all<P: AsRef<Path>>(path: P) -> Result<Vec<DockerCall>> {
+        let content = fs::read_to_string(path).context("Failed to read Docker call recording")?;
+        content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line).context("Invalid Docker call record"))
+            .collect()
+    }
+}
+
+/// Start appending every Docker request/response to `path` (JSONL, 0600)
+pub fn record_calls_to<P: AsRef<Path>>(path: P) -> Result<()> {
+    let mut options = OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let file = options
+        .open(path.as_ref())
+        .context("Failed to open Docker call recording file")?;
+
+    RECORDER
+        .set(Mutex::new(file))
+        .map_err(|_| anyhow::anyhow!("Docker call recording already enabled"))
+}
+
+/// Whether calls are being recorded
+pub fn is_recording() -> bool {
+    RECORDER.get().is_some()
+}
+
+/// Record one Docker interaction (no-op unless recording is enabled)
+#[cfg_attr(not(feature = "docker"), allow(dead_code))]
+pub(crate) fn record<R: Serialize, E: std::fmt::Display>(
+    op: &str,
+    request: Value,
+    result: &std::result::Result<R, E>,
+    started: Instant,
+) {
+    let Some(file) = RECORDER.get() else {
+        return;
+    };
+
+    let (response, error) = match result {
+        Ok(r) => (serde_json::to_value(r).ok().map(sanitize), None),
+        Err(e) => (None, Some(e.to_string())),
+    };
+    let call = DockerCall {
+        timestamp: Utc::now(),
+        op: op.to_string(),
+        request: sanitize(request),
+        response,
+        error,
+        duration_ms: started.elapsed().as_millis() as u64,
+    };
+
+    if let (Ok(line), Ok(mut file)) = (serde_json::to_string(&call), file.lock()) {
+        let _ = writeln!(file, "{line}");
+    }
+}
+
+/// Strip secrets and the user's home directory from a recorded value
+pub fn sanitize(value: Value) -> Value {
+    let home = std::env::var("HOME").ok().filter(|h| h.len() > 1);
+    sanitize_with(value, home.as_deref())
+}
+
+fn sanitize_with(value: Value, home: Option<&str>) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(k, v)| {
+                    let lower = k.to_lowercase();
+                    if SECRET_KEYS.iter().any(|s| lower.contains(s)) && !v.is_null() {
+                        (k, Value::String("<redacted>".to_string()))
+                    } else {
+                        (k, sanitize_with(v, home))
+                    }
+                })
+                .collect(),
+        ),
+        Value::Array(items) => {
+            Value::Array(items.into_iter().map(|v| sanitize_with(v, home)).collect())
+        }
+        Value::String(s) => match home {
+            Some(h) if s.contains(h) => Value::String(s.replace(h, "~")),
+            _ => Value::String(s),
+        },
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_sanitize() {
+        let value = json!({
+            "Env": ["API_KEY=hunter2"],
+            "HostConfig": { "Binds": ["/home/dev/project:/workspace"] },
+            "Cmd": ["echo", "hi"],
+        });
+        let clean = sanitize_with(value, Some("/home/dev"));
+        assert_eq!(clean["Env"], "<redacted>");
+        assert_eq!(clean["HostConfig"]["Binds"][0], "~/project:/workspace");
+        assert_eq!(clean["Cmd"][1], "hi");
+    }
+}
@@ -0,0 +1,186 @@
+//! Done files: tasks that can't call the CLI signal completion by creating
+//! a file (`touch .claude/done/T001`). An exit code written into the file
+//! (`echo 2 > ...`) marks the task failed instead.
+//!
+//! The daemon checks done files on every cycle, and on Linux watches their
+//! directories with inotify so a touched file is acted on at once instead
+//! of at the next interval.
+
+use crate::types::{TaskInfo, TaskStatus};
+use std::path::{Path, PathBuf};
+
+/// Directory (next to the registry) for done files named after their task
+pub const DONE_DIR: &str = "done";
+
+/// Default done file for `task_id`
+pub fn default_path(registry_path: &Path, task_id: &str) -> PathBuf {
+    registry_path
+        .parent()
+        .unwrap_or(Path::new("."))
+        .join(DONE_DIR)
+        .join(task_id)
+}
+
+/// Outcome `contents` report: no exit code or 0 is success
+fn outcome_of(contents: &str) -> (TaskStatus, Option<i64>) {
+    match contents
+        .lines()
+        .next()
+        .map(str::trim)
+        .map(str::parse::<i64>)
+    {
+        Some(Ok(code)) if code != 0 => (TaskStatus::Failed, Some(code)),
+        _ => (TaskStatus::Completed, None),
+    }
+}
+
+/// Whether `path` exists and was written after `task` started; an older
+/// file is left over from an earlier run
+pub fn written_during(path: &Path, task: &TaskInfo) -> bool {
+    let Some(modified) = std::fs::metadata(path).and_then(|m| m.modified()).ok() else {
+        return false;
+    };
+    // File times can be coarser than the start time's precision
+    chrono::DateTime::<chrono::Utc>::from(modified)
+        >= task.started_at - chrono::Duration::seconds(1)
+}
+
+/// The status and exit code `task`'s done file reports, if it has one and
+/// it was written during this run
+pub fn outcome(task: &TaskInfo) -> Option<(TaskStatus, Option<i64>)> {
+    let path = task.done_file.as_ref()?;
+    if !written_during(path, task) {
+        return None;
+    }
+    let contents = std::fs::read_to_string(path).unwrap_or_default();
+    Some(outcome_of(&contents))
+}
+
+#[cfg(target_os = "linux")]
+mod watch {
+    use anyhow::Result;
+    use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify, WatchDescriptor};
+    use std::collections::hash_map::Entry;
+    use std::collections::{HashMap, HashSet};
+    use std::ffi::OsString;
+    use std::os::fd::{AsFd, AsRawFd, RawFd};
+    use std::path::PathBuf;
+    use tokio::io::unix::AsyncFd;
+
+    struct Fd(Inotify);
+
+    impl AsRawFd for Fd {
+        fn as_raw_fd(&self) -> RawFd {
+            self.0.as_fd().as_raw_fd()
+        }
+    }
+
+    /// Wakes the daemon when a done file is created or touched (and `wait`
+    /// when the lifecycle journal is appended to). Their directories are
+    /// watched, since the files don't exist yet.
+    pub struct Waker {
+        inotify: AsyncFd<Fd>,
+        dirs: HashMap<PathBuf, WatchDescriptor>,
+        names: HashSet<OsString>,
+    }
+
+    impl Waker {
+        pub fn new() -> Result<Self> {
+            let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)?;
+            Ok(Self {
+                inotify: AsyncFd::new(Fd(inotify))?,
+                dirs: HashMap::new(),
+                names: HashSet::new(),
+            })
+        }
+
+        /// Watch for exactly `files` (ones in missing directories are
+        /// skipped until the directory exists)
+        pub fn watch(&mut self, files: Vec<PathBuf>) {
+            self.names = files
+                .iter()
+                .filter_map(|file| file.file_name().map(Into::into))
+                .collect();
+            let dirs: Vec<PathBuf> = files
+                .iter()
+                .filter_map(|file| Some(file.parent()?.to_path_buf()))
+                .collect();
+            let inotify = &self.inotify.get_ref().0;
+            self.dirs.retain(|dir, wd| {
+                let keep = dirs.contains(dir);
+                if !keep {
+                    let _ = inotify.rm_watch(*wd);
+                }
+                keep
+            });
+            // Not IN_CREATE: `echo 2 > file` creates it empty before writing
+            let flags = AddWatchFlags::IN_CLOSE_WRITE
+                | AddWatchFlags::IN_MOVED_TO
+                | AddWatchFlags::IN_ATTRIB;
+            for dir in dirs {
+                if let Entry::Vacant(entry) = self.dirs.entry(dir) {
+                    if let Ok(wd) = inotify.add_watch(entry.key(), flags) {
+                        entry.insert(wd);
+                    }
+                }
+            }
+        }
+
+        /// Resolves once a watched file was created or touched
+        pub async fn changed(&mut self) -> Result<()> {
+            loop {
+                let mut ready = self.inotify.readable().await?;
+                let Ok(events) =
+                    ready.try_io(|fd| fd.get_ref().0.read_events().map_err(Into::into))
+                else {
+                    continue;
+                };
+                let touched = events?
+                    .iter()
+                    .any(|event| event.name.as_ref().is_some_and(|n| self.names.contains(n)));
+                if touched {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod watch {
+    use anyhow::Result;
+    use std::path::PathBuf;
+
+    /// Without inotify done files are only seen by the regular check
+    pub struct Waker;
+
+    impl Waker {
+        pub fn new() -> Result<Self> {
+            Ok(Self)
+        }
+
+        pub fn watch(&mut self, _files: Vec<PathBuf>) {}
+
+        pub async fn changed(&mut self) -> Result<()> {
+            std::future::pending().await
+        }
+    }
+}
+
+pub use watch::Waker;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outcome_of() {
+        assert_eq!(outcome_of(""), (TaskStatus::Completed, None));
+        assert_eq!(outcome_of("0\n"), (TaskStatus::Completed, None));
+        assert_eq!(
+            outcome_of("all tests passed"),
+            (TaskStatus::Completed, None)
+        );
+        assert_eq!(outcome_of(" 2 \nTraceback"), (TaskStatus::Failed, Some(2)));
+    }
+}
@@ -0,0 +1,166 @@
+//! Process exits: on Linux the daemon holds a pidfd for every running
+//! native task and watched process, all in one epoll set, so an exit wakes
+//! it at once instead of at the next interval's check.
+//!
+//! The check that follows still decides what happened to the task; the
+//! waker only says when to look. Kernels without `pidfd_open` (before 5.3)
+//! and other platforms fall back to the interval.
+
+#[cfg(target_os = "linux")]
+mod watch {
+    use anyhow::Result;
+    use nix::libc;
+    use nix::sys::epoll::{Epoll, EpollCreateFlags, EpollEvent, EpollFlags};
+    use std::collections::hash_map::Entry;
+    use std::collections::{HashMap, HashSet};
+    use std::os::fd::{AsFd, AsRawFd, FromRawFd, OwnedFd, RawFd};
+    use tokio::io::unix::AsyncFd;
+
+    struct Fd(Epoll);
+
+    impl AsRawFd for Fd {
+        fn as_raw_fd(&self) -> RawFd {
+            self.0 .0.as_fd().as_raw_fd()
+        }
+    }
+
+    /// Wakes the daemon when a watched process exits
+    pub struct Waker {
+        epoll: AsyncFd<Fd>,
+        pidfds: HashMap<i32, OwnedFd>,
+        /// Exits already reported, not watched again while the check
+        /// still lists them (e.g. a zombie waiting to be reaped)
+        reported: HashSet<i32>,
+    }
+
+    impl Waker {
+        pub fn new() -> Result<Self> {
+            let epoll = Epoll::new(EpollCreateFlags::EPOLL_CLOEXEC)?;
+            Ok(Self {
+                epoll: AsyncFd::new(Fd(epoll))?,
+                pidfds: HashMap::new(),
+                reported: HashSet::new(),
+            })
+        }
+
+        /// Watch for exactly `pids` (ones already gone are skipped; the
+        /// check sees those)
+        pub fn watch(&mut self, pids: Vec<i32>) {
+            let pids: HashSet<i32> = pids.into_iter().collect();
+            let epoll = &self.epoll.get_ref().0;
+            self.pidfds.retain(|pid, fd| {
+                let keep = pids.contains(pid);
+                if !keep {
+                    let _ = epoll.delete(&*fd);
+                }
+                keep
+            });
+            self.reported.retain(|pid| pids.contains(pid));
+            for pid in pids {
+                if self.reported.contains(&pid) {
+                    continue;
+                }
+                if let Entry::Vacant(entry) = self.pidfds.entry(pid) {
+                    let Some(fd) = pidfd_open(pid) else {
+                        continue;
+                    };
+                    if epoll
+                        .add(&fd, EpollEvent::new(EpollFlags::EPOLLIN, pid as u64))
+                        .is_ok()
+                    {
+                        entry.insert(fd);
+                    }
+                }
+            }
+        }
+
+        /// Resolves with the PIDs that exited, which are then no longer
+        /// watched
+        pub async fn exited(&mut self) -> Result<Vec<i32>> {
+            let mut events = [EpollEvent::empty(); 32];
+            loop {
+                let mut ready = self.epoll.readable().await?;
+                let Ok(count) =
+                    ready.try_io(|fd| fd.get_ref().0.wait(&mut events, 0).map_err(Into::into))
+                else {
+                    continue;
+                };
+                let count = count?;
+                if count == 0 {
+                    ready.clear_ready();
+                    continue;
+                }
+                let pids: Vec<i32> = events[..count].iter().map(|e| e.data() as i32).collect();
+                // A pidfd stays readable once its process is gone
+                let epoll = &self.epoll.get_ref().0;
+                for pid in &pids {
+                    if let Some(fd) = self.pidfds.remove(pid) {
+                        let _ = epoll.delete(&fd);
+                    }
+                    self.reported.insert(*pid);
+                }
+                return Ok(pids);
+            }
+        }
+    }
+
+    /// A pidfd for `pid`, if it is still running and the kernel has them
+    fn pidfd_open(pid: i32) -> Option<OwnedFd> {
+        // SAFETY: pidfd_open takes a PID and flags and returns a new file
+        // descriptor (close-on-exec) or -1; it touches no memory of ours
+        let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid, 0) };
+        // SAFETY: a non-negative result is a descriptor nothing else owns
+        (fd >= 0).then(|| unsafe { OwnedFd::from_raw_fd(fd as RawFd) })
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod watch {
+    use anyhow::Result;
+
+    /// Without pidfds exits are only seen by the regular check
+    pub struct Waker;
+
+    impl Waker {
+        pub fn new() -> Result<Self> {
+            Ok(Self)
+        }
+
+        pub fn watch(&mut self, _pids: Vec<i32>) {}
+
+        pub async fn exited(&mut self) -> Result<Vec<i32>> {
+            std::future::pending().await
+        }
+    }
+}
+
+pub use watch::Waker;
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_exit_wakes_at_once() {
+        let mut child = std::process::Command::new("sleep")
+            .arg("0.2")
+            .spawn()
+            .unwrap();
+        let pid = child.id() as i32;
+        let mut waker = Waker::new().unwrap();
+        waker.watch(vec![pid]);
+
+        let exited = tokio::time::timeout(Duration::from_secs(5), waker.exited())
+            .await
+            .expect("exit not noticed")
+            .unwrap();
+        assert_eq!(exited, vec![pid]);
+        child.wait().unwrap();
+
+        // Not watched again, so nothing more to report
+        waker.watch(vec![pid]);
+        let again = tokio::time::timeout(Duration::from_millis(200), waker.exited()).await;
+        assert!(again.is_err());
+    }
+}
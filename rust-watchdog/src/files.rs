@@ -0,0 +1,75 @@
+//! File modes and identities of the registry and the files kept next to it.
+//! Unix modes are applied as given; other platforms have none, so there
+//! the files get the access their directory gives them.
+
+use std::fs::{Metadata, OpenOptions};
+use std::io;
+use std::path::Path;
+
+/// Options opening a file for appending, creating it with `mode`
+pub fn append_options(mode: u32) -> OpenOptions {
+    let mut options = OpenOptions::new();
+    options.create(true).append(true);
+    set_create_mode(&mut options, mode);
+    options
+}
+
+/// Options opening a file to write it from the start, creating it with
+/// `mode`
+pub fn create_options(mode: u32) -> OpenOptions {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    set_create_mode(&mut options, mode);
+    options
+}
+
+fn set_create_mode(options: &mut OpenOptions, mode: u32) {
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(options, mode);
+    #[cfg(not(unix))]
+    let _ = (options, mode);
+}
+
+/// Give `path` the permissions `mode`
+pub fn set_mode(path: &Path, mode: u32) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (path, mode);
+        Ok(())
+    }
+}
+
+/// Permissions of `path`, where the platform has modes and it exists
+pub fn mode(path: &Path) -> Option<u32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::metadata(path).ok().map(|m| m.permissions().mode())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        None
+    }
+}
+
+/// What tells a file from another put in its place: its inode, or on
+/// Windows its creation time
+pub fn file_id(meta: &Metadata) -> u64 {
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::MetadataExt::ino(meta)
+    }
+    #[cfg(not(unix))]
+    {
+        meta.created()
+            .ok()
+            .and_then(|created| created.duration_since(std::time::UNIX_EPOCH).ok())
+            .map_or(0, |since| since.as_nanos() as u64)
+    }
+}
@@ -0,0 +1,631 @@
+//! gRPC control API (`run --grpc-socket`, built with `--features grpc`), for
+//! orchestrators that want a typed, versioned protocol rather than the CLI
+//! or the JSON API.
+//!
+//! The service is `task_watchdog.v1.Watchdog`, defined in
+//! `proto/task_watchdog/v1/watchdog.proto`; clients are generated from that
+//! file. The messages below are its Rust side, derived by hand so the build
+//! needs no protoc, and build.rs generates the service stubs.
+//!
+//! It is served on a Unix socket only the daemon's user can open, by the
+//! daemon: requests load the registry from disk like the JSON API's, and
+//! `StreamEvents` forwards what the daemon publishes.
+
+// Handlers and streams return tonic's `Status`, which is large
+#![allow(clippy::result_large_err)]
+
+use crate::events::WatchdogEvent;
+use crate::lifecycle::Actor;
+use crate::registry::RegistryManager;
+use crate::tasks::{self, Details, Peer, TaskSpec};
+use crate::types::{TaskEntry, TaskInfo, TaskStatus};
+use crate::{info, verbose, warn};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::wrappers::{BroadcastStream, UnixListenerStream};
+use tokio_stream::{Stream, StreamExt};
+use tonic::{Request, Response, Status};
+
+pub use v1::watchdog_client::WatchdogClient;
+pub use v1::watchdog_server::WatchdogServer;
+
+/// Messages and stubs of `task_watchdog.v1`
+pub mod v1 {
+    use std::collections::BTreeMap;
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct RegisterTaskRequest {
+        #[prost(string, tag = "1")]
+        pub task_id: String,
+        #[prost(string, tag = "2")]
+        pub command: String,
+        #[prost(bool, tag = "3")]
+        pub shell: bool,
+        #[prost(string, optional, tag = "4")]
+        pub description: Option<String>,
+        #[prost(string, optional, tag = "5")]
+        pub expected_outcome: Option<String>,
+        #[prost(btree_map = "string, string", tag = "6")]
+        pub labels: BTreeMap<String, String>,
+        #[prost(string, optional, tag = "7")]
+        pub timeout: Option<String>,
+        #[prost(string, repeated, tag = "8")]
+        pub after: Vec<String>,
+        #[prost(string, optional, tag = "9")]
+        pub ready: Option<String>,
+        #[prost(string, optional, tag = "10")]
+        pub max_memory: Option<String>,
+        #[prost(double, optional, tag = "11")]
+        pub max_cpu: Option<f64>,
+        #[prost(bool, tag = "12")]
+        pub queue: bool,
+        #[prost(string, optional, tag = "13")]
+        pub group: Option<String>,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct KillRequest {
+        #[prost(string, tag = "1")]
+        pub task_id: String,
+        #[prost(bool, tag = "2")]
+        pub force: bool,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct CheckRequest {
+        #[prost(string, tag = "1")]
+        pub task_id: String,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct CheckResponse {
+        #[prost(message, optional, tag = "1")]
+        pub task: Option<Task>,
+        #[prost(bool, optional, tag = "2")]
+        pub alive: Option<bool>,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct StreamEventsRequest {
+        #[prost(string, repeated, tag = "1")]
+        pub events: Vec<String>,
+        #[prost(string, optional, tag = "2")]
+        pub task_id: Option<String>,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Event {
+        #[prost(string, tag = "1")]
+        pub event: String,
+        #[prost(string, optional, tag = "2")]
+        pub task_id: Option<String>,
+        #[prost(message, optional, tag = "3")]
+        pub at: Option<::prost_types::Timestamp>,
+        #[prost(string, tag = "4")]
+        pub json: String,
+    }
+
+    #[derive(Clone, Copy, PartialEq, ::prost::Message)]
+    pub struct StatsRequest {}
+
+    #[derive(Clone, Copy, PartialEq, ::prost::Message)]
+    pub struct Stats {
+        #[prost(uint64, tag = "1")]
+        pub total: u64,
+        #[prost(uint64, tag = "2")]
+        pub queued: u64,
+        #[prost(uint64, tag = "3")]
+        pub starting: u64,
+        #[prost(uint64, tag = "4")]
+        pub running: u64,
+        #[prost(uint64, tag = "5")]
+        pub completed: u64,
+        #[prost(uint64, tag = "6")]
+        pub failed: u64,
+        #[prost(uint64, tag = "7")]
+        pub killed: u64,
+        #[prost(uint64, tag = "8")]
+        pub timed_out: u64,
+        #[prost(uint64, tag = "9")]
+        pub unknown: u64,
+        #[prost(double, tag = "10")]
+        pub cost_usd: f64,
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum TaskStatus {
+        Unspecified = 0,
+        Queued = 1,
+        Starting = 2,
+        Running = 3,
+        Completed = 4,
+        Failed = 5,
+        Cancelled = 6,
+        Killed = 7,
+        TimedOut = 8,
+        Unknown = 9,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Task {
+        #[prost(string, tag = "1")]
+        pub task_id: String,
+        #[prost(enumeration = "TaskStatus", tag = "2")]
+        pub status: i32,
+        #[prost(string, tag = "3")]
+        pub command: String,
+        #[prost(string, optional, tag = "4")]
+        pub description: Option<String>,
+        #[prost(message, optional, tag = "5")]
+        pub started_at: Option<::prost_types::Timestamp>,
+        #[prost(message, optional, tag = "6")]
+        pub completed_at: Option<::prost_types::Timestamp>,
+        #[prost(int64, optional, tag = "7")]
+        pub exit_code: Option<i64>,
+        #[prost(string, optional, tag = "8")]
+        pub kill_reason: Option<String>,
+        #[prost(string, optional, tag = "9")]
+        pub failure_reason: Option<String>,
+        #[prost(string, optional, tag = "10")]
+        pub triage_hint: Option<String>,
+        #[prost(string, optional, tag = "11")]
+        pub failure_excerpt: Option<String>,
+        #[prost(int32, optional, tag = "12")]
+        pub pid: Option<i32>,
+        #[prost(string, optional, tag = "13")]
+        pub container_id: Option<String>,
+        #[prost(btree_map = "string, string", tag = "14")]
+        pub labels: BTreeMap<String, String>,
+        #[prost(string, optional, tag = "15")]
+        pub owner: Option<String>,
+        #[prost(uint32, tag = "16")]
+        pub restarts: u32,
+        #[prost(string, optional, tag = "17")]
+        pub log_file: Option<String>,
+        #[prost(string, tag = "18")]
+        pub json: String,
+    }
+
+    include!(concat!(env!("OUT_DIR"), "/task_watchdog.v1.Watchdog.rs"));
+}
+
+fn timestamp(at: DateTime<Utc>) -> prost_types::Timestamp {
+    prost_types::Timestamp {
+        seconds: at.timestamp(),
+        nanos: at.timestamp_subsec_nanos() as i32,
+    }
+}
+
+impl From<TaskStatus> for v1::TaskStatus {
+    fn from(status: TaskStatus) -> Self {
+        match status {
+            TaskStatus::Queued => v1::TaskStatus::Queued,
+            TaskStatus::Starting => v1::TaskStatus::Starting,
+            TaskStatus::Running => v1::TaskStatus::Running,
+            TaskStatus::Completed => v1::TaskStatus::Completed,
+            TaskStatus::Failed => v1::TaskStatus::Failed,
+            TaskStatus::Cancelled => v1::TaskStatus::Cancelled,
+            TaskStatus::Killed => v1::TaskStatus::Killed,
+            TaskStatus::TimedOut => v1::TaskStatus::TimedOut,
+            TaskStatus::Unknown => v1::TaskStatus::Unknown,
+        }
+    }
+}
+
+impl From<TaskEntry> for v1::Task {
+    fn from(entry: TaskEntry) -> Self {
+        let json = serde_json::to_string(&entry).unwrap_or_default();
+        let TaskEntry { task_id, task, .. } = entry;
+        v1::Task {
+            task_id,
+            status: v1::TaskStatus::from(task.status) as i32,
+            command: task.command.to_string(),
+            description: task.description,
+            started_at: Some(timestamp(task.started_at)),
+            completed_at: task.completed_at.map(timestamp),
+            exit_code: task.result.and_then(|result| result.exit_code),
+            kill_reason: task.kill_reason,
+            // As serialized: `system_rebooted`, ...
+            failure_reason: task
+                .failure_reason
+                .and_then(|reason| serde_json::to_value(reason).ok())
+                .and_then(|reason| reason.as_str().map(str::to_string)),
+            triage_hint: task.triage_hint,
+            failure_excerpt: task.failure_excerpt,
+            pid: task.native.map(|native| native.pid),
+            container_id: task.docker.map(|docker| docker.container_id),
+            labels: task.labels,
+            owner: task.owner,
+            restarts: task.restarts,
+            log_file: task.log_file.map(|path| path.display().to_string()),
+            json,
+        }
+    }
+}
+
+impl From<&WatchdogEvent> for v1::Event {
+    fn from(event: &WatchdogEvent) -> Self {
+        v1::Event {
+            event: event.name().to_string(),
+            task_id: event.task_id().map(str::to_string),
+            at: Some(timestamp(Utc::now())),
+            json: serde_json::to_string(event).unwrap_or_default(),
+        }
+    }
+}
+
+fn internal(e: anyhow::Error) -> Status {
+    Status::internal(format!("{e:#}"))
+}
+
+/// `Watchdog` for the registry at `registry_path`, streaming the events
+/// published on `events`
+pub struct WatchdogService {
+    registry_path: PathBuf,
+    events: broadcast::Sender<WatchdogEvent>,
+}
+
+impl WatchdogService {
+    pub fn new(registry_path: PathBuf, events: broadcast::Sender<WatchdogEvent>) -> Self {
+        Self {
+            registry_path,
+            events,
+        }
+    }
+
+    fn registry(&self) -> Result<RegistryManager, Status> {
+        let mut registry = RegistryManager::new(&self.registry_path);
+        registry.set_actor(Actor::Api);
+        registry.load().map_err(internal)?;
+        Ok(registry)
+    }
+}
+
+/// Resolve `query` to a task, loading history too so finished tasks can
+/// still be looked up
+fn find_task(registry: &mut RegistryManager, query: &str) -> Result<(String, TaskInfo), Status> {
+    registry.load_history().map_err(internal)?;
+    let task_id = registry
+        .resolve_task_id(query)
+        .map_err(internal)?
+        .unwrap_or_else(|| query.to_string());
+    match registry.get_task(&task_id) {
+        Some(task) => Ok((task_id.clone(), task.clone())),
+        None => Err(Status::not_found(format!("Task {task_id} not found"))),
+    }
+}
+
+/// The process that sent `request`, from the socket's peer credentials
+fn peer<T>(request: &Request<T>) -> Peer {
+    Peer::from_cred(
+        request
+            .extensions()
+            .get::<tonic::transport::server::UdsConnectInfo>()
+            .and_then(|info| info.peer_cred),
+    )
+}
+
+type EventStream = Pin<Box<dyn Stream<Item = Result<v1::Event, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl v1::watchdog_server::Watchdog for WatchdogService {
+    async fn register_task(
+        &self,
+        request: Request<v1::RegisterTaskRequest>,
+    ) -> Result<Response<v1::Task>, Status> {
+        let peer = peer(&request);
+        let request = request.into_inner();
+        let task_id = request.task_id.clone();
+        let spec = TaskSpec {
+            task_id: request.task_id,
+            command: request.command,
+            shell: request.shell,
+            description: request.description,
+            expected_outcome: request.expected_outcome,
+            labels: request.labels,
+            group: request.group,
+            memory: None,
+            cpu: None,
+            rules: Vec::new(),
+            metadata: serde_json::Value::Null,
+            timeout: request.timeout,
+            after: request.after,
+            ready: request.ready,
+            max_memory: request.max_memory,
+            max_cpu: request.max_cpu,
+            max_processes: None,
+            max_open_files: None,
+            max_threads: None,
+            rlimits: Vec::new(),
+        };
+        let task = spec
+            .into_task(&Details::default(), &[], None, peer.origin("grpc"))
+            .map_err(|e| Status::invalid_argument(format!("{e:#}")))?;
+
+        let mut registry = self.registry()?;
+        if registry
+            .get_task(&task_id)
+            .is_some_and(|task| !task.status.is_terminal())
+        {
+            return Err(Status::already_exists(format!(
+                "Task {task_id} is already registered and not finished"
+            )));
+        }
+        let task = tasks::register_for(&mut registry, &peer, &task_id, task, request.queue)
+            .map_err(|e| Status::resource_exhausted(format!("{e:#}")))?;
+        info!(task: &task_id; "✅ Task {} registered via gRPC", task_id);
+        Ok(Response::new(
+            TaskEntry {
+                task_id,
+                task,
+                alive: None,
+            }
+            .into(),
+        ))
+    }
+
+    async fn kill(&self, request: Request<v1::KillRequest>) -> Result<Response<v1::Task>, Status> {
+        let peer = peer(&request);
+        let request = request.into_inner();
+        let mut registry = self.registry()?;
+        let (task_id, task) = find_task(&mut registry, &request.task_id)?;
+        if task.status.is_terminal() {
+            return Err(Status::failed_precondition(format!(
+                "Task {task_id} already finished ({})",
+                task.status
+            )));
+        }
+        tasks::ensure_peer_may_kill(&task_id, &task, &peer)
+            .map_err(|e| Status::permission_denied(format!("{e:#}")))?;
+        tasks::kill(&mut registry, &task_id, &peer.origin("grpc"))
+            .await
+            .map_err(internal)?;
+        let task = registry.get_task(&task_id).cloned().unwrap_or(task);
+        Ok(Response::new(
+            TaskEntry {
+                task_id,
+                task,
+                alive: None,
+            }
+            .into(),
+        ))
+    }
+
+    async fn check(
+        &self,
+        request: Request<v1::CheckRequest>,
+    ) -> Result<Response<v1::CheckResponse>, Status> {
+        let (task_id, task) = find_task(&mut self.registry()?, &request.into_inner().task_id)?;
+        let alive = tasks::alive(&task).await;
+        Ok(Response::new(v1::CheckResponse {
+            task: Some(
+                TaskEntry {
+                    task_id,
+                    task,
+                    alive,
+                }
+                .into(),
+            ),
+            alive,
+        }))
+    }
+
+    type StreamEventsStream = EventStream;
+
+    async fn stream_events(
+        &self,
+        request: Request<v1::StreamEventsRequest>,
+    ) -> Result<Response<EventStream>, Status> {
+        let v1::StreamEventsRequest { events, task_id } = request.into_inner();
+        let stream =
+            BroadcastStream::new(self.events.subscribe()).filter_map(move |event| match event {
+                Ok(event) => {
+                    let wanted = (events.is_empty() || events.iter().any(|e| e == event.name()))
+                        && task_id
+                            .as_deref()
+                            .is_none_or(|task_id| event.task_id() == Some(task_id));
+                    wanted.then(|| Ok(v1::Event::from(&event)))
+                }
+                Err(BroadcastStreamRecvError::Lagged(missed)) => {
+                    warn!(
+                        "⚠️  gRPC event stream fell behind; {} events dropped",
+                        missed
+                    );
+                    None
+                }
+            });
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn stats(
+        &self,
+        _request: Request<v1::StatsRequest>,
+    ) -> Result<Response<v1::Stats>, Status> {
+        let mut registry = self.registry()?;
+        registry.load_history().map_err(internal)?;
+        let stats = registry.stats();
+        Ok(Response::new(v1::Stats {
+            total: stats.total as u64,
+            queued: stats.queued as u64,
+            starting: stats.starting as u64,
+            running: stats.running as u64,
+            completed: stats.completed as u64,
+            failed: stats.failed as u64,
+            killed: stats.killed as u64,
+            timed_out: stats.timed_out as u64,
+            unknown: stats.unknown as u64,
+            cost_usd: registry.total_cost().usd,
+        }))
+    }
+}
+
+/// Listen on `socket`, replacing one left behind by a watchdog that is gone.
+/// Only this user may connect.
+fn bind(socket: &Path) -> Result<tokio::net::UnixListener> {
+    use std::os::unix::fs::PermissionsExt;
+
+    if socket.exists() {
+        if std::os::unix::net::UnixStream::connect(socket).is_ok() {
+            anyhow::bail!("{} is already being served", socket.display());
+        }
+        std::fs::remove_file(socket)
+            .with_context(|| format!("Failed to remove stale socket {}", socket.display()))?;
+    }
+    let listener = tokio::net::UnixListener::bind(socket)
+        .with_context(|| format!("Failed to listen on {}", socket.display()))?;
+    std::fs::set_permissions(socket, std::fs::Permissions::from_mode(0o600))
+        .with_context(|| format!("Failed to restrict {}", socket.display()))?;
+    Ok(listener)
+}
+
+/// Serve the gRPC API on `socket` until the process exits
+pub async fn serve(socket: PathBuf, service: WatchdogService) -> Result<()> {
+    let listener = bind(&socket)?;
+    info!("🔌 gRPC API listening on {}", socket.display());
+    verbose!("   Registry: {}", service.registry_path.display());
+    tonic::transport::Server::builder()
+        .add_service(WatchdogServer::new(service))
+        .serve_with_incoming(UnixListenerStream::new(listener))
+        .await
+        .context("gRPC server failed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper_util::rt::TokioIo;
+    use std::collections::BTreeMap;
+    use tonic::transport::{Channel, Endpoint};
+
+    async fn connect(socket: PathBuf) -> WatchdogClient<Channel> {
+        let channel = Endpoint::from_static("http://localhost")
+            .connect_with_connector(tower::service_fn(move |_| {
+                let socket = socket.clone();
+                async move {
+                    let stream = tokio::net::UnixStream::connect(socket).await?;
+                    Ok::<_, std::io::Error>(TokioIo::new(stream))
+                }
+            }))
+            .await
+            .unwrap();
+        WatchdogClient::new(channel)
+    }
+
+    #[tokio::test]
+    async fn test_register_check_kill_and_stream() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("watchdog.sock");
+        let (events, _) = broadcast::channel(16);
+        let service = WatchdogService::new(dir.path().join("registry.json"), events.clone());
+        tokio::spawn(serve(socket.clone(), service));
+        while !socket.exists() {
+            tokio::task::yield_now().await;
+        }
+        let mut client = connect(socket.clone()).await;
+
+        let task = client
+            .register_task(v1::RegisterTaskRequest {
+                task_id: "T1".into(),
+                command: "sleep 600".into(),
+                labels: BTreeMap::from([("team".into(), "go".into())]),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(task.task_id, "T1");
+        assert_eq!(task.status(), v1::TaskStatus::Running);
+        assert_eq!(task.labels["team"], "go");
+        let again = client
+            .register_task(v1::RegisterTaskRequest {
+                task_id: "T1".into(),
+                command: "sleep 600".into(),
+                ..Default::default()
+            })
+            .await;
+        assert_eq!(again.unwrap_err().code(), tonic::Code::AlreadyExists);
+
+        let checked = client
+            .check(v1::CheckRequest {
+                task_id: "T1".into(),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(checked.task.unwrap().command, "sleep 600");
+        let missing = client
+            .check(v1::CheckRequest {
+                task_id: "T2".into(),
+            })
+            .await;
+        assert_eq!(missing.unwrap_err().code(), tonic::Code::NotFound);
+
+        let mut stream = client
+            .stream_events(v1::StreamEventsRequest {
+                events: vec!["task_died".into()],
+                task_id: None,
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        events
+            .send(WatchdogEvent::TaskCompleted {
+                task_id: "T0".into(),
+            })
+            .unwrap();
+        events
+            .send(WatchdogEvent::TaskDied {
+                task_id: "T1".into(),
+                exit_code: Some(1),
+                triage_hint: None,
+                failure_excerpt: None,
+            })
+            .unwrap();
+        let event = stream.message().await.unwrap().unwrap();
+        assert_eq!(event.event, "task_died");
+        assert_eq!(event.task_id.as_deref(), Some("T1"));
+        assert!(event.json.contains("\"exit_code\":1"));
+
+        // Another user's task can't be killed, force or not
+        let mut registry = RegistryManager::new(dir.path().join("registry.json"));
+        registry.load().unwrap();
+        let theirs = TaskInfo {
+            owner: Some("alice".to_string()),
+            ..registry.get_task("T1").cloned().unwrap()
+        };
+        registry.upsert_task("T2".to_string(), theirs).unwrap();
+        let refused = client
+            .kill(v1::KillRequest {
+                task_id: "T2".into(),
+                force: true,
+            })
+            .await;
+        assert_eq!(refused.unwrap_err().code(), tonic::Code::PermissionDenied);
+
+        let killed = client
+            .kill(v1::KillRequest {
+                task_id: "T1".into(),
+                force: false,
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(killed.status(), v1::TaskStatus::Killed);
+        let stats = client
+            .stats(v1::StatsRequest {})
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!((stats.total, stats.killed), (2, 1));
+
+        // A second server on the same socket is refused
+        let (events, _) = broadcast::channel(1);
+        let service = WatchdogService::new(dir.path().join("registry.json"), events);
+        assert!(serve(socket, service).await.is_err());
+    }
+}
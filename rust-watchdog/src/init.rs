@@ -0,0 +1,394 @@
+//! `task-watchdog init`: scaffold the `.claude/` layout in a new repo.
+//!
+//! Existing files are left alone unless `force` is set, and `.gitignore`
+//! is only ever appended to, so running `init` twice is harmless.
+//!
+//! The default layout keeps the registry directly in `.claude/`. The
+//! commit-safe layout moves everything ephemeral (registry, lock file, crash
+//! reports) into `.claude/state/`, so `.claude/` itself can be committed and
+//! a single `.gitignore` line keeps PIDs and absolute paths out of git.
+
+use crate::config;
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Registry location in the default layout
+pub const REGISTRY_PATH: &str = ".claude/process_registry.json";
+
+/// Directory holding all ephemeral state in the commit-safe layout
+pub const STATE_DIR: &str = ".claude/state";
+
+/// Registry location in the commit-safe layout
+pub const STATE_REGISTRY_PATH: &str = ".claude/state/process_registry.json";
+
+/// Registry path for whichever layout the current directory uses
+pub fn default_registry_path() -> String {
+    if Path::new(STATE_DIR).is_dir() {
+        STATE_REGISTRY_PATH.to_string()
+    } else {
+        REGISTRY_PATH.to_string()
+    }
+}
+
+/// Starter daemon configuration, listing the built-in defaults
+pub const WATCHDOG_TOML: &str = r#"# task-watchdog configuration
+#
+# Every value below is the built-in default; uncomment a line to change it.
+# A flag on the command line (e.g. `run --interval 60`) overrides the value
+# here.
+
+[daemon]
+# Seconds between checks
+# interval = 300
+# Warn when the watchdog's own RSS exceeds this many MB
+# memory_budget_mb = 20
+# Re-exec the watchdog when its memory budget or leak alarm trips
+# restart_on_leak = false
+# Kill tasks over their --max-memory / --max-cpu instead of only warning
+# enforce_limits = false
+# Processes a task may run at once unless it sets --max-processes, and new
+# processes it may start per minute (catches runaway spawning), unlimited
+# by default
+# max_processes = 64
+# max_process_rate = 120
+# Open files and threads a task's processes may hold between them unless it
+# sets --max-open-files / --max-threads (catches leaks), unlimited by default
+# max_open_files = 4096
+# max_threads = 1024
+
+[registry]
+# path = ".claude/process_registry.json"
+
+[capacity]
+# Percent of the machine kept free of task reservations
+# headroom_percent = 10
+
+[queue]
+# Label whose values share queued capacity fairly
+# fair_by = "session"
+
+[queue.weights]
+# Relative share per label value (unlisted values get 1)
+# interactive = 3
+
+[limits]
+# Reservation of tasks that don't declare one (`register`, `up`), none by
+# default. In docker mode it also limits the container (to 512m and 1 CPU
+# when unset).
+# memory = "512m"
+# cpu = "1.0"
+
+[cleanup]
+# Days finished tasks are kept by `cleanup`
+# days = 7
+
+[docker]
+# Image of docker tasks that don't name one
+# image = "python:3.11-slim"
+# Remove containers once they have stopped (logs and exit code are saved
+# first), or only those that didn't fail
+# auto_remove = false
+# keep_on_failure = false
+
+# Notification channels, none by default. Each [notify.<name>] table is one
+# channel (desktop, webhook or slack), sent task failures and zombie kills
+# unless it lists its own events. During its quiet hours and days, alerts
+# wait for a digest sent when they end, except escalations and the events
+# listed as critical.
+# [notify.desktop]
+# kind = "desktop"
+# quiet_hours = "22:00-07:00"
+# quiet_days = ["sat", "sun"]
+# critical = ["zombie_detected", "limit_exceeded"]
+
+# [notify.team]
+# kind = "slack"
+# url = "{{env.SLACK_WEBHOOK_URL}}"
+# events = ["task_died", "timed_out", "zombie_detected", "duration_anomaly"]
+"#;
+
+/// Default constitution, in the format the dev-kid validator reads
+pub const CONSTITUTION_MD: &str = r#"# Project Constitution
+
+**Purpose**: Define immutable development rules for your project
+
+---
+
+## Code Standards
+
+- TYPE_HINTS_REQUIRED: All functions must have type annotations
+- DOCSTRINGS_REQUIRED: All public functions must have docstrings
+
+## Security Standards
+
+- NO_HARDCODED_SECRETS: No hardcoded API keys or passwords
+
+## Testing Standards
+
+- TEST_COVERAGE_REQUIRED: All modules must have test files
+"#;
+
+/// Claude Code settings that rehydrate watchdog state on session start
+/// (including the restart after context compaction)
+pub const HOOKS_JSON: &str = r#"{
+  "hooks": {
+    "SessionStart": [
+      {
+        "hooks": [
+          {
+            "type": "command",
+            "command": "task-watchdog rehydrate"
+          }
+        ]
+      }
+    ]
+  }
+}
+"#;
+
+/// Ephemeral watchdog state that must never be committed (default layout)
+pub const GITIGNORE_ENTRIES: &[&str] = &[
+    ".claude/process_registry.json",
+    ".claude/process_registry.db",
+    ".claude/process_registry.lock",
+    ".claude/process_registry.output.sock",
+    ".claude/process_registry.api.sock",
+    ".claude/process_events.jsonl",
+    ".claude/process_registry.samples.json",
+    ".claude/process_registry.notify.json",
+    ".claude/shell_history",
+    ".claude/crash/",
+    ".claude/*.log",
+    ".claude/docker_calls.jsonl",
+];
+
+/// Ephemeral watchdog state that must never be committed (commit-safe layout)
+pub const COMMIT_SAFE_GITIGNORE_ENTRIES: &[&str] = &[".claude/state/"];
+
+/// Header written above the entries `init` appends to `.gitignore`
+const GITIGNORE_HEADER: &str = "# task-watchdog state";
+
+/// What `init` did with one path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Created,
+    Overwritten,
+    Updated,
+    /// Registry moved here from the default layout
+    Moved,
+    /// Already present; left untouched
+    Kept,
+}
+
+/// What `init` should scaffold
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InitOptions {
+    /// Write `.claude/settings.json` with the Claude Code hooks
+    pub hooks: bool,
+    /// Overwrite files that already exist
+    pub force: bool,
+    /// Use the commit-safe layout (ephemeral state under `.claude/state/`)
+    pub commit_safe: bool,
+}
+
+/// Scaffold the layout under `root`, returning what happened to each path
+pub fn init(root: &Path, options: InitOptions) -> Result<Vec<(PathBuf, Action)>> {
+    let claude_dir = root.join(".claude");
+    let mut actions = Vec::new();
+
+    let mut dirs = vec![claude_dir.clone()];
+    if options.commit_safe {
+        dirs.push(root.join(STATE_DIR));
+    }
+    for dir in dirs {
+        if dir.is_dir() {
+            actions.push((dir, Action::Kept));
+        } else {
+            fs::create_dir_all(&dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+            actions.push((dir, Action::Created));
+        }
+    }
+
+    if options.commit_safe {
+        let (from, to) = (root.join(REGISTRY_PATH), root.join(STATE_REGISTRY_PATH));
+        if from.exists() && !to.exists() {
+            fs::rename(&from, &to).with_context(|| {
+                format!("Failed to move {} to {}", from.display(), to.display())
+            })?;
+            actions.push((to, Action::Moved));
+        }
+    }
+
+    let config = if options.commit_safe {
+        WATCHDOG_TOML.replace(REGISTRY_PATH, STATE_REGISTRY_PATH)
+    } else {
+        WATCHDOG_TOML.to_string()
+    };
+    let mut files = vec![
+        (claude_dir.join(config::CONFIG_FILE), config.as_str()),
+        (
+            root.join("memory-bank")
+                .join("shared")
+                .join(".constitution.md"),
+            CONSTITUTION_MD,
+        ),
+    ];
+    if options.hooks {
+        files.push((claude_dir.join("settings.json"), HOOKS_JSON));
+    }
+    for (path, contents) in files {
+        let action = write_file(&path, contents, options.force)?;
+        actions.push((path, action));
+    }
+
+    let gitignore = root.join(".gitignore");
+    let entries = if options.commit_safe {
+        COMMIT_SAFE_GITIGNORE_ENTRIES
+    } else {
+        GITIGNORE_ENTRIES
+    };
+    let action = update_gitignore(&gitignore, entries)?;
+    actions.push((gitignore, action));
+
+    Ok(actions)
+}
+
+fn write_file(path: &Path, contents: &str, force: bool) -> Result<Action> {
+    let existed = path.exists();
+    if existed && !force {
+        return Ok(Action::Kept);
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(if existed {
+        Action::Overwritten
+    } else {
+        Action::Created
+    })
+}
+
+/// Append whichever of `entries` are missing
+fn update_gitignore(path: &Path, entries: &[&str]) -> Result<Action> {
+    let existing = match fs::read_to_string(path) {
+        Ok(s) => Some(s),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    let current = existing.as_deref().unwrap_or_default();
+    let present: Vec<&str> = current.lines().map(str::trim).collect();
+    let missing: Vec<&str> = entries
+        .iter()
+        .copied()
+        .filter(|entry| !present.contains(entry))
+        .collect();
+    if missing.is_empty() {
+        return Ok(Action::Kept);
+    }
+
+    let mut contents = current.to_string();
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    if !contents.is_empty() {
+        contents.push('\n');
+    }
+    contents.push_str(GITIGNORE_HEADER);
+    contents.push('\n');
+    for entry in missing {
+        contents.push_str(entry);
+        contents.push('\n');
+    }
+    fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))?;
+
+    Ok(if existing.is_some() {
+        Action::Updated
+    } else {
+        Action::Created
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_init_is_idempotent() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(".gitignore"), "target/").unwrap();
+
+        let options = InitOptions {
+            hooks: true,
+            ..Default::default()
+        };
+        let first = init(dir.path(), options).unwrap();
+        assert!(first
+            .iter()
+            .all(|(_, action)| matches!(action, Action::Created | Action::Updated)));
+        assert!(dir.path().join(".claude/watchdog.toml").exists());
+        assert!(dir.path().join(".claude/settings.json").exists());
+        assert!(dir
+            .path()
+            .join("memory-bank/shared/.constitution.md")
+            .exists());
+
+        let second = init(dir.path(), options).unwrap();
+        assert!(second.iter().all(|(_, action)| *action == Action::Kept));
+
+        let gitignore = fs::read_to_string(dir.path().join(".gitignore")).unwrap();
+        assert!(gitignore.starts_with("target/\n\n# task-watchdog state\n"));
+        assert_eq!(
+            gitignore.matches(".claude/process_registry.json").count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_init_keeps_existing_files_unless_forced() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join(".claude/watchdog.toml");
+        fs::create_dir_all(config.parent().unwrap()).unwrap();
+        fs::write(&config, "[daemon]\ninterval = 30\n").unwrap();
+
+        init(dir.path(), InitOptions::default()).unwrap();
+        assert_eq!(
+            fs::read_to_string(&config).unwrap(),
+            "[daemon]\ninterval = 30\n"
+        );
+        assert!(!dir.path().join(".claude/settings.json").exists());
+
+        let options = InitOptions {
+            force: true,
+            ..Default::default()
+        };
+        let forced = init(dir.path(), options).unwrap();
+        assert!(forced.contains(&(config.clone(), Action::Overwritten)));
+        assert_eq!(fs::read_to_string(&config).unwrap(), WATCHDOG_TOML);
+    }
+
+    #[test]
+    fn test_commit_safe_layout_moves_state_out_of_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let legacy = dir.path().join(REGISTRY_PATH);
+        fs::create_dir_all(legacy.parent().unwrap()).unwrap();
+        fs::write(&legacy, "{}").unwrap();
+
+        let options = InitOptions {
+            commit_safe: true,
+            ..Default::default()
+        };
+        let actions = init(dir.path(), options).unwrap();
+        let moved = dir.path().join(STATE_REGISTRY_PATH);
+        assert!(actions.contains(&(moved.clone(), Action::Moved)));
+        assert!(moved.exists() && !legacy.exists());
+
+        let gitignore = fs::read_to_string(dir.path().join(".gitignore")).unwrap();
+        assert_eq!(gitignore, "# task-watchdog state\n.claude/state/\n");
+        let config = fs::read_to_string(dir.path().join(".claude/watchdog.toml")).unwrap();
+        assert!(config.contains(STATE_REGISTRY_PATH));
+    }
+}
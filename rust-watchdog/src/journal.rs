@@ -0,0 +1,143 @@
+//! Structured logging to journald.
+//!
+//! Under systemd, output lines go straight to the journal over its native
+//! datagram protocol instead of stdout, with `SYSLOG_IDENTIFIER=task-watchdog`
+//! and, for task events, a `TASK_ID` field:
+//!
+//! ```text
+//! journalctl -t task-watchdog TASK_ID=T001
+//! ```
+//!
+//! Journal mode is for the daemons (`run`, `serve`): it is enabled
+//! explicitly with `--journald`, or automatically when systemd connected
+//! stderr to the journal (`JOURNAL_STREAM`). Other commands always print,
+//! so their results reach stdout even when a unit runs them. If a message
+//! cannot be sent, the caller falls back to printing it.
+
+#[cfg(unix)]
+use std::os::unix::net::UnixDatagram;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(unix)]
+use std::sync::OnceLock;
+
+/// Where journald listens for native protocol messages
+pub const SOCKET_PATH: &str = "/run/systemd/journal/socket";
+
+/// Identifier for `journalctl -t`
+pub const IDENTIFIER: &str = "task-watchdog";
+
+/// syslog priorities used by the output macros
+pub const PRIORITY_WARNING: u8 = 4;
+pub const PRIORITY_INFO: u8 = 6;
+pub const PRIORITY_DEBUG: u8 = 7;
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+#[cfg(unix)]
+static SOCKET: OnceLock<Option<UnixDatagram>> = OnceLock::new();
+
+/// Switch journal mode on when forced or when running under systemd.
+/// Returns whether it is on.
+pub fn init(force: bool) -> bool {
+    let active = (force || stderr_is_journal()) && Path::new(SOCKET_PATH).exists();
+    ACTIVE.store(active, Ordering::Relaxed);
+    active
+}
+
+/// Whether output goes to the journal
+pub fn active() -> bool {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+/// systemd sets `JOURNAL_STREAM=<dev>:<inode>` for the stream it connected;
+/// it only counts if that is still our stderr (not inherited by a child
+/// whose output was redirected)
+#[cfg(unix)]
+fn stderr_is_journal() -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    let Ok(stream) = std::env::var("JOURNAL_STREAM") else {
+        return false;
+    };
+    let Ok(meta) = std::fs::metadata("/proc/self/fd/2") else {
+        return false;
+    };
+    stream == format!("{}:{}", meta.dev(), meta.ino())
+}
+
+#[cfg(not(unix))]
+fn stderr_is_journal() -> bool {
+    false
+}
+
+/// Send one message. Returns false when journal mode is off or the send
+/// failed, so the caller can print the line instead.
+#[cfg(unix)]
+pub fn send(priority: u8, message: &str, task_id: Option<&str>) -> bool {
+    if !active() {
+        return false;
+    }
+    let socket = SOCKET.get_or_init(|| {
+        let socket = UnixDatagram::unbound().ok()?;
+        socket.connect(SOCKET_PATH).ok()?;
+        Some(socket)
+    });
+    let Some(socket) = socket else {
+        return false;
+    };
+    socket
+        .send(&encode(priority, message.trim(), task_id))
+        .is_ok()
+}
+
+#[cfg(not(unix))]
+pub fn send(_priority: u8, _message: &str, _task_id: Option<&str>) -> bool {
+    false
+}
+
+/// Encode fields in the journal's native protocol
+pub fn encode(priority: u8, message: &str, task_id: Option<&str>) -> Vec<u8> {
+    let mut payload = Vec::new();
+    let priority = priority.to_string();
+    let mut fields = vec![
+        ("MESSAGE", message),
+        ("PRIORITY", priority.as_str()),
+        ("SYSLOG_IDENTIFIER", IDENTIFIER),
+    ];
+    if let Some(task_id) = task_id {
+        fields.push(("TASK_ID", task_id));
+    }
+    for (key, value) in fields {
+        payload.extend_from_slice(key.as_bytes());
+        if value.contains('\n') {
+            // Multi-line values carry an explicit little-endian length
+            payload.push(b'\n');
+            payload.extend_from_slice(&(value.len() as u64).to_le_bytes());
+        } else {
+            payload.push(b'=');
+        }
+        payload.extend_from_slice(value.as_bytes());
+        payload.push(b'\n');
+    }
+    payload
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_native_protocol() {
+        let payload = encode(PRIORITY_WARNING, "💀 T001 died", Some("T001"));
+        assert_eq!(
+            String::from_utf8(payload).unwrap(),
+            "MESSAGE=💀 T001 died\nPRIORITY=4\nSYSLOG_IDENTIFIER=task-watchdog\nTASK_ID=T001\n"
+        );
+
+        let payload = encode(PRIORITY_INFO, "a\nb", None);
+        let mut expected = b"MESSAGE\n".to_vec();
+        expected.extend_from_slice(&3u64.to_le_bytes());
+        expected.extend_from_slice(b"a\nb\n");
+        assert!(payload.starts_with(&expected));
+    }
+}
@@ -0,0 +1,395 @@
+//! Launch files: a set of tasks declared together, started by `up` in
+//! dependency order and stopped together by `down`.
+//!
+//! ```toml
+//! name = "pipeline"   # defaults to the file name
+//!
+//! [tasks.db]
+//! command = "postgres -D data"
+//! ready = "port:5432"
+//! restart = "on-failure"
+//!
+//! [tasks.api]
+//! command = "cargo run -p api"
+//! after = ["db"]
+//! ready = "http://localhost:8080/health"
+//! memory = "2g"
+//!
+//! [tasks.e2e]
+//! command = "npm test"
+//! after = ["api"]
+//! timeout = "20m"
+//! ```
+//!
+//! TOML like `watchdog.toml`, or the same structure as JSON in a `.json`
+//! file. Commands and other text may use template variables
+//! (`{{branch}}`, `{{task_id}}`, `--var` values, see [`crate::template`]). Every task is labelled `up=<name>`, which is how `up` recognizes
+//! what it started before and `down` finds what to stop.
+
+use crate::config;
+use crate::policy;
+use crate::tasks::{self, Details};
+use crate::template::Vars;
+use crate::types::{
+    DockerTask, ExecutionMode, RestartPolicy, Rlimit, TaskCommand, TaskInfo, TaskOrigin,
+};
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+/// Label carrying the name of the set a task was started in
+pub const SET_LABEL: &str = "up";
+
+/// A launch file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LaunchFile {
+    /// Name of the set (the file name without its extension if left out)
+    pub name: Option<String>,
+    /// Tasks by ID
+    pub tasks: BTreeMap<String, LaunchTask>,
+}
+
+/// One task of a launch file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LaunchTask {
+    pub command: String,
+    /// Run the command with `sh -c` instead of direct execution
+    #[serde(default)]
+    pub shell: bool,
+    /// `native` (the default) or `docker`
+    pub mode: Option<ExecutionMode>,
+    /// Image for `docker` tasks
+    pub image: Option<String>,
+    /// Remove the container once it has stopped (`docker` tasks)
+    pub auto_remove: Option<bool>,
+    /// Keep the container of a failed run, removing only those that
+    /// completed (implies `auto_remove` for them)
+    pub keep_on_failure: Option<bool>,
+    /// Reservation, and the container's limits in `docker` mode
+    pub memory: Option<String>,
+    pub cpu: Option<String>,
+    /// Tasks that must be ready first (in this file or already registered)
+    #[serde(default)]
+    pub after: Vec<String>,
+    /// Readiness probe, as for `--ready` (`port:8080`, ...)
+    pub ready: Option<String>,
+    /// Longest it may run, as for `--timeout` (`30m`, ...)
+    pub timeout: Option<String>,
+    /// Memory and CPU above which it is flagged, as for `--max-memory`
+    /// (`2g`, ...) and `--max-cpu` (percent of one core)
+    pub max_memory: Option<String>,
+    pub max_cpu: Option<f64>,
+    /// Processes it may run at once, as for `--max-processes`
+    pub max_processes: Option<u64>,
+    /// As for `--max-open-files` and `--max-threads`
+    pub max_open_files: Option<u64>,
+    pub max_threads: Option<u64>,
+    /// Kernel limits, as for `--rlimit` (`nofile=4096`, ...)
+    #[serde(default)]
+    pub rlimits: Vec<String>,
+    /// Whether the daemon starts it again once it has finished
+    #[serde(default)]
+    pub restart: RestartPolicy,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+}
+
+/// What `up` gives tasks that leave it out (`--image`, `--memory`, `--cpu`,
+/// `--auto-remove`, `--keep-on-failure`)
+#[derive(Debug, Default)]
+pub struct Defaults {
+    /// Image of `docker` tasks
+    pub image: Option<String>,
+    pub memory: Option<String>,
+    pub cpu: Option<String>,
+    pub auto_remove: bool,
+    pub keep_on_failure: bool,
+}
+
+impl LaunchFile {
+    /// Read a launch file, returning it with the name of its set. Template
+    /// variables are replaced in the name and in each task's text fields.
+    pub fn load(path: &Path, vars: &Vars) -> Result<(String, Self)> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let value = if path.extension().is_some_and(|ext| ext == "json") {
+            serde_json::from_str(&content)?
+        } else {
+            config::toml_to_json(&content)?
+        };
+        let mut file: LaunchFile = serde_json::from_value(value)
+            .with_context(|| format!("Invalid launch file {}", path.display()))?;
+        for (task_id, task) in &mut file.tasks {
+            task.render(&vars.for_task(task_id))
+                .with_context(|| format!("In task {task_id} of {}", path.display()))?;
+        }
+        let name = match &file.name {
+            Some(name) => vars.render(name)?,
+            None => path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .context("A launch file without a `name` needs a file name")?,
+        };
+        if name.is_empty() {
+            bail!("The set name in {} is empty", path.display());
+        }
+        Ok((name, file))
+    }
+
+    /// Fill in what tasks leave out from `defaults`
+    pub fn apply_defaults(&mut self, defaults: &Defaults) {
+        for task in self.tasks.values_mut() {
+            if task.mode == Some(ExecutionMode::Docker) {
+                if task.image.is_none() {
+                    task.image = defaults.image.clone();
+                }
+                task.auto_remove.get_or_insert(defaults.auto_remove);
+                task.keep_on_failure.get_or_insert(defaults.keep_on_failure);
+            }
+            if task.memory.is_none() {
+                task.memory = defaults.memory.clone();
+            }
+            if task.cpu.is_none() {
+                task.cpu = defaults.cpu.clone();
+            }
+        }
+    }
+
+    /// Task IDs in the order to start them: each after the tasks in this
+    /// file it waits for. Fails on a dependency cycle.
+    pub fn start_order(&self) -> Result<Vec<String>> {
+        let mut order = Vec::with_capacity(self.tasks.len());
+        let mut started = BTreeSet::new();
+        while order.len() < self.tasks.len() {
+            let next: Vec<&String> = self
+                .tasks
+                .iter()
+                .filter(|(task_id, spec)| {
+                    !started.contains(task_id.as_str())
+                        && spec.after.iter().all(|dep| {
+                            started.contains(dep.as_str()) || !self.tasks.contains_key(dep)
+                        })
+                })
+                .map(|(task_id, _)| task_id)
+                .collect();
+            if next.is_empty() {
+                let stuck: Vec<&str> = self
+                    .tasks
+                    .keys()
+                    .filter(|task_id| !started.contains(task_id.as_str()))
+                    .map(String::as_str)
+                    .collect();
+                bail!("Dependency cycle between {}", stuck.join(", "));
+            }
+            for task_id in next {
+                started.insert(task_id.as_str());
+                order.push(task_id.clone());
+            }
+        }
+        Ok(order)
+    }
+}
+
+impl LaunchTask {
+    fn render(&mut self, vars: &Vars) -> Result<()> {
+        self.command = vars.render(&self.command)?;
+        for field in [
+            &mut self.image,
+            &mut self.memory,
+            &mut self.cpu,
+            &mut self.ready,
+            &mut self.timeout,
+            &mut self.max_memory,
+            &mut self.description,
+        ] {
+            vars.render_opt(field)?;
+        }
+        vars.render_values(&mut self.labels)
+    }
+
+    /// The task to start for this definition in `set`, without its process
+    /// or container yet. The command must pass the policy.
+    pub fn to_task(&self, set: &str, origin: TaskOrigin) -> Result<TaskInfo> {
+        let command = TaskCommand::parse(&self.command, self.shell)?;
+        policy::check_command(&command)?;
+        let mut labels = self.labels.clone();
+        labels.insert(SET_LABEL.to_string(), set.to_string());
+        let details = Details {
+            description: self.description.clone(),
+            labels,
+            timeout_secs: self
+                .timeout
+                .as_deref()
+                .map(tasks::parse_timeout)
+                .transpose()?,
+            max_memory_mb: self
+                .max_memory
+                .as_deref()
+                .map(tasks::parse_max_memory)
+                .transpose()?,
+            max_cpu_percent: self.max_cpu,
+            max_processes: self.max_processes,
+            max_open_files: self.max_open_files,
+            max_threads: self.max_threads,
+            rlimits: self
+                .rlimits
+                .iter()
+                .map(|setting| Rlimit::parse_setting(setting))
+                .collect::<Result<_>>()?,
+            depends_on: self.after.clone(),
+            readiness: self.ready.as_deref().map(str::parse).transpose()?,
+            ..Default::default()
+        };
+        let reservation = tasks::reservation_from(self.memory.clone(), self.cpu.clone())?;
+        let mut task = tasks::new_task(command, details, Vec::new(), reservation, origin);
+        task.mode = self.mode.clone().unwrap_or(ExecutionMode::Native);
+        task.restart = self.restart;
+        if task.mode == ExecutionMode::Native && self.image.is_some() {
+            bail!("`image` only applies to docker tasks");
+        }
+        if task.mode == ExecutionMode::Native
+            && (self.auto_remove == Some(true) || self.keep_on_failure == Some(true))
+        {
+            bail!("`auto_remove` and `keep_on_failure` only apply to docker tasks");
+        }
+        Ok(task)
+    }
+
+    /// Apply the container settings to `container`, started for this
+    /// definition
+    pub fn configure(&self, container: &mut DockerTask) {
+        container.keep_on_failure = self.keep_on_failure == Some(true);
+        container.auto_remove = self.auto_remove == Some(true) || container.keep_on_failure;
+    }
+
+    /// Whether `running` is what this definition (built as `wanted`) would
+    /// start, so `up` can leave it alone
+    pub fn is_running_as(&self, wanted: &TaskInfo, running: &TaskInfo) -> bool {
+        let image = running.docker.as_ref().and_then(|d| d.image.as_deref());
+        running.labels.get(SET_LABEL) == wanted.labels.get(SET_LABEL)
+            && running.command == wanted.command
+            && running.mode == wanted.mode
+            && running.reservation == wanted.reservation
+            && running.depends_on == wanted.depends_on
+            && running.readiness == wanted.readiness
+            && running.timeout_secs == wanted.timeout_secs
+            && running.max_memory_mb == wanted.max_memory_mb
+            && running.max_cpu_percent == wanted.max_cpu_percent
+            && running.max_processes == wanted.max_processes
+            && running.max_open_files == wanted.max_open_files
+            && running.max_threads == wanted.max_threads
+            && running.rlimits == wanted.rlimits
+            && running.restart == wanted.restart
+            && image == self.image.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(toml: &str) -> LaunchFile {
+        serde_json::from_value(config::toml_to_json(toml).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_start_order_follows_dependencies() {
+        let file = parse(
+            r#"
+            [tasks.e2e]
+            command = "npm test"
+            after = ["api", "fixtures"]
+
+            [tasks.api]
+            command = "cargo run"
+            after = ["db"]
+            ready = "port:8080"
+            restart = "on-failure"
+
+            [tasks.db]
+            command = "postgres"
+            labels = { team = "data" }
+
+            [tasks.fixtures]
+            command = "./load.sh"
+            after = ["registered-elsewhere"]
+            "#,
+        );
+        assert_eq!(file.tasks["api"].restart, RestartPolicy::OnFailure);
+        assert_eq!(file.tasks["db"].labels["team"], "data");
+        assert_eq!(
+            file.start_order().unwrap(),
+            vec!["db", "fixtures", "api", "e2e"]
+        );
+    }
+
+    #[test]
+    fn test_dependency_cycle_is_refused() {
+        let file = parse(
+            r#"
+            [tasks.a]
+            command = "true"
+            after = ["b"]
+
+            [tasks.b]
+            command = "true"
+            after = ["a"]
+            "#,
+        );
+        let err = file.start_order().unwrap_err().to_string();
+        assert!(err.contains("a, b"), "{err}");
+    }
+
+    #[test]
+    fn test_defaults_fill_what_tasks_leave_out() {
+        let mut file = parse(
+            r#"
+            [tasks.web]
+            command = "serve"
+            memory = "2g"
+
+            [tasks.job]
+            command = "python job.py"
+            mode = "docker"
+            "#,
+        );
+        file.apply_defaults(&Defaults {
+            image: Some("python:3.12-slim".into()),
+            memory: Some("512m".into()),
+            cpu: Some("1".into()),
+            keep_on_failure: true,
+            ..Default::default()
+        });
+        let (web, job) = (&file.tasks["web"], &file.tasks["job"]);
+        assert_eq!(web.image, None);
+        assert_eq!(web.keep_on_failure, None);
+        assert_eq!(web.memory.as_deref(), Some("2g"));
+        assert_eq!(web.cpu.as_deref(), Some("1"));
+        assert_eq!(job.image.as_deref(), Some("python:3.12-slim"));
+        assert_eq!(job.memory.as_deref(), Some("512m"));
+
+        // Keeping failed containers removes the others
+        let mut container = DockerTask {
+            container_id: "abc".into(),
+            container_name: "dev-task-job".into(),
+            resource_limits: Default::default(),
+            stop_timeout_secs: None,
+            image: None,
+            auto_remove: false,
+            keep_on_failure: false,
+            exit_code: None,
+        };
+        job.configure(&mut container);
+        assert!(container.auto_remove && container.keep_on_failure);
+    }
+
+    #[test]
+    fn test_unknown_fields_are_refused() {
+        let value = config::toml_to_json("[tasks.a]\ncomand = \"true\"\n").unwrap();
+        assert!(serde_json::from_value::<LaunchFile>(value).is_err());
+    }
+}
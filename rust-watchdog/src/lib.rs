@@ -1,7 +1,56 @@
 // Library interface for task-watchdog
 // Exposes modules for testing and external use
 
+#[cfg(feature = "http")]
+pub mod api;
+pub mod build_info;
+pub mod capacity;
+pub mod capture;
+#[cfg(unix)]
+pub mod chaos;
+pub mod config;
+pub mod coredump;
+pub mod crash;
+pub mod daemon;
+pub mod discover;
 pub mod docker;
+pub mod docker_recorder;
+pub mod done_files;
+pub mod exits;
+pub mod files;
+#[cfg(all(feature = "grpc", unix))]
+pub mod grpc;
+pub mod init;
+pub mod journal;
+pub mod launch;
+pub mod lifecycle;
+pub mod logs;
+pub mod metrics;
+pub mod monitor;
+pub mod notify;
+pub mod output;
+pub mod policy;
+pub mod ports;
 pub mod process;
+pub mod queue;
+pub mod readiness;
 pub mod registry;
-pub mod types;
+pub mod samples;
+pub mod self_memory;
+pub mod shell;
+pub mod storage;
+pub mod swarm;
+pub mod tasks;
+pub mod template;
+pub mod thermal;
+pub mod tmux;
+#[cfg(feature = "self-update")]
+pub mod update;
+pub mod whoami;
+
+// The data model and report logic live in `task-watchdog-core` (no OS or
+// async dependencies); re-exported so `task_watchdog::types` etc. still work
+pub use task_watchdog_core::{
+    alerts, anomaly, bundle, clock, events, excerpt, filter, health, report, state, timeline,
+    triage, types, typescript, units,
+};
@@ -0,0 +1,278 @@
+//! The lifecycle journal: every status change of every task, appended to
+//! `process_events.jsonl` next to the registry with when it happened and
+//! who did it, so `history` can tell how a task got where it is and
+//! `replay` can rebuild what happened during an incident afterwards (see
+//! [`crate::timeline`]).
+//!
+//! The registry only keeps a task's latest status; marking a task failed
+//! overwrites whatever it was before. Every process that changes the
+//! registry records its changes here, under the registry lock, so the
+//! journal is in the order the changes were made. The daemon adds the
+//! events it publishes that change no status (zombies, thresholds, the
+//! counts after a check when they changed), so the journal grows with
+//! activity rather than with uptime.
+
+use crate::events::WatchdogEvent;
+use crate::files;
+use crate::process::ProcessManager;
+use crate::report::RegistryStats;
+use crate::timeline;
+use crate::types::{ProcessRegistry, TaskInfo, TaskStatus};
+use anyhow::{Context, Result};
+use chrono::Utc;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+pub use crate::timeline::{Actor, Change, EventRecord, JournalLine, Transition};
+
+/// Lifecycle journal of the registry at `registry_path`:
+/// `process_registry.json` → `process_events.jsonl`
+pub fn events_path(registry_path: &Path) -> PathBuf {
+    let stem = registry_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+    let name = stem.strip_suffix("_registry").unwrap_or(&stem);
+    registry_path.with_file_name(format!("{name}_events.jsonl"))
+}
+
+/// A status change of `task_id` from `from` to `to`'s status, made now
+fn transition(
+    task_id: &str,
+    from: Option<&TaskStatus>,
+    to: Option<&TaskInfo>,
+    actor: Actor,
+) -> Transition {
+    static USER: OnceLock<Option<String>> = OnceLock::new();
+    let to_status = to.map(|task| &task.status);
+    Transition {
+        at: Utc::now(),
+        task_id: task_id.to_string(),
+        change: Change::of(from, to_status),
+        from: from.cloned(),
+        to: to_status.cloned(),
+        actor,
+        user: USER.get_or_init(ProcessManager::current_user).clone(),
+        reason: to.and_then(reason),
+    }
+}
+
+/// Why `task` finished, if it did
+fn reason(task: &TaskInfo) -> Option<String> {
+    match task.status {
+        TaskStatus::Killed | TaskStatus::TimedOut => task.kill_reason.clone(),
+        TaskStatus::Failed | TaskStatus::Completed => {
+            if let Some(failure) = task.failure_reason {
+                return Some(failure.to_string());
+            }
+            let exit_code = task
+                .docker
+                .as_ref()
+                .and_then(|docker| docker.exit_code)
+                .or_else(|| task.result.as_ref().and_then(|result| result.exit_code));
+            exit_code.map(|code| format!("exit code {code}"))
+        }
+        _ => None,
+    }
+}
+
+/// Tasks registered in `after` that weren't in `before`, and tasks whose
+/// status differs between them. Tasks missing from `after` are left out:
+/// they were archived or removed, and cleanup records its own.
+pub fn diff(before: &ProcessRegistry, after: &ProcessRegistry, actor: Actor) -> Vec<Transition> {
+    let mut changes: Vec<Transition> = after
+        .tasks
+        .iter()
+        .filter_map(|(task_id, task)| {
+            let from = before.tasks.get(task_id).map(|task| &task.status);
+            (from != Some(&task.status)).then(|| transition(task_id, from, Some(task), actor))
+        })
+        .collect();
+    changes.sort_by(|a, b| a.task_id.cmp(&b.task_id));
+    changes
+}
+
+/// `tasks` were cleaned up
+pub fn cleaned_up(tasks: &[(String, TaskInfo)], actor: Actor) -> Vec<Transition> {
+    tasks
+        .iter()
+        .map(|(task_id, task)| transition(task_id, Some(&task.status), None, actor))
+        .collect()
+}
+
+/// Append `transitions` to the journal at `path`, created with `mode`
+pub fn append(path: &Path, transitions: &[Transition], mode: u32) -> Result<()> {
+    if transitions.is_empty() {
+        return Ok(());
+    }
+    let mut lines = String::new();
+    for transition in transitions {
+        lines += &serde_json::to_string(transition)?;
+        lines.push('\n');
+    }
+    let mut file = files::append_options(mode)
+        .open(path)
+        .with_context(|| format!("Failed to open lifecycle journal {}", path.display()))?;
+    // One write, so concurrent appends don't interleave
+    file.write_all(lines.as_bytes())
+        .with_context(|| format!("Failed to append to lifecycle journal {}", path.display()))
+}
+
+/// Every line of the journal at `path`, oldest first, with the number of
+/// lines that couldn't be read (e.g. cut short by a crash, or from a newer
+/// release). A missing journal has none.
+pub fn read_all(path: &Path) -> Result<(Vec<JournalLine>, usize)> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((Vec::new(), 0)),
+        Err(e) => {
+            return Err(e)
+                .with_context(|| format!("Failed to open lifecycle journal {}", path.display()))
+        }
+    };
+    let mut lines = Vec::new();
+    let mut skipped = 0;
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(line) => lines.push(line),
+            Err(_) => skipped += 1,
+        }
+    }
+    Ok((lines, skipped))
+}
+
+/// Every transition of `task_id` in the journal at `path`, oldest first,
+/// with the number of lines that couldn't be read
+pub fn read(path: &Path, task_id: &str) -> Result<(Vec<Transition>, usize)> {
+    let (lines, skipped) = read_all(path)?;
+    let transitions = lines
+        .into_iter()
+        .filter_map(|line| match line {
+            JournalLine::Transition(transition) if transition.task_id == task_id => {
+                Some(transition)
+            }
+            _ => None,
+        })
+        .collect();
+    Ok((transitions, skipped))
+}
+
+/// Appends the daemon's events to the journal
+pub struct EventLog {
+    file: File,
+    last_stats: Option<RegistryStats>,
+}
+
+impl EventLog {
+    /// Open the journal at `path` for appending, creating it with `mode`
+    pub fn open(path: &Path, mode: u32) -> Result<Self> {
+        let file = files::append_options(mode)
+            .open(path)
+            .with_context(|| format!("Failed to open lifecycle journal {}", path.display()))?;
+        Ok(Self {
+            file,
+            last_stats: None,
+        })
+    }
+
+    /// Append `event` as published now, unless it is a status change
+    /// (recorded already) or a check that left the counts as they were
+    pub fn record(&mut self, event: &WatchdogEvent) -> Result<()> {
+        if !timeline::is_journaled(event) {
+            return Ok(());
+        }
+        if let WatchdogEvent::CheckCompleted { stats } = event {
+            if self.last_stats.as_ref() == Some(stats) {
+                return Ok(());
+            }
+            self.last_stats = Some(stats.clone());
+        }
+        let line = JournalLine::Event(EventRecord {
+            at: Utc::now(),
+            event: event.clone(),
+        });
+        let mut line = serde_json::to_string(&line)?;
+        line.push('\n');
+        // One write per line, so appends from other processes don't interleave
+        self.file.write_all(line.as_bytes())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events_path() {
+        assert_eq!(
+            events_path(Path::new(".claude/process_registry.json")),
+            Path::new(".claude/process_events.jsonl")
+        );
+        assert_eq!(
+            events_path(Path::new("/tmp/team.json")),
+            Path::new("/tmp/team_events.jsonl")
+        );
+    }
+
+    #[test]
+    fn test_daemon_events_join_the_transitions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("process_events.jsonl");
+        let mut registry = ProcessRegistry::new();
+        let before = registry.clone();
+        let task: TaskInfo = serde_json::from_value(serde_json::json!({
+            "mode": "native",
+            "command": "pytest",
+            "status": "running",
+            "started_at": "2026-10-16T14:00:00Z",
+        }))
+        .unwrap();
+        registry.tasks.insert("T1".to_string(), task);
+        append(&path, &diff(&before, &registry, Actor::Cli), 0o600).unwrap();
+
+        let check = WatchdogEvent::CheckCompleted {
+            stats: RegistryStats::default(),
+        };
+        let died = WatchdogEvent::TaskDied {
+            task_id: "T1".into(),
+            exit_code: Some(1),
+            triage_hint: None,
+            failure_excerpt: None,
+        };
+        let zombie = WatchdogEvent::ZombieDetected {
+            task_id: "T1".into(),
+        };
+        let mut log = EventLog::open(&path, 0o600).unwrap();
+        for event in [&check, &died, &check, &zombie] {
+            log.record(event).unwrap();
+        }
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"{\"at\":\"torn")
+            .unwrap();
+
+        // The death is the registry's transition; the repeated check is left out
+        let (lines, skipped) = read_all(&path).unwrap();
+        assert_eq!(skipped, 1);
+        let events: Vec<_> = lines
+            .iter()
+            .filter_map(|line| match line {
+                JournalLine::Event(record) => Some(&record.event),
+                JournalLine::Transition(_) => None,
+            })
+            .collect();
+        assert_eq!(events, [&check, &zombie]);
+        let (transitions, _) = read(&path, "T1").unwrap();
+        assert_eq!(transitions.len(), 1);
+        assert_eq!(transitions[0].change, Change::Registered);
+    }
+}
@@ -0,0 +1,187 @@
+//! Output of spawned tasks, kept in `logs/<task_id>.log` next to the
+//! registry (`logs` command).
+//!
+//! Spawned processes write straight into their log file (detached) or
+//! through [`tee`] (foreground, so the output still reaches the terminal).
+//! The file is opened for appending, which lets the daemon rotate it in
+//! place while the task keeps writing: the content is copied to `.log.1`
+//! and the file truncated.
+
+use crate::files;
+use anyhow::{Context, Result};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Directory (next to the registry) holding task logs
+pub const LOG_DIR: &str = "logs";
+
+/// Size at which a log is rotated
+pub const MAX_LOG_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Rotated generations kept (`.log.1` is the newest)
+pub const KEEP_ROTATED: usize = 3;
+
+/// Lines from the end of a log that failure triage looks at
+pub const TRIAGE_LINES: usize = 50;
+
+/// Bytes from the end of a log searched for a failure excerpt
+pub const EXCERPT_BYTES: u64 = 64 * 1024;
+
+/// Log file for `task_id`
+pub fn log_path(registry_path: &Path, task_id: &str) -> PathBuf {
+    registry_path
+        .parent()
+        .unwrap_or(Path::new("."))
+        .join(LOG_DIR)
+        .join(format!("{task_id}.log"))
+}
+
+/// Open (creating it with `mode`, and its directory) a log for appending
+pub fn open(path: &Path, mode: u32) -> Result<File> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    files::append_options(mode)
+        .open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))
+}
+
+fn rotated(path: &Path, generation: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{generation}"));
+    PathBuf::from(name)
+}
+
+/// Rotate `path` if it grew past `max_bytes`, keeping `keep` old copies.
+/// Returns whether it was rotated.
+pub fn rotate_if_large(path: &Path, max_bytes: u64, keep: usize) -> io::Result<bool> {
+    match fs::metadata(path) {
+        Ok(meta) if meta.len() > max_bytes => {}
+        _ => return Ok(false),
+    }
+    for generation in (1..keep).rev() {
+        let from = rotated(path, generation);
+        if from.exists() {
+            fs::rename(&from, rotated(path, generation + 1))?;
+        }
+    }
+    fs::copy(path, rotated(path, 1))?;
+    // Writers append, so they carry on at the new end
+    OpenOptions::new().write(true).open(path)?.set_len(0)?;
+    Ok(true)
+}
+
+/// The last `lines` lines of `path`
+pub fn tail(path: &Path, lines: usize) -> io::Result<String> {
+    let content = fs::read(path)?;
+    Ok(last_lines(&String::from_utf8_lossy(&content), lines))
+}
+
+/// The last `lines` lines of `content`
+pub fn last_lines(content: &str, lines: usize) -> String {
+    if lines == 0 {
+        return String::new();
+    }
+    let start = content
+        .char_indices()
+        .rev()
+        .filter(|(_, c)| *c == '\n')
+        // The final newline ends the last line rather than starting one
+        .skip(usize::from(content.ends_with('\n')))
+        .nth(lines - 1)
+        .map_or(0, |(i, _)| i + 1);
+    content[start..].to_string()
+}
+
+/// The last `bytes` bytes of `path`, from the first line that starts in
+/// them
+pub fn tail_bytes(path: &Path, bytes: u64) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let start = len.saturating_sub(bytes);
+    file.seek(SeekFrom::Start(start))?;
+    let mut content = Vec::new();
+    file.read_to_end(&mut content)?;
+    let content = String::from_utf8_lossy(&content);
+    Ok(match content.find('\n') {
+        // Mid-line: drop the partial line
+        Some(i) if start > 0 => content[i + 1..].to_string(),
+        _ => content.into_owned(),
+    })
+}
+
+/// The block at the end of `path` that shows why the task failed (see
+/// [`crate::excerpt`])
+pub fn failure_excerpt(path: &Path) -> Option<String> {
+    crate::excerpt::extract(&tail_bytes(path, EXCERPT_BYTES).ok()?)
+}
+
+/// Bytes appended to `path` since `offset`, and the new offset. A file
+/// shorter than `offset` was rotated and is read from the start.
+pub fn read_from(path: &Path, offset: u64) -> io::Result<(Vec<u8>, u64)> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let offset = if len < offset { 0 } else { offset };
+    file.seek(SeekFrom::Start(offset))?;
+    let mut new = Vec::new();
+    file.read_to_end(&mut new)?;
+    let end = offset + new.len() as u64;
+    Ok((new, end))
+}
+
+/// Copy `from` to both `to` and `log` until `from` closes
+pub async fn tee<R, W>(mut from: R, mut to: W, log: File) -> io::Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut log = tokio::fs::File::from_std(log);
+    let mut buf = [0u8; 8192];
+    loop {
+        let n = from.read(&mut buf).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        to.write_all(&buf[..n]).await?;
+        to.flush().await?;
+        log.write_all(&buf[..n]).await?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_rotate_and_tail() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = log_path(&dir.path().join("registry.json"), "T1");
+        let mut log = open(&path, 0o600).unwrap();
+        writeln!(log, "one\ntwo\nthree").unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(tail(&path, 2).unwrap(), "two\nthree\n");
+        assert_eq!(tail(&path, 10).unwrap(), "one\ntwo\nthree\n");
+        assert_eq!(tail_bytes(&path, 8).unwrap(), "three\n");
+        assert_eq!(tail_bytes(&path, 100).unwrap(), "one\ntwo\nthree\n");
+
+        assert!(!rotate_if_large(&path, 100, 2).unwrap());
+        for generation in ["first", "second", "third"] {
+            assert!(rotate_if_large(&path, 0, 2).unwrap());
+            // The open handle keeps appending to the truncated file
+            write!(log, "{generation}").unwrap();
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), "third");
+        assert_eq!(fs::read_to_string(rotated(&path, 1)).unwrap(), "second");
+        assert_eq!(fs::read_to_string(rotated(&path, 2)).unwrap(), "first");
+        assert!(!rotated(&path, 3).exists());
+
+        // A reader past the end after rotation starts over
+        let (new, offset) = read_from(&path, 100).unwrap();
+        assert_eq!((new.as_slice(), offset), (&b"third"[..], 5));
+    }
+}
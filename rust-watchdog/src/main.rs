@@ -1,23 +1,172 @@
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
 use chrono::Local;
-use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, Parser, Subcommand};
+use comfy_table::Cell;
+use rustyline::error::ReadlineError;
+use rustyline::history::DefaultHistory;
+use rustyline::Editor;
+use std::collections::{BTreeMap, HashSet};
+use std::ffi::OsString;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+#[cfg(unix)]
+use tokio::signal::unix::{signal, SignalKind};
 use tokio::time::{sleep, Duration};
 
 // Consume the library crate instead of re-declaring `mod docker; …`. Declaring
 // the modules here too compiled every module twice (once in the lib, once
 // inlined in the bin) and made the lib-only API surface look like dead code in
 // the bin build. One compilation, one source of truth.
+use task_watchdog::alerts;
+use task_watchdog::anomaly;
+#[cfg(feature = "http")]
+use task_watchdog::api;
+use task_watchdog::build_info::BuildInfo;
+use task_watchdog::bundle::{self, ContextBundle};
+use task_watchdog::capacity::{self, Resources};
+use task_watchdog::capture;
+#[cfg(unix)]
+use task_watchdog::chaos;
+use task_watchdog::clock;
+use task_watchdog::config::Config;
+use task_watchdog::coredump;
+use task_watchdog::crash;
+use task_watchdog::daemon::{DaemonLock, ProcessLimits, Watchdog};
+use task_watchdog::discover;
 use task_watchdog::docker::DockerManager;
+use task_watchdog::docker_recorder;
+use task_watchdog::done_files;
+use task_watchdog::events::WatchdogEvent;
+use task_watchdog::exits;
+use task_watchdog::filter::Filter;
+use task_watchdog::health::{self, Health, HealthStatus};
+use task_watchdog::init::{self, Action, InitOptions};
+use task_watchdog::journal;
+use task_watchdog::launch::{self, LaunchFile, LaunchTask, SET_LABEL};
+use task_watchdog::lifecycle::{self, Change, EventLog, EventRecord, JournalLine};
+use task_watchdog::logs;
+use task_watchdog::metrics;
+use task_watchdog::monitor::SystemMonitor;
+use task_watchdog::notify::{self, Notifier};
+use task_watchdog::output::{
+    enabled, format_bytes, format_duration, format_relative, format_time, format_when, json_output,
+    new_table, number_cell, print_json, set_display_zone, set_output_format, set_verbosity,
+    sparkline, status_cell, table_width, truncate, DisplayZone, OutputFormat, Verbosity,
+};
+use task_watchdog::policy;
+use task_watchdog::ports;
 use task_watchdog::process::ProcessManager;
-use task_watchdog::registry::RegistryManager;
+#[cfg(unix)]
+use task_watchdog::process::{self, TreeKill};
+use task_watchdog::queue::{self, Fairness};
+use task_watchdog::readiness;
+use task_watchdog::registry::{RegistryManager, RegistryStats};
+use task_watchdog::report::{Rehydration, Report, TaskQuery};
+use task_watchdog::samples::{self, ResourceHistory};
+use task_watchdog::self_memory::MemoryTracker;
+use task_watchdog::shell::{self, ShellHelper};
+use task_watchdog::storage::{self, BackendKind};
+use task_watchdog::swarm;
+use task_watchdog::tasks::{
+    self, check_rlimits, new_task, parse_max_cpu, parse_max_memory, parse_timeout,
+    reservation_from, Details, TaskSpec,
+};
+use task_watchdog::template::Vars;
+use task_watchdog::thermal::ThermalStatus;
+use task_watchdog::timeline::{self, Timeline};
+use task_watchdog::tmux;
+use task_watchdog::triage;
 use task_watchdog::types::*;
+use task_watchdog::typescript;
+use task_watchdog::units;
+#[cfg(feature = "self-update")]
+use task_watchdog::update::{self, UpdateOutcome};
+use task_watchdog::whoami;
+use task_watchdog::{debug, info, verbose, warn};
 
 #[derive(Parser)]
 #[command(name = "task-watchdog")]
 #[command(version = env!("CARGO_PKG_VERSION"))]
 #[command(about = "High-performance process monitoring daemon for AI coding tools", long_about = None)]
 struct Cli {
+    /// Only print warnings and errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Increase detail (-v: resource details, -vv: debug internals)
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Record every Docker API request/response (sanitized) to a JSONL file
+    #[arg(long, global = true, value_name = "FILE")]
+    record_docker_calls: Option<String>,
+
+    /// Tool invoking the watchdog, recorded on registered and killed tasks
+    /// (cli, api, python-sdk, mcp, ...)
+    #[arg(
+        long,
+        global = true,
+        env = "TASK_WATCHDOG_ORIGIN",
+        default_value = "cli",
+        value_name = "TOOL"
+    )]
+    origin: String,
+
+    /// Log `run` and `serve` to journald with TASK_ID fields (automatic
+    /// under systemd)
+    #[arg(long, global = true, env = "TASK_WATCHDOG_JOURNALD")]
+    journald: bool,
+
+    /// Where the registry is stored (default: SQLite if its database
+    /// exists next to the registry path, JSON otherwise). Switching to
+    /// SQLite imports the JSON registry.
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        env = "TASK_WATCHDOG_REGISTRY_BACKEND",
+        value_name = "BACKEND"
+    )]
+    registry_backend: Option<BackendKind>,
+
+    /// Configuration file whose settings stand in for flags left out
+    /// (default: .claude/watchdog.toml, if there is one)
+    #[arg(long, global = true, env = "TASK_WATCHDOG_CONFIG", value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// Template variable as key=value, for `{{key}}` in commands and launch
+    /// files; overrides the built-in ones (branch, task_id, repo_root,
+    /// today) (repeatable)
+    #[arg(long = "var", global = true, value_parser = parse_label, value_name = "KEY=VALUE")]
+    vars: Vec<(String, String)>,
+
+    /// Print a JSON document instead of text (check, stats, report,
+    /// list, rehydrate, cleanup, capacity, register, up, down, kill,
+    /// inspect, context-bundle, version)
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    output: OutputFormat,
+
+    /// Time zone for times in text output: local, utc or an IANA name like
+    /// Europe/Berlin (JSON output is always RFC 3339 in UTC)
+    #[arg(
+        long,
+        global = true,
+        env = "TASK_WATCHDOG_TIME_ZONE",
+        value_name = "ZONE"
+    )]
+    time_zone: Option<DisplayZone>,
+
+    /// Show times in UTC (overrides --time-zone)
+    #[arg(long, global = true)]
+    utc: bool,
+
+    /// Run gdb on the core a crashed native task left, and add the top of
+    /// its backtrace to the triage hint (run, spawn)
+    #[arg(long, global = true, env = "TASK_WATCHDOG_CORE_BACKTRACE")]
+    core_backtrace: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -30,137 +179,972 @@ enum Commands {
         #[arg(long, default_value = "300")]
         interval: u64,
 
+        /// Warn when the watchdog's own RSS exceeds this many MB
+        #[arg(long)]
+        memory_budget: Option<u64>,
+
+        /// Re-exec the watchdog when its memory budget or leak alarm trips
+        #[arg(long)]
+        restart_on_leak: bool,
+
+        /// Label whose values share queued capacity fairly
+        #[arg(long, value_name = "LABEL", default_value = queue::DEFAULT_FAIR_LABEL)]
+        fair_by: String,
+
+        /// Relative share of queued capacity for a label value, as value=N
+        /// (repeatable; unlisted values get 1)
+        #[arg(long, value_parser = parse_weight)]
+        weight: Vec<(String, u32)>,
+
+        /// Percent of the machine kept free when starting queued tasks
+        #[arg(long, default_value_t = capacity::DEFAULT_HEADROOM_PERCENT)]
+        headroom: u8,
+
+        /// Replace a watchdog already running for this registry (e.g. after
+        /// upgrading the binary) once it finishes its current check
+        #[arg(long)]
+        takeover: bool,
+
+        /// Also serve the HTTP API on this Unix socket (see `serve`)
+        #[arg(long, value_name = "PATH")]
+        http_socket: Option<PathBuf>,
+
+        /// Also serve the gRPC API on this Unix socket (builds with
+        /// `--features grpc`)
+        #[arg(long, value_name = "PATH")]
+        grpc_socket: Option<PathBuf>,
+
+        /// Kill tasks that go over their --max-memory, --max-cpu or process
+        /// limits instead of only warning
+        #[arg(long)]
+        enforce_limits: bool,
+
+        /// Processes a task's group may hold unless it sets its own
+        /// --max-processes
+        #[arg(long, value_name = "COUNT")]
+        max_processes_per_task: Option<u64>,
+
+        /// New processes a task may start per minute
+        #[arg(long, value_name = "COUNT")]
+        max_process_rate: Option<u64>,
+
+        /// Open files a task's processes may hold unless it sets its own
+        /// --max-open-files
+        #[arg(long, value_name = "COUNT")]
+        max_open_files_per_task: Option<u64>,
+
+        /// Threads a task's processes may run unless it sets its own
+        /// --max-threads
+        #[arg(long, value_name = "COUNT")]
+        max_threads_per_task: Option<u64>,
+
+        /// Alert again when a condition (a task running unusually long, in
+        /// unknown state, crashing on every restart) lasts this long;
+        /// comma-separated for several tiers, e.g. 1h,4h
+        #[arg(
+            long,
+            value_delimiter = ',',
+            value_name = "DURATIONS",
+            value_parser = parse_timeout,
+            default_value = "1h"
+        )]
+        escalate_after: Vec<u64>,
+
+        /// Registry file path
+        #[arg(long, default_value_t = init::default_registry_path())]
+        registry: String,
+    },
+
+    /// Serve the registry as a JSON API on a Unix socket: list, inspect,
+    /// register and kill tasks, read their logs and the report
+    Serve {
+        /// Socket to listen on (next to the registry by default)
+        #[arg(long, value_name = "PATH")]
+        socket: Option<PathBuf>,
+
+        /// Registry file path
+        #[arg(long, default_value_t = init::default_registry_path())]
+        registry: String,
+    },
+
+    /// Find the task a process or container belongs to (through its
+    /// parents, task ID tag, process group or container), and show it
+    Whoami {
+        /// Process to look up
+        #[arg(
+            long,
+            required_unless_present = "container",
+            conflicts_with = "container"
+        )]
+        pid: Option<i32>,
+
+        /// Container ID (or prefix) or name to look up
+        #[arg(long)]
+        container: Option<String>,
+
         /// Registry file path
-        #[arg(long, default_value = ".claude/process_registry.json")]
+        #[arg(long, default_value_t = init::default_registry_path())]
         registry: String,
     },
 
     /// Check status of a specific task
     Check {
-        /// Task ID to check
+        /// Task ID (prefix, fuzzy match or @selector: @last, @newest-running, @last-<status>)
         task_id: String,
 
+        /// Also show the last N lines of its output, from its log or, for a
+        /// task the daemon started without one, from the daemon's memory
+        #[arg(long, value_name = "N")]
+        tail: Option<usize>,
+
+        /// Registry file path
+        #[arg(long, default_value_t = init::default_registry_path())]
+        registry: String,
+    },
+
+    /// Block until a task finishes, reporting the tasks it waits for
+    /// (`--after`) as they finish. Exits 0 if it completed, with its exit
+    /// code (or 1) if it failed, 124 if it timed out, 137 if it was killed
+    /// and 75 if --timeout passed first
+    Wait {
+        /// Task ID to wait for (prefix, fuzzy match or @last/@newest-running)
+        #[arg(required_unless_present = "any", conflicts_with = "any")]
+        task_id: Option<String>,
+
+        /// Wait for whichever of these tasks finishes first, and exit as it did
+        #[arg(long, num_args = 1.., value_name = "TASK_ID")]
+        any: Vec<String>,
+
+        /// Give up after this long, e.g. 90s, 30m or 2h
+        #[arg(long, value_name = "DURATION", value_parser = parse_timeout)]
+        timeout: Option<u64>,
+
         /// Registry file path
-        #[arg(long, default_value = ".claude/process_registry.json")]
+        #[arg(long, default_value_t = init::default_registry_path())]
         registry: String,
     },
 
     /// Kill a running task
     Kill {
-        /// Task ID to kill
-        task_id: String,
+        /// Task ID (prefix, fuzzy match or @selector: @last, @newest-running, @last-<status>)
+        #[arg(required_unless_present_any = ["filter", "group"])]
+        task_id: Option<String>,
+
+        /// Kill every unfinished task matching a filter, e.g. 'label.swarm=7 and started<1h ago'
+        #[arg(long = "where", value_name = "EXPR", conflicts_with = "task_id")]
+        filter: Option<Filter>,
+
+        /// Kill every unfinished task in this group (and matching --where)
+        #[arg(long, value_name = "NAME", conflicts_with = "task_id")]
+        group: Option<String>,
+
+        /// Kill even if the task belongs to another user or checkout
+        #[arg(long)]
+        force: bool,
+
+        /// Registry file path
+        #[arg(long, default_value_t = init::default_registry_path())]
+        registry: String,
+    },
+
+    /// Kill a process and everything it started, whether or not it is a
+    /// task: SIGTERM (or --signal) to the tree and its process group, then
+    /// SIGKILL to what outlives --grace
+    #[cfg(unix)]
+    KillTree {
+        /// Process to kill, with its descendants
+        #[arg(long)]
+        pid: i32,
+
+        /// Signal to send first, e.g. TERM, INT, HUP or 15
+        #[arg(long, default_value = "TERM", value_parser = process::parse_signal)]
+        signal: nix::sys::signal::Signal,
+
+        /// How long to wait before sending SIGKILL, e.g. 0.5s, 5s
+        #[arg(long, default_value = "5s", value_parser = parse_grace)]
+        grace: Duration,
+
+        /// Kill even if the process belongs to another user, or is a task of
+        /// another user or checkout
+        #[arg(long)]
+        force: bool,
 
         /// Registry file path
-        #[arg(long, default_value = ".claude/process_registry.json")]
+        #[arg(long, default_value_t = init::default_registry_path())]
         registry: String,
     },
 
     /// Rehydrate context after compression (show what's running)
     Rehydrate {
         /// Registry file path
-        #[arg(long, default_value = ".claude/process_registry.json")]
+        #[arg(long, default_value_t = init::default_registry_path())]
+        registry: String,
+    },
+
+    /// Gather everything about one task into a document for an LLM
+    ContextBundle {
+        /// Task ID (prefix, fuzzy match or @selector: @last, @newest-running, @last-<status>)
+        #[arg(long)]
+        task: String,
+
+        /// Token budget; earlier runs and related tasks are trimmed to fit
+        #[arg(long, default_value_t = bundle::DEFAULT_MAX_TOKENS)]
+        max_tokens: usize,
+
+        /// Emit JSON instead of Markdown
+        #[arg(long)]
+        json: bool,
+
+        /// Registry file path
+        #[arg(long, default_value_t = init::default_registry_path())]
         registry: String,
     },
 
     /// Show resource usage report
     Report {
+        /// Show tasks matching a filter (finished ones too) instead of active tasks
+        #[arg(long = "where", value_name = "EXPR")]
+        filter: Option<Filter>,
+
+        /// Registry file path
+        #[arg(long, default_value_t = init::default_registry_path())]
+        registry: String,
+    },
+
+    /// List tasks, finished ones too, newest first
+    List {
+        /// Only tasks with this status (repeatable or comma-separated:
+        /// running, failed, completed, killed, ...)
+        #[arg(long, value_delimiter = ',')]
+        status: Vec<TaskStatus>,
+
+        /// Only tasks started within this long (`30m`, `2h`, `7d`)
+        #[arg(long, value_name = "DURATION", value_parser = parse_age)]
+        since: Option<u64>,
+
+        /// Order by start time (newest first) or CPU time (most first)
+        #[arg(long, default_value = "started", value_parser = ["started", "cpu"])]
+        sort: String,
+
+        /// Show at most this many tasks
+        #[arg(long, value_name = "N")]
+        limit: Option<usize>,
+
+        /// Only tasks also matching a filter, e.g. 'label.team=infra'
+        #[arg(long = "where", value_name = "EXPR")]
+        filter: Option<Filter>,
+
+        /// Only tasks in this group
+        #[arg(long, value_name = "NAME")]
+        group: Option<String>,
+
+        /// Registry file path
+        #[arg(long, default_value_t = init::default_registry_path())]
+        registry: String,
+    },
+
+    /// Show every status change of a task from the lifecycle journal:
+    /// when, by whom (watchdog, CLI or API) and why
+    History {
+        /// Task ID (prefix, fuzzy match or @selector: @last, @newest-running, @last-<status>)
+        task_id: String,
+
+        /// Registry file path
+        #[arg(long, default_value_t = init::default_registry_path())]
+        registry: String,
+    },
+
+    /// Replay the lifecycle journal: what happened to which task during
+    /// a window, with the registry's state as of its end
+    Replay {
+        /// Journal to read (default: the registry's, see `history`)
+        #[arg(long, value_name = "FILE")]
+        from: Option<PathBuf>,
+
+        /// Play the events back at this multiple of real time, e.g. 10x
+        /// (pauses are cut to 5s); without it everything prints at once
+        #[arg(long, value_parser = parse_speed)]
+        speed: Option<f64>,
+
+        /// Start of the window: an age (`2h`), a local time (`14:05`,
+        /// `2026-10-16 14:05`) or an RFC 3339 timestamp
+        #[arg(long, value_name = "WHEN", value_parser = parse_when)]
+        since: Option<chrono::DateTime<chrono::Utc>>,
+
+        /// End of the window, as for --since
+        #[arg(long, value_name = "WHEN", value_parser = parse_when)]
+        until: Option<chrono::DateTime<chrono::Utc>>,
+
+        /// Registry file path
+        #[arg(long, default_value_t = init::default_registry_path())]
+        registry: String,
+    },
+
+    /// Soak-test supervision: start dummy workloads, disrupt some and
+    /// check that every outcome is detected and recorded
+    #[command(hide = true)]
+    Chaos {
+        /// Dummy workloads to start
+        #[arg(long, default_value_t = 10)]
+        spawn: usize,
+
+        /// Randomly kill, hang (SIGSTOP) or keep running after they
+        /// report done some of the workloads
+        #[arg(long)]
+        kill_random: bool,
+
+        /// How long the run lasts (`30s`, `5m`)
+        #[arg(long, default_value = "1m", value_parser = parse_age)]
+        duration: u64,
+
+        /// Seed of the random plan, to repeat a run
+        #[arg(long)]
+        seed: Option<u64>,
+
         /// Registry file path
-        #[arg(long, default_value = ".claude/process_registry.json")]
+        #[arg(long, default_value_t = init::default_registry_path())]
         registry: String,
     },
 
     /// Show registry statistics
     Stats {
+        /// Break counts, spend and CPU time down by the value of this label
+        #[arg(long, value_name = "LABEL")]
+        group_by: Option<String>,
+
+        /// Break counts, spend and CPU time down by day
+        #[arg(long)]
+        per_day: bool,
+
+        /// Only count tasks in this group
+        #[arg(long, value_name = "NAME")]
+        group: Option<String>,
+
+        /// Registry file path
+        #[arg(long, default_value_t = init::default_registry_path())]
+        registry: String,
+    },
+
+    /// Add AI API usage (tokens, spend) reported by a task
+    Cost {
+        /// Task ID (prefix, fuzzy match or @selector: @last, @newest-running, @last-<status>)
+        task_id: String,
+
+        /// Input/prompt tokens to add
+        #[arg(long, default_value_t = 0)]
+        input_tokens: u64,
+
+        /// Output/completion tokens to add
+        #[arg(long, default_value_t = 0)]
+        output_tokens: u64,
+
+        /// Spend in US dollars to add
+        #[arg(long, default_value_t = 0.0)]
+        usd: f64,
+
+        /// Registry file path
+        #[arg(long, default_value_t = init::default_registry_path())]
+        registry: String,
+    },
+
+    /// Show reserved vs. used memory/CPU against machine capacity
+    Capacity {
+        /// Percent of the machine kept free of reservations
+        #[arg(long, default_value_t = capacity::DEFAULT_HEADROOM_PERCENT)]
+        headroom: u8,
+
+        /// Registry file path
+        #[arg(long, default_value_t = init::default_registry_path())]
+        registry: String,
+    },
+
+    /// Write Prometheus metrics once (for cron and node_exporter's textfile collector)
+    ExportMetrics {
+        /// File to write atomically, e.g. /var/lib/node_exporter/textfile/watchdog.prom
+        /// (prints to stdout when omitted)
+        #[arg(long, value_name = "FILE")]
+        textfile: Option<PathBuf>,
+
+        /// Only export tasks matching a filter, e.g. 'label.team=infra'
+        #[arg(long = "where", value_name = "EXPR")]
+        filter: Option<Filter>,
+
         /// Registry file path
-        #[arg(long, default_value = ".claude/process_registry.json")]
+        #[arg(long, default_value_t = init::default_registry_path())]
         registry: String,
     },
 
-    /// Cleanup old completed tasks
+    /// Cleanup old completed tasks (and remove their containers)
     Cleanup {
         /// Days to keep (older tasks will be removed)
         #[arg(long, default_value = "7")]
         days: u64,
 
+        /// Only remove finished tasks that also match a filter, e.g. 'status=completed'
+        #[arg(long = "where", value_name = "EXPR")]
+        filter: Option<Filter>,
+
+        /// Only remove finished tasks in this group
+        #[arg(long, value_name = "NAME")]
+        group: Option<String>,
+
+        /// Registry file path
+        #[arg(long, default_value_t = init::default_registry_path())]
+        registry: String,
+    },
+
+    /// Remove the stopped containers of finished tasks, keeping their registry entries
+    PruneContainers {
+        /// Only finished tasks matching a filter, e.g. 'status=completed'
+        #[arg(long = "where", value_name = "EXPR")]
+        filter: Option<Filter>,
+
         /// Registry file path
-        #[arg(long, default_value = ".claude/process_registry.json")]
+        #[arg(long, default_value_t = init::default_registry_path())]
         registry: String,
     },
 
     /// Register a new task with constitution rules
     Register {
+        /// Task ID
+        #[arg(required_unless_present_any = ["from_file", "stdin"])]
+        task_id: Option<String>,
+
+        /// Command to execute (split into argv and run without a shell)
+        #[arg(short, long, required_unless_present_any = ["from_file", "stdin"])]
+        command: Option<String>,
+
+        /// Register every task in a JSON array in one locked write; flags
+        /// other than the task ID and command apply to all of them
+        #[arg(long, conflicts_with_all = ["task_id", "command", "done_file"])]
+        from_file: Option<PathBuf>,
+
+        /// Read a JSON task (or array of tasks), as in --from-file, from
+        /// stdin instead of a file
+        #[arg(long, conflicts_with_all = ["task_id", "command", "done_file", "from_file"])]
+        stdin: bool,
+
+        /// Run the command with `sh -c` instead of direct execution
+        #[arg(long)]
+        shell: bool,
+
+        /// Mark the task finished when this file appears, e.g. from a script
+        /// that runs `touch`; an exit code written into it marks it failed.
+        /// Without a value: done/<TASK_ID> next to the registry
+        #[arg(long, value_name = "PATH", num_args = 0..=1, default_missing_value = "")]
+        done_file: Option<String>,
+
+        /// What the task is for (shown first when rehydrating)
+        #[arg(long)]
+        description: Option<String>,
+
+        /// What should be true once the task has succeeded
+        #[arg(long)]
+        expected_outcome: Option<String>,
+
+        /// Label as key=value, for grouping in `stats --group-by` (repeatable)
+        #[arg(long, value_parser = parse_label)]
+        label: Vec<(String, String)>,
+
+        /// Group of related tasks (e.g. one agent swarm) to manage together
+        /// with `--group` on list, kill, cleanup and stats
+        #[arg(long, value_name = "NAME")]
+        group: Option<String>,
+
+        /// Memory to reserve for the task (e.g. 512m, 2g)
+        #[arg(long)]
+        memory: Option<String>,
+
+        /// CPUs to reserve for the task (e.g. 1.5)
+        #[arg(long)]
+        cpu: Option<String>,
+
+        /// Percent of the machine kept free of reservations
+        #[arg(long, default_value_t = capacity::DEFAULT_HEADROOM_PERCENT)]
+        headroom: u8,
+
+        /// Register as queued instead of refusing when the reservation doesn't
+        /// fit or the tasks it comes after aren't ready
+        #[arg(long)]
+        queue: bool,
+
+        /// Terminate the task once it has run this long (e.g. 90s, 30m, 2h)
+        #[arg(long, value_name = "DURATION", value_parser = parse_timeout)]
+        timeout: Option<u64>,
+
+        /// Warn when its processes use more memory than this (e.g. 512m,
+        /// 2g); kill it under `run --enforce-limits`
+        #[arg(long, value_name = "SIZE", value_parser = parse_max_memory)]
+        max_memory: Option<u64>,
+
+        /// Warn when it uses more CPU than this between two checks, in
+        /// percent of one core (e.g. 150); kill it under `run --enforce-limits`
+        #[arg(long, value_name = "PERCENT", value_parser = parse_max_cpu)]
+        max_cpu: Option<f64>,
+
+        /// Warn when its process group holds more processes than this;
+        /// kill it under `run --enforce-limits`
+        #[arg(long, value_name = "COUNT")]
+        max_processes: Option<u64>,
+
+        /// Warn when its processes hold more open files than this; also
+        /// their kernel limit (RLIMIT_NOFILE) when the watchdog starts them
+        #[arg(long, value_name = "COUNT")]
+        max_open_files: Option<u64>,
+
+        /// Warn when its processes run more threads than this; kill it
+        /// under `run --enforce-limits`
+        #[arg(long, value_name = "COUNT")]
+        max_threads: Option<u64>,
+
+        /// Kernel resource limit the watchdog starts it under, e.g.
+        /// nofile=4096, nproc=256 or fsize=1g (repeatable; names: nofile,
+        /// nproc, fsize, as, core, cpu, stack)
+        #[arg(long, value_name = "NAME=VALUE", value_parser = Rlimit::parse_setting)]
+        rlimit: Vec<(Rlimit, u64)>,
+
+        /// Tasks that must be ready first (comma-separated)
+        #[arg(long, value_delimiter = ',', value_name = "TASK_IDS")]
+        after: Vec<String>,
+
+        /// How tasks that come after this one tell it's ready: port:N,
+        /// http(s)://URL or cmd:COMMAND (otherwise: once it completes)
+        #[arg(
+            long,
+            value_name = "PROBE",
+            value_parser = readiness::parse_probe,
+            conflicts_with_all = ["from_file", "stdin"]
+        )]
+        ready: Option<ReadinessProbe>,
+
+        /// Constitution rules (comma-separated)
+        #[arg(long)]
+        rules: Option<String>,
+
+        /// Arbitrary JSON stored with the task and exported unchanged,
+        /// e.g. '{"job": "ci-4411"}'
+        #[arg(long, value_name = "JSON", value_parser = parse_metadata)]
+        metadata_json: Option<serde_json::Value>,
+
+        /// Registry file path
+        #[arg(short, long, default_value_t = init::default_registry_path())]
+        registry: String,
+    },
+
+    /// Start a command in its own process group and register it with its
+    /// PID, waiting for it to exit unless detached
+    Spawn {
         /// Task ID
         task_id: String,
 
-        /// Command to execute
+        /// Command to execute (split into argv and run without a shell)
         #[arg(short, long)]
         command: String,
 
+        /// Run the command with `sh -c` instead of direct execution
+        #[arg(long)]
+        shell: bool,
+
+        /// Return once started and leave the task to the daemon
+        #[arg(short, long)]
+        detach: bool,
+
+        /// Mark the task finished when this file appears, e.g. from a script
+        /// that runs `touch`; an exit code written into it marks it failed.
+        /// Without a value: done/<TASK_ID> next to the registry
+        #[arg(long, value_name = "PATH", num_args = 0..=1, default_missing_value = "")]
+        done_file: Option<String>,
+
+        /// What the task is for (shown first when rehydrating)
+        #[arg(long)]
+        description: Option<String>,
+
+        /// What should be true once the task has succeeded
+        #[arg(long)]
+        expected_outcome: Option<String>,
+
+        /// Label as key=value, for grouping in `stats --group-by` (repeatable)
+        #[arg(long, value_parser = parse_label)]
+        label: Vec<(String, String)>,
+
+        /// Group of related tasks (e.g. one agent swarm) to manage together
+        /// with `--group` on list, kill, cleanup and stats
+        #[arg(long, value_name = "NAME")]
+        group: Option<String>,
+
+        /// Terminate the task once it has run this long (e.g. 90s, 30m, 2h)
+        #[arg(long, value_name = "DURATION", value_parser = parse_timeout)]
+        timeout: Option<u64>,
+
+        /// Warn when its processes use more memory than this (e.g. 512m,
+        /// 2g); kill it under `run --enforce-limits`
+        #[arg(long, value_name = "SIZE", value_parser = parse_max_memory)]
+        max_memory: Option<u64>,
+
+        /// Warn when it uses more CPU than this between two checks, in
+        /// percent of one core (e.g. 150); kill it under `run --enforce-limits`
+        #[arg(long, value_name = "PERCENT", value_parser = parse_max_cpu)]
+        max_cpu: Option<f64>,
+
+        /// Warn when its process group holds more processes than this;
+        /// kill it under `run --enforce-limits`
+        #[arg(long, value_name = "COUNT")]
+        max_processes: Option<u64>,
+
+        /// Warn when its processes hold more open files than this; also
+        /// their kernel limit (RLIMIT_NOFILE) when the watchdog starts them
+        #[arg(long, value_name = "COUNT")]
+        max_open_files: Option<u64>,
+
+        /// Warn when its processes run more threads than this; kill it
+        /// under `run --enforce-limits`
+        #[arg(long, value_name = "COUNT")]
+        max_threads: Option<u64>,
+
+        /// Kernel resource limit the watchdog starts it under, e.g.
+        /// nofile=4096, nproc=256 or fsize=1g (repeatable; names: nofile,
+        /// nproc, fsize, as, core, cpu, stack)
+        #[arg(long, value_name = "NAME=VALUE", value_parser = Rlimit::parse_setting)]
+        rlimit: Vec<(Rlimit, u64)>,
+
+        /// Wait until these tasks are ready before starting (comma-separated)
+        #[arg(long, value_delimiter = ',', value_name = "TASK_IDS")]
+        after: Vec<String>,
+
+        /// How tasks that come after this one tell it's ready: port:N,
+        /// http(s)://URL or cmd:COMMAND (otherwise: once it completes)
+        #[arg(long, value_name = "PROBE", value_parser = readiness::parse_probe)]
+        ready: Option<ReadinessProbe>,
+
         /// Constitution rules (comma-separated)
         #[arg(long)]
         rules: Option<String>,
 
+        /// Arbitrary JSON stored with the task and exported unchanged
+        #[arg(long, value_name = "JSON", value_parser = parse_metadata)]
+        metadata_json: Option<serde_json::Value>,
+
         /// Registry file path
-        #[arg(short, long, default_value = ".claude/process_registry.json")]
+        #[arg(short, long, default_value_t = init::default_registry_path())]
         registry: String,
     },
-}
 
-/// Validate registry path to prevent path traversal attacks
-///
-/// Security checks:
-/// 1. Path must not contain parent directory references (..)
-/// 2. Canonicalized path must be within current working directory
-/// 3. Path must not point to sensitive system directories
-///
-/// Returns validated canonical path or error
-fn validate_registry_path(path: &str) -> Result<PathBuf> {
-    let path_buf = PathBuf::from(path);
+    /// Start the tasks of a launch file in dependency order. Tasks already
+    /// running as declared are left alone, changed ones are replaced and
+    /// ones dropped from the file are stopped
+    Up {
+        /// Launch file (TOML, or JSON with a .json extension)
+        file: PathBuf,
 
-    // SECURITY: Prevent path traversal with .. components
-    if path.contains("..") {
-        bail!("Registry path cannot contain parent directory references (..)");
-    }
+        /// Image of docker tasks that don't name one (default:
+        /// python:3.11-slim)
+        #[arg(long)]
+        image: Option<String>,
 
-    // Convert to absolute path (handles relative paths safely)
-    let absolute_path = if path_buf.is_absolute() {
-        path_buf.clone()
-    } else {
-        std::env::current_dir()?.join(&path_buf)
-    };
+        /// Memory to reserve for tasks that don't declare any (e.g. 512m)
+        #[arg(long)]
+        memory: Option<String>,
 
-    // Canonicalize to resolve symlinks and normalize path
-    let canonical = match absolute_path.canonicalize() {
-        Ok(p) => p,
-        Err(_) => {
-            // If file doesn't exist yet, validate parent directory
-            let parent = absolute_path
-                .parent()
-                .ok_or_else(|| anyhow::anyhow!("Invalid registry path: no parent directory"))?;
+        /// CPUs to reserve for tasks that don't declare any (e.g. 1.5)
+        #[arg(long)]
+        cpu: Option<String>,
 
-            if !parent.exists() {
-                bail!(
-                    "Registry path parent directory does not exist: {}",
-                    parent.display()
-                );
-            }
+        /// Remove the containers of docker tasks once they have stopped
+        /// (after saving their logs and exit code)
+        #[arg(long)]
+        auto_remove: bool,
 
-            // Return the non-canonical path for new files (will be created)
-            absolute_path
-        }
-    };
+        /// Keep the containers of failed runs for `inspect`, removing the
+        /// others
+        #[arg(long)]
+        keep_on_failure: bool,
 
-    // SECURITY: Prevent access to sensitive system directories
-    let canonical_str = canonical.to_string_lossy();
-    let forbidden_prefixes = ["/etc", "/root", "/sys", "/proc", "/boot", "/dev"];
+        /// Registry file path
+        #[arg(short, long, default_value_t = init::default_registry_path())]
+        registry: String,
+    },
 
-    for prefix in &forbidden_prefixes {
-        if canonical_str.starts_with(prefix) {
-            bail!("Registry path cannot be in system directory: {}", prefix);
-        }
-    }
+    /// Stop the unfinished tasks started from a launch file, each before
+    /// the tasks it waits for
+    Down {
+        /// Launch file the tasks were started from
+        file: PathBuf,
 
-    // SECURITY: Ensure path is within current working directory (or .claude subdir)
+        /// Registry file path
+        #[arg(short, long, default_value_t = init::default_registry_path())]
+        registry: String,
+    },
+
+    /// Run a list of commands from a file, at most --jobs at a time. The
+    /// rest are queued and the daemon starts them as slots free up
+    Swarm {
+        /// Swarm file: a YAML (or JSON) list of commands, or of tasks as for
+        /// `register --from-file` with the task ID optional
+        #[arg(long, value_name = "FILE")]
+        from: PathBuf,
+
+        /// Most tasks of the swarm running at once
+        #[arg(short, long, default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..))]
+        jobs: u32,
+
+        /// Group the tasks go in (default: the file's name); tasks without
+        /// an ID are named <GROUP>-<N>
+        #[arg(long, value_name = "NAME")]
+        group: Option<String>,
+
+        /// Label as key=value for every task (repeatable)
+        #[arg(long, value_parser = parse_label)]
+        label: Vec<(String, String)>,
+
+        /// Percent of the machine kept free of reservations
+        #[arg(long, default_value_t = capacity::DEFAULT_HEADROOM_PERCENT)]
+        headroom: u8,
+
+        /// Registry file path
+        #[arg(short, long, default_value_t = init::default_registry_path())]
+        registry: String,
+    },
+
+    /// Register the process listening on a TCP port as a running task; the
+    /// daemon then reports when the port stops being served
+    Adopt {
+        /// Task ID (defaults to port-<PORT>)
+        task_id: Option<String>,
+
+        /// Port the process listens on
+        #[arg(long)]
+        port: u16,
+
+        /// What the task is for (shown first when rehydrating)
+        #[arg(long)]
+        description: Option<String>,
+
+        /// Registry file path
+        #[arg(long, default_value_t = init::default_registry_path())]
+        registry: String,
+    },
+
+    /// Find long-running processes started by AI tools (claude, aider,
+    /// cursor, ...) that were never registered, and optionally adopt them
+    Discover {
+        /// Only processes running at least this long (`90s`, `5m`, `1h`)
+        #[arg(long, default_value = "5m", value_parser = parse_age)]
+        min_age: u64,
+
+        /// Register every process found as a running task
+        #[arg(long)]
+        adopt: bool,
+
+        /// Registry file path
+        #[arg(long, default_value_t = init::default_registry_path())]
+        registry: String,
+    },
+
+    /// Watch a process that wasn't started as a task: the daemon reports
+    /// its resources and warns when it exits
+    WatchPid {
+        /// Process ID
+        pid: i32,
+
+        /// Name to report it under (defaults to the PID)
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Registry file path
+        #[arg(long, default_value_t = init::default_registry_path())]
+        registry: String,
+    },
+
+    /// Stop watching a process added with watch-pid
+    Unwatch {
+        /// Name it is watched under
+        name: String,
+
+        /// Registry file path
+        #[arg(long, default_value_t = init::default_registry_path())]
+        registry: String,
+    },
+
+    /// Switch tmux to the pane a task was started from
+    Goto {
+        /// Task ID (prefix, fuzzy match or @selector: @last, @newest-running, @last-<status>)
+        task_id: String,
+
+        /// Registry file path
+        #[arg(long, default_value_t = init::default_registry_path())]
+        registry: String,
+    },
+
+    /// Show the output of a spawned task or a container
+    Logs {
+        /// Task ID (prefix, fuzzy match or @selector: @last, @newest-running, @last-<status>)
+        task_id: String,
+
+        /// Keep printing new output until the task finishes
+        #[arg(short, long)]
+        follow: bool,
+
+        /// Only the last N lines
+        #[arg(short = 'n', long, value_name = "N")]
+        tail: Option<usize>,
+
+        /// Registry file path
+        #[arg(long, default_value_t = init::default_registry_path())]
+        registry: String,
+    },
+
+    /// Post-mortem of a task: how it ended, its container as Docker still
+    /// has it, and the end of its output
+    Inspect {
+        /// Task ID (prefix, fuzzy match or @selector: @last, @newest-running, @last-<status>)
+        task_id: String,
+
+        /// Lines of output to show
+        #[arg(short = 'n', long, value_name = "N", default_value_t = 20)]
+        tail: usize,
+
+        /// Registry file path
+        #[arg(long, default_value_t = init::default_registry_path())]
+        registry: String,
+    },
+
+    /// Scaffold .claude/, a starter config, a constitution and .gitignore entries
+    Init {
+        /// Also write Claude Code hooks that rehydrate on session start
+        #[arg(long)]
+        hooks: bool,
+
+        /// Overwrite files that already exist
+        #[arg(long)]
+        force: bool,
+
+        /// Keep ephemeral state (registry, lock, crash reports) in .claude/state/
+        /// so the rest of .claude/ can be committed
+        #[arg(long)]
+        commit_safe: bool,
+    },
+
+    /// Diagnose the installation (registry, Docker, recent daemon crashes)
+    Doctor {
+        /// Registry file path
+        #[arg(long, default_value_t = init::default_registry_path())]
+        registry: String,
+    },
+
+    /// Show version, commit, target triple and compiled-in features
+    Version {
+        /// Print as JSON (includes the registry schema version)
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Print TypeScript definitions for the registry and JSON output
+    Typescript,
+
+    /// Update this binary to the latest GitHub release
+    SelfUpdate {
+        /// Only report whether a newer release is available
+        #[arg(long)]
+        check_only: bool,
+
+        /// Install a release this build has no signing key to verify,
+        /// trusting its checksum alone
+        #[arg(long)]
+        insecure: bool,
+    },
+
+    /// Interactive prompt running watchdog commands in this process, with
+    /// tab completion of commands and task IDs
+    Shell {
+        /// Registry file path (the default for every command run)
+        #[arg(short, long, default_value_t = init::default_registry_path())]
+        registry: String,
+    },
+}
+
+impl Commands {
+    /// Whether the command can print JSON (`--output json`)
+    fn has_json_output(&self) -> bool {
+        matches!(
+            self,
+            Commands::Check { .. }
+                | Commands::Inspect { .. }
+                | Commands::Stats { .. }
+                | Commands::Report { .. }
+                | Commands::List { .. }
+                | Commands::Replay { .. }
+                | Commands::History { .. }
+                | Commands::Rehydrate { .. }
+                | Commands::Cleanup { .. }
+                | Commands::PruneContainers { .. }
+                | Commands::Capacity { .. }
+                | Commands::Register { .. }
+                | Commands::Up { .. }
+                | Commands::Down { .. }
+                | Commands::Swarm { .. }
+                | Commands::Kill { .. }
+                | Commands::Whoami { .. }
+                | Commands::ContextBundle { .. }
+                | Commands::Version { .. }
+        )
+    }
+}
+
+/// Validate registry path to prevent path traversal attacks
+///
+/// Security checks:
+/// 1. Path must not contain parent directory references (..)
+/// 2. Canonicalized path must be within current working directory
+/// 3. Path must not point to sensitive system directories
+///
+/// Returns validated canonical path or error
+fn validate_registry_path(path: &str) -> Result<PathBuf> {
+    let path_buf = PathBuf::from(path);
+
+    // SECURITY: Prevent path traversal with .. components
+    if path.contains("..") {
+        bail!("Registry path cannot contain parent directory references (..)");
+    }
+
+    // Convert to absolute path (handles relative paths safely)
+    let absolute_path = if path_buf.is_absolute() {
+        path_buf.clone()
+    } else {
+        std::env::current_dir()?.join(&path_buf)
+    };
+
+    // Canonicalize to resolve symlinks and normalize path
+    let canonical = match absolute_path.canonicalize() {
+        Ok(p) => p,
+        Err(_) => {
+            // If file doesn't exist yet, validate parent directory
+            let parent = absolute_path
+                .parent()
+                .ok_or_else(|| anyhow::anyhow!("Invalid registry path: no parent directory"))?;
+
+            if !parent.exists() {
+                bail!(
+                    "Registry path parent directory does not exist: {}",
+                    parent.display()
+                );
+            }
+
+            // Return the non-canonical path for new files (will be created)
+            absolute_path
+        }
+    };
+
+    // SECURITY: Prevent access to sensitive system directories
+    let canonical_str = canonical.to_string_lossy();
+    let forbidden_prefixes = ["/etc", "/root", "/sys", "/proc", "/boot", "/dev"];
+
+    for prefix in &forbidden_prefixes {
+        if canonical_str.starts_with(prefix) {
+            bail!("Registry path cannot be in system directory: {}", prefix);
+        }
+    }
+
+    // SECURITY: Ensure path is within current working directory (or .claude subdir)
     let cwd = std::env::current_dir()?;
     if !canonical.starts_with(&cwd) {
         bail!(
@@ -169,466 +1153,3866 @@ fn validate_registry_path(path: &str) -> Result<PathBuf> {
         );
     }
 
-    Ok(canonical)
-}
+    Ok(canonical)
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let mut args: Vec<OsString> = std::env::args_os().collect();
+    let matches = Cli::command().get_matches_from(&args);
+    apply_config(&mut args, &matches)?;
+    let cli = Cli::parse_from(args);
+    configure(&cli)?;
+    // Only the daemons log; other commands print their results, which
+    // belong on stdout wherever stderr goes
+    if matches!(cli.command, Commands::Run { .. } | Commands::Serve { .. }) {
+        journal::init(cli.journald);
+    }
+    if let Some(path) = &cli.record_docker_calls {
+        let validated_path = validate_registry_path(path)?;
+        docker_recorder::record_calls_to(&validated_path)?;
+        debug!("   Recording Docker calls to {}", validated_path.display());
+    }
+    execute(cli).await
+}
+
+/// Add the flags the configuration stands for to `args` (as parsed into
+/// `matches`), for the arguments of its command left at their defaults.
+/// Only commands working on a registry read the configuration.
+fn apply_config<T>(args: &mut Vec<T>, matches: &ArgMatches) -> Result<()>
+where
+    T: From<String> + PartialEq<str>,
+{
+    let Some((name, sub)) = matches.subcommand() else {
+        return Ok(());
+    };
+    let command = Cli::command();
+    let Some(definition) = command.find_subcommand(name) else {
+        return Ok(());
+    };
+    let has_arg = |id: &str| definition.get_arguments().any(|arg| arg.get_id() == id);
+    if !has_arg("registry") {
+        return Ok(());
+    }
+    let config = Config::find(matches.get_one::<PathBuf>("config").map(PathBuf::as_path))?;
+    let flags: Vec<String> = config
+        .flags()
+        .into_iter()
+        .filter(|(id, _)| {
+            has_arg(id) && matches!(sub.value_source(id), None | Some(ValueSource::DefaultValue))
+        })
+        .flat_map(|(_, flag)| flag)
+        .collect();
+    if let Some(at) = args.iter().position(|arg| *arg == *name) {
+        args.splice(at + 1..at + 1, flags.into_iter().map(T::from));
+    }
+    Ok(())
+}
+
+/// Apply the global flags that can change from one `shell` line to the next
+fn configure(cli: &Cli) -> Result<()> {
+    set_verbosity(Verbosity::from_flags(cli.quiet, cli.verbose));
+    if cli.output == OutputFormat::Json && !cli.command.has_json_output() {
+        bail!("--output json is not supported by this command");
+    }
+    set_output_format(cli.output);
+    set_display_zone(if cli.utc {
+        DisplayZone::Utc
+    } else {
+        cli.time_zone.unwrap_or_default()
+    });
+    coredump::set_backtraces(cli.core_backtrace);
+    if let Some(kind) = cli.registry_backend {
+        storage::set_backend(kind)?;
+    }
+    Ok(())
+}
+
+/// Serve the HTTP API on `socket` alongside the daemon
+#[cfg(feature = "http")]
+fn serve_http(socket: PathBuf, registry_path: &Path) -> Result<()> {
+    let registry_path = registry_path.to_path_buf();
+    tokio::spawn(async move {
+        if let Err(e) = api::serve(socket, registry_path).await {
+            warn!("⚠️  {:#}", e);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(feature = "http"))]
+fn serve_http(_socket: PathBuf, _registry_path: &Path) -> Result<()> {
+    bail!("This build has no HTTP API; rebuild with `--features http`")
+}
+
+/// Serve the HTTP API on `socket` (next to the registry by default) until
+/// stopped
+#[cfg(feature = "http")]
+async fn serve_api(socket: Option<PathBuf>, registry_path: PathBuf) -> Result<()> {
+    let socket = socket.unwrap_or_else(|| api::socket_path(&registry_path));
+    api::serve(socket, registry_path).await
+}
+
+#[cfg(not(feature = "http"))]
+async fn serve_api(_socket: Option<PathBuf>, _registry_path: PathBuf) -> Result<()> {
+    bail!("This build has no HTTP API; rebuild with `--features http`")
+}
+
+/// Serve the gRPC API on `socket` alongside the daemon, streaming its events
+#[cfg(all(feature = "grpc", unix))]
+fn serve_grpc(socket: PathBuf, registry_path: &Path, watchdog: &Watchdog) -> Result<()> {
+    use task_watchdog::grpc::{self, WatchdogService};
+
+    let service = WatchdogService::new(registry_path.to_path_buf(), watchdog.event_sender());
+    tokio::spawn(async move {
+        if let Err(e) = grpc::serve(socket, service).await {
+            warn!("⚠️  {:#}", e);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(feature = "grpc"))]
+fn serve_grpc(_socket: PathBuf, _registry_path: &Path, _watchdog: &Watchdog) -> Result<()> {
+    bail!("This build has no gRPC API; rebuild with `--features grpc`")
+}
+
+#[cfg(all(feature = "grpc", not(unix)))]
+fn serve_grpc(_socket: PathBuf, _registry_path: &Path, _watchdog: &Watchdog) -> Result<()> {
+    bail!("The gRPC API is served on a Unix socket, which this platform lacks")
+}
+
+/// Run a parsed command line
+async fn execute(cli: Cli) -> Result<()> {
+    match cli.command {
+        Commands::Run {
+            interval,
+            memory_budget,
+            restart_on_leak,
+            fair_by,
+            weight,
+            headroom,
+            takeover,
+            http_socket,
+            grpc_socket,
+            enforce_limits,
+            max_processes_per_task,
+            max_process_rate,
+            max_open_files_per_task,
+            max_threads_per_task,
+            escalate_after,
+            registry,
+        } => {
+            let validated_path = validate_registry_path(&registry)?;
+            let _daemon_lock = DaemonLock::acquire(&validated_path, takeover).await?;
+            if let Some(socket) = http_socket {
+                serve_http(socket, &validated_path)?;
+            }
+            let memory = MemoryTracker::new(memory_budget.map(|mb| mb * 1024));
+            let fairness = Fairness {
+                label: fair_by,
+                weights: weight.into_iter().collect(),
+            };
+            let mut watchdog = Watchdog::new(&validated_path, fairness, headroom);
+            let events_path = watchdog.registry().events_path();
+            let mode = watchdog.registry().file_mode();
+            watchdog.set_event_log(EventLog::open(&events_path, mode)?);
+            watchdog.set_enforce_limits(enforce_limits);
+            watchdog.set_process_limits(ProcessLimits {
+                max: max_processes_per_task,
+                per_minute: max_process_rate,
+                open_files: max_open_files_per_task,
+                threads: max_threads_per_task,
+            });
+            let escalate_after = escalate_after.into_iter().map(Duration::from_secs);
+            watchdog.set_escalation(escalate_after.collect());
+            let socket = capture::socket_path(&validated_path);
+            let (mode, output) = (watchdog.registry().file_mode(), watchdog.output());
+            tokio::spawn(async move {
+                if let Err(e) = capture::serve(socket, mode, output).await {
+                    warn!("⚠️  {:#}", e);
+                }
+            });
+            if let Some(socket) = grpc_socket {
+                serve_grpc(socket, &validated_path, &watchdog)?;
+            }
+            let config = Config::find(cli.config.as_deref())?;
+            let mut notifier = Notifier::new(&config.notify)?;
+            let mode = watchdog.registry().file_mode();
+            notifier.keep_held_in(notify::held_path(&validated_path), mode)?;
+            run_watchdog(
+                interval,
+                watchdog,
+                notifier,
+                memory,
+                restart_on_leak,
+                &validated_path.to_string_lossy(),
+            )
+            .await?
+        }
+        Commands::Serve { socket, registry } => {
+            let validated_path = validate_registry_path(&registry)?;
+            serve_api(socket, validated_path).await?
+        }
+        Commands::Whoami {
+            pid,
+            container,
+            registry,
+        } => {
+            let validated_path = validate_registry_path(&registry)?;
+            whoami(pid, container.as_deref(), &validated_path.to_string_lossy()).await?
+        }
+        Commands::Check {
+            task_id,
+            tail,
+            registry,
+        } => {
+            let validated_path = validate_registry_path(&registry)?;
+            check_task(&task_id, tail, &validated_path.to_string_lossy()).await?
+        }
+        Commands::Wait {
+            task_id,
+            any,
+            timeout,
+            registry,
+        } => {
+            let validated_path = validate_registry_path(&registry)?;
+            let queries = match task_id {
+                Some(task_id) => vec![task_id],
+                None => any,
+            };
+            let timeout = timeout.map(Duration::from_secs);
+            wait_task(&queries, timeout, &validated_path.to_string_lossy()).await?
+        }
+        Commands::Kill {
+            task_id,
+            filter,
+            group,
+            force,
+            registry,
+        } => {
+            let validated_path = validate_registry_path(&registry)?;
+            let origin = origin_here(&cli.origin);
+            let registry_path = validated_path.to_string_lossy();
+            let killed = match (task_id, Filter::in_group(filter, group.as_deref())) {
+                (_, Some(filter)) => kill_matching(&filter, force, &origin, &registry_path).await?,
+                (Some(task_id), None) => {
+                    kill_task(&task_id, force, &origin, &registry_path).await?
+                }
+                (None, None) => bail!("Give a task ID, --where or --group"),
+            };
+            if json_output() {
+                print_json(&serde_json::json!({ "killed": killed }))?;
+            }
+        }
+        #[cfg(unix)]
+        Commands::KillTree {
+            pid,
+            signal,
+            grace,
+            force,
+            registry,
+        } => {
+            let validated_path = validate_registry_path(&registry)?;
+            let origin = origin_here(&cli.origin);
+            let killed = kill_tree(
+                pid,
+                signal,
+                grace,
+                force,
+                &origin,
+                &validated_path.to_string_lossy(),
+            )?;
+            if json_output() {
+                print_json(&killed)?;
+            }
+        }
+        Commands::Rehydrate { registry } => {
+            let validated_path = validate_registry_path(&registry)?;
+            rehydrate(&validated_path.to_string_lossy()).await?
+        }
+        Commands::ContextBundle {
+            task,
+            max_tokens,
+            json,
+            registry,
+        } => {
+            let validated_path = validate_registry_path(&registry)?;
+            let json = json || json_output();
+            context_bundle(&task, max_tokens, json, &validated_path.to_string_lossy()).await?
+        }
+        Commands::Report { filter, registry } => {
+            let validated_path = validate_registry_path(&registry)?;
+            show_report(&validated_path.to_string_lossy(), filter.as_ref()).await?
+        }
+        Commands::List {
+            status,
+            since,
+            sort,
+            limit,
+            filter,
+            group,
+            registry,
+        } => {
+            let validated_path = validate_registry_path(&registry)?;
+            let query = TaskQuery {
+                statuses: status,
+                since: since.map(|secs| chrono::Duration::seconds(secs as i64)),
+                filter: Filter::in_group(filter, group.as_deref()),
+                order: sort.parse().map_err(anyhow::Error::msg)?,
+                limit,
+            };
+            list_tasks(&query, &validated_path.to_string_lossy())?
+        }
+        Commands::History { task_id, registry } => {
+            let validated_path = validate_registry_path(&registry)?;
+            task_history(&task_id, &validated_path.to_string_lossy())?
+        }
+        Commands::Replay {
+            from,
+            speed,
+            since,
+            until,
+            registry,
+        } => {
+            let path = match from {
+                Some(path) => path,
+                None => lifecycle::events_path(&validate_registry_path(&registry)?),
+            };
+            replay(&path, since, until, speed).await?
+        }
+        Commands::Chaos {
+            spawn,
+            kill_random,
+            duration,
+            seed,
+            registry,
+        } => {
+            let validated_path = validate_registry_path(&registry)?;
+            run_chaos(
+                spawn,
+                kill_random,
+                duration,
+                seed,
+                origin_here(&cli.origin),
+                &validated_path,
+            )
+            .await?
+        }
+        Commands::Stats {
+            group_by,
+            per_day,
+            group,
+            registry,
+        } => {
+            let validated_path = validate_registry_path(&registry)?;
+            show_stats(
+                group_by.as_deref(),
+                per_day,
+                group.as_deref(),
+                &validated_path.to_string_lossy(),
+            )
+            .await?
+        }
+        Commands::Cost {
+            task_id,
+            input_tokens,
+            output_tokens,
+            usd,
+            registry,
+        } => {
+            let validated_path = validate_registry_path(&registry)?;
+            let cost = TaskCost {
+                input_tokens,
+                output_tokens,
+                usd,
+            };
+            report_cost(&task_id, cost, &validated_path.to_string_lossy()).await?
+        }
+        Commands::Capacity { headroom, registry } => {
+            let validated_path = validate_registry_path(&registry)?;
+            show_capacity(headroom, &validated_path.to_string_lossy()).await?
+        }
+        Commands::ExportMetrics {
+            textfile,
+            filter,
+            registry,
+        } => {
+            let validated_path = validate_registry_path(&registry)?;
+            export_metrics(textfile.as_deref(), filter.as_ref(), &validated_path)?
+        }
+        Commands::Cleanup {
+            days,
+            filter,
+            group,
+            registry,
+        } => {
+            let validated_path = validate_registry_path(&registry)?;
+            let filter = Filter::in_group(filter, group.as_deref());
+            cleanup_tasks(days, filter.as_ref(), &validated_path.to_string_lossy()).await?
+        }
+        Commands::PruneContainers { filter, registry } => {
+            let validated_path = validate_registry_path(&registry)?;
+            prune_containers(filter.as_ref(), &validated_path.to_string_lossy()).await?
+        }
+        Commands::Register {
+            task_id,
+            command,
+            from_file,
+            stdin,
+            shell,
+            done_file,
+            description,
+            expected_outcome,
+            label,
+            group,
+            memory,
+            cpu,
+            headroom,
+            queue,
+            timeout,
+            max_memory,
+            max_cpu,
+            max_processes,
+            max_open_files,
+            max_threads,
+            rlimit,
+            after,
+            ready,
+            rules,
+            metadata_json,
+            registry,
+        } => {
+            let validated_path = validate_registry_path(&registry)?;
+            let admission = Admission {
+                reservation: reservation_from(memory, cpu)?,
+                headroom,
+                queue,
+            };
+            let mut details = Details {
+                description,
+                expected_outcome,
+                labels: label.into_iter().collect(),
+                group,
+                metadata: metadata_json.unwrap_or_default(),
+                done_file: None,
+                timeout_secs: timeout,
+                max_memory_mb: max_memory,
+                max_cpu_percent: max_cpu,
+                max_processes,
+                max_open_files,
+                max_threads,
+                rlimits: rlimit.into_iter().collect(),
+                depends_on: after,
+                readiness: ready,
+            };
+            check_rlimits(&details.rlimits)?;
+            let entries = match (from_file, stdin) {
+                (Some(path), _) => {
+                    let content = std::fs::read_to_string(&path)
+                        .with_context(|| format!("Failed to read {}", path.display()))?;
+                    Some(parse_task_specs(&content, &path.display().to_string())?)
+                }
+                (None, true) => {
+                    let mut content = String::new();
+                    std::io::stdin()
+                        .read_to_string(&mut content)
+                        .context("Failed to read stdin")?;
+                    Some(parse_task_specs(&content, "stdin")?)
+                }
+                (None, false) => None,
+            };
+            if let Some(entries) = entries {
+                return register_batch(
+                    entries,
+                    details,
+                    rules,
+                    admission,
+                    &template_vars(&cli.vars)?,
+                    origin_here(&cli.origin),
+                    &validated_path.to_string_lossy(),
+                )
+                .await;
+            }
+            let (Some(task_id), Some(command)) = (task_id, command) else {
+                bail!("A task ID and --command are required without --from-file");
+            };
+            let vars = template_vars(&cli.vars)?.for_task(&task_id);
+            details.render(&vars)?;
+            let command = vars.render(&command)?;
+            details.done_file = done_file
+                .map(|path| resolve_done_file(&path, &task_id, &validated_path))
+                .transpose()?;
+            let command = TaskCommand::parse(&command, shell)?;
+            policy::check_command(&command)?;
+            register_task(
+                &task_id,
+                command,
+                details,
+                rules,
+                admission,
+                origin_here(&cli.origin),
+                &validated_path.to_string_lossy(),
+            )
+            .await?
+        }
+        Commands::Spawn {
+            task_id,
+            command,
+            shell,
+            detach,
+            done_file,
+            description,
+            expected_outcome,
+            label,
+            group,
+            timeout,
+            max_memory,
+            max_cpu,
+            max_processes,
+            max_open_files,
+            max_threads,
+            rlimit,
+            after,
+            ready,
+            rules,
+            metadata_json,
+            registry,
+        } => {
+            let validated_path = validate_registry_path(&registry)?;
+            let mut details = Details {
+                description,
+                expected_outcome,
+                labels: label.into_iter().collect(),
+                group,
+                metadata: metadata_json.unwrap_or_default(),
+                done_file: done_file
+                    .map(|path| resolve_done_file(&path, &task_id, &validated_path))
+                    .transpose()?,
+                timeout_secs: timeout,
+                max_memory_mb: max_memory,
+                max_cpu_percent: max_cpu,
+                max_processes,
+                max_open_files,
+                max_threads,
+                rlimits: rlimit.into_iter().collect(),
+                depends_on: after,
+                readiness: ready,
+            };
+            check_rlimits(&details.rlimits)?;
+            let vars = template_vars(&cli.vars)?.for_task(&task_id);
+            details.render(&vars)?;
+            let command = TaskCommand::parse(&vars.render(&command)?, shell)?;
+            policy::check_command(&command)?;
+            spawn_task(
+                &task_id,
+                command,
+                details,
+                rules,
+                detach,
+                origin_here(&cli.origin),
+                &validated_path.to_string_lossy(),
+            )
+            .await?
+        }
+        Commands::Up {
+            file,
+            image,
+            memory,
+            cpu,
+            auto_remove,
+            keep_on_failure,
+            registry,
+        } => {
+            let validated_path = validate_registry_path(&registry)?;
+            let vars = template_vars(&cli.vars)?;
+            let defaults = launch::Defaults {
+                image,
+                memory,
+                cpu,
+                auto_remove,
+                keep_on_failure,
+            };
+            up_tasks(
+                &file,
+                &vars,
+                &defaults,
+                origin_here(&cli.origin),
+                &validated_path,
+            )
+            .await?
+        }
+        Commands::Down { file, registry } => {
+            let validated_path = validate_registry_path(&registry)?;
+            // Stopping needs only the set's name and task IDs
+            let vars = template_vars(&cli.vars)?.lenient();
+            down_tasks(&file, &vars, origin_here(&cli.origin), &validated_path).await?
+        }
+        Commands::Swarm {
+            from,
+            jobs,
+            group,
+            label,
+            headroom,
+            registry,
+        } => {
+            let validated_path = validate_registry_path(&registry)?;
+            let group = match group {
+                Some(group) => group,
+                None => swarm::default_group(&from)?,
+            };
+            let defaults = Details {
+                labels: label.into_iter().collect(),
+                group: Some(group),
+                ..Default::default()
+            };
+            swarm_tasks(
+                &from,
+                jobs,
+                defaults,
+                headroom,
+                &template_vars(&cli.vars)?,
+                origin_here(&cli.origin),
+                &validated_path,
+            )
+            .await?
+        }
+        Commands::Adopt {
+            task_id,
+            port,
+            description,
+            registry,
+        } => {
+            let validated_path = validate_registry_path(&registry)?;
+            let origin = origin_here(&cli.origin);
+            adopt_port(
+                task_id,
+                port,
+                description,
+                origin,
+                &validated_path.to_string_lossy(),
+            )?
+        }
+        Commands::Discover {
+            min_age,
+            adopt,
+            registry,
+        } => {
+            let validated_path = validate_registry_path(&registry)?;
+            discover_tasks(
+                min_age,
+                adopt,
+                origin_here(&cli.origin),
+                &validated_path.to_string_lossy(),
+            )?
+        }
+        Commands::WatchPid {
+            pid,
+            name,
+            registry,
+        } => {
+            let validated_path = validate_registry_path(&registry)?;
+            watch_pid(pid, name, &validated_path.to_string_lossy())?
+        }
+        Commands::Unwatch { name, registry } => {
+            let validated_path = validate_registry_path(&registry)?;
+            let mut registry = RegistryManager::new(validated_path);
+            registry.load()?;
+            match registry.unwatch(&name)? {
+                Some(process) => info!("👁️  No longer watching {} (PID {})", name, process.pid),
+                None => bail!("Not watching anything named '{}'", name),
+            }
+        }
+        Commands::Goto { task_id, registry } => {
+            let validated_path = validate_registry_path(&registry)?;
+            goto_task(&task_id, &validated_path.to_string_lossy())?
+        }
+        Commands::Logs {
+            task_id,
+            follow,
+            tail,
+            registry,
+        } => {
+            let validated_path = validate_registry_path(&registry)?;
+            show_logs(&task_id, follow, tail, &validated_path.to_string_lossy()).await?
+        }
+        Commands::Inspect {
+            task_id,
+            tail,
+            registry,
+        } => {
+            let validated_path = validate_registry_path(&registry)?;
+            inspect_task(&task_id, tail, &validated_path).await?
+        }
+        Commands::Init {
+            hooks,
+            force,
+            commit_safe,
+        } => init_project(InitOptions {
+            hooks,
+            force,
+            commit_safe,
+        })?,
+        Commands::Doctor { registry } => {
+            let validated_path = validate_registry_path(&registry)?;
+            doctor(&validated_path).await?
+        }
+        Commands::Version { json } => {
+            let build = BuildInfo::current();
+            if json || json_output() {
+                // Machine-readable: printed even with -q
+                println!("{}", serde_json::to_string_pretty(&build)?);
+            } else {
+                info!("{}", build);
+            }
+        }
+        Commands::Typescript => {
+            // Meant for redirecting into a file: printed even with -q
+            print!("{}", typescript::DEFINITIONS);
+        }
+        Commands::SelfUpdate {
+            check_only,
+            insecure,
+        } => self_update(check_only, insecure).await?,
+        Commands::Shell { registry } => {
+            let validated_path = validate_registry_path(&registry)?;
+            run_shell(&validated_path, cli.config.as_deref()).await?
+        }
+    }
+
+    Ok(())
+}
+
+/// Commands that can't run inside `shell`
+const NOT_IN_SHELL: &[&str] = &["run", "shell"];
+
+/// Read commands at a prompt and run each like a `task-watchdog` command
+/// line, until `exit` or Ctrl-D
+async fn run_shell(registry_path: &Path, config_path: Option<&Path>) -> Result<()> {
+    let commands = Cli::command()
+        .get_subcommands()
+        .filter(|command| !command.is_hide_set())
+        .map(|command| command.get_name().to_string())
+        .filter(|name| !NOT_IN_SHELL.contains(&name.as_str()))
+        .chain(["exit".to_string()])
+        .collect();
+    let mut editor: Editor<ShellHelper, DefaultHistory> = Editor::new()?;
+    editor.set_helper(Some(ShellHelper::new(commands)));
+    let history = shell::history_path(registry_path);
+    let _ = editor.load_history(&history);
+    let mut registry = RegistryManager::new(registry_path);
+
+    info!("🐚 task-watchdog shell on {}", registry_path.display());
+    info!("   `help` lists commands, Tab completes, Ctrl-D exits");
+    loop {
+        // Completion offers the tasks registered as of this prompt
+        if registry.load().is_ok() {
+            let task_ids = registry.registry().tasks.keys().cloned().collect();
+            if let Some(helper) = editor.helper_mut() {
+                helper.set_task_ids(task_ids);
+            }
+        }
+        let line = match editor.readline("watchdog> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let _ = editor.add_history_entry(line);
+        if matches!(line, "exit" | "quit") {
+            break;
+        }
+        if let Err(e) = run_shell_line(line, registry_path, config_path).await {
+            warn!("❌ {:#}", e);
+        }
+    }
+    if let Err(e) = editor.save_history(&history) {
+        debug!("   Could not save shell history: {}", e);
+    }
+    Ok(())
+}
+
+/// Run one shell line. Commands without `--registry` or `--config` use
+/// the shell's.
+async fn run_shell_line(
+    line: &str,
+    registry_path: &Path,
+    config_path: Option<&Path>,
+) -> Result<()> {
+    let mut args = vec!["task-watchdog".to_string()];
+    args.extend(shell::split(line)?);
+    let matches = match Cli::command().try_get_matches_from(&args) {
+        Ok(matches) => matches,
+        Err(e) => {
+            // Also how `help` and `--help` print
+            e.print()?;
+            return Ok(());
+        }
+    };
+    if let Some((name, sub)) = matches.subcommand() {
+        if NOT_IN_SHELL.contains(&name) {
+            bail!("`{}` can't run inside the shell", name);
+        }
+        let defaulted = sub.ids().any(|id| id.as_str() == "registry")
+            && sub.value_source("registry") == Some(ValueSource::DefaultValue);
+        if let (true, Some(at)) = (defaulted, args.iter().position(|arg| arg == name)) {
+            let path = registry_path.to_string_lossy().into_owned();
+            args.splice(at + 1..at + 1, ["--registry".to_string(), path]);
+        }
+    }
+    if let (Some(path), None) = (config_path, matches.get_one::<PathBuf>("config")) {
+        let path = path.to_string_lossy().into_owned();
+        args.splice(1..1, ["--config".to_string(), path]);
+    }
+    let matches = Cli::command().try_get_matches_from(&args)?;
+    apply_config(&mut args, &matches)?;
+    let cli = Cli::try_parse_from(&args)?;
+    if let Commands::Spawn { detach: false, .. } = cli.command {
+        bail!("Use `spawn --detach` in the shell: a foreground spawn exits with its task");
+    }
+    configure(&cli)?;
+    Box::pin(execute(cli)).await
+}
+
+/// Main watchdog loop
+async fn run_watchdog(
+    interval_secs: u64,
+    mut watchdog: Watchdog,
+    mut notifier: Notifier,
+    mut memory: MemoryTracker,
+    restart_on_leak: bool,
+    registry_path: &str,
+) -> Result<()> {
+    info!("🐕 Task Watchdog v{}", env!("CARGO_PKG_VERSION"));
+    verbose!("   Built with Rust for AI coding tools (Claude-tested)");
+    info!("   Check interval: {}s", interval_secs);
+    info!("   Registry: {}", registry_path);
+    if !notifier.is_empty() {
+        info!("   Notifications: {}", notifier.names().join(", "));
+    }
+    verbose!("   Memory usage: {}KB", watchdog.self_memory_kb());
+    info!();
+
+    let mut alerts = watchdog.subscribe_alerts();
+    if watchdog.docker_available() {
+        info!("✅ Docker available");
+    } else {
+        info!("⚠️  Docker not available (native processes only)");
+    }
+    info!();
+
+    // Leave a crash report behind instead of dying silently
+    crash::install_panic_hook(
+        crash::crash_dir_for(Path::new(registry_path)),
+        BTreeMap::from([
+            ("interval_secs".to_string(), interval_secs.to_string()),
+            ("registry".to_string(), registry_path.to_string()),
+            (
+                "docker".to_string(),
+                watchdog.docker_available().to_string(),
+            ),
+            ("restart_on_leak".to_string(), restart_on_leak.to_string()),
+        ]),
+    );
+
+    let mut was_throttling = false;
+
+    // Operators can poke the daemon without waiting for the interval:
+    // SIGUSR1 runs a check now, SIGUSR2 dumps a full report
+    #[cfg(unix)]
+    let (mut check_now, mut dump_report) = (
+        signal(SignalKind::user_defined1())?,
+        signal(SignalKind::user_defined2())?,
+    );
+    // SIGTERM (e.g. from `run --takeover`) is only acted on between checks
+    #[cfg(unix)]
+    let mut terminate = signal(SignalKind::terminate())?;
+    #[cfg(not(unix))]
+    let (mut check_now, mut dump_report, mut terminate) = (NoSignal, NoSignal, NoSignal);
+    // A touched done file is acted on at once
+    let mut done_files = done_files::Waker::new()?;
+    // So does a task or watched process exiting
+    let mut exits = exits::Waker::new()?;
+
+    loop {
+        let check_time = format_time(chrono::Utc::now(), "%H:%M:%S");
+        info!("🔍 Watchdog check - {}", check_time);
+
+        // Queued tasks wait while the CPU was hot last check
+        let stats = watchdog.check(!was_throttling).await?;
+
+        // New conditions are in the check's own output; repeat only the
+        // ones that have lasted past an escalation threshold
+        let mut raised = Vec::new();
+        while let Ok(alert) = alerts.try_recv() {
+            if alert.is_escalation() {
+                let age = (chrono::Utc::now() - alert.first_seen).num_seconds();
+                warn!(
+                    "🚨 Ongoing for {}: {}",
+                    format_duration(age as f64),
+                    alerts::describe(&alert.event)
+                );
+            }
+            raised.push(alert);
+        }
+        // Even with nothing new, so a digest goes out when quiet hours end
+        notifier.deliver(&raised, Local::now().naive_local()).await;
+
+        // Show stats
+        crash::update_stats(&stats);
+        info!("\n📊 Status:");
+        info!("   Running: {}", stats.running);
+        info!("   Completed: {}", stats.completed);
+        info!("   Failed: {}", stats.failed);
+        info!("   Killed: {}", stats.killed);
+        print_in_between(&stats);
+        info!("   Total: {}", stats.total);
+
+        let rss_kb = watchdog.self_memory_kb();
+        let alarm = memory.record(rss_kb);
+        match memory.trend_kb_per_hour() {
+            Some(trend) => verbose!("   Memory: {}KB (trend {:+.0}KB/h)", rss_kb, trend),
+            None => verbose!("   Memory: {}KB", rss_kb),
+        }
+        if let Some(alarm) = alarm {
+            warn!("🧠 Watchdog memory alarm: {}", alarm);
+            if restart_on_leak {
+                warn!("♻️  Restarting watchdog");
+                restart_self()?;
+            }
+        }
+
+        let thermal = ThermalStatus::sample();
+        if let Some(summary) = thermal.summary() {
+            verbose!("   Thermal: {}", summary);
+        }
+        // Only announce changes, not every cycle
+        match (thermal.is_throttling(), was_throttling) {
+            (true, false) => warn!("🔥 CPU is thermally throttling"),
+            (false, true) => info!("❄️  CPU no longer throttling"),
+            _ => {}
+        }
+        was_throttling = thermal.is_throttling();
+
+        let health = assess_health(
+            watchdog.registry().registry(),
+            Path::new(registry_path),
+            was_throttling,
+        );
+        print_health(&health);
+
+        done_files.watch(watchdog.done_files());
+        exits.watch(watchdog.exit_pids());
+        debug!("\n💤 Next check in {}s...\n", interval_secs);
+        let next_check = sleep(Duration::from_secs(interval_secs));
+        tokio::pin!(next_check);
+        loop {
+            tokio::select! {
+                _ = &mut next_check => break,
+                changed = done_files.changed() => {
+                    changed?;
+                    verbose!("\n📨 Done file written: checking now");
+                    break;
+                }
+                pids = exits.exited() => {
+                    let pids: Vec<String> = pids?.iter().map(i32::to_string).collect();
+                    verbose!("\n💀 PID {} exited: checking now", pids.join(", "));
+                    break;
+                }
+                _ = check_now.recv() => {
+                    info!("\n📣 SIGUSR1: checking now");
+                    break;
+                }
+                _ = dump_report.recv() => {
+                    info!("\n📣 SIGUSR2: status report");
+                    if let Err(e) = show_report(registry_path, None).await {
+                        warn!("⚠️  Status report failed: {:#}", e);
+                    }
+                }
+                _ = terminate.recv() => {
+                    info!("\n👋 SIGTERM: exiting");
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Stands in for the signals operators send the daemon where there are
+/// none: never received
+#[cfg(not(unix))]
+struct NoSignal;
+
+#[cfg(not(unix))]
+impl NoSignal {
+    async fn recv(&mut self) -> Option<()> {
+        std::future::pending().await
+    }
+}
+
+/// Exit code of `wait` when its `--timeout` passed first (`EX_TEMPFAIL`:
+/// the task may still finish, try again later)
+const WAIT_TIMED_OUT: i32 = 75;
+
+/// How often `wait` looks at the registry when the lifecycle journal
+/// hasn't changed, in case a change was missed or there is no inotify
+const WAIT_FALLBACK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Block until the first of `queries` has finished, reporting each task
+/// they depend on (directly or not) as it finishes, then exit with
+/// [`finished_exit_code`]. A queued task whose dependency finished without
+/// becoming ready fails right away; dependencies that keep running
+/// (servers) don't hold it up. The registry is looked at again whenever
+/// the lifecycle journal changes rather than on a timer, since every
+/// status change is appended to it.
+async fn wait_task(
+    queries: &[String],
+    timeout: Option<Duration>,
+    registry_path: &str,
+) -> Result<()> {
+    let mut registry = RegistryManager::new(registry_path);
+    // Watch before the first load so no change falls between the two
+    let mut journal = done_files::Waker::new()?;
+    journal.watch(vec![registry.events_path()]);
+    registry.load()?;
+    registry.load_history()?;
+    let mut task_ids = Vec::new();
+    for query in queries {
+        let task_id = registry
+            .resolve_task_id(query)?
+            .unwrap_or_else(|| query.to_string());
+        if !task_ids.contains(&task_id) {
+            task_ids.push(task_id);
+        }
+    }
+
+    let deadline = timeout.map(|timeout| tokio::time::Instant::now() + timeout);
+    let mut reported = HashSet::new();
+    let mut announced = false;
+    loop {
+        for task_id in &task_ids {
+            let Some(task) = registry.get_task(task_id) else {
+                bail!("Task {} not found", task_id);
+            };
+            for dep_id in registry.registry().dependency_closure(task_id) {
+                let dep = &registry.registry().tasks[&dep_id];
+                if dep.status.is_terminal() && reported.insert(dep_id.clone()) {
+                    report_finished(&dep_id, dep);
+                }
+            }
+            if task.status.is_terminal() {
+                report_finished(task_id, task);
+                let code = finished_exit_code(task);
+                if code != 0 {
+                    std::process::exit(code);
+                }
+                return Ok(());
+            }
+            if task.status == TaskStatus::Queued {
+                if let Dependencies::Failed(dep_id) =
+                    registry.registry().dependencies_of(&task.depends_on)
+                {
+                    warn!(
+                        "❌ Task {} can't start: {} finished without becoming ready",
+                        task_id, dep_id
+                    );
+                    std::process::exit(1);
+                }
+            }
+        }
+        if !announced {
+            let waiting: Vec<String> = task_ids
+                .iter()
+                .filter_map(|task_id| {
+                    let task = registry.get_task(task_id)?;
+                    Some(format!("{} ({})", task_id, task.status))
+                })
+                .collect();
+            info!("⏳ Waiting for {}...", waiting.join(" or "));
+            announced = true;
+        }
+        tokio::select! {
+            changed = journal.changed() => changed?,
+            _ = sleep(WAIT_FALLBACK_INTERVAL) => {}
+            _ = async {
+                match deadline {
+                    Some(deadline) => tokio::time::sleep_until(deadline).await,
+                    None => std::future::pending().await,
+                }
+            } => {
+                warn!("⏰ Gave up waiting for {}", task_ids.join(", "));
+                std::process::exit(WAIT_TIMED_OUT);
+            }
+        }
+        registry.load()?;
+        registry.load_history()?;
+    }
+}
+
+/// One line on how a finished task ended
+fn report_finished(task_id: &str, task: &TaskInfo) {
+    if task.status == TaskStatus::Completed {
+        info!(task: task_id; "✅ Task {} completed", task_id);
+        return;
+    }
+    let reason = task
+        .kill_reason
+        .as_ref()
+        .map(|reason| format!(": {reason}"))
+        .unwrap_or_default();
+    warn!(task: task_id; "❌ Task {} {}{}", task_id, task.status, reason);
+}
+
+/// What `wait` exits with for a finished task: 0 if it completed, its own
+/// exit code if it failed with one, 124 if it timed out (like coreutils
+/// `timeout`), 137 if it was killed (like SIGKILL) and 1 otherwise
+fn finished_exit_code(task: &TaskInfo) -> i32 {
+    match task.status {
+        TaskStatus::Completed => 0,
+        TaskStatus::TimedOut => 124,
+        TaskStatus::Killed => 137,
+        _ => task
+            .result
+            .as_ref()
+            .and_then(|result| result.exit_code)
+            .filter(|code| *code != 0)
+            .and_then(|code| i32::try_from(code).ok())
+            .unwrap_or(1),
+    }
+}
+
+/// Where a crashed task's core dump is, how to open it, and the top of its
+/// backtrace
+fn print_coredump(dump: &Coredump) {
+    let location = match &dump.path {
+        Some(path) => path.display().to_string(),
+        None if dump.coredumpctl => "systemd-coredump".to_string(),
+        None => "not found".to_string(),
+    };
+    match dump.signal {
+        Some(signal) => info!(
+            "   💥 Core dump ({}): {}",
+            coredump::signal_name(signal),
+            location
+        ),
+        None => info!("   💥 Core dump: {}", location),
+    }
+    if let Some(command) = dump.debug_command() {
+        info!("      Debug: {}", command);
+    }
+    for frame in &dump.backtrace {
+        info!("      {}", frame);
+    }
+}
+
+/// The end of a failed task's output that shows what went wrong
+fn print_failure_excerpt(excerpt: &str) {
+    info!("   📄 Failure excerpt:");
+    for line in excerpt.lines() {
+        info!("      {}", line);
+    }
+}
+
+/// Show the task process `pid` or `container` belongs to
+async fn whoami(pid: Option<i32>, container: Option<&str>, registry_path: &str) -> Result<()> {
+    let mut registry = RegistryManager::new(registry_path);
+    registry.load()?;
+    registry.load_history()?;
+
+    let (what, owner) = match (pid, container) {
+        (Some(pid), _) => {
+            let command = ProcessManager::command_line(pid);
+            if command.is_none() && !ProcessManager::is_alive(pid) {
+                bail!("No process with PID {}", pid);
+            }
+            let what = match command {
+                Some(command) => format!("PID {} ({})", pid, truncate(&command, 60)),
+                None => format!("PID {pid}"),
+            };
+            (what, whoami::owner_of_pid(registry.registry(), pid))
+        }
+        (None, Some(container)) => (
+            format!("Container {container}"),
+            whoami::owner_of_container(registry.registry(), container),
+        ),
+        (None, None) => bail!("Give --pid or --container"),
+    };
+    let Some(owner) = owner else {
+        bail!("{} doesn't belong to any task", what);
+    };
+
+    if json_output() {
+        let Some(task) = registry.get_task(&owner.task_id) else {
+            bail!("Task {} not found", owner.task_id);
+        };
+        return print_json(&serde_json::json!({
+            "pid": pid,
+            "container": container,
+            "owner": owner,
+            "task": TaskEntry {
+                task_id: owner.task_id.clone(),
+                alive: tasks::alive(task).await,
+                task: task.clone(),
+            },
+        }));
+    }
+    info!(
+        "🔎 {} belongs to task {}: {}",
+        what, owner.task_id, owner.how
+    );
+    info!("");
+    check_task(&owner.task_id, None, registry_path).await
+}
+
+/// Check status of specific task
+async fn check_task(query: &str, tail: Option<usize>, registry_path: &str) -> Result<()> {
+    let mut registry = RegistryManager::new(registry_path);
+    registry.load()?;
+    registry.load_history()?;
+
+    // Accept prefixes, fuzzy matches and @selectors; unknown IDs fall through to "not found"
+    let task_id = registry
+        .resolve_task_id(query)?
+        .unwrap_or_else(|| query.to_string());
+    let task_id = task_id.as_str();
+
+    if json_output() {
+        let Some(task) = registry.get_task(task_id) else {
+            bail!("Task {} not found", task_id);
+        };
+        return print_json(&TaskEntry {
+            task_id: task_id.to_string(),
+            alive: tasks::alive(task).await,
+            task: task.clone(),
+        });
+    }
+
+    match registry.get_task(task_id) {
+        Some(task) => {
+            info!("📋 Task: {}", task_id);
+            if let Some(description) = &task.description {
+                info!("   Goal: {}", description);
+            }
+            if let Some(expected) = &task.expected_outcome {
+                info!("   Expected outcome: {}", expected);
+            }
+            info!("   Command: {}", task.command);
+            info!("   Mode: {:?}", task.mode);
+            info!("   Status: {:?}", task.status);
+            if let Some(reason) = &task.kill_reason {
+                info!("   Kill reason: {}", reason);
+            }
+            if let Some(reason) = &task.failure_reason {
+                info!("   Failure reason: {}", reason);
+            }
+            if let Some(hint) = &task.triage_hint {
+                info!("   💡 Likely cause: {}", hint);
+            }
+            if let Some(dump) = &task.coredump {
+                print_coredump(dump);
+            }
+            if let Some(excerpt) = &task.failure_excerpt {
+                print_failure_excerpt(excerpt);
+            }
+            if let Some(result) = &task.result {
+                if let Some(summary) = &result.summary {
+                    info!("   Result: {}", summary);
+                }
+                if !result.artifacts.is_empty() {
+                    info!("   Artifacts: {}", result.artifacts.join(", "));
+                }
+            }
+            info!("   Started: {}", format_when(task.started_at));
+            if let Some(at) = task.completed_at {
+                info!("   Finished: {}", format_when(at));
+            }
+            if let Some(a) = anomaly::find_anomalies(registry.registry(), chrono::Utc::now())
+                .into_iter()
+                .find(|a| a.task_id == task_id)
+            {
+                warn!(
+                    "   🐢 Running {:.1}x longer than this command's usual p95 ({})",
+                    a.ratio(),
+                    format_duration(a.p95_secs)
+                );
+            }
+            if let Some(owner) = &task.owner {
+                info!("   Owner: {}", owner);
+            }
+            if let Some(origin) = &task.origin {
+                info!("   Origin: {}", origin);
+                if let Some(pane) = &origin.tmux {
+                    info!(
+                        "   tmux: {} ({}), `goto {}` to switch",
+                        pane, pane.pane_id, task_id
+                    );
+                }
+            }
+            if let Some(path) = &task.done_file {
+                info!("   Done file: {}", path.display());
+            }
+            if let Some(path) = &task.log_file {
+                info!("   Log: {}", path.display());
+            }
+            if let Some(secs) = task.timeout_secs {
+                info!("   Timeout: {}", format_duration(secs as f64));
+            }
+            if let Some(mb) = task.max_memory_mb {
+                info!("   Max memory: {}MB", mb);
+            }
+            if let Some(percent) = task.max_cpu_percent {
+                info!("   Max CPU: {:.0}%", percent);
+            }
+            if let Some(count) = task.max_processes {
+                info!("   Max processes: {}", count);
+            }
+            if let Some(count) = task.max_open_files {
+                info!("   Max open files: {}", count);
+            }
+            if let Some(count) = task.max_threads {
+                info!("   Max threads: {}", count);
+            }
+            if !task.rlimits.is_empty() {
+                let limits: Vec<String> = task
+                    .rlimits
+                    .iter()
+                    .map(|(rlimit, value)| format!("{}={}", rlimit, rlimit.format_value(*value)))
+                    .collect();
+                info!("   Rlimits: {}", limits.join(", "));
+            }
+            if !task.restart.is_no() {
+                info!(
+                    "   Restart: {} (restarts so far: {})",
+                    task.restart, task.restarts
+                );
+            }
+            if !task.depends_on.is_empty() {
+                info!("   After: {}", task.depends_on.join(", "));
+                if let Dependencies::Waiting(dep_id) =
+                    registry.registry().dependencies_of(&task.depends_on)
+                {
+                    info!("   ⏳ Waiting for {} to become ready", dep_id);
+                }
+            }
+            if let Some(probe) = &task.readiness {
+                match task.ready_at {
+                    Some(at) => info!("   Ready: {} (since {})", probe, format_when(at)),
+                    None => info!("   Ready: {} (not yet)", probe),
+                }
+            }
+            if let Some(port) = task.port {
+                match ports::is_listening(port) {
+                    Some(false) => warn!("   Port: {} (nothing listening)", port),
+                    _ => info!("   Port: {}", port),
+                }
+            }
+            if !task.labels.is_empty() {
+                let labels: Vec<String> = task
+                    .labels
+                    .iter()
+                    .map(|(k, v)| format!("{k}={v}"))
+                    .collect();
+                info!("   Labels: {}", labels.join(", "));
+            }
+            if let Some(group) = &task.group {
+                info!("   Group: {}", group);
+            }
+            if let Some(seconds) = task.cpu_seconds {
+                info!("   CPU time: {}", format_duration(seconds));
+            }
+            if let Some(cost) = &task.cost {
+                info!(
+                    "   Spend: ${:.2} ({} tokens in, {} out)",
+                    cost.usd, cost.input_tokens, cost.output_tokens
+                );
+            }
+            if let Some(cwd) = &task.cwd {
+                info!("   Directory: {}", cwd.display());
+            }
+            if let Some(root) = &task.repo_root {
+                verbose!("   Repo root: {}", root.display());
+            }
+            if !task.constitution_rules.is_empty() {
+                verbose!("   Rules: {}", task.constitution_rules.join(", "));
+            }
+            if !task.metadata.is_null() {
+                verbose!("   Metadata: {}", task.metadata);
+            }
+
+            match &task.mode {
+                ExecutionMode::Native => {
+                    if let Some(native) = &task.native {
+                        let is_alive = ProcessManager::is_alive(native.pid);
+                        info!(
+                            "   PID: {} ({})",
+                            native.pid,
+                            if is_alive { "✅ alive" } else { "💀 dead" }
+                        );
+                        verbose!("   PGID: {}", native.pgid);
+                        debug!("   Start time: {}", native.start_time);
+                        if let Some(tag) = &native.env_tag {
+                            debug!("   Env tag: {}", tag);
+                        }
+
+                        if is_alive {
+                            let mut monitor = SystemMonitor::new();
+                            monitor.refresh(&[native.pid]).await;
+                            if let Some(usage) = monitor.usage(native.pid) {
+                                verbose!("   CPU: {:.1}%", usage.cpu_percent);
+                                verbose!("   Memory: {}MB", usage.memory_kb / 1024);
+                                verbose!("   I/O: {}", usage.io);
+                            }
+                        }
+                    }
+                }
+                ExecutionMode::Docker => {
+                    if let Some(docker_info) = &task.docker {
+                        info!("   Container: {}", &docker_info.container_id[..12]);
+                        verbose!(
+                            "   Limits: {} memory, {} CPU",
+                            docker_info.resource_limits.memory,
+                            docker_info.resource_limits.cpu
+                        );
+                        debug!("   Container name: {}", docker_info.container_name);
+
+                        if let Some(docker) = DockerManager::new() {
+                            let is_running = docker.is_running(&docker_info.container_id).await;
+                            info!(
+                                "   Status: {}",
+                                if is_running {
+                                    "✅ running"
+                                } else {
+                                    "💀 stopped"
+                                }
+                            );
+                            if is_running && enabled(Verbosity::Verbose) {
+                                if let Ok(stats) = docker.get_stats(&docker_info.container_id).await
+                                {
+                                    verbose!("   I/O: {}", stats.io);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            if let Some(lines) = tail {
+                match task_output(task_id, task, Path::new(registry_path), lines).await {
+                    Some(output) if !output.is_empty() => {
+                        info!("   📜 Last {} lines of output:", lines);
+                        for line in output.lines() {
+                            info!("      {}", line);
+                        }
+                    }
+                    _ => info!("   📜 No output recorded"),
+                }
+            }
+        }
+        None => {
+            warn!("❌ Task {} not found", task_id);
+        }
+    }
+
+    Ok(())
+}
+
+/// The last `lines` lines `task` wrote: from its log, or from the daemon if
+/// it started the task without one (see [`capture`])
+async fn task_output(
+    task_id: &str,
+    task: &TaskInfo,
+    registry_path: &Path,
+    lines: usize,
+) -> Option<String> {
+    let path = task
+        .log_file
+        .clone()
+        .unwrap_or_else(|| logs::log_path(registry_path, task_id));
+    if path.exists() {
+        return logs::tail(&path, lines).ok();
+    }
+    capture::request(registry_path, task_id, Some(lines)).await
+}
+
+/// Kill a running task
+async fn kill_task(
+    query: &str,
+    force: bool,
+    origin: &TaskOrigin,
+    registry_path: &str,
+) -> Result<Vec<String>> {
+    let mut registry = RegistryManager::new(registry_path);
+    registry.load()?;
+
+    // Accept prefixes, fuzzy matches and @selectors; unknown IDs fall through to "not found"
+    let task_id = registry
+        .resolve_task_id(query)?
+        .unwrap_or_else(|| query.to_string());
+    let task_id = task_id.as_str();
+
+    match registry.get_task(task_id) {
+        Some(task) => {
+            if !force {
+                ensure_own_task(task_id, task)?;
+                ensure_same_project(task_id, task)?;
+            }
+            tasks::kill(&mut registry, task_id, origin).await?;
+            Ok(vec![task_id.to_string()])
+        }
+        None => {
+            warn!("❌ Task {} not found", task_id);
+            Ok(Vec::new())
+        }
+    }
+}
+
+/// Kill every unfinished task matching `filter`. Tasks of other users or
+/// checkouts are skipped (with a warning) unless `force` is set.
+async fn kill_matching(
+    filter: &Filter,
+    force: bool,
+    origin: &TaskOrigin,
+    registry_path: &str,
+) -> Result<Vec<String>> {
+    let mut registry = RegistryManager::new(registry_path);
+    registry.load()?;
+
+    let now = chrono::Utc::now();
+    let mut matching: Vec<String> = registry
+        .active_tasks()
+        .into_iter()
+        .filter(|(task_id, task)| filter.matches(task_id, task, now))
+        .map(|(task_id, _)| task_id.clone())
+        .collect();
+    matching.sort();
+    if matching.is_empty() {
+        warn!("❌ No unfinished tasks match '{}'", filter);
+        return Ok(Vec::new());
+    }
+
+    let mut killed = Vec::new();
+    for task_id in &matching {
+        if let Some(task) = registry.get_task(task_id) {
+            if !force {
+                if let Err(e) =
+                    ensure_own_task(task_id, task).and_then(|_| ensure_same_project(task_id, task))
+                {
+                    warn!(task: task_id; "⏭️  Skipping {}: {:#}", task_id, e);
+                    continue;
+                }
+            }
+            tasks::kill(&mut registry, task_id, origin).await?;
+            killed.push(task_id.clone());
+        }
+    }
+
+    Ok(killed)
+}
+
+/// Kill process `pid` and its descendants. A task running as (or leading
+/// the group of) `pid` gets the same checks as `kill`, and is marked killed.
+#[cfg(unix)]
+fn kill_tree(
+    pid: i32,
+    signal: nix::sys::signal::Signal,
+    grace: Duration,
+    force: bool,
+    origin: &TaskOrigin,
+    registry_path: &str,
+) -> Result<TreeKill> {
+    let mut registry = RegistryManager::new(registry_path);
+    registry.load()?;
+    ProcessManager::ensure_killable(pid, force)?;
+    let task_id = registry
+        .active_tasks()
+        .into_iter()
+        .find(|(_, task)| {
+            task.native
+                .as_ref()
+                .is_some_and(|native| native.pid == pid || native.pgid == pid)
+        })
+        .map(|(task_id, _)| task_id.clone());
+    if let Some(task_id) = &task_id {
+        if let Some(task) = registry.get_task(task_id) {
+            if !force {
+                ensure_own_task(task_id, task)?;
+                ensure_same_project(task_id, task)?;
+            }
+        }
+    }
+
+    let killed = ProcessManager::kill_tree(pid, signal, grace)?;
+    if let Some(task_id) = &task_id {
+        registry.mark_killed(task_id, &format!("killed by user via {origin} (kill-tree)"))?;
+        info!(task: task_id; "📝 Marked task {} killed", task_id);
+    }
+    if !json_output() {
+        let summary = if killed.killed.is_empty() {
+            format!(
+                "✅ All {} processes exited after {}",
+                killed.processes.len(),
+                killed.signal
+            )
+        } else {
+            format!(
+                "🔪 Sent SIGKILL to {} processes still running after {}: {}",
+                killed.killed.len(),
+                format_duration(grace.as_secs_f64()),
+                killed
+                    .killed
+                    .iter()
+                    .map(i32::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        };
+        match &task_id {
+            Some(task_id) => info!(task: task_id; "{}", summary),
+            None => info!("{}", summary),
+        }
+    }
+    Ok(killed)
+}
+
+/// Identify this invocation: `tool` plus the current user and host
+fn origin_here(tool: &str) -> TaskOrigin {
+    TaskOrigin {
+        tool: tool.to_string(),
+        user: ProcessManager::current_user(),
+        host: ProcessManager::hostname(),
+        tmux: tmux::current_pane(),
+    }
+}
+
+/// Refuse to act on a task another user registered on a shared registry
+fn ensure_own_task(task_id: &str, task: &TaskInfo) -> Result<()> {
+    let user = ProcessManager::current_user();
+    if !task.is_owned_by(user.as_deref()) {
+        bail!(
+            "Task {} belongs to user {}. Ask them, or pass --force",
+            task_id,
+            task.owner.as_deref().unwrap_or_default()
+        );
+    }
+    Ok(())
+}
+
+/// Refuse to act on a task registered from another checkout of the repo
+fn ensure_same_project(task_id: &str, task: &TaskInfo) -> Result<()> {
+    let here = std::env::current_dir()
+        .ok()
+        .and_then(|dir| ProcessManager::repo_root(&dir));
+    tasks::ensure_same_project(task_id, task, here.as_deref())
+        .map_err(|e| anyhow::anyhow!("{}. Run from that checkout or pass --force", e))
+}
+
+/// Rehydrate context after compression
+async fn rehydrate(registry_path: &str) -> Result<()> {
+    info!("🧠 Context Re-Hydration Report");
+    info!("================================\n");
+
+    let mut registry = RegistryManager::new(registry_path);
+    registry.load()?;
+
+    let active = registry.active_tasks();
+
+    if json_output() {
+        let mut entries = Vec::new();
+        for (task_id, task) in active {
+            entries.push(TaskEntry {
+                task_id: task_id.clone(),
+                alive: Some(tasks::alive(task).await.unwrap_or(false)),
+                task: task.clone(),
+            });
+        }
+        return print_json(&Rehydration {
+            active: entries,
+            stats: registry.stats(),
+        });
+    }
+
+    if active.is_empty() {
+        info!("✅ No tasks currently running\n");
+    } else {
+        info!("📊 ACTIVE TASKS ({})\n", active.len());
+
+        for (task_id, task) in active {
+            info!("Task {} [{}]", task_id, task.status);
+            if let Some(description) = &task.description {
+                info!("  Goal: {}", description);
+            }
+            if let Some(expected) = &task.expected_outcome {
+                info!("  Expected outcome: {}", expected);
+            }
+            info!("  Command: {}", task.command);
+            info!("  Mode: {:?}", task.mode);
+            info!("  Started: {}", format_when(task.started_at));
+            if let Some(origin) = &task.origin {
+                info!("  Origin: {}", origin);
+            }
+
+            let is_alive = tasks::alive(task).await.unwrap_or(false);
+
+            info!(
+                "  Status: {}",
+                if is_alive {
+                    "✅ Running"
+                } else if task.status == TaskStatus::Queued {
+                    "⏳ Queued"
+                } else {
+                    "⚠️  DEAD"
+                }
+            );
+            info!();
+        }
+    }
+
+    let stats = registry.stats();
+    info!("💡 SUMMARY:");
+    info!("   Running: {}", stats.running);
+    info!("   Completed: {}", stats.completed);
+    info!("   Failed: {}", stats.failed);
+    info!("   Killed: {}", stats.killed);
+    print_in_between(&stats);
+    verbose!("\n📄 Full context: {}", registry_path);
+
+    Ok(())
+}
+
+/// Resource table for the report, with the recent CPU and memory trend of
+/// each task the daemon has sampled
+async fn print_task_table(
+    tasks: Vec<(&String, &TaskInfo)>,
+    monitor: &SystemMonitor,
+    history: &ResourceHistory,
+) {
+    let mut table = new_table([
+        "Task",
+        "Mode",
+        "Status",
+        "Started",
+        "PID / Container",
+        "CPU",
+        "Memory",
+        "I/O",
+        "Command",
+    ]);
+    let command_width = (table_width(&table) as usize / 3).max(20);
+    let docker = DockerManager::new();
+    let now = chrono::Utc::now();
+
+    for (task_id, task) in tasks {
+        let none = || "-".to_string();
+        let (target, cpu, memory, io) = match &task.mode {
+            ExecutionMode::Native => match &task.native {
+                Some(native) => match monitor.usage(native.pid) {
+                    Some(usage) => (
+                        native.pid.to_string(),
+                        format!("{:.1}%", usage.cpu_percent),
+                        format!("{}MB", usage.memory_kb / 1024),
+                        usage.io.to_string(),
+                    ),
+                    None => (
+                        format!("{} (not found)", native.pid),
+                        none(),
+                        none(),
+                        none(),
+                    ),
+                },
+                None => (none(), none(), none(), none()),
+            },
+            ExecutionMode::Docker => match &task.docker {
+                Some(docker_info) => {
+                    let io = match &docker {
+                        Some(docker) => docker
+                            .get_stats(&docker_info.container_id)
+                            .await
+                            .map_or_else(|_| none(), |stats| stats.io.to_string()),
+                        None => none(),
+                    };
+                    (
+                        docker_info.container_id[..12].to_string(),
+                        format!("≤{}", docker_info.resource_limits.cpu),
+                        format!("≤{}", docker_info.resource_limits.memory),
+                        io,
+                    )
+                }
+                None => (none(), none(), none(), none()),
+            },
+        };
+        let trend = |spark: String, value: String| {
+            if spark.is_empty() {
+                value
+            } else {
+                format!("{spark} {value}")
+            }
+        };
+        let cpu = trend(sparkline(&history.cpu_percent(task_id)), cpu);
+        let memory = trend(sparkline(&history.memory_mb(task_id)), memory);
+        let io = trend(sparkline(&history.io_bytes_per_sec(task_id)), io);
+
+        table.add_row(vec![
+            Cell::new(task_id),
+            Cell::new(format!("{:?}", task.mode).to_lowercase()),
+            status_cell(&task.status),
+            Cell::new(format_relative(
+                (now - task.started_at).num_milliseconds() as f64 / 1000.0,
+            )),
+            Cell::new(target),
+            number_cell(cpu),
+            number_cell(memory),
+            number_cell(io),
+            Cell::new(truncate(&task.command.to_string(), command_width)),
+        ]);
+    }
+
+    info!("{table}");
+}
+
+/// Processes added with watch-pid, for the report
+fn print_watched(watched: &BTreeMap<String, WatchedProcess>, monitor: &SystemMonitor) {
+    if watched.is_empty() {
+        return;
+    }
+    let mut table = new_table(["Watched", "PID", "CPU", "Memory", "Since", "Command"]);
+    let command_width = (table_width(&table) as usize / 3).max(20);
+    for (name, process) in watched {
+        let (cpu, memory) = match monitor.usage(process.pid) {
+            Some(usage) => (
+                format!("{:.1}%", usage.cpu_percent),
+                format!("{}MB", usage.memory_kb / 1024),
+            ),
+            None => ("-".to_string(), "-".to_string()),
+        };
+        table.add_row(vec![
+            Cell::new(name),
+            Cell::new(process.pid),
+            number_cell(cpu),
+            number_cell(memory),
+            Cell::new(format_when(process.since)),
+            Cell::new(truncate(
+                process.command.as_deref().unwrap_or("-"),
+                command_width,
+            )),
+        ]);
+    }
+    info!("\n{table}");
+}
+
+/// Print a task's context bundle, trimmed to `max_tokens`
+async fn context_bundle(
+    query: &str,
+    max_tokens: usize,
+    json: bool,
+    registry_path: &str,
+) -> Result<()> {
+    let mut registry = RegistryManager::new(registry_path);
+    registry.load()?;
+    registry.load_history()?;
+
+    let task_id = registry
+        .resolve_task_id(query)?
+        .unwrap_or_else(|| query.to_string());
+    let Some(mut bundle) = ContextBundle::build(registry.registry(), &task_id, chrono::Utc::now())
+    else {
+        bail!("Task {} not found", task_id);
+    };
+
+    bundle.policy_violation = policy::check_command(&bundle.task.command)
+        .err()
+        .map(|e| e.to_string());
+    if let Some(native) = &bundle.task.native {
+        if !bundle.task.status.is_terminal() {
+            let mut monitor = SystemMonitor::new();
+            monitor.refresh(&[native.pid]).await;
+            if let Some(usage) = monitor.usage(native.pid) {
+                bundle.resources.cpu_percent = Some(usage.cpu_percent);
+                bundle.resources.memory_kb = Some(usage.memory_kb);
+            }
+        }
+    }
+
+    // Meant for piping into another tool: printed even with -q
+    let text = if json {
+        bundle.fit(max_tokens, |b| {
+            serde_json::to_string_pretty(b).unwrap_or_default()
+        })
+    } else {
+        bundle.fit(max_tokens, ContextBundle::to_markdown)
+    };
+    println!("{}", text.trim_end());
+    Ok(())
+}
+
+/// Show resource usage report
+async fn show_report(registry_path: &str, filter: Option<&Filter>) -> Result<()> {
+    info!("📊 Resource Usage Report");
+    info!("========================\n");
+
+    let mut registry = RegistryManager::new(registry_path);
+    registry.load()?;
+    registry.load_history()?;
+
+    let mut active = match filter {
+        Some(filter) => {
+            let now = chrono::Utc::now();
+            let matching: Vec<_> = registry
+                .registry()
+                .tasks
+                .iter()
+                .filter(|(id, task)| filter.matches(id, task, now))
+                .collect();
+            if matching.is_empty() && !json_output() {
+                info!("✅ No tasks match '{}'", filter);
+                return Ok(());
+            }
+            matching
+        }
+        None => registry.active_tasks(),
+    };
+    if json_output() {
+        return print_json(&Report {
+            stats: registry.stats(),
+            cost: registry.total_cost(),
+            tasks: active
+                .into_iter()
+                .map(|(id, task)| (id.clone(), task.clone()))
+                .collect(),
+            watched: match filter {
+                Some(_) => BTreeMap::new(),
+                None => registry.registry().watched.clone(),
+            },
+        });
+    }
+    // One sample of every process the report shows
+    let watched = &registry.registry().watched;
+    let mut pids: Vec<i32> = active
+        .iter()
+        .filter_map(|(_, task)| task.native.as_ref().map(|native| native.pid))
+        .collect();
+    if filter.is_none() {
+        pids.extend(watched.values().map(|process| process.pid));
+    }
+    let mut monitor = SystemMonitor::new();
+    monitor.refresh(&pids).await;
+    if active.is_empty() {
+        info!("✅ No tasks currently running");
+    } else {
+        active.sort_by(|a, b| a.0.cmp(b.0));
+        let history = ResourceHistory::load(&samples::samples_path(Path::new(registry_path)))
+            .unwrap_or_default();
+        print_task_table(active, &monitor, &history).await;
+    }
+    if filter.is_none() {
+        print_watched(watched, &monitor);
+    }
+
+    let thermal = ThermalStatus::sample();
+    if let Some(summary) = thermal.summary() {
+        info!("\n🌡️  {}", summary);
+    }
+    if thermal.is_throttling() {
+        warn!("🔥 CPU is at its thermal limit; expect tasks to run slower");
+    }
+
+    Ok(())
+}
+
+/// Print the tasks `query` selects
+fn list_tasks(query: &TaskQuery, registry_path: &str) -> Result<()> {
+    let mut registry = RegistryManager::new(registry_path);
+    registry.load()?;
+    registry.load_history()?;
+
+    let now = chrono::Utc::now();
+    let tasks = registry.registry().select(query, now);
+
+    if json_output() {
+        let entries: Vec<TaskEntry> = tasks
+            .into_iter()
+            .map(|(id, task)| TaskEntry {
+                task_id: id.clone(),
+                task: task.clone(),
+                alive: None,
+            })
+            .collect();
+        return print_json(&entries);
+    }
+    if tasks.is_empty() {
+        info!("✅ No tasks match");
+        return Ok(());
+    }
+
+    let mut table = new_table(["Task", "Status", "Started", "Duration", "CPU", "Command"]);
+    let command_width = (table_width(&table) as usize / 3).max(20);
+    for (task_id, task) in tasks {
+        let ran = clock::secs_between(task.started_at, task.completed_at.unwrap_or(now));
+        table.add_row(vec![
+            Cell::new(task_id),
+            status_cell(&task.status),
+            Cell::new(format_when(task.started_at)),
+            number_cell(format_duration(ran)),
+            number_cell(task.cpu_seconds.map_or("-".to_string(), format_duration)),
+            Cell::new(truncate(&task.command.to_string(), command_width)),
+        ]);
+    }
+    info!("{table}");
+    Ok(())
+}
+
+/// Print the lifecycle of `query` as recorded in the journal, including
+/// tasks archived or cleaned up since
+fn task_history(query: &str, registry_path: &str) -> Result<()> {
+    let mut registry = RegistryManager::new(registry_path);
+    registry.load()?;
+    registry.load_history()?;
+    // Cleaned up tasks are only in the journal, so take unknown IDs as given
+    let task_id = registry
+        .resolve_task_id(query)?
+        .unwrap_or_else(|| query.to_string());
+
+    let path = registry.events_path();
+    let (transitions, skipped) = lifecycle::read(&path, &task_id)?;
+    if skipped > 0 {
+        warn!(
+            "⚠️  Skipped {} unreadable lines of {}",
+            skipped,
+            path.display()
+        );
+    }
+    if json_output() {
+        return print_json(&serde_json::json!({
+            "task_id": task_id,
+            "transitions": transitions,
+        }));
+    }
+    if transitions.is_empty() {
+        bail!("No history recorded for task {}", task_id);
+    }
+
+    info!("📜 Task {}: {} changes", task_id, transitions.len());
+    info!();
+    let mut table = new_table(["When", "Change", "Status", "By", "Reason"]);
+    for transition in &transitions {
+        let status = match (&transition.from, &transition.to) {
+            (Some(from), Some(to)) => format!("{from} → {to}"),
+            (None, Some(to)) => to.to_string(),
+            (Some(from), None) => format!("{from} → (removed)"),
+            (None, None) => "-".to_string(),
+        };
+        let by = match &transition.user {
+            Some(user) => format!("{} ({})", transition.actor, user),
+            None => transition.actor.to_string(),
+        };
+        table.add_row(vec![
+            Cell::new(format_time(transition.at, "%Y-%m-%d %H:%M:%S")),
+            Cell::new(transition.change),
+            Cell::new(status),
+            Cell::new(by),
+            Cell::new(transition.reason.as_deref().unwrap_or("-")),
+        ]);
+    }
+    info!("{table}");
+    Ok(())
+}
+
+/// Longest pause between two events when replaying at a `--speed`
+const MAX_REPLAY_PAUSE: Duration = Duration::from_secs(5);
+
+/// Print the timeline of a window of the lifecycle journal at `path`, paced at
+/// `speed` times real time if given
+async fn replay(
+    path: &Path,
+    since: Option<chrono::DateTime<chrono::Utc>>,
+    until: Option<chrono::DateTime<chrono::Utc>>,
+    speed: Option<f64>,
+) -> Result<()> {
+    let (lines, skipped) = lifecycle::read_all(path)?;
+    if skipped > 0 {
+        warn!(
+            "⚠️  Skipped {} unreadable lines of {}",
+            skipped,
+            path.display()
+        );
+    }
+    let timeline = Timeline::replay(lines, since, until);
+    if json_output() {
+        return print_json(&timeline);
+    }
+    let (Some(start), Some(end)) = (timeline.start, timeline.end) else {
+        info!("✅ Nothing happened in this window");
+        return Ok(());
+    };
+    info!(
+        "🎞️  {} events from {} to {}",
+        timeline.entries.len(),
+        format_time(start, "%Y-%m-%d %H:%M:%S"),
+        format_time(end, "%Y-%m-%d %H:%M:%S")
+    );
+    info!();
+
+    let mut previous = timeline
+        .entries
+        .first()
+        .map_or(start, |entry| entry.line.at());
+    for entry in &timeline.entries {
+        if let Some(speed) = speed {
+            let gap = (entry.line.at() - previous).to_std().unwrap_or_default();
+            sleep(gap.div_f64(speed).min(MAX_REPLAY_PAUSE)).await;
+        }
+        previous = entry.line.at();
+        let description = match &entry.line {
+            JournalLine::Event(EventRecord {
+                event: WatchdogEvent::CheckCompleted { stats },
+                ..
+            }) => format!("Registry: {}", counts_line(stats)),
+            _ => entry.description.clone(),
+        };
+        let offset = entry.offset_secs.max(0);
+        info!(
+            "  +{:02}:{:02}:{:02}  {}  {} {}",
+            offset / 3600,
+            offset / 60 % 60,
+            offset % 60,
+            format_time(entry.line.at(), "%H:%M:%S"),
+            line_icon(&entry.line),
+            description
+        );
+    }
+
+    if timeline.tasks.is_empty() {
+        return Ok(());
+    }
+    let counts: Vec<String> = timeline
+        .status_counts()
+        .into_iter()
+        .map(|(status, count)| format!("{count} {status}"))
+        .collect();
+    info!(
+        "\n📊 At {}: {}",
+        format_time(end, "%H:%M:%S"),
+        counts.join(", ")
+    );
+    let mut table = new_table(["Task", "Status", "Last event", "Events"]);
+    for (task_id, state) in &timeline.tasks {
+        table.add_row(vec![
+            Cell::new(task_id),
+            state
+                .status
+                .as_ref()
+                .map_or_else(|| Cell::new("registered"), status_cell),
+            Cell::new(
+                state
+                    .last_event
+                    .map_or("-".to_string(), |at| format_time(at, "%H:%M:%S")),
+            ),
+            number_cell(state.events),
+        ]);
+    }
+    info!("{table}");
+    Ok(())
+}
+
+/// The non-zero counts of `stats`, e.g. `3 running, 1 failed`
+fn counts_line(stats: &RegistryStats) -> String {
+    let counts = [
+        (stats.queued, "queued"),
+        (stats.starting, "starting"),
+        (stats.running, "running"),
+        (stats.completed, "completed"),
+        (stats.failed, "failed"),
+        (stats.killed, "killed"),
+        (stats.timed_out, "timed out"),
+        (stats.unknown, "unknown"),
+    ];
+    let line: Vec<String> = counts
+        .iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, name)| format!("{count} {name}"))
+        .collect();
+    if line.is_empty() {
+        "no tasks".to_string()
+    } else {
+        line.join(", ")
+    }
+}
+
+/// Marker for a line of a replayed timeline
+fn line_icon(line: &JournalLine) -> &'static str {
+    let transition = match line {
+        JournalLine::Transition(transition) => transition,
+        JournalLine::Event(record) => return event_icon(&record.event),
+    };
+    match transition.change {
+        Change::Registered => "📝",
+        Change::Started => "🚀",
+        Change::Restarted => "♻️ ",
+        Change::Completed => "🏁",
+        Change::Failed => "💀",
+        Change::Cancelled => "🚫",
+        Change::Killed => "🔪",
+        Change::TimedOut => "⏰",
+        Change::Lost => "❓",
+        Change::CleanedUp => "🧹",
+    }
+}
+
+/// Marker for an event in a replayed timeline
+fn event_icon(event: &WatchdogEvent) -> &'static str {
+    use WatchdogEvent::*;
+    match event {
+        TaskRegistered { .. } => "📝",
+        TaskStarted { .. } => "🚀",
+        TaskCompleted { .. } => "🏁",
+        TaskDied { .. } => "💀",
+        TimedOut { .. } => "⏰",
+        TaskReady { .. } => "✅",
+        TaskRestarted { .. } => "♻️ ",
+        DependencyFailed { .. } => "⛔",
+        ZombieDetected { .. } => "🧟",
+        TaskUnknown { .. } => "❓",
+        ThresholdExceeded { .. } => "📈",
+        LimitExceeded { .. } => "🧱",
+        DurationAnomaly { .. } => "🐢",
+        PortClosed { .. } => "🔌",
+        WatchedProcessExited { .. } => "👁️ ",
+        CheckCompleted { .. } => "📊",
+    }
+}
+
+/// Run a chaos soak test; fails unless every workload ended as planned
+#[cfg(unix)]
+async fn run_chaos(
+    spawn: usize,
+    disrupt: bool,
+    duration_secs: u64,
+    seed: Option<u64>,
+    origin: TaskOrigin,
+    registry_path: &Path,
+) -> Result<()> {
+    if duration_secs < chaos::MIN_DURATION_SECS {
+        bail!("A chaos run needs at least {}s", chaos::MIN_DURATION_SECS);
+    }
+    let duration = Duration::from_secs(duration_secs);
+    let seed = seed.unwrap_or_else(|| chrono::Utc::now().timestamp_micros() as u64);
+    let run = Local::now().format("%H%M%S").to_string();
+    let workloads = chaos::plan(&run, spawn, duration, disrupt, seed);
+    info!(
+        "🌪️  Chaos run {}: {} workloads over {} (--seed {})",
+        run,
+        workloads.len(),
+        format_duration(duration_secs as f64),
+        seed
+    );
+
+    let outcomes = chaos::run(
+        registry_path,
+        &run,
+        workloads,
+        duration,
+        Duration::from_secs(1),
+        origin,
+    )
+    .await?;
+    info!();
+    chaos::print_outcomes(&outcomes);
+    let failed = outcomes.iter().filter(|outcome| !outcome.passed()).count();
+    if failed > 0 {
+        bail!(
+            "{} of {} workloads were not recorded as expected",
+            failed,
+            outcomes.len()
+        );
+    }
+    info!("✅ Every workload was detected and recorded");
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn run_chaos(
+    _spawn: usize,
+    _disrupt: bool,
+    _duration_secs: u64,
+    _seed: Option<u64>,
+    _origin: TaskOrigin,
+    _registry_path: &Path,
+) -> Result<()> {
+    bail!("chaos is only supported on Unix")
+}
+
+/// Print queued/starting/unknown/timed-out counts, only when non-zero
+fn print_in_between(stats: &RegistryStats) {
+    if stats.queued > 0 {
+        info!("   Queued: {}", stats.queued);
+    }
+    if stats.starting > 0 {
+        info!("   Starting: {}", stats.starting);
+    }
+    if stats.unknown > 0 {
+        info!("   Unknown: {}", stats.unknown);
+    }
+    if stats.timed_out > 0 {
+        info!("   Timed out: {}", stats.timed_out);
+    }
+}
+
+/// Show registry statistics
+async fn show_stats(
+    group_by: Option<&str>,
+    per_day: bool,
+    group: Option<&str>,
+    registry_path: &str,
+) -> Result<()> {
+    let mut registry = RegistryManager::new(registry_path);
+    registry.load()?;
+    registry.load_history()?;
+    // With --group everything below, health included, is about its tasks
+    let scoped = group.map(|group| {
+        let filter = Filter::group(group);
+        let now = chrono::Utc::now();
+        let mut scoped = registry.registry().clone();
+        scoped
+            .tasks
+            .retain(|task_id, task| filter.matches(task_id, task, now));
+        scoped
+    });
+    let tasks = scoped.as_ref().unwrap_or(registry.registry());
+
+    if group_by.is_some() || per_day {
+        if json_output() {
+            return print_json(&tasks.stats_grouped(group_by, per_day));
+        }
+        return show_group_stats(tasks, group_by, per_day);
+    }
+
+    let stats = tasks.stats();
+    let cpu: f64 = tasks
+        .stats_grouped(None, false)
+        .values()
+        .map(|g| g.cpu_seconds)
+        .sum();
+    let cost = tasks.total_cost();
+    if json_output() {
+        return print_json(&serde_json::json!({
+            "group": group,
+            "stats": stats,
+            "cost": cost,
+            "cpu_seconds": cpu,
+        }));
+    }
+
+    match group {
+        Some(group) => info!("📈 Registry Statistics: group {}", group),
+        None => info!("📈 Registry Statistics"),
+    }
+    info!("=====================\n");
+
+    let mut table = new_table(["Status", "Tasks"]);
+    table.add_row(vec![
+        status_cell(&TaskStatus::Running),
+        number_cell(stats.running),
+    ]);
+    table.add_row(vec![
+        status_cell(&TaskStatus::Completed),
+        number_cell(stats.completed),
+    ]);
+    table.add_row(vec![
+        status_cell(&TaskStatus::Failed),
+        number_cell(stats.failed),
+    ]);
+    table.add_row(vec![
+        status_cell(&TaskStatus::Killed),
+        number_cell(stats.killed),
+    ]);
+    // In-between states only appear when something is in them
+    for (status, count) in [
+        (TaskStatus::Queued, stats.queued),
+        (TaskStatus::Starting, stats.starting),
+        (TaskStatus::Unknown, stats.unknown),
+        (TaskStatus::TimedOut, stats.timed_out),
+    ] {
+        if count > 0 {
+            table.add_row(vec![status_cell(&status), number_cell(count)]);
+        }
+    }
+    table.add_row(vec![Cell::new("total"), number_cell(stats.total)]);
+
+    info!("{table}");
+
+    if cpu > 0.0 {
+        info!("\n⚙️  CPU time: {}", format_duration(cpu));
+    }
+
+    if cost != TaskCost::default() {
+        info!(
+            "\n💰 Spend: ${:.2} ({} tokens in, {} out)",
+            cost.usd, cost.input_tokens, cost.output_tokens
+        );
+    }
+
+    let throttling = ThermalStatus::sample().is_throttling();
+    print_health(&assess_health(tasks, Path::new(registry_path), throttling));
+
+    Ok(())
+}
+
+/// Score the project's health, gathering signals from outside the registry
+fn assess_health(tasks: &ProcessRegistry, registry_path: &Path, throttling: bool) -> Health {
+    let hour_ago = chrono::Utc::now() - chrono::Duration::hours(1);
+    let recent_daemon_crashes = crash::recent_crashes(&crash::crash_dir_for(registry_path), 10)
+        .map(|crashes| {
+            crashes
+                .iter()
+                .filter(|(_, report)| report.timestamp >= hour_ago)
+                .count()
+        })
+        .unwrap_or(0);
+    let signals = health::Signals {
+        recent_daemon_crashes,
+        throttling,
+    };
+    Health::assess(tasks, signals, chrono::Utc::now())
+}
+
+fn print_health(health: &Health) {
+    let icon = match health.status() {
+        HealthStatus::Healthy => "💚",
+        HealthStatus::Degraded => "💛",
+        HealthStatus::Unhealthy => "💔",
+    };
+    info!(
+        "\n{} Health: {}/100 ({})",
+        icon,
+        health.score,
+        health.status()
+    );
+    for reason in &health.reasons {
+        info!("   - {}", reason);
+    }
+}
+
+/// `stats --group-by/--per-day`: one row per label value and/or day
+fn show_group_stats(tasks: &ProcessRegistry, label: Option<&str>, per_day: bool) -> Result<()> {
+    let group = match (label, per_day) {
+        (Some(label), true) => format!("{label} / day"),
+        (Some(label), false) => label.to_string(),
+        (None, _) => "day".to_string(),
+    };
+    info!("📈 Registry Statistics by {}", group);
+    info!("=====================\n");
+
+    let mut table = new_table([
+        group.as_str(),
+        "Tasks",
+        "Running",
+        "Failed",
+        "CPU time",
+        "Tokens",
+        "Spend",
+    ]);
+    for (group, stats) in tasks.stats_grouped(label, per_day) {
+        table.add_row(vec![
+            Cell::new(group),
+            number_cell(stats.tasks),
+            number_cell(stats.running),
+            number_cell(stats.failed),
+            number_cell(format_duration(stats.cpu_seconds)),
+            number_cell(stats.cost.total_tokens()),
+            number_cell(format!("${:.2}", stats.cost.usd)),
+        ]);
+    }
+    info!("{table}");
+
+    Ok(())
+}
+
+/// Add reported token/spend counters to a task
+async fn report_cost(query: &str, cost: TaskCost, registry_path: &str) -> Result<()> {
+    let mut registry = RegistryManager::new(registry_path);
+    registry.load()?;
+
+    let Some(task_id) = registry.resolve_task_id(query)? else {
+        bail!("Task {} not found", query);
+    };
+    let total = registry.add_cost(&task_id, &cost)?;
+    info!(
+        "💰 Task {}: ${:.2} ({} tokens in, {} out)",
+        task_id, total.usd, total.input_tokens, total.output_tokens
+    );
+
+    Ok(())
+}
+
+/// Register whatever listens on `port` as a running native task
+fn adopt_port(
+    task_id: Option<String>,
+    port: u16,
+    description: Option<String>,
+    origin: TaskOrigin,
+    registry_path: &str,
+) -> Result<()> {
+    let Some(pid) = ports::find_listener(port) else {
+        bail!(
+            "No process found listening on port {} (other users' processes are not visible)",
+            port
+        );
+    };
+    let details = Details {
+        description,
+        expected_outcome: None,
+        labels: BTreeMap::new(),
+        metadata: serde_json::Value::Null,
+        done_file: None,
+        ..Default::default()
+    };
+    let mut task = adopted_task(pid, details, origin)?;
+    task.port = Some(port);
+    let command = task.command.to_string();
+
+    let task_id = task_id.unwrap_or_else(|| format!("port-{port}"));
+    let mut registry = RegistryManager::new(registry_path);
+    registry.load()?;
+    registry.upsert_task(task_id.clone(), task)?;
+    info!(
+        "✅ Task {} adopted: PID {} serving port {} ({})",
+        task_id, pid, port, command
+    );
+    Ok(())
+}
+
+/// A running task for the existing process `pid`, with its command line
+/// and working directory read from the system
+fn adopted_task(pid: i32, details: Details, origin: TaskOrigin) -> Result<TaskInfo> {
+    let (argv, cwd) = ProcessManager::command_argv(pid)
+        .with_context(|| format!("PID {pid} exited while being adopted"))?;
+
+    let mut task = new_task(TaskCommand::argv(argv), details, vec![], None, origin);
+    // Its output is in the pane it was started from, not the adopter's
+    if let Some(origin) = &mut task.origin {
+        origin.tmux = tmux::pane_of(pid);
+    }
+    if cwd.is_some() {
+        task.repo_root = cwd.as_deref().and_then(ProcessManager::repo_root);
+        task.cwd = cwd;
+    }
+    task.native = Some(NativeTask {
+        pid,
+        pgid: ProcessManager::process_group(pid).unwrap_or(pid),
+        start_time: ProcessManager::get_start_time(pid)?,
+        env_tag: None,
+        job: None,
+    });
+    Ok(task)
+}
+
+/// List processes AI tools left running without registering them, and
+/// with `adopt` register each as a task
+fn discover_tasks(
+    min_age_secs: u64,
+    adopt: bool,
+    origin: TaskOrigin,
+    registry_path: &str,
+) -> Result<()> {
+    let mut registry = RegistryManager::new(registry_path);
+    registry.load()?;
+    let registered: Vec<i32> = registry
+        .active_tasks()
+        .iter()
+        .filter_map(|(_, task)| task.native.as_ref().map(|n| n.pid))
+        .chain(registry.registry().watched.values().map(|w| w.pid))
+        .collect();
+
+    let processes = discover::scan();
+    let candidates = discover::find_candidates(&processes, &registered, min_age_secs);
+    if candidates.is_empty() {
+        info!("✅ No unregistered work found under AI tools");
+        return Ok(());
+    }
+
+    let mut table = new_table(["PID", "Started by", "Running", "Command"]);
+    let command_width = (table_width(&table) as usize / 2).max(20);
+    for candidate in &candidates {
+        table.add_row(vec![
+            Cell::new(candidate.pid),
+            Cell::new(format!("{} ({})", candidate.tool, candidate.tool_pid)),
+            number_cell(format_duration(candidate.run_secs as f64)),
+            Cell::new(truncate(
+                ProcessManager::command_line(candidate.pid)
+                    .as_deref()
+                    .unwrap_or("-"),
+                command_width,
+            )),
+        ]);
+    }
+    info!("\n{table}");
+
+    if !adopt {
+        info!(
+            "🔎 {} unregistered process(es); adopt them with `discover --adopt`, or one with `watch-pid <PID>`",
+            candidates.len()
+        );
+        return Ok(());
+    }
+    for candidate in candidates {
+        let details = Details {
+            description: None,
+            expected_outcome: None,
+            labels: [("discovered_by".to_string(), candidate.tool.to_string())].into(),
+            metadata: serde_json::Value::Null,
+            done_file: None,
+            ..Default::default()
+        };
+        let task_id = format!("{}-{}", candidate.tool, candidate.pid);
+        match adopted_task(candidate.pid, details, origin.clone()) {
+            Ok(task) => {
+                registry.upsert_task(task_id.clone(), task)?;
+                info!("✅ Task {} adopted: PID {}", task_id, candidate.pid);
+            }
+            Err(e) => warn!("⚠️  Could not adopt PID {}: {}", candidate.pid, e),
+        }
+    }
+    Ok(())
+}
+
+/// Switch tmux to the pane `query`'s task was started from
+fn goto_task(query: &str, registry_path: &str) -> Result<()> {
+    let mut registry = RegistryManager::new(registry_path);
+    registry.load()?;
+    registry.load_history()?;
+    let task_id = registry
+        .resolve_task_id(query)?
+        .unwrap_or_else(|| query.to_string());
+    let Some(task) = registry.get_task(&task_id) else {
+        bail!("Task {} not found", task_id);
+    };
+    let Some(pane) = task.origin.as_ref().and_then(|o| o.tmux.as_ref()) else {
+        bail!("Task {} wasn't started from tmux", task_id);
+    };
+    let now = tmux::focus(pane)?;
+    verbose!(
+        "🪟 Task {} is in tmux pane {} ({})",
+        task_id,
+        now,
+        now.pane_id
+    );
+    Ok(())
+}
+
+/// Print a task's log, then with `follow` what it appends until the task
+/// finishes
+async fn show_logs(
+    query: &str,
+    follow: bool,
+    tail: Option<usize>,
+    registry_path: &str,
+) -> Result<()> {
+    use futures_util::stream::StreamExt;
+    use std::io::Write;
+
+    let mut registry = RegistryManager::new(registry_path);
+    registry.load()?;
+    registry.load_history()?;
+    let task_id = registry
+        .resolve_task_id(query)?
+        .unwrap_or_else(|| query.to_string());
+    let Some(task) = registry.get_task(&task_id) else {
+        bail!("Task {} not found", task_id);
+    };
+    let mut stdout = std::io::stdout();
+    // Straight from the container while it exists, then the copy the
+    // daemon saved when it stopped
+    if let (None, Some(container)) = (&task.log_file, &task.docker) {
+        if let Some(docker) = DockerManager::new() {
+            if docker.exit_state(&container.container_id).await.is_ok() {
+                let mut chunks = docker.logs(&container.container_id, follow, tail);
+                while let Some(chunk) = chunks.next().await {
+                    stdout.write_all(&chunk?)?;
+                    stdout.flush()?;
+                }
+                return Ok(());
+            }
+        }
+    }
+    let path = task
+        .log_file
+        .clone()
+        .unwrap_or_else(|| logs::log_path(Path::new(registry_path), &task_id));
+    if !path.exists() {
+        // Started by the daemon without a log: it has the end in memory
+        if let Some(output) = capture::request(Path::new(registry_path), &task_id, tail).await {
+            if follow {
+                warn!(
+                    "⚠️  Task {} has no log to follow; showing its recent output",
+                    task_id
+                );
+            }
+            stdout.write_all(output.as_bytes())?;
+            return Ok(());
+        }
+        bail!(
+            "Task {} has no log (only spawned tasks and stopped containers are logged)",
+            task_id
+        );
+    }
+
+    let mut offset = match tail {
+        Some(lines) => {
+            stdout.write_all(logs::tail(&path, lines)?.as_bytes())?;
+            std::fs::metadata(&path)?.len()
+        }
+        None => 0,
+    };
+    loop {
+        let (new, end) = logs::read_from(&path, offset)?;
+        stdout.write_all(&new)?;
+        stdout.flush()?;
+        offset = end;
+        if !follow {
+            return Ok(());
+        }
+        registry.load()?;
+        let finished = registry
+            .get_task(&task_id)
+            .is_none_or(|task| task.status.is_terminal());
+        if finished {
+            // One last read for what was written before it finished
+            let (new, _) = logs::read_from(&path, offset)?;
+            stdout.write_all(&new)?;
+            return Ok(());
+        }
+        sleep(Duration::from_millis(500)).await;
+    }
+}
+
+/// Show how a finished (or unfinished) task ended, for post-mortems
+async fn inspect_task(query: &str, tail: usize, registry_path: &Path) -> Result<()> {
+    let mut registry = RegistryManager::new(registry_path);
+    registry.load()?;
+    registry.load_history()?;
+    let task_id = registry
+        .resolve_task_id(query)?
+        .unwrap_or_else(|| query.to_string());
+    let Some(task) = registry.get_task(&task_id) else {
+        bail!("Task {} not found", task_id);
+    };
+
+    // What Docker still knows of the container, if it wasn't removed
+    let container = match (&task.docker, DockerManager::new()) {
+        (Some(info), Some(docker)) => docker.exit_state(&info.container_id).await.ok(),
+        _ => None,
+    };
+    let exit_code = container
+        .as_ref()
+        .and_then(|c| c.exit_code)
+        .or(task.docker.as_ref().and_then(|d| d.exit_code))
+        .or(task.result.as_ref().and_then(|r| r.exit_code));
+    let log_path = task
+        .log_file
+        .clone()
+        .unwrap_or_else(|| logs::log_path(registry_path, &task_id));
+    let output = logs::tail(&log_path, tail).ok();
+
+    if json_output() {
+        return print_json(&serde_json::json!({
+            "task_id": task_id,
+            "status": task.status,
+            "started_at": task.started_at,
+            "completed_at": task.completed_at,
+            "exit_code": exit_code,
+            "kill_reason": task.kill_reason,
+            "failure_reason": task.failure_reason,
+            "triage_hint": task.triage_hint,
+            "coredump": task.coredump,
+            "failure_excerpt": task.failure_excerpt,
+            "result": task.result,
+            "docker": task.docker,
+            "container": container,
+            "log_file": output.as_ref().map(|_| &log_path),
+            "output": output,
+        }));
+    }
+
+    info!("🔍 Task {} ({})", task_id, task.status);
+    info!("   Command: {}", task.command);
+    info!("   Started: {}", format_when(task.started_at));
+    if let Some(at) = task.completed_at {
+        info!(
+            "   Finished: {}, after {}",
+            format_when(at),
+            format_duration(clock::secs_between(task.started_at, at))
+        );
+    }
+    if let Some(code) = exit_code {
+        info!("   Exit code: {}", code);
+    }
+    if let Some(reason) = &task.kill_reason {
+        info!("   Kill reason: {}", reason);
+    }
+    if let Some(reason) = &task.failure_reason {
+        info!("   Failure reason: {}", reason);
+    }
+    if let Some(hint) = &task.triage_hint {
+        info!("   💡 Likely cause: {}", hint);
+    }
+    if let Some(dump) = &task.coredump {
+        print_coredump(dump);
+    }
+    if let Some(excerpt) = &task.failure_excerpt {
+        print_failure_excerpt(excerpt);
+    }
+    if let Some(summary) = task.result.as_ref().and_then(|r| r.summary.as_ref()) {
+        info!("   Result: {}", summary);
+    }
+    if let Some(info) = &task.docker {
+        info!(
+            "   Container: {} ({})",
+            info.container_name,
+            &info.container_id[..12.min(info.container_id.len())]
+        );
+        if let Some(image) = &info.image {
+            info!("   Image: {}", image);
+        }
+        match &container {
+            Some(state) if state.running => info!("   State: running"),
+            Some(state) => {
+                let mut line = "exited".to_string();
+                if let Some(at) = state.finished_at {
+                    line.push_str(&format!(" {}", format_when(at)));
+                }
+                if state.oom_killed {
+                    line.push_str(", OOM-killed");
+                }
+                info!("   State: {}", line);
+                if let Some(error) = &state.error {
+                    info!("   Docker error: {}", error);
+                }
+                info!(
+                    "   Kept for inspection: `docker inspect {}`",
+                    info.container_name
+                );
+            }
+            None if DockerManager::new().is_none() => {
+                info!("   State: unknown (Docker not available)")
+            }
+            None => info!("   State: removed"),
+        }
+    }
+    match output {
+        Some(output) if !output.is_empty() => {
+            info!("   Output ({}):", log_path.display());
+            for line in output.lines() {
+                info!("   │ {}", line);
+            }
+        }
+        _ => info!("   Output: none saved"),
+    }
+    Ok(())
+}
+
+/// Start watching an arbitrary process
+fn watch_pid(pid: i32, name: Option<String>, registry_path: &str) -> Result<()> {
+    if !ProcessManager::is_alive(pid) {
+        bail!("No process with PID {}", pid);
+    }
+    let process = WatchedProcess {
+        pid,
+        start_time: ProcessManager::get_start_time(pid)?,
+        since: chrono::Utc::now(),
+        command: ProcessManager::command_line(pid),
+    };
+    let name = name.unwrap_or_else(|| pid.to_string());
+
+    let mut registry = RegistryManager::new(registry_path);
+    registry.load()?;
+    registry.watch(name.clone(), process.clone())?;
+    info!(
+        "👁️  Watching {} (PID {}): {}",
+        name,
+        pid,
+        process.command.as_deref().unwrap_or("?")
+    );
+    Ok(())
+}
+
+/// Cleanup old tasks
+async fn cleanup_tasks(days: u64, filter: Option<&Filter>, registry_path: &str) -> Result<()> {
+    let mut registry = RegistryManager::new(registry_path);
+    registry.load()?;
+
+    match filter {
+        Some(filter) => info!(
+            "🧹 Cleaning up tasks older than {} days matching '{}'...",
+            days, filter
+        ),
+        None => info!("🧹 Cleaning up tasks older than {} days...", days),
+    }
+
+    let removed = registry.cleanup_old_tasks(days, filter)?;
+    let containers = remove_containers(removed.iter().map(|(_, task)| task)).await;
+    if json_output() {
+        return print_json(&serde_json::json!({
+            "removed": removed.len(),
+            "containers": containers,
+        }));
+    }
+
+    info!("✅ Removed {} old tasks", removed.len());
+    if !containers.is_empty() {
+        info!("🐳 Removed {} containers", containers.len());
+    }
+
+    Ok(())
+}
+
+/// Remove the containers of finished tasks, which are kept after exiting
+/// until their registry entries are cleaned up
+async fn prune_containers(filter: Option<&Filter>, registry_path: &str) -> Result<()> {
+    let mut registry = RegistryManager::new(registry_path);
+    registry.load()?;
+    registry.load_history()?;
+
+    let now = chrono::Utc::now();
+    let finished = registry
+        .registry()
+        .tasks
+        .iter()
+        .filter(|(task_id, task)| {
+            task.status.is_terminal() && filter.is_none_or(|f| f.matches(task_id, task, now))
+        })
+        .map(|(_, task)| task);
+    let containers = remove_containers(finished).await;
+    if json_output() {
+        return print_json(&serde_json::json!({ "containers": containers }));
+    }
+
+    info!("✅ Removed {} containers", containers.len());
+    Ok(())
+}
+
+/// Remove the containers of `tasks`, skipping (with a warning) any that
+/// can't be removed. Returns the names of those removed.
+async fn remove_containers<'a>(tasks: impl Iterator<Item = &'a TaskInfo>) -> Vec<String> {
+    let mut containers: Vec<&DockerTask> = tasks.filter_map(|task| task.docker.as_ref()).collect();
+    containers.sort_by(|a, b| a.container_id.cmp(&b.container_id));
+    containers.dedup_by(|a, b| a.container_id == b.container_id);
+    if containers.is_empty() {
+        return Vec::new();
+    }
+    let Some(docker) = DockerManager::new() else {
+        warn!(
+            "⚠️  Docker not available: {} containers left in place",
+            containers.len()
+        );
+        return Vec::new();
+    };
+
+    let mut removed = Vec::new();
+    for container in containers {
+        match docker.remove_container(&container.container_id).await {
+            Ok(true) => {
+                verbose!("   Removed container {}", container.container_name);
+                removed.push(container.container_name.clone());
+            }
+            Ok(false) => {}
+            Err(e) => warn!("⚠️  {}: {:#}", container.container_name, e),
+        }
+    }
+    removed
+}
+
+/// Reservation and admission settings for `register`
+struct Admission {
+    reservation: Option<ResourceLimits>,
+    headroom: u8,
+    queue: bool,
+}
+
+/// Absolute path for `--done-file` (empty: the default next to the
+/// registry), with its directory created so the daemon can watch it
+fn resolve_done_file(path: &str, task_id: &str, registry_path: &Path) -> Result<PathBuf> {
+    let path = if path.is_empty() {
+        done_files::default_path(registry_path, task_id)
+    } else {
+        PathBuf::from(path)
+    };
+    let path = std::path::absolute(&path)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    Ok(path)
+}
+
+/// Parse `--metadata-json`
+fn parse_metadata(s: &str) -> Result<serde_json::Value> {
+    serde_json::from_str(s).context("--metadata-json must be valid JSON")
+}
+
+/// Template variables for commands run here, with the `--var` overrides
+fn template_vars(overrides: &[(String, String)]) -> Result<Vars> {
+    Ok(Vars::here(&std::env::current_dir()?, overrides.to_vec()))
+}
+
+/// Parse a `key=value` label
+fn parse_label(s: &str) -> Result<(String, String)> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.trim().to_string()))
+        }
+        _ => bail!("Labels must look like key=value, got '{}'", s),
+    }
+}
+
+/// Parse a `value=N` queue weight
+fn parse_weight(s: &str) -> Result<(String, u32)> {
+    let (value, weight) = parse_label(s)?;
+    match weight.parse::<u32>() {
+        Ok(weight) if weight > 0 => Ok((value, weight)),
+        _ => bail!("Weights must be positive integers, got '{}'", s),
+    }
+}
+
+fn parse_when(s: &str) -> Result<chrono::DateTime<chrono::Utc>> {
+    timeline::parse_when(s, chrono::Utc::now())
+}
+
+/// A replay speed like `10x` (or just `10`)
+fn parse_speed(s: &str) -> Result<f64> {
+    match s.strip_suffix('x').unwrap_or(s).parse::<f64>() {
+        Ok(speed) if speed.is_finite() && speed > 0.0 => Ok(speed),
+        _ => bail!("Invalid speed '{}' (e.g. 10x or 0.5x)", s),
+    }
+}
+
+fn parse_age(s: &str) -> Result<u64> {
+    match units::parse_duration(s) {
+        Some(secs) => Ok(secs as u64),
+        None => bail!("Invalid duration '{}' (e.g. 90s, 5m, 2h)", s),
+    }
+}
+
+/// `--grace`: a duration, fractions of a second allowed
+#[cfg(unix)]
+fn parse_grace(s: &str) -> Result<Duration> {
+    match units::parse_duration(s) {
+        Some(secs) => Ok(Duration::from_secs_f64(secs)),
+        None => bail!("Invalid duration '{}' (e.g. 0.5s, 5s, 1m)", s),
+    }
+}
+
+/// Register a new task with constitution rules
+async fn register_task(
+    task_id: &str,
+    command: TaskCommand,
+    details: Details,
+    rules: Option<String>,
+    admission: Admission,
+    origin: TaskOrigin,
+    registry_path: &str,
+) -> Result<()> {
+    let mut registry = RegistryManager::new(registry_path);
+    registry.load()?;
+
+    let constitution_rules = tasks::parse_rules(rules);
+
+    let mut task = new_task(
+        command,
+        details,
+        constitution_rules,
+        admission.reservation,
+        origin,
+    );
+
+    // With --queue, a hot machine defers new launches too
+    let hot = admission.queue && ThermalStatus::sample().is_throttling();
+    if hot {
+        task.status = TaskStatus::Queued;
+    }
+
+    let reservable = Resources::machine().minus_headroom(admission.headroom);
+    let status = registry.admit_task(task_id.to_string(), task, reservable, admission.queue)?;
+    if hot {
+        warn!("⏳ Task {} queued: CPU is thermally throttling", task_id);
+    } else if status == TaskStatus::Queued {
+        warn!(
+            "⏳ Task {} queued: its reservation does not fit right now",
+            task_id
+        );
+    }
+
+    info!(
+        "✅ Task {} registered with {} constitution rules",
+        task_id,
+        registry
+            .get_task(task_id)
+            .map(|t| t.constitution_rules.len())
+            .unwrap_or(0)
+    );
+    if json_output() {
+        if let Some(task) = registry.get_task(task_id) {
+            print_json(&TaskEntry {
+                task_id: task_id.to_string(),
+                task: task.clone(),
+                alive: None,
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Start `command` in a new process group and register it. In the
+/// foreground the task's status follows the process's exit, which is
+/// passed on as our own exit code; Ctrl-C kills the whole group.
+async fn spawn_task(
+    task_id: &str,
+    command: TaskCommand,
+    details: Details,
+    rules: Option<String>,
+    detach: bool,
+    origin: TaskOrigin,
+    registry_path: &str,
+) -> Result<()> {
+    let mut registry = RegistryManager::new(registry_path);
+    registry.load()?;
+    // Refuse before starting anything rather than orphan a process
+    if registry
+        .get_task(task_id)
+        .is_some_and(|task| !task.status.is_terminal())
+    {
+        bail!("Task {} is already registered and not finished", task_id);
+    }
+    if !details.depends_on.is_empty() {
+        readiness::wait_for(&mut registry, &details.depends_on).await?;
+    }
+
+    let constitution_rules = tasks::parse_rules(rules);
+    let mut task = new_task(command, details, constitution_rules, None, origin);
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let cli = Cli::parse();
+    let log_path = std::path::absolute(logs::log_path(Path::new(registry_path), task_id))?;
+    let log = logs::open(&log_path, registry.file_mode())?;
+    let mut child = tasks::command_for(task_id, &task);
+    if detach {
+        child
+            .stdin(Stdio::null())
+            .stdout(log.try_clone()?)
+            .stderr(log.try_clone()?);
+    } else {
+        child.stdout(Stdio::piped()).stderr(Stdio::piped());
+    }
+    let mut child = child
+        .spawn()
+        .with_context(|| format!("Failed to start `{}`", task.command))?;
+    let pid = child.id().context("Spawned process exited immediately")? as i32;
+    task.log_file = Some(log_path.clone());
+    let timeout = task.timeout_secs.map(Duration::from_secs);
 
-    match cli.command {
-        Commands::Run { interval, registry } => {
-            let validated_path = validate_registry_path(&registry)?;
-            run_watchdog(interval, &validated_path.to_string_lossy()).await?
-        }
-        Commands::Check { task_id, registry } => {
-            let validated_path = validate_registry_path(&registry)?;
-            check_task(&task_id, &validated_path.to_string_lossy()).await?
-        }
-        Commands::Kill { task_id, registry } => {
-            let validated_path = validate_registry_path(&registry)?;
-            kill_task(&task_id, &validated_path.to_string_lossy()).await?
+    let native = tasks::native_task(task_id, pid);
+    task.native = Some(native.clone());
+    if let Err(e) = registry.upsert_task(task_id.to_string(), task) {
+        let _ = ProcessManager::kill_task(&native);
+        return Err(e);
+    }
+
+    if detach {
+        info!("🚀 Task {} spawned: PID {} (detached)", task_id, pid);
+        return Ok(());
+    }
+    verbose!("🚀 Task {} spawned: PID {}", task_id, pid);
+
+    // Output goes to the terminal and the log
+    let mut copies = Vec::new();
+    if let Some(out) = child.stdout.take() {
+        copies.push(tokio::spawn(logs::tee(
+            out,
+            tokio::io::stdout(),
+            log.try_clone()?,
+        )));
+    }
+    if let Some(err) = child.stderr.take() {
+        copies.push(tokio::spawn(logs::tee(err, tokio::io::stderr(), log)));
+    }
+
+    let status = tokio::select! {
+        status = child.wait() => status?,
+        _ = tokio::signal::ctrl_c() => {
+            ProcessManager::kill_task(&native)?;
+            registry.mark_killed(task_id, "interrupted (Ctrl-C) in spawn")?;
+            warn!("🛑 Task {} interrupted", task_id);
+            std::process::exit(130);
         }
-        Commands::Rehydrate { registry } => {
-            let validated_path = validate_registry_path(&registry)?;
-            rehydrate(&validated_path.to_string_lossy()).await?
+        _ = async {
+            match timeout {
+                Some(timeout) => tokio::time::sleep(timeout).await,
+                None => std::future::pending().await,
+            }
+        } => {
+            tasks::time_out(&mut registry, task_id).await?;
+            // What coreutils `timeout` exits with
+            std::process::exit(124);
         }
-        Commands::Report { registry } => {
-            let validated_path = validate_registry_path(&registry)?;
-            show_report(&validated_path.to_string_lossy()).await?
+    };
+    // Children left running may hold the pipes open; don't wait on them
+    let _ = tokio::time::timeout(Duration::from_secs(1), async {
+        for copy in copies {
+            let _ = copy.await;
         }
-        Commands::Stats { registry } => {
-            let validated_path = validate_registry_path(&registry)?;
-            show_stats(&validated_path.to_string_lossy()).await?
+    })
+    .await;
+
+    // Killed by a signal: report it the way shells do
+    let code = status
+        .code()
+        .or_else(|| killed_by(&status).map(|(signal, _)| 128 + signal))
+        .unwrap_or(1);
+    if code == 0 {
+        registry.mark_complete(task_id)?;
+        info!("✅ Task {} completed", task_id);
+        return Ok(());
+    }
+    let output = logs::tail(&log_path, logs::TRIAGE_LINES).ok();
+    // A shell wrapper exits 128 + signal when the command it ran crashed
+    let crash_signal = match killed_by(&status) {
+        Some((signal, core_dumped)) => core_dumped.then_some(signal),
+        None => (code > 128 && coredump::dumps_core(code - 128)).then_some(code - 128),
+    };
+    let coredump = match (registry.get_task(task_id), crash_signal) {
+        (Some(task), Some(signal)) => coredump::collect(task, Some(signal)).await,
+        _ => None,
+    };
+    let hint = match &coredump {
+        Some(dump) => Some(coredump::hint(dump, output.as_deref())),
+        None => triage::hint(triage::Evidence {
+            exit_code: Some(code.into()),
+            output: output.as_deref(),
+            ..Default::default()
+        }),
+    };
+    let excerpt = logs::failure_excerpt(&log_path);
+    registry.with_transaction(|txn| {
+        txn.mark_failed(task_id);
+        if let Some(hint) = hint.clone() {
+            txn.set_triage_hint(task_id, hint);
         }
-        Commands::Cleanup { days, registry } => {
-            let validated_path = validate_registry_path(&registry)?;
-            cleanup_tasks(days, &validated_path.to_string_lossy()).await?
+        if let Some(dump) = coredump.clone() {
+            txn.set_coredump(task_id, dump);
         }
-        Commands::Register {
-            task_id,
-            command,
-            rules,
-            registry,
-        } => {
-            let validated_path = validate_registry_path(&registry)?;
-            register_task(&task_id, &command, rules, &validated_path.to_string_lossy()).await?
+        if let Some(excerpt) = excerpt {
+            txn.set_failure_excerpt(task_id, excerpt);
         }
+        Ok(())
+    })?;
+    warn!("❌ Task {} failed with exit code {}", task_id, code);
+    if let Some(hint) = hint {
+        warn!("💡 {}", hint);
     }
+    if let Some(command) = coredump.as_ref().and_then(|dump| dump.debug_command()) {
+        warn!("🔍 {}", command);
+    }
+    std::process::exit(code);
+}
 
-    Ok(())
+/// Signal that ended the process that exited with `status`, and whether it
+/// dumped core
+#[cfg(unix)]
+fn killed_by(status: &std::process::ExitStatus) -> Option<(i32, bool)> {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal().map(|signal| (signal, status.core_dumped()))
 }
 
-/// Main watchdog loop
-async fn run_watchdog(interval_secs: u64, registry_path: &str) -> Result<()> {
-    println!("🐕 Task Watchdog v{}", env!("CARGO_PKG_VERSION"));
-    println!("   Built with Rust for AI coding tools (Claude-tested)");
-    println!("   Check interval: {}s", interval_secs);
-    println!("   Registry: {}", registry_path);
-    println!("   Memory usage: {}KB", get_self_memory_kb());
-    println!();
-
-    // Initialize Docker if available
-    let docker = DockerManager::new();
-    if docker.is_some() {
-        println!("✅ Docker available");
-    } else {
-        println!("⚠️  Docker not available (native processes only)");
-    }
-    println!();
+#[cfg(not(unix))]
+fn killed_by(_status: &std::process::ExitStatus) -> Option<(i32, bool)> {
+    None
+}
 
+/// Bring up the tasks of a launch file: start what isn't running, in
+/// dependency order and once each task's dependencies are ready; replace
+/// tasks whose definition changed; stop tasks of the set the file no longer
+/// declares
+async fn up_tasks(
+    path: &Path,
+    vars: &Vars,
+    defaults: &launch::Defaults,
+    origin: TaskOrigin,
+    registry_path: &Path,
+) -> Result<()> {
+    let (set, mut file) = LaunchFile::load(path, vars)?;
+    file.apply_defaults(defaults);
+    let order = file.start_order()?;
     let mut registry = RegistryManager::new(registry_path);
+    registry.load()?;
 
-    loop {
-        let check_time = Local::now().format("%H:%M:%S");
-        println!("🔍 Watchdog check - {}", check_time);
-
-        // Load latest registry state
-        registry.load()?;
-
-        // Find orphans in native processes
-        let orphan_report = registry.find_orphans();
-
-        // Check Docker containers if available
-        if let Some(ref docker_client) = docker {
-            check_docker_tasks(&mut registry, docker_client).await?;
+    let mut stopped = Vec::new();
+    for task_id in set_members(&registry, &set) {
+        if !file.tasks.contains_key(&task_id) {
+            info!(task: &task_id; "🗑️  Task {} is no longer in {}", task_id, set);
+            tasks::kill(&mut registry, &task_id, &origin).await?;
+            stopped.push(task_id);
         }
+    }
 
-        // Report findings
-        if orphan_report.has_issues() {
-            println!("\n⚠️  Found {} issues:", orphan_report.total_issues());
-
-            if !orphan_report.dead_processes.is_empty() {
-                println!(
-                    "\n💀 Dead Processes ({}):",
-                    orphan_report.dead_processes.len()
-                );
-                for task_id in &orphan_report.dead_processes {
-                    if let Some(task) = registry.get_task(task_id) {
-                        println!("  {} - {}", task_id, task.command);
-                    }
-                    // Mark as failed
-                    registry.mark_failed(task_id)?;
-                }
+    let mut started = Vec::new();
+    let mut unchanged = Vec::new();
+    for task_id in &order {
+        let spec = &file.tasks[task_id];
+        let wanted = spec.to_task(&set, origin.clone())?;
+        if let Some(existing) = registry
+            .get_task(task_id)
+            .filter(|task| !task.status.is_terminal())
+        {
+            if spec.is_running_as(&wanted, existing) {
+                verbose!("✔️  Task {} is up to date", task_id);
+                unchanged.push(task_id.clone());
+                continue;
             }
-
-            if !orphan_report.zombie_processes.is_empty() {
-                println!(
-                    "\n🧟 Zombie Processes ({}):",
-                    orphan_report.zombie_processes.len()
+            if existing.labels.get(SET_LABEL) != Some(&set) {
+                bail!(
+                    "Task {} is already registered outside {} and not finished",
+                    task_id,
+                    set
                 );
-                for task_id in &orphan_report.zombie_processes {
-                    if let Some(task) = registry.get_task(task_id) {
-                        println!("  {} - {}", task_id, task.command);
-
-                        // Kill zombie
-                        match &task.mode {
-                            ExecutionMode::Native => {
-                                if let Some(native) = &task.native {
-                                    let _ = ProcessManager::kill_process_group(native.pgid);
-                                }
-                            }
-                            ExecutionMode::Docker => {
-                                if let (Some(docker_client), Some(docker_info)) =
-                                    (&docker, &task.docker)
-                                {
-                                    let _ = docker_client
-                                        .stop_container(&docker_info.container_id)
-                                        .await;
-                                }
-                            }
-                        }
-                    }
-                }
             }
+            ensure_own_task(task_id, existing)?;
+            info!(task: task_id; "♻️  Task {} changed; replacing it", task_id);
+            tasks::kill(&mut registry, task_id, &origin).await?;
         }
+        if !wanted.depends_on.is_empty() {
+            readiness::wait_for(&mut registry, &wanted.depends_on).await?;
+        }
+        start_launched(&mut registry, task_id, wanted, spec).await?;
+        started.push(task_id.clone());
+    }
 
-        // Show stats
-        let stats = registry.stats();
-        println!("\n📊 Status:");
-        println!("   Running: {}", stats.running);
-        println!("   Completed: {}", stats.completed);
-        println!("   Failed: {}", stats.failed);
-        println!("   Total: {}", stats.total);
-        println!("   Memory: {}KB", get_self_memory_kb());
-
-        println!("\n💤 Next check in {}s...\n", interval_secs);
-        sleep(Duration::from_secs(interval_secs)).await;
+    if json_output() {
+        return print_json(&serde_json::json!({
+            "set": set,
+            "started": started,
+            "unchanged": unchanged,
+            "stopped": stopped,
+        }));
     }
+    info!(
+        "✅ {} is up: {} started, {} unchanged, {} stopped",
+        set,
+        started.len(),
+        unchanged.len(),
+        stopped.len()
+    );
+    Ok(())
 }
 
-/// Check Docker containers for running tasks
-async fn check_docker_tasks(registry: &mut RegistryManager, docker: &DockerManager) -> Result<()> {
-    // Collect task_ids to mark as failed (separate from iteration)
-    let mut failed_tasks = Vec::new();
-
-    for (task_id, task) in registry.running_tasks() {
-        if task.mode == ExecutionMode::Docker {
-            if let Some(docker_info) = &task.docker {
-                let is_running = docker.is_running(&docker_info.container_id).await;
-
-                if !is_running && task.status == TaskStatus::Running {
-                    println!("⚠️  Docker task {} stopped unexpectedly", task_id);
-                    failed_tasks.push(task_id.clone());
-                }
-            }
+/// Start a task of a launch file in the background and register it,
+/// replacing the record of its previous run
+async fn start_launched(
+    registry: &mut RegistryManager,
+    task_id: &str,
+    mut task: TaskInfo,
+    spec: &LaunchTask,
+) -> Result<()> {
+    match task.mode {
+        ExecutionMode::Native => {
+            let log_path = std::path::absolute(logs::log_path(registry.path(), task_id))?;
+            let native = tasks::start_detached(task_id, &task, &log_path, registry.file_mode())?;
+            info!(task: task_id; "🚀 Task {} started: PID {}", task_id, native.pid);
+            task.native = Some(native);
+            task.log_file = Some(log_path);
+        }
+        ExecutionMode::Docker => {
+            let docker = DockerManager::new().context("Docker is not available")?;
+            let mut container =
+                tasks::start_container(&docker, task_id, &task, spec.image.as_deref(), None)
+                    .await?;
+            spec.configure(&mut container);
+            info!(task: task_id; "🚀 Task {} started in {}", task_id, container.container_name);
+            task.docker = Some(container);
+            // Running once the daemon sees the container up
+            task.status = TaskStatus::Starting;
         }
     }
-
-    // Now mark them as failed (no borrow conflict)
-    for task_id in failed_tasks {
-        registry.mark_failed(&task_id)?;
+    let native = task.native.clone();
+    let result = registry.with_transaction(|txn| {
+        txn.remove_task(task_id);
+        txn.register(task_id.to_string(), task)
+    });
+    if let (Err(_), Some(native)) = (&result, native) {
+        let _ = ProcessManager::kill_task(&native);
     }
-
-    Ok(())
+    result
 }
 
-/// Check status of specific task
-async fn check_task(task_id: &str, registry_path: &str) -> Result<()> {
+/// Stop the unfinished tasks of a launch file's set: any the file no
+/// longer declares first, then each task before the ones it waits for
+async fn down_tasks(
+    path: &Path,
+    vars: &Vars,
+    origin: TaskOrigin,
+    registry_path: &Path,
+) -> Result<()> {
+    let (set, file) = LaunchFile::load(path, vars)?;
+    let order = file.start_order()?;
     let mut registry = RegistryManager::new(registry_path);
     registry.load()?;
 
-    match registry.get_task(task_id) {
-        Some(task) => {
-            println!("📋 Task: {}", task_id);
-            println!("   Command: {}", task.command);
-            println!("   Mode: {:?}", task.mode);
-            println!("   Status: {:?}", task.status);
-            println!(
-                "   Started: {}",
-                task.started_at.format("%Y-%m-%d %H:%M:%S")
-            );
-
-            match &task.mode {
-                ExecutionMode::Native => {
-                    if let Some(native) = &task.native {
-                        let is_alive = ProcessManager::is_alive(native.pid);
-                        println!(
-                            "   PID: {} ({})",
-                            native.pid,
-                            if is_alive { "✅ alive" } else { "💀 dead" }
-                        );
-                        println!("   PGID: {}", native.pgid);
-
-                        if is_alive {
-                            if let Some(usage) = ProcessManager::get_resource_usage(native.pid) {
-                                println!("   CPU: {:.1}%", usage.cpu_percent);
-                                println!("   Memory: {}MB", usage.memory_kb / 1024);
-                            }
-                        }
-                    }
-                }
-                ExecutionMode::Docker => {
-                    if let Some(docker_info) = &task.docker {
-                        println!("   Container: {}", &docker_info.container_id[..12]);
-                        println!(
-                            "   Limits: {} memory, {} CPU",
-                            docker_info.resource_limits.memory, docker_info.resource_limits.cpu
-                        );
+    let members = set_members(&registry, &set);
+    let mut stopping: Vec<&String> = members.iter().filter(|id| !order.contains(id)).collect();
+    stopping.extend(order.iter().rev().filter(|id| members.contains(id)));
 
-                        if let Some(docker) = DockerManager::new() {
-                            let is_running = docker.is_running(&docker_info.container_id).await;
-                            println!(
-                                "   Status: {}",
-                                if is_running {
-                                    "✅ running"
-                                } else {
-                                    "💀 stopped"
-                                }
-                            );
-                        }
-                    }
-                }
+    let mut stopped = Vec::new();
+    for task_id in stopping {
+        if let Some(task) = registry.get_task(task_id) {
+            if let Err(e) = ensure_own_task(task_id, task) {
+                warn!(task: task_id; "⏭️  Skipping {}: {:#}", task_id, e);
+                continue;
             }
-        }
-        None => {
-            println!("❌ Task {} not found", task_id);
+            tasks::kill(&mut registry, task_id, &origin).await?;
+            stopped.push(task_id.clone());
         }
     }
 
+    if json_output() {
+        return print_json(&serde_json::json!({ "set": set, "stopped": stopped }));
+    }
+    info!("✅ {} is down: {} stopped", set, stopped.len());
     Ok(())
 }
 
-/// Kill a running task
-async fn kill_task(task_id: &str, registry_path: &str) -> Result<()> {
-    let mut registry = RegistryManager::new(registry_path);
-    registry.load()?;
+/// Unfinished tasks started from the launch file set `set`
+fn set_members(registry: &RegistryManager, set: &str) -> Vec<String> {
+    let mut members: Vec<String> = registry
+        .active_tasks()
+        .into_iter()
+        .filter(|(_, task)| task.labels.get(SET_LABEL).is_some_and(|name| name == set))
+        .map(|(task_id, _)| task_id.clone())
+        .collect();
+    members.sort();
+    members
+}
 
-    match registry.get_task(task_id) {
-        Some(task) => {
-            println!("🔪 Killing task: {}", task_id);
+/// Register tasks read from a file or stdin with one lock/load/save cycle.
+///
+/// `defaults` and `rules` apply to every entry; an entry's own fields win.
+/// Entries that can't be registered (bad command, policy, no room) are
+/// reported and the rest still go in; the command fails if any did.
+async fn register_batch(
+    entries: Vec<TaskSpec>,
+    defaults: Details,
+    rules: Option<String>,
+    admission: Admission,
+    vars: &Vars,
+    origin: TaskOrigin,
+    registry_path: &str,
+) -> Result<()> {
+    let default_rules = tasks::parse_rules(rules);
 
-            match &task.mode {
-                ExecutionMode::Native => {
-                    if let Some(native) = &task.native {
-                        ProcessManager::kill_process_group(native.pgid)?;
-                        println!("✅ Killed process group {}", native.pgid);
-                    }
-                }
-                ExecutionMode::Docker => {
-                    if let Some(docker_info) = &task.docker {
-                        if let Some(docker) = DockerManager::new() {
-                            docker.stop_container(&docker_info.container_id).await?;
-                            println!("✅ Stopped container {}", &docker_info.container_id[..12]);
-                        }
-                    }
+    // With --queue, a hot machine defers new launches too
+    let hot = admission.queue && ThermalStatus::sample().is_throttling();
+
+    let total = entries.len();
+    let mut refused = Vec::new();
+    let mut tasks = Vec::new();
+    for mut entry in entries {
+        let task_id = entry.task_id.clone();
+        let vars = vars.for_task(&task_id);
+        let mut defaults = defaults.clone();
+        let prepared = entry
+            .render(&vars)
+            .and_then(|()| defaults.render(&vars))
+            .and_then(|()| {
+                entry.into_task(
+                    &defaults,
+                    &default_rules,
+                    admission.reservation.as_ref(),
+                    origin.clone(),
+                )
+            });
+        match prepared {
+            Ok(mut task) => {
+                if hot {
+                    task.status = TaskStatus::Queued;
                 }
+                tasks.push((task_id, task));
             }
+            Err(e) => refused.push((task_id, e)),
+        }
+    }
 
-            registry.mark_complete(task_id)?;
+    let mut registry = RegistryManager::new(registry_path);
+    let reservable = Resources::machine().minus_headroom(admission.headroom);
+    let mut registered = BTreeMap::new();
+    for (task_id, result) in registry.admit_batch(tasks, reservable, admission.queue)? {
+        match result {
+            Ok(TaskStatus::Queued) => info!(task: &task_id; "⏳ Task {} queued", task_id),
+            Ok(_) => info!(task: &task_id; "✅ Task {} registered", task_id),
+            Err(e) => {
+                refused.push((task_id, e));
+                continue;
+            }
         }
-        None => {
-            println!("❌ Task {} not found", task_id);
+        if let Some(task) = registry.get_task(&task_id) {
+            registered.insert(task_id.clone(), task.status.clone());
         }
     }
+    if hot {
+        warn!("⏳ CPU is thermally throttling: new tasks were queued");
+    }
 
+    for (task_id, e) in &refused {
+        warn!(task: task_id; "❌ Task {} not registered: {:#}", task_id, e);
+    }
+    if json_output() {
+        let refused: BTreeMap<&String, String> = refused
+            .iter()
+            .map(|(id, e)| (id, format!("{e:#}")))
+            .collect();
+        print_json(&serde_json::json!({ "registered": registered, "refused": refused }))?;
+    }
+    if !refused.is_empty() {
+        bail!("{} of {} tasks not registered", refused.len(), total);
+    }
     Ok(())
 }
 
-/// Rehydrate context after compression
-async fn rehydrate(registry_path: &str) -> Result<()> {
-    println!("🧠 Context Re-Hydration Report");
-    println!("================================\n");
-
+/// Register the tasks of a swarm file in the group `defaults` names,
+/// starting as many as `jobs` leaves room for next to the group's tasks
+/// still running and queueing the rest, for the daemon to start as slots
+/// free up. Tasks that can't be registered or started are reported and the
+/// rest still go in; the command fails if any did.
+async fn swarm_tasks(
+    path: &Path,
+    jobs: u32,
+    defaults: Details,
+    headroom: u8,
+    vars: &Vars,
+    origin: TaskOrigin,
+    registry_path: &Path,
+) -> Result<()> {
+    let group = defaults.group.clone().unwrap_or_default();
+    let entries = swarm::load(path, &group)?;
     let mut registry = RegistryManager::new(registry_path);
     registry.load()?;
+    let mut slots =
+        (jobs as usize).saturating_sub(queue::running_in_group(registry.registry(), &group));
+    // A hot machine holds the whole swarm back
+    let hot = ThermalStatus::sample().is_throttling();
 
-    let running = registry.running_tasks();
-
-    if running.is_empty() {
-        println!("✅ No tasks currently running\n");
-    } else {
-        println!("📊 ACTIVE TASKS ({})\n", running.len());
-
-        for (task_id, task) in running {
-            println!("Task {}", task_id);
-            println!("  Command: {}", task.command);
-            println!("  Mode: {:?}", task.mode);
-            println!("  Started: {}", task.started_at.format("%H:%M:%S"));
-
-            // Check if still alive
-            let is_alive = match &task.mode {
-                ExecutionMode::Native => task
-                    .native
-                    .as_ref()
-                    .is_some_and(|n| ProcessManager::is_alive(n.pid)),
-                ExecutionMode::Docker => {
-                    if let Some(docker_info) = &task.docker {
-                        if let Some(docker) = DockerManager::new() {
-                            docker.is_running(&docker_info.container_id).await
-                        } else {
-                            false
-                        }
-                    } else {
-                        false
-                    }
-                }
-            };
-
-            println!(
-                "  Status: {}",
-                if is_alive {
-                    "✅ Running"
+    let total = entries.len();
+    let mut refused = Vec::new();
+    let mut tasks = Vec::new();
+    for mut entry in entries {
+        let task_id = entry.task_id.clone();
+        let vars = vars.for_task(&task_id);
+        let mut defaults = defaults.clone();
+        let prepared = entry
+            .render(&vars)
+            .and_then(|()| defaults.render(&vars))
+            .and_then(|()| entry.into_task(&defaults, &[], None, origin.clone()));
+        match prepared {
+            Ok(mut task) => {
+                // Its own group would escape the swarm's limit
+                task.group = Some(group.clone());
+                task.jobs = Some(jobs);
+                if hot || slots == 0 {
+                    task.status = TaskStatus::Queued;
                 } else {
-                    "⚠️  DEAD"
+                    slots -= 1;
                 }
-            );
-            println!();
+                tasks.push((task_id, task));
+            }
+            Err(e) => refused.push((task_id, e)),
         }
     }
 
-    let stats = registry.stats();
-    println!("💡 SUMMARY:");
-    println!("   Running: {}", stats.running);
-    println!("   Completed: {}", stats.completed);
-    println!("   Failed: {}", stats.failed);
-    println!("\n📄 Full context: {}", registry_path);
+    let reservable = Resources::machine().minus_headroom(headroom);
+    let mut started = Vec::new();
+    let mut queued = Vec::new();
+    for (task_id, result) in registry.admit_batch(tasks, reservable, true)? {
+        match result {
+            Ok(TaskStatus::Queued) => {
+                info!(task: &task_id; "⏳ Task {} queued", task_id);
+                queued.push(task_id);
+            }
+            Ok(_) => match tasks::start_promoted(&mut registry, &task_id) {
+                Ok(native) => {
+                    info!(task: &task_id; "🚀 Task {} started: PID {}", task_id, native.pid);
+                    started.push(task_id);
+                }
+                Err(e) => refused.push((task_id, e)),
+            },
+            Err(e) => refused.push((task_id, e)),
+        }
+    }
+    if hot {
+        warn!("⏳ CPU is thermally throttling: the swarm was queued");
+    }
 
+    for (task_id, e) in &refused {
+        warn!(task: task_id; "❌ Task {} not started: {:#}", task_id, e);
+    }
+    if !queued.is_empty() && !DaemonLock::is_held(registry_path) {
+        warn!("⚠️  No watchdog is running to start queued tasks: `task-watchdog run`");
+    }
+    if json_output() {
+        let refused: BTreeMap<&String, String> = refused
+            .iter()
+            .map(|(id, e)| (id, format!("{e:#}")))
+            .collect();
+        print_json(&serde_json::json!({
+            "group": group,
+            "jobs": jobs,
+            "started": started,
+            "queued": queued,
+            "refused": refused,
+        }))?;
+    } else {
+        info!(
+            "✅ Swarm {}: {} started, {} queued (at most {} at a time)",
+            group,
+            started.len(),
+            queued.len(),
+            jobs
+        );
+    }
+    if !refused.is_empty() {
+        bail!("{} of {} tasks not started", refused.len(), total);
+    }
     Ok(())
 }
 
-/// Show resource usage report
-async fn show_report(registry_path: &str) -> Result<()> {
-    println!("📊 Resource Usage Report");
-    println!("========================\n");
+/// Tasks to register from `content`, a JSON task or array of tasks read
+/// from `source`
+fn parse_task_specs(content: &str, source: &str) -> Result<Vec<TaskSpec>> {
+    let value: serde_json::Value = serde_json::from_str(content)
+        .with_context(|| format!("Failed to parse {} as JSON", source))?;
+    let entries = match value {
+        serde_json::Value::Array(_) => serde_json::from_value(value),
+        _ => serde_json::from_value(value).map(|entry| vec![entry]),
+    };
+    entries.with_context(|| format!("Invalid task definition in {}", source))
+}
 
+/// Render metrics from the registry in one shot
+fn export_metrics(
+    textfile: Option<&Path>,
+    filter: Option<&Filter>,
+    registry_path: &Path,
+) -> Result<()> {
     let mut registry = RegistryManager::new(registry_path);
     registry.load()?;
 
-    for (task_id, task) in registry.running_tasks() {
-        println!("Task {}", task_id);
-
-        match &task.mode {
-            ExecutionMode::Native => {
-                if let Some(native) = &task.native {
-                    if let Some(usage) = ProcessManager::get_resource_usage(native.pid) {
-                        println!("  CPU: {:.1}%", usage.cpu_percent);
-                        println!("  Memory: {}MB", usage.memory_kb / 1024);
-                    } else {
-                        println!("  ⚠️  Process not found");
-                    }
-                }
-            }
-            ExecutionMode::Docker => {
-                if let Some(docker_info) = &task.docker {
-                    println!("  Container: {}", &docker_info.container_id[..12]);
-                    println!(
-                        "  Limits: {}, {}",
-                        docker_info.resource_limits.memory, docker_info.resource_limits.cpu
-                    );
-                }
-            }
+    registry.load_history()?;
+    // Health stays project-wide; the filter narrows the task metrics
+    let health = assess_health(
+        registry.registry(),
+        registry_path,
+        ThermalStatus::sample().is_throttling(),
+    );
+    let now = chrono::Utc::now();
+    let mut tasks = registry.registry().clone();
+    if let Some(filter) = filter {
+        tasks
+            .tasks
+            .retain(|task_id, task| filter.matches(task_id, task, now));
+    }
+    let text = metrics::render(&tasks, &health, now);
+    match textfile {
+        Some(path) => {
+            metrics::write_textfile(path, &text)?;
+            verbose!("📈 Metrics written to {}", path.display());
         }
-        println!();
+        None => print!("{text}"),
     }
-
     Ok(())
 }
 
-/// Show registry statistics
-async fn show_stats(registry_path: &str) -> Result<()> {
+/// Show reserved vs. used resources against machine capacity
+async fn show_capacity(headroom: u8, registry_path: &str) -> Result<()> {
     let mut registry = RegistryManager::new(registry_path);
     registry.load()?;
 
-    let stats = registry.stats();
+    let machine = Resources::machine();
+    let reservable = machine.minus_headroom(headroom);
+    let reserved = capacity::reserved(registry.registry(), None);
+    let in_use = Resources::in_use();
+
+    if json_output() {
+        return print_json(&serde_json::json!({
+            "headroom_percent": headroom,
+            "machine": machine,
+            "reservable": reservable,
+            "reserved": reserved,
+            "in_use": in_use,
+            "queued": registry.stats().queued,
+        }));
+    }
+
+    info!("🧮 Capacity (headroom {}%)", headroom);
+    info!("=========================\n");
+
+    let mut table = new_table(["Resource", "Machine", "Reservable", "Reserved", "In use"]);
+    table.add_row(vec![
+        Cell::new("memory"),
+        number_cell(format_bytes(machine.memory_bytes)),
+        number_cell(format_bytes(reservable.memory_bytes)),
+        number_cell(format_bytes(reserved.memory_bytes)),
+        number_cell(format_bytes(in_use.memory_bytes)),
+    ]);
+    table.add_row(vec![
+        Cell::new("cpu"),
+        number_cell(format!("{:.0}", machine.cpus)),
+        number_cell(format!("{:.2}", reservable.cpus)),
+        number_cell(format!("{:.2}", reserved.cpus)),
+        number_cell(format!("{:.2}", in_use.cpus)),
+    ]);
+    info!("{table}");
 
-    println!("📈 Registry Statistics");
-    println!("=====================\n");
-    println!("Total tasks: {}", stats.total);
-    println!("Running: {}", stats.running);
-    println!("Completed: {}", stats.completed);
-    println!("Failed: {}", stats.failed);
+    let stats = registry.stats();
+    if stats.queued > 0 {
+        info!("\n⏳ {} task(s) queued waiting for capacity", stats.queued);
+    }
 
     Ok(())
 }
 
-/// Cleanup old tasks
-async fn cleanup_tasks(days: u64, registry_path: &str) -> Result<()> {
-    let mut registry = RegistryManager::new(registry_path);
-    registry.load()?;
+/// Diagnose the installation and surface recent daemon crashes
+async fn doctor(registry_path: &Path) -> Result<()> {
+    info!("🩺 Task Watchdog Doctor (v{})", env!("CARGO_PKG_VERSION"));
+    info!("===========================\n");
 
-    println!("🧹 Cleaning up tasks older than {} days...", days);
+    let mut registry = RegistryManager::new(registry_path);
+    let backend = registry.backend();
+    if !backend.exists() && !backend.needs_migration() {
+        info!(
+            "⚠️  Registry: {} does not exist yet",
+            backend.path().display()
+        );
+    } else {
+        match registry.load() {
+            Ok(()) => info!(
+                "✅ Registry: {} ({} tasks, {})",
+                registry.backend().path().display(),
+                registry.stats().total,
+                registry.backend().name()
+            ),
+            Err(e) => info!("❌ Registry: {:#}", e),
+        }
+        match clock::find_skew(registry.registry(), chrono::Utc::now()) {
+            None => info!("✅ Clock: registry timestamps agree with the system clock"),
+            Some(skew) => info!(
+                "⏰ Clock: {} task(s) in the future (up to {}), {} over {} years old",
+                skew.ahead.len(),
+                format_duration(skew.max_ahead_secs as f64),
+                skew.ancient.len(),
+                clock::MAX_AGE_DAYS / 365
+            ),
+        }
+    }
 
-    let removed = registry.cleanup_old_tasks(days)?;
+    match DockerManager::new() {
+        Some(docker) if docker.ping().await => info!("✅ Docker: daemon reachable"),
+        _ => info!("⚠️  Docker: not available (native processes only)"),
+    }
 
-    println!("✅ Removed {} old tasks", removed);
+    let crash_dir = crash::crash_dir_for(registry_path);
+    let crashes = crash::recent_crashes(&crash_dir, 5)?;
+    if crashes.is_empty() {
+        info!("✅ Crashes: none recorded");
+    } else {
+        info!(
+            "💥 Crashes: {} recent report(s) in {}",
+            crashes.len(),
+            crash_dir.display()
+        );
+        for (path, report) in &crashes {
+            info!(
+                "   {} - {}{}",
+                format_time(report.timestamp, "%Y-%m-%d %H:%M:%S"),
+                report.message,
+                report
+                    .location
+                    .as_ref()
+                    .map(|l| format!(" ({l})"))
+                    .unwrap_or_default()
+            );
+            verbose!("     {}", path.display());
+        }
+    }
 
     Ok(())
 }
 
-/// Register a new task with constitution rules
-async fn register_task(
-    task_id: &str,
-    command: &str,
-    rules: Option<String>,
-    registry_path: &str,
-) -> Result<()> {
-    let mut registry = RegistryManager::new(registry_path);
-    registry.load()?;
+/// Scaffold the watchdog layout in the current directory
+fn init_project(options: InitOptions) -> Result<()> {
+    let root = std::env::current_dir()?;
+    info!("🛠️  Initializing task-watchdog in {}", root.display());
 
-    // Parse constitution rules from comma-separated string
-    let constitution_rules = rules
-        .map(|r| r.split(',').map(|s| s.trim().to_string()).collect())
-        .unwrap_or_default();
+    let actions = init::init(&root, options)?;
+    for (path, action) in &actions {
+        let shown = path.strip_prefix(&root).unwrap_or(path).display();
+        match action {
+            Action::Created => info!("   ✅ created {}", shown),
+            Action::Overwritten => info!("   ✅ overwrote {}", shown),
+            Action::Updated => info!("   ✅ updated {}", shown),
+            Action::Moved => info!("   📦 moved registry to {}", shown),
+            Action::Kept => info!("   ⏭️  kept {} (already exists)", shown),
+        }
+    }
 
-    // Create task info with default Native mode and Running status
-    let task = TaskInfo {
-        mode: ExecutionMode::Native,
-        command: command.to_string(),
-        status: TaskStatus::Running,
-        started_at: chrono::Utc::now(),
-        completed_at: None,
-        native: None,
-        docker: None,
-        constitution_rules,
-    };
+    let settings = root.join(".claude").join("settings.json");
+    if options.hooks && actions.contains(&(settings, Action::Kept)) {
+        info!("\n   Merge these hooks into .claude/settings.json (or rerun with --force):");
+        info!("{}", init::HOOKS_JSON);
+    }
 
-    registry.upsert_task(task_id.to_string(), task)?;
+    Ok(())
+}
 
-    println!(
-        "✅ Task {} registered with {} constitution rules",
-        task_id,
-        registry
-            .get_task(task_id)
-            .map(|t| t.constitution_rules.len())
-            .unwrap_or(0)
+/// Check for (and optionally install) a newer release
+#[cfg(feature = "self-update")]
+async fn self_update(check_only: bool, insecure: bool) -> Result<()> {
+    info!(
+        "🔄 Checking for updates (current v{})...",
+        env!("CARGO_PKG_VERSION")
     );
 
+    let outcome =
+        tokio::task::spawn_blocking(move || update::self_update(check_only, insecure)).await??;
+
+    match outcome {
+        UpdateOutcome::UpToDate => info!("✅ Already up to date"),
+        UpdateOutcome::Available(tag) => {
+            info!(
+                "⬆️  Update available: {} (run without --check-only to install)",
+                tag
+            )
+        }
+        UpdateOutcome::Installed(tag) => info!("✅ Updated to {}", tag),
+    }
+
     Ok(())
 }
 
-/// Get memory usage of current process
-fn get_self_memory_kb() -> u64 {
-    use sysinfo::{Pid, System};
-
-    let mut sys = System::new_all();
-    sys.refresh_all();
+#[cfg(not(feature = "self-update"))]
+async fn self_update(_check_only: bool, _insecure: bool) -> Result<()> {
+    bail!("This build cannot update itself; rebuild with `--features self-update`")
+}
 
-    let pid = Pid::from_u32(std::process::id());
+/// Replace this process with a fresh copy of itself (same arguments)
+#[cfg(unix)]
+fn restart_self() -> Result<()> {
+    use std::os::unix::process::CommandExt;
 
-    if let Some(process) = sys.process(pid) {
-        return process.memory();
-    }
+    let exe = std::env::current_exe()?;
+    let err = std::process::Command::new(exe)
+        .args(std::env::args_os().skip(1))
+        .exec();
+    // exec only returns on failure
+    Err(err.into())
+}
 
-    0
+#[cfg(not(unix))]
+fn restart_self() -> Result<()> {
+    bail!("--restart-on-leak is only supported on Unix")
 }
@@ -0,0 +1,173 @@
+//! Prometheus metrics in the text exposition format.
+//!
+//! `export-metrics` renders the registry once and exits, which suits cron
+//! plus node_exporter's textfile collector on machines that don't run the
+//! daemon. The file is written to a temp sibling and renamed into place so
+//! the collector never scrapes half a file.
+
+use crate::anomaly;
+use crate::capacity;
+use crate::health::Health;
+use crate::types::{ProcessRegistry, TaskCost, TaskStatus};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+/// Every status, so absent ones still export as 0
+const STATUSES: [TaskStatus; 9] = [
+    TaskStatus::Queued,
+    TaskStatus::Starting,
+    TaskStatus::Running,
+    TaskStatus::Completed,
+    TaskStatus::Failed,
+    TaskStatus::Cancelled,
+    TaskStatus::Killed,
+    TaskStatus::TimedOut,
+    TaskStatus::Unknown,
+];
+
+/// Render the registry as Prometheus text
+pub fn render(registry: &ProcessRegistry, health: &Health, now: DateTime<Utc>) -> String {
+    let mut out = String::new();
+
+    metric(
+        &mut out,
+        "task_watchdog_tasks",
+        "gauge",
+        "Tasks in the registry by status",
+    );
+    for status in &STATUSES {
+        let count = registry
+            .tasks
+            .values()
+            .filter(|t| &t.status == status)
+            .count();
+        let _ = writeln!(out, "task_watchdog_tasks{{status=\"{status}\"}} {count}");
+    }
+
+    let mut cost = TaskCost::default();
+    let mut cpu_seconds = 0.0;
+    for task in registry.tasks.values() {
+        if let Some(c) = &task.cost {
+            cost.add(c);
+        }
+        cpu_seconds += task.cpu_seconds.unwrap_or(0.0);
+    }
+    metric(
+        &mut out,
+        "task_watchdog_tokens",
+        "gauge",
+        "AI API tokens reported by tasks in the registry",
+    );
+    let _ = writeln!(
+        out,
+        "task_watchdog_tokens{{kind=\"input\"}} {}",
+        cost.input_tokens
+    );
+    let _ = writeln!(
+        out,
+        "task_watchdog_tokens{{kind=\"output\"}} {}",
+        cost.output_tokens
+    );
+    gauge(
+        &mut out,
+        "task_watchdog_cost_usd",
+        "AI API spend reported by tasks in the registry",
+        cost.usd,
+    );
+    gauge(
+        &mut out,
+        "task_watchdog_cpu_seconds",
+        "CPU time used by tasks in the registry",
+        cpu_seconds,
+    );
+
+    let reserved = capacity::reserved(registry, None);
+    gauge(
+        &mut out,
+        "task_watchdog_reserved_memory_bytes",
+        "Memory reserved by started tasks",
+        reserved.memory_bytes as f64,
+    );
+    gauge(
+        &mut out,
+        "task_watchdog_reserved_cpus",
+        "CPUs reserved by started tasks",
+        reserved.cpus,
+    );
+    gauge(
+        &mut out,
+        "task_watchdog_duration_anomalies",
+        "Running tasks far beyond their command's historical p95",
+        anomaly::find_anomalies(registry, now).len() as f64,
+    );
+    gauge(
+        &mut out,
+        "task_watchdog_health_score",
+        "Project health score (0-100)",
+        f64::from(health.score),
+    );
+    gauge(
+        &mut out,
+        "task_watchdog_export_timestamp_seconds",
+        "When these metrics were written",
+        now.timestamp() as f64,
+    );
+
+    out
+}
+
+/// Atomically replace `path` with `contents`
+pub fn write_textfile(path: &Path, contents: &str) -> Result<()> {
+    let tmp_path = path.with_extension("prom.tmp");
+    fs::write(&tmp_path, contents)
+        .with_context(|| format!("Failed to write {}", tmp_path.display()))?;
+    fs::rename(&tmp_path, path)
+        .with_context(|| format!("Failed to rename metrics into {}", path.display()))
+}
+
+fn metric(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: f64) {
+    metric(out, name, "gauge", help);
+    let _ = writeln!(out, "{name} {value}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::health::Signals;
+
+    #[test]
+    fn test_render_exports_every_status() {
+        let registry = ProcessRegistry::new();
+        let now = Utc::now();
+        let health = Health::assess(&registry, Signals::default(), now);
+        let text = render(&registry, &health, now);
+
+        assert!(text.contains("# TYPE task_watchdog_tasks gauge\n"));
+        assert!(text.contains("task_watchdog_tasks{status=\"queued\"} 0\n"));
+        assert!(text.contains("task_watchdog_tasks{status=\"unknown\"} 0\n"));
+        assert!(text.contains("task_watchdog_health_score 100\n"));
+        // Every sample line is `name{labels} value`
+        for line in text.lines().filter(|l| !l.starts_with('#')) {
+            let value = line.rsplit(' ').next().unwrap();
+            assert!(value.parse::<f64>().is_ok(), "bad sample: {line}");
+        }
+    }
+
+    #[test]
+    fn test_write_textfile_replaces_atomically() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("watchdog.prom");
+        write_textfile(&path, "a 1\n").unwrap();
+        write_textfile(&path, "a 2\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a 2\n");
+        assert!(!dir.path().join("watchdog.prom.tmp").exists());
+    }
+}
@@ -0,0 +1,120 @@
+//! CPU, memory and disk I/O of individual processes, from one long-lived
+//! sysinfo `System`.
+//!
+//! `System::new_all()` reads every process on the machine, and the CPU
+//! usage of a process is only known from its second refresh. A
+//! [`SystemMonitor`] refreshes just the PIDs it is asked about, so a check
+//! costs O(tracked processes) however busy the machine is, and the daemon,
+//! which keeps one across checks, gets each process's CPU since the
+//! previous check.
+
+use crate::types::{IoStats, ResourceUsage};
+use sysinfo::{Pid, ProcessRefreshKind, System, MINIMUM_CPU_UPDATE_INTERVAL};
+
+fn refresh_kind() -> ProcessRefreshKind {
+    ProcessRefreshKind::new()
+        .with_cpu()
+        .with_memory()
+        .with_disk_usage()
+}
+
+/// Samples of the processes the watchdog tracks, and of itself
+pub struct SystemMonitor {
+    sys: System,
+    own_pid: Pid,
+}
+
+impl Default for SystemMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SystemMonitor {
+    pub fn new() -> Self {
+        Self {
+            sys: System::new(),
+            own_pid: Pid::from_u32(std::process::id()),
+        }
+    }
+
+    /// Sample `pids`; processes not among them are forgotten. PIDs the
+    /// monitor hasn't seen before are sampled again after sysinfo's minimum
+    /// interval, so their CPU usage is a rate rather than 0. The wait is a
+    /// tokio sleep, so the daemon's other work carries on meanwhile.
+    pub async fn refresh(&mut self, pids: &[i32]) {
+        let pids: Vec<Pid> = pids.iter().map(|pid| Pid::from_u32(*pid as u32)).collect();
+        let known: Vec<bool> = pids
+            .iter()
+            .map(|pid| self.sys.process(*pid).is_some())
+            .collect();
+        self.sys.refresh_pids_specifics(&pids, refresh_kind());
+        let new = pids
+            .iter()
+            .zip(known)
+            .any(|(pid, known)| !known && self.sys.process(*pid).is_some());
+        if new {
+            tokio::time::sleep(MINIMUM_CPU_UPDATE_INTERVAL).await;
+            self.sys.refresh_pids_specifics(&pids, refresh_kind());
+        }
+    }
+
+    /// What `pid` used as of the last [`SystemMonitor::refresh`] that
+    /// included it
+    pub fn usage(&self, pid: i32) -> Option<ResourceUsage> {
+        let process = self.sys.process(Pid::from_u32(pid as u32))?;
+        // Disk usage comes from /proc/<pid>/io on Linux
+        let disk = process.disk_usage();
+        Some(ResourceUsage {
+            cpu_percent: process.cpu_usage(),
+            // sysinfo reports bytes
+            memory_kb: process.memory() / 1024,
+            io: IoStats {
+                read_bytes: disk.total_read_bytes,
+                written_bytes: disk.total_written_bytes,
+            },
+        })
+    }
+
+    /// Resident memory of the watchdog itself, sampled now
+    pub fn self_memory_kb(&mut self) -> u64 {
+        // Unlike `refresh`, this keeps the other processes' samples
+        self.sys
+            .refresh_process_specifics(self.own_pid, refresh_kind());
+        self.sys
+            .process(self.own_pid)
+            .map_or(0, |process| process.memory() / 1024)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_samples_only_tracked_processes() {
+        let own = std::process::id() as i32;
+        let mut monitor = SystemMonitor::new();
+        assert!(monitor.self_memory_kb() > 0);
+
+        let mut child = std::process::Command::new("sleep")
+            .arg("10")
+            .spawn()
+            .unwrap();
+        let child_pid = child.id() as i32;
+        monitor.refresh(&[child_pid, 999999]).await;
+        let usage = monitor.usage(child_pid);
+        assert!(usage.is_some_and(|usage| usage.memory_kb > 0));
+        assert!(monitor.usage(999999).is_none());
+        // Not asked about
+        assert!(monitor.usage(own).is_none());
+
+        // Dropped once no longer tracked
+        monitor.refresh(&[own]).await;
+        child.kill().unwrap();
+        child.wait().unwrap();
+        assert!(monitor.usage(child_pid).is_none());
+        assert!(monitor.usage(own).is_some());
+    }
+}
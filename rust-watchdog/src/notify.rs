@@ -0,0 +1,747 @@
+//! Notifications: alerts from the daemon delivered to the channels
+//! configured as `[notify.<name>]` tables in `watchdog.toml`.
+//!
+//! ```toml
+//! [notify.desktop]
+//! kind = "desktop"              # notify-send
+//! quiet_hours = "22:00-07:00"   # local time
+//! quiet_days = ["sat", "sun"]
+//! critical = ["zombie_detected", "limit_exceeded"]
+//!
+//! [notify.team]
+//! kind = "slack"                # or "webhook": the alert as JSON
+//! url = "{{env.SLACK_WEBHOOK_URL}}"
+//! events = ["task_died", "zombie_detected", "duration_anomaly"]
+//! ```
+//!
+//! A channel sends the events in its `events` (by default failures and
+//! zombie kills, [`DEFAULT_EVENTS`]). During its quiet hours and days,
+//! alerts are held and sent as one digest once the quiet time is over.
+//! The daemon keeps them in `process_registry.notify.json` next to the
+//! registry meanwhile, so the digest survives a restart.
+//! Alerts for events listed as `critical`, and escalations of conditions
+//! that persist, break through. Only the alerts
+//! [`AlertTracker`](crate::alerts::AlertTracker) lets through reach a
+//! channel, so an ongoing condition is not repeated.
+//!
+//! Channels are built with `--features notify` (on by default; it pulls in
+//! ureq). Without it the `[notify]` tables still parse, but [`Notifier::new`]
+//! only warns that this build cannot send them.
+
+#[cfg(feature = "notify")]
+use crate::alerts;
+use crate::alerts::Alert;
+#[cfg(feature = "notify")]
+use crate::events::{WatchdogEvent, EVENT_NAMES};
+#[cfg(feature = "notify")]
+use crate::files;
+#[cfg(feature = "notify")]
+use crate::template::Vars;
+use crate::warn;
+use anyhow::Result;
+#[cfg(feature = "notify")]
+use anyhow::{bail, Context};
+use chrono::NaiveDateTime;
+#[cfg(feature = "notify")]
+use chrono::{Datelike, NaiveTime, Weekday};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+#[cfg(feature = "notify")]
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "notify")]
+use std::time::Duration;
+
+/// Events a channel sends unless it lists its own: a task failing (dying,
+/// timing out, killed for a limit, or failed for its dependency) and a
+/// zombie being killed
+pub const DEFAULT_EVENTS: &[&str] = &[
+    "task_died",
+    "timed_out",
+    "limit_exceeded",
+    "dependency_failed",
+    "zombie_detected",
+];
+
+/// How long a webhook may take to answer
+#[cfg(feature = "notify")]
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Alerts the daemon of the registry at `registry_path` holds for quiet
+/// channels
+pub fn held_path(registry_path: &Path) -> PathBuf {
+    registry_path.with_extension("notify.json")
+}
+
+/// How a channel delivers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChannelKind {
+    /// A desktop notification through `notify-send`
+    Desktop,
+    /// A JSON POST to `url`: title, text, urgency and the events
+    Webhook,
+    /// A message to a Slack incoming webhook at `url`
+    Slack,
+}
+
+/// A `[notify.<name>]` table
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChannelConfig {
+    pub kind: ChannelKind,
+    /// Endpoint of a webhook or Slack channel; `{{env.NAME}}` reads it
+    /// from the environment
+    pub url: Option<String>,
+    /// Events to send ([`DEFAULT_EVENTS`] if not given)
+    pub events: Option<Vec<String>>,
+    /// `HH:MM-HH:MM` in local time; may wrap past midnight
+    pub quiet_hours: Option<String>,
+    /// Days that are quiet all day (`mon`, `sat`, ...)
+    #[serde(default)]
+    pub quiet_days: Vec<String>,
+    /// Events (`task_died`, `zombie_detected`, ...) sent even when quiet
+    #[serde(default)]
+    pub critical: Vec<String>,
+}
+
+/// When a channel holds back all but critical alerts
+#[cfg(feature = "notify")]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QuietSchedule {
+    hours: Option<(NaiveTime, NaiveTime)>,
+    days: Vec<Weekday>,
+}
+
+#[cfg(feature = "notify")]
+impl QuietSchedule {
+    pub fn parse(hours: Option<&str>, days: &[String]) -> Result<Self> {
+        let hours = hours
+            .map(|window| -> Result<(NaiveTime, NaiveTime)> {
+                let (start, end) = window.split_once('-').with_context(|| {
+                    format!("Quiet hours must look like 22:00-07:00, got '{window}'")
+                })?;
+                let time = |t: &str| {
+                    NaiveTime::parse_from_str(t.trim(), "%H:%M")
+                        .with_context(|| format!("Invalid time '{}' in quiet hours", t.trim()))
+                };
+                Ok((time(start)?, time(end)?))
+            })
+            .transpose()?;
+        let days = days
+            .iter()
+            .map(|day| {
+                day.parse::<Weekday>()
+                    .map_err(|_| anyhow::anyhow!("Invalid quiet day '{day}' (mon, tue, ...)"))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { hours, days })
+    }
+
+    /// Whether `at` (local time) is quiet
+    pub fn is_quiet(&self, at: NaiveDateTime) -> bool {
+        if self.days.contains(&at.weekday()) {
+            return true;
+        }
+        match self.hours {
+            Some((start, end)) if start <= end => start <= at.time() && at.time() < end,
+            // Wraps past midnight
+            Some((start, end)) => at.time() >= start || at.time() < end,
+            None => false,
+        }
+    }
+}
+
+/// What a channel is to send
+#[cfg(feature = "notify")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub title: String,
+    pub body: String,
+    /// Sent despite quiet hours, or an escalation: shown as urgent
+    pub urgent: bool,
+    /// What it reports, for webhooks
+    pub events: Vec<WatchdogEvent>,
+    /// The failure excerpt of the task it reports, for Slack and webhooks
+    /// (too long for a desktop notification)
+    pub excerpt: Option<String>,
+}
+
+#[cfg(feature = "notify")]
+impl Message {
+    /// Body of a webhook POST
+    pub fn webhook_payload(&self) -> serde_json::Value {
+        serde_json::json!({
+            "title": self.title,
+            "text": self.body,
+            "urgent": self.urgent,
+            "events": self.events,
+            "excerpt": self.excerpt,
+        })
+    }
+
+    /// Body of a Slack incoming webhook POST
+    pub fn slack_payload(&self) -> serde_json::Value {
+        let siren = if self.urgent { ":rotating_light: " } else { "" };
+        let mut text = format!("{siren}*{}*\n{}", self.title, self.body);
+        if let Some(excerpt) = &self.excerpt {
+            text.push_str(&format!("\n```\n{excerpt}\n```"));
+        }
+        serde_json::json!({ "text": text })
+    }
+}
+
+/// Where a channel's messages go
+#[cfg(feature = "notify")]
+#[derive(Debug, Clone, PartialEq)]
+enum Target {
+    Desktop,
+    Webhook(String),
+    Slack(String),
+}
+
+/// A configured channel and the alerts it holds during quiet time
+#[cfg(feature = "notify")]
+#[derive(Debug)]
+pub struct Channel {
+    name: String,
+    target: Target,
+    schedule: QuietSchedule,
+    /// Events it sends, including the critical ones
+    events: Vec<String>,
+    critical: Vec<String>,
+    held: Vec<Alert>,
+}
+
+#[cfg(feature = "notify")]
+impl Channel {
+    pub fn new(name: &str, config: &ChannelConfig) -> Result<Self> {
+        let schedule = QuietSchedule::parse(config.quiet_hours.as_deref(), &config.quiet_days)
+            .with_context(|| format!("In [notify.{name}]"))?;
+        let mut events: Vec<String> = match &config.events {
+            Some(events) => events.clone(),
+            None => DEFAULT_EVENTS
+                .iter()
+                .map(|event| event.to_string())
+                .collect(),
+        };
+        for (field, list) in [("events", &events), ("critical", &config.critical)] {
+            if let Some(event) = list.iter().find(|e| !EVENT_NAMES.contains(&e.as_str())) {
+                bail!("[notify.{name}]: unknown event '{event}' in {field}");
+            }
+        }
+        events.extend(config.critical.iter().cloned());
+
+        let url = config
+            .url
+            .as_deref()
+            .map(|url| Vars::default().render(url))
+            .transpose()
+            .with_context(|| format!("In [notify.{name}] url"))?;
+        let target = match (config.kind, url) {
+            (ChannelKind::Desktop, None) => Target::Desktop,
+            (ChannelKind::Desktop, Some(_)) => {
+                bail!("[notify.{name}]: a desktop channel has no url")
+            }
+            (_, None) => bail!("[notify.{name}]: url is required"),
+            (_, Some(url)) if !url.starts_with("http://") && !url.starts_with("https://") => {
+                bail!("[notify.{name}]: url must start with http:// or https://, got '{url}'")
+            }
+            (ChannelKind::Webhook, Some(url)) => Target::Webhook(url),
+            (ChannelKind::Slack, Some(url)) => Target::Slack(url),
+        };
+        Ok(Self {
+            name: name.to_string(),
+            target,
+            schedule,
+            events,
+            critical: config.critical.clone(),
+            held: Vec::new(),
+        })
+    }
+
+    fn is_critical(&self, alert: &Alert) -> bool {
+        alert.is_escalation()
+            || self
+                .critical
+                .iter()
+                .any(|event| event == alert.event.name())
+    }
+
+    /// Messages to send at `now` for `alerts`: each alert on its own unless
+    /// it is quiet, plus the digest of held alerts once it no longer is
+    pub fn route(&mut self, alerts: &[Alert], now: NaiveDateTime) -> Vec<Message> {
+        let quiet = self.schedule.is_quiet(now);
+        let mut messages = Vec::new();
+        if !quiet && !self.held.is_empty() {
+            let held = std::mem::take(&mut self.held);
+            let lines: Vec<String> = held
+                .iter()
+                .map(|alert| format!("• {}", alerts::describe(&alert.event)))
+                .collect();
+            messages.push(Message {
+                title: format!("task-watchdog: {} alerts during quiet hours", held.len()),
+                body: lines.join("\n"),
+                urgent: false,
+                events: held.into_iter().map(|alert| alert.event).collect(),
+                excerpt: None,
+            });
+        }
+        let wanted = alerts
+            .iter()
+            .filter(|alert| self.events.iter().any(|e| e == alert.event.name()));
+        for alert in wanted {
+            let critical = self.is_critical(alert);
+            if quiet && !critical {
+                self.held.push(alert.clone());
+                continue;
+            }
+            let title = if alert.is_escalation() {
+                "task-watchdog: still ongoing"
+            } else {
+                "task-watchdog"
+            };
+            let excerpt = match &alert.event {
+                WatchdogEvent::TaskDied {
+                    failure_excerpt, ..
+                } => failure_excerpt.clone(),
+                _ => None,
+            };
+            messages.push(Message {
+                title: title.to_string(),
+                body: alerts::describe(&alert.event),
+                urgent: critical,
+                events: vec![alert.event.clone()],
+                excerpt,
+            });
+        }
+        messages
+    }
+
+    async fn send(&self, message: &Message) -> Result<()> {
+        let (url, payload) = match &self.target {
+            Target::Webhook(url) => (url.clone(), message.webhook_payload()),
+            Target::Slack(url) => (url.clone(), message.slack_payload()),
+            Target::Desktop => {
+                let urgency = if message.urgent { "critical" } else { "normal" };
+                let status = tokio::process::Command::new("notify-send")
+                    .args(["--app-name", "task-watchdog", "--urgency", urgency])
+                    .arg(&message.title)
+                    .arg(&message.body)
+                    .status()
+                    .await
+                    .context("Failed to run notify-send")?;
+                if !status.success() {
+                    bail!("notify-send exited with {status}");
+                }
+                return Ok(());
+            }
+        };
+        // The URL is left out of errors: for Slack it is the secret
+        tokio::task::spawn_blocking(move || {
+            match ureq::post(&url).timeout(WEBHOOK_TIMEOUT).send_json(payload) {
+                Ok(_) => Ok(()),
+                Err(ureq::Error::Status(code, _)) => bail!("endpoint answered HTTP {code}"),
+                Err(ureq::Error::Transport(e)) => bail!("POST failed: {}", e.kind()),
+            }
+        })
+        .await?
+    }
+}
+
+/// Every configured channel
+#[cfg(feature = "notify")]
+#[derive(Debug, Default)]
+pub struct Notifier {
+    channels: Vec<Channel>,
+    /// Where held alerts are kept, and the mode to create it with
+    held_file: Option<(PathBuf, u32)>,
+}
+
+#[cfg(feature = "notify")]
+impl Notifier {
+    /// Channels from the `[notify]` section of the configuration
+    pub fn new(config: &BTreeMap<String, ChannelConfig>) -> Result<Self> {
+        let channels = config
+            .iter()
+            .map(|(name, channel)| Channel::new(name, channel))
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            channels,
+            held_file: None,
+        })
+    }
+
+    /// Keep held alerts in `path` (created with `mode`) from now on,
+    /// starting with those an earlier daemon left there for channels that
+    /// are still configured
+    pub fn keep_held_in(&mut self, path: PathBuf, mode: u32) -> Result<()> {
+        let mut held: BTreeMap<String, Vec<Alert>> = match std::fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse {}", path.display()))?,
+            Err(e) if e.kind() == ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        for channel in &mut self.channels {
+            channel.held = held.remove(&channel.name).unwrap_or_default();
+        }
+        self.held_file = Some((path, mode));
+        Ok(())
+    }
+
+    /// Replace the held alerts file with what the channels hold now,
+    /// removing it once they hold nothing
+    fn save_held(&self) -> Result<()> {
+        use std::io::Write;
+
+        let Some((path, mode)) = &self.held_file else {
+            return Ok(());
+        };
+        let held: BTreeMap<&str, &Vec<Alert>> = self
+            .channels
+            .iter()
+            .filter(|channel| !channel.held.is_empty())
+            .map(|channel| (channel.name.as_str(), &channel.held))
+            .collect();
+        if held.is_empty() {
+            return match std::fs::remove_file(path) {
+                Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            };
+        }
+        let content = serde_json::to_string(&held)?;
+        let tmp_path = path.with_extension("json.tmp");
+        files::create_options(*mode)
+            .open(&tmp_path)
+            .and_then(|mut file| file.write_all(content.as_bytes()))
+            .with_context(|| format!("Failed to write {}", tmp_path.display()))?;
+        std::fs::rename(&tmp_path, path)
+            .with_context(|| format!("Failed to replace {}", path.display()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.channels.is_empty()
+    }
+
+    /// Names of the channels
+    pub fn names(&self) -> Vec<&str> {
+        self.channels
+            .iter()
+            .map(|channel| channel.name.as_str())
+            .collect()
+    }
+
+    /// Deliver `alerts` (possibly none, to flush digests) on every channel.
+    /// A channel that fails is reported and doesn't stop the others.
+    pub async fn deliver(&mut self, alerts: &[Alert], now: NaiveDateTime) {
+        let mut held_changed = false;
+        for channel in &mut self.channels {
+            let held = channel.held.len();
+            let messages = channel.route(alerts, now);
+            // A quiet alert adds to the list, a digest empties it
+            held_changed |= channel.held.len() != held;
+            for message in messages {
+                if let Err(e) = channel.send(&message).await {
+                    warn!("⚠️  Notification on {} failed: {:#}", channel.name, e);
+                }
+            }
+        }
+        if held_changed {
+            if let Err(e) = self.save_held() {
+                warn!("⚠️  Held alerts may be lost on restart: {:#}", e);
+            }
+        }
+    }
+}
+
+/// Stands in for the channels in a build without them
+#[cfg(not(feature = "notify"))]
+#[derive(Debug, Default)]
+pub struct Notifier;
+
+#[cfg(not(feature = "notify"))]
+impl Notifier {
+    /// Warns about the channels it cannot send to
+    pub fn new(config: &BTreeMap<String, ChannelConfig>) -> Result<Self> {
+        if !config.is_empty() {
+            let names: Vec<&str> = config.keys().map(String::as_str).collect();
+            warn!(
+                "⚠️  Notification channels {} are configured, but this build cannot send \
+                 them; rebuild with `--features notify`",
+                names.join(", ")
+            );
+        }
+        Ok(Self)
+    }
+
+    pub fn keep_held_in(&mut self, _path: PathBuf, _mode: u32) -> Result<()> {
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        true
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        Vec::new()
+    }
+
+    pub async fn deliver(&mut self, _alerts: &[Alert], _now: NaiveDateTime) {}
+}
+
+#[cfg(all(test, feature = "notify"))]
+mod tests {
+    use super::*;
+    use crate::events::WatchdogEvent;
+    use chrono::NaiveDate;
+
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        // 2026-06-01 is a Monday
+        NaiveDate::from_ymd_opt(2026, 6, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    fn alert(event: WatchdogEvent, tier: usize) -> Alert {
+        Alert {
+            event,
+            tier,
+            first_seen: chrono::Utc::now(),
+            occurrences: 1,
+        }
+    }
+
+    fn died(task_id: &str) -> Alert {
+        let event = WatchdogEvent::TaskDied {
+            task_id: task_id.into(),
+            exit_code: Some(1),
+            triage_hint: None,
+            failure_excerpt: None,
+        };
+        alert(event, 0)
+    }
+
+    fn channel_config(kind: ChannelKind, url: Option<&str>) -> ChannelConfig {
+        ChannelConfig {
+            kind,
+            url: url.map(String::from),
+            events: None,
+            quiet_hours: None,
+            quiet_days: Vec::new(),
+            critical: Vec::new(),
+        }
+    }
+
+    fn channel() -> Channel {
+        let config = ChannelConfig {
+            quiet_hours: Some("22:00-07:00".into()),
+            quiet_days: vec!["sun".into()],
+            critical: vec!["zombie_detected".into()],
+            ..channel_config(ChannelKind::Desktop, None)
+        };
+        Channel::new("desktop", &config).unwrap()
+    }
+
+    #[test]
+    fn test_quiet_schedule_wraps_midnight_and_covers_days() {
+        let schedule = QuietSchedule::parse(Some("22:00-07:00"), &["sun".into()]).unwrap();
+        assert!(schedule.is_quiet(at(1, 23, 30)));
+        assert!(schedule.is_quiet(at(2, 6, 59)));
+        assert!(!schedule.is_quiet(at(2, 7, 0)));
+        assert!(!schedule.is_quiet(at(2, 12, 0)));
+        assert!(schedule.is_quiet(at(7, 12, 0)));
+
+        let office = QuietSchedule::parse(Some("12:00-13:00"), &[]).unwrap();
+        assert!(office.is_quiet(at(2, 12, 30)));
+        assert!(!office.is_quiet(at(2, 13, 30)));
+
+        assert!(QuietSchedule::parse(Some("22-7"), &[]).is_err());
+        assert!(QuietSchedule::parse(None, &["someday".into()]).is_err());
+    }
+
+    #[test]
+    fn test_quiet_alerts_wait_for_the_digest() {
+        let mut channel = channel();
+        assert!(channel
+            .route(&[died("T001"), died("T002")], at(1, 23, 0))
+            .is_empty());
+
+        let zombie = alert(
+            WatchdogEvent::ZombieDetected {
+                task_id: "T003".into(),
+            },
+            0,
+        );
+        let escalated = alert(
+            WatchdogEvent::TimedOut {
+                task_id: "T004".into(),
+                timeout_secs: 60,
+            },
+            1,
+        );
+        let urgent = channel.route(&[zombie, escalated], at(2, 3, 0));
+        assert_eq!(urgent.len(), 2);
+        assert!(urgent.iter().all(|message| message.urgent));
+
+        // Still quiet: nothing more; then the morning digest
+        assert!(channel.route(&[], at(2, 6, 0)).is_empty());
+        let morning = channel.route(&[died("T005")], at(2, 7, 5));
+        assert_eq!(morning.len(), 2);
+        assert!(morning[0].title.contains("2 alerts"));
+        assert!(morning[0].body.contains("T001") && morning[0].body.contains("T002"));
+        assert!(morning[1].body.contains("T005"));
+        assert!(channel.route(&[], at(2, 8, 0)).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_held_alerts_outlive_the_daemon() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = held_path(&dir.path().join("process_registry.json"));
+        let config = BTreeMap::from([(
+            "desktop".to_string(),
+            ChannelConfig {
+                quiet_hours: Some("22:00-07:00".into()),
+                ..channel_config(ChannelKind::Desktop, None)
+            },
+        )]);
+        let mut notifier = Notifier::new(&config).unwrap();
+        notifier.keep_held_in(path.clone(), 0o600).unwrap();
+        notifier.deliver(&[died("T001")], at(1, 23, 0)).await;
+        assert!(path.exists());
+
+        // The next daemon sends them in its morning digest
+        let mut restarted = Notifier::new(&config).unwrap();
+        restarted.keep_held_in(path.clone(), 0o600).unwrap();
+        let morning = restarted.channels[0].route(&[], at(2, 7, 5));
+        assert_eq!(morning.len(), 1);
+        assert!(morning[0].body.contains("T001"));
+        restarted.save_held().unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_unknown_event_is_refused() {
+        let config = ChannelConfig {
+            critical: vec!["task_dead".into()],
+            ..channel_config(ChannelKind::Desktop, None)
+        };
+        assert!(Channel::new("desktop", &config).is_err());
+        let config = ChannelConfig {
+            events: Some(vec!["zombie".into()]),
+            ..channel_config(ChannelKind::Desktop, None)
+        };
+        assert!(Channel::new("desktop", &config).is_err());
+    }
+
+    #[test]
+    fn test_channel_sends_only_its_events() {
+        let anomaly = alert(
+            WatchdogEvent::DurationAnomaly {
+                task_id: "T002".into(),
+                running_secs: 600.0,
+                p95_secs: 60.0,
+            },
+            0,
+        );
+        let now = at(2, 12, 0);
+        let mut failures = Channel::new("d", &channel_config(ChannelKind::Desktop, None)).unwrap();
+        let sent = failures.route(&[died("T001"), anomaly.clone()], now);
+        assert_eq!(sent.len(), 1);
+        assert!(sent[0].body.contains("T001"));
+
+        let config = ChannelConfig {
+            events: Some(vec!["duration_anomaly".into()]),
+            ..channel_config(ChannelKind::Desktop, None)
+        };
+        let mut slow = Channel::new("d", &config).unwrap();
+        let sent = slow.route(&[died("T001"), anomaly], now);
+        assert_eq!(sent.len(), 1);
+        assert!(sent[0].body.contains("T002"));
+    }
+
+    #[test]
+    fn test_webhook_channels_need_a_url() {
+        assert!(Channel::new("w", &channel_config(ChannelKind::Webhook, None)).is_err());
+        assert!(Channel::new(
+            "w",
+            &channel_config(ChannelKind::Slack, Some("hooks.slack.com"))
+        )
+        .is_err());
+        assert!(
+            Channel::new("d", &channel_config(ChannelKind::Desktop, Some("http://x"))).is_err()
+        );
+
+        std::env::set_var("TASK_WATCHDOG_TEST_HOOK", "https://hooks.example.com/T1");
+        let from_env = channel_config(ChannelKind::Slack, Some("{{env.TASK_WATCHDOG_TEST_HOOK}}"));
+        let channel = Channel::new("s", &from_env).unwrap();
+        assert_eq!(
+            channel.target,
+            Target::Slack("https://hooks.example.com/T1".into())
+        );
+    }
+
+    #[test]
+    fn test_payloads() {
+        let mut channel = channel();
+        let message = channel.route(&[died("T001")], at(2, 12, 0)).remove(0);
+        let webhook = message.webhook_payload();
+        assert_eq!(webhook["events"][0]["event"], "task_died");
+        assert_eq!(webhook["urgent"], false);
+        assert_eq!(
+            message.slack_payload()["text"],
+            "*task-watchdog*\nTask T001 failed with exit code 1"
+        );
+
+        let event = WatchdogEvent::TaskDied {
+            task_id: "T002".into(),
+            exit_code: Some(1),
+            triage_hint: None,
+            failure_excerpt: Some("ValueError: bad row".into()),
+        };
+        let message = channel.route(&[alert(event, 0)], at(2, 12, 0)).remove(0);
+        assert_eq!(message.webhook_payload()["excerpt"], "ValueError: bad row");
+        assert_eq!(
+            message.slack_payload()["text"],
+            "*task-watchdog*\nTask T002 failed with exit code 1\n```\nValueError: bad row\n```"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_webhook_posts_the_alert() {
+        use std::io::{BufRead, BufReader, Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            let response = "HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n";
+            reader.get_mut().write_all(response.as_bytes()).unwrap();
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+        });
+
+        let mut notifier = Notifier::new(&BTreeMap::from([(
+            "hook".to_string(),
+            channel_config(ChannelKind::Webhook, Some(&url)),
+        )]))
+        .unwrap();
+        notifier.deliver(&[died("T001")], at(2, 12, 0)).await;
+        let posted = server.join().unwrap();
+        assert_eq!(posted["events"][0]["task_id"], "T001");
+        assert_eq!(posted["text"], "Task T001 failed with exit code 1");
+    }
+}
@@ -0,0 +1,415 @@
+use crate::journal;
+use crate::types::TaskStatus;
+use chrono::{DateTime, Local, Utc};
+use comfy_table::presets::UTF8_FULL_CONDENSED;
+use comfy_table::{Cell, CellAlignment, Color, ContentArrangement, Table};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::Mutex;
+
+pub use task_watchdog_core::units::{format_bytes, format_duration, format_relative};
+
+/// How much the watchdog prints.
+///
+/// Set once from the global `-q` / `-v` / `-vv` flags and read by every
+/// module through the `info!`, `verbose!` and `debug!` macros.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Only warnings and errors (for scripts)
+    Quiet = 0,
+    /// Concise default output
+    Normal = 1,
+    /// Adds resource details (`-v`)
+    Verbose = 2,
+    /// Adds debug internals (`-vv`)
+    Debug = 3,
+}
+
+impl Verbosity {
+    /// Map CLI flags to a level. `-q` wins over any number of `-v`.
+    pub fn from_flags(quiet: bool, verbose: u8) -> Self {
+        if quiet {
+            return Verbosity::Quiet;
+        }
+        match verbose {
+            0 => Verbosity::Normal,
+            1 => Verbosity::Verbose,
+            _ => Verbosity::Debug,
+        }
+    }
+
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Verbosity::Quiet,
+            1 => Verbosity::Normal,
+            2 => Verbosity::Verbose,
+            _ => Verbosity::Debug,
+        }
+    }
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+
+/// Set the process-wide verbosity level
+pub fn set_verbosity(level: Verbosity) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// Current process-wide verbosity level
+pub fn verbosity() -> Verbosity {
+    Verbosity::from_u8(LEVEL.load(Ordering::Relaxed))
+}
+
+/// Whether output at `level` should be printed
+pub fn enabled(level: Verbosity) -> bool {
+    verbosity() >= level
+}
+
+/// What commands print: text for people or JSON for scripts (`--output`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// Set the process-wide output format. JSON also silences the text output
+/// (warnings still go to stderr), so stdout holds only the document.
+pub fn set_output_format(format: OutputFormat) {
+    let json = format == OutputFormat::Json;
+    JSON.store(json, Ordering::Relaxed);
+    if json {
+        set_verbosity(Verbosity::Quiet);
+    }
+}
+
+/// Whether commands should print JSON instead of text
+pub fn json_output() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// Print `value` as the command's JSON document
+pub fn print_json<T: serde::Serialize + ?Sized>(value: &T) -> anyhow::Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// Zone times are shown in (`--time-zone`, `--utc`); JSON always has
+/// RFC 3339 UTC timestamps
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum DisplayZone {
+    /// The system's zone (`TZ`, `/etc/localtime`)
+    #[default]
+    Local,
+    Utc,
+    Named(chrono_tz::Tz),
+}
+
+impl std::str::FromStr for DisplayZone {
+    type Err = anyhow::Error;
+
+    /// `local`, `utc` or an IANA name (`Europe/Berlin`)
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "local" => Ok(Self::Local),
+            "utc" | "z" => Ok(Self::Utc),
+            _ => s.parse().map(Self::Named).map_err(|_| {
+                anyhow::anyhow!("Unknown time zone '{s}' (e.g. local, utc, Europe/Berlin)")
+            }),
+        }
+    }
+}
+
+static ZONE: Mutex<DisplayZone> = Mutex::new(DisplayZone::Local);
+
+/// Set the process-wide zone times are shown in
+pub fn set_display_zone(zone: DisplayZone) {
+    *ZONE.lock().unwrap_or_else(|e| e.into_inner()) = zone;
+}
+
+/// Zone times are shown in
+pub fn display_zone() -> DisplayZone {
+    *ZONE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// `at` in the display zone, formatted with a `strftime` `format`
+pub fn format_time(at: DateTime<Utc>, format: &str) -> String {
+    match display_zone() {
+        DisplayZone::Local => at.with_timezone(&Local).format(format).to_string(),
+        DisplayZone::Utc => at.format(format).to_string(),
+        DisplayZone::Named(zone) => at.with_timezone(&zone).format(format).to_string(),
+    }
+}
+
+/// `at` as a date and time in the display zone, with how long ago it was
+/// (`2026-10-16 14:05 (17m ago)`)
+pub fn format_when(at: DateTime<Utc>) -> String {
+    let ago = (Utc::now() - at).num_milliseconds() as f64 / 1000.0;
+    format!(
+        "{} ({})",
+        format_time(at, "%Y-%m-%d %H:%M"),
+        format_relative(ago)
+    )
+}
+
+/// Bars of a sparkline, lowest first
+const SPARK_BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// `values` as a sparkline (`▁▂▄▇█▅`), one bar each, scaled from the
+/// smallest to the largest so slow growth still shows (a steady nonzero
+/// value is a full line). Empty when there are fewer than two values,
+/// since one is no trend.
+pub fn sparkline(values: &[f64]) -> String {
+    if values.len() < 2 {
+        return String::new();
+    }
+    let max = values.iter().cloned().fold(f64::MIN, f64::max);
+    let min = values.iter().cloned().fold(f64::MAX, f64::min).max(0.0);
+    let min = if min == max { 0.0 } else { min };
+    values
+        .iter()
+        .map(|value| {
+            let level = if max > min {
+                ((value - min) / (max - min) * (SPARK_BARS.len() - 1) as f64).round()
+            } else {
+                0.0
+            };
+            SPARK_BARS[(level.max(0.0) as usize).min(SPARK_BARS.len() - 1)]
+        })
+        .collect()
+}
+
+/// How many recent output lines are kept for crash reports
+const RECENT_EVENTS: usize = 100;
+
+static RECENT: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// Remember an output line (at any level) for crash reports
+pub fn record(line: &str) {
+    let mut recent = RECENT.lock().unwrap_or_else(|e| e.into_inner());
+    if recent.len() == RECENT_EVENTS {
+        recent.pop_front();
+    }
+    recent.push_back(format!(
+        "{} {}",
+        chrono::Utc::now().format("%H:%M:%S"),
+        line
+    ));
+}
+
+/// Most recent output lines, oldest first.
+///
+/// Uses `try_lock` because it is called from the panic hook, which may run
+/// while the panicking thread holds the lock.
+pub fn recent_events() -> Vec<String> {
+    match RECENT.try_lock() {
+        Ok(recent) => recent.iter().cloned().collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Print (or send to the journal) one line at `level`; `None` is a warning
+#[doc(hidden)]
+pub fn emit(level: Option<Verbosity>, task_id: Option<&str>, line: &str) {
+    record(line);
+    match level {
+        None => {
+            if !journal::send(journal::PRIORITY_WARNING, line, task_id) {
+                eprintln!("{line}");
+            }
+        }
+        Some(level) if enabled(level) => {
+            let priority = if level == Verbosity::Debug {
+                journal::PRIORITY_DEBUG
+            } else {
+                journal::PRIORITY_INFO
+            };
+            if !journal::send(priority, line, task_id) {
+                println!("{line}");
+            }
+        }
+        Some(_) => {}
+    }
+}
+
+/// Warning on stderr, printed at every level (including `-q`).
+///
+/// Prefix with `task: id;` to tag the line with a task (a `TASK_ID`
+/// field in the journal).
+#[macro_export]
+macro_rules! warn {
+    () => {
+        if !$crate::journal::active() {
+            eprintln!()
+        }
+    };
+    (task: $task:expr; $($arg:tt)*) => {
+        $crate::output::emit(None, Some($task), &format!($($arg)*))
+    };
+    ($($arg:tt)*) => {
+        $crate::output::emit(None, None, &format!($($arg)*))
+    };
+}
+
+/// Default output, suppressed by `-q`
+#[macro_export]
+macro_rules! info {
+    () => {
+        $crate::__print_at!($crate::output::Verbosity::Normal)
+    };
+    ($($arg:tt)*) => {
+        $crate::__print_at!($crate::output::Verbosity::Normal, $($arg)*)
+    };
+}
+
+/// Resource details, shown with `-v`
+#[macro_export]
+macro_rules! verbose {
+    ($($arg:tt)*) => {
+        $crate::__print_at!($crate::output::Verbosity::Verbose, $($arg)*)
+    };
+}
+
+/// Debug internals, shown with `-vv`
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        $crate::__print_at!($crate::output::Verbosity::Debug, $($arg)*)
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __print_at {
+    ($level:expr) => {
+        if $crate::output::enabled($level) && !$crate::journal::active() {
+            println!();
+        }
+    };
+    ($level:expr, task: $task:expr; $($arg:tt)*) => {
+        $crate::output::emit(Some($level), Some($task), &format!($($arg)*))
+    };
+    ($level:expr, $($arg:tt)*) => {
+        $crate::output::emit(Some($level), None, &format!($($arg)*))
+    };
+}
+
+/// Fallback terminal width when stdout is not a TTY
+const DEFAULT_WIDTH: u16 = 120;
+
+/// Create a table with the watchdog's standard look.
+///
+/// Layout adapts to the terminal width; colors are only emitted when
+/// stdout is a TTY, so piped output stays plain.
+pub fn new_table<I, T>(header: I) -> Table
+where
+    I: IntoIterator<Item = T>,
+    T: Into<Cell>,
+{
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL_CONDENSED)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header(header);
+    table
+}
+
+/// Width available to a table (terminal width, or a sane default)
+pub fn table_width(table: &Table) -> u16 {
+    table.width().unwrap_or(DEFAULT_WIDTH)
+}
+
+/// Status cell colored green (running), cyan (queued/starting),
+/// yellow (completed/cancelled), red (failed), magenta (killed, timed out)
+/// or grey (unknown)
+pub fn status_cell(status: &TaskStatus) -> Cell {
+    let color = match status {
+        TaskStatus::Queued | TaskStatus::Starting => Color::Cyan,
+        TaskStatus::Running => Color::Green,
+        TaskStatus::Completed | TaskStatus::Cancelled => Color::Yellow,
+        TaskStatus::Failed => Color::Red,
+        TaskStatus::Killed | TaskStatus::TimedOut => Color::Magenta,
+        TaskStatus::Unknown => Color::DarkGrey,
+    };
+    Cell::new(status).fg(color)
+}
+
+/// Right-aligned cell for numeric columns
+pub fn number_cell<T: std::fmt::Display>(value: T) -> Cell {
+    Cell::new(value).set_alignment(CellAlignment::Right)
+}
+
+/// Truncate `text` to at most `max` characters, marking the cut with `…`.
+///
+/// With `-v` nothing is truncated, so the full command is always reachable.
+pub fn truncate(text: &str, max: usize) -> String {
+    if enabled(Verbosity::Verbose) || text.chars().count() <= max {
+        return text.to_string();
+    }
+    let keep = max.saturating_sub(1);
+    let mut out: String = text.chars().take(keep).collect();
+    out.push('…');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_flags() {
+        assert_eq!(Verbosity::from_flags(false, 0), Verbosity::Normal);
+        assert_eq!(Verbosity::from_flags(false, 1), Verbosity::Verbose);
+        assert_eq!(Verbosity::from_flags(false, 5), Verbosity::Debug);
+        assert_eq!(Verbosity::from_flags(true, 2), Verbosity::Quiet);
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("python very_long_script.py", 10), "python ve…");
+        assert_eq!(truncate("ünïcödé-text", 5), "ünïc…");
+    }
+
+    #[test]
+    fn test_sparkline() {
+        assert_eq!(sparkline(&[0.0, 50.0, 100.0, 25.0]), "▁▅█▃");
+        // Memory growing from 200MB to 270MB shows the growth
+        assert_eq!(sparkline(&[200.0, 235.0, 270.0]), "▁▅█");
+        assert_eq!(sparkline(&[4.0, 4.0]), "██");
+        assert_eq!(sparkline(&[0.0, 0.0]), "▁▁");
+        assert_eq!(sparkline(&[12.0]), "");
+    }
+
+    #[test]
+    fn test_display_zone_parses() {
+        assert_eq!("UTC".parse::<DisplayZone>().unwrap(), DisplayZone::Utc);
+        assert_eq!("local".parse::<DisplayZone>().unwrap(), DisplayZone::Local);
+        assert_eq!(
+            "Asia/Tokyo".parse::<DisplayZone>().unwrap(),
+            DisplayZone::Named(chrono_tz::Asia::Tokyo)
+        );
+        assert!("Mars/Olympus".parse::<DisplayZone>().is_err());
+    }
+
+    #[test]
+    fn test_record_keeps_recent_events() {
+        for i in 0..RECENT_EVENTS + 5 {
+            record(&format!("event {i}"));
+        }
+        let events = recent_events();
+        assert_eq!(events.len(), RECENT_EVENTS);
+        let newest = format!("event {}", RECENT_EVENTS + 4);
+        assert!(events.iter().any(|e| e.ends_with(&newest)));
+        assert!(!events.iter().any(|e| e.ends_with("event 0")));
+    }
+
+    #[test]
+    fn test_level_ordering() {
+        assert!(Verbosity::Debug > Verbosity::Verbose);
+        assert!(Verbosity::Verbose > Verbosity::Normal);
+        assert!(Verbosity::Normal > Verbosity::Quiet);
+    }
+}
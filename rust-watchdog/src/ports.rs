@@ -0,0 +1,111 @@
+//! Which process serves a TCP port: listening sockets from
+//! `/proc/net/tcp{,6}`, matched to a process through the socket inodes in
+//! `/proc/<pid>/fd`. Linux only; elsewhere nothing is known.
+
+#[cfg(target_os = "linux")]
+use std::fs;
+
+/// TCP state code for a listening socket in `/proc/net/tcp`
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+const TCP_LISTEN: &str = "0A";
+
+/// Inodes of sockets listening on `port` in a `/proc/net/tcp` table
+/// (`sl local_address rem_address st ... uid timeout inode`)
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_listeners(table: &str, port: u16) -> Vec<u64> {
+    table
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (_, hex_port) = fields.get(1)?.rsplit_once(':')?;
+            if *fields.get(3)? != TCP_LISTEN || u16::from_str_radix(hex_port, 16).ok()? != port {
+                return None;
+            }
+            fields.get(9)?.parse().ok()
+        })
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+fn listening_inodes(port: u16) -> Vec<u64> {
+    ["/proc/net/tcp", "/proc/net/tcp6"]
+        .iter()
+        .filter_map(|table| fs::read_to_string(table).ok())
+        .flat_map(|content| parse_listeners(&content, port))
+        .collect()
+}
+
+/// Whether anything listens on `port` (`None` where it can't be told)
+#[cfg(target_os = "linux")]
+pub fn is_listening(port: u16) -> Option<bool> {
+    Some(!listening_inodes(port).is_empty())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn is_listening(_port: u16) -> Option<bool> {
+    None
+}
+
+/// PID of the process listening on `port`. When several share the socket
+/// (a server and its forked workers) the lowest PID, usually the parent,
+/// wins. Processes whose descriptors we may not read are skipped.
+#[cfg(target_os = "linux")]
+pub fn find_listener(port: u16) -> Option<i32> {
+    let sockets: Vec<String> = listening_inodes(port)
+        .iter()
+        .map(|inode| format!("socket:[{inode}]"))
+        .collect();
+    if sockets.is_empty() {
+        return None;
+    }
+
+    let mut pids: Vec<i32> = fs::read_dir("/proc")
+        .ok()?
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+        .collect();
+    pids.sort_unstable();
+    pids.into_iter().find(|pid| {
+        fs::read_dir(format!("/proc/{pid}/fd"))
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(|fd| fs::read_link(fd.path()).ok())
+            .any(|target| sockets.iter().any(|s| target.as_os_str() == s.as_str()))
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn find_listener(_port: u16) -> Option<i32> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_listeners() {
+        let table = "\
+  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 00000000:2253 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 41234 1 0000000000000000 100 0 0 10 0
+   1: 0100007F:2253 0100007F:9C40 01 00000000:00000000 00:00000000 00000000  1000        0 41299 1 0000000000000000 20 4 30 10 -1
+   2: 0100007F:1F90 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 41500 1 0000000000000000 100 0 0 10 0
+";
+        // 0x2253 = 8787; the established connection on it doesn't count
+        assert_eq!(parse_listeners(table, 8787), [41234]);
+        assert_eq!(parse_listeners(table, 8080), [41500]);
+        assert!(parse_listeners(table, 3000).is_empty());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_finds_own_listener() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert_eq!(is_listening(port), Some(true));
+        assert_eq!(find_listener(port), Some(std::process::id() as i32));
+        drop(listener);
+        assert_eq!(is_listening(port), Some(false));
+    }
+}
@@ -1,12 +1,28 @@
-use crate::types::ResourceUsage;
-use anyhow::{Context, Result};
-use std::process::Command;
+use crate::types::{IoStats, NativeTask};
+use crate::{info, verbose};
+use anyhow::Result;
+use chrono::{Local, NaiveDateTime, TimeZone};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+#[cfg(unix)]
+use anyhow::Context;
+#[cfg(unix)]
+use std::time::{Duration, Instant};
 
 #[cfg(unix)]
 use nix::sys::signal::{kill, killpg, Signal};
 #[cfg(unix)]
 use nix::unistd::Pid;
 
+/// Environment variable `spawn` sets to the task ID in every process it
+/// starts, so children that leave the process group can still be found
+pub const TASK_ID_ENV: &str = "TASK_WATCHDOG_TASK_ID";
+
+/// Format of start times on Unix: what `ps -o lstart` printed when the
+/// watchdog still asked it, so registries written back then still match
+const LSTART_FORMAT: &str = "%a %b %e %H:%M:%S %Y";
+
 /// Process manager for native OS processes
 pub struct ProcessManager;
 
@@ -15,60 +31,73 @@ impl ProcessManager {
     /// Uses signal 0 which doesn't actually send a signal, just checks existence
     #[cfg(unix)]
     pub fn is_alive(pid: i32) -> bool {
-        kill(Pid::from_raw(pid), None).is_ok()
+        // EPERM: the process exists but belongs to another user
+        let exists = matches!(
+            kill(Pid::from_raw(pid), None),
+            Ok(()) | Err(nix::errno::Errno::EPERM)
+        );
+        // An exited process nobody has reaped yet still takes signals
+        exists && !Self::is_zombie(pid)
+    }
+
+    /// Whether `pid` has exited but not been reaped by its parent
+    #[cfg(unix)]
+    fn is_zombie(pid: i32) -> bool {
+        let Ok(stat) = std::fs::read_to_string(format!("/proc/{pid}/stat")) else {
+            return false;
+        };
+        // The state follows the command name, which may itself hold ") "
+        stat.rsplit_once(") ")
+            .is_some_and(|(_, rest)| rest.starts_with('Z'))
     }
 
     #[cfg(windows)]
     pub fn is_alive(pid: i32) -> bool {
-        // Windows implementation using tasklist
-        if let Ok(output) = Command::new("tasklist")
-            .args(&["/FI", &format!("PID eq {}", pid)])
-            .output()
-        {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            return stdout.contains(&pid.to_string());
-        }
-        false
+        win32::is_alive(pid)
     }
 
     /// Get process start time to prevent PID recycling confusion
     /// This is critical for ensuring we're checking the SAME process
-    #[cfg(unix)]
+    #[cfg(target_os = "linux")]
     pub fn get_start_time(pid: i32) -> Result<String> {
-        let output = Command::new("ps")
-            .args(["-p", &pid.to_string(), "-o", "lstart="])
-            .output()
-            .context("Failed to execute ps command")?;
+        use nix::unistd::{sysconf, SysconfVar};
 
-        if !output.status.success() {
-            anyhow::bail!("Process {} not found", pid);
-        }
+        let stat = std::fs::read_to_string(format!("/proc/{pid}/stat"))
+            .with_context(|| format!("Process {} not found", pid))?;
+        let fields = parse_stat(&stat).with_context(|| format!("Unreadable stat of {}", pid))?;
+        let ticks_per_second = sysconf(SysconfVar::CLK_TCK)
+            .ok()
+            .flatten()
+            .context("Failed to read the clock tick rate")? as u64;
+        let boot_time = boot_time().context("Failed to read the boot time")?;
+        Ok(format_start_time(
+            boot_time + fields.start_ticks / ticks_per_second,
+        ))
+    }
 
-        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    #[cfg(all(unix, not(target_os = "linux")))]
+    pub fn get_start_time(pid: i32) -> Result<String> {
+        use sysinfo::{Pid as SysPid, ProcessRefreshKind, System};
+
+        let mut sys = System::new();
+        let sys_pid = SysPid::from_u32(pid as u32);
+        sys.refresh_process_specifics(sys_pid, ProcessRefreshKind::new());
+        let process = sys
+            .process(sys_pid)
+            .with_context(|| format!("Process {} not found", pid))?;
+        Ok(format_start_time(process.start_time()))
     }
 
     #[cfg(windows)]
     pub fn get_start_time(pid: i32) -> Result<String> {
-        // Windows implementation using wmic
-        let output = Command::new("wmic")
-            .args(&[
-                "process",
-                "where",
-                &format!("ProcessId={}", pid),
-                "get",
-                "CreationDate",
-            ])
-            .output()
-            .context("Failed to execute wmic")?;
-
-        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        win32::start_time(pid)
     }
 
     /// Validate that a PID is the same process we started
     /// Prevents accidentally killing a different process if PID gets recycled
     pub fn validate_process(pid: i32, expected_start: &str) -> bool {
         if let Ok(actual_start) = Self::get_start_time(pid) {
-            return actual_start == expected_start;
+            return start_times_match(expected_start, &actual_start);
         }
         false
     }
@@ -78,16 +107,16 @@ impl ProcessManager {
     pub fn kill_process(pid: i32) -> Result<()> {
         // Try SIGTERM first (graceful)
         if let Ok(()) = kill(Pid::from_raw(pid), Signal::SIGTERM) {
-            println!("  Sent SIGTERM to PID {}", pid);
+            verbose!("  Sent SIGTERM to PID {}", pid);
 
             // Wait 2 seconds
-            std::thread::sleep(std::time::Duration::from_secs(2));
+            std::thread::sleep(Duration::from_secs(2));
 
             // Check if still alive
             if Self::is_alive(pid) {
                 // Force kill with SIGKILL
                 kill(Pid::from_raw(pid), Signal::SIGKILL).context("Failed to send SIGKILL")?;
-                println!("  Sent SIGKILL to PID {}", pid);
+                verbose!("  Sent SIGKILL to PID {}", pid);
             }
         }
 
@@ -96,34 +125,30 @@ impl ProcessManager {
 
     #[cfg(windows)]
     pub fn kill_process(pid: i32) -> Result<()> {
-        Command::new("taskkill")
-            .args(&["/PID", &pid.to_string(), "/F"])
-            .output()
-            .context("Failed to kill process")?;
-        Ok(())
+        win32::terminate(pid)
     }
 
     /// Kill entire process group (handles process trees)
     /// This is the key to cleaning up all child processes
     #[cfg(unix)]
     pub fn kill_process_group(pgid: i32) -> Result<()> {
-        println!("🔪 Killing process group {}", pgid);
+        info!("🔪 Killing process group {}", pgid);
 
         // SIGTERM first (graceful shutdown)
         if let Ok(()) = killpg(Pid::from_raw(pgid), Signal::SIGTERM) {
-            println!("  Sent SIGTERM to PGID {}", pgid);
+            verbose!("  Sent SIGTERM to PGID {}", pgid);
 
             // Wait 2 seconds for graceful shutdown
-            std::thread::sleep(std::time::Duration::from_secs(2));
+            std::thread::sleep(Duration::from_secs(2));
 
             // Check if any process in group still alive
             if Self::is_alive(pgid) {
                 // Force kill entire group
                 killpg(Pid::from_raw(pgid), Signal::SIGKILL)
                     .context("Failed to send SIGKILL to process group")?;
-                println!("  Sent SIGKILL to PGID {}", pgid);
+                verbose!("  Sent SIGKILL to PGID {}", pgid);
             } else {
-                println!("  ✅ Process group terminated gracefully");
+                verbose!("  ✅ Process group terminated gracefully");
             }
         }
 
@@ -132,63 +157,759 @@ impl ProcessManager {
 
     #[cfg(windows)]
     pub fn kill_process_group(pgid: i32) -> Result<()> {
-        // Windows doesn't have process groups in the same way
-        // Fall back to single process kill
+        // No process groups: without its job (see `kill_task`), only the
+        // process itself can be killed
         Self::kill_process(pgid)
     }
 
-    /// Get process resource usage (CPU and memory)
-    pub fn get_resource_usage(pid: i32) -> Option<ResourceUsage> {
-        use sysinfo::{Pid as SysPid, System};
+    /// Kill a native task with everything it started: its process group,
+    /// or on Windows its job object (just its process if it has none)
+    pub fn kill_task(native: &NativeTask) -> Result<()> {
+        #[cfg(windows)]
+        if let Some(job) = &native.job {
+            info!("🔪 Killing job {}", job);
+            match win32::terminate_job(job) {
+                Ok(true) => return Ok(()),
+                // The task's own process may still run outside of it
+                Ok(false) => verbose!("  No job {}; killing PID {}", job, native.pgid),
+                Err(e) => verbose!("  {:#}; killing PID {}", e, native.pgid),
+            }
+            if !Self::is_alive(native.pgid) {
+                return Ok(());
+            }
+        }
+        Self::kill_process_group(native.pgid)
+    }
 
-        let mut sys = System::new_all();
-        sys.refresh_all();
+    /// Why `pid` mustn't be killed with everything it started: it is init,
+    /// the watchdog, or a parent or process group the watchdog runs in, or
+    /// (unless `force`) it belongs to another user
+    #[cfg(unix)]
+    pub fn ensure_killable(pid: i32, force: bool) -> Result<()> {
+        if pid <= 1 {
+            anyhow::bail!("Refusing to kill PID {}", pid);
+        }
+        if !Self::is_alive(pid) {
+            anyhow::bail!("No process with PID {}", pid);
+        }
+        let parents = parent_pids();
+        let mut ancestor = Some(std::process::id() as i32);
+        while let Some(current) = ancestor.filter(|pid| *pid > 1) {
+            if current == pid {
+                anyhow::bail!("PID {} is the watchdog or one of its parents", pid);
+            }
+            ancestor = parents.get(&current).copied();
+        }
+        if nix::unistd::getpgrp().as_raw() == pid {
+            anyhow::bail!("PID {} leads the watchdog's own process group", pid);
+        }
+        if !force {
+            let uid = nix::unistd::getuid().as_raw();
+            if let Some(owner) = process_uid(pid).filter(|owner| *owner != uid) {
+                let user = nix::unistd::User::from_uid(owner.into())
+                    .ok()
+                    .flatten()
+                    .map_or_else(|| owner.to_string(), |user| user.name);
+                anyhow::bail!(
+                    "PID {} belongs to user {}. Ask them, or pass --force",
+                    pid,
+                    user
+                );
+            }
+        }
+        Ok(())
+    }
 
-        let sys_pid = SysPid::from_u32(pid as u32);
+    /// `pid` and every process descended from it, parents first
+    pub fn process_tree(pid: i32) -> Vec<i32> {
+        let mut children: std::collections::HashMap<i32, Vec<i32>> = Default::default();
+        for (child, parent) in parent_pids() {
+            children.entry(parent).or_default().push(child);
+        }
+        let mut tree = vec![pid];
+        let mut i = 0;
+        while let Some(current) = tree.get(i).copied() {
+            if let Some(kids) = children.get_mut(&current) {
+                kids.sort_unstable();
+                tree.append(kids);
+            }
+            i += 1;
+        }
+        tree
+    }
 
-        if let Some(process) = sys.process(sys_pid) {
-            return Some(ResourceUsage {
-                cpu_percent: process.cpu_usage(),
-                memory_kb: process.memory(),
-            });
+    /// Parent of `pid`, its parent, and so on up to (not including) init
+    pub fn ancestors(pid: i32) -> Vec<i32> {
+        let parents = parent_pids();
+        let mut ancestors = Vec::new();
+        let mut current = pid;
+        while let Some(parent) = parents.get(&current).copied() {
+            if parent <= 1 || ancestors.contains(&parent) {
+                break;
+            }
+            ancestors.push(parent);
+            current = parent;
         }
+        ancestors
+    }
 
-        None
+    /// ID of the container a process runs in, from its cgroup (Linux only)
+    pub fn container_id(pid: i32) -> Option<String> {
+        let cgroup = std::fs::read_to_string(format!("/proc/{pid}/cgroup")).ok()?;
+        parse_cgroup_container(&cgroup)
     }
 
-    /// Get all PIDs matching an environment variable tag
-    /// This allows finding orphaned child processes
+    /// Send `signal` to `pid` and everything it started (its whole process
+    /// group too, when it leads one), give them `grace` to exit, then
+    /// SIGKILL whatever is left, including children started meanwhile.
+    /// Check [`ProcessManager::ensure_killable`] first.
     #[cfg(unix)]
-    pub fn find_processes_by_env(env_key: &str, env_value: &str) -> Vec<i32> {
-        let mut pids = Vec::new();
-
-        let output = Command::new("ps")
-            .args(["axe"]) // 'e' shows environment
-            .output();
-
-        if let Ok(output) = output {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            let search = format!("{}={}", env_key, env_value);
-
-            for line in stdout.lines() {
-                if line.contains(&search) {
-                    if let Some(pid_str) = line.split_whitespace().next() {
-                        if let Ok(pid) = pid_str.parse::<i32>() {
-                            pids.push(pid);
-                        }
+    pub fn kill_tree(pid: i32, signal: Signal, grace: Duration) -> Result<TreeKill> {
+        let pgid = Self::process_group(pid).filter(|pgid| *pgid == pid);
+        let processes = Self::process_tree(pid);
+        let send = |pids: &[i32], signal: Signal| {
+            if let Some(pgid) = pgid {
+                let _ = killpg(Pid::from_raw(pgid), signal);
+            }
+            // Including those that left the group (setsid, daemons)
+            for pid in pids {
+                let _ = kill(Pid::from_raw(*pid), signal);
+            }
+        };
+        info!(
+            "🔪 Sending {} to PID {} and {} descendants{}",
+            signal,
+            pid,
+            processes.len() - 1,
+            pgid.map_or_else(String::new, |pgid| format!(" (process group {pgid})"))
+        );
+        send(&processes, signal);
+
+        // What's left of the tree, and of the group
+        let running = || -> Vec<i32> {
+            let mut running: Vec<i32> = processes
+                .iter()
+                .filter(|pid| Self::is_alive(**pid))
+                .flat_map(|pid| Self::process_tree(*pid))
+                .chain(pgid.into_iter().flat_map(|pgid| {
+                    parent_pids()
+                        .into_keys()
+                        .filter(move |pid| Self::process_group(*pid) == Some(pgid))
+                }))
+                .filter(|pid| Self::is_alive(*pid))
+                .collect();
+            running.sort_unstable();
+            running.dedup();
+            running
+        };
+        let deadline = Instant::now() + grace;
+        let mut left = running();
+        while signal != Signal::SIGKILL && !left.is_empty() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(100));
+            left = running();
+        }
+
+        let mut killed = Vec::new();
+        if signal != Signal::SIGKILL && !left.is_empty() {
+            killed = left;
+            send(&killed, Signal::SIGKILL);
+            verbose!("  Sent SIGKILL to {} remaining processes", killed.len());
+        }
+        Ok(TreeKill {
+            pid,
+            pgid,
+            signal: signal.to_string(),
+            processes,
+            killed,
+        })
+    }
+
+    /// Put `pid`, started suspended (see `tasks::command_for`), in a job
+    /// object of its own and let it run. Returns the job's name, or `None`
+    /// if it could not be created: the task then runs outside any job.
+    #[cfg(windows)]
+    pub fn start_in_job(task_id: &str, pid: i32) -> Option<String> {
+        let job = format!("Local\\task-watchdog-{task_id}-{pid}");
+        let assigned = win32::assign_to_job(pid, &job);
+        if let Err(e) = win32::resume(pid) {
+            // Rather than leave it suspended forever; the daemon finds it dead
+            crate::warn!("⚠️  Could not resume process {}: {:#}", pid, e);
+            let _ = win32::terminate(pid);
+        }
+        match assigned {
+            Ok(()) => Some(job),
+            Err(e) => {
+                crate::warn!("⚠️  Task {} runs outside a job object: {:#}", task_id, e);
+                None
+            }
+        }
+    }
+
+    /// Arguments of a running process (just its name for kernel threads
+    /// and others that hide their arguments) and its working directory
+    pub fn command_argv(pid: i32) -> Option<(Vec<String>, Option<PathBuf>)> {
+        use sysinfo::{Pid as SysPid, ProcessRefreshKind, System, UpdateKind};
+
+        let mut sys = System::new();
+        let sys_pid = SysPid::from_u32(pid as u32);
+        sys.refresh_process_specifics(
+            sys_pid,
+            ProcessRefreshKind::new()
+                .with_cmd(UpdateKind::Always)
+                .with_cwd(UpdateKind::Always),
+        );
+        let process = sys.process(sys_pid)?;
+        let argv = match process.cmd() {
+            [] => vec![process.name().to_string()],
+            cmd => cmd.to_vec(),
+        };
+        Some((argv, process.cwd().map(Path::to_path_buf)))
+    }
+
+    /// Value of `key` in a running process's environment (only readable
+    /// for our own user's processes)
+    pub fn env_var(pid: i32, key: &str) -> Option<String> {
+        use sysinfo::{Pid as SysPid, ProcessRefreshKind, System, UpdateKind};
+
+        let mut sys = System::new();
+        let sys_pid = SysPid::from_u32(pid as u32);
+        sys.refresh_process_specifics(
+            sys_pid,
+            ProcessRefreshKind::new().with_environ(UpdateKind::Always),
+        );
+        let prefix = format!("{key}=");
+        sys.process(sys_pid)?
+            .environ()
+            .iter()
+            .find_map(|var| var.strip_prefix(&prefix).map(str::to_string))
+    }
+
+    /// Command line of a running process, arguments joined by spaces
+    pub fn command_line(pid: i32) -> Option<String> {
+        Self::command_argv(pid).map(|(argv, _)| argv.join(" "))
+    }
+
+    /// Process group of a running process
+    #[cfg(unix)]
+    pub fn process_group(pid: i32) -> Option<i32> {
+        nix::unistd::getpgid(Some(Pid::from_raw(pid)))
+            .ok()
+            .map(Pid::as_raw)
+    }
+
+    #[cfg(windows)]
+    pub fn process_group(pid: i32) -> Option<i32> {
+        // No process groups; a task's "group" is the process itself
+        Some(pid)
+    }
+
+    /// Cumulative CPU time (user + system) of every process in group `pgid`,
+    /// including children they already reaped, in seconds
+    pub fn group_cpu_seconds(pgid: i32) -> Option<f64> {
+        Self::group_usage(pgid).map(|usage| usage.cpu_seconds)
+    }
+
+    /// CPU time, resident memory, threads and open files of the processes
+    /// in group `pgid`, and how many there are
+    #[cfg(target_os = "linux")]
+    pub fn group_usage(pgid: i32) -> Option<GroupUsage> {
+        use nix::unistd::{sysconf, SysconfVar};
+
+        let ticks_per_second = sysconf(SysconfVar::CLK_TCK).ok().flatten()? as f64;
+        let page_kb = sysconf(SysconfVar::PAGE_SIZE).ok().flatten()? as u64 / 1024;
+        let mut ticks = 0;
+        let mut pages = 0;
+        let mut processes = 0;
+        let mut threads = 0;
+        let mut open_files = 0;
+        let mut io = IoStats::default();
+        for (_, dir) in proc_dirs() {
+            let Ok(stat) = std::fs::read_to_string(dir.join("stat")) else {
+                continue;
+            };
+            if let Some(fields) = parse_stat(&stat) {
+                if fields.pgrp == pgid {
+                    ticks += fields.cpu_ticks;
+                    pages += fields.rss_pages;
+                    processes += 1;
+                    threads += fields.threads;
+                    // Unreadable for other users' processes
+                    if let Ok(fds) = std::fs::read_dir(dir.join("fd")) {
+                        open_files += fds.count() as u64;
+                    }
+                    if let Some(process_io) = std::fs::read_to_string(dir.join("io"))
+                        .ok()
+                        .and_then(|content| parse_io(&content))
+                    {
+                        io.read_bytes += process_io.read_bytes;
+                        io.written_bytes += process_io.written_bytes;
                     }
                 }
             }
         }
+        (processes > 0).then(|| GroupUsage {
+            cpu_seconds: ticks as f64 / ticks_per_second,
+            memory_kb: pages * page_kb,
+            processes,
+            threads,
+            open_files,
+            io,
+        })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn group_usage(_pgid: i32) -> Option<GroupUsage> {
+        None
+    }
 
+    /// Get all PIDs matching an environment variable tag
+    /// This allows finding orphaned child processes (only our own user's:
+    /// other environments are unreadable)
+    #[cfg(target_os = "linux")]
+    pub fn find_processes_by_env(env_key: &str, env_value: &str) -> Vec<i32> {
+        let search = format!("{}={}", env_key, env_value);
+        let mut pids: Vec<i32> = proc_dirs()
+            .filter(|(_, dir)| {
+                std::fs::read(dir.join("environ")).is_ok_and(|environ| {
+                    environ
+                        .split(|b| *b == 0)
+                        .any(|var| var == search.as_bytes())
+                })
+            })
+            .map(|(pid, _)| pid)
+            .collect();
+        pids.sort_unstable();
         pids
     }
 
-    #[cfg(windows)]
-    pub fn find_processes_by_env(_env_key: &str, _env_value: &str) -> Vec<i32> {
-        // Windows doesn't easily support env var inspection
-        // Would need WMI queries - skip for now
-        Vec::new()
+    #[cfg(not(target_os = "linux"))]
+    pub fn find_processes_by_env(env_key: &str, env_value: &str) -> Vec<i32> {
+        use sysinfo::{ProcessRefreshKind, System, UpdateKind};
+
+        let mut sys = System::new();
+        sys.refresh_processes_specifics(ProcessRefreshKind::new().with_environ(UpdateKind::Always));
+        let search = format!("{}={}", env_key, env_value);
+        let mut pids: Vec<i32> = sys
+            .processes()
+            .iter()
+            .filter(|(_, process)| process.environ().iter().any(|var| *var == search))
+            .map(|(pid, _)| pid.as_u32() as i32)
+            .collect();
+        pids.sort_unstable();
+        pids
+    }
+
+    /// Repository root containing `dir`: the nearest ancestor with a `.git`
+    /// entry (a directory, or a file for worktrees and submodules)
+    pub fn repo_root(dir: &Path) -> Option<PathBuf> {
+        dir.ancestors()
+            .find(|d| d.join(".git").exists())
+            .map(Path::to_path_buf)
+    }
+
+    /// Kernel boot ID, which changes on every boot (Linux only)
+    pub fn boot_id() -> Option<String> {
+        std::fs::read_to_string("/proc/sys/kernel/random/boot_id")
+            .ok()
+            .map(|id| id.trim().to_string())
+            .filter(|id| !id.is_empty())
+    }
+
+    /// Name of this machine
+    pub fn hostname() -> Option<String> {
+        #[cfg(unix)]
+        if let Some(name) = nix::unistd::gethostname()
+            .ok()
+            .and_then(|h| h.into_string().ok())
+        {
+            return Some(name);
+        }
+        std::env::var("HOSTNAME")
+            .or_else(|_| std::env::var("COMPUTERNAME"))
+            .ok()
+            .filter(|name| !name.is_empty())
+    }
+
+    /// Name of the user running the watchdog (falls back to `$USER`)
+    pub fn current_user() -> Option<String> {
+        #[cfg(unix)]
+        if let Ok(Some(user)) = nix::unistd::User::from_uid(nix::unistd::getuid()) {
+            return Some(user.name);
+        }
+        std::env::var("USER")
+            .or_else(|_| std::env::var("USERNAME"))
+            .ok()
+            .filter(|name| !name.is_empty())
+    }
+}
+
+/// What [`ProcessManager::kill_tree`] did
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TreeKill {
+    pub pid: i32,
+    /// Process group signalled as a whole, as `pid` leads it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pgid: Option<i32>,
+    /// `SIGTERM`, ...
+    pub signal: String,
+    /// `pid` and its descendants when the signal was sent
+    pub processes: Vec<i32>,
+    /// Processes still running after the grace period, which got SIGKILL
+    pub killed: Vec<i32>,
+}
+
+/// A signal by name (`TERM`, `SIGTERM`, case-insensitive) or number
+#[cfg(unix)]
+pub fn parse_signal(s: &str) -> Result<Signal> {
+    let name = s.trim().to_uppercase();
+    if let Ok(number) = name.parse::<i32>() {
+        return Signal::try_from(number).map_err(|_| anyhow::anyhow!("Unknown signal {}", number));
+    }
+    let name = if name.starts_with("SIG") {
+        name
+    } else {
+        format!("SIG{name}")
+    };
+    name.parse()
+        .map_err(|_| anyhow::anyhow!("Unknown signal '{}' (e.g. TERM, INT, KILL or 15)", s))
+}
+
+/// The container ID in a `/proc/<pid>/cgroup` file: a 64-hex-digit path
+/// segment, as Docker (`/docker/<id>`, `docker-<id>.scope`), Podman and
+/// containerd name them
+fn parse_cgroup_container(cgroup: &str) -> Option<String> {
+    cgroup
+        .lines()
+        .filter_map(|line| line.splitn(3, ':').nth(2))
+        .flat_map(|path| path.split('/'))
+        .map(|segment| {
+            let segment = segment.strip_suffix(".scope").unwrap_or(segment);
+            segment.rsplit('-').next().unwrap_or(segment)
+        })
+        .find(|id| id.len() == 64 && id.chars().all(|c| c.is_ascii_hexdigit()))
+        .map(str::to_string)
+}
+
+/// The parent of every process
+#[cfg(target_os = "linux")]
+fn parent_pids() -> std::collections::HashMap<i32, i32> {
+    proc_dirs()
+        .filter_map(|(pid, dir)| {
+            let stat = std::fs::read_to_string(dir.join("stat")).ok()?;
+            Some((pid, parse_stat(&stat)?.ppid))
+        })
+        .collect()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn parent_pids() -> std::collections::HashMap<i32, i32> {
+    let mut sys = sysinfo::System::new();
+    sys.refresh_processes_specifics(sysinfo::ProcessRefreshKind::new());
+    sys.processes()
+        .iter()
+        .filter_map(|(pid, process)| Some((pid.as_u32() as i32, process.parent()?.as_u32() as i32)))
+        .collect()
+}
+
+/// User ID a process runs as
+#[cfg(target_os = "linux")]
+fn process_uid(pid: i32) -> Option<u32> {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(format!("/proc/{pid}"))
+        .ok()
+        .map(|meta| meta.uid())
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn process_uid(pid: i32) -> Option<u32> {
+    use sysinfo::{Pid as SysPid, ProcessRefreshKind, System, UpdateKind};
+
+    let mut sys = System::new();
+    let sys_pid = SysPid::from_u32(pid as u32);
+    sys.refresh_process_specifics(
+        sys_pid,
+        ProcessRefreshKind::new().with_user(UpdateKind::Always),
+    );
+    sys.process(sys_pid)?.user_id().map(|uid| **uid)
+}
+
+/// What a process group uses, summed over its processes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GroupUsage {
+    /// Cumulative CPU time, including reaped children
+    pub cpu_seconds: f64,
+    /// Resident memory
+    pub memory_kb: u64,
+    pub processes: u64,
+    /// Not known for containers (0)
+    pub threads: u64,
+    /// File descriptors, sockets and pipes included; not known for
+    /// containers (0)
+    pub open_files: u64,
+    /// Bytes read from and written to storage, of the processes whose
+    /// `/proc/<pid>/io` is readable (the user's own)
+    pub io: IoStats,
+}
+
+/// `secs` since the epoch as a start time
+#[cfg_attr(windows, allow(dead_code))]
+fn format_start_time(secs: u64) -> String {
+    match Local.timestamp_opt(secs as i64, 0).single() {
+        Some(at) => at.format(LSTART_FORMAT).to_string(),
+        None => secs.to_string(),
+    }
+}
+
+/// Whether a recorded start time and a process's current one are the same
+/// moment. Those `ps` recorded pad the day differently, and may be a second
+/// off the boot-relative time read from `/proc`; anything else (Windows
+/// FILETIMEs, test values) has to match exactly.
+fn start_times_match(expected: &str, actual: &str) -> bool {
+    if expected == actual {
+        return true;
+    }
+    let parse = |s: &str| {
+        let s = s.split_whitespace().collect::<Vec<_>>().join(" ");
+        NaiveDateTime::parse_from_str(&s, LSTART_FORMAT).ok()
+    };
+    match (parse(expected), parse(actual)) {
+        (Some(expected), Some(actual)) => (expected - actual).num_seconds().abs() <= 1,
+        _ => false,
+    }
+}
+
+/// When the machine booted, in seconds since the epoch
+#[cfg(target_os = "linux")]
+fn boot_time() -> Option<u64> {
+    std::fs::read_to_string("/proc/stat")
+        .ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix("btime "))?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Every process's PID and `/proc` directory
+#[cfg(target_os = "linux")]
+fn proc_dirs() -> impl Iterator<Item = (i32, PathBuf)> {
+    std::fs::read_dir("/proc")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let pid = entry.file_name().to_str()?.parse().ok()?;
+            Some((pid, entry.path()))
+        })
+}
+
+/// The fields of a `/proc/<pid>/stat` line the watchdog reads
+#[derive(Debug, PartialEq)]
+struct StatFields {
+    ppid: i32,
+    pgrp: i32,
+    /// utime+stime+cutime+cstime, in clock ticks
+    cpu_ticks: u64,
+    threads: u64,
+    /// Clock ticks after boot the process started
+    start_ticks: u64,
+    rss_pages: u64,
+}
+
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_stat(stat: &str) -> Option<StatFields> {
+    // The command name may contain spaces and parens; fields follow the last ')'
+    let fields: Vec<&str> = stat[stat.rfind(')')? + 1..].split_whitespace().collect();
+    let ppid = fields.get(1)?.parse().ok()?;
+    let pgrp = fields.get(2)?.parse().ok()?;
+    let cpu_ticks = fields
+        .get(11..15)?
+        .iter()
+        .map(|f| f.parse::<u64>().ok())
+        .sum::<Option<u64>>()?;
+    let threads = fields.get(17)?.parse().ok()?;
+    let start_ticks = fields.get(19)?.parse().ok()?;
+    let rss_pages = fields.get(21)?.parse().ok()?;
+    Some(StatFields {
+        ppid,
+        pgrp,
+        cpu_ticks,
+        threads,
+        start_ticks,
+        rss_pages,
+    })
+}
+
+/// Storage I/O from a `/proc/<pid>/io` file: `read_bytes` and
+/// `write_bytes`, which unlike `rchar`/`wchar` leave out reads served from
+/// the page cache and pipe traffic
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_io(content: &str) -> Option<IoStats> {
+    let field = |name: &str| {
+        content.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            (key == name).then(|| value.trim().parse().ok())?
+        })
+    };
+    Some(IoStats {
+        read_bytes: field("read_bytes")?,
+        written_bytes: field("write_bytes")?,
+    })
+}
+
+/// The Win32 calls behind the Windows side of [`ProcessManager`]: process
+/// queries, and the job objects that hold each spawned task's process tree
+#[cfg(windows)]
+mod win32 {
+    use anyhow::{bail, Context, Result};
+    use windows_sys::Win32::Foundation::{
+        CloseHandle, GetLastError, ERROR_ACCESS_DENIED, FILETIME, HANDLE, INVALID_HANDLE_VALUE,
+        STILL_ACTIVE,
+    };
+    use windows_sys::Win32::System::Diagnostics::ToolHelp::{
+        CreateToolhelp32Snapshot, Thread32First, Thread32Next, TH32CS_SNAPTHREAD, THREADENTRY32,
+    };
+    use windows_sys::Win32::System::JobObjects::{
+        AssignProcessToJobObject, CreateJobObjectW, OpenJobObjectW, TerminateJobObject,
+    };
+    use windows_sys::Win32::System::SystemServices::JOB_OBJECT_TERMINATE;
+    use windows_sys::Win32::System::Threading::{
+        GetExitCodeProcess, GetProcessTimes, OpenProcess, OpenThread, ResumeThread,
+        TerminateProcess, PROCESS_QUERY_LIMITED_INFORMATION, PROCESS_SET_QUOTA, PROCESS_TERMINATE,
+        THREAD_SUSPEND_RESUME,
+    };
+
+    /// Exit code of processes killed by the watchdog
+    const KILLED_EXIT_CODE: u32 = 1;
+
+    /// An open handle, closed when dropped
+    struct Handle(HANDLE);
+
+    impl Handle {
+        /// `handle`, unless the call that returned it failed
+        fn new(handle: HANDLE) -> Option<Self> {
+            (handle != 0 && handle != INVALID_HANDLE_VALUE).then_some(Self(handle))
+        }
+    }
+
+    impl Drop for Handle {
+        fn drop(&mut self) {
+            // SAFETY: the handle is open and only closed here
+            unsafe { CloseHandle(self.0) };
+        }
+    }
+
+    /// `s` as a NUL-terminated UTF-16 string
+    fn wide(s: &str) -> Vec<u16> {
+        s.encode_utf16().chain(std::iter::once(0)).collect()
+    }
+
+    fn open_process(pid: i32, access: u32) -> Option<Handle> {
+        // SAFETY: no pointers; failure returns a null handle
+        Handle::new(unsafe { OpenProcess(access, 0, pid as u32) })
+    }
+
+    pub fn is_alive(pid: i32) -> bool {
+        let Some(process) = open_process(pid, PROCESS_QUERY_LIMITED_INFORMATION) else {
+            // It exists, but belongs to a more privileged user
+            // SAFETY: no arguments
+            return unsafe { GetLastError() } == ERROR_ACCESS_DENIED;
+        };
+        let mut code = 0;
+        // SAFETY: `code` outlives the call
+        let ok = unsafe { GetExitCodeProcess(process.0, &mut code) } != 0;
+        ok && code == STILL_ACTIVE as u32
+    }
+
+    /// When `pid` was created, in 100ns intervals since 1601
+    pub fn start_time(pid: i32) -> Result<String> {
+        let Some(process) = open_process(pid, PROCESS_QUERY_LIMITED_INFORMATION) else {
+            bail!("Process {} not found", pid);
+        };
+        let zero = FILETIME {
+            dwLowDateTime: 0,
+            dwHighDateTime: 0,
+        };
+        let [mut created, mut exited, mut kernel, mut user] = [zero; 4];
+        // SAFETY: the four FILETIMEs outlive the call
+        let ok = unsafe {
+            GetProcessTimes(process.0, &mut created, &mut exited, &mut kernel, &mut user)
+        } != 0;
+        if !ok {
+            bail!("Failed to read the start time of process {}", pid);
+        }
+        let ticks = (created.dwHighDateTime as u64) << 32 | created.dwLowDateTime as u64;
+        Ok(ticks.to_string())
+    }
+
+    pub fn terminate(pid: i32) -> Result<()> {
+        let process = open_process(pid, PROCESS_TERMINATE)
+            .with_context(|| format!("Failed to open process {}", pid))?;
+        // SAFETY: no pointers
+        if unsafe { TerminateProcess(process.0, KILLED_EXIT_CODE) } == 0 {
+            bail!("Failed to terminate process {}", pid);
+        }
+        Ok(())
+    }
+
+    /// Put `pid`, started suspended, in a new job object named `job`, so
+    /// every process it starts joins it too
+    pub fn assign_to_job(pid: i32, job: &str) -> Result<()> {
+        let name = wide(job);
+        // SAFETY: `name` is NUL-terminated and outlives the call
+        let job_handle = Handle::new(unsafe { CreateJobObjectW(std::ptr::null(), name.as_ptr()) })
+            .with_context(|| format!("Failed to create job object {}", job))?;
+        let process = open_process(pid, PROCESS_SET_QUOTA | PROCESS_TERMINATE)
+            .with_context(|| format!("Failed to open process {}", pid))?;
+        // SAFETY: both handles are open
+        if unsafe { AssignProcessToJobObject(job_handle.0, process.0) } == 0 {
+            bail!("Failed to put process {} in job object {}", pid, job);
+        }
+        // Closing our handle leaves the job (and its name) to the
+        // processes in it, for as long as any of them runs
+        Ok(())
+    }
+
+    /// Resume the threads of `pid`, started suspended
+    pub fn resume(pid: i32) -> Result<()> {
+        // SAFETY: no pointers
+        let snapshot = Handle::new(unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPTHREAD, 0) })
+            .context("Failed to list threads")?;
+        // SAFETY: THREADENTRY32 is plain data
+        let mut entry: THREADENTRY32 = unsafe { std::mem::zeroed() };
+        entry.dwSize = std::mem::size_of::<THREADENTRY32>() as u32;
+        // SAFETY: `entry` is sized and outlives the calls
+        let mut more = unsafe { Thread32First(snapshot.0, &mut entry) } != 0;
+        while more {
+            if entry.th32OwnerProcessID == pid as u32 {
+                // SAFETY: no pointers
+                let thread = unsafe { OpenThread(THREAD_SUSPEND_RESUME, 0, entry.th32ThreadID) };
+                if let Some(thread) = Handle::new(thread) {
+                    // SAFETY: the handle is open
+                    unsafe { ResumeThread(thread.0) };
+                }
+            }
+            // SAFETY: as for Thread32First
+            more = unsafe { Thread32Next(snapshot.0, &mut entry) } != 0;
+        }
+        Ok(())
+    }
+
+    /// Terminate every process in the job object named `job`. False if
+    /// there is no such job: its processes have all exited.
+    pub fn terminate_job(job: &str) -> Result<bool> {
+        let name = wide(job);
+        // SAFETY: `name` is NUL-terminated and outlives the call
+        let Some(job_handle) =
+            Handle::new(unsafe { OpenJobObjectW(JOB_OBJECT_TERMINATE, 0, name.as_ptr()) })
+        else {
+            return Ok(false);
+        };
+        // SAFETY: the handle is open
+        if unsafe { TerminateJobObject(job_handle.0, KILLED_EXIT_CODE) } == 0 {
+            bail!("Failed to terminate job object {}", job);
+        }
+        Ok(true)
     }
 }
 
@@ -196,6 +917,38 @@ impl ProcessManager {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_stat() {
+        let stat = "4242 (python (worker)) S 4200 4200 4200 0 -1 4194304 \
+                    1500 0 0 0 250 40 7 3 20 0 4 0 12345 98304000 2400";
+        assert_eq!(
+            parse_stat(stat),
+            Some(StatFields {
+                ppid: 4200,
+                pgrp: 4200,
+                cpu_ticks: 300,
+                threads: 4,
+                start_ticks: 12345,
+                rss_pages: 2400,
+            })
+        );
+        assert_eq!(parse_stat("garbage"), None);
+    }
+
+    #[test]
+    fn test_parse_io() {
+        let io = "rchar: 32768\nwchar: 4096\nsyscr: 12\nsyscw: 3\n\
+                  read_bytes: 8192\nwrite_bytes: 1024\ncancelled_write_bytes: 0\n";
+        assert_eq!(
+            parse_io(io),
+            Some(IoStats {
+                read_bytes: 8192,
+                written_bytes: 1024,
+            })
+        );
+        assert_eq!(parse_io("rchar: 1\n"), None);
+    }
+
     #[test]
     fn test_is_alive() {
         // Current process should be alive
@@ -211,5 +964,164 @@ mod tests {
         let pid = std::process::id() as i32;
         let start_time = ProcessManager::get_start_time(pid);
         assert!(start_time.is_ok());
+        assert!(ProcessManager::validate_process(pid, &start_time.unwrap()));
+        assert!(ProcessManager::get_start_time(999999).is_err());
+    }
+
+    #[test]
+    fn test_start_times_match_ps_format() {
+        // As `ps -o lstart` printed them
+        assert!(start_times_match(
+            "Fri Oct  2 09:05:01 2026",
+            "Fri Oct  2 09:05:01 2026"
+        ));
+        assert!(start_times_match(
+            "Fri Oct 2 09:05:01 2026",
+            "Fri Oct  2 09:05:02 2026"
+        ));
+        assert!(!start_times_match(
+            "Fri Oct  2 09:05:01 2026",
+            "Fri Oct  2 09:05:09 2026"
+        ));
+        assert!(!start_times_match(
+            "133512345678901234",
+            "133512345678901235"
+        ));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_find_processes_by_env() {
+        let tag = format!("find-me-{}", std::process::id());
+        let mut child = std::process::Command::new("sleep")
+            .arg("10")
+            .env(TASK_ID_ENV, &tag)
+            .spawn()
+            .unwrap();
+        // Until it execs, the child has our environment
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut pids = ProcessManager::find_processes_by_env(TASK_ID_ENV, &tag);
+        while pids.is_empty() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+            pids = ProcessManager::find_processes_by_env(TASK_ID_ENV, &tag);
+        }
+        // Task T001 mustn't find the processes of T0011
+        let none = ProcessManager::find_processes_by_env(TASK_ID_ENV, &tag[..tag.len() - 1]);
+        child.kill().unwrap();
+        child.wait().unwrap();
+        assert_eq!(pids, [child.id() as i32]);
+        assert!(none.is_empty());
+    }
+
+    /// `sh -c script` in a process group of its own, once it has started
+    /// `children` processes
+    #[cfg(unix)]
+    fn spawn_tree(script: &str, children: usize) -> std::process::Child {
+        use std::os::unix::process::CommandExt;
+
+        let child = std::process::Command::new("sh")
+            .args(["-c", script])
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while ProcessManager::process_tree(child.id() as i32).len() < children + 1 {
+            assert!(
+                Instant::now() < deadline,
+                "children of {script} didn't start"
+            );
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        child
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_kill_tree() {
+        let mut child = spawn_tree("sleep 30 & sleep 30 & wait", 2);
+        let pid = child.id() as i32;
+        ProcessManager::ensure_killable(pid, false).unwrap();
+        let killed =
+            ProcessManager::kill_tree(pid, Signal::SIGTERM, Duration::from_secs(5)).unwrap();
+        child.wait().unwrap();
+        assert_eq!(killed.pgid, Some(pid));
+        assert_eq!(killed.signal, "SIGTERM");
+        assert_eq!(killed.processes.len(), 3);
+        assert_eq!(killed.processes[0], pid);
+        assert!(killed.killed.is_empty());
+        assert!(!killed
+            .processes
+            .iter()
+            .any(|pid| ProcessManager::is_alive(*pid)));
+
+        // Ignoring SIGTERM only lasts the grace period
+        let mut child = spawn_tree("trap '' TERM; sleep 30 & wait", 1);
+        let pid = child.id() as i32;
+        let killed =
+            ProcessManager::kill_tree(pid, Signal::SIGTERM, Duration::from_millis(300)).unwrap();
+        child.wait().unwrap();
+        assert_eq!(killed.killed, killed.processes);
+        assert_eq!(killed.killed.len(), 2);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_ensure_killable_refuses_the_watchdog() {
+        assert!(ProcessManager::ensure_killable(1, true).is_err());
+        assert!(ProcessManager::ensure_killable(std::process::id() as i32, true).is_err());
+        let parent = nix::unistd::getppid().as_raw();
+        assert!(ProcessManager::ensure_killable(parent, true).is_err());
+        assert!(ProcessManager::ensure_killable(i32::MAX, false).is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_parse_signal() {
+        assert_eq!(parse_signal("TERM").unwrap(), Signal::SIGTERM);
+        assert_eq!(parse_signal("sigint").unwrap(), Signal::SIGINT);
+        assert_eq!(parse_signal("9").unwrap(), Signal::SIGKILL);
+        assert!(parse_signal("NOPE").is_err());
+        assert!(parse_signal("999").is_err());
+    }
+
+    #[test]
+    fn test_parse_cgroup_container() {
+        let id = "4f1c2d3e".repeat(8);
+        let v1 = format!("12:memory:/docker/{id}\n11:cpu:/docker/{id}\n");
+        assert_eq!(parse_cgroup_container(&v1), Some(id.clone()));
+        let v2 = format!("0::/system.slice/docker-{id}.scope\n");
+        assert_eq!(parse_cgroup_container(&v2), Some(id.clone()));
+        let containerd = format!("0::/kubepods/pod1/cri-containerd-{id}.scope\n");
+        assert_eq!(parse_cgroup_container(&containerd), Some(id));
+        assert_eq!(
+            parse_cgroup_container("0::/user.slice/user-1000.slice/session-2.scope\n"),
+            None
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_ancestors() {
+        let mut child = spawn_tree("sleep 30 & wait", 1);
+        let pid = child.id() as i32;
+        let grandchild = ProcessManager::process_tree(pid)[1];
+        let ancestors = ProcessManager::ancestors(grandchild);
+        ProcessManager::kill_tree(pid, Signal::SIGKILL, Duration::ZERO).unwrap();
+        child.wait().unwrap();
+        assert_eq!(&ancestors[..2], [pid, std::process::id() as i32]);
+    }
+
+    #[test]
+    fn test_repo_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("checkout/src/deep");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::create_dir(dir.path().join("checkout/.git")).unwrap();
+
+        assert_eq!(
+            ProcessManager::repo_root(&nested),
+            Some(dir.path().join("checkout"))
+        );
+        assert_eq!(ProcessManager::repo_root(dir.path()), None);
     }
 }
@@ -0,0 +1,279 @@
+//! Fair promotion of queued tasks.
+//!
+//! Queued tasks are grouped by the value of one label (`session` unless
+//! configured otherwise; tasks without it share a single group). Whenever
+//! capacity frees up, the next task comes from the group with the fewest
+//! started tasks relative to its weight, first-in first-out within a group.
+//! A swarm that queued fifty tasks still gets its share, but an interactive
+//! session with one task waiting is not stuck behind all fifty.
+
+use crate::capacity::{self, Resources};
+use crate::types::{Dependencies, ProcessRegistry, TaskInfo, TaskStatus};
+use chrono::Utc;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+/// Label whose values share queued capacity by default
+pub const DEFAULT_FAIR_LABEL: &str = "session";
+
+/// Weight of a group that has none configured
+pub const DEFAULT_WEIGHT: u32 = 1;
+
+/// How queued capacity is shared between groups
+#[derive(Debug, Clone)]
+pub struct Fairness {
+    /// Label that decides a task's group
+    pub label: String,
+    /// Relative share per label value
+    pub weights: BTreeMap<String, u32>,
+}
+
+impl Default for Fairness {
+    fn default() -> Self {
+        Self {
+            label: DEFAULT_FAIR_LABEL.to_string(),
+            weights: BTreeMap::new(),
+        }
+    }
+}
+
+impl Fairness {
+    /// The group a task is scheduled in (`None` for unlabelled tasks)
+    pub fn group_of<'a>(&self, task: &'a TaskInfo) -> Option<&'a str> {
+        task.labels.get(&self.label).map(String::as_str)
+    }
+
+    fn weight(&self, group: Option<&str>) -> f64 {
+        let weight = group
+            .and_then(|g| self.weights.get(g))
+            .copied()
+            .unwrap_or(DEFAULT_WEIGHT);
+        f64::from(weight.max(1))
+    }
+}
+
+/// Tasks of `group` that have started and not finished: what counts
+/// against a swarm's `jobs`
+pub fn running_in_group(registry: &ProcessRegistry, group: &str) -> usize {
+    registry
+        .tasks
+        .values()
+        .filter(|task| task.group.as_deref() == Some(group))
+        .filter(|task| task.status != TaskStatus::Queued && !task.status.is_terminal())
+        .count()
+}
+
+/// Start queued tasks that fit in `reservable`, fairly across groups.
+///
+/// Promoted tasks become `running` with `started_at` reset to now. A group
+/// whose oldest task doesn't fit, waits for a task it depends on to be
+/// ready, or belongs to a swarm already running its `jobs`, is skipped for
+/// this round, so tasks are never reordered within a group. Returns the
+/// promoted task IDs in order.
+pub fn promote(
+    registry: &mut ProcessRegistry,
+    fairness: &Fairness,
+    reservable: Resources,
+) -> Vec<String> {
+    let mut started: HashMap<Option<String>, usize> = HashMap::new();
+    let mut queued: Vec<(&String, &TaskInfo)> = Vec::new();
+    for (task_id, task) in &registry.tasks {
+        let group = fairness.group_of(task).map(str::to_string);
+        match task.status {
+            TaskStatus::Queued => queued.push((task_id, task)),
+            ref s if !s.is_terminal() => *started.entry(group).or_default() += 1,
+            _ => {}
+        }
+    }
+    queued.sort_by(|a, b| a.1.started_at.cmp(&b.1.started_at).then(a.0.cmp(b.0)));
+
+    let mut groups: BTreeMap<Option<String>, VecDeque<String>> = BTreeMap::new();
+    for (task_id, task) in queued {
+        groups
+            .entry(fairness.group_of(task).map(str::to_string))
+            .or_default()
+            .push_back(task_id.clone());
+    }
+
+    let mut promoted = Vec::new();
+    loop {
+        // Lowest share of started tasks per unit of weight goes next; ties
+        // go to whichever group has waited longest
+        let next = groups
+            .iter()
+            .filter_map(|(group, ids)| Some((group, ids.front()?)))
+            .min_by(|(a, a_id), (b, b_id)| {
+                let share = |g: &Option<String>| {
+                    started.get(g).copied().unwrap_or(0) as f64 / fairness.weight(g.as_deref())
+                };
+                let waited = |id: &String| registry.tasks.get(id).map(|t| t.started_at);
+                share(a)
+                    .total_cmp(&share(b))
+                    .then(waited(a_id).cmp(&waited(b_id)))
+            })
+            .map(|(group, id)| (group.clone(), id.clone()));
+        let Some((group, task_id)) = next else {
+            break;
+        };
+
+        let ready = registry
+            .tasks
+            .get(&task_id)
+            .is_none_or(|task| registry.dependencies_of(&task.depends_on) == Dependencies::Ready);
+        let fits = registry
+            .tasks
+            .get(&task_id)
+            .and_then(capacity::reservation_of)
+            .is_none_or(|request| {
+                let reserved = capacity::reserved(registry, None);
+                capacity::check_admission(reservable, reserved, request).is_ok()
+            });
+        let has_slot =
+            registry
+                .tasks
+                .get(&task_id)
+                .is_none_or(|task| match (task.jobs, &task.group) {
+                    (Some(jobs), Some(group)) => running_in_group(registry, group) < jobs as usize,
+                    _ => true,
+                });
+        if !ready || !fits || !has_slot {
+            groups.remove(&group);
+            continue;
+        }
+
+        if let Some(task) = registry.tasks.get_mut(&task_id) {
+            if task.transition_to(TaskStatus::Running).is_ok() {
+                task.started_at = Utc::now();
+                promoted.push(task_id);
+            }
+        }
+        if let Some(ids) = groups.get_mut(&group) {
+            ids.pop_front();
+        }
+        *started.entry(group).or_default() += 1;
+    }
+    promoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{FailureReason, ReadinessProbe, ResourceLimits, TaskCommand};
+    use chrono::Duration;
+
+    fn task(session: &str, status: TaskStatus, minutes_ago: i64) -> TaskInfo {
+        TaskInfo {
+            status,
+            started_at: Utc::now() - Duration::minutes(minutes_ago),
+            labels: BTreeMap::from([("session".to_string(), session.to_string())]),
+            reservation: Some(ResourceLimits {
+                memory: "1g".to_string(),
+                cpu: "1".to_string(),
+            }),
+            ..TaskInfo::new(TaskCommand::shell("pytest"))
+        }
+    }
+
+    fn room_for(tasks: u64) -> Resources {
+        Resources {
+            memory_bytes: tasks * 1024 * 1024 * 1024,
+            cpus: 64.0,
+        }
+    }
+
+    #[test]
+    fn test_interactive_session_is_not_starved_by_a_swarm() {
+        let mut registry = ProcessRegistry::new();
+        registry.add_task("S0".into(), task("swarm", TaskStatus::Running, 60));
+        for i in 1..=5 {
+            registry.add_task(format!("S{i}"), task("swarm", TaskStatus::Queued, 50 - i));
+        }
+        // Queued after the whole swarm
+        registry.add_task("I1".into(), task("interactive", TaskStatus::Queued, 1));
+
+        // Room for two more: FIFO would pick S1 and S2
+        let promoted = promote(&mut registry, &Fairness::default(), room_for(3));
+        assert_eq!(promoted, vec!["I1", "S1"]);
+        assert_eq!(registry.tasks["I1"].status, TaskStatus::Running);
+        assert_eq!(registry.tasks["S2"].status, TaskStatus::Queued);
+
+        // Nothing more fits
+        assert!(promote(&mut registry, &Fairness::default(), room_for(3)).is_empty());
+    }
+
+    #[test]
+    fn test_weights_share_capacity_proportionally() {
+        let mut registry = ProcessRegistry::new();
+        for i in 0..6 {
+            registry.add_task(format!("A{i}"), task("a", TaskStatus::Queued, 30 - i));
+            registry.add_task(format!("B{i}"), task("b", TaskStatus::Queued, 30 - i));
+        }
+        let fairness = Fairness {
+            weights: BTreeMap::from([("a".to_string(), 2)]),
+            ..Default::default()
+        };
+
+        let promoted = promote(&mut registry, &fairness, room_for(6));
+        let from_a = promoted.iter().filter(|id| id.starts_with('A')).count();
+        assert_eq!((from_a, promoted.len() - from_a), (4, 2));
+    }
+
+    #[test]
+    fn test_swarm_runs_at_most_its_jobs() {
+        let mut registry = ProcessRegistry::new();
+        for i in 0..5 {
+            let mut task = task("swarm", TaskStatus::Queued, 30 - i);
+            task.group = Some("tasks".into());
+            task.jobs = Some(2);
+            registry.add_task(format!("tasks-{i}"), task);
+        }
+
+        let promoted = promote(&mut registry, &Fairness::default(), room_for(8));
+        assert_eq!(promoted, vec!["tasks-0", "tasks-1"]);
+        assert!(promote(&mut registry, &Fairness::default(), room_for(8)).is_empty());
+
+        // A slot frees up when one finishes
+        let done = registry.tasks.get_mut("tasks-0").unwrap();
+        done.transition_to(TaskStatus::Completed).unwrap();
+        let promoted = promote(&mut registry, &Fairness::default(), room_for(8));
+        assert_eq!(promoted, vec!["tasks-2"]);
+        assert_eq!(running_in_group(&registry, "tasks"), 2);
+    }
+
+    #[test]
+    fn test_dependent_waits_until_server_is_ready() {
+        let mut registry = ProcessRegistry::new();
+        let mut server = task("pipeline", TaskStatus::Running, 10);
+        server.readiness = Some(ReadinessProbe::Port(8080));
+        registry.add_task("API".into(), server);
+        let mut tests = task("pipeline", TaskStatus::Queued, 5);
+        tests.depends_on = vec!["API".into()];
+        registry.add_task("TESTS".into(), tests);
+
+        // Running isn't ready
+        assert!(promote(&mut registry, &Fairness::default(), room_for(4)).is_empty());
+
+        registry.tasks.get_mut("API").unwrap().ready_at = Some(Utc::now());
+        let promoted = promote(&mut registry, &Fairness::default(), room_for(4));
+        assert_eq!(promoted, vec!["TESTS"]);
+    }
+
+    #[test]
+    fn test_dependents_of_a_failed_task_fail() {
+        let mut registry = ProcessRegistry::new();
+        let mut build = task("pipeline", TaskStatus::Running, 10);
+        build.transition_to(TaskStatus::Failed).unwrap();
+        registry.add_task("BUILD".into(), build);
+        let mut deploy = task("pipeline", TaskStatus::Queued, 5);
+        deploy.depends_on = vec!["BUILD".into()];
+        registry.add_task("DEPLOY".into(), deploy);
+
+        assert_eq!(
+            registry.fail_blocked(),
+            vec![("DEPLOY".to_string(), "BUILD".to_string())]
+        );
+        let deploy = &registry.tasks["DEPLOY"];
+        assert_eq!(deploy.status, TaskStatus::Failed);
+        assert_eq!(deploy.failure_reason, Some(FailureReason::DependencyFailed));
+        assert!(registry.fail_blocked().is_empty());
+    }
+}
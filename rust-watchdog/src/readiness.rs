@@ -0,0 +1,142 @@
+//! Readiness probes: when a server task is ready for the tasks that depend
+//! on it, not merely running.
+//!
+//! The daemon probes running tasks on every check until their probe passes
+//! once, and records when it did; `spawn --after` probes its dependencies
+//! itself while it waits, so it doesn't need a daemon.
+
+use crate::info;
+use crate::registry::RegistryManager;
+use crate::types::{Dependencies, ReadinessProbe, TaskInfo, TaskStatus};
+#[cfg(not(feature = "ureq"))]
+use crate::verbose;
+use anyhow::{bail, Result};
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+
+/// How long a single probe may take before it counts as failed
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often `spawn --after` looks at its dependencies again
+const WAIT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Parse a `--ready` probe, refusing HTTP probes in a build that cannot
+/// send them (without ureq, from the `notify` or `self-update` feature)
+pub fn parse_probe(s: &str) -> Result<ReadinessProbe> {
+    let probe = s.parse()?;
+    if cfg!(not(feature = "ureq")) && matches!(probe, ReadinessProbe::Http(_)) {
+        bail!("This build cannot make HTTP probes; rebuild with `--features notify`");
+    }
+    Ok(probe)
+}
+
+/// Whether `probe` passes now. Commands run in `cwd` (the task's directory).
+pub async fn probe(probe: &ReadinessProbe, cwd: Option<&Path>) -> bool {
+    match probe {
+        ReadinessProbe::Port(port) => matches!(
+            tokio::time::timeout(
+                PROBE_TIMEOUT,
+                tokio::net::TcpStream::connect(("127.0.0.1", *port))
+            )
+            .await,
+            Ok(Ok(_))
+        ),
+        #[cfg(feature = "ureq")]
+        ReadinessProbe::Http(url) => {
+            let url = url.clone();
+            // ureq turns 4xx/5xx answers into errors
+            tokio::task::spawn_blocking(move || {
+                ureq::get(&url).timeout(PROBE_TIMEOUT).call().is_ok()
+            })
+            .await
+            .unwrap_or(false)
+        }
+        // Registered by another tool: it never passes
+        #[cfg(not(feature = "ureq"))]
+        ReadinessProbe::Http(url) => {
+            verbose!(
+                "   Cannot probe {}: HTTP probes need the notify or self-update feature",
+                url
+            );
+            false
+        }
+        ReadinessProbe::Command(command) => {
+            let argv = command.exec_argv();
+            let mut cmd = tokio::process::Command::new(&argv[0]);
+            cmd.args(&argv[1..])
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .kill_on_drop(true);
+            if let Some(cwd) = cwd {
+                cmd.current_dir(cwd);
+            }
+            matches!(
+                tokio::time::timeout(PROBE_TIMEOUT, cmd.status()).await,
+                Ok(Ok(status)) if status.success()
+            )
+        }
+    }
+}
+
+/// Probe running tasks whose readiness isn't confirmed yet (only those in
+/// `only`, if given) and record the ones that pass. Returns their IDs.
+pub async fn probe_unready(
+    registry: &mut RegistryManager,
+    only: Option<&[String]>,
+) -> Result<Vec<String>> {
+    let pending: Vec<(String, TaskInfo)> = registry
+        .active_tasks()
+        .into_iter()
+        .filter(|(task_id, task)| {
+            task.status == TaskStatus::Running
+                && task.readiness.is_some()
+                && task.ready_at.is_none()
+                && only.is_none_or(|ids| ids.contains(task_id))
+        })
+        .map(|(task_id, task)| (task_id.clone(), task.clone()))
+        .collect();
+
+    let mut ready = Vec::new();
+    for (task_id, task) in pending {
+        let Some(readiness) = &task.readiness else {
+            continue;
+        };
+        if probe(readiness, task.cwd.as_deref()).await && registry.mark_ready(&task_id)? {
+            info!(task: &task_id; "🟢 Task {} is ready ({})", task_id, readiness);
+            ready.push(task_id);
+        }
+    }
+    Ok(ready)
+}
+
+/// Block until every task in `depends_on` is ready, probing them meanwhile.
+/// Fails if one finishes without becoming ready.
+pub async fn wait_for(registry: &mut RegistryManager, depends_on: &[String]) -> Result<()> {
+    let mut announced = None;
+    loop {
+        registry.load()?;
+        if let Some(dep_id) = depends_on
+            .iter()
+            .find(|dep_id| registry.get_task(dep_id).is_none())
+        {
+            bail!("Unknown dependency: {}", dep_id);
+        }
+        match registry.registry().dependencies_of(depends_on) {
+            Dependencies::Ready => return Ok(()),
+            Dependencies::Failed(dep_id) => {
+                bail!("{} finished without becoming ready", dep_id)
+            }
+            Dependencies::Waiting(dep_id) => {
+                if announced.as_ref() != Some(&dep_id) {
+                    info!("⏳ Waiting for {} to become ready...", dep_id);
+                    announced = Some(dep_id);
+                }
+            }
+        }
+        if probe_unready(registry, Some(depends_on)).await?.is_empty() {
+            tokio::time::sleep(WAIT_INTERVAL).await;
+        }
+    }
+}
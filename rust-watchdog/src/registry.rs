@@ -1,73 +1,400 @@
+use crate::capacity::{self, Resources};
+use crate::clock;
+use crate::files;
+use crate::filter::Filter;
+use crate::lifecycle::{self, Actor};
 use crate::process::ProcessManager;
-use crate::types::{ExecutionMode, OrphanReport, ProcessRegistry, TaskInfo, TaskStatus};
-use anyhow::{Context, Result};
+use crate::queue::{self, Fairness};
+use crate::storage::{self, RegistryBackend};
+use crate::types::{
+    Coredump, Dependencies, DockerTask, ExecutionMode, NativeTask, OrphanReport, ProcessRegistry,
+    TaskCost, TaskIdMatch, TaskInfo, TaskResult, TaskStatus, WatchedProcess,
+};
+use crate::{debug, warn};
+use anyhow::{bail, Context, Result};
 use fs2::FileExt;
-use std::fs::{self, OpenOptions, Permissions};
-use std::os::unix::fs::PermissionsExt;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+pub use task_watchdog_core::report::{GroupStats, RegistryStats};
+
+/// Finished tasks stay in the registry file this long before
+/// [`RegistryManager::archive_finished`] moves them to the history file
+pub const ARCHIVE_AFTER_HOURS: i64 = 1;
+
+/// A task that reports done (e.g. through its done file) is usually still
+/// exiting; only one running this long after completing is a zombie
+pub const ZOMBIE_GRACE_SECS: i64 = 5;
+
+/// One line of the history file
+#[derive(Serialize, Deserialize)]
+struct HistoryEntry {
+    task_id: String,
+    task: TaskInfo,
+}
 
 /// Registry manager for persisting task state
 pub struct RegistryManager {
     registry_path: PathBuf,
+    /// Where `registry` is read from and written to
+    backend: Box<dyn RegistryBackend>,
+    /// Tasks in the registry file: unfinished and recently finished ones
     registry: ProcessRegistry,
+    /// Archived tasks read by [`Self::load_history`]
+    history: ProcessRegistry,
+    /// Inode of the history file and how many bytes of it are in `history`
+    history_read: Option<(u64, u64)>,
+    /// `registry` in front of `history`, built on first use after a change
+    merged: OnceLock<ProcessRegistry>,
+    /// Status counts of every task, updated as tasks change. The daemon
+    /// asks for them several times per check; with thousands of archived
+    /// tasks a recount is a full scan.
+    stats: RegistryStats,
+    /// Whether `stats` may be off since [`Self::get_task_mut`] handed out
+    /// a task
+    stats_stale: bool,
+    /// Whether clock skew has been reported already
+    skew_warned: bool,
+    /// Who the changes made through this manager are recorded as
+    actor: Actor,
 }
 
 impl RegistryManager {
-    /// Create new registry manager
+    /// Create new registry manager, stored in the process-wide backend
+    /// (see [`storage::open`])
     pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        let backend = storage::open(path.as_ref());
+        Self::with_backend(path, backend)
+    }
+
+    /// Registry manager storing the registry at `path` in `backend`
+    pub fn with_backend<P: AsRef<Path>>(path: P, backend: Box<dyn RegistryBackend>) -> Self {
         Self {
             registry_path: PathBuf::from(path.as_ref()),
+            backend,
             registry: ProcessRegistry::new(),
+            history: ProcessRegistry::new(),
+            history_read: None,
+            merged: OnceLock::new(),
+            stats: RegistryStats::default(),
+            stats_stale: false,
+            skew_warned: false,
+            actor: Actor::default(),
         }
     }
 
+    /// Record the changes made through this manager in the lifecycle
+    /// journal as made by `actor` (the CLI unless set)
+    pub fn set_actor(&mut self, actor: Actor) {
+        self.actor = actor;
+    }
+
+    /// Forget the merged view; called whenever `registry` or `history` may
+    /// have changed
+    fn invalidate(&mut self) {
+        self.merged = OnceLock::new();
+    }
+
+    /// Update `stats` for `registry` having been `before` until now. Only
+    /// the tasks of the registry file are looked at, not archived ones.
+    fn count_changes(&mut self, before: &ProcessRegistry) {
+        if self.stats_stale {
+            return self.recount();
+        }
+        let changed = before.tasks.keys().chain(
+            self.registry
+                .tasks
+                .keys()
+                .filter(|task_id| !before.tasks.contains_key(*task_id)),
+        );
+        for task_id in changed {
+            // A task leaving the registry file may still count as archived
+            let status = |registry: &ProcessRegistry| {
+                registry
+                    .get_task(task_id)
+                    .or_else(|| self.history.get_task(task_id))
+                    .map(|task| task.status.clone())
+            };
+            let (old, new) = (status(before), status(&self.registry));
+            if old != new {
+                if let Some(old) = &old {
+                    self.stats.remove(old);
+                }
+                if let Some(new) = &new {
+                    self.stats.add(new);
+                }
+            }
+        }
+    }
+
+    /// Count every task afresh
+    fn recount(&mut self) {
+        let mut stats = self.registry.stats();
+        for (task_id, task) in &self.history.tasks {
+            if !self.registry.tasks.contains_key(task_id) {
+                stats.add(&task.status);
+            }
+        }
+        self.stats = stats;
+        self.stats_stale = false;
+    }
+
     /// Path to advisory lock file (lives next to registry)
     fn lock_path(&self) -> PathBuf {
         self.registry_path.with_extension("lock")
     }
 
-    /// Load registry from disk
+    /// Registry path this manager was opened with. Logs, done files and
+    /// the history file live next to it, whatever the backend.
+    pub fn path(&self) -> &Path {
+        &self.registry_path
+    }
+
+    /// Where the registry is actually stored
+    pub fn backend(&self) -> &dyn RegistryBackend {
+        self.backend.as_ref()
+    }
+
+    /// Append-only file of archived tasks, one JSON object per line
+    pub fn history_path(&self) -> PathBuf {
+        self.registry_path.with_extension("history.jsonl")
+    }
+
+    /// Append-only journal of every task's status changes (see
+    /// [`crate::lifecycle`])
+    pub fn events_path(&self) -> PathBuf {
+        lifecycle::events_path(&self.registry_path)
+    }
+
+    /// Load the registry file from disk. Archived tasks are not read; call
+    /// [`Self::load_history`] when finished tasks matter.
     pub fn load(&mut self) -> Result<()> {
-        if !self.registry_path.exists() {
+        self.invalidate();
+        if self.backend.needs_migration() {
+            let _lock = self.lock()?;
+            // Another process may have migrated it while we waited
+            if self.backend.needs_migration() {
+                self.backend.migrate()?;
+            }
+        }
+        if !self.backend.exists() {
             // Create directory if it doesn't exist
             if let Some(parent) = self.registry_path.parent() {
                 fs::create_dir_all(parent).context("Failed to create registry directory")?;
             }
 
             // Initialize empty registry
-            self.registry = ProcessRegistry::new();
+            let before = std::mem::replace(&mut self.registry, ProcessRegistry::new());
+            self.count_changes(&before);
+            self.registry.boot_id = ProcessManager::boot_id();
             self.save()?;
             return Ok(());
         }
 
-        let content =
-            fs::read_to_string(&self.registry_path).context("Failed to read registry file")?;
+        let before = std::mem::replace(&mut self.registry, self.backend.read()?);
+        self.count_changes(&before);
+        debug!(
+            "   Loaded {} tasks from {}",
+            self.registry.tasks.len(),
+            self.backend.path().display()
+        );
+
+        self.warn_clock_skew();
+        self.check_reboot()
+    }
+
+    /// Warn, once, about timestamps the system clock can't have written:
+    /// durations and timeouts are off until it is fixed
+    fn warn_clock_skew(&mut self) {
+        if self.skew_warned {
+            return;
+        }
+        let Some(skew) = clock::find_skew(&self.registry, chrono::Utc::now()) else {
+            return;
+        };
+        self.skew_warned = true;
+        let some = |ids: &[String]| match ids.len() {
+            0..=5 => ids.join(", "),
+            n => format!("{}, and {} more", ids[..5].join(", "), n - 5),
+        };
+        if !skew.ahead.is_empty() {
+            warn!(
+                "⏰ {} task(s) have timestamps up to {} ahead of the system clock ({}): \
+                 the clock may have gone back (VM restored from a snapshot?) or be in \
+                 the wrong time zone. Their durations count from 0 until it catches up.",
+                skew.ahead.len(),
+                crate::units::format_duration(skew.max_ahead_secs as f64),
+                some(&skew.ahead)
+            );
+        }
+        if !skew.ancient.is_empty() {
+            warn!(
+                "⏰ {} task(s) have timestamps over {} years old ({}): the clock was \
+                 probably wrong when they were written",
+                skew.ancient.len(),
+                clock::MAX_AGE_DAYS / 365,
+                some(&skew.ancient)
+            );
+        }
+    }
+
+    /// Read tasks archived since the last call (all of them on the first
+    /// call). The history file only grows, so a long-running daemon parses
+    /// each archived task once; after `cleanup` rewrites the file it is
+    /// read again from the start.
+    pub fn load_history(&mut self) -> Result<()> {
+        let path = self.history_path();
+        let mut file = match File::open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                if self.history_read.take().is_some() {
+                    self.history = ProcessRegistry::new();
+                    self.invalidate();
+                    self.recount();
+                }
+                return Ok(());
+            }
+            Err(e) => return Err(e).context("Failed to open task history"),
+        };
+        let meta = file.metadata().context("Failed to stat task history")?;
+
+        let offset = match self.history_read {
+            Some((inode, offset)) if inode == files::file_id(&meta) && offset <= meta.len() => {
+                offset
+            }
+            _ => {
+                self.history = ProcessRegistry::new();
+                self.invalidate();
+                self.recount();
+                0
+            }
+        };
+        if offset == meta.len() {
+            self.history_read = Some((files::file_id(&meta), offset));
+            return Ok(());
+        }
+
+        file.seek(SeekFrom::Start(offset))
+            .context("Failed to seek task history")?;
+        let mut content = String::new();
+        file.read_to_string(&mut content)
+            .context("Failed to read task history")?;
 
-        self.registry = serde_json::from_str(&content).context("Failed to parse registry JSON")?;
+        // A line still being appended is picked up on the next call
+        let complete = content.rfind('\n').map_or(0, |i| i + 1);
+        for line in content[..complete].lines().filter(|l| !l.trim().is_empty()) {
+            let entry: HistoryEntry =
+                serde_json::from_str(line).context("Failed to parse task history")?;
+            // Counted unless the registry file has a newer version of it
+            if !self.registry.tasks.contains_key(&entry.task_id) {
+                if let Some(old) = self.history.get_task(&entry.task_id) {
+                    self.stats.remove(&old.status);
+                }
+                self.stats.add(&entry.task.status);
+            }
+            self.history.add_task(entry.task_id, entry.task);
+        }
+        self.history_read = Some((files::file_id(&meta), offset + complete as u64));
+        self.invalidate();
+        debug!(
+            "   Loaded {} archived tasks from {}",
+            self.history.tasks.len(),
+            path.display()
+        );
+        Ok(())
+    }
 
+    /// Move tasks that finished more than [`ARCHIVE_AFTER_HOURS`] ago from
+    /// the registry file to the history file (concurrent-safe), so loading
+    /// the registry stays cheap however much history piles up. Returns how
+    /// many tasks were moved.
+    pub fn archive_finished(&mut self) -> Result<usize> {
+        let cutoff = chrono::Utc::now() - chrono::Duration::hours(ARCHIVE_AFTER_HOURS);
+        let is_old = |task: &TaskInfo| {
+            task.status.is_terminal() && task.completed_at.is_some_and(|at| at < cutoff)
+        };
+        // Most checks have nothing to move; don't rewrite the registry then
+        if !self.registry.tasks.values().any(is_old) {
+            return Ok(0);
+        }
+
+        let history_path = self.history_path();
+        let mode = self.file_mode();
+        self.locked_mutate(|r| {
+            let mut ids: Vec<String> = r
+                .tasks
+                .iter()
+                .filter(|(_, task)| is_old(task))
+                .map(|(id, _)| id.clone())
+                .collect();
+            ids.sort();
+
+            let mut lines = String::new();
+            for task_id in &ids {
+                if let Some(task) = r.remove_task(task_id) {
+                    let entry = HistoryEntry {
+                        task_id: task_id.clone(),
+                        task,
+                    };
+                    lines += &serde_json::to_string(&entry).context("Failed to serialize task")?;
+                    lines.push('\n');
+                }
+            }
+
+            // Appended before the registry is saved: a crash in between
+            // leaves a task in both files (the registry's copy wins), never
+            // in neither
+            let mut file = files::append_options(mode)
+                .open(&history_path)
+                .context("Failed to open task history")?;
+            file.write_all(lines.as_bytes())
+                .context("Failed to append to task history")?;
+            Ok(ids.len())
+        })
+    }
+
+    /// Fail native tasks left running when the machine rebooted (see
+    /// [`ProcessRegistry::record_boot`]). Only writes when the boot ID changed.
+    fn check_reboot(&mut self) -> Result<()> {
+        let Some(boot_id) = ProcessManager::boot_id() else {
+            return Ok(());
+        };
+        if self.registry.boot_id.as_deref() == Some(boot_id.as_str()) {
+            return Ok(());
+        }
+
+        let failed = self.locked_mutate(|r| Ok(r.record_boot(&boot_id)))?;
+        for task_id in failed {
+            warn!(task: &task_id; "🔌 Task {} failed: system rebooted while it was running", task_id);
+        }
         Ok(())
     }
 
-    /// Save registry to disk using atomic write (temp file → rename).
+    /// Save the whole registry (atomically: readers see the old or the
+    /// new one).
     ///
     /// Callers that need safe concurrent access should use `locked_mutate`
     /// instead, which wraps this with an exclusive advisory lock + re-read.
     pub fn save(&self) -> Result<()> {
-        let json =
-            serde_json::to_string_pretty(&self.registry).context("Failed to serialize registry")?;
-
-        // Write to a sibling temp file, then rename (atomic on Linux/macOS)
-        let tmp_path = self.registry_path.with_extension("json.tmp");
-        fs::write(&tmp_path, &json).context("Failed to write temp registry file")?;
-
-        fs::rename(&tmp_path, &self.registry_path)
-            .context("Failed to atomically rename registry file")?;
+        self.backend.write(None, &self.registry, self.file_mode())
+    }
 
-        // SECURITY-003: Set permissions to 0600 (owner read/write only)
-        fs::set_permissions(&self.registry_path, Permissions::from_mode(0o600))
-            .context("Failed to set registry file permissions to 0600")?;
+    /// Whether the registry was made group-writable for a team sharing it
+    pub fn is_shared(&self) -> bool {
+        files::mode(self.backend.path()).is_some_and(|mode| mode & 0o060 == 0o060)
+    }
 
-        Ok(())
+    /// Permissions of the files kept next to the registry: the owner's
+    /// only, or the group's too on a shared registry
+    pub fn file_mode(&self) -> u32 {
+        if self.is_shared() {
+            0o660
+        } else {
+            0o600
+        }
     }
 
     /// Acquire exclusive advisory lock, re-read registry from disk, apply
@@ -76,10 +403,62 @@ impl RegistryManager {
     /// This is the correct primitive for all state-mutating operations when
     /// multiple `task-watchdog` processes may run concurrently
     /// (e.g. PARALLEL_SWARM tasks completing simultaneously).
-    fn locked_mutate<F>(&mut self, f: F) -> Result<()>
+    ///
+    /// If the closure returns an error nothing is written, and its partial
+    /// changes are undone in memory too.
+    fn locked_mutate<F, T>(&mut self, f: F) -> Result<T>
     where
-        F: FnOnce(&mut ProcessRegistry),
+        F: FnOnce(&mut ProcessRegistry) -> Result<T>,
     {
+        let _lock = self.lock()?;
+
+        // Re-read from disk to pick up any updates written by other processes
+        // since our last load.
+        if self.backend.exists() {
+            let reread = self
+                .backend
+                .read()
+                .context("Failed to re-read registry under lock")?;
+            let before = std::mem::replace(&mut self.registry, reread);
+            self.count_changes(&before);
+        }
+
+        // Apply the mutation
+        self.invalidate();
+        let before = self.registry.clone();
+        let written = f(&mut self.registry).and_then(|result| {
+            // Write the updated state
+            self.backend
+                .write(Some(&before), &self.registry, self.file_mode())?;
+            Ok(result)
+        });
+        let result = match written {
+            Ok(result) => {
+                self.count_changes(&before);
+                result
+            }
+            Err(e) => {
+                self.registry = before;
+                self.invalidate();
+                return Err(e);
+            }
+        };
+
+        // Still under the lock, so the journal is in the order changes were
+        // made. The registry is written already; a journal that can't be
+        // doesn't undo that.
+        let changes = lifecycle::diff(&before, &self.registry, self.actor);
+        if let Err(e) = lifecycle::append(&self.events_path(), &changes, self.file_mode()) {
+            warn!("⚠️  {:#}", e);
+        }
+
+        // The lock file drops here → flock released
+        Ok(result)
+    }
+
+    /// Take the exclusive advisory lock on the registry, released when the
+    /// returned file is dropped
+    fn lock(&self) -> Result<File> {
         // Ensure parent directory exists for both registry and lock file
         if let Some(parent) = self.registry_path.parent() {
             fs::create_dir_all(parent).context("Failed to create registry directory")?;
@@ -93,53 +472,170 @@ impl RegistryManager {
             .truncate(false) // lock file is flock-only; never write content to it
             .open(self.lock_path())
             .context("Failed to open registry lock file")?;
+        if self.is_shared() {
+            // Best effort: only the lock file's owner may change its mode
+            let _ = files::set_mode(&self.lock_path(), 0o660);
+        }
         lock_file
             .lock_exclusive()
             .context("Failed to acquire exclusive registry lock")?;
+        Ok(lock_file)
+    }
 
-        // Re-read from disk to pick up any updates written by other processes
-        // since our last load.
-        if self.registry_path.exists() {
-            let content = fs::read_to_string(&self.registry_path)
-                .context("Failed to re-read registry under lock")?;
-            self.registry = serde_json::from_str(&content)
-                .context("Failed to parse registry JSON under lock")?;
-        }
+    /// Add or update a task (concurrent-safe).
+    ///
+    /// Replacing an existing task is a status transition and must be allowed
+    /// by the state machine, so a finished task cannot be silently revived.
+    pub fn upsert_task(&mut self, task_id: String, task: TaskInfo) -> Result<()> {
+        self.with_transaction(|txn| txn.register(task_id, task))
+    }
 
-        // Apply the mutation
-        f(&mut self.registry);
+    /// Apply several changes atomically: other writers and readers see the
+    /// registry either before or after all of them.
+    ///
+    /// ```no_run
+    /// # fn retry(registry: &mut task_watchdog::registry::RegistryManager,
+    /// #          task: task_watchdog::types::TaskInfo) -> anyhow::Result<()> {
+    /// registry.with_transaction(|txn| {
+    ///     txn.mark_failed("T001");
+    ///     txn.register("T001-retry".to_string(), task)
+    /// })
+    /// # }
+    /// ```
+    ///
+    /// If the closure returns an error nothing is written.
+    pub fn with_transaction<F, T>(&mut self, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut Transaction) -> Result<T>,
+    {
+        self.locked_mutate(|registry| f(&mut Transaction { registry }))
+    }
 
-        // Atomically write the updated state
-        self.save()?;
+    /// Register a task only if its reservation fits in `reservable` next to
+    /// every started task. Checked and written under the lock, so concurrent
+    /// launches cannot oversubscribe the machine together.
+    ///
+    /// A task that doesn't fit is registered as `queued` when `queue_if_full`
+    /// is set and refused otherwise. Returns the status it was given.
+    pub fn admit_task(
+        &mut self,
+        task_id: String,
+        task: TaskInfo,
+        reservable: Resources,
+        queue_if_full: bool,
+    ) -> Result<TaskStatus> {
+        self.with_transaction(|txn| txn.admit(task_id, task, reservable, queue_if_full))
+    }
 
-        // lock_file drops here → flock released
-        Ok(())
+    /// Admit many tasks in one locked load/save cycle, in order, so a swarm
+    /// launcher doesn't pay for (and race between) one lock per task.
+    ///
+    /// Each task is admitted as by [`admit_task`](Self::admit_task) and
+    /// counts against the reservations of the tasks before it. A task that
+    /// is refused does not stop the rest; every task gets its own result.
+    pub fn admit_batch(
+        &mut self,
+        tasks: Vec<(String, TaskInfo)>,
+        reservable: Resources,
+        queue_if_full: bool,
+    ) -> Result<Vec<(String, Result<TaskStatus>)>> {
+        self.with_transaction(|txn| {
+            Ok(tasks
+                .into_iter()
+                .map(|(task_id, task)| {
+                    let result = txn.admit(task_id.clone(), task, reservable, queue_if_full);
+                    (task_id, result)
+                })
+                .collect())
+        })
     }
 
-    /// Add or update a task (concurrent-safe)
-    pub fn upsert_task(&mut self, task_id: String, task: TaskInfo) -> Result<()> {
+    /// Record that a task's readiness probe passed (concurrent-safe).
+    /// Returns false if it was already recorded, or the task is gone.
+    pub fn mark_ready(&mut self, task_id: &str) -> Result<bool> {
         self.locked_mutate(|r| {
-            r.add_task(task_id, task);
+            let Some(task) = r.get_task_mut(task_id) else {
+                return Ok(false);
+            };
+            if task.ready_at.is_some() {
+                return Ok(false);
+            }
+            task.ready_at = Some(chrono::Utc::now());
+            Ok(true)
         })
     }
 
-    /// Get task by ID
+    /// Fail queued tasks whose dependencies finished without becoming
+    /// ready (see [`ProcessRegistry::fail_blocked`])
+    pub fn fail_blocked(&mut self) -> Result<Vec<(String, String)>> {
+        if !self
+            .registry
+            .tasks
+            .values()
+            .any(|task| task.status == TaskStatus::Queued && !task.depends_on.is_empty())
+        {
+            return Ok(Vec::new());
+        }
+        self.locked_mutate(|r| Ok(r.fail_blocked()))
+    }
+
+    /// Start whichever queued tasks now fit in `reservable`, sharing the
+    /// capacity fairly between label groups (see [`queue::promote`]).
+    /// Returns the promoted task IDs.
+    pub fn promote_queued(
+        &mut self,
+        fairness: &Fairness,
+        reservable: Resources,
+    ) -> Result<Vec<String>> {
+        self.locked_mutate(|r| Ok(queue::promote(r, fairness, reservable)))
+    }
+
+    /// Get task by ID, archived or not
     pub fn get_task(&self, task_id: &str) -> Option<&TaskInfo> {
-        self.registry.get_task(task_id)
+        self.registry
+            .get_task(task_id)
+            .or_else(|| self.history.get_task(task_id))
+    }
+
+    /// Resolve a full ID, unique prefix, fuzzy match or `@` selector to a
+    /// registered task ID.
+    ///
+    /// Returns `Ok(None)` when nothing matches and an error listing the
+    /// candidates when the query is ambiguous.
+    pub fn resolve_task_id(&self, query: &str) -> Result<Option<String>> {
+        match self.registry().match_task_id(query) {
+            TaskIdMatch::Found(id) => {
+                if id != query {
+                    debug!("   Resolved '{}' → '{}'", query, id);
+                }
+                Ok(Some(id))
+            }
+            TaskIdMatch::NotFound => Ok(None),
+            TaskIdMatch::UnknownSelector(selector) => bail!(
+                "Unknown selector '{}' (use @last, @newest-running or @last-<status>)",
+                selector
+            ),
+            TaskIdMatch::Ambiguous(candidates) => {
+                bail!(
+                    "Task ID '{}' is ambiguous, matches {} tasks:\n  {}",
+                    query,
+                    candidates.len(),
+                    candidates.join("\n  ")
+                )
+            }
+        }
     }
 
     /// Get mutable task reference
     pub fn get_task_mut(&mut self, task_id: &str) -> Option<&mut TaskInfo> {
+        self.invalidate();
+        self.stats_stale = true;
         self.registry.get_task_mut(task_id)
     }
 
     /// Remove task (concurrent-safe)
     pub fn remove_task(&mut self, task_id: &str) -> Result<Option<TaskInfo>> {
-        let mut removed: Option<TaskInfo> = None;
-        self.locked_mutate(|r| {
-            removed = r.remove_task(task_id);
-        })?;
-        Ok(removed)
+        self.with_transaction(|txn| Ok(txn.remove_task(task_id)))
     }
 
     /// Get all running tasks
@@ -147,15 +643,21 @@ impl RegistryManager {
         self.registry.running_tasks()
     }
 
+    /// Get all tasks that have not finished yet
+    pub fn active_tasks(&self) -> Vec<(&String, &TaskInfo)> {
+        self.registry.active_tasks()
+    }
+
     /// Get all completed tasks
     pub fn completed_tasks(&self) -> Vec<(&String, &TaskInfo)> {
-        self.registry.completed_tasks()
+        self.registry().completed_tasks()
     }
 
     /// Find orphaned processes (dead but not marked complete)
     /// and zombie processes (complete but still running)
     pub fn find_orphans(&self) -> OrphanReport {
         let mut report = OrphanReport::default();
+        let now = chrono::Utc::now();
 
         for (task_id, task) in &self.registry.tasks {
             let is_alive = match &task.mode {
@@ -168,7 +670,14 @@ impl RegistryManager {
                             false // PID was recycled, original process is dead
                         }
                     } else {
-                        false
+                        // Without a PID there is nothing to check, unless
+                        // the task reports through a done file
+                        if task.done_file.is_none()
+                            && matches!(task.status, TaskStatus::Running | TaskStatus::Starting)
+                        {
+                            report.unknown.push(task_id.clone());
+                        }
+                        continue;
                     }
                 }
                 ExecutionMode::Docker => {
@@ -178,13 +687,25 @@ impl RegistryManager {
                 }
             };
 
+            debug!("   {} [{}]: alive={}", task_id, task.status, is_alive);
+
             // Dead process but task still running
-            if !is_alive && task.status == TaskStatus::Running {
+            if !is_alive
+                && matches!(
+                    task.status,
+                    TaskStatus::Running | TaskStatus::Starting | TaskStatus::Unknown
+                )
+            {
                 report.dead_processes.push(task_id.clone());
             }
 
-            // Process alive but task marked complete
-            if is_alive && task.status == TaskStatus::Completed {
+            // Process alive but task marked complete a while ago
+            if is_alive
+                && task.status == TaskStatus::Completed
+                && task
+                    .completed_at
+                    .is_none_or(|at| (now - at).num_seconds() >= ZOMBIE_GRACE_SECS)
+            {
                 report.zombie_processes.push(task_id.clone());
             }
         }
@@ -194,90 +715,444 @@ impl RegistryManager {
 
     /// Mark task as completed (concurrent-safe)
     pub fn mark_complete(&mut self, task_id: &str) -> Result<()> {
-        let id = task_id.to_string();
+        self.set_status(task_id, TaskStatus::Completed)
+    }
+
+    /// Mark task as failed (concurrent-safe)
+    pub fn mark_failed(&mut self, task_id: &str) -> Result<()> {
+        self.set_status(task_id, TaskStatus::Failed)
+    }
+
+    /// Mark task as killed, recording why (concurrent-safe).
+    /// See [`Transaction::mark_killed`].
+    pub fn mark_killed(&mut self, task_id: &str, reason: &str) -> Result<()> {
+        self.with_transaction(|txn| {
+            txn.mark_killed(task_id, reason);
+            Ok(())
+        })
+    }
+
+    /// Mark task as timed out, recording why (concurrent-safe).
+    /// See [`Transaction::mark_timed_out`].
+    pub fn mark_timed_out(&mut self, task_id: &str, reason: &str) -> Result<()> {
+        self.with_transaction(|txn| {
+            txn.mark_timed_out(task_id, reason);
+            Ok(())
+        })
+    }
+
+    /// Record the process just started for a task promoted from the queue,
+    /// the log its output goes to and the done file its exit code will be
+    /// written to (concurrent-safe)
+    pub fn mark_started(
+        &mut self,
+        task_id: &str,
+        native: NativeTask,
+        log_file: PathBuf,
+        done_file: PathBuf,
+    ) -> Result<()> {
         self.locked_mutate(|r| {
-            if let Some(task) = r.get_task_mut(&id) {
-                task.status = TaskStatus::Completed;
-                task.completed_at = Some(chrono::Utc::now());
+            let Some(task) = r.get_task_mut(task_id) else {
+                bail!("Task {} not found", task_id);
+            };
+            task.native = Some(native);
+            task.log_file = Some(log_file);
+            task.done_file = Some(done_file);
+            Ok(())
+        })
+    }
+
+    /// Start a finished task over (see [`TaskInfo::restart`]) as the
+    /// process or container just launched for it (concurrent-safe).
+    /// Returns how many times it has restarted.
+    pub fn mark_restarted(
+        &mut self,
+        task_id: &str,
+        native: Option<NativeTask>,
+        docker: Option<DockerTask>,
+    ) -> Result<u32> {
+        self.locked_mutate(|r| {
+            let Some(task) = r.get_task_mut(task_id) else {
+                bail!("Task {} not found", task_id);
+            };
+            task.restart()?;
+            task.native = native;
+            task.docker = docker;
+            Ok(task.restarts)
+        })
+    }
+
+    /// Add reported token/spend counters to a task (concurrent-safe)
+    pub fn add_cost(&mut self, task_id: &str, cost: &TaskCost) -> Result<TaskCost> {
+        self.with_transaction(|txn| txn.add_cost(task_id, cost))
+    }
+
+    /// Store sampled cumulative CPU times (concurrent-safe). Samples never
+    /// lower a recorded value, so a late sample can't undo an earlier one.
+    pub fn record_cpu_times(&mut self, samples: &[(String, f64)]) -> Result<()> {
+        if samples.is_empty() {
+            return Ok(());
+        }
+        self.locked_mutate(|r| {
+            for (task_id, seconds) in samples {
+                if let Some(task) = r.get_task_mut(task_id) {
+                    if task.cpu_seconds.is_none_or(|prev| *seconds > prev) {
+                        task.cpu_seconds = Some(*seconds);
+                    }
+                }
             }
+            Ok(())
         })
     }
 
-    /// Mark task as failed (concurrent-safe)
-    pub fn mark_failed(&mut self, task_id: &str) -> Result<()> {
-        let id = task_id.to_string();
+    /// Start watching a process under `name` (concurrent-safe)
+    pub fn watch(&mut self, name: String, process: WatchedProcess) -> Result<()> {
         self.locked_mutate(|r| {
-            if let Some(task) = r.get_task_mut(&id) {
-                task.status = TaskStatus::Failed;
-                task.completed_at = Some(chrono::Utc::now());
+            if let Some(existing) = r.watched.get(&name) {
+                bail!("Already watching PID {} as '{}'", existing.pid, name);
             }
+            r.watched.insert(name, process);
+            Ok(())
         })
     }
 
-    /// Get registry statistics
-    pub fn stats(&self) -> RegistryStats {
-        let total = self.registry.tasks.len();
-        let running = self.registry.running_tasks().len();
-        let completed = self.registry.completed_tasks().len();
-        let failed = self
-            .registry
-            .tasks
-            .values()
-            .filter(|t| t.status == TaskStatus::Failed)
-            .count();
+    /// Stop watching a process (concurrent-safe)
+    pub fn unwatch(&mut self, name: &str) -> Result<Option<WatchedProcess>> {
+        if !self.registry.watched.contains_key(name) {
+            return Ok(None);
+        }
+        self.locked_mutate(|r| Ok(r.watched.remove(name)))
+    }
+
+    /// Apply a state-machine transition under the lock (concurrent-safe).
+    /// Invalid transitions (e.g. the task already finished in another
+    /// process) are logged and leave the task unchanged.
+    pub fn set_status(&mut self, task_id: &str, to: TaskStatus) -> Result<()> {
+        self.with_transaction(|txn| {
+            txn.set_status(task_id, to);
+            Ok(())
+        })
+    }
 
-        RegistryStats {
-            total,
-            running,
-            completed,
-            failed,
+    /// Get registry statistics, kept up to date as tasks change rather than
+    /// counted on every call
+    pub fn stats(&self) -> RegistryStats {
+        if self.stats_stale {
+            return self.registry().stats();
         }
+        self.stats.clone()
     }
 
-    /// Cleanup old completed tasks (older than specified days) — concurrent-safe
-    pub fn cleanup_old_tasks(&mut self, days: u64) -> Result<usize> {
-        let cutoff = chrono::Utc::now() - chrono::Duration::days(days as i64);
-        let mut removed = 0usize;
+    /// Per-group counts, spend and CPU time (see [`ProcessRegistry::stats_grouped`])
+    pub fn stats_grouped(
+        &self,
+        label: Option<&str>,
+        per_day: bool,
+    ) -> BTreeMap<String, GroupStats> {
+        self.registry().stats_grouped(label, per_day)
+    }
 
-        self.locked_mutate(|r| {
+    /// Total reported spend across all tasks
+    pub fn total_cost(&self) -> TaskCost {
+        self.registry().total_cost()
+    }
+
+    /// Cleanup old completed tasks (older than specified days), optionally
+    /// only those matching `filter`, from both the registry and the history
+    /// file — concurrent-safe. Returns the removed tasks.
+    pub fn cleanup_old_tasks(
+        &mut self,
+        days: u64,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<(String, TaskInfo)>> {
+        let now = chrono::Utc::now();
+        let cutoff = now - chrono::Duration::days(days as i64);
+        let is_old = |task_id: &str, task: &TaskInfo| {
+            task.status.is_terminal()
+                && task.completed_at.is_some_and(|c| c < cutoff)
+                && filter.is_none_or(|f| f.matches(task_id, task, now))
+        };
+
+        let history_path = self.history_path();
+        let events_path = self.events_path();
+        let mode = self.file_mode();
+        let actor = self.actor;
+        let removed = self.locked_mutate(|r| {
             let to_remove: Vec<String> = r
                 .tasks
                 .iter()
-                .filter(|(_, task)| {
-                    matches!(task.status, TaskStatus::Completed | TaskStatus::Failed)
-                        && task.completed_at.is_some_and(|c| c < cutoff)
-                })
+                .filter(|(task_id, task)| is_old(task_id, task))
                 .map(|(id, _)| id.clone())
                 .collect();
 
-            removed = to_remove.len();
-            for task_id in to_remove {
-                r.remove_task(&task_id);
+            let mut removed: Vec<(String, TaskInfo)> = to_remove
+                .into_iter()
+                .filter_map(|task_id| r.remove_task(&task_id).map(|task| (task_id, task)))
+                .collect();
+            removed.extend(compact_history(&history_path, mode, |id, task| {
+                !is_old(id, task)
+            })?);
+            let cleaned_up = lifecycle::cleaned_up(&removed, actor);
+            if let Err(e) = lifecycle::append(&events_path, &cleaned_up, mode) {
+                warn!("⚠️  {:#}", e);
             }
+            Ok(removed)
         })?;
 
+        // The history file was replaced; read it afresh next time
+        self.history = ProcessRegistry::new();
+        self.history_read = None;
+        self.invalidate();
+        self.recount();
         Ok(removed)
     }
 
-    /// Get reference to inner registry
+    /// Every known task: the registry file, plus archived tasks once
+    /// [`Self::load_history`] has been called
     pub fn registry(&self) -> &ProcessRegistry {
-        &self.registry
+        if self.history.tasks.is_empty() {
+            return &self.registry;
+        }
+        self.merged.get_or_init(|| {
+            let mut all = self.registry.clone();
+            for (task_id, task) in &self.history.tasks {
+                all.tasks
+                    .entry(task_id.clone())
+                    .or_insert_with(|| task.clone());
+            }
+            all
+        })
+    }
+}
+
+/// Changes applied together under one registry lock
+/// (see [`RegistryManager::with_transaction`]). Each change sees the ones
+/// made before it.
+pub struct Transaction<'a> {
+    registry: &'a mut ProcessRegistry,
+}
+
+impl Transaction<'_> {
+    /// Task as of this point in the transaction
+    pub fn get_task(&self, task_id: &str) -> Option<&TaskInfo> {
+        self.registry.get_task(task_id)
+    }
+
+    /// Add or replace a task. Replacing is a status transition and must be
+    /// allowed by the state machine.
+    pub fn register(&mut self, task_id: String, task: TaskInfo) -> Result<()> {
+        check_replace(self.registry, &task_id, &task)?;
+        self.registry.add_task(task_id, task);
+        Ok(())
+    }
+
+    /// Add `task` if its reservation fits next to every started task and
+    /// the tasks it depends on are ready, queueing or refusing it otherwise.
+    /// Returns the status it was given.
+    pub fn admit(
+        &mut self,
+        task_id: String,
+        mut task: TaskInfo,
+        reservable: Resources,
+        queue_if_full: bool,
+    ) -> Result<TaskStatus> {
+        if let Some(dep_id) = task
+            .depends_on
+            .iter()
+            .find(|dep_id| self.registry.get_task(dep_id).is_none())
+        {
+            bail!("Task {} depends on unknown task {}", task_id, dep_id);
+        }
+        match self.registry.dependencies_of(&task.depends_on) {
+            Dependencies::Ready => {}
+            Dependencies::Failed(dep_id) => bail!(
+                "Task {} not admitted: {} finished without becoming ready",
+                task_id,
+                dep_id
+            ),
+            Dependencies::Waiting(dep_id) => {
+                if !queue_if_full {
+                    bail!(
+                        "Task {} not admitted: {} isn't ready yet (use --queue to wait for it)",
+                        task_id,
+                        dep_id
+                    );
+                }
+                task.status = TaskStatus::Queued;
+            }
+        }
+        if let Some(request) = capacity::reservation_of(&task) {
+            let reserved = capacity::reserved(self.registry, Some(&task_id));
+            if let Err(e) = capacity::check_admission(reservable, reserved, request) {
+                if !queue_if_full {
+                    bail!("Task {} not admitted: {}", task_id, e);
+                }
+                task.status = TaskStatus::Queued;
+            }
+        }
+        let status = task.status.clone();
+        self.register(task_id, task)?;
+        Ok(status)
+    }
+
+    /// Apply a state-machine transition. Invalid transitions (e.g. the task
+    /// already finished in another process) are logged and leave the task
+    /// unchanged.
+    pub fn set_status(&mut self, task_id: &str, to: TaskStatus) {
+        if let Some(task) = self.registry.get_task_mut(task_id) {
+            if let Err(e) = task.transition_to(to) {
+                warn!("⚠️  Task {}: {}", task_id, e);
+            }
+        }
+    }
+
+    pub fn mark_complete(&mut self, task_id: &str) {
+        self.set_status(task_id, TaskStatus::Completed)
+    }
+
+    /// Record the likely cause of a failure (see [`crate::triage`]).
+    /// Only failed tasks take a hint.
+    pub fn set_triage_hint(&mut self, task_id: &str, hint: String) {
+        if let Some(task) = self.registry.get_task_mut(task_id) {
+            if task.status == TaskStatus::Failed {
+                task.triage_hint = Some(hint);
+            }
+        }
+    }
+
+    /// Record the block of output that shows why a task failed (see
+    /// [`crate::excerpt`]). Only failed tasks take one.
+    pub fn set_failure_excerpt(&mut self, task_id: &str, excerpt: String) {
+        if let Some(task) = self.registry.get_task_mut(task_id) {
+            if task.status == TaskStatus::Failed {
+                task.failure_excerpt = Some(excerpt);
+            }
+        }
+    }
+
+    /// Record the core dump a crashed task left. Only failed tasks take one.
+    pub fn set_coredump(&mut self, task_id: &str, coredump: Coredump) {
+        if let Some(task) = self.registry.get_task_mut(task_id) {
+            if task.status == TaskStatus::Failed {
+                task.coredump = Some(coredump);
+            }
+        }
+    }
+
+    /// Record how a task's container ended
+    pub fn set_container_exit(&mut self, task_id: &str, exit_code: Option<i64>) {
+        if let Some(docker) = self
+            .registry
+            .get_task_mut(task_id)
+            .and_then(|task| task.docker.as_mut())
+        {
+            docker.exit_code = exit_code;
+        }
+    }
+
+    /// Record what the task reported about its own run
+    pub fn set_result(&mut self, task_id: &str, result: TaskResult) {
+        if let Some(task) = self.registry.get_task_mut(task_id) {
+            task.result = Some(result);
+        }
+    }
+
+    pub fn mark_failed(&mut self, task_id: &str) {
+        self.set_status(task_id, TaskStatus::Failed)
+    }
+
+    /// Mark a task killed, recording why. A task that already finished
+    /// keeps its status (e.g. a zombie left behind by a completed task);
+    /// only the reason is recorded.
+    pub fn mark_killed(&mut self, task_id: &str, reason: &str) {
+        if let Some(task) = self.registry.get_task_mut(task_id) {
+            if !task.status.is_terminal() {
+                // Any unfinished task may be killed
+                let _ = task.transition_to(TaskStatus::Killed);
+            }
+            task.kill_reason = Some(reason.to_string());
+        }
+    }
+
+    /// Mark a task timed out, recording why. A task that finished in the
+    /// meantime keeps its status and has nothing recorded.
+    pub fn mark_timed_out(&mut self, task_id: &str, reason: &str) {
+        if let Some(task) = self.registry.get_task_mut(task_id) {
+            if task.transition_to(TaskStatus::TimedOut).is_ok() {
+                task.kill_reason = Some(reason.to_string());
+            }
+        }
+    }
+
+    /// Add reported token/spend counters to a task, returning its total
+    pub fn add_cost(&mut self, task_id: &str, cost: &TaskCost) -> Result<TaskCost> {
+        let Some(task) = self.registry.get_task_mut(task_id) else {
+            bail!("Task {} not found", task_id);
+        };
+        let total = task.cost.get_or_insert_with(TaskCost::default);
+        total.add(cost);
+        Ok(*total)
+    }
+
+    pub fn remove_task(&mut self, task_id: &str) -> Option<TaskInfo> {
+        self.registry.remove_task(task_id)
     }
 }
 
-/// Registry statistics
-#[derive(Debug, Clone)]
-pub struct RegistryStats {
-    pub total: usize,
-    pub running: usize,
-    pub completed: usize,
-    pub failed: usize,
+/// Rewrite the history file keeping only tasks for which `keep` holds.
+/// Must be called under the registry lock. Returns the dropped tasks.
+fn compact_history<F>(path: &Path, mode: u32, keep: F) -> Result<Vec<(String, TaskInfo)>>
+where
+    F: Fn(&str, &TaskInfo) -> bool,
+{
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).context("Failed to read task history"),
+    };
+
+    let mut kept = String::new();
+    let mut dropped = Vec::new();
+    for line in content.lines().filter(|l| !l.trim().is_empty()) {
+        let entry: HistoryEntry =
+            serde_json::from_str(line).context("Failed to parse task history")?;
+        if keep(&entry.task_id, &entry.task) {
+            kept += line;
+            kept.push('\n');
+        } else {
+            dropped.push((entry.task_id, entry.task));
+        }
+    }
+    if dropped.is_empty() {
+        return Ok(dropped);
+    }
+
+    let tmp_path = path.with_extension("jsonl.tmp");
+    fs::write(&tmp_path, kept).context("Failed to write temp task history")?;
+    files::set_mode(&tmp_path, mode).context("Failed to set task history permissions")?;
+    fs::rename(&tmp_path, path).context("Failed to replace task history")?;
+    Ok(dropped)
+}
+
+/// Reject replacing `task_id` unless the state machine allows the change
+fn check_replace(registry: &ProcessRegistry, task_id: &str, task: &TaskInfo) -> Result<()> {
+    if let Some(existing) = registry.get_task(task_id) {
+        if existing.status != task.status && !existing.status.can_transition_to(&task.status) {
+            bail!(
+                "Task {} is already {}; cannot re-register it as {} (use a new task ID)",
+                task_id,
+                existing.status,
+                task.status
+            );
+        }
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::{ExecutionMode, NativeTask, TaskStatus};
+    use crate::types::{NativeTask, ReadinessProbe, ResourceLimits, TaskCommand, TaskStatus};
     use chrono::Utc;
 
     #[test]
@@ -287,19 +1162,14 @@ mod tests {
 
         // Add a task
         let task = TaskInfo {
-            mode: ExecutionMode::Native,
-            command: "test command".to_string(),
-            status: TaskStatus::Running,
-            started_at: Utc::now(),
-            completed_at: None,
             native: Some(NativeTask {
                 pid: 12345,
                 pgid: 12344,
                 start_time: "test time".to_string(),
                 env_tag: None,
+                job: None,
             }),
-            docker: None,
-            constitution_rules: vec![],
+            ..TaskInfo::new(TaskCommand::shell("test command"))
         };
 
         manager.upsert_task("TEST-001".to_string(), task).unwrap();
@@ -338,19 +1208,14 @@ mod tests {
                     let mut mgr = RegistryManager::new(p.as_str());
                     mgr.load().unwrap();
                     let task = TaskInfo {
-                        mode: ExecutionMode::Native,
-                        command: format!("task {i}"),
-                        status: TaskStatus::Running,
-                        started_at: Utc::now(),
-                        completed_at: None,
                         native: Some(NativeTask {
                             pid: 10000 + i,
                             pgid: 10000 + i,
                             start_time: format!("t{i}"),
                             env_tag: None,
+                            job: None,
                         }),
-                        docker: None,
-                        constitution_rules: vec![],
+                        ..TaskInfo::new(TaskCommand::shell(format!("task {i}")))
                     };
                     mgr.upsert_task(format!("T{:03}", i), task).unwrap();
                 })
@@ -376,4 +1241,288 @@ mod tests {
         let _ = fs::remove_file(temp_path);
         let _ = fs::remove_file("/tmp/test_registry_concurrent.lock");
     }
+
+    #[test]
+    fn test_mark_killed_is_not_a_success() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut manager = RegistryManager::new(dir.path().join("process_registry.json"));
+        manager.load().unwrap();
+
+        let task = TaskInfo::new(TaskCommand::shell("sleep 1000"));
+        manager.upsert_task("T001".to_string(), task).unwrap();
+        manager.mark_killed("T001", "killed by user").unwrap();
+
+        let killed = manager.get_task("T001").unwrap();
+        assert_eq!(killed.status, TaskStatus::Killed);
+        assert_eq!(killed.kill_reason.as_deref(), Some("killed by user"));
+        assert!(killed.completed_at.is_some());
+
+        let stats = manager.stats();
+        assert_eq!((stats.completed, stats.killed), (0, 1));
+
+        // Cached counts follow every kind of change
+        manager.get_task_mut("T001").unwrap().status = TaskStatus::Failed;
+        assert_eq!((manager.stats().killed, manager.stats().failed), (0, 1));
+        manager.remove_task("T001").unwrap();
+        assert_eq!((manager.stats().total, manager.stats().failed), (0, 0));
+    }
+
+    #[test]
+    fn test_cost_is_cumulative_and_grouped_by_label() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut manager = RegistryManager::new(dir.path().join("process_registry.json"));
+        manager.load().unwrap();
+
+        for (id, team) in [
+            ("T001", Some("infra")),
+            ("T002", Some("infra")),
+            ("T003", None),
+        ] {
+            let mut task = TaskInfo::new(TaskCommand::shell("agent"));
+            if let Some(team) = team {
+                task.labels.insert("team".to_string(), team.to_string());
+            }
+            manager.upsert_task(id.to_string(), task).unwrap();
+        }
+
+        let step = TaskCost {
+            input_tokens: 1000,
+            output_tokens: 200,
+            usd: 0.5,
+        };
+        manager.add_cost("T001", &step).unwrap();
+        let total = manager.add_cost("T001", &step).unwrap();
+        assert_eq!((total.input_tokens, total.usd), (2000, 1.0));
+        manager.add_cost("T003", &step).unwrap();
+        assert!(manager.add_cost("T999", &step).is_err());
+
+        let groups = manager.stats_grouped(Some("team"), false);
+        assert_eq!(groups["infra"].tasks, 2);
+        assert_eq!(groups["infra"].cost.total_tokens(), 2400);
+        assert_eq!(groups["-"].cost.usd, 0.5);
+        assert_eq!(manager.total_cost().usd, 1.5);
+
+        // CPU samples only ever grow, and roll up per label and day
+        manager
+            .record_cpu_times(&[("T001".to_string(), 30.0), ("T002".to_string(), 12.5)])
+            .unwrap();
+        manager
+            .record_cpu_times(&[("T001".to_string(), 20.0)])
+            .unwrap();
+        assert_eq!(manager.get_task("T001").unwrap().cpu_seconds, Some(30.0));
+        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        let groups = manager.stats_grouped(Some("team"), true);
+        assert_eq!(groups[&format!("infra {today}")].cpu_seconds, 42.5);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_shared_registry_stays_group_writable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("process_registry.json");
+        let mut manager = RegistryManager::new(&path);
+        manager.save().unwrap();
+        assert_eq!(
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o660)).unwrap();
+        manager.load().unwrap();
+        manager.mark_complete("T001").unwrap();
+        assert!(manager.is_shared());
+        assert_eq!(
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o660
+        );
+    }
+
+    #[test]
+    fn test_admit_batch_reports_each_task() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut manager = RegistryManager::new(dir.path().join("process_registry.json"));
+        let task = |memory: &str| TaskInfo {
+            reservation: Some(ResourceLimits {
+                memory: memory.to_string(),
+                cpu: "0".to_string(),
+            }),
+            ..TaskInfo::new(TaskCommand::shell("pytest"))
+        };
+        let room = Resources {
+            memory_bytes: 2 * 1024 * 1024 * 1024,
+            cpus: 4.0,
+        };
+
+        // The third no longer fits next to the first two
+        let tasks = vec![
+            ("T001".to_string(), task("1g")),
+            ("T002".to_string(), task("1g")),
+            ("T003".to_string(), task("1g")),
+        ];
+        let results = manager.admit_batch(tasks, room, false).unwrap();
+        let outcomes: Vec<_> = results.iter().map(|(_, r)| r.is_ok()).collect();
+        assert_eq!(outcomes, [true, true, false]);
+        assert!(manager.get_task("T003").is_none());
+
+        let results = manager
+            .admit_batch(vec![("T003".to_string(), task("1g"))], room, true)
+            .unwrap();
+        assert_eq!(results[0].1.as_ref().unwrap(), &TaskStatus::Queued);
+
+        let mut reloaded = RegistryManager::new(dir.path().join("process_registry.json"));
+        reloaded.load().unwrap();
+        assert_eq!(reloaded.registry().tasks.len(), 3);
+
+        // Dependents wait for the server's probe, not just for it to run
+        let mut server = task("0m");
+        server.readiness = Some(ReadinessProbe::Port(8080));
+        let mut client = task("0m");
+        client.depends_on = vec!["SERVER".to_string()];
+        let mut orphan = task("0m");
+        orphan.depends_on = vec!["NOPE".to_string()];
+        let results = manager
+            .admit_batch(
+                vec![
+                    ("SERVER".to_string(), server),
+                    ("CLIENT".to_string(), client.clone()),
+                    ("ORPHAN".to_string(), orphan),
+                ],
+                room,
+                false,
+            )
+            .unwrap();
+        let outcomes: Vec<_> = results.iter().map(|(_, r)| r.is_ok()).collect();
+        assert_eq!(outcomes, [true, false, false]);
+
+        assert!(manager.mark_ready("SERVER").unwrap());
+        assert!(!manager.mark_ready("SERVER").unwrap());
+        let status = manager
+            .admit_task("CLIENT".to_string(), client, room, false)
+            .unwrap();
+        assert_eq!(status, TaskStatus::Running);
+    }
+
+    #[test]
+    fn test_transaction_is_all_or_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("process_registry.json");
+        let mut manager = RegistryManager::new(&path);
+        let task = TaskInfo::new(TaskCommand::shell("pytest"));
+        manager
+            .upsert_task("T001".to_string(), task.clone())
+            .unwrap();
+
+        // A failing step discards the earlier ones
+        let err = manager.with_transaction(|txn| {
+            txn.mark_failed("T001");
+            txn.add_cost("T404", &TaskCost::default())
+        });
+        assert!(err.is_err());
+        assert_eq!(
+            manager.get_task("T001").unwrap().status,
+            TaskStatus::Running
+        );
+        let mut reader = RegistryManager::new(&path);
+        reader.load().unwrap();
+        assert_eq!(reader.get_task("T001").unwrap().status, TaskStatus::Running);
+
+        // Retry: the old attempt fails and the new one appears together
+        manager
+            .with_transaction(|txn| {
+                txn.mark_failed("T001");
+                assert_eq!(txn.get_task("T001").unwrap().status, TaskStatus::Failed);
+                txn.register("T001-retry".to_string(), task)
+            })
+            .unwrap();
+        reader.load().unwrap();
+        assert_eq!(reader.get_task("T001").unwrap().status, TaskStatus::Failed);
+        assert_eq!(
+            reader.get_task("T001-retry").unwrap().status,
+            TaskStatus::Running
+        );
+    }
+
+    #[test]
+    fn test_finished_tasks_move_to_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("process_registry.json");
+        let mut manager = RegistryManager::new(&path);
+        let task = |status: TaskStatus, finished_hours_ago: Option<i64>| TaskInfo {
+            status,
+            started_at: Utc::now() - chrono::Duration::days(3),
+            completed_at: finished_hours_ago.map(|h| Utc::now() - chrono::Duration::hours(h)),
+            ..TaskInfo::new(TaskCommand::shell("cargo test"))
+        };
+        manager
+            .with_transaction(|txn| {
+                txn.register("RUN".to_string(), task(TaskStatus::Running, None))?;
+                txn.register("RECENT".to_string(), task(TaskStatus::Completed, Some(0)))?;
+                txn.register("OLD".to_string(), task(TaskStatus::Failed, Some(5)))?;
+                txn.register(
+                    "ANCIENT".to_string(),
+                    task(TaskStatus::Completed, Some(24 * 30)),
+                )
+            })
+            .unwrap();
+
+        assert_eq!(manager.archive_finished().unwrap(), 2);
+        assert_eq!(manager.archive_finished().unwrap(), 0);
+
+        // The registry file keeps only unfinished and recent tasks
+        let mut fresh = RegistryManager::new(&path);
+        fresh.load().unwrap();
+        let mut hot: Vec<_> = fresh.registry().tasks.keys().cloned().collect();
+        hot.sort();
+        assert_eq!(hot, ["RECENT", "RUN"]);
+
+        // History is read on request and merged into every view
+        fresh.load_history().unwrap();
+        assert_eq!(fresh.stats().total, 4);
+        assert_eq!(fresh.get_task("OLD").unwrap().status, TaskStatus::Failed);
+
+        // Later appends are picked up without re-reading the rest
+        fresh
+            .upsert_task("LATE".to_string(), task(TaskStatus::Killed, Some(2)))
+            .unwrap();
+        fresh.archive_finished().unwrap();
+        fresh.load_history().unwrap();
+        assert_eq!(fresh.history.tasks.len(), 3);
+        // Counts kept through every change agree with a recount, in this
+        // manager and in one that only sees the change on load
+        assert_eq!(fresh.stats(), fresh.registry().stats());
+        manager.load().unwrap();
+        manager.load_history().unwrap();
+        assert_eq!(manager.stats(), fresh.stats());
+
+        // Cleanup reaches into the history file
+        assert_eq!(fresh.cleanup_old_tasks(7, None).unwrap().len(), 1);
+        fresh.load_history().unwrap();
+        assert!(fresh.get_task("ANCIENT").is_none());
+        assert_eq!(fresh.stats().total, 4);
+        assert_eq!(fresh.stats(), fresh.registry().stats());
+    }
+
+    #[test]
+    fn test_ambiguous_fuzzy_match_lists_candidates() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut manager = RegistryManager::new(dir.path().join("process_registry.json"));
+        for id in ["worker-17-retry-3", "worker-18"] {
+            let task = TaskInfo::new(TaskCommand::shell("cargo test"));
+            manager.upsert_task(id.to_string(), task).unwrap();
+        }
+
+        assert_eq!(
+            manager.resolve_task_id("w17r3").unwrap().as_deref(),
+            Some("worker-17-retry-3")
+        );
+        let e = manager.resolve_task_id("wr").unwrap_err().to_string();
+        assert!(e.contains("ambiguous, matches 2 tasks"), "{e}");
+        assert!(
+            e.contains("worker-17-retry-3") && e.contains("worker-18"),
+            "{e}"
+        );
+        assert_eq!(manager.resolve_task_id("zz").unwrap(), None);
+    }
 }
@@ -0,0 +1,207 @@
+//! Resource history: the last [`HISTORY_LEN`] CPU, memory and disk I/O
+//! samples of every running task, taken by the daemon once per check and
+//! kept in `process_registry.samples.json` next to the registry, so
+//! `report` can show each task's trend as a sparkline.
+//!
+//! Only running tasks are kept: a task drops out of the file on the first
+//! check that doesn't sample it, so the file stays small however long the
+//! daemon runs.
+
+use crate::process::GroupUsage;
+use crate::types::IoStats;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// Samples kept per task
+pub const HISTORY_LEN: usize = 24;
+
+/// Resource history of the registry at `registry_path`
+pub fn samples_path(registry_path: &Path) -> PathBuf {
+    registry_path.with_extension("samples.json")
+}
+
+/// What a task used at one check
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Sample {
+    pub at: DateTime<Utc>,
+    /// Cumulative CPU time
+    pub cpu_seconds: f64,
+    pub memory_kb: u64,
+    /// Cumulative disk I/O (zero in samples written before it was taken)
+    #[serde(default)]
+    pub io: IoStats,
+}
+
+/// Recent samples of every running task, oldest first
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResourceHistory {
+    tasks: BTreeMap<String, VecDeque<Sample>>,
+}
+
+impl ResourceHistory {
+    /// The history at `path`; empty if the daemon hasn't written one
+    pub fn load(path: &Path) -> Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse {}", path.display())),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        }
+    }
+
+    /// Replace the history at `path`
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_string(self)?)
+            .context("Failed to write temp resource history")?;
+        fs::rename(&tmp_path, path).context("Failed to replace resource history")?;
+        Ok(())
+    }
+
+    /// Add the samples of one check taken `at`; tasks it didn't sample are
+    /// forgotten
+    pub fn record(&mut self, usage: &[(String, GroupUsage)], at: DateTime<Utc>) {
+        let mut tasks = BTreeMap::new();
+        for (task_id, usage) in usage {
+            let mut samples = self.tasks.remove(task_id).unwrap_or_default();
+            if samples.len() == HISTORY_LEN {
+                samples.pop_front();
+            }
+            samples.push_back(Sample {
+                at,
+                cpu_seconds: usage.cpu_seconds,
+                memory_kb: usage.memory_kb,
+                io: usage.io,
+            });
+            tasks.insert(task_id.clone(), samples);
+        }
+        self.tasks = tasks;
+    }
+
+    /// CPU use of `task_id` between successive samples, in percent of a core
+    pub fn cpu_percent(&self, task_id: &str) -> Vec<f64> {
+        self.rate(task_id, |sample| sample.cpu_seconds * 100.0)
+    }
+
+    /// Disk I/O of `task_id` between successive samples, reads and writes
+    /// together, in bytes per second
+    pub fn io_bytes_per_sec(&self, task_id: &str) -> Vec<f64> {
+        self.rate(task_id, |sample| {
+            (sample.io.read_bytes + sample.io.written_bytes) as f64
+        })
+    }
+
+    /// How fast the cumulative `counter` of `task_id` grew between
+    /// successive samples, per second. A counter that went down (a process
+    /// of the task exited) counts as no growth.
+    fn rate(&self, task_id: &str, counter: impl Fn(&Sample) -> f64) -> Vec<f64> {
+        let Some(samples) = self.tasks.get(task_id) else {
+            return Vec::new();
+        };
+        samples
+            .iter()
+            .zip(samples.iter().skip(1))
+            .filter_map(|(before, after)| {
+                let elapsed = (after.at - before.at).num_milliseconds() as f64 / 1000.0;
+                (elapsed > 0.0).then(|| ((counter(after) - counter(before)) / elapsed).max(0.0))
+            })
+            .collect()
+    }
+
+    /// Memory of `task_id` at each sample, in MB
+    pub fn memory_mb(&self, task_id: &str) -> Vec<f64> {
+        self.tasks.get(task_id).map_or_else(Vec::new, |samples| {
+            samples
+                .iter()
+                .map(|sample| sample.memory_kb as f64 / 1024.0)
+                .collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn usage(cpu_seconds: f64, memory_kb: u64) -> GroupUsage {
+        GroupUsage {
+            cpu_seconds,
+            memory_kb,
+            processes: 1,
+            threads: 1,
+            open_files: 3,
+            io: IoStats::default(),
+        }
+    }
+
+    #[test]
+    fn test_record_keeps_recent_samples_of_running_tasks() {
+        let start = Utc::now();
+        let mut history = ResourceHistory::default();
+        for i in 0..HISTORY_LEN + 2 {
+            let at = start + Duration::seconds(10 * i as i64);
+            let sampled = vec![
+                (
+                    "T1".to_string(),
+                    usage(i as f64 * 5.0, 1024 * (i as u64 + 1)),
+                ),
+                ("T2".to_string(), usage(0.0, 2048)),
+            ];
+            history.record(&sampled, at);
+        }
+        let cpu = history.cpu_percent("T1");
+        assert_eq!(cpu.len(), HISTORY_LEN - 1);
+        assert!(cpu.iter().all(|percent| (percent - 50.0).abs() < 1e-9));
+        let memory = history.memory_mb("T1");
+        assert_eq!(memory.len(), HISTORY_LEN);
+        assert_eq!(memory.last(), Some(&(HISTORY_LEN as f64 + 2.0)));
+
+        // T2 finished: gone after the next check
+        history.record(&[("T1".to_string(), usage(200.0, 1024))], start);
+        assert!(history.memory_mb("T2").is_empty());
+        assert!(history.cpu_percent("T3").is_empty());
+    }
+
+    #[test]
+    fn test_io_rate_between_samples() {
+        let start = Utc::now();
+        // 1 MB read and 1 MB written per sample, 10s apart
+        let io = |i: u64| IoStats {
+            read_bytes: i << 20,
+            written_bytes: i << 20,
+        };
+        let mut history = ResourceHistory::default();
+        for i in 0..3 {
+            let sampled = GroupUsage {
+                io: io(i),
+                ..usage(0.0, 1024)
+            };
+            history.record(
+                &[("T1".to_string(), sampled)],
+                start + Duration::seconds(10 * i as i64),
+            );
+        }
+        let rate = 2.0 * (1 << 20) as f64 / 10.0;
+        assert_eq!(history.io_bytes_per_sec("T1"), [rate, rate]);
+    }
+
+    #[test]
+    fn test_history_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = samples_path(&dir.path().join("process_registry.json"));
+        assert_eq!(
+            ResourceHistory::load(&path).unwrap(),
+            ResourceHistory::default()
+        );
+        let mut history = ResourceHistory::default();
+        history.record(&[("T1".to_string(), usage(1.5, 4096))], Utc::now());
+        history.save(&path).unwrap();
+        assert_eq!(ResourceHistory::load(&path).unwrap(), history);
+    }
+}
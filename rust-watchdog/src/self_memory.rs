@@ -0,0 +1,141 @@
+//! The daemon's own memory: budget enforcement and leak detection.
+//!
+//! The daemon samples its RSS once per check cycle. It raises an alarm when
+//! the RSS exceeds the configured budget, or when it has grown on every one
+//! of the last [`WINDOW`] samples and by more than 10% overall (a steady
+//! climb rather than noise).
+
+use std::collections::VecDeque;
+use std::time::Instant;
+
+/// Number of recent samples used for the trend
+pub const WINDOW: usize = 12;
+
+/// Minimum overall growth across the window to call it a leak (10%)
+const LEAK_GROWTH: f64 = 1.10;
+
+/// Why the daemon's memory needs attention
+#[derive(Debug, Clone, PartialEq)]
+pub enum MemoryAlarm {
+    /// RSS is above the configured budget
+    OverBudget { rss_kb: u64, budget_kb: u64 },
+    /// RSS grew on every sample in the window
+    Leaking { from_kb: u64, to_kb: u64 },
+}
+
+impl std::fmt::Display for MemoryAlarm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MemoryAlarm::OverBudget { rss_kb, budget_kb } => {
+                write!(f, "using {rss_kb}KB, over its {budget_kb}KB budget")
+            }
+            MemoryAlarm::Leaking { from_kb, to_kb } => write!(
+                f,
+                "grew from {from_kb}KB to {to_kb}KB over the last {WINDOW} checks"
+            ),
+        }
+    }
+}
+
+/// Rolling window of the daemon's RSS samples
+#[derive(Debug)]
+pub struct MemoryTracker {
+    budget_kb: Option<u64>,
+    /// (seconds since start, RSS in KB)
+    samples: VecDeque<(f64, u64)>,
+    started: Instant,
+}
+
+impl MemoryTracker {
+    pub fn new(budget_kb: Option<u64>) -> Self {
+        Self {
+            budget_kb,
+            samples: VecDeque::with_capacity(WINDOW),
+            started: Instant::now(),
+        }
+    }
+
+    /// Record a sample taken now and check it against the budget and trend
+    pub fn record(&mut self, rss_kb: u64) -> Option<MemoryAlarm> {
+        self.record_at(self.started.elapsed().as_secs_f64(), rss_kb)
+    }
+
+    fn record_at(&mut self, at_secs: f64, rss_kb: u64) -> Option<MemoryAlarm> {
+        if self.samples.len() == WINDOW {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((at_secs, rss_kb));
+
+        if let Some(budget_kb) = self.budget_kb {
+            if rss_kb > budget_kb {
+                return Some(MemoryAlarm::OverBudget { rss_kb, budget_kb });
+            }
+        }
+
+        if self.samples.len() == WINDOW {
+            let rising = self
+                .samples
+                .iter()
+                .zip(self.samples.iter().skip(1))
+                .all(|((_, a), (_, b))| b > a);
+            let (from_kb, to_kb) = (self.samples[0].1, rss_kb);
+            if rising && to_kb as f64 > from_kb as f64 * LEAK_GROWTH {
+                return Some(MemoryAlarm::Leaking { from_kb, to_kb });
+            }
+        }
+
+        None
+    }
+
+    /// RSS growth rate over the window in KB per hour, once two samples exist
+    pub fn trend_kb_per_hour(&self) -> Option<f64> {
+        let (t0, first) = *self.samples.front()?;
+        let (t1, last) = *self.samples.back()?;
+        if t1 <= t0 {
+            return None;
+        }
+        Some((last as f64 - first as f64) / (t1 - t0) * 3600.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_over_budget() {
+        let mut tracker = MemoryTracker::new(Some(10_000));
+        assert_eq!(tracker.record_at(0.0, 8_000), None);
+        assert_eq!(
+            tracker.record_at(60.0, 12_000),
+            Some(MemoryAlarm::OverBudget {
+                rss_kb: 12_000,
+                budget_kb: 10_000
+            })
+        );
+    }
+
+    #[test]
+    fn test_monotonic_growth_is_a_leak() {
+        let mut tracker = MemoryTracker::new(None);
+        let mut alarm = None;
+        for i in 0..WINDOW as u64 {
+            alarm = tracker.record_at(i as f64 * 60.0, 5_000 + i * 100);
+        }
+        assert!(matches!(
+            alarm,
+            Some(MemoryAlarm::Leaking { from_kb: 5_000, .. })
+        ));
+        assert_eq!(tracker.trend_kb_per_hour(), Some(6_000.0));
+    }
+
+    #[test]
+    fn test_noise_is_not_a_leak() {
+        let mut tracker = MemoryTracker::new(None);
+        for i in 0..WINDOW as u64 * 2 {
+            // Sawtooth: allocations freed again every other cycle
+            let rss = if i % 2 == 0 { 5_000 } else { 5_400 };
+            assert_eq!(tracker.record_at(i as f64, rss), None);
+        }
+    }
+}
@@ -0,0 +1,134 @@
+//! Line editing for `shell`: splitting a line into arguments, tab
+//! completion of command names and task IDs, and the history file.
+//!
+//! The prompt loop itself lives with the CLI, which parses and runs each
+//! line like a `task-watchdog` invocation in the same process.
+
+use anyhow::{Context as _, Result};
+use rustyline::completion::{Completer, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+use std::path::{Path, PathBuf};
+
+/// History of shell lines, kept next to the registry
+pub const HISTORY_FILE: &str = "shell_history";
+
+/// History file for the registry at `registry_path`
+pub fn history_path(registry_path: &Path) -> PathBuf {
+    registry_path.with_file_name(HISTORY_FILE)
+}
+
+/// Arguments of a shell line, quoted as in a POSIX shell
+pub fn split(line: &str) -> Result<Vec<String>> {
+    shell_words::split(line).context("Unbalanced quotes")
+}
+
+/// Completes the first word to a command and later ones to a task ID
+pub struct ShellHelper {
+    commands: Vec<String>,
+    task_ids: Vec<String>,
+}
+
+impl ShellHelper {
+    pub fn new(commands: Vec<String>) -> Self {
+        Self {
+            commands,
+            task_ids: Vec::new(),
+        }
+    }
+
+    /// Task IDs to complete, refreshed before each prompt
+    pub fn set_task_ids(&mut self, task_ids: Vec<String>) {
+        self.task_ids = task_ids;
+    }
+
+    fn candidates(&self, line: &str, pos: usize) -> (usize, Vec<Pair>) {
+        let before = &line[..pos];
+        let start = before.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let word = &before[start..];
+        let first = before[..start].trim().is_empty();
+        let pool = if first {
+            &self.commands
+        } else if word.starts_with('-') {
+            return (start, Vec::new());
+        } else {
+            &self.task_ids
+        };
+        let mut matches: Vec<Pair> = pool
+            .iter()
+            .filter(|candidate| candidate.starts_with(word))
+            .map(|candidate| Pair {
+                display: candidate.clone(),
+                replacement: format!("{candidate} "),
+            })
+            .collect();
+        matches.sort_by(|a, b| a.display.cmp(&b.display));
+        (start, matches)
+    }
+}
+
+impl Completer for ShellHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        Ok(self.candidates(line, pos))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn helper() -> ShellHelper {
+        let mut helper = ShellHelper::new(vec!["check".into(), "cleanup".into(), "kill".into()]);
+        helper.set_task_ids(vec!["T002".into(), "T001".into(), "build".into()]);
+        helper
+    }
+
+    fn complete(line: &str) -> (usize, Vec<String>) {
+        let (start, pairs) = helper().candidates(line, line.len());
+        (start, pairs.into_iter().map(|pair| pair.display).collect())
+    }
+
+    #[test]
+    fn test_first_word_completes_commands() {
+        assert_eq!(complete("c"), (0, vec!["check".into(), "cleanup".into()]));
+        assert_eq!(complete("  ki"), (2, vec!["kill".into()]));
+    }
+
+    #[test]
+    fn test_later_words_complete_task_ids() {
+        assert_eq!(
+            complete("check T0"),
+            (6, vec!["T001".into(), "T002".into()])
+        );
+        assert_eq!(complete("kill --reason x b"), (16, vec!["build".into()]));
+        assert_eq!(complete("check --ver"), (6, vec![]));
+    }
+
+    #[test]
+    fn test_split_honours_quotes() {
+        assert_eq!(
+            split(r#"register T1 --command "pytest -x""#).unwrap(),
+            vec!["register", "T1", "--command", "pytest -x"]
+        );
+        assert!(split("check 'T1").is_err());
+    }
+}
@@ -0,0 +1,165 @@
+//! Where the registry is stored.
+//!
+//! [`RegistryManager`](crate::registry::RegistryManager) keeps the registry
+//! in memory and reads and writes it through a [`RegistryBackend`], always
+//! under its lock file. JSON, one file rewritten on every change, is the
+//! default. SQLite (`--features sqlite`, `--registry-backend sqlite`) writes
+//! only the tasks a change touched, which matters once thousands of tasks
+//! are registered. Archived tasks go to the history file either way.
+//!
+//! The first time a registry is opened with SQLite, a JSON registry at the
+//! same path is imported and renamed to `*.json.migrated`. From then on the
+//! database is found and used without the flag.
+
+use crate::files;
+#[cfg(not(feature = "sqlite"))]
+use crate::warn;
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU8, Ordering};
+use task_watchdog_core::types::ProcessRegistry;
+
+#[cfg(feature = "sqlite")]
+mod sqlite;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteBackend;
+
+/// Storage for the tasks not yet archived, the watched processes and the
+/// boot ID. Writes and migrations happen with the registry lock held.
+pub trait RegistryBackend: Send + Sync {
+    /// `json`, `sqlite`
+    fn name(&self) -> &'static str;
+
+    /// File the registry lives in
+    fn path(&self) -> &Path;
+
+    /// Whether a registry has been stored yet
+    fn exists(&self) -> bool;
+
+    /// Read the whole registry
+    fn read(&self) -> Result<ProcessRegistry>;
+
+    /// Store `registry`, readable by the owner only or, for a shared
+    /// registry, by the group too (`mode`). `before` is the registry as it
+    /// was read, when known, so a backend can write only what changed.
+    fn write(
+        &self,
+        before: Option<&ProcessRegistry>,
+        registry: &ProcessRegistry,
+        mode: u32,
+    ) -> Result<()>;
+
+    /// Whether a registry in another format waits to be imported
+    fn needs_migration(&self) -> bool {
+        false
+    }
+
+    /// Import that registry. Returns how many tasks it held.
+    fn migrate(&self) -> Result<usize> {
+        Ok(0)
+    }
+}
+
+/// Registry backend to use (`--registry-backend`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum BackendKind {
+    Json,
+    Sqlite,
+}
+
+/// Chosen backend: 0 until chosen (see [`open`]), then the kind plus one
+static BACKEND: AtomicU8 = AtomicU8::new(0);
+
+/// Set the process-wide registry backend. Fails if this build lacks it.
+pub fn set_backend(kind: BackendKind) -> Result<()> {
+    if kind == BackendKind::Sqlite && !cfg!(feature = "sqlite") {
+        bail!("This build has no SQLite support (build with --features sqlite)");
+    }
+    BACKEND.store(kind as u8 + 1, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Backend for the registry at `registry_path` (the JSON file's path).
+/// Without a chosen backend, SQLite is used if its database exists.
+pub fn open(registry_path: &Path) -> Box<dyn RegistryBackend> {
+    let kind = match BACKEND.load(Ordering::Relaxed) {
+        1 => BackendKind::Json,
+        2 => BackendKind::Sqlite,
+        _ if database_path(registry_path).exists() => BackendKind::Sqlite,
+        _ => BackendKind::Json,
+    };
+    match kind {
+        #[cfg(feature = "sqlite")]
+        BackendKind::Sqlite => Box::new(SqliteBackend::new(registry_path)),
+        #[cfg(not(feature = "sqlite"))]
+        BackendKind::Sqlite => {
+            warn!(
+                "⚠️  {} exists, but this build has no SQLite support; using JSON",
+                database_path(registry_path).display()
+            );
+            Box::new(JsonBackend::new(registry_path))
+        }
+        BackendKind::Json => Box::new(JsonBackend::new(registry_path)),
+    }
+}
+
+/// SQLite database next to the JSON registry path
+pub fn database_path(registry_path: &Path) -> PathBuf {
+    registry_path.with_extension("db")
+}
+
+/// The registry as one pretty-printed JSON file, replaced atomically
+pub struct JsonBackend {
+    path: PathBuf,
+}
+
+impl JsonBackend {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+        }
+    }
+}
+
+impl RegistryBackend for JsonBackend {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn exists(&self) -> bool {
+        self.path.exists()
+    }
+
+    fn read(&self) -> Result<ProcessRegistry> {
+        let content = fs::read_to_string(&self.path).context("Failed to read registry file")?;
+        serde_json::from_str(&content).context("Failed to parse registry JSON")
+    }
+
+    fn write(
+        &self,
+        _before: Option<&ProcessRegistry>,
+        registry: &ProcessRegistry,
+        mode: u32,
+    ) -> Result<()> {
+        let json =
+            serde_json::to_string_pretty(registry).context("Failed to serialize registry")?;
+
+        // Write to a sibling temp file, then rename (atomic on Linux/macOS)
+        let tmp_path = self.path.with_extension("json.tmp");
+        fs::write(&tmp_path, &json).context("Failed to write temp registry file")?;
+
+        fs::rename(&tmp_path, &self.path).context("Failed to atomically rename registry file")?;
+
+        // SECURITY-003: Set permissions to 0600 (owner read/write only),
+        // or keep 0660 on a registry deliberately shared with a group
+        files::set_mode(&self.path, mode)
+            .with_context(|| format!("Failed to set registry file permissions to {mode:o}"))?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,222 @@
+//! The registry in SQLite: one row per task, so a change rewrites only the
+//! tasks it touched.
+
+use super::{JsonBackend, RegistryBackend};
+use crate::files;
+use crate::info;
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use task_watchdog_core::types::{ProcessRegistry, TaskInfo};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS tasks (
+        task_id TEXT PRIMARY KEY,
+        task TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS meta (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+";
+
+/// `meta` key of everything in the registry but its tasks
+const REST_KEY: &str = "registry";
+
+/// How long to wait for another connection's write (the registry lock
+/// already serializes task-watchdog processes)
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct SqliteBackend {
+    path: PathBuf,
+    /// JSON registry imported on first use
+    json: JsonBackend,
+}
+
+impl SqliteBackend {
+    /// Database next to the JSON registry at `registry_path`
+    pub fn new(registry_path: &Path) -> Self {
+        Self {
+            path: super::database_path(registry_path),
+            json: JsonBackend::new(registry_path),
+        }
+    }
+
+    fn connect(&self) -> Result<Connection> {
+        let conn = Connection::open(&self.path)
+            .with_context(|| format!("Failed to open {}", self.path.display()))?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        conn.execute_batch(SCHEMA)
+            .context("Failed to create registry tables")?;
+        Ok(conn)
+    }
+}
+
+impl RegistryBackend for SqliteBackend {
+    fn name(&self) -> &'static str {
+        "sqlite"
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn exists(&self) -> bool {
+        self.path.exists()
+    }
+
+    fn read(&self) -> Result<ProcessRegistry> {
+        let conn = self.connect()?;
+        let rest: Option<String> = conn
+            .query_row("SELECT value FROM meta WHERE key = ?1", [REST_KEY], |row| {
+                row.get(0)
+            })
+            .optional()?;
+        let mut registry: ProcessRegistry = match rest {
+            Some(rest) => serde_json::from_str(&rest).context("Failed to parse registry")?,
+            None => ProcessRegistry::new(),
+        };
+
+        let mut statement = conn.prepare("SELECT task_id, task FROM tasks")?;
+        let rows = statement.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+        for row in rows {
+            let (task_id, task) = row?;
+            let task: TaskInfo = serde_json::from_str(&task)
+                .with_context(|| format!("Failed to parse task {task_id}"))?;
+            registry.tasks.insert(task_id, task);
+        }
+        Ok(registry)
+    }
+
+    fn write(
+        &self,
+        before: Option<&ProcessRegistry>,
+        registry: &ProcessRegistry,
+        mode: u32,
+    ) -> Result<()> {
+        let mut conn = self.connect()?;
+        let txn = conn.transaction()?;
+
+        // Tasks as they were stored, to skip the unchanged ones
+        let stored: HashMap<&String, String> = match before {
+            Some(before) => before
+                .tasks
+                .iter()
+                .map(|(task_id, task)| Ok((task_id, serde_json::to_string(task)?)))
+                .collect::<Result<_>>()?,
+            None => {
+                txn.execute("DELETE FROM tasks", [])?;
+                HashMap::new()
+            }
+        };
+        for task_id in stored.keys() {
+            if !registry.tasks.contains_key(*task_id) {
+                txn.execute("DELETE FROM tasks WHERE task_id = ?1", [task_id])?;
+            }
+        }
+        for (task_id, task) in &registry.tasks {
+            let json = serde_json::to_string(task).context("Failed to serialize task")?;
+            if stored.get(task_id) != Some(&json) {
+                txn.execute(
+                    "INSERT OR REPLACE INTO tasks (task_id, task) VALUES (?1, ?2)",
+                    params![task_id, json],
+                )?;
+            }
+        }
+
+        let rest = ProcessRegistry {
+            tasks: HashMap::new(),
+            boot_id: registry.boot_id.clone(),
+            watched: registry.watched.clone(),
+        };
+        txn.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES (?1, ?2)",
+            params![REST_KEY, serde_json::to_string(&rest)?],
+        )?;
+        txn.commit().context("Failed to write registry")?;
+
+        files::set_mode(&self.path, mode)
+            .with_context(|| format!("Failed to set registry file permissions to {mode:o}"))?;
+        Ok(())
+    }
+
+    fn needs_migration(&self) -> bool {
+        !self.path.exists() && self.json.exists()
+    }
+
+    fn migrate(&self) -> Result<usize> {
+        let registry = self.json.read()?;
+        // The owner's only where there are no modes to copy
+        let mode = files::mode(self.json.path()).unwrap_or(0o600) & 0o777;
+        self.write(None, &registry, mode)?;
+
+        // Kept rather than deleted, and renamed so nothing reads it as current
+        let mut migrated = self.json.path().as_os_str().to_owned();
+        migrated.push(".migrated");
+        fs::rename(self.json.path(), &migrated)
+            .context("Failed to move the migrated JSON registry aside")?;
+        info!(
+            "📦 Moved {} tasks from {} to {}",
+            registry.tasks.len(),
+            self.json.path().display(),
+            self.path.display()
+        );
+        Ok(registry.tasks.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::RegistryManager;
+    use task_watchdog_core::types::{ExecutionMode, TaskCommand, TaskOrigin, TaskStatus};
+
+    fn task() -> TaskInfo {
+        crate::tasks::new_task(
+            TaskCommand::shell("pytest"),
+            Default::default(),
+            Vec::new(),
+            None,
+            TaskOrigin {
+                tool: "cli".into(),
+                user: None,
+                host: None,
+                tmux: None,
+            },
+        )
+    }
+
+    #[test]
+    fn test_json_registry_is_migrated_and_updated_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("process_registry.json");
+        let mut json = RegistryManager::with_backend(&path, Box::new(JsonBackend::new(&path)));
+        json.upsert_task("T001".into(), task()).unwrap();
+
+        let mut manager = RegistryManager::with_backend(&path, Box::new(SqliteBackend::new(&path)));
+        manager.load().unwrap();
+        assert!(!path.exists());
+        assert!(dir.path().join("process_registry.json.migrated").exists());
+        assert_eq!(
+            manager.get_task("T001").unwrap().mode,
+            ExecutionMode::Native
+        );
+
+        manager.upsert_task("T002".into(), task()).unwrap();
+        manager.mark_complete("T001").unwrap();
+        let mut reloaded =
+            RegistryManager::with_backend(&path, Box::new(SqliteBackend::new(&path)));
+        reloaded.load().unwrap();
+        assert_eq!(
+            reloaded.get_task("T001").unwrap().status,
+            TaskStatus::Completed
+        );
+        assert!(reloaded.get_task("T002").is_some());
+        assert_eq!(reloaded.registry().boot_id, manager.registry().boot_id);
+    }
+}
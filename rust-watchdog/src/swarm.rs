@@ -0,0 +1,94 @@
+//! Swarm files: a list of commands for `swarm` to run, a few at a time.
+//!
+//! ```yaml
+//! - pytest tests/api
+//! - pytest tests/db
+//! - task_id: lint
+//!   command: make lint && make test
+//!   shell: true
+//!   memory: 1g
+//! ```
+//!
+//! An entry is a command, or a task as for `register --from-file` whose
+//! `task_id` may be left out. Entries without one are named
+//! `<group>-<N>`, counting from 1 in file order. JSON parses too.
+
+use crate::tasks::TaskSpec;
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use std::path::Path;
+
+/// The swarm's group when none is given: the file's name
+pub fn default_group(path: &Path) -> Result<String> {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .context("A swarm file without a --group needs a file name")
+}
+
+/// Read the tasks of the swarm file at `path`, naming unnamed ones after
+/// `group`
+pub fn load(path: &Path, group: &str) -> Result<Vec<TaskSpec>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    parse(&content, group).with_context(|| format!("Invalid swarm file {}", path.display()))
+}
+
+fn parse(content: &str, group: &str) -> Result<Vec<TaskSpec>> {
+    let Value::Array(entries) = serde_yaml_ng::from_str(content)? else {
+        bail!("Expected a list of commands or tasks");
+    };
+    entries
+        .into_iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let n = i + 1;
+            let mut entry = match entry {
+                Value::String(command) => serde_json::json!({ "command": command }),
+                // YAML reads `- true` as a boolean, not the command
+                Value::Bool(_) | Value::Number(_) => {
+                    serde_json::json!({ "command": entry.to_string() })
+                }
+                Value::Object(task) => Value::Object(task),
+                _ => bail!("Entry {} is neither a command nor a task", n),
+            };
+            if entry.get("task_id").is_none() {
+                entry["task_id"] = format!("{group}-{n}").into();
+            }
+            serde_json::from_value(entry).with_context(|| format!("In entry {n}"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commands_and_tasks_mix() {
+        let specs = parse(
+            "- pytest tests/api\n\
+             - task_id: lint\n  command: make lint && make test\n  shell: true\n\
+             - command: cargo test\n  memory: 1g\n",
+            "ci",
+        )
+        .unwrap();
+        let names: Vec<_> = specs.iter().map(|s| s.task_id.as_str()).collect();
+        assert_eq!(names, ["ci-1", "lint", "ci-3"]);
+        assert_eq!(specs[0].command, "pytest tests/api");
+        assert!(specs[1].shell);
+        assert_eq!(specs[2].memory.as_deref(), Some("1g"));
+
+        // JSON is YAML too
+        let specs = parse(r#"["make", {"command": "make test"}]"#, "ci").unwrap();
+        assert_eq!(specs[1].task_id, "ci-2");
+        assert_eq!(parse("- true", "ci").unwrap()[0].command, "true");
+    }
+
+    #[test]
+    fn test_bad_entries_are_refused() {
+        assert!(parse("command: make", "ci").is_err());
+        assert!(parse("- [make, test]", "ci").is_err());
+        let e = parse("- pytest\n- comand: make\n", "ci").unwrap_err();
+        assert!(format!("{e:#}").contains("In entry 2"));
+    }
+}
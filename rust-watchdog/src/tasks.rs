@@ -0,0 +1,801 @@
+//! Creating and stopping tasks, shared by the CLI and the HTTP and gRPC APIs.
+
+use crate::capacity::{self, Resources};
+use crate::capture::OutputBuffers;
+use crate::docker::{DockerManager, StopOutcome};
+use crate::done_files;
+use crate::logs;
+use crate::policy;
+use crate::process::{ProcessManager, TASK_ID_ENV};
+use crate::readiness;
+use crate::registry::RegistryManager;
+use crate::template::Vars;
+use crate::thermal::ThermalStatus;
+use crate::types::{
+    DockerTask, ExecutionMode, NativeTask, ReadinessProbe, ResourceLimits, Rlimit, TaskCommand,
+    TaskInfo, TaskOrigin, TaskStatus,
+};
+use crate::units;
+use crate::{info, warn};
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+/// Descriptive fields for a new task: what it is meant to achieve (for
+/// whoever rehydrates it later), labels and a group to sort it by, the
+/// caller's own metadata, the file that signals it is done, how long it may
+/// run and how much memory and CPU it may use, the tasks it waits for and
+/// how tasks waiting for it can tell it's ready
+#[derive(Debug, Clone, Default)]
+pub struct Details {
+    pub description: Option<String>,
+    pub expected_outcome: Option<String>,
+    pub labels: BTreeMap<String, String>,
+    pub group: Option<String>,
+    pub metadata: serde_json::Value,
+    pub done_file: Option<PathBuf>,
+    pub timeout_secs: Option<u64>,
+    pub max_memory_mb: Option<u64>,
+    pub max_cpu_percent: Option<f64>,
+    pub max_processes: Option<u64>,
+    pub max_open_files: Option<u64>,
+    pub max_threads: Option<u64>,
+    pub rlimits: BTreeMap<Rlimit, u64>,
+    pub depends_on: Vec<String>,
+    pub readiness: Option<ReadinessProbe>,
+}
+
+impl Details {
+    /// Replace template variables in the description, expected outcome,
+    /// group and label values
+    pub fn render(&mut self, vars: &Vars) -> Result<()> {
+        vars.render_opt(&mut self.description)?;
+        vars.render_opt(&mut self.expected_outcome)?;
+        vars.render_opt(&mut self.group)?;
+        vars.render_values(&mut self.labels)
+    }
+}
+
+/// Native task in `Running` status, remembering where it was registered
+/// from so checkouts can be told apart
+pub fn new_task(
+    command: TaskCommand,
+    details: Details,
+    constitution_rules: Vec<String>,
+    reservation: Option<ResourceLimits>,
+    origin: TaskOrigin,
+) -> TaskInfo {
+    let cwd = std::env::current_dir().ok();
+    let repo_root = cwd.as_deref().and_then(ProcessManager::repo_root);
+    TaskInfo {
+        description: details.description,
+        expected_outcome: details.expected_outcome,
+        cwd,
+        repo_root,
+        owner: ProcessManager::current_user(),
+        origin: Some(origin),
+        labels: details.labels,
+        group: details.group,
+        reservation,
+        done_file: details.done_file,
+        timeout_secs: details.timeout_secs,
+        max_memory_mb: details.max_memory_mb,
+        max_cpu_percent: details.max_cpu_percent,
+        max_processes: details.max_processes,
+        max_open_files: details.max_open_files,
+        max_threads: details.max_threads,
+        rlimits: details.rlimits,
+        depends_on: details.depends_on,
+        readiness: details.readiness,
+        constitution_rules,
+        metadata: details.metadata,
+        ..TaskInfo::new(command)
+    }
+}
+
+/// Reservation from `--memory` / `--cpu` (either may be left out)
+pub fn reservation_from(
+    memory: Option<String>,
+    cpu: Option<String>,
+) -> Result<Option<ResourceLimits>> {
+    if memory.is_none() && cpu.is_none() {
+        return Ok(None);
+    }
+    let limits = ResourceLimits {
+        memory: memory.unwrap_or_else(|| "0m".to_string()),
+        cpu: cpu.unwrap_or_else(|| "0".to_string()),
+    };
+    Resources::from_limits(&limits)?;
+    Ok(Some(limits))
+}
+
+/// Seconds from a `--timeout` like `90s`, `30m` or `2h`
+pub fn parse_timeout(s: &str) -> Result<u64> {
+    match units::parse_duration(s) {
+        Some(secs) if secs >= 1.0 => Ok(secs.round() as u64),
+        _ => anyhow::bail!("Invalid timeout '{}' (expected e.g. 90s, 30m or 2h)", s),
+    }
+}
+
+/// Megabytes from a `--max-memory` like `512m` or `2g`
+pub fn parse_max_memory(s: &str) -> Result<u64> {
+    match DockerManager::parse_memory(s) {
+        Ok(bytes) if bytes >= 1024 * 1024 => Ok(bytes as u64 / (1024 * 1024)),
+        _ => anyhow::bail!("Invalid memory limit '{}' (expected e.g. 512m or 2g)", s),
+    }
+}
+
+/// Percent of one core from a `--max-cpu` like `150` or `150%`
+pub fn parse_max_cpu(s: &str) -> Result<f64> {
+    match s.trim().trim_end_matches('%').parse::<f64>() {
+        Ok(percent) if percent > 0.0 => Ok(percent),
+        _ => anyhow::bail!(
+            "Invalid CPU limit '{}' (expected a percent of one core, e.g. 150)",
+            s
+        ),
+    }
+}
+
+/// Constitution rules from a comma-separated list
+pub fn parse_rules(rules: Option<String>) -> Vec<String> {
+    rules
+        .map(|r| r.split(',').map(|s| s.trim().to_string()).collect())
+        .unwrap_or_default()
+}
+
+/// A task to register, as given in a `register --from-file` array or
+/// posted to the API
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TaskSpec {
+    pub task_id: String,
+    pub command: String,
+    #[serde(default)]
+    pub shell: bool,
+    pub description: Option<String>,
+    pub expected_outcome: Option<String>,
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+    /// Group it belongs to, as for `--group`
+    pub group: Option<String>,
+    pub memory: Option<String>,
+    pub cpu: Option<String>,
+    #[serde(default)]
+    pub rules: Vec<String>,
+    #[serde(default)]
+    pub metadata: serde_json::Value,
+    /// Longest it may run, as for `--timeout` (`30m`, ...)
+    pub timeout: Option<String>,
+    /// Tasks that must be ready first
+    #[serde(default)]
+    pub after: Vec<String>,
+    /// Readiness probe, as for `--ready` (`port:8080`, ...)
+    pub ready: Option<String>,
+    /// Memory above which it is flagged, as for `--max-memory` (`512m`, ...)
+    pub max_memory: Option<String>,
+    /// CPU above which it is flagged, as for `--max-cpu` (percent of one core)
+    pub max_cpu: Option<f64>,
+    /// Processes it may run at once, as for `--max-processes`
+    pub max_processes: Option<u64>,
+    /// As for `--max-open-files` and `--max-threads`
+    pub max_open_files: Option<u64>,
+    pub max_threads: Option<u64>,
+    /// Kernel limits, as for `--rlimit` (`nofile=4096`, ...), on top of
+    /// the defaults
+    #[serde(default)]
+    pub rlimits: Vec<String>,
+}
+
+impl TaskSpec {
+    /// Replace template variables in the command and the other text fields
+    pub fn render(&mut self, vars: &Vars) -> Result<()> {
+        self.command = vars.render(&self.command)?;
+        vars.render_opt(&mut self.description)?;
+        vars.render_opt(&mut self.expected_outcome)?;
+        vars.render_values(&mut self.labels)?;
+        vars.render_opt(&mut self.group)?;
+        vars.render_opt(&mut self.ready)
+    }
+
+    /// The task to register. `defaults`, `rules` and `reservation` fill in
+    /// what the spec leaves out; the command must pass the policy.
+    pub fn into_task(
+        self,
+        defaults: &Details,
+        rules: &[String],
+        reservation: Option<&ResourceLimits>,
+        origin: TaskOrigin,
+    ) -> Result<TaskInfo> {
+        let command = TaskCommand::parse(&self.command, self.shell)?;
+        policy::check_command(&command)?;
+        let reservation = match (self.memory, self.cpu) {
+            (None, None) => reservation.cloned(),
+            (memory, cpu) => reservation_from(memory, cpu)?,
+        };
+        let mut labels = defaults.labels.clone();
+        labels.extend(self.labels);
+        let mut rlimits = defaults.rlimits.clone();
+        for setting in &self.rlimits {
+            let (rlimit, value) = Rlimit::parse_setting(setting)?;
+            rlimits.insert(rlimit, value);
+        }
+        check_rlimits(&rlimits)?;
+        let details = Details {
+            description: self.description.or_else(|| defaults.description.clone()),
+            expected_outcome: self
+                .expected_outcome
+                .or_else(|| defaults.expected_outcome.clone()),
+            labels,
+            group: self.group.or_else(|| defaults.group.clone()),
+            metadata: if self.metadata.is_null() {
+                defaults.metadata.clone()
+            } else {
+                self.metadata
+            },
+            done_file: None,
+            timeout_secs: match self.timeout {
+                Some(timeout) => Some(parse_timeout(&timeout)?),
+                None => defaults.timeout_secs,
+            },
+            max_memory_mb: match self.max_memory {
+                Some(memory) => Some(parse_max_memory(&memory)?),
+                None => defaults.max_memory_mb,
+            },
+            max_cpu_percent: self.max_cpu.or(defaults.max_cpu_percent),
+            max_processes: self.max_processes.or(defaults.max_processes),
+            max_open_files: self.max_open_files.or(defaults.max_open_files),
+            max_threads: self.max_threads.or(defaults.max_threads),
+            rlimits,
+            depends_on: if self.after.is_empty() {
+                defaults.depends_on.clone()
+            } else {
+                self.after
+            },
+            readiness: self
+                .ready
+                .as_deref()
+                .map(readiness::parse_probe)
+                .transpose()?,
+        };
+        let rules = if self.rules.is_empty() {
+            rules.to_vec()
+        } else {
+            self.rules
+        };
+        Ok(new_task(command, details, rules, reservation, origin))
+    }
+}
+
+/// Command that runs `task` from the directory it was registered in, with
+/// its ID in [`TASK_ID_ENV`]: in its own process group and under its
+/// kernel limits ([`kernel_limits`]) on Unix, and on Windows suspended
+/// until [`native_task`] has put it in a job object
+pub fn command_for(task_id: &str, task: &TaskInfo) -> tokio::process::Command {
+    let argv = task.command.exec_argv();
+    let mut command = tokio::process::Command::new(&argv[0]);
+    command.args(&argv[1..]).env(TASK_ID_ENV, task_id);
+    if let Some(cwd) = &task.cwd {
+        command.current_dir(cwd);
+    }
+    #[cfg(unix)]
+    {
+        command.process_group(0);
+        apply_kernel_limits(&mut command, task);
+    }
+    #[cfg(windows)]
+    command.creation_flags(windows_sys::Win32::System::Threading::CREATE_SUSPENDED);
+    command
+}
+
+/// Record of a task [`command_for`] started as `pid`. On Windows this is
+/// what lets it run, once it is in a job object that holds everything it
+/// starts.
+pub fn native_task(task_id: &str, pid: i32) -> NativeTask {
+    #[cfg(windows)]
+    let job = ProcessManager::start_in_job(task_id, pid);
+    #[cfg(not(windows))]
+    let job = None;
+    NativeTask {
+        pid,
+        pgid: pid,
+        // Empty if it already exited; the daemon then finds it dead
+        start_time: ProcessManager::get_start_time(pid).unwrap_or_default(),
+        env_tag: Some(task_id.to_string()),
+        job,
+    }
+}
+
+#[cfg(unix)]
+fn apply_kernel_limits(command: &mut tokio::process::Command, task: &TaskInfo) {
+    use nix::sys::resource::{getrlimit, setrlimit};
+
+    let limits: Vec<_> = kernel_limits(task)
+        .into_iter()
+        .map(|(rlimit, value)| {
+            let resource = resource_of(rlimit);
+            // Raising it past our own hard limit takes privileges
+            let value = match getrlimit(resource) {
+                Ok((_, hard)) => value.min(hard),
+                Err(_) => value,
+            };
+            (resource, value)
+        })
+        .collect();
+    if !limits.is_empty() {
+        // SAFETY: setrlimit is async-signal-safe and the closure allocates nothing
+        unsafe {
+            command.pre_exec(move || {
+                for (resource, value) in &limits {
+                    setrlimit(*resource, *value, *value).map_err(std::io::Error::from)?;
+                }
+                Ok(())
+            });
+        }
+    }
+}
+
+/// The kernel limits a native task is started under: its `--rlimit`s, and
+/// its `--max-open-files` as `nofile` unless it sets that itself
+pub fn kernel_limits(task: &TaskInfo) -> BTreeMap<Rlimit, u64> {
+    let mut limits = task.rlimits.clone();
+    if let Some(max) = task.max_open_files {
+        limits.entry(Rlimit::Nofile).or_insert(max);
+    }
+    limits
+}
+
+/// Refuse `--rlimit`s above this process's hard limits: only privileged
+/// processes may raise those, so the task couldn't run with them
+#[cfg(unix)]
+pub fn check_rlimits(rlimits: &BTreeMap<Rlimit, u64>) -> Result<()> {
+    for (rlimit, value) in rlimits {
+        if let Ok((_, hard)) = nix::sys::resource::getrlimit(resource_of(*rlimit)) {
+            if *value > hard {
+                bail!(
+                    "rlimit {}={} is above the hard limit of {}",
+                    rlimit,
+                    rlimit.format_value(*value),
+                    rlimit.format_value(hard)
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Refuse any `--rlimit`: there are none to set without a Unix kernel
+#[cfg(not(unix))]
+pub fn check_rlimits(rlimits: &BTreeMap<Rlimit, u64>) -> Result<()> {
+    if let Some(rlimit) = rlimits.keys().next() {
+        bail!("rlimit {} needs a Unix kernel", rlimit);
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn resource_of(rlimit: Rlimit) -> nix::sys::resource::Resource {
+    use nix::sys::resource::Resource;
+    match rlimit {
+        Rlimit::Nofile => Resource::RLIMIT_NOFILE,
+        Rlimit::Nproc => Resource::RLIMIT_NPROC,
+        Rlimit::Fsize => Resource::RLIMIT_FSIZE,
+        Rlimit::As => Resource::RLIMIT_AS,
+        Rlimit::Core => Resource::RLIMIT_CORE,
+        Rlimit::Cpu => Resource::RLIMIT_CPU,
+        Rlimit::Stack => Resource::RLIMIT_STACK,
+    }
+}
+
+/// Refuse to act on a task registered from another checkout of the repo
+/// than `here` (the caller's, if known)
+pub fn ensure_same_project(task_id: &str, task: &TaskInfo, here: Option<&Path>) -> Result<()> {
+    let Some(expected) = &task.repo_root else {
+        return Ok(());
+    };
+    if here != Some(expected.as_path()) {
+        bail!(
+            "Task {} belongs to {}, not {}",
+            task_id,
+            expected.display(),
+            here.map_or_else(|| "this directory".to_string(), |p| p.display().to_string())
+        );
+    }
+    Ok(())
+}
+
+/// The process on the other end of an API connection, from the socket's
+/// peer credentials
+#[derive(Debug, Clone)]
+pub struct Peer {
+    pub uid: u32,
+    pub pid: Option<i32>,
+}
+
+impl Peer {
+    /// Peer with `cred`. Credentials are always there for a connected Unix
+    /// socket; without them the peer gets a UID nobody has, which is
+    /// refused any task with an owner.
+    #[cfg(unix)]
+    pub fn from_cred(cred: Option<tokio::net::unix::UCred>) -> Self {
+        match cred {
+            Some(cred) => Self {
+                uid: cred.uid(),
+                pid: cred.pid(),
+            },
+            None => Self {
+                uid: u32::MAX,
+                pid: None,
+            },
+        }
+    }
+
+    /// Name of the peer's user (its UID when it has none), to compare with
+    /// task owners
+    pub fn user(&self) -> String {
+        #[cfg(unix)]
+        if let Ok(Some(user)) = nix::unistd::User::from_uid(self.uid.into()) {
+            return user.name;
+        }
+        self.uid.to_string()
+    }
+
+    /// Working directory of the peer, if it can be read
+    pub fn cwd(&self) -> Option<PathBuf> {
+        ProcessManager::command_argv(self.pid?)?.1
+    }
+
+    /// Checkout the peer runs in, if its working directory can be read
+    pub fn repo_root(&self) -> Option<PathBuf> {
+        ProcessManager::repo_root(&self.cwd()?)
+    }
+
+    /// Requests the peer makes through `tool`, as recorded in task origins
+    /// and kill reasons
+    pub fn origin(&self, tool: &str) -> TaskOrigin {
+        TaskOrigin {
+            tool: tool.to_string(),
+            user: Some(self.user()),
+            host: ProcessManager::hostname(),
+            tmux: None,
+        }
+    }
+}
+
+/// Register `task` for `peer`, whose task it is rather than the server's:
+/// theirs to kill, from their checkout. With `queue`, a task that doesn't
+/// fit next to the started ones is queued, and so is any task while the
+/// machine is throttling. Returns the task as registered.
+pub fn register_for(
+    registry: &mut RegistryManager,
+    peer: &Peer,
+    task_id: &str,
+    mut task: TaskInfo,
+    queue: bool,
+) -> Result<TaskInfo> {
+    task.owner = Some(peer.user());
+    task.cwd = peer.cwd();
+    task.repo_root = task.cwd.as_deref().and_then(ProcessManager::repo_root);
+    if queue && ThermalStatus::sample().is_throttling() {
+        task.status = TaskStatus::Queued;
+    }
+    let reservable = Resources::machine().minus_headroom(capacity::DEFAULT_HEADROOM_PERCENT);
+    registry.admit_task(task_id.to_string(), task, reservable, queue)?;
+    registry
+        .get_task(task_id)
+        .cloned()
+        .context("Registered task vanished")
+}
+
+/// Refuse `peer` a kill of another user's task, or of one registered from
+/// another checkout than the peer's, as the CLI refuses one without `--force`
+pub fn ensure_peer_may_kill(task_id: &str, task: &TaskInfo, peer: &Peer) -> Result<()> {
+    if !task.is_owned_by(Some(&peer.user())) {
+        bail!(
+            "Task {} belongs to user {}",
+            task_id,
+            task.owner.as_deref().unwrap_or_default()
+        );
+    }
+    ensure_same_project(task_id, task, peer.repo_root().as_deref())
+}
+
+/// Start a task `swarm` queued once it has been given a slot (it is
+/// already running in the registry), in the background with its output in
+/// its log. One that can't be started is marked failed.
+///
+/// Nobody waits for it, so on Unix a shell around it writes its exit code
+/// to its done file for the daemon to read.
+pub fn start_promoted(registry: &mut RegistryManager, task_id: &str) -> Result<NativeTask> {
+    let Some(task) = registry.get_task(task_id).cloned() else {
+        bail!("Task {} not found", task_id);
+    };
+    let log_path = std::path::absolute(logs::log_path(registry.path(), task_id))?;
+    let done_file = std::path::absolute(done_files::default_path(registry.path(), task_id))?;
+    let mut wrapped = task.clone();
+    if cfg!(unix) {
+        let mut argv = vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            r#""$@"; echo $? > "$0""#.to_string(),
+            done_file.to_string_lossy().into_owned(),
+        ];
+        argv.extend(task.command.exec_argv());
+        wrapped.command = TaskCommand { argv, shell: false };
+    }
+    let mode = registry.file_mode();
+    let started = done_file
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .map_err(anyhow::Error::from)
+        .and_then(|()| {
+            // Left by an earlier task of the same name
+            let _ = std::fs::remove_file(&done_file);
+            start_detached(task_id, &wrapped, &log_path, mode)
+        });
+    let native = match started {
+        Ok(native) => native,
+        Err(e) => {
+            registry.mark_failed(task_id)?;
+            return Err(e);
+        }
+    };
+    registry
+        .mark_started(task_id, native.clone(), log_path, done_file)
+        .inspect_err(|_| {
+            let _ = ProcessManager::kill_task(&native);
+        })?;
+    Ok(native)
+}
+
+/// Start `task` in the background with its output appended to `log_path`
+/// (created with `mode`). Returns its process group, for the task to record.
+pub fn start_detached(
+    task_id: &str,
+    task: &TaskInfo,
+    log_path: &Path,
+    mode: u32,
+) -> Result<NativeTask> {
+    let log = logs::open(log_path, mode)?;
+    let child = command_for(task_id, task)
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log)
+        .spawn()
+        .with_context(|| format!("Failed to start `{}`", task.command))?;
+    let pid = child.id().context("Spawned process exited immediately")? as i32;
+    Ok(native_task(task_id, pid))
+}
+
+/// Create and start a container running `task` in its directory, limited
+/// to its reservation (Docker's defaults otherwise). The container is kept
+/// after it exits so it can be restarted in place; with `auto_remove` the
+/// daemon removes it once it has saved its logs and exit code.
+pub async fn start_container(
+    docker: &DockerManager,
+    task_id: &str,
+    task: &TaskInfo,
+    image: Option<&str>,
+    stop_timeout_secs: Option<u64>,
+) -> Result<DockerTask> {
+    let limits = task.reservation.clone().unwrap_or_default();
+    let work_dir = match &task.cwd {
+        Some(cwd) => cwd.clone(),
+        None => std::env::current_dir()?,
+    };
+    let container_id = docker
+        .run_container(
+            task_id,
+            &task.command,
+            &work_dir.to_string_lossy(),
+            &limits,
+            image,
+            false,
+        )
+        .await?;
+    Ok(DockerTask {
+        container_id,
+        container_name: DockerManager::container_name(
+            task_id,
+            ProcessManager::current_user().as_deref(),
+        ),
+        resource_limits: limits,
+        stop_timeout_secs,
+        image: image.map(str::to_string),
+        auto_remove: false,
+        keep_on_failure: false,
+        exit_code: None,
+    })
+}
+
+/// Start a finished task again under its restart policy: a native task's
+/// command afresh, a container in place, keeping what it left in its
+/// filesystem, or recreated from its image once it has been removed.
+/// A native task without a log file writes to the default log, and
+/// `output`, if given, follows it from there. Returns how many times the
+/// task has restarted.
+pub async fn restart(
+    registry: &mut RegistryManager,
+    task_id: &str,
+    docker: Option<&DockerManager>,
+    output: Option<&OutputBuffers>,
+) -> Result<u32> {
+    let Some(task) = registry.get_task(task_id).cloned() else {
+        bail!("Task {} not found", task_id);
+    };
+    match task.mode {
+        ExecutionMode::Native => {
+            let mode = registry.file_mode();
+            let native = match &task.log_file {
+                Some(path) => start_detached(task_id, &task, path, mode)?,
+                None => {
+                    let default_log =
+                        std::path::absolute(logs::log_path(registry.path(), task_id))?;
+                    // What earlier runs wrote isn't this run's output
+                    let from = std::fs::metadata(&default_log).map_or(0, |meta| meta.len());
+                    let native = start_detached(task_id, &task, &default_log, mode)?;
+                    if let Some(output) = output {
+                        output.follow(task_id, default_log, from);
+                    }
+                    native
+                }
+            };
+            registry
+                .mark_restarted(task_id, Some(native.clone()), None)
+                .inspect_err(|_| {
+                    let _ = ProcessManager::kill_task(&native);
+                })
+        }
+        ExecutionMode::Docker => {
+            let (Some(docker), Some(container)) = (docker, &task.docker) else {
+                bail!("Docker is not available to restart {}", task_id);
+            };
+            let container = if docker
+                .restart_container(&container.container_id, container.stop_timeout())
+                .await?
+            {
+                DockerTask {
+                    exit_code: None,
+                    ..container.clone()
+                }
+            } else {
+                // Removed since (auto-remove, prune-containers)
+                info!(task: task_id; "🐳 Container of {} is gone; recreating it", task_id);
+                DockerTask {
+                    auto_remove: container.auto_remove,
+                    keep_on_failure: container.keep_on_failure,
+                    ..start_container(
+                        docker,
+                        task_id,
+                        &task,
+                        container.image.as_deref(),
+                        container.stop_timeout_secs,
+                    )
+                    .await?
+                }
+            };
+            registry.mark_restarted(task_id, None, Some(container))
+        }
+    }
+}
+
+/// Whether a task's process or container is running (`None` when it has
+/// neither, or Docker isn't available)
+pub async fn alive(task: &TaskInfo) -> Option<bool> {
+    match &task.mode {
+        ExecutionMode::Native => task
+            .native
+            .as_ref()
+            .map(|native| ProcessManager::is_alive(native.pid)),
+        ExecutionMode::Docker => {
+            let docker_info = task.docker.as_ref()?;
+            let docker = DockerManager::new()?;
+            Some(docker.is_running(&docker_info.container_id).await)
+        }
+    }
+}
+
+/// Stop a task's process group or container and mark it killed
+pub async fn kill(
+    registry: &mut RegistryManager,
+    task_id: &str,
+    origin: &TaskOrigin,
+) -> Result<()> {
+    let Some(task) = registry.get_task(task_id).cloned() else {
+        return Ok(());
+    };
+    info!(task: task_id; "🔪 Killing task: {}", task_id);
+
+    let mut reason = format!("killed by user via {origin}");
+    if let Some(outcome) = terminate(registry, task_id, &task).await? {
+        reason = format!("{reason}; container {outcome}");
+    }
+    registry.mark_killed(task_id, &reason)
+}
+
+/// Stop a task that has run longer than its `--timeout` and mark it timed out
+pub async fn time_out(registry: &mut RegistryManager, task_id: &str) -> Result<()> {
+    let Some(task) = registry.get_task(task_id).cloned() else {
+        return Ok(());
+    };
+    let limit = units::format_duration(task.timeout_secs.unwrap_or_default() as f64);
+    warn!(task: task_id; "⏰ Task {} exceeded its {} timeout", task_id, limit);
+
+    let mut reason = format!("exceeded its {limit} timeout");
+    if let Some(outcome) = terminate(registry, task_id, &task).await? {
+        reason = format!("{reason}; container {outcome}");
+    }
+    registry.mark_timed_out(task_id, &reason)
+}
+
+/// Stop a task that went over its `--max-memory` or `--max-cpu` and mark it
+/// killed, with `reason` saying which
+pub async fn kill_over_limit(
+    registry: &mut RegistryManager,
+    task_id: &str,
+    reason: &str,
+) -> Result<()> {
+    let Some(task) = registry.get_task(task_id).cloned() else {
+        return Ok(());
+    };
+    warn!(task: task_id; "🛑 Killing task {}: {}", task_id, reason);
+
+    let mut reason = reason.to_string();
+    if let Some(outcome) = terminate(registry, task_id, &task).await? {
+        reason = format!("{reason}; container {outcome}");
+    }
+    registry.mark_killed(task_id, &reason)
+}
+
+/// Stop a task's process group or container, recording its last CPU
+/// sample first. Returns how a container was stopped.
+async fn terminate(
+    registry: &mut RegistryManager,
+    task_id: &str,
+    task: &TaskInfo,
+) -> Result<Option<StopOutcome>> {
+    // Last CPU sample before the processes are gone
+    let cpu_seconds = match (&task.native, &task.docker) {
+        (Some(native), _) => ProcessManager::group_cpu_seconds(native.pgid),
+        (None, Some(docker_info)) => match DockerManager::new() {
+            Some(docker) => docker
+                .get_stats(&docker_info.container_id)
+                .await
+                .ok()
+                .map(|s| s.cpu_seconds),
+            None => None,
+        },
+        _ => None,
+    };
+
+    let mut stopped = None;
+    match &task.mode {
+        ExecutionMode::Native => {
+            if let Some(native) = &task.native {
+                ProcessManager::kill_task(native)?;
+                info!("✅ Killed process group {}", native.pgid);
+            }
+        }
+        ExecutionMode::Docker => {
+            if let Some(docker_info) = &task.docker {
+                if let Some(docker) = DockerManager::new() {
+                    let outcome = docker
+                        .stop_container(&docker_info.container_id, docker_info.stop_timeout())
+                        .await?;
+                    info!(
+                        "✅ Stopped container {} ({})",
+                        &docker_info.container_id[..12],
+                        outcome
+                    );
+                    stopped = Some(outcome);
+                }
+            }
+        }
+    }
+
+    if let Some(seconds) = cpu_seconds {
+        registry.record_cpu_times(&[(task_id.to_string(), seconds)])?;
+    }
+    Ok(stopped)
+}
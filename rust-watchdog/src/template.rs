@@ -0,0 +1,200 @@
+//! Template variables in commands and launch files.
+//!
+//! `{{name}}` is replaced by a variable: `branch` (the current git branch),
+//! `task_id`, `repo_root`, `today` (`YYYY-MM-DD`, local time),
+//! `env.NAME` (an environment variable) or one given with `--var
+//! name=value`, which also overrides the built-in ones. An unknown name is
+//! an error rather than left in place, so a typo can't reach a command.
+//! Braces around anything not starting with a letter, such as Go templates
+//! (`docker ps --format '{{.Names}}'`), are kept as they are.
+
+use crate::process::ProcessManager;
+use anyhow::{bail, Result};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Variables to render templates with
+#[derive(Debug, Clone, Default)]
+pub struct Vars {
+    /// Built-in variables
+    builtin: BTreeMap<String, String>,
+    /// `--var` values, which take precedence
+    overrides: BTreeMap<String, String>,
+    /// Leave unknown variables in place instead of failing
+    lenient: bool,
+}
+
+impl Vars {
+    /// Built-in variables for a command run in `dir`, plus `overrides`
+    pub fn here(dir: &Path, overrides: impl IntoIterator<Item = (String, String)>) -> Self {
+        let mut builtin = BTreeMap::new();
+        builtin.insert(
+            "today".to_string(),
+            chrono::Local::now().format("%Y-%m-%d").to_string(),
+        );
+        if let Some(root) = ProcessManager::repo_root(dir) {
+            builtin.insert("repo_root".to_string(), root.to_string_lossy().into_owned());
+        }
+        if let Some(branch) = git_branch(dir) {
+            builtin.insert("branch".to_string(), branch);
+        }
+        Self {
+            builtin,
+            overrides: overrides.into_iter().collect(),
+            lenient: false,
+        }
+    }
+
+    /// These variables, leaving any that can't be resolved as they are:
+    /// for reading a definition only to find what it named
+    pub fn lenient(&self) -> Self {
+        Self {
+            lenient: true,
+            ..self.clone()
+        }
+    }
+
+    /// These variables with `task_id` set
+    pub fn for_task(&self, task_id: &str) -> Self {
+        let mut vars = self.clone();
+        vars.builtin
+            .insert("task_id".to_string(), task_id.to_string());
+        vars
+    }
+
+    fn get(&self, name: &str) -> Result<String> {
+        if let Some(value) = self.overrides.get(name).or_else(|| self.builtin.get(name)) {
+            return Ok(value.clone());
+        }
+        if let Some(env) = name.strip_prefix("env.") {
+            return match std::env::var(env) {
+                Ok(value) => Ok(value),
+                Err(_) => bail!(
+                    "Environment variable {} is not set (in `{{{{{}}}}}`)",
+                    env,
+                    name
+                ),
+            };
+        }
+        match name {
+            "branch" | "repo_root" => bail!("`{{{{{}}}}}` needs a git repository", name),
+            "task_id" => bail!("`{{{{task_id}}}}` is only available for a task"),
+            _ => bail!(
+                "Unknown template variable `{{{{{}}}}}` (set it with --var {}=...)",
+                name,
+                name
+            ),
+        }
+    }
+
+    /// `text` with its variables replaced
+    pub fn render(&self, text: &str) -> Result<String> {
+        let mut out = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find("{{") {
+            let Some(len) = rest[start + 2..].find("}}") else {
+                break;
+            };
+            let name = rest[start + 2..start + 2 + len].trim();
+            out.push_str(&rest[..start]);
+            let whole = &rest[start..start + len + 4];
+            match is_variable(name).then(|| self.get(name)) {
+                Some(Ok(value)) => out.push_str(&value),
+                Some(Err(e)) if !self.lenient => return Err(e),
+                _ => out.push_str(whole),
+            }
+            rest = &rest[start + len + 4..];
+        }
+        out.push_str(rest);
+        Ok(out)
+    }
+
+    /// Render an optional field in place
+    pub fn render_opt(&self, text: &mut Option<String>) -> Result<()> {
+        if let Some(text) = text {
+            *text = self.render(text)?;
+        }
+        Ok(())
+    }
+
+    /// Render the values of a label map in place
+    pub fn render_values(&self, labels: &mut BTreeMap<String, String>) -> Result<()> {
+        for value in labels.values_mut() {
+            *value = self.render(value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether `{{name}}` refers to a variable: letters, digits, `_` and `.`,
+/// starting with a letter
+fn is_variable(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+/// Branch checked out in the repository containing `dir` (`HEAD` when
+/// detached)
+fn git_branch(dir: &Path) -> Option<String> {
+    let output = std::process::Command::new("git")
+        .args(["rev-parse", "--abbrev-ref", "HEAD"])
+        .current_dir(dir)
+        .stderr(std::process::Stdio::null())
+        .output()
+        .ok()?;
+    let branch = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    (output.status.success() && !branch.is_empty()).then_some(branch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars() -> Vars {
+        Vars {
+            builtin: [("branch".to_string(), "main".to_string())].into(),
+            overrides: [("port".to_string(), "8080".to_string())].into(),
+            lenient: false,
+        }
+    }
+
+    #[test]
+    fn test_variables_are_replaced() {
+        let vars = vars().for_task("T001");
+        assert_eq!(
+            vars.render("serve --port {{port}} --tag {{ branch }}-{{task_id}}")
+                .unwrap(),
+            "serve --port 8080 --tag main-T001"
+        );
+        assert_eq!(
+            vars.render("echo {{env.CARGO_PKG_NAME}}").unwrap(),
+            "echo task-watchdog"
+        );
+    }
+
+    #[test]
+    fn test_overrides_win_and_unknown_names_fail() {
+        let mut vars = vars();
+        vars.overrides.insert("branch".into(), "release".into());
+        assert_eq!(vars.render("{{branch}}").unwrap(), "release");
+
+        let err = vars.render("{{prot}}").unwrap_err().to_string();
+        assert!(err.contains("--var prot="), "{err}");
+        assert!(vars.render("{{task_id}}").is_err());
+        assert_eq!(vars.lenient().render("{{prot}}").unwrap(), "{{prot}}");
+    }
+
+    #[test]
+    fn test_other_braces_are_kept() {
+        let vars = vars();
+        for text in [
+            "docker ps --format '{{.Names}}'",
+            "echo {{ }}",
+            "awk '{print}' {{",
+        ] {
+            assert_eq!(vars.render(text).unwrap(), text);
+        }
+    }
+}
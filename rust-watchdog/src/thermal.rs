@@ -0,0 +1,129 @@
+//! CPU temperature and frequency, for spotting thermal throttling.
+//!
+//! Everything here is best effort: virtual machines and containers often
+//! expose no sensors, in which case the fields are `None` and nothing is
+//! reported as throttling.
+
+use sysinfo::{Components, System};
+
+/// Assumed critical temperature when the sensor doesn't report one (°C)
+const FALLBACK_CRITICAL_C: f32 = 95.0;
+
+/// How close to critical counts as throttling (°C)
+const THROTTLE_MARGIN_C: f32 = 5.0;
+
+/// Snapshot of the CPU's thermal state
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ThermalStatus {
+    /// Hottest CPU sensor (°C)
+    pub cpu_temp_c: Option<f32>,
+    /// Critical temperature reported by that sensor (°C)
+    pub critical_c: Option<f32>,
+    /// Average current CPU frequency (MHz)
+    pub freq_mhz: Option<u64>,
+    /// Maximum CPU frequency (MHz, Linux only)
+    pub max_freq_mhz: Option<u64>,
+}
+
+impl ThermalStatus {
+    /// Read sensors and CPU frequencies
+    pub fn sample() -> Self {
+        let components = Components::new_with_refreshed_list();
+        let hottest = components
+            .list()
+            .iter()
+            .filter(|c| is_cpu_sensor(c.label()))
+            .filter(|c| c.temperature().is_finite() && c.temperature() > 0.0)
+            .max_by(|a, b| a.temperature().total_cmp(&b.temperature()));
+
+        let mut sys = System::new();
+        sys.refresh_cpu();
+        let freqs: Vec<u64> = sys
+            .cpus()
+            .iter()
+            .map(|c| c.frequency())
+            .filter(|f| *f > 0)
+            .collect();
+
+        Self {
+            cpu_temp_c: hottest.map(|c| c.temperature()),
+            critical_c: hottest.and_then(|c| c.critical()),
+            freq_mhz: (!freqs.is_empty()).then(|| freqs.iter().sum::<u64>() / freqs.len() as u64),
+            max_freq_mhz: max_freq_mhz(),
+        }
+    }
+
+    /// Whether the CPU is at (or within a few degrees of) its critical temperature
+    pub fn is_throttling(&self) -> bool {
+        match self.cpu_temp_c {
+            Some(temp) => {
+                temp >= self.critical_c.unwrap_or(FALLBACK_CRITICAL_C) - THROTTLE_MARGIN_C
+            }
+            None => false,
+        }
+    }
+
+    /// One-line summary (`CPU 78°C (critical 100°C), 2400/4800 MHz`),
+    /// or `None` when nothing could be read
+    pub fn summary(&self) -> Option<String> {
+        let mut parts = Vec::new();
+        if let Some(temp) = self.cpu_temp_c {
+            match self.critical_c {
+                Some(critical) => parts.push(format!("CPU {temp:.0}°C (critical {critical:.0}°C)")),
+                None => parts.push(format!("CPU {temp:.0}°C")),
+            }
+        }
+        let prefix = if parts.is_empty() { "CPU " } else { "" };
+        match (self.freq_mhz, self.max_freq_mhz) {
+            (Some(freq), Some(max)) => parts.push(format!("{prefix}{freq}/{max} MHz")),
+            (Some(freq), None) => parts.push(format!("{prefix}{freq} MHz")),
+            _ => {}
+        }
+        (!parts.is_empty()).then(|| parts.join(", "))
+    }
+}
+
+/// Sensor labels that belong to the CPU package or cores
+fn is_cpu_sensor(label: &str) -> bool {
+    let label = label.to_lowercase();
+    [
+        "cpu", "core", "package", "tctl", "tdie", "k10temp", "coretemp",
+    ]
+    .iter()
+    .any(|key| label.contains(key))
+}
+
+#[cfg(target_os = "linux")]
+fn max_freq_mhz() -> Option<u64> {
+    let khz =
+        std::fs::read_to_string("/sys/devices/system/cpu/cpu0/cpufreq/cpuinfo_max_freq").ok()?;
+    khz.trim().parse::<u64>().ok().map(|khz| khz / 1000)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn max_freq_mhz() -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_throttling() {
+        let at = |temp: f32, critical: Option<f32>| ThermalStatus {
+            cpu_temp_c: Some(temp),
+            critical_c: critical,
+            ..Default::default()
+        };
+        assert!(!at(70.0, Some(100.0)).is_throttling());
+        assert!(at(96.0, Some(100.0)).is_throttling());
+        assert!(at(91.0, None).is_throttling());
+        assert!(!ThermalStatus::default().is_throttling());
+        assert_eq!(ThermalStatus::default().summary(), None);
+        assert_eq!(
+            at(78.4, Some(100.0)).summary().as_deref(),
+            Some("CPU 78°C (critical 100°C)")
+        );
+    }
+}
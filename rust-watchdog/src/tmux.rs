@@ -0,0 +1,109 @@
+//! tmux panes: the pane a task is started from is recorded with its origin,
+//! and `goto` brings that pane back into view.
+//!
+//! Panes are addressed by their ID (`%12`), which stays the same while the
+//! pane lives even when windows are moved or renumbered.
+
+use crate::process::ProcessManager;
+use crate::types::TmuxPane;
+use anyhow::{bail, Context, Result};
+use std::process::Command;
+
+/// Fields asked of `tmux display-message`, tab-separated
+const FORMAT: &str = "#{pane_id}\t#{session_name}\t#{window_index}\t#{pane_index}";
+
+fn tmux(socket: Option<&str>) -> Command {
+    let mut command = Command::new("tmux");
+    if let Some(socket) = socket {
+        command.args(["-S", socket]);
+    }
+    command
+}
+
+/// Socket path from `$TMUX` (`<socket>,<server pid>,<session>`)
+fn socket_from(tmux_env: &str) -> Option<String> {
+    tmux_env
+        .split(',')
+        .next()
+        .filter(|socket| !socket.is_empty())
+        .map(str::to_string)
+}
+
+fn parse_pane(line: &str, socket: Option<String>) -> Option<TmuxPane> {
+    let mut fields = line.trim_end().split('\t');
+    Some(TmuxPane {
+        pane_id: fields.next().filter(|id| id.starts_with('%'))?.to_string(),
+        session: fields.next()?.to_string(),
+        window: fields.next()?.parse().ok()?,
+        pane: fields.next()?.parse().ok()?,
+        socket,
+    })
+}
+
+/// Where pane `pane_id` is now, or `None` if it (or tmux) is gone
+fn describe(pane_id: &str, socket: Option<String>) -> Option<TmuxPane> {
+    let output = tmux(socket.as_deref())
+        .args(["display-message", "-p", "-t", pane_id, FORMAT])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_pane(&String::from_utf8_lossy(&output.stdout), socket)
+}
+
+/// The pane this process runs in, when inside tmux
+pub fn current_pane() -> Option<TmuxPane> {
+    let pane_id = std::env::var("TMUX_PANE").ok()?;
+    let socket = std::env::var("TMUX").ok();
+    describe(&pane_id, socket.as_deref().and_then(socket_from))
+}
+
+/// The pane another process was started in, from its environment
+pub fn pane_of(pid: i32) -> Option<TmuxPane> {
+    let pane_id = ProcessManager::env_var(pid, "TMUX_PANE")?;
+    let socket = ProcessManager::env_var(pid, "TMUX");
+    describe(&pane_id, socket.as_deref().and_then(socket_from))
+}
+
+fn run(command: &mut Command) -> Result<()> {
+    let status = command.status().context("Failed to run tmux")?;
+    if !status.success() {
+        bail!("tmux exited with {}", status);
+    }
+    Ok(())
+}
+
+/// Select `pane` and show it: switch this client to it inside tmux, attach
+/// to its session otherwise. Returns where the pane is now.
+pub fn focus(pane: &TmuxPane) -> Result<TmuxPane> {
+    let Some(now) = describe(&pane.pane_id, pane.socket.clone()) else {
+        bail!("tmux pane {} ({}) no longer exists", pane.pane_id, pane);
+    };
+    let socket = pane.socket.as_deref();
+    run(tmux(socket).args(["select-window", "-t", &pane.pane_id]))?;
+    run(tmux(socket).args(["select-pane", "-t", &pane.pane_id]))?;
+    let show = if std::env::var_os("TMUX").is_some() {
+        "switch-client"
+    } else {
+        "attach-session"
+    };
+    run(tmux(socket).args([show, "-t", &pane.pane_id]))?;
+    Ok(now)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pane() {
+        let socket = socket_from("/tmp/tmux-1000/default,4242,0");
+        assert_eq!(socket.as_deref(), Some("/tmp/tmux-1000/default"));
+
+        let pane = parse_pane("%12\twork\t3\t1\n", socket).unwrap();
+        assert_eq!(pane.pane_id, "%12");
+        assert_eq!(pane.to_string(), "work:3.1");
+        assert!(parse_pane("no server running", None).is_none());
+    }
+}
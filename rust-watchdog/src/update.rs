@@ -0,0 +1,287 @@
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+use crate::{debug, info, verbose, warn};
+
+/// GitHub repository publishing task-watchdog release binaries
+const RELEASES_URL: &str = "https://api.github.com/repos/gyasis/dev-kid/releases";
+
+/// Checksum manifest attached to every release (`<sha256>  <asset name>` lines)
+const CHECKSUMS_ASSET: &str = "SHA256SUMS";
+
+/// Minisign public key baked in at build time. When set, the `.minisig`
+/// signature of the downloaded binary is mandatory; without it a binary is
+/// only installed when the user accepts one verified by checksum alone.
+const UPDATE_PUBLIC_KEY: Option<&str> = option_env!("TASK_WATCHDOG_UPDATE_PUBKEY");
+
+/// Release metadata (subset of the GitHub releases API)
+#[derive(Debug, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    #[serde(default)]
+    pub draft: bool,
+    #[serde(default)]
+    pub prerelease: bool,
+    #[serde(default)]
+    pub assets: Vec<ReleaseAsset>,
+}
+
+/// Downloadable file attached to a release
+#[derive(Debug, Deserialize)]
+pub struct ReleaseAsset {
+    pub name: String,
+    pub browser_download_url: String,
+}
+
+impl Release {
+    fn asset(&self, name: &str) -> Option<&ReleaseAsset> {
+        self.assets.iter().find(|a| a.name == name)
+    }
+
+    /// Version parsed from the tag (`v2.1.0`, `task-watchdog-v2.1.0`, …)
+    pub fn version(&self) -> Option<(u64, u64, u64)> {
+        parse_version(&self.tag_name)
+    }
+}
+
+/// Outcome of a self-update run
+#[derive(Debug, PartialEq)]
+pub enum UpdateOutcome {
+    UpToDate,
+    Available(String),
+    Installed(String),
+}
+
+/// Release asset name for the running binary's target triple
+pub fn asset_name() -> String {
+    let target = env!("TASK_WATCHDOG_TARGET");
+    if cfg!(windows) {
+        format!("task-watchdog-{target}.exe")
+    } else {
+        format!("task-watchdog-{target}")
+    }
+}
+
+/// Parse `major.minor.patch` out of a tag, ignoring any prefix up to the last `v`
+pub fn parse_version(tag: &str) -> Option<(u64, u64, u64)> {
+    let version = tag.rsplit_once('v').map_or(tag, |(_, v)| v);
+    let core = version.split(['-', '+']).next()?;
+    let mut parts = core.split('.').map(|p| p.parse::<u64>().ok());
+    Some((
+        parts.next()??,
+        parts.next()??,
+        parts.next().unwrap_or(Some(0))?,
+    ))
+}
+
+/// Find the expected SHA-256 for `asset` in a `SHA256SUMS` manifest
+pub fn expected_checksum(manifest: &str, asset: &str) -> Option<String> {
+    manifest.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        let hash = fields.next()?;
+        let name = fields.next()?.trim_start_matches('*');
+        (name == asset).then(|| hash.to_lowercase())
+    })
+}
+
+/// Lowercase hex SHA-256 of `bytes`
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Check GitHub for a newer release and, unless `check_only`, install it
+/// over the running executable. A build without a signing key refuses to
+/// install one unless `insecure` is set.
+pub fn self_update(check_only: bool, insecure: bool) -> Result<UpdateOutcome> {
+    let current = parse_version(env!("CARGO_PKG_VERSION")).context("Invalid CARGO_PKG_VERSION")?;
+    let asset = asset_name();
+    verbose!(
+        "   Current version: {}.{}.{}",
+        current.0,
+        current.1,
+        current.2
+    );
+    verbose!("   Looking for asset: {}", asset);
+
+    let release = latest_release_with(&asset)?;
+    let latest = release
+        .version()
+        .with_context(|| format!("Cannot parse release tag '{}'", release.tag_name))?;
+
+    if latest <= current {
+        return Ok(UpdateOutcome::UpToDate);
+    }
+    if check_only {
+        return Ok(UpdateOutcome::Available(release.tag_name));
+    }
+    if UPDATE_PUBLIC_KEY.is_none() {
+        if !insecure {
+            bail!(
+                "This build has no update signing key to verify {} with; \
+                 pass --insecure to install it with only its checksum verified",
+                release.tag_name
+            );
+        }
+        warn!("⚠️  No update signing key compiled in; the download's signature is not checked");
+    }
+
+    let url = release
+        .asset(&asset)
+        .map(|a| a.browser_download_url.clone())
+        .with_context(|| format!("Release {} has no {asset}", release.tag_name))?;
+    info!("⬇️  Downloading {} ({})", asset, release.tag_name);
+    let binary = download(&url)?;
+    verify_checksum(&release, &asset, &binary)?;
+    if let Some(public_key) = UPDATE_PUBLIC_KEY {
+        verify_signature(&release, &asset, &binary, public_key)?;
+    }
+
+    let exe = std::env::current_exe().context("Cannot locate running executable")?;
+    replace_executable(&exe, &binary)?;
+
+    Ok(UpdateOutcome::Installed(release.tag_name))
+}
+
+/// Newest published (non-draft, non-prerelease) release carrying `asset`
+fn latest_release_with(asset: &str) -> Result<Release> {
+    debug!("   GET {}", RELEASES_URL);
+    let releases: Vec<Release> = ureq::get(RELEASES_URL)
+        .set(
+            "User-Agent",
+            concat!("task-watchdog/", env!("CARGO_PKG_VERSION")),
+        )
+        .set("Accept", "application/vnd.github+json")
+        .call()
+        .context("Failed to query GitHub releases")?
+        .into_json()
+        .context("Failed to parse GitHub releases response")?;
+
+    releases
+        .into_iter()
+        .filter(|r| !r.draft && !r.prerelease && r.asset(asset).is_some())
+        .max_by_key(|r| r.version())
+        .with_context(|| format!("No release provides {asset}"))
+}
+
+fn download(url: &str) -> Result<Vec<u8>> {
+    debug!("   GET {}", url);
+    let mut bytes = Vec::new();
+    ureq::get(url)
+        .set(
+            "User-Agent",
+            concat!("task-watchdog/", env!("CARGO_PKG_VERSION")),
+        )
+        .call()
+        .with_context(|| format!("Failed to download {url}"))?
+        .into_reader()
+        .read_to_end(&mut bytes)
+        .context("Failed to read download")?;
+    Ok(bytes)
+}
+
+fn verify_checksum(release: &Release, asset: &str, binary: &[u8]) -> Result<()> {
+    let manifest = release
+        .asset(CHECKSUMS_ASSET)
+        .with_context(|| format!("Release {} has no {CHECKSUMS_ASSET}", release.tag_name))?;
+    let manifest = String::from_utf8(download(&manifest.browser_download_url)?)
+        .context("Checksum manifest is not UTF-8")?;
+    let expected = expected_checksum(&manifest, asset)
+        .with_context(|| format!("{CHECKSUMS_ASSET} has no entry for {asset}"))?;
+
+    let actual = sha256_hex(binary);
+    if actual != expected {
+        bail!("Checksum mismatch for {asset}: expected {expected}, got {actual}");
+    }
+    info!("   ✅ SHA-256 verified");
+    Ok(())
+}
+
+fn verify_signature(release: &Release, asset: &str, binary: &[u8], public_key: &str) -> Result<()> {
+    let sig_name = format!("{asset}.minisig");
+    let sig_asset = release
+        .asset(&sig_name)
+        .with_context(|| format!("Release {} is missing {sig_name}", release.tag_name))?;
+    let signature = String::from_utf8(download(&sig_asset.browser_download_url)?)
+        .context("Signature is not UTF-8")?;
+
+    let key = minisign_verify::PublicKey::from_base64(public_key)
+        .map_err(|e| anyhow::anyhow!("Invalid compiled-in public key: {e}"))?;
+    let signature = minisign_verify::Signature::decode(&signature)
+        .map_err(|e| anyhow::anyhow!("Invalid signature file: {e}"))?;
+    key.verify(binary, &signature, false)
+        .map_err(|e| anyhow::anyhow!("Signature verification failed: {e}"))?;
+
+    info!("   ✅ Signature verified");
+    Ok(())
+}
+
+/// Swap the new binary in next to the old one and rename over it
+fn replace_executable(exe: &Path, binary: &[u8]) -> Result<()> {
+    let staged = exe.with_extension("new");
+    fs::write(&staged, binary).context("Failed to write downloaded binary")?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&staged, fs::Permissions::from_mode(0o755))
+            .context("Failed to make new binary executable")?;
+    }
+
+    // Windows cannot overwrite a running executable, but it can rename it
+    #[cfg(windows)]
+    {
+        let old = exe.with_extension("old");
+        let _ = fs::remove_file(&old);
+        fs::rename(exe, &old).context("Failed to move running executable aside")?;
+    }
+
+    fs::rename(&staged, exe).context("Failed to replace executable")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(parse_version("v2.1.0"), Some((2, 1, 0)));
+        assert_eq!(parse_version("task-watchdog-v2.10.3"), Some((2, 10, 3)));
+        assert_eq!(parse_version("2.0.0-rc.1"), Some((2, 0, 0)));
+        assert_eq!(parse_version("v3.1"), Some((3, 1, 0)));
+        assert_eq!(parse_version("nightly"), None);
+        assert!(parse_version("v2.10.0") > parse_version("v2.9.9"));
+    }
+
+    #[test]
+    fn test_expected_checksum() {
+        let manifest = "\
+abc123  task-watchdog-x86_64-unknown-linux-gnu
+DEF456 *task-watchdog-aarch64-apple-darwin
+";
+        assert_eq!(
+            expected_checksum(manifest, "task-watchdog-x86_64-unknown-linux-gnu"),
+            Some("abc123".to_string())
+        );
+        assert_eq!(
+            expected_checksum(manifest, "task-watchdog-aarch64-apple-darwin"),
+            Some("def456".to_string())
+        );
+        assert_eq!(expected_checksum(manifest, "missing"), None);
+    }
+
+    #[test]
+    fn test_sha256_hex() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
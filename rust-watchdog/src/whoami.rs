@@ -0,0 +1,240 @@
+//! Reverse lookup for `whoami`: the task a process or container belongs to.
+//!
+//! A process is matched, most certain first, by being a task's process or
+//! descending from one, by the [`TASK_ID_ENV`] tag spawned tasks pass on to
+//! everything they start (which survives reparenting to init), by sharing
+//! an unfinished task's process group, and by running in a task's
+//! container. A task's process only counts while its start time still
+//! matches, so a recycled PID isn't blamed on an old task.
+
+use crate::process::{ProcessManager, TASK_ID_ENV};
+use crate::types::{ProcessRegistry, TaskInfo};
+use serde::Serialize;
+
+/// Why a process or container belongs to a task
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "by", rename_all = "snake_case")]
+pub enum How {
+    /// It is the task's process
+    Process,
+    /// It was started, directly or not, by the task's process
+    Descendant { of: i32 },
+    /// It carries the task's ID in its environment
+    EnvTag,
+    /// It is in the task's process group
+    ProcessGroup { pgid: i32 },
+    /// It is (or runs in) the task's container
+    Container { container_id: String },
+}
+
+impl std::fmt::Display for How {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            How::Process => write!(f, "it is the task's process"),
+            How::Descendant { of } => {
+                write!(f, "it descends from PID {of}, the task's process")
+            }
+            How::EnvTag => write!(f, "it was started with {TASK_ID_ENV} set to the task"),
+            How::ProcessGroup { pgid } => write!(f, "it is in the task's process group {pgid}"),
+            How::Container { container_id } => {
+                let short = &container_id[..container_id.len().min(12)];
+                write!(f, "it runs in the task's container {short}")
+            }
+        }
+    }
+}
+
+/// The task a process or container belongs to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Owner {
+    pub task_id: String,
+    #[serde(flatten)]
+    pub how: How,
+}
+
+/// Tasks most likely to own something first: unfinished ones, then the
+/// most recently started
+fn candidates(registry: &ProcessRegistry) -> Vec<(&String, &TaskInfo)> {
+    let mut tasks: Vec<_> = registry.tasks.iter().collect();
+    tasks.sort_by_key(|(task_id, task)| {
+        (
+            task.status.is_terminal(),
+            std::cmp::Reverse(task.started_at),
+            *task_id,
+        )
+    });
+    tasks
+}
+
+/// The task process `pid` belongs to
+pub fn owner_of_pid(registry: &ProcessRegistry, pid: i32) -> Option<Owner> {
+    let tasks = candidates(registry);
+    let owner = |task_id: &str, how| {
+        Some(Owner {
+            task_id: task_id.to_string(),
+            how,
+        })
+    };
+
+    let lineage = std::iter::once(pid).chain(ProcessManager::ancestors(pid));
+    for ancestor in lineage {
+        let task = tasks.iter().find(|(_, task)| {
+            task.native.as_ref().is_some_and(|native| {
+                native.pid == ancestor
+                    && ProcessManager::validate_process(ancestor, &native.start_time)
+            })
+        });
+        if let Some((task_id, _)) = task {
+            let how = if ancestor == pid {
+                How::Process
+            } else {
+                How::Descendant { of: ancestor }
+            };
+            return owner(task_id, how);
+        }
+    }
+
+    if let Some(tag) = ProcessManager::env_var(pid, TASK_ID_ENV) {
+        if registry.tasks.contains_key(&tag) {
+            return owner(&tag, How::EnvTag);
+        }
+    }
+
+    if let Some(pgid) = ProcessManager::process_group(pid) {
+        let task = tasks.iter().find(|(_, task)| {
+            !task.status.is_terminal() && task.native.as_ref().is_some_and(|n| n.pgid == pgid)
+        });
+        if let Some((task_id, _)) = task {
+            return owner(task_id, How::ProcessGroup { pgid });
+        }
+    }
+
+    let container_id = ProcessManager::container_id(pid)?;
+    owner_of_container(registry, &container_id)
+}
+
+/// The task whose container has ID (or ID prefix) or name `container`
+pub fn owner_of_container(registry: &ProcessRegistry, container: &str) -> Option<Owner> {
+    let container = container.trim_start_matches('/');
+    if container.is_empty() {
+        return None;
+    }
+    candidates(registry)
+        .into_iter()
+        .find_map(|(task_id, task)| {
+            let docker = task.docker.as_ref()?;
+            let id = docker.container_id.as_str();
+            // Either may be the short ID
+            let matches = (!id.is_empty()
+                && (id.starts_with(container) || container.starts_with(id)))
+                || docker.container_name.trim_start_matches('/') == container;
+            matches.then(|| Owner {
+                task_id: task_id.clone(),
+                how: How::Container {
+                    container_id: docker.container_id.clone(),
+                },
+            })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{DockerTask, NativeTask, TaskCommand, TaskOrigin};
+
+    fn native_task(pid: i32, start_time: String) -> TaskInfo {
+        let origin = TaskOrigin {
+            tool: "test".into(),
+            user: None,
+            host: None,
+            tmux: None,
+        };
+        let mut task = crate::tasks::new_task(
+            TaskCommand::shell("sleep 30"),
+            Default::default(),
+            Vec::new(),
+            None,
+            origin,
+        );
+        task.native = Some(NativeTask {
+            pid,
+            pgid: pid,
+            start_time,
+            env_tag: None,
+            job: None,
+        });
+        task
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_owner_of_pid() {
+        use std::os::unix::process::CommandExt;
+
+        let mut child = std::process::Command::new("sh")
+            .args(["-c", "sleep 30 & sleep 30 & wait"])
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        let pid = child.id() as i32;
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        let mut tree = ProcessManager::process_tree(pid);
+        while tree.len() < 3 && std::time::Instant::now() < deadline {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            tree = ProcessManager::process_tree(pid);
+        }
+        let start_time = ProcessManager::get_start_time(pid).unwrap();
+
+        let mut registry = ProcessRegistry::new();
+        registry.add_task("T1".into(), native_task(pid, start_time));
+        let by_process = owner_of_pid(&registry, pid);
+        let by_ancestry = owner_of_pid(&registry, tree[1]);
+        // A recycled PID isn't the task's
+        registry.add_task(
+            "T1".into(),
+            native_task(pid, "Thu Jan  1 00:00:00 1970".into()),
+        );
+        let by_group = owner_of_pid(&registry, tree[2]);
+        let stranger = owner_of_pid(&registry, std::process::id() as i32);
+        ProcessManager::kill_tree(pid, nix::sys::signal::Signal::SIGKILL, Default::default())
+            .unwrap();
+        child.wait().unwrap();
+
+        assert_eq!(by_process.unwrap().how, How::Process);
+        assert_eq!(by_ancestry.unwrap().how, How::Descendant { of: pid });
+        assert_eq!(by_group.unwrap().how, How::ProcessGroup { pgid: pid });
+        assert_eq!(stranger, None);
+    }
+
+    #[test]
+    fn test_owner_of_container() {
+        let id = "4f1c2d3e".repeat(8);
+        let mut task = native_task(0, String::new());
+        task.native = None;
+        task.docker = Some(DockerTask {
+            container_id: id.clone(),
+            container_name: "/watchdog-T2".into(),
+            resource_limits: Default::default(),
+            stop_timeout_secs: None,
+            image: None,
+            auto_remove: false,
+            keep_on_failure: false,
+            exit_code: None,
+        });
+        let mut registry = ProcessRegistry::new();
+        registry.add_task("T2".into(), task);
+
+        for query in [&id[..12], &id, "watchdog-T2"] {
+            let owner = owner_of_container(&registry, query).unwrap();
+            assert_eq!(owner.task_id, "T2");
+            assert_eq!(
+                owner.how,
+                How::Container {
+                    container_id: id.clone()
+                }
+            );
+        }
+        assert_eq!(owner_of_container(&registry, "deadbeef"), None);
+        assert_eq!(owner_of_container(&registry, ""), None);
+    }
+}
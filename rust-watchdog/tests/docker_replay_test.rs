@@ -0,0 +1,226 @@
+use task_watchdog::docker_recorder::DockerCall;
+
+const RECORDING: &str = "tests/fixtures/docker_calls.jsonl";
+
+/// A recording from `--record-docker-calls` replays offline: the
+/// "why did my container not start" trail is readable without Docker.
+#[test]
+fn test_replay_recorded_calls() {
+    let calls = DockerCall::load_all(RECORDING).unwrap();
+
+    let ops: Vec<&str> = calls.iter().map(|c| c.op.as_str()).collect();
+    assert_eq!(
+        ops,
+        [
+            "create_container",
+            "start_container",
+            "inspect_container",
+            "inspect_container",
+            "remove_container"
+        ]
+    );
+
+    // Container died from OOM while the watchdog was watching it
+    let inspect = &calls[3];
+    let state = &inspect.response.as_ref().unwrap()["state"];
+    assert_eq!(state["Running"], false);
+    assert_eq!(state["OOMKilled"], true);
+
+    // Failed calls keep the error instead of a response
+    let remove = &calls[4];
+    assert!(remove.response.is_none());
+    assert!(remove.error.as_ref().unwrap().contains("No such container"));
+}
+
+/// The same recording, answered by a fake Docker daemon, drives the real
+/// `DockerManager` and daemon check to the registry state it led to.
+#[cfg(all(unix, feature = "docker"))]
+#[tokio::test]
+async fn test_replay_through_the_watchdog() {
+    use task_watchdog::daemon::Watchdog;
+    use task_watchdog::docker::DockerManager;
+    use task_watchdog::queue::Fairness;
+    use task_watchdog::registry::RegistryManager;
+    use task_watchdog::types::{
+        DockerTask, ExecutionMode, ResourceLimits, TaskCommand, TaskInfo, TaskStatus,
+    };
+
+    let dir = tempfile::tempdir().unwrap();
+    let socket = dir.path().join("docker.sock");
+    let listener = tokio::net::UnixListener::bind(&socket).unwrap();
+    tokio::spawn(fake_docker::serve(
+        listener,
+        DockerCall::load_all(RECORDING).unwrap(),
+    ));
+    // Every DockerManager of this test binary talks to the fake
+    std::env::set_var("DOCKER_HOST", format!("unix://{}", socket.display()));
+
+    let docker = DockerManager::new().unwrap();
+    let command = TaskCommand::argv(["python", "job.py"]);
+    let limits = ResourceLimits::default();
+    let container_id = docker
+        .run_container("T001", &command, "/tmp/project", &limits, None, true)
+        .await
+        .unwrap();
+    assert_eq!(&container_id[..12], "4f1c2a9be0d3");
+
+    // Registered as `start` does for a container it has just started
+    let registry_path = dir.path().join("process_registry.json");
+    let task = TaskInfo {
+        mode: ExecutionMode::Docker,
+        status: TaskStatus::Starting,
+        docker: Some(DockerTask {
+            container_id,
+            container_name: "dev-task-T001".to_string(),
+            resource_limits: limits,
+            stop_timeout_secs: None,
+            image: None,
+            auto_remove: true,
+            keep_on_failure: false,
+            exit_code: None,
+        }),
+        ..TaskInfo::new(command)
+    };
+    RegistryManager::new(&registry_path)
+        .upsert_task("T001".to_string(), task)
+        .unwrap();
+
+    let mut watchdog = Watchdog::new(&registry_path, Fairness::default(), 0);
+    watchdog.check(false).await.unwrap();
+    let status = |watchdog: &Watchdog| watchdog.registry().get_task("T001").unwrap().status.clone();
+    assert_eq!(status(&watchdog), TaskStatus::Running);
+
+    // The next inspect finds it OOM-killed; auto-remove already took it
+    watchdog.check(false).await.unwrap();
+    let mut registry = RegistryManager::new(&registry_path);
+    registry.load().unwrap();
+    let task = registry.get_task("T001").unwrap();
+    assert_eq!(task.status, TaskStatus::Killed);
+    assert_eq!(
+        task.kill_reason.as_deref(),
+        Some("memory limit 512m exceeded (OOM-killed)")
+    );
+    assert_eq!(task.docker.as_ref().unwrap().exit_code, Some(137));
+}
+
+#[cfg(all(unix, feature = "docker"))]
+mod fake_docker {
+    use std::collections::{HashMap, VecDeque};
+    use task_watchdog::docker_recorder::DockerCall;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{UnixListener, UnixStream};
+
+    /// Answer each request with the next recorded call of its operation
+    /// (the last one again once they run out). Operations never recorded
+    /// get a 404, as for a container that is gone.
+    pub async fn serve(listener: UnixListener, calls: Vec<DockerCall>) {
+        let mut recorded: HashMap<String, VecDeque<DockerCall>> = HashMap::new();
+        for call in calls {
+            recorded.entry(call.op.clone()).or_default().push_back(call);
+        }
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let Some((method, path)) = read_request(&mut stream).await else {
+                continue;
+            };
+            let call = op(&method, &path).and_then(|op| {
+                let calls = recorded.get_mut(op)?;
+                if calls.len() > 1 {
+                    calls.pop_front()
+                } else {
+                    calls.front().cloned()
+                }
+            });
+            let (status, body) = match (call, op(&method, &path)) {
+                (Some(call), _) => answer(call),
+                (None, Some("ping")) => (200, "OK".to_string()),
+                (None, _) => (404, r#"{"message":"not recorded"}"#.to_string()),
+            };
+            let response = format!(
+                "HTTP/1.1 {status} Replayed\r\nContent-Type: application/json\r\n\
+                 Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            let _ = stream.write_all(response.as_bytes()).await;
+        }
+    }
+
+    /// The recorder's name for the Docker API call behind a request
+    fn op(method: &str, path: &str) -> Option<&'static str> {
+        let path = path.split('?').next().unwrap_or_default();
+        let segments: Vec<&str> = path
+            .split('/')
+            .filter(|s| !s.is_empty() && !s.starts_with("v1."))
+            .collect();
+        Some(match (method, segments.as_slice()) {
+            ("GET" | "HEAD", ["_ping"]) => "ping",
+            ("POST", ["containers", "create"]) => "create_container",
+            ("POST", ["containers", _, "start"]) => "start_container",
+            ("POST", ["containers", _, "kill"]) => "kill_container",
+            ("GET", ["containers", _, "json"]) => "inspect_container",
+            ("DELETE", ["containers", _]) => "remove_container",
+            _ => return None,
+        })
+    }
+
+    fn answer(call: DockerCall) -> (u16, String) {
+        if let Some(error) = call.error {
+            // "Docker responded with status code 404: No such container"
+            let (status, message) = error
+                .split_once("status code ")
+                .and_then(|(_, rest)| rest.split_once(": "))
+                .and_then(|(code, message)| Some((code.parse().ok()?, message)))
+                .unwrap_or((500, error.as_str()));
+            return (
+                status,
+                serde_json::json!({ "message": message }).to_string(),
+            );
+        }
+        match call.response {
+            None | Some(serde_json::Value::Null) => (204, String::new()),
+            // Only the state of an inspect is recorded
+            Some(response) if call.op == "inspect_container" => (
+                200,
+                serde_json::json!({ "Id": call.request["id"], "State": response["state"] })
+                    .to_string(),
+            ),
+            Some(response) => (200, response.to_string()),
+        }
+    }
+
+    /// Method and path of the next request, its body skipped
+    async fn read_request(stream: &mut UnixStream) -> Option<(String, String)> {
+        let mut buf = Vec::new();
+        let mut chunk = [0; 4096];
+        let head_end = loop {
+            let n = stream.read(&mut chunk).await.ok()?;
+            if n == 0 {
+                return None;
+            }
+            buf.extend_from_slice(&chunk[..n]);
+            if let Some(at) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                break at + 4;
+            }
+        };
+        let head = String::from_utf8_lossy(&buf[..head_end]).into_owned();
+        let length: usize = head
+            .lines()
+            .find_map(|line| {
+                let (name, value) = line.split_once(':')?;
+                name.eq_ignore_ascii_case("content-length")
+                    .then(|| value.trim().parse().ok())?
+            })
+            .unwrap_or(0);
+        while buf.len() < head_end + length {
+            let n = stream.read(&mut chunk).await.ok()?;
+            if n == 0 {
+                break;
+            }
+            buf.extend_from_slice(&chunk[..n]);
+        }
+        let mut request_line = head.lines().next()?.split_whitespace();
+        Some((
+            request_line.next()?.to_string(),
+            request_line.next()?.to_string(),
+        ))
+    }
+}
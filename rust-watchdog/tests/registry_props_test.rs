@@ -0,0 +1,193 @@
+//! Property-based tests for the registry, the crate's core data structure.
+//!
+//! Random sequences of register/kill/complete/fail/cleanup operations are
+//! applied both to a `RegistryManager` and to a trivial in-memory model, and
+//! the invariants below must hold after every step.
+
+use proptest::prelude::*;
+use std::collections::HashMap;
+use std::path::Path;
+use task_watchdog::registry::RegistryManager;
+use task_watchdog::types::{NativeTask, TaskCommand, TaskInfo, TaskStatus};
+
+const IDS: [&str; 4] = ["T001", "T002", "T003", "T004"];
+
+#[derive(Debug, Clone)]
+enum Op {
+    Register(usize),
+    Kill(usize),
+    Complete(usize),
+    Fail(usize),
+    Cleanup,
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    let id = 0..IDS.len();
+    prop_oneof![
+        id.clone().prop_map(Op::Register),
+        id.clone().prop_map(Op::Kill),
+        id.clone().prop_map(Op::Complete),
+        id.prop_map(Op::Fail),
+        Just(Op::Cleanup),
+    ]
+}
+
+fn new_task(n: usize) -> TaskInfo {
+    TaskInfo {
+        native: Some(NativeTask {
+            pid: 40000 + n as i32,
+            pgid: 40000 + n as i32,
+            start_time: format!("t{n}"),
+            env_tag: None,
+            job: None,
+        }),
+        ..TaskInfo::new(TaskCommand::shell(format!("job {n}")))
+    }
+}
+
+/// Apply `op` to the registry and to the model of expected statuses
+fn apply(mgr: &mut RegistryManager, model: &mut HashMap<String, TaskStatus>, op: &Op) {
+    match op {
+        Op::Register(i) => {
+            let result = mgr.upsert_task(IDS[*i].to_string(), new_task(*i));
+            // Finished tasks are never revived; everything else (re-)registers
+            match model.get(IDS[*i]) {
+                Some(status) if status.is_terminal() => assert!(result.is_err()),
+                _ => {
+                    result.unwrap();
+                    model.insert(IDS[*i].to_string(), TaskStatus::Running);
+                }
+            }
+        }
+        // `kill` records the task as killed, with a reason
+        Op::Kill(i) => {
+            mgr.mark_killed(IDS[*i], "killed by user").unwrap();
+            if let Some(status @ TaskStatus::Running) = model.get_mut(IDS[*i]) {
+                *status = TaskStatus::Killed;
+            }
+        }
+        Op::Complete(i) => {
+            mgr.mark_complete(IDS[*i]).unwrap();
+            // Finished tasks keep their first terminal status
+            if let Some(status @ TaskStatus::Running) = model.get_mut(IDS[*i]) {
+                *status = TaskStatus::Completed;
+            }
+        }
+        Op::Fail(i) => {
+            mgr.mark_failed(IDS[*i]).unwrap();
+            if let Some(status @ TaskStatus::Running) = model.get_mut(IDS[*i]) {
+                *status = TaskStatus::Failed;
+            }
+        }
+        Op::Cleanup => {
+            mgr.cleanup_old_tasks(0, None).unwrap();
+            model.retain(|_, s| !s.is_terminal());
+        }
+    }
+}
+
+fn check_invariants(mgr: &RegistryManager) {
+    for (id, task) in &mgr.registry().tasks {
+        // No task is both Running and finished
+        assert!(
+            !(task.status == TaskStatus::Running && task.completed_at.is_some()),
+            "{id} is running but has completed_at"
+        );
+        // Terminal tasks always record when they finished
+        if task.status.is_terminal() {
+            assert!(
+                task.completed_at.is_some(),
+                "{id} finished without completed_at"
+            );
+        }
+        if let Some(done) = task.completed_at {
+            assert!(done >= task.started_at, "{id} finished before it started");
+        }
+    }
+
+    let stats = mgr.stats();
+    // Counts kept up to date as tasks change agree with a recount
+    assert_eq!(stats, mgr.registry().stats());
+    assert_eq!(stats.total, mgr.registry().tasks.len());
+    assert!(stats.running + stats.completed + stats.failed + stats.killed <= stats.total);
+}
+
+/// save→load must be the identity on the persisted JSON
+fn assert_round_trip(mgr: &RegistryManager, path: &Path) {
+    mgr.save().unwrap();
+    let mut reloaded = RegistryManager::new(path);
+    reloaded.load().unwrap();
+    assert_eq!(
+        serde_json::to_value(mgr.registry()).unwrap(),
+        serde_json::to_value(reloaded.registry()).unwrap()
+    );
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn prop_sequential_ops_match_model(ops in prop::collection::vec(op(), 1..40)) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("process_registry.json");
+        let mut mgr = RegistryManager::new(&path);
+        mgr.load().unwrap();
+        let mut model = HashMap::new();
+
+        for op in &ops {
+            apply(&mut mgr, &mut model, op);
+            check_invariants(&mgr);
+
+            let actual: HashMap<String, TaskStatus> = mgr
+                .registry()
+                .tasks
+                .iter()
+                .map(|(id, t)| (id.clone(), t.status.clone()))
+                .collect();
+            prop_assert_eq!(&actual, &model);
+        }
+
+        assert_round_trip(&mgr, &path);
+    }
+
+    #[test]
+    fn prop_concurrent_writers_keep_invariants(
+        per_thread in prop::collection::vec(prop::collection::vec(op(), 1..10), 2..5)
+    ) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("process_registry.json");
+        RegistryManager::new(&path).load().unwrap();
+
+        // Each thread owns its own manager, as separate CLI processes would
+        let handles: Vec<_> = per_thread
+            .into_iter()
+            .map(|ops| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    let mut mgr = RegistryManager::new(&path);
+                    mgr.load().unwrap();
+                    let mut model = HashMap::new();
+                    for op in &ops {
+                        match op {
+                            // Another writer may have finished this ID, so a
+                            // rejected re-registration is expected here
+                            Op::Register(i) => {
+                                let _ = mgr.upsert_task(IDS[*i].to_string(), new_task(*i));
+                            }
+                            _ => apply(&mut mgr, &mut model, op),
+                        }
+                        check_invariants(&mgr);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let mut merged = RegistryManager::new(&path);
+        merged.load().unwrap();
+        check_invariants(&merged);
+        assert_round_trip(&merged, &path);
+    }
+}
@@ -1,14 +1,17 @@
 use std::env;
+use std::time::Duration;
 use task_watchdog::docker::DockerManager;
+use task_watchdog::types::{ResourceLimits, TaskCommand};
 
 #[tokio::test]
 async fn test_command_injection_prevention() {
     // This test verifies that command injection is prevented
     // by passing commands directly to Docker instead of through shell
 
+    // `new()` connects lazily, so also ping the daemon before relying on it
     let manager = match DockerManager::new() {
-        Some(m) => m,
-        None => {
+        Some(m) if m.ping().await => m,
+        _ => {
             println!("Docker not available, skipping test");
             return;
         }
@@ -34,11 +37,14 @@ async fn test_command_injection_prevention() {
     let result = manager
         .run_container(
             "injection-test",
-            malicious_command,
+            &TaskCommand::argv(malicious_command),
             &work_dir,
-            "512m",
-            "1.0",
+            &ResourceLimits {
+                memory: "512m".to_string(),
+                cpu: "1.0".to_string(),
+            },
             Some("alpine:latest"),
+            false,
         )
         .await;
 
@@ -56,7 +62,10 @@ async fn test_command_injection_prevention() {
 
     if let Ok(container_id) = result {
         // Clean up
-        let _ = manager.stop_container(&container_id).await;
+        let _ = manager
+            .stop_container(&container_id, Duration::from_secs(1))
+            .await;
+        let _ = manager.remove_container(&container_id).await;
     }
 }
 
@@ -64,9 +73,10 @@ async fn test_command_injection_prevention() {
 async fn test_safe_command_execution() {
     // Verify legitimate commands still work correctly
 
+    // `new()` connects lazily, so also ping the daemon before relying on it
     let manager = match DockerManager::new() {
-        Some(m) => m,
-        None => {
+        Some(m) if m.ping().await => m,
+        _ => {
             println!("Docker not available, skipping test");
             return;
         }
@@ -84,11 +94,14 @@ async fn test_safe_command_execution() {
     let result = manager
         .run_container(
             "safe-test",
-            safe_command,
+            &TaskCommand::argv(safe_command),
             &work_dir,
-            "256m",
-            "0.5",
+            &ResourceLimits {
+                memory: "256m".to_string(),
+                cpu: "0.5".to_string(),
+            },
             Some("alpine:latest"),
+            false,
         )
         .await;
 
@@ -104,6 +117,9 @@ async fn test_safe_command_execution() {
 
     if let Ok(container_id) = result {
         // Clean up
-        let _ = manager.stop_container(&container_id).await;
+        let _ = manager
+            .stop_container(&container_id, Duration::from_secs(1))
+            .await;
+        let _ = manager.remove_container(&container_id).await;
     }
 }